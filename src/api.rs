@@ -139,6 +139,7 @@ impl PlaybookRunner {
         temp_parser.sudo = self.config.sudo.clone();
         temp_parser.extra_vars = self.config.extra_vars.clone();
         temp_parser.verbosity = self.config.verbosity;
+        temp_parser.show_diff = self.config.show_diff;
 
         // Create playbook context
         let context = Arc::new(RwLock::new(PlaybookContext::new(&temp_parser)));
@@ -290,6 +291,11 @@ impl PlaybookRunnerBuilder {
         self
     }
 
+    pub fn show_diff(mut self) -> Self {
+        self.config = self.config.show_diff(true);
+        self
+    }
+
     pub fn async_mode(mut self) -> Self {
         self.config = self.config.async_mode(true);
         self
@@ -23,3 +23,4 @@ pub mod parser;
 pub mod playbooks;
 pub mod secrets_diagnostic;
 pub mod show;
+pub mod vault;
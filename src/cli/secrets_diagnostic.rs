@@ -245,7 +245,8 @@ mod tests {
         );
         let inventory = group_vars_inventory("all", &["public_var"]);
         let extra = serde_yaml::Value::Mapping(serde_yaml::Mapping::new());
-        let missing = missing_secret_variables(&[fx.playbook.clone()], &[], &inventory, &extra);
+        let missing =
+            missing_secret_variables(std::slice::from_ref(&fx.playbook), &[], &inventory, &extra);
         let expected: BTreeSet<String> = ["secret_only"].iter().map(|s| s.to_string()).collect();
         assert_eq!(missing, expected);
     }
@@ -257,7 +258,8 @@ mod tests {
         );
         let inventory = group_vars_inventory("all", &[]);
         let extra = mapping(&["from_e"]);
-        let missing = missing_secret_variables(&[fx.playbook.clone()], &[], &inventory, &extra);
+        let missing =
+            missing_secret_variables(std::slice::from_ref(&fx.playbook), &[], &inventory, &extra);
         assert!(missing.is_empty(), "got: {missing:?}");
     }
 
@@ -268,7 +270,8 @@ mod tests {
         );
         let inventory = group_vars_inventory("all", &[]);
         let extra = serde_yaml::Value::Mapping(serde_yaml::Mapping::new());
-        let missing = missing_secret_variables(&[fx.playbook.clone()], &[], &inventory, &extra);
+        let missing =
+            missing_secret_variables(std::slice::from_ref(&fx.playbook), &[], &inventory, &extra);
         assert!(missing.is_empty());
     }
 
@@ -277,7 +280,8 @@ mod tests {
         let fx = playbook("this: : is not\nvalid playbook: yaml:\n  - [");
         let inventory = group_vars_inventory("all", &[]);
         let extra = serde_yaml::Value::Mapping(serde_yaml::Mapping::new());
-        let missing = missing_secret_variables(&[fx.playbook.clone()], &[], &inventory, &extra);
+        let missing =
+            missing_secret_variables(std::slice::from_ref(&fx.playbook), &[], &inventory, &extra);
         assert!(missing.is_empty());
     }
 
@@ -299,7 +303,8 @@ mod tests {
             "- name: site\n  groups: [gA]\n  tasks:\n    - !echo\n      msg: \"{{ secret_in_b }}\"\n",
         );
         let extra = serde_yaml::Value::Mapping(serde_yaml::Mapping::new());
-        let missing = missing_secret_variables(&[fx.playbook.clone()], &[], &inventory, &extra);
+        let missing =
+            missing_secret_variables(std::slice::from_ref(&fx.playbook), &[], &inventory, &extra);
         let expected: BTreeSet<String> = ["secret_in_b"].iter().map(|s| s.to_string()).collect();
         assert_eq!(missing, expected);
     }
@@ -316,7 +321,8 @@ mod tests {
             "- name: site\n  groups: [ghost]\n  tasks:\n    - !echo\n      msg: \"{{ nowhere }}\"\n",
         );
         let extra = serde_yaml::Value::Mapping(serde_yaml::Mapping::new());
-        let missing = missing_secret_variables(&[fx.playbook.clone()], &[], &inventory, &extra);
+        let missing =
+            missing_secret_variables(std::slice::from_ref(&fx.playbook), &[], &inventory, &extra);
         assert!(missing.is_empty(), "got: {missing:?}");
     }
 }
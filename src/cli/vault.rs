@@ -0,0 +1,278 @@
+// Jetporch
+// Copyright (C) 2023 - Michael DeHaan <michael@michaeldehaan.net> + contributors
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// long with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+// `jetpack vault` — manage encrypted secrets files so they can be committed next
+// to the inventory they belong to. See util/vault.rs for the format.
+//
+//    jetp vault encrypt inventory/group_vars/prod
+//    jetp vault decrypt inventory/group_vars/prod
+//    jetp vault edit inventory/group_vars/prod
+//    jetp vault rekey inventory/group_vars/prod --new-vault-password-file ~/.new_pass
+//    echo -n hunter2 | jetp vault encrypt-string db_password
+
+use crate::cli::parser::CliParser;
+use crate::util::io::read_local_file;
+use crate::util::terminal::banner;
+use crate::util::vault;
+use std::io::{IsTerminal, Read, Write};
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+const DEFAULT_EDITOR: &str = "vi";
+
+pub fn vault_command(parser: &CliParser) -> i32 {
+    match vault_inner(parser) {
+        Ok(Some(message)) => {
+            banner(&message);
+            0
+        }
+        Ok(None) => 0,
+        Err(e) => {
+            eprintln!("{e}");
+            1
+        }
+    }
+}
+
+fn vault_inner(parser: &CliParser) -> Result<Option<String>, String> {
+    let action = parser.vault_action.as_deref().unwrap_or_default();
+    let password_file = parser.vault_password_file.as_deref();
+    match action {
+        "encrypt-string" => {
+            let name = single_argument(parser, "encrypt-string", "a variable name")?;
+            let password = vault::resolve_password(password_file, "vault password")?;
+            let value = read_secret_value()?;
+            let envelope = vault::encrypt(value.as_bytes(), &password)?;
+            print!("{}", vault::format_tagged_value(&name, &envelope));
+            Ok(None)
+        }
+        "edit" => {
+            let path = PathBuf::from(single_argument(parser, "edit", "a file")?);
+            let password = vault::resolve_password(password_file, "vault password")?;
+            match edit(&path, &password)? {
+                true => Ok(Some(format!("encrypted {}", path.display()))),
+                false => Ok(Some(format!("{} unchanged", path.display()))),
+            }
+        }
+        "encrypt" | "decrypt" | "rekey" => {
+            let paths = file_arguments(parser, action)?;
+            let password = vault::resolve_password(password_file, "vault password")?;
+            let new_password = match action {
+                "rekey" => Some(match parser.new_vault_password_file.as_deref() {
+                    Some(path) => vault::read_password_file(path)?,
+                    None if std::io::stdin().is_terminal() => {
+                        vault::prompt_password("new vault password")?
+                    }
+                    None => {
+                        return Err(String::from(
+                            "vault rekey requires --new-vault-password-file when not run from a terminal",
+                        ));
+                    }
+                }),
+                _ => None,
+            };
+            for path in paths.iter() {
+                let content = read_local_file(path)?;
+                let replacement = match new_password.as_deref() {
+                    Some(new_password) => {
+                        let (text, count) = vault::rekey_text(&content, &password, new_password)
+                            .map_err(|e| format!("{}: {}", path.display(), e))?;
+                        if count == 0 {
+                            return Err(format!("{}: nothing vault encrypted", path.display()));
+                        }
+                        text
+                    }
+                    None => transform(action, &content, &password)
+                        .map_err(|e| format!("{}: {}", path.display(), e))?,
+                };
+                write_in_place(path, replacement.as_bytes())?;
+            }
+            let verb = match action {
+                "encrypt" => "encrypted",
+                "decrypt" => "decrypted",
+                _ => "rekeyed",
+            };
+            Ok(Some(format!("{} {} file(s)", verb, paths.len())))
+        }
+        _ => Err(format!("unknown vault action: {}", action)),
+    }
+}
+
+fn transform(action: &str, content: &str, password: &str) -> Result<String, String> {
+    match (action, vault::is_encrypted(content)) {
+        ("encrypt", false) => vault::encrypt(content.as_bytes(), password),
+        ("encrypt", true) => Err(String::from("already vault encrypted")),
+        (_, true) => String::from_utf8(vault::decrypt(content, password)?)
+            .map_err(|_| String::from("vault content is not valid UTF-8")),
+        (_, false) => Err(String::from("not vault encrypted")),
+    }
+}
+
+// decrypt into a private scratch file next to the original, open $EDITOR on it, and
+// re-encrypt the result. Returns false when the content did not change.
+
+fn edit(path: &Path, password: &str) -> Result<bool, String> {
+    let original = match path.exists() {
+        true => {
+            let content = read_local_file(path)?;
+            transform("decrypt", &content, password)
+                .map_err(|e| format!("{}: {}", path.display(), e))?
+        }
+        false => String::new(),
+    };
+
+    let scratch = scratch_path(path);
+    let result = (|| {
+        let mut file = std::fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .mode(0o600)
+            .open(&scratch)
+            .map_err(|e| format!("unable to create {}: {}", scratch.display(), e))?;
+        file.write_all(original.as_bytes())
+            .map_err(|e| format!("unable to write {}: {}", scratch.display(), e))?;
+        drop(file);
+
+        let editor = std::env::var("EDITOR").unwrap_or_else(|_| DEFAULT_EDITOR.to_string());
+        // $EDITOR may carry arguments ("code --wait"), so hand it to the shell
+        let status = Command::new("sh")
+            .arg("-c")
+            .arg(format!("{} \"$1\"", editor))
+            .arg("sh")
+            .arg(&scratch)
+            .status()
+            .map_err(|e| format!("unable to run editor {}: {}", editor, e))?;
+        if !status.success() {
+            return Err(format!("editor {} exited with {}", editor, status));
+        }
+        read_local_file(&scratch)
+    })();
+    let _ = std::fs::remove_file(&scratch);
+
+    let edited = result?;
+    if edited == original && path.exists() {
+        return Ok(false);
+    }
+    write_in_place(
+        path,
+        vault::encrypt(edited.as_bytes(), password)?.as_bytes(),
+    )?;
+    Ok(true)
+}
+
+fn scratch_path(path: &Path) -> PathBuf {
+    let name = path
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_default();
+    path.with_file_name(format!(".{}.{}.jetvault", name, std::process::id()))
+}
+
+// replace a file by writing a sibling and renaming over it, so an interrupted
+// write never leaves a half-encrypted secrets file behind
+
+fn write_in_place(path: &Path, data: &[u8]) -> Result<(), String> {
+    let tmp = scratch_path(path).with_extension("tmp");
+    let permissions = std::fs::metadata(path).ok().map(|m| m.permissions());
+    std::fs::write(&tmp, data).map_err(|e| format!("unable to write {}: {}", tmp.display(), e))?;
+    if let Some(permissions) = permissions {
+        let _ = std::fs::set_permissions(&tmp, permissions);
+    }
+    std::fs::rename(&tmp, path).map_err(|e| {
+        let _ = std::fs::remove_file(&tmp);
+        format!("unable to replace {}: {}", path.display(), e)
+    })
+}
+
+fn read_secret_value() -> Result<String, String> {
+    let mut value = String::new();
+    if std::io::stdin().is_terminal() {
+        value = vault::read_hidden_line("enter value to encrypt: ")?;
+    } else {
+        std::io::stdin()
+            .read_to_string(&mut value)
+            .map_err(|e| format!("failure reading input: {}", e))?;
+    }
+    // a single trailing newline comes from the terminal or `echo`, not the secret
+    if value.ends_with('\n') {
+        value.pop();
+        if value.ends_with('\r') {
+            value.pop();
+        }
+    }
+    Ok(value)
+}
+
+fn single_argument(parser: &CliParser, action: &str, what: &str) -> Result<String, String> {
+    match parser.vault_args.as_slice() {
+        [one] => Ok(one.clone()),
+        _ => Err(format!(
+            "vault {} takes exactly one argument: {}",
+            action, what
+        )),
+    }
+}
+
+fn file_arguments(parser: &CliParser, action: &str) -> Result<Vec<PathBuf>, String> {
+    if parser.vault_args.is_empty() {
+        return Err(format!("vault {} requires one or more files", action));
+    }
+    let paths: Vec<PathBuf> = parser.vault_args.iter().map(PathBuf::from).collect();
+    for path in paths.iter() {
+        if !path.is_file() {
+            return Err(format!("{} is not a file", path.display()));
+        }
+    }
+    Ok(paths)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn encrypt_and_decrypt_transform_refuse_the_wrong_direction() {
+        let sealed = transform("encrypt", "a: 1\n", "pw").unwrap();
+        assert!(vault::is_encrypted(&sealed));
+        assert_eq!(
+            transform("encrypt", &sealed, "pw").unwrap_err(),
+            "already vault encrypted"
+        );
+        assert_eq!(transform("decrypt", &sealed, "pw").unwrap(), "a: 1\n");
+        assert_eq!(
+            transform("decrypt", "a: 1\n", "pw").unwrap_err(),
+            "not vault encrypted"
+        );
+    }
+
+    #[test]
+    fn write_in_place_keeps_permissions_and_leaves_no_scratch_files() {
+        use std::os::unix::fs::PermissionsExt;
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("prod");
+        std::fs::write(&path, "a: 1\n").unwrap();
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o640)).unwrap();
+
+        write_in_place(&path, b"b: 2\n").unwrap();
+
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "b: 2\n");
+        let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o640);
+        assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 1);
+    }
+}
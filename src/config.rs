@@ -39,6 +39,7 @@ pub struct JetpackConfig {
     pub login_password: Option<String>,
     pub private_key_file: Option<String>,
     pub check_mode: bool,
+    pub show_diff: bool,
     pub async_mode: bool,
     pub connection_mode: ConnectionMode,
     pub playbook_contents: Vec<(String, String)>,
@@ -79,6 +80,7 @@ impl Default for JetpackConfig {
             login_password: None,
             private_key_file: None,
             check_mode: false,
+            show_diff: false,
             async_mode: false,
             connection_mode: ConnectionMode::Ssh,
             playbook_contents: Vec::new(),
@@ -171,6 +173,11 @@ impl JetpackConfig {
         self
     }
 
+    pub fn show_diff(mut self, show_diff: bool) -> Self {
+        self.show_diff = show_diff;
+        self
+    }
+
     pub fn async_mode(mut self, async_mode: bool) -> Self {
         self.async_mode = async_mode;
         self
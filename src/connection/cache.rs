@@ -58,6 +58,11 @@ impl ConnectionCache {
         Arc::clone(self.connections.get(&host2.name.clone()).unwrap())
     }
 
+    pub fn remove_connection(&mut self, host: &Arc<RwLock<Host>>) {
+        let host2 = host.read().expect("host read");
+        self.connections.remove(&host2.name);
+    }
+
     pub fn clear(&mut self) {
         self.connections.clear();
     }
@@ -27,13 +27,13 @@
 //! runtime, rather than minting a throwaway runtime per call.
 
 use crate::dns::DnsConfig;
+use crate::util::secrets::SecretSource;
 use serde::Deserialize;
-use std::env;
-use std::fs;
 
 /// Native Gravity API configuration. The bearer token is resolved, in priority
-/// order, from `api_token`, then `api_token_file`, then `api_token_env` —
-/// prefer the file/env forms so the secret never lives in inventory.
+/// order, from `api_token`, then `api_token_file`, then `api_token_env`, then
+/// `api_token_ref` (a secret reference such as `pass:infra/gravity`) — prefer
+/// the last three so the secret never lives in inventory.
 #[derive(Debug, Clone, Deserialize)]
 #[serde(deny_unknown_fields)]
 pub struct GravityConfig {
@@ -41,6 +41,7 @@ pub struct GravityConfig {
     pub api_token: Option<String>,
     pub api_token_file: Option<String>,
     pub api_token_env: Option<String>,
+    pub api_token_ref: Option<String>,
     /// TTL (seconds) applied to records this client creates. Defaults to
     /// [`DEFAULT_TTL`] when unset.
     pub default_ttl: Option<u32>,
@@ -92,21 +93,17 @@ fn gravity_config(config: &DnsConfig) -> Result<&GravityConfig, String> {
         .ok_or_else(|| "Gravity config not present".to_string())
 }
 
-/// Resolve the Gravity bearer token. Priority: inline → file → env var → error.
+/// Resolve the Gravity bearer token. Priority: inline → file → env var →
+/// secret reference → error.
 pub(crate) fn resolve_token(gravity: &GravityConfig) -> Result<String, String> {
-    if let Some(token) = gravity.api_token.clone() {
-        return Ok(token);
+    SecretSource {
+        value: gravity.api_token.as_deref(),
+        file: gravity.api_token_file.as_deref(),
+        env: gravity.api_token_env.as_deref(),
+        reference: gravity.api_token_ref.as_deref(),
     }
-    if let Some(path) = gravity.api_token_file.as_ref() {
-        return fs::read_to_string(path)
-            .map(|s| s.trim().to_string())
-            .map_err(|e| format!("Failed to read Gravity API token file '{}': {}", path, e));
-    }
-    if let Some(env_name) = gravity.api_token_env.as_ref() {
-        return env::var(env_name)
-            .map_err(|_| format!("Gravity API token env var '{}' is not set", env_name));
-    }
-    Err("Gravity API token not configured".to_string())
+    .resolve("Gravity API token")?
+    .ok_or_else(|| "Gravity API token not configured".to_string())
 }
 
 fn token_for(config: &DnsConfig) -> Result<String, String> {
@@ -372,6 +369,7 @@ mod tests {
             api_token: token,
             api_token_file: None,
             api_token_env: None,
+            api_token_ref: None,
             default_ttl: None,
         }
     }
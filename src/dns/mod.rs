@@ -254,10 +254,10 @@ pub fn remove_host_record(config: &DnsConfig, inventory_name: &str) -> Result<bo
     if changed && config.auto_sync {
         if config.has_native_gravity() {
             gravity::delete_record(config, &zone, &hostname, "A")?;
-            if let (Some(ip), Some(reverse_zone)) = (previous_ip, config.reverse_zone.as_ref()) {
-                if let Some(host_part) = ip.rsplit('.').next() {
-                    gravity::delete_record(config, reverse_zone, host_part, "PTR")?;
-                }
+            if let (Some(ip), Some(reverse_zone)) = (previous_ip, config.reverse_zone.as_ref())
+                && let Some(host_part) = ip.rsplit('.').next()
+            {
+                gravity::delete_record(config, reverse_zone, host_part, "PTR")?;
             }
         } else {
             sync(config, &zone)?;
@@ -38,6 +38,7 @@ const CATEGORY_ORDER: &[&str] = &[
     "packages",
     "proxmox",
     "services",
+    "system",
 ];
 
 /// Flags that take no value (the parser's standalone-flag set). Everything else
@@ -51,6 +52,7 @@ const STANDALONE_FLAGS: &[&str] = &[
     "--ask-login-password",
     "--async",
     "--no-browser",
+    "--diff",
 ];
 
 // =================================================================================================
@@ -105,7 +105,7 @@ impl TaskHandle {
         }
     }
 
-    pub fn debug(&self, _request: &Arc<TaskRequest>, message: &String) {
+    pub fn debug(&self, _request: &Arc<TaskRequest>, message: &str) {
         self.run_state
             .visitor
             .read()
@@ -24,11 +24,16 @@ use crate::handle::template::Template;
 use crate::inventory::hosts::{Host, HostOSType};
 use crate::playbooks::traversal::RunState;
 use crate::tasks::FileAttributesEvaluated;
+use crate::tasks::checksum::Algorithm;
 use crate::tasks::cmd_library::screen_general_input_loose;
 use crate::tasks::fields::Field;
-use crate::tasks::files::Recurse;
+use crate::tasks::files::{FileFlags, LinkType, Recurse};
 use crate::tasks::request::{TaskRequest, TaskRequestType};
 use crate::tasks::response::TaskResponse;
+use crate::util::diff::{MAX_DIFF_BYTES, too_large};
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use std::collections::HashMap;
 use std::path::Path;
 use std::path::PathBuf;
 use std::sync::{Arc, Mutex, RwLock};
@@ -321,9 +326,65 @@ impl Remote {
         Ok(())
     }
 
-    // writes a string (for example, from a template) to a remote file location
+    // neither an immutable nor an append-only file can be replaced, so when the task
+    // manages chattr flags they come off the destination before a transfer and go
+    // back on (or change) once the new file is in place. Flags cannot be put on the
+    // transferred file any earlier, as it could then not be moved.
+
+    fn transfer_with_flags<F>(
+        &self,
+        request: &Arc<TaskRequest>,
+        path: &str,
+        attributes: &Option<FileAttributesEvaluated>,
+        transfer: F,
+    ) -> Result<(), Arc<TaskResponse>>
+    where
+        F: FnOnce() -> Result<(), Arc<TaskResponse>>,
+    {
+        let attributes = match attributes.as_ref().filter(|a| a.has_flags()) {
+            Some(attributes) => attributes,
+            None => return transfer(),
+        };
+        let previous = self.unlock_file(request, path)?;
+        let result = transfer();
+        // a failed transfer left the file as it was, so it gets its own flags back
+        let flags = match result {
+            Ok(_) => {
+                FileAttributesEvaluated::merge_flags(Some(attributes), previous.unwrap_or_default())
+            }
+            Err(_) => previous.unwrap_or_default(),
+        };
+        if flags != FileFlags::default() {
+            let restored = self.set_file_flags(request, path, flags, Recurse::No);
+            // the transfer error, if any, is the one worth reporting
+            result?;
+            restored?;
+            return Ok(());
+        }
+        result
+    }
+
+    // writes a string (for example, from a template) to a remote file location.
+    // before_complete runs on the file before it is moved into place, the chattr
+    // flags of `attributes` are applied after that (see transfer_with_flags).
 
     pub fn write_data<G>(
+        &self,
+        request: &Arc<TaskRequest>,
+        data: &str,
+        path: &str,
+        attributes: &Option<FileAttributesEvaluated>,
+        before_complete: G,
+    ) -> Result<(), Arc<TaskResponse>>
+    where
+        G: FnMut(&String) -> Result<(), Arc<TaskResponse>>,
+    {
+        self.transfer_with_flags(request, path, attributes, || {
+            self.write_data_unflagged(request, data, path, before_complete)
+        })
+    }
+
+    fn write_data_unflagged<G>(
         &self,
         request: &Arc<TaskRequest>,
         data: &str,
@@ -353,6 +414,34 @@ impl Remote {
         Ok(())
     }
 
+    // rewrites an existing file, keeping its current owner, group and mode unless
+    // the task's attributes say otherwise. Used by modules that edit files in place.
+
+    pub fn write_data_in_place(
+        &self,
+        request: &Arc<TaskRequest>,
+        data: &str,
+        path: &str,
+        attributes_in: &Option<FileAttributesEvaluated>,
+    ) -> Result<(), Arc<TaskResponse>> {
+        let mode = self.get_mode(request, path)?;
+        let ownership = self.get_ownership(request, path)?;
+        let given = attributes_in.as_ref();
+        let attributes = Some(FileAttributesEvaluated {
+            owner: given
+                .and_then(|a| a.owner.clone())
+                .or_else(|| ownership.as_ref().map(|(owner, _)| owner.clone())),
+            group: given
+                .and_then(|a| a.group.clone())
+                .or_else(|| ownership.as_ref().map(|(_, group)| group.clone())),
+            mode: given.and_then(|a| a.mode.clone()).or(mode),
+            ..given.cloned().unwrap_or_default()
+        });
+        self.write_data(request, data, path, &attributes, |f| {
+            self.process_transferred_file_attributes(request, f, &attributes)
+        })
+    }
+
     // fetches a file from the remote and returns its bytes.
     // also stores the content in run_state.fetched_files keyed by remote_path.
 
@@ -374,9 +463,103 @@ impl Remote {
         Ok(content)
     }
 
-    // copies a file to a remote location
+    // reads a remote file as text, returning None when it does not exist. Unlike
+    // fetch_file this runs as a command, so it honors sudo.
+
+    pub fn read_file(
+        &self,
+        request: &Arc<TaskRequest>,
+        path: &str,
+    ) -> Result<Option<String>, Arc<TaskResponse>> {
+        match self.read_file_bytes(request, path)? {
+            None => Ok(None),
+            Some(bytes) => String::from_utf8(bytes).map(Some).map_err(|_| {
+                self.response
+                    .is_failed(request, &format!("{} is not a text file", path))
+            }),
+        }
+    }
+
+    pub fn read_file_bytes(
+        &self,
+        request: &Arc<TaskRequest>,
+        path: &str,
+    ) -> Result<Option<Vec<u8>>, Arc<TaskResponse>> {
+        if !self.file_exists(request, path)? {
+            return Ok(None);
+        }
+        let get_cmd_result =
+            crate::tasks::cmd_library::get_file_contents_command(self.get_os_type(), path);
+        let cmd = self.unwrap_string_result(request, &get_cmd_result)?;
+        let result = self.run(request, &cmd, CheckRc::Checked)?;
+        let (_, out) = cmd_info(&result);
+        let encoded: String = out.split_whitespace().collect();
+        BASE64.decode(encoded.as_bytes()).map(Some).map_err(|e| {
+            self.response
+                .is_failed(request, &format!("unable to read {}: {}", path, e))
+        })
+    }
+
+    // the size of a remote file in bytes, None if it cannot be read
+
+    pub fn get_file_size(
+        &self,
+        request: &Arc<TaskRequest>,
+        path: &str,
+    ) -> Result<Option<usize>, Arc<TaskResponse>> {
+        let get_cmd_result =
+            crate::tasks::cmd_library::get_file_size_command(self.get_os_type(), path);
+        let cmd = self.unwrap_string_result(request, &get_cmd_result)?;
+        let result = self.run(request, &cmd, CheckRc::Unchecked)?;
+        let (rc, out) = cmd_info(&result);
+        match rc {
+            0 => Ok(out
+                .split_whitespace()
+                .next()
+                .and_then(|x| x.parse::<usize>().ok())),
+            _ => Ok(None),
+        }
+    }
+
+    // under --diff, a unified diff from the current remote content to `data`, fetched
+    // during the query leg. Oversized remote files are not downloaded just to be summarised.
+
+    pub fn diff_file(
+        &self,
+        request: &Arc<TaskRequest>,
+        path: &str,
+        data: &[u8],
+    ) -> Result<Option<String>, Arc<TaskResponse>> {
+        if !self.response.wants_diff() {
+            return Ok(None);
+        }
+        let before = match self.get_file_size(request, path)? {
+            None => None,
+            Some(size) if size > MAX_DIFF_BYTES => return Ok(Some(too_large(path))),
+            Some(_) => self.read_file_bytes(request, path)?,
+        };
+        Ok(crate::util::diff::unified(path, before.as_deref(), data))
+    }
+
+    // copies a file to a remote location, see write_data
 
     pub fn copy_file<G>(
+        &self,
+        request: &Arc<TaskRequest>,
+        src: &Path,
+        dest: &str,
+        attributes: &Option<FileAttributesEvaluated>,
+        before_complete: G,
+    ) -> Result<(), Arc<TaskResponse>>
+    where
+        G: FnMut(&String) -> Result<(), Arc<TaskResponse>>,
+    {
+        self.transfer_with_flags(request, dest, attributes, || {
+            self.copy_file_unflagged(request, src, dest, before_complete)
+        })
+    }
+
+    fn copy_file_unflagged<G>(
         &self,
         request: &Arc<TaskRequest>,
         src: &Path,
@@ -498,6 +681,21 @@ impl Remote {
         self.run(request, &cmd, CheckRc::Checked)
     }
 
+    // copies path to path.backup.<UTC timestamp> and returns the name of the copy
+
+    pub fn backup_file(
+        &self,
+        request: &Arc<TaskRequest>,
+        path: &str,
+    ) -> Result<String, Arc<TaskResponse>> {
+        let suffix = chrono::Utc::now().format("%Y%m%d_%H%M%S").to_string();
+        let get_cmd_result =
+            crate::tasks::cmd_library::get_backup_file_command(self.get_os_type(), path, &suffix);
+        let cmd = self.unwrap_string_result(request, &get_cmd_result)?;
+        self.run(request, &cmd, CheckRc::Checked)?;
+        Ok(format!("{}.backup.{}", path, suffix))
+    }
+
     pub fn delete_directory(
         &self,
         request: &Arc<TaskRequest>,
@@ -545,6 +743,62 @@ impl Remote {
         Ok(rc == 0)
     }
 
+    // the target a symlink points to, as written (not resolved), or None when the
+    // path is missing or is not a symlink
+
+    pub fn read_link(
+        &self,
+        request: &Arc<TaskRequest>,
+        path: &str,
+    ) -> Result<Option<String>, Arc<TaskResponse>> {
+        let get_cmd_result =
+            crate::tasks::cmd_library::get_read_link_command(self.get_os_type(), path);
+        let cmd = self.unwrap_string_result(request, &get_cmd_result)?;
+        let result = self.run(request, &cmd, CheckRc::Unchecked)?;
+        let (rc, out) = cmd_info(&result);
+        match rc {
+            0 => Ok(Some(out.trim_end_matches(['\r', '\n']).to_string())),
+            _ => Ok(None),
+        }
+    }
+
+    // "device:inode" of the path itself, or None if it does not exist
+
+    pub fn get_file_identity(
+        &self,
+        request: &Arc<TaskRequest>,
+        path: &str,
+    ) -> Result<Option<String>, Arc<TaskResponse>> {
+        let get_cmd_result =
+            crate::tasks::cmd_library::get_file_identity_command(self.get_os_type(), path);
+        let cmd = self.unwrap_string_result(request, &get_cmd_result)?;
+        let result = self.run(request, &cmd, CheckRc::Unchecked)?;
+        let (rc, out) = cmd_info(&result);
+        match rc {
+            0 => Ok(Some(out.trim().to_string())),
+            _ => Ok(None),
+        }
+    }
+
+    pub fn create_link(
+        &self,
+        request: &Arc<TaskRequest>,
+        src: &str,
+        path: &str,
+        link_type: LinkType,
+        replace: bool,
+    ) -> Result<Arc<TaskResponse>, Arc<TaskResponse>> {
+        let get_cmd_result = crate::tasks::cmd_library::get_create_link_command(
+            self.get_os_type(),
+            src,
+            path,
+            link_type,
+            replace,
+        );
+        let cmd = self.unwrap_string_result(request, &get_cmd_result)?;
+        self.run(request, &cmd, CheckRc::Checked)
+    }
+
     // return the (owner,group) tuple for a remote file.  If the command fails this will instead return None
     // so consider running get_mode first.  See the various file modules for examples.
 
@@ -590,6 +844,53 @@ impl Remote {
         Ok(Some((owner.to_string(), group.to_string())))
     }
 
+    // the primary group id and home directory of a user, None if there is no such user
+
+    pub fn get_user_home(
+        &self,
+        request: &Arc<TaskRequest>,
+        user: &str,
+    ) -> Result<Option<(String, String)>, Arc<TaskResponse>> {
+        let os_type = self.get_os_type();
+        let get_cmd_result = crate::tasks::cmd_library::get_user_home_command(os_type, user);
+        let cmd = self.unwrap_string_result(request, &get_cmd_result)?;
+
+        let result = self.run(request, &cmd, CheckRc::Unchecked)?;
+        let (rc, out) = cmd_info(&result);
+        if rc != 0 {
+            return Ok(None);
+        }
+        let entry = match os_type {
+            // user:pwd:uid:gid:gecos:home:shell
+            HostOSType::Linux => {
+                let fields: Vec<&str> = out.trim().split(':').collect();
+                match fields.len() {
+                    7 => Some((fields[3].to_string(), fields[5].to_string())),
+                    _ => None,
+                }
+            }
+            // "PrimaryGroupID: 20" and "NFSHomeDirectory: /Users/alice" lines
+            HostOSType::MacOS => {
+                let value = |key: &str| {
+                    out.lines()
+                        .find_map(|line| line.strip_prefix(key))
+                        .map(|x| x.trim().to_string())
+                };
+                match (value("PrimaryGroupID:"), value("NFSHomeDirectory:")) {
+                    (Some(gid), Some(home)) => Some((gid, home)),
+                    _ => None,
+                }
+            }
+        };
+        match entry {
+            Some((gid, home)) if !home.is_empty() => Ok(Some((gid, home))),
+            _ => Err(self.response.is_failed(
+                request,
+                &format!("unexpected output format from {}: {}", cmd, out),
+            )),
+        }
+    }
+
     pub fn set_owner(
         &self,
         request: &Arc<TaskRequest>,
@@ -641,6 +942,146 @@ impl Remote {
         self.run(request, &cmd, CheckRc::Checked)
     }
 
+    // the ACL entries of a file, in getfacl's long form and without the effective-rights comments
+
+    pub fn get_acl(
+        &self,
+        request: &Arc<TaskRequest>,
+        remote_path: &str,
+    ) -> Result<Vec<String>, Arc<TaskResponse>> {
+        let get_cmd_result =
+            crate::tasks::cmd_library::get_acl_command(self.get_os_type(), remote_path);
+        let cmd = self.unwrap_string_result(request, &get_cmd_result)?;
+        let result = self.run(request, &cmd, CheckRc::Checked)?;
+        let (_, out) = cmd_info(&result);
+        Ok(out
+            .lines()
+            .filter_map(|line| line.split('#').next())
+            .map(str::trim)
+            .filter(|entry| !entry.is_empty())
+            .map(String::from)
+            .collect())
+    }
+
+    pub fn set_acl(
+        &self,
+        request: &Arc<TaskRequest>,
+        remote_path: &str,
+        entries: &[String],
+        recurse: Recurse,
+    ) -> Result<Arc<TaskResponse>, Arc<TaskResponse>> {
+        let get_cmd_result = crate::tasks::cmd_library::set_acl_command(
+            self.get_os_type(),
+            remote_path,
+            entries,
+            recurse,
+        );
+        let cmd = self.unwrap_string_result(request, &get_cmd_result)?;
+        self.run(request, &cmd, CheckRc::Checked)
+    }
+
+    // the (user, type) parts of a file's SELinux context, None when there is none to read
+
+    pub fn get_selinux_context(
+        &self,
+        request: &Arc<TaskRequest>,
+        remote_path: &str,
+    ) -> Result<Option<(String, String)>, Arc<TaskResponse>> {
+        let get_cmd_result =
+            crate::tasks::cmd_library::get_selinux_context_command(self.get_os_type(), remote_path);
+        let cmd = self.unwrap_string_result(request, &get_cmd_result)?;
+        let result = self.run(request, &cmd, CheckRc::Unchecked)?;
+        let (rc, out) = cmd_info(&result);
+        if rc != 0 {
+            return Ok(None);
+        }
+        // user:role:type:level, or "?" when SELinux is disabled
+        let parts: Vec<&str> = out.trim().split(':').collect();
+        match parts.len() >= 3 {
+            true => Ok(Some((parts[0].to_string(), parts[2].to_string()))),
+            false => Ok(None),
+        }
+    }
+
+    pub fn set_selinux_context(
+        &self,
+        request: &Arc<TaskRequest>,
+        remote_path: &str,
+        seuser: Option<&str>,
+        setype: Option<&str>,
+        recurse: Recurse,
+    ) -> Result<Arc<TaskResponse>, Arc<TaskResponse>> {
+        let get_cmd_result = crate::tasks::cmd_library::set_selinux_context_command(
+            self.get_os_type(),
+            remote_path,
+            seuser,
+            setype,
+            recurse,
+        );
+        let cmd = self.unwrap_string_result(request, &get_cmd_result)?;
+        self.run(request, &cmd, CheckRc::Checked)
+    }
+
+    // the chattr flags of a file, None when they cannot be read (not Linux, a missing
+    // file, or a filesystem without them)
+
+    pub fn get_file_flags(
+        &self,
+        request: &Arc<TaskRequest>,
+        remote_path: &str,
+    ) -> Result<Option<FileFlags>, Arc<TaskResponse>> {
+        if self.get_os_type() != HostOSType::Linux {
+            return Ok(None);
+        }
+        let get_cmd_result =
+            crate::tasks::cmd_library::get_file_flags_command(self.get_os_type(), remote_path);
+        let cmd = self.unwrap_string_result(request, &get_cmd_result)?;
+        let result = self.run(request, &cmd, CheckRc::Unchecked)?;
+        let (rc, out) = cmd_info(&result);
+        if rc != 0 {
+            return Ok(None);
+        }
+        // "----i---------e------- /path"
+        Ok(out.split_whitespace().next().map(|flags| FileFlags {
+            immutable: flags.contains('i'),
+            append_only: flags.contains('a'),
+        }))
+    }
+
+    pub fn set_file_flags(
+        &self,
+        request: &Arc<TaskRequest>,
+        remote_path: &str,
+        flags: FileFlags,
+        recurse: Recurse,
+    ) -> Result<Arc<TaskResponse>, Arc<TaskResponse>> {
+        let get_cmd_result = crate::tasks::cmd_library::set_file_flags_command(
+            self.get_os_type(),
+            remote_path,
+            flags,
+            recurse,
+        );
+        let cmd = self.unwrap_string_result(request, &get_cmd_result)?;
+        self.run(request, &cmd, CheckRc::Checked)
+    }
+
+    // clears any chattr flags so the file can be changed, returning the flags it had
+    // (None if they could not be read) so the caller can put them back
+
+    fn unlock_file(
+        &self,
+        request: &Arc<TaskRequest>,
+        remote_path: &str,
+    ) -> Result<Option<FileFlags>, Arc<TaskResponse>> {
+        let flags = self.get_file_flags(request, remote_path)?;
+        if let Some(current) = flags
+            && current != FileFlags::default()
+        {
+            self.set_file_flags(request, remote_path, FileFlags::default(), Recurse::No)?;
+        }
+        Ok(flags)
+    }
+
     pub fn get_sha512(
         &self,
         request: &Arc<TaskRequest>,
@@ -655,9 +1096,21 @@ impl Remote {
         &self,
         request: &Arc<TaskRequest>,
         path: &str,
+    ) -> Result<String, Arc<TaskResponse>> {
+        self.get_checksum(request, path, Algorithm::Sha512)
+    }
+
+    // hex digest of a remote file, or an empty string if it does not exist
+
+    pub fn get_checksum(
+        &self,
+        request: &Arc<TaskRequest>,
+        path: &str,
+        algorithm: Algorithm,
     ) -> Result<String, Arc<TaskResponse>> {
         let os_type = self.get_os_type();
-        let get_cmd_result = crate::tasks::cmd_library::get_sha512_command(os_type, path);
+        let get_cmd_result =
+            crate::tasks::cmd_library::get_checksum_command(os_type, path, algorithm);
         let cmd = self.unwrap_string_result(request, &get_cmd_result)?;
 
         let result = self.run(request, &cmd, CheckRc::Unchecked)?;
@@ -679,8 +1132,51 @@ impl Remote {
         }
     }
 
+    // sha512 of every regular file below a remote directory, keyed by path relative to it
+
+    pub fn get_tree_sha512(
+        &self,
+        request: &Arc<TaskRequest>,
+        path: &str,
+    ) -> Result<HashMap<String, String>, Arc<TaskResponse>> {
+        let get_cmd_result =
+            crate::tasks::cmd_library::get_tree_sha512_command(self.get_os_type(), path);
+        let cmd = self.unwrap_string_result(request, &get_cmd_result)?;
+        let result = self.run_unsafe(request, &cmd, CheckRc::Checked)?;
+        let (_, out) = cmd_info(&result);
+        let prefix = format!("{}/", path.trim_end_matches('/'));
+        let mut sums: HashMap<String, String> = HashMap::new();
+        for line in out.lines() {
+            if let Some((sum, file)) = crate::tasks::checksum::parse_sum_line(line)
+                && let Some(relative) = file.strip_prefix(&prefix)
+            {
+                sums.insert(relative.to_string(), sum);
+            }
+        }
+        Ok(sums)
+    }
+
+    // every directory below a remote directory, relative to it
+
+    pub fn get_tree_directories(
+        &self,
+        request: &Arc<TaskRequest>,
+        path: &str,
+    ) -> Result<Vec<String>, Arc<TaskResponse>> {
+        let get_cmd_result =
+            crate::tasks::cmd_library::get_tree_directories_command(self.get_os_type(), path);
+        let cmd = self.unwrap_string_result(request, &get_cmd_result)?;
+        let result = self.run(request, &cmd, CheckRc::Checked)?;
+        let (_, out) = cmd_info(&result);
+        let prefix = format!("{}/", path.trim_end_matches('/'));
+        Ok(out
+            .lines()
+            .filter_map(|line| line.strip_prefix(&prefix))
+            .map(String::from)
+            .collect())
+    }
+
     // supporting code for any tasks that has an 'attributes' member, see 'template' for one example of usage
-    // TODO: add SELinux
 
     pub fn query_common_file_attributes(
         &self,
@@ -698,9 +1194,22 @@ impl Remote {
         }
 
         if attributes_in.is_some() && recurse == Recurse::Yes {
+            let attributes = attributes_in.as_ref().unwrap();
             changes.push(Field::Owner);
             changes.push(Field::Group);
             changes.push(Field::Mode);
+            if attributes.acl.is_some() {
+                changes.push(Field::Acl);
+            }
+            if attributes.seuser.is_some() || attributes.setype.is_some() {
+                changes.push(Field::Selinux);
+            }
+            if attributes.immutable.is_some() {
+                changes.push(Field::Immutable);
+            }
+            if attributes.append_only.is_some() {
+                changes.push(Field::AppendOnly);
+            }
             return Ok(remote_mode);
         }
 
@@ -729,13 +1238,68 @@ impl Remote {
             {
                 changes.push(Field::Mode);
             }
+            // extra entries already on the file are left alone, only missing ones count
+            if let Some(acl) = &attributes.acl {
+                let remote_acl = self.get_acl(request, remote_path)?;
+                if !acl.iter().all(|entry| remote_acl.contains(entry)) {
+                    changes.push(Field::Acl);
+                }
+            }
+            if attributes.seuser.is_some() || attributes.setype.is_some() {
+                let (remote_seuser, remote_setype) =
+                    match self.get_selinux_context(request, remote_path)? {
+                        Some(context) => context,
+                        None => {
+                            return Err(self.response.is_failed(
+                                request,
+                                &format!(
+                                    "cannot read the SELinux context of {}, is SELinux enabled?",
+                                    remote_path
+                                ),
+                            ));
+                        }
+                    };
+                if attributes
+                    .seuser
+                    .as_ref()
+                    .is_some_and(|x| *x != remote_seuser)
+                    || attributes
+                        .setype
+                        .as_ref()
+                        .is_some_and(|x| *x != remote_setype)
+                {
+                    changes.push(Field::Selinux);
+                }
+            }
+            if attributes.has_flags() {
+                let remote_flags = match self.get_file_flags(request, remote_path)? {
+                    Some(flags) => flags,
+                    None => {
+                        return Err(self.response.is_failed(
+                            request,
+                            &format!("cannot read the chattr flags of {}", remote_path),
+                        ));
+                    }
+                };
+                if attributes
+                    .immutable
+                    .is_some_and(|x| x != remote_flags.immutable)
+                {
+                    changes.push(Field::Immutable);
+                }
+                if attributes
+                    .append_only
+                    .is_some_and(|x| x != remote_flags.append_only)
+                {
+                    changes.push(Field::AppendOnly);
+                }
+            }
         }
         Ok(remote_mode)
     }
 
     // supporting code for workign with files that have configurable attributes. See above + also
     // modules like template.
-    // TODO: add SELinux
 
     pub fn process_common_file_attributes(
         &self,
@@ -745,11 +1309,62 @@ impl Remote {
         changes: &[Field],
         recurse: Recurse,
     ) -> Result<(), Arc<TaskResponse>> {
-        if attributes_in.is_none() {
+        if attributes_in.is_none() || changes.is_empty() {
             return Ok(());
         }
         let attributes = attributes_in.as_ref().unwrap();
+        if !attributes.has_flags() {
+            return self.apply_common_file_attributes(
+                request,
+                remote_path,
+                attributes,
+                changes,
+                recurse,
+            );
+        }
+
+        // an immutable or append-only file refuses every other change too, so its
+        // flags come off first and are settled once the rest is done
+        let previous = self.unlock_file(request, remote_path)?;
+        let result =
+            self.apply_common_file_attributes(request, remote_path, attributes, changes, recurse);
+        let flags =
+            FileAttributesEvaluated::merge_flags(Some(attributes), previous.unwrap_or_default());
+        if flags != FileFlags::default() {
+            self.set_file_flags(request, remote_path, flags, recurse)?;
+        }
+        result
+    }
+
+    // for the before_complete callback of write_data and copy_file: everything but the
+    // chattr flags, which those apply once the file has been moved into place
+
+    pub fn process_transferred_file_attributes(
+        &self,
+        request: &Arc<TaskRequest>,
+        remote_path: &str,
+        attributes_in: &Option<FileAttributesEvaluated>,
+    ) -> Result<(), Arc<TaskResponse>> {
+        match attributes_in {
+            Some(attributes) => self.apply_common_file_attributes(
+                request,
+                remote_path,
+                attributes,
+                &Field::all_file_attributes(),
+                Recurse::No,
+            ),
+            None => Ok(()),
+        }
+    }
 
+    fn apply_common_file_attributes(
+        &self,
+        request: &Arc<TaskRequest>,
+        remote_path: &str,
+        attributes: &FileAttributesEvaluated,
+        changes: &[Field],
+        recurse: Recurse,
+    ) -> Result<(), Arc<TaskResponse>> {
         for change in changes.iter() {
             match change {
                 Field::Owner => {
@@ -770,6 +1385,20 @@ impl Remote {
                         recurse,
                     )?;
                 }
+                Field::Acl => {
+                    if let Some(acl) = &attributes.acl {
+                        self.set_acl(request, remote_path, acl, recurse)?;
+                    }
+                }
+                Field::Selinux if attributes.seuser.is_some() || attributes.setype.is_some() => {
+                    self.set_selinux_context(
+                        request,
+                        remote_path,
+                        attributes.seuser.as_deref(),
+                        attributes.setype.as_deref(),
+                        recurse,
+                    )?;
+                }
                 _ => {}
             }
         }
@@ -59,6 +59,7 @@ impl Response {
             command_result: Arc::new(None),
             with: Arc::new(None),
             and: Arc::new(None),
+            diff: None,
         })
     }
 
@@ -89,6 +90,7 @@ impl Response {
             command_result: Arc::clone(result),
             with: Arc::new(None),
             and: Arc::new(None),
+            diff: None,
         })
     }
 
@@ -113,6 +115,7 @@ impl Response {
             command_result: Arc::clone(result),
             with: Arc::new(None),
             and: Arc::new(None),
+            diff: None,
         })
     }
 
@@ -129,6 +132,7 @@ impl Response {
             command_result: Arc::new(None),
             with: Arc::new(None),
             and: Arc::new(None),
+            diff: None,
         })
     }
 
@@ -148,6 +152,7 @@ impl Response {
             command_result: Arc::new(None),
             with: Arc::new(None),
             and: Arc::new(None),
+            diff: None,
         })
     }
 
@@ -164,6 +169,7 @@ impl Response {
             command_result: Arc::new(None),
             with: Arc::new(None),
             and: Arc::new(None),
+            diff: None,
         })
     }
 
@@ -181,6 +187,7 @@ impl Response {
             command_result: Arc::new(None),
             with: Arc::new(None),
             and: Arc::new(None),
+            diff: None,
         })
     }
 
@@ -197,6 +204,7 @@ impl Response {
             command_result: Arc::new(None),
             with: Arc::new(None),
             and: Arc::new(None),
+            diff: None,
         })
     }
 
@@ -214,6 +222,7 @@ impl Response {
             command_result: Arc::new(None),
             with: Arc::new(None),
             and: Arc::new(None),
+            diff: None,
         })
     }
 
@@ -234,6 +243,7 @@ impl Response {
             command_result: Arc::new(None),
             with: Arc::new(None),
             and: Arc::new(None),
+            diff: None,
         })
     }
 
@@ -250,6 +260,7 @@ impl Response {
             command_result: Arc::new(None),
             with: Arc::new(None),
             and: Arc::new(None),
+            diff: None,
         })
     }
 
@@ -271,6 +282,50 @@ impl Response {
             command_result: Arc::new(None),
             with: Arc::new(None),
             and: Arc::new(None),
+            diff: None,
+        })
+    }
+
+    pub fn wants_diff(&self) -> bool {
+        // file modules only fetch remote content for a diff when --diff was given
+        self.get_context().read().unwrap().show_diff
+    }
+
+    pub fn with_diff(
+        &self,
+        response: Arc<TaskResponse>,
+        diff: Option<String>,
+    ) -> Arc<TaskResponse> {
+        // attaches a unified diff (see util/diff.rs) to a query response, the FSM carries
+        // it forward onto the final created/modified response
+        if diff.is_none() {
+            return response;
+        }
+        Arc::new(TaskResponse {
+            status: response.status.clone(),
+            changes: response.changes.clone(),
+            msg: response.msg.clone(),
+            command_result: Arc::clone(&response.command_result),
+            with: Arc::clone(&response.with),
+            and: Arc::clone(&response.and),
+            diff,
+        })
+    }
+
+    pub fn with_msg(&self, response: Arc<TaskResponse>, msg: Option<String>) -> Arc<TaskResponse> {
+        // attaches a short note to a successful response, such as how many lines a
+        // query found to change or a modification changed
+        if msg.is_none() {
+            return response;
+        }
+        Arc::new(TaskResponse {
+            status: response.status.clone(),
+            changes: response.changes.clone(),
+            msg,
+            command_result: Arc::clone(&response.command_result),
+            with: Arc::clone(&response.with),
+            and: Arc::clone(&response.and),
+            diff: response.diff.clone(),
         })
     }
 
@@ -287,6 +342,7 @@ impl Response {
             command_result: Arc::new(None),
             with: Arc::new(None),
             and: Arc::new(None),
+            diff: None,
         })
     }
 
@@ -304,6 +360,7 @@ impl Response {
             command_result: Arc::new(None),
             with: Arc::new(None),
             and: Arc::new(None),
+            diff: None,
         })
     }
 
@@ -320,6 +377,7 @@ impl Response {
             command_result: Arc::new(None),
             with: Arc::new(None),
             and: Arc::new(None),
+            diff: None,
         })
     }
 }
@@ -17,11 +17,12 @@
 use crate::handle::response::Response;
 use crate::inventory::hosts::Host;
 use crate::playbooks::context::PlaybookContext;
-use crate::playbooks::templar::{Templar, TemplateMode};
+use crate::playbooks::templar::{Templar, TemplateEngine, TemplateMode};
 use crate::playbooks::traversal::RunState;
 use crate::tasks::cmd_library::{screen_general_input_strict, screen_path};
 use crate::tasks::request::TaskRequest;
 use crate::tasks::response::TaskResponse;
+use crate::tasks::templated::{Templated, TemplatedScalar};
 use std::path::PathBuf;
 use std::sync::{Arc, RwLock};
 
@@ -30,7 +31,8 @@ use std::sync::{Arc, RwLock};
 // sure they are the right type.
 //
 // because module arguments come in as strings, we evaluate templates here and then see if they can
-// be parsed as their desired types.
+// be parsed as their desired types. Typed (boolean, integer) parameters are declared as
+// Templated<T> so they may also be written as native YAML scalars, see tasks/templated.rs
 
 // when blend target must be specified, it is either the template module or *not*.
 // the only real difference (at the moment) is that the template module is allowed access
@@ -99,13 +101,15 @@ impl Template {
         _field: &str,
         template: &str,
         blend_target: BlendTarget,
+        engine: Option<TemplateEngine>,
     ) -> Result<String, Arc<TaskResponse>> {
-        let result = self.run_state.context.read().unwrap().render_template(
-            template,
-            &self.host,
-            blend_target,
-            tm,
-        );
+        let context = self.run_state.context.read().unwrap();
+        let result = match engine {
+            Some(engine) => {
+                context.render_template_with_engine(template, &self.host, blend_target, tm, engine)
+            }
+            None => context.render_template(template, &self.host, blend_target, tm),
+        };
         if let Ok(result_ok) = &result
             && result_ok.is_empty()
         {
@@ -123,10 +127,19 @@ impl Template {
         tm: TemplateMode,
         field: &str,
         template: &str,
+        engine: Option<TemplateEngine>,
     ) -> Result<String, Arc<TaskResponse>> {
         // this is the version of templating that gives access to secret variables, we don't allow them elsewhere as they would be easy to leak to CI/CD/build output/logs
         // and the contents to templates are not shown to anything
-        self.template_unsafe_internal(request, tm, field, template, BlendTarget::TemplateModule)
+        // the engine comes from the template file's extension, when None the play's engine is used
+        self.template_unsafe_internal(
+            request,
+            tm,
+            field,
+            template,
+            BlendTarget::TemplateModule,
+            engine,
+        )
     }
 
     pub fn string_unsafe_for_shell(
@@ -137,7 +150,14 @@ impl Template {
         template: &str,
     ) -> Result<String, Arc<TaskResponse>> {
         // indicates templating a string that will not without further processing, be passed to a shell command
-        self.template_unsafe_internal(request, tm, field, template, BlendTarget::NotTemplateModule)
+        self.template_unsafe_internal(
+            request,
+            tm,
+            field,
+            template,
+            BlendTarget::NotTemplateModule,
+            None,
+        )
     }
 
     // FIXME: this code is possibly a bit redundant - perhaps calling methods can use the public function and this can be eliminated
@@ -179,6 +199,7 @@ impl Template {
                 field,
                 template.as_ref().unwrap(),
                 BlendTarget::NotTemplateModule,
+                None,
             )?)),
         }
     }
@@ -341,14 +362,8 @@ impl Template {
             return Ok(0);
         }
         let st = self.string(request, tm, field, template)?;
-        let num = st.parse::<u64>();
-        match num {
-            Ok(num) => Ok(num),
-            Err(_err) => Err(self.response.is_failed(
-                request,
-                &format!("field ({}) value is not an integer: {}", field, st),
-            )),
-        }
+        Templated::<u64>::parse_rendered(field, &st)
+            .map_err(|e| self.response.is_failed(request, &e))
     }
 
     #[allow(dead_code)]
@@ -357,25 +372,16 @@ impl Template {
         request: &Arc<TaskRequest>,
         tm: TemplateMode,
         field: &str,
-        template: &Option<String>,
+        template: &Option<Templated<u64>>,
         default: Option<u64>,
     ) -> Result<Option<u64>, Arc<TaskResponse>> {
         // templates an optional value that must resolve to an integer or None
         if tm == TemplateMode::Off {
             return Ok(None);
         }
-        if template.is_none() {
-            return Ok(default);
-        }
-        let st = self.string(request, tm, field, template.as_ref().unwrap())?;
-        let num = st.parse::<u64>();
-        // FIXME: these can use map_err
-        match num {
-            Ok(num) => Ok(Some(num)),
-            Err(_err) => Err(self.response.is_failed(
-                request,
-                &format!("field ({}) value is not an integer: {}", field, st),
-            )),
+        match self.typed_option(request, tm, field, template)? {
+            Some(num) => Ok(Some(num)),
+            None => Ok(default),
         }
     }
 
@@ -384,26 +390,16 @@ impl Template {
         request: &Arc<TaskRequest>,
         tm: TemplateMode,
         field: &str,
-        template: &Option<String>,
+        template: &Option<Templated<u64>>,
         default: u64,
     ) -> Result<u64, Arc<TaskResponse>> {
         // templates an optional value that must resolve to an integer
         if tm == TemplateMode::Off {
             return Ok(0);
         }
-        if template.is_none() {
-            return Ok(default);
-        }
-        let st = self.string(request, tm, field, template.as_ref().unwrap())?;
-        let num = st.parse::<u64>();
-        // FIXME: these can use map_err
-        match num {
-            Ok(num) => Ok(num),
-            Err(_err) => Err(self.response.is_failed(
-                request,
-                &format!("field ({}) value is not an integer: {}", field, st),
-            )),
-        }
+        Ok(self
+            .typed_option(request, tm, field, template)?
+            .unwrap_or(default))
     }
 
     #[allow(dead_code)]
@@ -421,14 +417,8 @@ impl Template {
             return Ok(true);
         }
         let st = self.string(request, tm, field, template)?;
-        let x = st.parse::<bool>();
-        match x {
-            Ok(x) => Ok(x),
-            Err(_err) => Err(self.response.is_failed(
-                request,
-                &format!("field ({}) value is not a boolean: {}", field, st),
-            )),
-        }
+        Templated::<bool>::parse_rendered(field, &st)
+            .map_err(|e| self.response.is_failed(request, &e))
     }
 
     #[allow(dead_code)]
@@ -437,7 +427,7 @@ impl Template {
         request: &Arc<TaskRequest>,
         tm: TemplateMode,
         field: &str,
-        template: &Option<String>,
+        template: &Option<Templated<bool>>,
     ) -> Result<bool, Arc<TaskResponse>> {
         // templates an optional value that resolves to a boolean, if omitted, assume the answer is true
         self.internal_boolean_option(request, tm, field, template, true)
@@ -448,7 +438,7 @@ impl Template {
         request: &Arc<TaskRequest>,
         tm: TemplateMode,
         field: &str,
-        template: &Option<String>,
+        template: &Option<Templated<bool>>,
     ) -> Result<bool, Arc<TaskResponse>> {
         // templates an optional value that resolves to a boolean, if omitted, assume the answer is false
         self.internal_boolean_option(request, tm, field, template, false)
@@ -459,25 +449,16 @@ impl Template {
         request: &Arc<TaskRequest>,
         tm: TemplateMode,
         field: &str,
-        template: &Option<String>,
+        template: &Option<Templated<bool>>,
         default: bool,
     ) -> Result<bool, Arc<TaskResponse>> {
         // supporting code for boolean parsing above
         if tm == TemplateMode::Off {
             return Ok(false);
         }
-        if template.is_none() {
-            return Ok(default);
-        }
-        let st = self.string(request, tm, field, template.as_ref().unwrap())?;
-        let x = st.parse::<bool>();
-        match x {
-            Ok(x) => Ok(x),
-            Err(_err) => Err(self.response.is_failed(
-                request,
-                &format!("field ({}) value is not a boolean: {}", field, st),
-            )),
-        }
+        Ok(self
+            .typed_option(request, tm, field, template)?
+            .unwrap_or(default))
     }
 
     pub fn boolean_option_default_none(
@@ -485,23 +466,52 @@ impl Template {
         request: &Arc<TaskRequest>,
         tm: TemplateMode,
         field: &str,
-        template: &Option<String>,
+        template: &Option<Templated<bool>>,
     ) -> Result<Option<bool>, Arc<TaskResponse>> {
         // supports an optional boolean value that does not default to true or false - effectively making the option a trinary value where None is "no preference"
         if tm == TemplateMode::Off {
             return Ok(None);
         }
-        if template.is_none() {
-            return Ok(None);
+        self.typed_option(request, tm, field, template)
+    }
+
+    pub fn scalar_option_default<T: TemplatedScalar>(
+        &self,
+        request: &Arc<TaskRequest>,
+        tm: TemplateMode,
+        field: &str,
+        template: &Option<Templated<T>>,
+        default: T,
+    ) -> Result<T, Arc<TaskResponse>> {
+        // for module specific scalar types (such as an upgrade mode) that mix
+        // booleans and keywords, like the boolean options above
+        if tm == TemplateMode::Off {
+            return Ok(default);
         }
-        let st = self.string(request, tm, field, template.as_ref().unwrap())?;
-        let x = st.parse::<bool>();
-        match x {
-            Ok(x) => Ok(Some(x)),
-            Err(_err) => Err(self.response.is_failed(
-                request,
-                &format!("field ({}) value is not a boolean: {}", field, st),
-            )),
+        Ok(self
+            .typed_option(request, tm, field, template)?
+            .unwrap_or(default))
+    }
+
+    fn typed_option<T: TemplatedScalar>(
+        &self,
+        request: &Arc<TaskRequest>,
+        tm: TemplateMode,
+        field: &str,
+        template: &Option<Templated<T>>,
+    ) -> Result<Option<T>, Arc<TaskResponse>> {
+        // native YAML scalars pass straight through, template strings are rendered and then
+        // parsed, so a value of the wrong type fails here (at validation) naming the field
+        match template {
+            None => Ok(None),
+            Some(Templated::Value(x)) => Ok(Some(x.clone())),
+            Some(Templated::Template(t)) => {
+                let st = self.string(request, tm, field, t)?;
+                match Templated::<T>::parse_rendered(field, &st) {
+                    Ok(x) => Ok(Some(x)),
+                    Err(e) => Err(self.response.is_failed(request, &e)),
+                }
+            }
         }
     }
 
@@ -212,16 +212,16 @@ mod tests {
     use super::*;
 
     fn create_test_host(name: &str) -> Arc<RwLock<Host>> {
-        Arc::new(RwLock::new(Host::new(&name.to_string())))
+        Arc::new(RwLock::new(Host::new(name)))
     }
 
     fn create_test_group(name: &str) -> Arc<RwLock<Group>> {
-        Arc::new(RwLock::new(Group::new(&name.to_string())))
+        Arc::new(RwLock::new(Group::new(name)))
     }
 
     #[test]
     fn test_group_new() {
-        let group = Group::new(&"test-group".to_string());
+        let group = Group::new("test-group");
         assert_eq!(group.name, "test-group");
         assert!(group.subgroups.is_empty());
         assert!(group.parents.is_empty());
@@ -231,10 +231,10 @@ mod tests {
 
     #[test]
     fn test_add_subgroup() {
-        let mut group = Group::new(&"parent".to_string());
+        let mut group = Group::new("parent");
         let subgroup = create_test_group("child");
 
-        group.add_subgroup(&"child".to_string(), subgroup.clone());
+        group.add_subgroup("child", subgroup.clone());
 
         assert_eq!(group.subgroups.len(), 1);
         assert!(group.subgroups.contains_key("child"));
@@ -243,19 +243,19 @@ mod tests {
     #[test]
     #[should_panic]
     fn test_add_subgroup_self_reference() {
-        let mut group = Group::new(&"test".to_string());
+        let mut group = Group::new("test");
         let self_ref = create_test_group("test");
 
         // Should panic - can't add self as subgroup
-        group.add_subgroup(&"test".to_string(), self_ref);
+        group.add_subgroup("test", self_ref);
     }
 
     #[test]
     fn test_add_host() {
-        let mut group = Group::new(&"web".to_string());
+        let mut group = Group::new("web");
         let host = create_test_host("webserver1");
 
-        group.add_host(&"webserver1".to_string(), host.clone());
+        group.add_host("webserver1", host.clone());
 
         assert_eq!(group.hosts.len(), 1);
         assert!(group.hosts.contains_key("webserver1"));
@@ -263,10 +263,10 @@ mod tests {
 
     #[test]
     fn test_add_parent() {
-        let mut group = Group::new(&"child".to_string());
+        let mut group = Group::new("child");
         let parent = create_test_group("parent");
 
-        group.add_parent(&"parent".to_string(), parent.clone());
+        group.add_parent("parent", parent.clone());
 
         assert_eq!(group.parents.len(), 1);
         assert!(group.parents.contains_key("parent"));
@@ -275,25 +275,25 @@ mod tests {
     #[test]
     #[should_panic]
     fn test_add_parent_self_reference() {
-        let mut group = Group::new(&"test".to_string());
+        let mut group = Group::new("test");
         let self_ref = create_test_group("test");
 
         // Should panic - can't add self as parent
-        group.add_parent(&"test".to_string(), self_ref);
+        group.add_parent("test", self_ref);
     }
 
     #[test]
     fn test_get_ancestor_groups() {
-        let mut child = Group::new(&"child".to_string());
+        let mut child = Group::new("child");
         let parent = create_test_group("parent");
         let grandparent = create_test_group("grandparent");
 
         // Set up hierarchy
-        child.add_parent(&"parent".to_string(), parent.clone());
+        child.add_parent("parent", parent.clone());
         parent
             .write()
             .unwrap()
-            .add_parent(&"grandparent".to_string(), grandparent.clone());
+            .add_parent("grandparent", grandparent.clone());
 
         let ancestors = child.get_ancestor_groups(10);
         assert!(ancestors.contains_key("parent"));
@@ -307,10 +307,10 @@ mod tests {
 
     #[test]
     fn test_get_ancestor_group_names() {
-        let mut child = Group::new(&"child".to_string());
+        let mut child = Group::new("child");
         let parent = create_test_group("parent");
 
-        child.add_parent(&"parent".to_string(), parent);
+        child.add_parent("parent", parent);
 
         let names = child.get_ancestor_group_names();
         assert!(names.contains(&"parent".to_string()));
@@ -318,16 +318,16 @@ mod tests {
 
     #[test]
     fn test_get_descendant_groups() {
-        let mut parent = Group::new(&"parent".to_string());
+        let mut parent = Group::new("parent");
         let child = create_test_group("child");
         let grandchild = create_test_group("grandchild");
 
         // Set up hierarchy
-        parent.add_subgroup(&"child".to_string(), child.clone());
+        parent.add_subgroup("child", child.clone());
         child
             .write()
             .unwrap()
-            .add_subgroup(&"grandchild".to_string(), grandchild.clone());
+            .add_subgroup("grandchild", grandchild.clone());
 
         let descendants = parent.get_descendant_groups(10);
         assert!(descendants.contains_key("child"));
@@ -341,10 +341,10 @@ mod tests {
 
     #[test]
     fn test_get_descendant_group_names() {
-        let mut parent = Group::new(&"parent".to_string());
+        let mut parent = Group::new("parent");
         let child = create_test_group("child");
 
-        parent.add_subgroup(&"child".to_string(), child);
+        parent.add_subgroup("child", child);
 
         let names = parent.get_descendant_group_names();
         assert!(names.contains(&"child".to_string()));
@@ -352,12 +352,12 @@ mod tests {
 
     #[test]
     fn test_get_parent_groups_and_names() {
-        let mut child = Group::new(&"child".to_string());
+        let mut child = Group::new("child");
         let parent1 = create_test_group("parent1");
         let parent2 = create_test_group("parent2");
 
-        child.add_parent(&"parent1".to_string(), parent1);
-        child.add_parent(&"parent2".to_string(), parent2);
+        child.add_parent("parent1", parent1);
+        child.add_parent("parent2", parent2);
 
         let parents = child.get_parent_groups();
         assert_eq!(parents.len(), 2);
@@ -372,12 +372,12 @@ mod tests {
 
     #[test]
     fn test_get_subgroups_and_names() {
-        let mut parent = Group::new(&"parent".to_string());
+        let mut parent = Group::new("parent");
         let child1 = create_test_group("child1");
         let child2 = create_test_group("child2");
 
-        parent.add_subgroup(&"child1".to_string(), child1);
-        parent.add_subgroup(&"child2".to_string(), child2);
+        parent.add_subgroup("child1", child1);
+        parent.add_subgroup("child2", child2);
 
         let subgroups = parent.get_subgroups();
         assert_eq!(subgroups.len(), 2);
@@ -392,12 +392,12 @@ mod tests {
 
     #[test]
     fn test_get_direct_hosts_and_names() {
-        let mut group = Group::new(&"web".to_string());
+        let mut group = Group::new("web");
         let host1 = create_test_host("web1");
         let host2 = create_test_host("web2");
 
-        group.add_host(&"web1".to_string(), host1);
-        group.add_host(&"web2".to_string(), host2);
+        group.add_host("web1", host1);
+        group.add_host("web2", host2);
 
         let hosts = group.get_direct_hosts();
         assert_eq!(hosts.len(), 2);
@@ -412,19 +412,19 @@ mod tests {
 
     #[test]
     fn test_get_descendant_hosts() {
-        let mut parent = Group::new(&"parent".to_string());
+        let mut parent = Group::new("parent");
         let child = create_test_group("child");
         let host1 = create_test_host("host1");
         let host2 = create_test_host("host2");
 
         // Add host to parent
-        parent.add_host(&"host1".to_string(), host1);
+        parent.add_host("host1", host1);
 
         // Add host to child
-        child.write().unwrap().add_host(&"host2".to_string(), host2);
+        child.write().unwrap().add_host("host2", host2);
 
         // Add child to parent
-        parent.add_subgroup(&"child".to_string(), child);
+        parent.add_subgroup("child", child);
 
         let descendants = parent.get_descendant_hosts();
         assert_eq!(descendants.len(), 2);
@@ -439,7 +439,7 @@ mod tests {
 
     #[test]
     fn test_variables() {
-        let mut group = Group::new(&"test".to_string());
+        let mut group = Group::new("test");
 
         // Initially empty
         assert!(group.get_variables().is_empty());
@@ -458,7 +458,7 @@ mod tests {
 
     #[test]
     fn test_update_variables() {
-        let mut group = Group::new(&"test".to_string());
+        let mut group = Group::new("test");
 
         // Set initial variables
         let mut vars = serde_yaml::Mapping::new();
@@ -487,7 +487,7 @@ mod tests {
 
     #[test]
     fn test_get_blended_variables() {
-        let mut child = Group::new(&"child".to_string());
+        let mut child = Group::new("child");
         let parent = create_test_group("parent");
         let grandparent = create_test_group("grandparent");
 
@@ -518,7 +518,7 @@ mod tests {
                 serde_yaml::Value::String("p_value".to_string()),
             );
             p_mut.set_variables(p_vars);
-            p_mut.add_parent(&"grandparent".to_string(), grandparent);
+            p_mut.add_parent("grandparent", grandparent);
         }
 
         let mut c_vars = serde_yaml::Mapping::new();
@@ -531,7 +531,7 @@ mod tests {
             serde_yaml::Value::String("c_value".to_string()),
         );
         child.set_variables(c_vars);
-        child.add_parent(&"parent".to_string(), parent);
+        child.add_parent("parent", parent);
 
         // Test blending - child overrides parent overrides grandparent
         let blended = child.get_blended_variables();
@@ -543,7 +543,7 @@ mod tests {
 
     #[test]
     fn test_get_variables_yaml() {
-        let mut group = Group::new(&"test".to_string());
+        let mut group = Group::new("test");
 
         let mut vars = serde_yaml::Mapping::new();
         vars.insert(
@@ -558,7 +558,7 @@ mod tests {
 
     #[test]
     fn test_get_blended_variables_yaml() {
-        let mut group = Group::new(&"test".to_string());
+        let mut group = Group::new("test");
 
         let mut vars = serde_yaml::Mapping::new();
         vars.insert(
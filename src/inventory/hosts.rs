@@ -355,12 +355,12 @@ mod tests {
     use super::*;
 
     fn create_test_group(name: &str) -> Arc<RwLock<Group>> {
-        Arc::new(RwLock::new(Group::new(&name.to_string())))
+        Arc::new(RwLock::new(Group::new(name)))
     }
 
     #[test]
     fn test_host_new() {
-        let host = Host::new(&"test-host".to_string());
+        let host = Host::new("test-host");
         assert_eq!(host.name, "test-host");
         assert!(host.groups.is_empty());
         assert!(host.variables.is_empty());
@@ -370,7 +370,7 @@ mod tests {
 
     #[test]
     fn test_set_os_info_linux() {
-        let mut host = Host::new(&"test-host".to_string());
+        let mut host = Host::new("test-host");
         let result = host.set_os_info(&"Linux 5.15.0-58-generic".to_string());
         assert!(result.is_ok());
         assert_eq!(host.os_type, Some(HostOSType::Linux));
@@ -378,7 +378,7 @@ mod tests {
 
     #[test]
     fn test_set_os_info_macos() {
-        let mut host = Host::new(&"test-host".to_string());
+        let mut host = Host::new("test-host");
         let result = host.set_os_info(&"Darwin 21.6.0".to_string());
         assert!(result.is_ok());
         assert_eq!(host.os_type, Some(HostOSType::MacOS));
@@ -386,7 +386,7 @@ mod tests {
 
     #[test]
     fn test_set_os_info_unknown() {
-        let mut host = Host::new(&"test-host".to_string());
+        let mut host = Host::new("test-host");
         let result = host.set_os_info(&"UnknownOS 1.0".to_string());
         assert!(result.is_err());
         assert!(
@@ -398,80 +398,74 @@ mod tests {
 
     #[test]
     fn test_notify_and_is_notified() {
-        let mut host = Host::new(&"test-host".to_string());
+        let mut host = Host::new("test-host");
 
         // Initially not notified
-        assert!(!host.is_notified(1, &"handler1".to_string()));
+        assert!(!host.is_notified(1, "handler1"));
 
         // Notify handler
-        host.notify(1, &"handler1".to_string());
-        assert!(host.is_notified(1, &"handler1".to_string()));
+        host.notify(1, "handler1");
+        assert!(host.is_notified(1, "handler1"));
 
         // Different play number
-        assert!(!host.is_notified(2, &"handler1".to_string()));
+        assert!(!host.is_notified(2, "handler1"));
 
         // Multiple handlers in same play
-        host.notify(1, &"handler2".to_string());
-        assert!(host.is_notified(1, &"handler1".to_string()));
-        assert!(host.is_notified(1, &"handler2".to_string()));
+        host.notify(1, "handler2");
+        assert!(host.is_notified(1, "handler1"));
+        assert!(host.is_notified(1, "handler2"));
     }
 
     #[test]
     fn test_checksum_cache() {
-        let mut host = Host::new(&"test-host".to_string());
+        let mut host = Host::new("test-host");
 
         // Initially empty
-        assert!(
-            host.get_checksum_cache(1, &"/path/file".to_string())
-                .is_none()
-        );
+        assert!(host.get_checksum_cache(1, "/path/file").is_none());
 
         // Set checksum
-        host.set_checksum_cache(&"/path/file".to_string(), &"abc123".to_string());
+        host.set_checksum_cache("/path/file", "abc123");
         assert_eq!(
-            host.get_checksum_cache(1, &"/path/file".to_string()),
+            host.get_checksum_cache(1, "/path/file"),
             Some("abc123".to_string())
         );
 
         // Different task ID clears cache
-        assert!(
-            host.get_checksum_cache(2, &"/path/file".to_string())
-                .is_none()
-        );
+        assert!(host.get_checksum_cache(2, "/path/file").is_none());
 
         // Set and get multiple checksums
-        host.set_checksum_cache(&"/path/file1".to_string(), &"checksum1".to_string());
-        host.set_checksum_cache(&"/path/file2".to_string(), &"checksum2".to_string());
+        host.set_checksum_cache("/path/file1", "checksum1");
+        host.set_checksum_cache("/path/file2", "checksum2");
         assert_eq!(
-            host.get_checksum_cache(2, &"/path/file1".to_string()),
+            host.get_checksum_cache(2, "/path/file1"),
             Some("checksum1".to_string())
         );
         assert_eq!(
-            host.get_checksum_cache(2, &"/path/file2".to_string()),
+            host.get_checksum_cache(2, "/path/file2"),
             Some("checksum2".to_string())
         );
     }
 
     #[test]
     fn test_add_group_and_has_group() {
-        let mut host = Host::new(&"test-host".to_string());
+        let mut host = Host::new("test-host");
         let group = create_test_group("web");
 
         assert!(!host.has_group(&"web".to_string()));
 
-        host.add_group(&"web".to_string(), group);
+        host.add_group("web", group);
         assert!(host.has_group(&"web".to_string()));
         assert!(!host.has_group(&"db".to_string()));
     }
 
     #[test]
     fn test_get_groups_and_group_names() {
-        let mut host = Host::new(&"test-host".to_string());
+        let mut host = Host::new("test-host");
         let group1 = create_test_group("web");
         let group2 = create_test_group("prod");
 
-        host.add_group(&"web".to_string(), group1);
-        host.add_group(&"prod".to_string(), group2);
+        host.add_group("web", group1);
+        host.add_group("prod", group2);
 
         let groups = host.get_groups();
         assert_eq!(groups.len(), 2);
@@ -486,10 +480,10 @@ mod tests {
 
     #[test]
     fn test_has_ancestor_group() {
-        let mut host = Host::new(&"test-host".to_string());
+        let mut host = Host::new("test-host");
         let group = create_test_group("web");
 
-        host.add_group(&"web".to_string(), group.clone());
+        host.add_group("web", group.clone());
 
         // Direct group membership
         assert!(host.has_ancestor_group(&"web".to_string()));
@@ -500,7 +494,7 @@ mod tests {
 
     #[test]
     fn test_variables() {
-        let mut host = Host::new(&"test-host".to_string());
+        let mut host = Host::new("test-host");
 
         // Initially empty
         assert!(host.get_variables().is_empty());
@@ -519,7 +513,7 @@ mod tests {
 
     #[test]
     fn test_update_variables() {
-        let mut host = Host::new(&"test-host".to_string());
+        let mut host = Host::new("test-host");
 
         // Set initial variables
         let mut vars = serde_yaml::Mapping::new();
@@ -548,7 +542,7 @@ mod tests {
 
     #[test]
     fn test_update_facts() {
-        let mut host = Host::new(&"test-host".to_string());
+        let mut host = Host::new("test-host");
 
         let mut facts = serde_yaml::Mapping::new();
         facts.insert(
@@ -570,7 +564,7 @@ mod tests {
 
     #[test]
     fn test_get_variables_yaml() {
-        let mut host = Host::new(&"test-host".to_string());
+        let mut host = Host::new("test-host");
 
         let mut vars = serde_yaml::Mapping::new();
         vars.insert(
@@ -585,7 +579,7 @@ mod tests {
 
     #[test]
     fn test_get_blended_variables_yaml() {
-        let mut host = Host::new(&"test-host".to_string());
+        let mut host = Host::new("test-host");
 
         let mut vars = serde_yaml::Mapping::new();
         vars.insert(
@@ -600,7 +594,7 @@ mod tests {
 
     #[test]
     fn test_package_preference() {
-        let mut host = Host::new(&"test-host".to_string());
+        let mut host = Host::new("test-host");
 
         assert!(host.package_preference.is_none());
 
@@ -619,12 +613,12 @@ mod tests {
 
     #[test]
     fn test_get_ancestor_groups_and_names() {
-        let mut host = Host::new(&"test-host".to_string());
+        let mut host = Host::new("test-host");
         let group1 = create_test_group("web");
         let group2 = create_test_group("prod");
 
-        host.add_group(&"web".to_string(), group1);
-        host.add_group(&"prod".to_string(), group2);
+        host.add_group("web", group1);
+        host.add_group("prod", group2);
 
         let ancestors = host.get_ancestor_groups(10);
         assert!(ancestors.len() >= 2);
@@ -636,7 +630,7 @@ mod tests {
 
     #[test]
     fn test_blended_variables_with_groups() {
-        let mut host = Host::new(&"test-host".to_string());
+        let mut host = Host::new("test-host");
 
         // Create group with variables
         let group = create_test_group("web");
@@ -654,7 +648,7 @@ mod tests {
             group_mut.set_variables(group_vars);
         }
 
-        host.add_group(&"web".to_string(), group);
+        host.add_group("web", group);
 
         // Set host variables
         let mut host_vars = serde_yaml::Mapping::new();
@@ -683,7 +677,7 @@ mod tests {
         // jet_* builtins) must resolve in strict mode (regression: a task
         // `msg: "...{{ inventory_hostname }}"` failed strict-mode templating
         // with "variable in strict mode Some(\"inventory_hostname\")").
-        let host = Host::new(&"web-01.lon.riff.cc".to_string());
+        let host = Host::new("web-01.lon.riff.cc");
         let blended = host.get_blended_variables();
         assert_eq!(blended["inventory_hostname"], "web-01.lon.riff.cc");
         assert_eq!(blended["inventory_hostname_short"], "web-01");
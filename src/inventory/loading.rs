@@ -18,7 +18,9 @@ use crate::connection::local::convert_out;
 use crate::inventory::inventory::Inventory;
 use crate::provisioners::ProvisionConfig;
 use crate::util::io::directory_as_string;
-use crate::util::io::{is_executable, jet_file_open, path_basename_as_string, path_walk};
+use crate::util::io::{is_executable, path_basename_as_string, path_walk};
+use crate::util::redact::register_yaml_values;
+use crate::util::vault::{decrypt_mapping, decrypt_tagged_values, read_yaml_file};
 use crate::util::yaml::show_yaml_error_in_context;
 use Vec;
 use serde::Deserialize;
@@ -114,6 +116,35 @@ pub fn load_inventory(
     Ok(())
 }
 
+// every value in a secrets_inventory overlay is treated as secret and masked in
+// output and logs. The overlay is loaded by load_inventory like any other path, this
+// walks its group_vars/ and host_vars/ again only to register the values.
+
+pub fn register_secrets_inventory(secrets_paths: &[PathBuf]) -> Result<(), String> {
+    for secrets_path in secrets_paths.iter() {
+        for subdir in ["group_vars", "host_vars"] {
+            let vars_dir = secrets_path.join(subdir);
+            if !vars_dir.is_dir() {
+                continue;
+            }
+            path_walk(&vars_dir, |vars_path| {
+                let name = path_basename_as_string(vars_path);
+                if name.ends_with("~") || name.starts_with(".") || !vars_path.is_file() {
+                    return Ok(());
+                }
+                let file = read_yaml_file(vars_path)?;
+                // parse errors are reported when the overlay itself is loaded
+                if let Ok(mut value) = serde_yaml::from_str::<serde_yaml::Value>(&file) {
+                    decrypt_tagged_values(&mut value)?;
+                    register_yaml_values(&value);
+                }
+                Ok(())
+            })?;
+        }
+    }
+    Ok(())
+}
+
 // ==============================================================================================================
 // PRIVATE INTERNALS
 // ==============================================================================================================
@@ -235,9 +266,9 @@ fn load_groups_directory(inventory: &Arc<RwLock<Inventory>>, path: &Path) -> Res
             group_name = group_name[0..group_name.len() - 4].to_string();
         }
 
-        let groups_file = jet_file_open(groups_file_path)?;
+        let groups_file = read_yaml_file(groups_file_path)?;
         let groups_file_parse_result: Result<YamlGroup, serde_yaml::Error> =
-            serde_yaml::from_reader(groups_file);
+            serde_yaml::from_str(&groups_file);
         let yaml_result = match groups_file_parse_result {
             Ok(y) => y,
             Err(e) => {
@@ -310,16 +341,18 @@ fn load_vars_directory(
             }
         }
 
-        let file = jet_file_open(vars_path)?;
+        let file = read_yaml_file(vars_path)?;
         let file_parse_result: Result<serde_yaml::Mapping, serde_yaml::Error> =
-            serde_yaml::from_reader(file);
-        let yaml_result = match file_parse_result {
+            serde_yaml::from_str(&file);
+        let mut yaml_result = match file_parse_result {
             Ok(y) => y,
             Err(e) => {
                 show_yaml_error_in_context(&e, vars_path);
                 return Err("edit the file and try again?".to_string());
             }
         };
+        // individually encrypted values (`key: !vault |`) in otherwise plaintext files
+        decrypt_mapping(&mut yaml_result).map_err(|e| format!("{}: {}", vars_path.display(), e))?;
 
         // serialize the vars again just to make them easier to store/output elsewhere
         // this will also remove any comments and shorten things up
@@ -544,6 +577,19 @@ mod tests {
             .get_variables()
     }
 
+    #[test]
+    fn secrets_inventory_values_are_registered_for_redaction() {
+        use crate::util::redact::{MASK, redact};
+        let (_keep, path) = inventory_tree_with_group_vars(
+            "db_password: loading-test-hunter2\nnested:\n  token: loading-test-token\n",
+        );
+        register_secrets_inventory(&[path]).unwrap();
+        assert_eq!(
+            redact("mysql -ploading-test-hunter2 --token=loading-test-token"),
+            format!("mysql -p{} --token={}", MASK, MASK)
+        );
+    }
+
     // Regression for #18: group_vars from a later --inventory path must merge onto
     // (not replace) an earlier path's group_vars for the same group, with the later
     // path winning on key conflicts.
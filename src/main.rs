@@ -25,16 +25,18 @@ use jetpack::cli::playbooks::{
 };
 use jetpack::cli::secrets_diagnostic::missing_secret_variables;
 use jetpack::cli::show::{show_inventory_group, show_inventory_host};
+use jetpack::cli::vault::vault_command;
 use jetpack::inventory::inventory::Inventory;
-use jetpack::inventory::loading::load_inventory;
+use jetpack::inventory::loading::{load_inventory, register_secrets_inventory};
 use jetpack::util::io::quit;
+use jetpack::util::redact::redact;
 use jetpack::util::terminal::two_column_table;
 use std::process;
 use std::sync::{Arc, RwLock};
 
 fn main() {
     if let Err(e) = liftoff() {
-        quit(&e)
+        quit(&redact(&e))
     }
 }
 
@@ -61,6 +63,9 @@ fn liftoff() -> Result<(), String> {
         two_column_table("field", "value", &cli_parser.resolution_summary());
     }
 
+    // encrypted inventory and vars_files are decrypted on load with this password
+    jetpack::util::vault::set_password_file(cli_parser.vault_password_file.clone());
+
     let inventory: Arc<RwLock<Inventory>> = Arc::new(RwLock::new(Inventory::new()));
 
     match cli_parser.mode {
@@ -76,6 +81,7 @@ fn liftoff() -> Result<(), String> {
                 Arc::new(RwLock::new(cli_parser.inventory_load_paths())),
                 cli_parser.extra_vars.clone(),
             )?;
+            register_secrets(&cli_parser)?;
             if !cli_parser.inventory_set {
                 return Err(String::from(
                     "--inventory is required (pass -i PATH; for zero-arg runs, declare \
@@ -96,6 +102,7 @@ fn liftoff() -> Result<(), String> {
                     Arc::new(RwLock::new(cli_parser.inventory_load_paths())),
                     cli_parser.extra_vars.clone(),
                 )?;
+                register_secrets(&cli_parser)?;
             }
             // Ensure localhost is in the inventory for local execution
             inventory
@@ -108,11 +115,12 @@ fn liftoff() -> Result<(), String> {
         | jetpack::cli::parser::CLI_MODE_FULL_CHECK
         | jetpack::cli::parser::CLI_MODE_DOCS
         | jetpack::cli::parser::CLI_MODE_GEN_REFERENCE
-        | jetpack::cli::parser::CLI_MODE_INSTALL => {
+        | jetpack::cli::parser::CLI_MODE_INSTALL
+        | jetpack::cli::parser::CLI_MODE_VAULT => {
             // validation modes load inventory on demand inside the check
             // functions; do not seed localhost so inventory-check inspects the
             // on-disk tree exactly as declared. install is a local self-setup
-            // utility and needs no inventory.
+            // utility and needs no inventory, nor does vault.
         }
         _ => {
             inventory
@@ -127,7 +135,8 @@ fn liftoff() -> Result<(), String> {
         | jetpack::cli::parser::CLI_MODE_INVENTORY_CHECK
         | jetpack::cli::parser::CLI_MODE_DOCS
         | jetpack::cli::parser::CLI_MODE_GEN_REFERENCE
-        | jetpack::cli::parser::CLI_MODE_INSTALL => {}
+        | jetpack::cli::parser::CLI_MODE_INSTALL
+        | jetpack::cli::parser::CLI_MODE_VAULT => {}
         jetpack::cli::parser::CLI_MODE_PULL => {
             if !cli_parser.playbook_set && cli_parser.pull_url.is_none() {
                 return Err(String::from(
@@ -229,6 +238,7 @@ fn liftoff() -> Result<(), String> {
         jetpack::cli::parser::CLI_MODE_DOCS => docs(&cli_parser),
         jetpack::cli::parser::CLI_MODE_GEN_REFERENCE => gen_reference(&cli_parser),
         jetpack::cli::parser::CLI_MODE_INSTALL => install(&cli_parser),
+        jetpack::cli::parser::CLI_MODE_VAULT => vault_command(&cli_parser),
 
         _ => {
             println!("invalid CLI mode");
@@ -241,6 +251,14 @@ fn liftoff() -> Result<(), String> {
     Ok(())
 }
 
+// values from the secrets overlay are masked wherever output is produced
+fn register_secrets(cli_parser: &CliParser) -> Result<(), String> {
+    if cli_parser.no_secrets {
+        return Ok(());
+    }
+    register_secrets_inventory(&cli_parser.secrets_paths.read().unwrap())
+}
+
 pub fn handle_show(inventory: &Arc<RwLock<Inventory>>, parser: &CliParser) -> Result<(), String> {
     // jetp show -i inventory
     // jetp show -i inventory --groups g1:g2
@@ -0,0 +1,603 @@
+// Jetporch
+// Copyright (C) 2023 - JetPorch Project Contributors
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// long with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use crate::handle::handle::{CheckRc, TaskHandle};
+use crate::tasks::cmd_library::shell_quote;
+use crate::tasks::fields::Field;
+use crate::tasks::files::Recurse;
+use crate::tasks::*;
+use serde::Deserialize;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::vec::Vec;
+
+const MODULE: &str = "authorized_key";
+
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+#[derive(Default)]
+pub struct AuthorizedKeyTask {
+    pub name: Option<String>,
+    pub user: String,
+    pub key: Option<String>,
+    pub keys: Option<Vec<String>>,
+    pub url: Option<String>,
+    pub file: Option<String>,
+    pub options: Option<String>,
+    pub path: Option<String>,
+    pub exclusive: Option<Templated<bool>>,
+    pub remove: Option<Templated<bool>>,
+    pub with: Option<PreLogicInput>,
+    pub and: Option<PostLogicInput>,
+}
+
+struct AuthorizedKeyAction {
+    pub user: String,
+    pub keys: Vec<AuthorizedKey>,
+    pub url: Option<String>,
+    pub file: Option<PathBuf>,
+    pub options: Option<String>,
+    pub path: Option<String>,
+    pub exclusive: bool,
+    pub remove: bool,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+struct AuthorizedKey {
+    options: Option<String>,
+    kind: String,
+    blob: String,
+    comment: Option<String>,
+}
+
+// the file as it should be, plus one summary line per key that changes
+struct KeyPlan {
+    content: String,
+    summary: Vec<String>,
+}
+
+struct KeyLocation {
+    owner: String,
+    gid: String,
+    // the ~/.ssh directory, when the default location is used
+    directory: Option<String>,
+    path: String,
+}
+
+impl IsTask for AuthorizedKeyTask {
+    fn get_module(&self) -> String {
+        String::from(MODULE)
+    }
+    fn get_name(&self) -> Option<String> {
+        self.name.clone()
+    }
+    fn get_with(&self) -> Option<PreLogicInput> {
+        self.with.clone()
+    }
+
+    fn evaluate(
+        &self,
+        handle: &Arc<TaskHandle>,
+        request: &Arc<TaskRequest>,
+        tm: TemplateMode,
+    ) -> Result<EvaluatedTask, Arc<TaskResponse>> {
+        let mut keys: Vec<AuthorizedKey> = Vec::new();
+        let mut lines: Vec<String> = Vec::new();
+        if let Some(key) = &self.key {
+            lines.push(
+                handle
+                    .template
+                    .string_unsafe_for_shell(request, tm, "key", key)?,
+            );
+        }
+        for key in self.keys.iter().flatten() {
+            lines.push(
+                handle
+                    .template
+                    .string_unsafe_for_shell(request, tm, "keys", key)?,
+            );
+        }
+        if tm == TemplateMode::Strict {
+            for line in lines.iter() {
+                keys.extend(parse_keys(line).map_err(|e| handle.response.is_failed(request, &e))?);
+            }
+        }
+        let url = handle.template.string_option_unsafe_for_shell(
+            request,
+            tm,
+            &String::from("url"),
+            &self.url,
+        )?;
+        let file = match &self.file {
+            Some(file) => {
+                let file = handle.template.string(request, tm, "file", file)?;
+                Some(handle.template.find_file_path(request, tm, "file", &file)?)
+            }
+            None => None,
+        };
+        let exclusive = handle.template.boolean_option_default_false(
+            request,
+            tm,
+            &String::from("exclusive"),
+            &self.exclusive,
+        )?;
+        let remove = handle.template.boolean_option_default_false(
+            request,
+            tm,
+            &String::from("remove"),
+            &self.remove,
+        )?;
+        if tm == TemplateMode::Strict {
+            if self.key.is_none() && self.keys.is_none() && url.is_none() && file.is_none() {
+                return Err(handle
+                    .response
+                    .is_failed(request, "one of key, keys, url or file is required"));
+            }
+            if exclusive && remove {
+                return Err(handle
+                    .response
+                    .is_failed(request, "exclusive:true and remove:true conflict"));
+            }
+        }
+
+        Ok(EvaluatedTask {
+            action: Arc::new(AuthorizedKeyAction {
+                user: handle.template.string_no_spaces(
+                    request,
+                    tm,
+                    &String::from("user"),
+                    &self.user,
+                )?,
+                keys,
+                url,
+                file,
+                // options go into the file verbatim, which is all the quoting they need
+                options: handle.template.string_option_unsafe_for_shell(
+                    request,
+                    tm,
+                    &String::from("options"),
+                    &self.options,
+                )?,
+                path: handle.template.string_option_no_spaces(
+                    request,
+                    tm,
+                    &String::from("path"),
+                    &self.path,
+                )?,
+                exclusive,
+                remove,
+            }),
+            with: Arc::new(PreLogicInput::template(handle, request, tm, &self.with)?),
+            and: Arc::new(PostLogicInput::template(handle, request, tm, &self.and)?),
+        })
+    }
+}
+
+impl IsAction for AuthorizedKeyAction {
+    fn dispatch(
+        &self,
+        handle: &Arc<TaskHandle>,
+        request: &Arc<TaskRequest>,
+    ) -> Result<Arc<TaskResponse>, Arc<TaskResponse>> {
+        match request.request_type {
+            TaskRequestType::Query => {
+                let location = self.get_location(handle, request)?;
+                let mut changes: Vec<Field> = Vec::new();
+                let plan = self.get_plan(handle, request, &location)?;
+                if plan.is_some() {
+                    changes.push(Field::Content);
+                }
+                // a missing file or directory is created with the right permissions
+                for (path, mode) in location.get_paths() {
+                    if !handle.remote.file_exists(request, &path)? {
+                        continue;
+                    }
+                    if handle.remote.get_mode(request, &path)?.as_deref() != Some(mode)
+                        && !changes.contains(&Field::Mode)
+                    {
+                        changes.push(Field::Mode);
+                    }
+                    let owner = handle.remote.get_ownership(request, &path)?;
+                    if owner.map(|(owner, _)| owner) != Some(location.owner.clone())
+                        && !changes.contains(&Field::Owner)
+                    {
+                        changes.push(Field::Owner);
+                    }
+                }
+                if changes.is_empty() {
+                    return Ok(handle.response.is_matched(request));
+                }
+                let response = handle.response.needs_modification(request, &changes);
+                let summary = plan.map(|x| x.summary.join("\n"));
+                Ok(handle.response.with_diff(response, summary))
+            }
+
+            TaskRequestType::Modify => {
+                let location = self.get_location(handle, request)?;
+                if let Some(directory) = &location.directory
+                    && !handle.remote.get_is_directory(request, directory)?
+                {
+                    handle.remote.create_directory(request, directory)?;
+                }
+                if request.changes.contains(&Field::Content)
+                    && let Some(plan) = self.get_plan(handle, request, &location)?
+                {
+                    handle.remote.write_data(
+                        request,
+                        &plan.content,
+                        &location.path,
+                        &None,
+                        |_| Ok(()),
+                    )?;
+                }
+                for (path, mode) in location.get_paths() {
+                    if !handle.remote.file_exists(request, &path)? {
+                        continue;
+                    }
+                    handle.remote.set_mode(request, &path, mode, Recurse::No)?;
+                    handle
+                        .remote
+                        .set_owner(request, &path, &location.owner, Recurse::No)?;
+                    handle
+                        .remote
+                        .set_group(request, &path, &location.gid, Recurse::No)?;
+                }
+                Ok(handle
+                    .response
+                    .is_modified(request, request.changes.clone()))
+            }
+
+            _ => Err(handle.response.not_supported(request)),
+        }
+    }
+}
+
+impl KeyLocation {
+    // sshd refuses keys in group or world writable places, so these are the
+    // permissions ssh-keygen and ssh-copy-id would use
+    fn get_paths(&self) -> Vec<(String, &'static str)> {
+        let mut paths: Vec<(String, &'static str)> = Vec::new();
+        if let Some(directory) = &self.directory {
+            paths.push((directory.clone(), "700"));
+        }
+        paths.push((self.path.clone(), "600"));
+        paths
+    }
+}
+
+impl AuthorizedKeyAction {
+    fn get_location(
+        &self,
+        handle: &Arc<TaskHandle>,
+        request: &Arc<TaskRequest>,
+    ) -> Result<KeyLocation, Arc<TaskResponse>> {
+        let (gid, home) = match handle.remote.get_user_home(request, &self.user)? {
+            Some(entry) => entry,
+            None => {
+                return Err(handle
+                    .response
+                    .is_failed(request, &format!("user {} does not exist", self.user)));
+            }
+        };
+        Ok(match &self.path {
+            Some(path) => KeyLocation {
+                owner: self.user.clone(),
+                gid,
+                directory: None,
+                path: path.clone(),
+            },
+            None => KeyLocation {
+                owner: self.user.clone(),
+                gid,
+                directory: Some(format!("{}/.ssh", home)),
+                path: format!("{}/.ssh/authorized_keys", home),
+            },
+        })
+    }
+
+    // keys from key/keys, then those fetched from the url and the file, with the
+    // task's options (when given) replacing whatever options they came with
+
+    fn get_keys(
+        &self,
+        handle: &Arc<TaskHandle>,
+        request: &Arc<TaskRequest>,
+    ) -> Result<Vec<AuthorizedKey>, Arc<TaskResponse>> {
+        let mut keys = self.keys.clone();
+        if let Some(url) = &self.url {
+            let result = handle.remote.run_unsafe(
+                request,
+                &format!("curl -fsSL {}", shell_quote(url)),
+                CheckRc::Checked,
+            )?;
+            let (_rc, out) = cmd_info(&result);
+            keys.extend(parse_keys(&out).map_err(|e| {
+                handle
+                    .response
+                    .is_failed(request, &format!("{}: {}", url, e))
+            })?);
+        }
+        if let Some(file) = &self.file {
+            let content = handle.local.read_file(request, file)?;
+            keys.extend(parse_keys(&content).map_err(|e| {
+                handle
+                    .response
+                    .is_failed(request, &format!("{}: {}", file.display(), e))
+            })?);
+        }
+        if keys.is_empty() && !self.remove {
+            return Err(handle
+                .response
+                .is_failed(request, "no keys were found to authorize"));
+        }
+        for key in keys.iter_mut() {
+            if self.options.is_some() {
+                key.options = self.options.clone();
+            }
+        }
+        Ok(keys)
+    }
+
+    fn get_plan(
+        &self,
+        handle: &Arc<TaskHandle>,
+        request: &Arc<TaskRequest>,
+        location: &KeyLocation,
+    ) -> Result<Option<KeyPlan>, Arc<TaskResponse>> {
+        let keys = self.get_keys(handle, request)?;
+        let current = handle.remote.read_file(request, &location.path)?;
+        Ok(plan_keys(
+            current.as_deref().unwrap_or(""),
+            &keys,
+            self.exclusive,
+            self.remove,
+        ))
+    }
+}
+
+impl AuthorizedKey {
+    fn same_key(&self, other: &AuthorizedKey) -> bool {
+        self.kind == other.kind && self.blob == other.blob
+    }
+
+    fn render(&self) -> String {
+        let mut line = String::new();
+        if let Some(options) = &self.options {
+            line.push_str(options);
+            line.push(' ');
+        }
+        line.push_str(&format!("{} {}", self.kind, self.blob));
+        if let Some(comment) = &self.comment {
+            line.push(' ');
+            line.push_str(comment);
+        }
+        line
+    }
+
+    // enough to tell keys apart in output without printing all of them
+    fn describe(&self) -> String {
+        let start = self.blob.len().saturating_sub(8);
+        let mut out = format!("{} ...{}", self.kind, &self.blob[start..]);
+        if let Some(comment) = &self.comment {
+            out.push(' ');
+            out.push_str(comment);
+        }
+        out
+    }
+}
+
+fn is_key_type(token: &str) -> bool {
+    ["ssh-", "ecdsa-sha2-", "sk-ssh-", "sk-ecdsa-sha2-"]
+        .iter()
+        .any(|prefix| token.starts_with(prefix))
+}
+
+/// Parse one authorized_keys line: `[options] type base64 [comment]`, where options
+/// is a comma separated list that may hold quoted spaces, as in `command="a b"`.
+fn parse_key(line: &str) -> Option<AuthorizedKey> {
+    let line = line.trim();
+    let first = line.split_whitespace().next()?;
+    let (options, rest) = match is_key_type(first) {
+        true => (None, line),
+        false => {
+            let mut quoted = false;
+            let mut escaped = false;
+            let mut end = line.len();
+            for (i, c) in line.char_indices() {
+                match c {
+                    _ if escaped => escaped = false,
+                    '\\' => escaped = true,
+                    '"' => quoted = !quoted,
+                    c if c.is_whitespace() && !quoted => {
+                        end = i;
+                        break;
+                    }
+                    _ => {}
+                }
+            }
+            (Some(line[..end].to_string()), line[end..].trim_start())
+        }
+    };
+    let mut parts = rest.splitn(3, char::is_whitespace);
+    let kind = parts.next()?;
+    let blob = parts.next()?;
+    if !is_key_type(kind)
+        || !blob
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '+' | '/' | '='))
+    {
+        return None;
+    }
+    Some(AuthorizedKey {
+        options,
+        kind: kind.to_string(),
+        blob: blob.to_string(),
+        comment: parts
+            .next()
+            .map(|x| x.trim().to_string())
+            .filter(|x| !x.is_empty()),
+    })
+}
+
+/// Parse every key in some authorized_keys text, skipping blank lines and comments.
+fn parse_keys(text: &str) -> Result<Vec<AuthorizedKey>, String> {
+    let mut keys: Vec<AuthorizedKey> = Vec::new();
+    for line in text.lines().map(|x| x.trim()) {
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        match parse_key(line) {
+            Some(key) => keys.push(key),
+            None => return Err(format!("not an SSH public key: {}", line)),
+        }
+    }
+    Ok(keys)
+}
+
+/// Work out the new authorized_keys content. Keys already present are updated in
+/// place, new ones are appended, and in exclusive mode every other key goes.
+/// Comments and blank lines are kept. None when nothing changes.
+fn plan_keys(
+    current: &str,
+    keys: &[AuthorizedKey],
+    exclusive: bool,
+    remove: bool,
+) -> Option<KeyPlan> {
+    let mut lines: Vec<String> = Vec::new();
+    let mut summary: Vec<String> = Vec::new();
+    let mut seen: Vec<&AuthorizedKey> = Vec::new();
+    for line in current.lines() {
+        let parsed = match line.trim() {
+            x if x.is_empty() || x.starts_with('#') => {
+                lines.push(line.to_string());
+                continue;
+            }
+            x => parse_key(x),
+        };
+        let wanted = parsed
+            .as_ref()
+            .and_then(|e| keys.iter().find(|k| k.same_key(e)));
+        match (parsed, wanted) {
+            (Some(existing), Some(_)) if remove => {
+                summary.push(format!("- {}", existing.describe()));
+            }
+            // a key listed twice in the file is only kept once
+            (Some(existing), Some(wanted)) if seen.iter().any(|k| k.same_key(wanted)) => {
+                summary.push(format!("- {} (duplicate)", existing.describe()));
+            }
+            (Some(existing), Some(wanted)) => {
+                seen.push(wanted);
+                if existing.options != wanted.options || existing.comment != wanted.comment {
+                    summary.push(format!("~ {}", wanted.describe()));
+                    lines.push(wanted.render());
+                } else {
+                    lines.push(line.to_string());
+                }
+            }
+            (Some(existing), None) if exclusive => {
+                summary.push(format!("- {}", existing.describe()));
+            }
+            // lines that do not parse as keys are left alone, unless the file is to
+            // hold nothing but the listed keys
+            (None, _) if exclusive => {
+                summary.push(format!("- {}", line.trim()));
+            }
+            _ => lines.push(line.to_string()),
+        }
+    }
+    if !remove {
+        for key in keys.iter() {
+            if !seen.iter().any(|k| k.same_key(key)) {
+                seen.push(key);
+                summary.push(format!("+ {}", key.describe()));
+                lines.push(key.render());
+            }
+        }
+    }
+    if summary.is_empty() {
+        return None;
+    }
+    let mut content = lines.join("\n");
+    if !content.is_empty() {
+        content.push('\n');
+    }
+    Some(KeyPlan { content, summary })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ALICE: &str = "ssh-ed25519 AAAAC3NzaC1lZDI1NTE5AAAAIGaliceaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa alice@laptop";
+    const BOB: &str = "ssh-rsa AAAAB3NzaC1yc2EAAAADAQABAAABAQCbobbbbbbbbbbbbbbbbbbbbbbbbbb bob";
+
+    #[test]
+    fn parses_options_with_quoted_spaces() {
+        let key = parse_key(&format!(
+            "from=\"10.0.0.0/8\",command=\"/usr/bin/backup --now\" {}",
+            ALICE
+        ))
+        .unwrap();
+        assert_eq!(
+            key.options.as_deref(),
+            Some("from=\"10.0.0.0/8\",command=\"/usr/bin/backup --now\"")
+        );
+        assert_eq!(key.kind, "ssh-ed25519");
+        assert_eq!(key.comment.as_deref(), Some("alice@laptop"));
+        assert_eq!(parse_key(ALICE).unwrap().options, None);
+        assert!(parse_key("not a key").is_none());
+        assert!(parse_keys("# comment\n\nssh-ed25519\n").is_err());
+    }
+
+    #[test]
+    fn adds_updates_and_keeps_keys() {
+        let alice = vec![parse_key(ALICE).unwrap()];
+        let mut restricted = alice.clone();
+        restricted[0].options = Some(String::from("from=\"10.0.0.1\""));
+        let current = format!("# managed by hand\n{}\n", BOB);
+
+        let plan = plan_keys(&current, &alice, false, false).unwrap();
+        assert_eq!(
+            plan.content,
+            format!("# managed by hand\n{}\n{}\n", BOB, ALICE)
+        );
+        assert_eq!(plan.summary.len(), 1);
+        assert!(plan.summary[0].starts_with("+ ssh-ed25519"));
+
+        // already there: nothing to do, different options: rewritten in place
+        assert!(plan_keys(&plan.content, &alice, false, false).is_none());
+        let plan = plan_keys(&plan.content, &restricted, false, false).unwrap();
+        assert_eq!(
+            plan.content,
+            format!("# managed by hand\n{}\nfrom=\"10.0.0.1\" {}\n", BOB, ALICE)
+        );
+        assert!(plan.summary[0].starts_with("~ "));
+    }
+
+    #[test]
+    fn exclusive_and_remove_drop_keys() {
+        let alice = vec![parse_key(ALICE).unwrap()];
+        let current = format!("{}\n{}\n", BOB, ALICE);
+
+        let plan = plan_keys(&current, &alice, true, false).unwrap();
+        assert_eq!(plan.content, format!("{}\n", ALICE));
+        assert_eq!(plan.summary, vec!["- ssh-rsa ...bbbbbbbb bob"]);
+
+        let plan = plan_keys(&current, &alice, false, true).unwrap();
+        assert_eq!(plan.content, format!("{}\n", BOB));
+        assert!(plan_keys(&plan.content, &alice, false, true).is_none());
+    }
+}
@@ -31,11 +31,11 @@ const MODULE: &str = "group";
 pub struct GroupTask {
     pub name: Option<String>,
     pub group: String,
-    pub gid: Option<String>,
+    pub gid: Option<Templated<u64>>,
     pub users: Option<HashSet<String>>,
-    pub append: Option<String>,
-    pub system: Option<String>,
-    pub remove: Option<String>,
+    pub append: Option<Templated<bool>>,
+    pub system: Option<Templated<bool>>,
+    pub remove: Option<Templated<bool>>,
     pub with: Option<PreLogicInput>,
     pub and: Option<PostLogicInput>,
 }
@@ -15,5 +15,6 @@
 // long with this program.  If not, see <http://www.gnu.org/licenses/>.
 
 /** ADD MODULES HERE, KEEP ALPHABETIZED **/
+pub mod authorized_key;
 pub mod group;
 pub mod user;
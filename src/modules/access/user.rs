@@ -16,8 +16,12 @@
 
 use crate::handle::handle::TaskHandle;
 use crate::inventory::hosts::HostOSType;
+use crate::tasks::checksum::Algorithm;
+use crate::tasks::cmd_library::shell_quote;
 use crate::tasks::fields::Field;
 use crate::tasks::*;
+use crate::util::redact::register_secret;
+use chrono::NaiveDate;
 use serde::Deserialize;
 use std::collections::HashSet;
 use std::sync::Arc;
@@ -31,17 +35,26 @@ const MODULE: &str = "user";
 pub struct UserTask {
     pub name: Option<String>,
     pub user: String,
-    pub uid: Option<String>,
-    pub system: Option<String>,
+    pub uid: Option<Templated<u64>>,
+    pub system: Option<Templated<bool>>,
     pub gid: Option<String>,
     pub groups: Option<HashSet<String>>,
-    pub append: Option<String>,
-    pub create_home: Option<String>,
-    pub create_user_group: Option<String>,
+    pub append: Option<Templated<bool>>,
+    pub create_home: Option<Templated<bool>>,
+    pub create_user_group: Option<Templated<bool>>,
     pub gecos: Option<String>,
     pub shell: Option<String>,
-    pub remove: Option<String>,
-    pub cleanup: Option<String>,
+    /// Password hash as it appears in /etc/shadow, e.g. from `openssl passwd -6`
+    pub password: Option<String>,
+    pub locked: Option<Templated<bool>>,
+    /// Account expiry date as YYYY-MM-DD, or "never"
+    pub expires: Option<String>,
+    pub password_min_days: Option<Templated<u64>>,
+    pub password_max_days: Option<Templated<u64>>,
+    pub password_warn_days: Option<Templated<u64>>,
+    pub password_inactive_days: Option<Templated<u64>>,
+    pub remove: Option<Templated<bool>>,
+    pub cleanup: Option<Templated<bool>>,
     pub with: Option<PreLogicInput>,
     pub and: Option<PostLogicInput>,
 }
@@ -57,6 +70,14 @@ struct UserAction {
     pub create_user_group: bool,
     pub gecos: Option<String>,
     pub shell: Option<String>,
+    pub password: Option<String>,
+    pub locked: Option<bool>,
+    // days since the epoch, Some(None) clears the expiry
+    pub expires: Option<Option<u64>>,
+    pub min_days: Option<u64>,
+    pub max_days: Option<u64>,
+    pub warn_days: Option<u64>,
+    pub inactive_days: Option<u64>,
     pub remove: bool,
     pub cleanup: bool,
 }
@@ -70,6 +91,16 @@ struct UserDetails {
     shell: Option<String>,
 }
 
+// the /etc/shadow entry, empty fields are None
+struct ShadowDetails {
+    password: String,
+    min_days: Option<u64>,
+    max_days: Option<u64>,
+    warn_days: Option<u64>,
+    inactive_days: Option<u64>,
+    expires: Option<u64>,
+}
+
 impl IsTask for UserTask {
     fn get_module(&self) -> String {
         String::from(MODULE)
@@ -87,6 +118,35 @@ impl IsTask for UserTask {
         request: &Arc<TaskRequest>,
         tm: TemplateMode,
     ) -> Result<EvaluatedTask, Arc<TaskResponse>> {
+        // hashes are full of $ and / so they are never passed through the shell unquoted
+        let password = handle.template.string_option_unsafe_for_shell(
+            request,
+            tm,
+            &String::from("password"),
+            &self.password,
+        )?;
+        let expires = handle.template.string_option_no_spaces(
+            request,
+            tm,
+            &String::from("expires"),
+            &self.expires,
+        )?;
+        let mut expires_days: Option<Option<u64>> = None;
+        if tm == TemplateMode::Strict {
+            if let Some(password) = &password
+                && (password.contains(':') || password.contains('\n'))
+            {
+                return Err(handle
+                    .response
+                    .is_failed(request, "password must be a single hash without ':'"));
+            }
+            if let Some(expires) = &expires {
+                expires_days = Some(
+                    parse_expiry(expires).map_err(|e| handle.response.is_failed(request, &e))?,
+                );
+            }
+        }
+
         Ok(EvaluatedTask {
             action: Arc::new(UserAction {
                 user: handle.template.string_no_spaces(
@@ -158,6 +218,42 @@ impl IsTask for UserTask {
                     &String::from("shell"),
                     &self.shell,
                 )?,
+                password,
+                locked: handle.template.boolean_option_default_none(
+                    request,
+                    tm,
+                    &String::from("locked"),
+                    &self.locked,
+                )?,
+                expires: expires_days,
+                min_days: handle.template.integer_option(
+                    request,
+                    tm,
+                    &String::from("password_min_days"),
+                    &self.password_min_days,
+                    None,
+                )?,
+                max_days: handle.template.integer_option(
+                    request,
+                    tm,
+                    &String::from("password_max_days"),
+                    &self.password_max_days,
+                    None,
+                )?,
+                warn_days: handle.template.integer_option(
+                    request,
+                    tm,
+                    &String::from("password_warn_days"),
+                    &self.password_warn_days,
+                    None,
+                )?,
+                inactive_days: handle.template.integer_option(
+                    request,
+                    tm,
+                    &String::from("password_inactive_days"),
+                    &self.password_inactive_days,
+                    None,
+                )?,
                 remove: handle.template.boolean_option_default_false(
                     request,
                     tm,
@@ -216,6 +312,10 @@ impl IsAction for UserAction {
                         if self.groups_wants_change(&actual) {
                             changes.push(Field::Groups);
                         }
+                        if self.wants_shadow() {
+                            let shadow = self.get_shadow_details(handle, request)?;
+                            changes.extend(self.shadow_changes(&shadow));
+                        }
 
                         match changes.len() {
                             0 => Ok(handle.response.is_matched(request)),
@@ -228,13 +328,31 @@ impl IsAction for UserAction {
             TaskRequestType::Create => {
                 let cmd = self.create_user_command();
                 handle.remote.run(request, &cmd, CheckRc::Checked)?;
+                if self.wants_shadow() {
+                    let shadow = self.get_shadow_details(handle, request)?;
+                    let changes = self.shadow_changes(&shadow);
+                    self.apply_shadow_changes(handle, request, &shadow, &changes)?;
+                }
                 Ok(handle.response.is_created(request))
             }
 
             TaskRequestType::Modify => {
-                let actual: UserDetails = self.get_user_details(handle, request)?;
-                let cmd = self.modify_user_command(&actual);
-                handle.remote.run(request, &cmd, CheckRc::Checked)?;
+                let passwd_changes = [
+                    Field::Uid,
+                    Field::Gid,
+                    Field::Gecos,
+                    Field::Shell,
+                    Field::Groups,
+                ];
+                if request.changes.iter().any(|c| passwd_changes.contains(c)) {
+                    let actual: UserDetails = self.get_user_details(handle, request)?;
+                    let cmd = self.modify_user_command(&actual);
+                    handle.remote.run(request, &cmd, CheckRc::Checked)?;
+                }
+                if self.wants_shadow() {
+                    let shadow = self.get_shadow_details(handle, request)?;
+                    self.apply_shadow_changes(handle, request, &shadow, &request.changes)?;
+                }
                 Ok(handle
                     .response
                     .is_modified(request, request.changes.clone()))
@@ -316,6 +434,150 @@ impl UserAction {
         }
     }
 
+    fn wants_shadow(&self) -> bool {
+        self.password.is_some()
+            || self.locked.is_some()
+            || self.expires.is_some()
+            || self.min_days.is_some()
+            || self.max_days.is_some()
+            || self.warn_days.is_some()
+            || self.inactive_days.is_some()
+    }
+
+    fn get_shadow_details(
+        &self,
+        handle: &Arc<TaskHandle>,
+        request: &Arc<TaskRequest>,
+    ) -> Result<ShadowDetails, Arc<TaskResponse>> {
+        if handle.remote.get_os_type() != HostOSType::Linux {
+            return Err(handle.response.is_failed(
+                request,
+                &String::from(
+                    "password, locked, expires and the *_days settings need /etc/shadow, which this host does not have",
+                ),
+            ));
+        }
+        // /etc/shadow is only readable by root, so the whole script runs through sudo.
+        // Command output is echoed at -vvv, so it never carries the hash itself
+        let cmd = format!("sh -c {}", shell_quote(&shadow_script(&self.user)));
+        let result = handle.remote.run_unsafe(request, &cmd, CheckRc::Checked)?;
+        let (_, out) = cmd_info(&result);
+        match parse_shadow_entry(&out) {
+            Some(shadow) => Ok(shadow),
+            None => Err(handle.response.is_failed(
+                request,
+                &format!("could not parse the shadow entry of '{}'", self.user),
+            )),
+        }
+    }
+
+    fn shadow_changes(&self, actual: &ShadowDetails) -> Vec<Field> {
+        let mut changes: Vec<Field> = Vec::new();
+        // a lock is a '!' in front of the hash, so the hash is compared without it
+        let actual_locked = actual.password.starts_with('!');
+        if let Some(password) = &self.password
+            && hash_digest(unlocked(password)) != unlocked(&actual.password)
+        {
+            changes.push(Field::Password);
+        }
+        // a '!' or '!!' with no hash behind it only means there is no password yet,
+        // usermod -U refuses to unlock that unless a password is set along with it
+        let lockable = has_hash(&actual.password) || self.password.is_some();
+        if let Some(locked) = self.locked
+            && locked != actual_locked
+            && (locked || lockable)
+        {
+            changes.push(Field::Locked);
+        }
+        if let Some(expires) = &self.expires
+            && *expires != actual.expires
+        {
+            changes.push(Field::Expires);
+        }
+        if UserAction::u64_wants_change(&self.min_days, &actual.min_days) {
+            changes.push(Field::MinDays);
+        }
+        if UserAction::u64_wants_change(&self.max_days, &actual.max_days) {
+            changes.push(Field::MaxDays);
+        }
+        if UserAction::u64_wants_change(&self.warn_days, &actual.warn_days) {
+            changes.push(Field::WarnDays);
+        }
+        if UserAction::u64_wants_change(&self.inactive_days, &actual.inactive_days) {
+            changes.push(Field::InactiveDays);
+        }
+        changes
+    }
+
+    fn apply_shadow_changes(
+        &self,
+        handle: &Arc<TaskHandle>,
+        request: &Arc<TaskRequest>,
+        actual: &ShadowDetails,
+        changes: &[Field],
+    ) -> Result<(), Arc<TaskResponse>> {
+        if let Some(cmd) = self.set_password_command(actual, changes) {
+            // the command line is echoed by -v and in failures, the hash must not be
+            if let Some(password) = &self.password {
+                register_secret(unlocked(password));
+            }
+            handle.remote.run_unsafe(request, &cmd, CheckRc::Checked)?;
+        } else if changes.contains(&Field::Locked) {
+            let flag = match self.locked {
+                Some(true) => "-L",
+                _ => "-U",
+            };
+            let cmd = format!("usermod {} '{}'", flag, self.user);
+            handle.remote.run(request, &cmd, CheckRc::Checked)?;
+        }
+        if let Some(cmd) = self.chage_command(changes) {
+            handle.remote.run(request, &cmd, CheckRc::Checked)?;
+        }
+        Ok(())
+    }
+
+    fn set_password_command(&self, actual: &ShadowDetails, changes: &[Field]) -> Option<String> {
+        if !changes.contains(&Field::Password) {
+            return None;
+        }
+        let password = self.password.as_ref()?;
+        // writing the hash would drop an existing lock, so the wanted lock state is
+        // written along with it
+        let locked = self
+            .locked
+            .unwrap_or_else(|| actual.password.starts_with('!') && has_hash(&actual.password));
+        let hash = match locked {
+            true => format!("!{}", unlocked(password)),
+            false => unlocked(password).to_string(),
+        };
+        Some(format!("usermod -p {} '{}'", shell_quote(&hash), self.user))
+    }
+
+    fn chage_command(&self, changes: &[Field]) -> Option<String> {
+        let mut cmd = String::from("chage");
+        let days = [
+            (Field::MinDays, "-m", self.min_days),
+            (Field::MaxDays, "-M", self.max_days),
+            (Field::WarnDays, "-W", self.warn_days),
+            (Field::InactiveDays, "-I", self.inactive_days),
+        ];
+        for (field, flag, value) in days {
+            if let (true, Some(value)) = (changes.contains(&field), value) {
+                cmd.push_str(&format!(" {} {}", flag, value));
+            }
+        }
+        if let (true, Some(expires)) = (changes.contains(&Field::Expires), &self.expires) {
+            match expires {
+                Some(day) => cmd.push_str(&format!(" -E {}", day)),
+                None => cmd.push_str(" -E -1"),
+            }
+        }
+        match cmd == "chage" {
+            true => None,
+            false => Some(format!("{} '{}'", cmd, self.user)),
+        }
+    }
+
     fn get_user_command(&self) -> String {
         // returns a string devided by 6 colons (':')
         // user:pwd:UID:GID:Gecos:Homedir:Shell
@@ -472,3 +734,157 @@ impl UserAction {
         }
     }
 }
+
+// prints the shadow entry with its hash replaced by "sha256-<digest of the hash>",
+// keeping any '!' lock prefix, and an empty or '*' hash as it is
+
+fn shadow_script(user: &str) -> String {
+    format!(
+        "e=$(getent shadow {}) || exit 2; h=${{e#*:}}; h=${{h%%:*}}; p=${{h%%[!!]*}}; u=${{h#\"$p\"}}; \
+         case $u in ''|'*') d=$u ;; *) d=sha256-$(printf '%s' \"$u\" | sha256sum | cut -d ' ' -f 1) ;; esac; \
+         printf '%s:%s%s:%s\\n' \"${{e%%:*}}\" \"$p\" \"$d\" \"${{e#*:*:}}\"",
+        shell_quote(user)
+    )
+}
+
+// the form a wanted hash has in the output of shadow_script
+
+fn hash_digest(hash: &str) -> String {
+    match hash {
+        "" | "*" => hash.to_string(),
+        _ => format!("sha256-{}", Algorithm::Sha256.digest(hash.as_bytes())),
+    }
+}
+
+fn unlocked(hash: &str) -> &str {
+    hash.trim_start_matches('!')
+}
+
+fn has_hash(password: &str) -> bool {
+    !matches!(unlocked(password), "" | "*")
+}
+
+fn parse_shadow_entry(line: &str) -> Option<ShadowDetails> {
+    // user:hash:last change:min:max:warn:inactive:expire:reserved
+    let items: Vec<&str> = line.trim_end().split(':').collect();
+    if items.len() < 8 {
+        return None;
+    }
+    let days = |item: &str| item.parse::<u64>().ok();
+    Some(ShadowDetails {
+        password: items[1].to_string(),
+        min_days: days(items[3]),
+        max_days: days(items[4]),
+        warn_days: days(items[5]),
+        inactive_days: days(items[6]),
+        expires: days(items[7]),
+    })
+}
+
+fn parse_expiry(expires: &str) -> Result<Option<u64>, String> {
+    if expires.is_empty() || expires == "never" {
+        return Ok(None);
+    }
+    let epoch = NaiveDate::from_ymd_opt(1970, 1, 1).unwrap();
+    match NaiveDate::parse_from_str(expires, "%Y-%m-%d") {
+        Ok(date) if date >= epoch => Ok(Some((date - epoch).num_days() as u64)),
+        _ => Err(format!(
+            "expires must be a YYYY-MM-DD date or 'never', got '{}'",
+            expires
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shadow_entry_and_expiry_are_parsed_as_days() {
+        let shadow = parse_shadow_entry("alice:!$6$salt$hash:19700:0:90:7::19783:\n").unwrap();
+        assert_eq!(shadow.password, "!$6$salt$hash");
+        assert_eq!(shadow.min_days, Some(0));
+        assert_eq!(shadow.max_days, Some(90));
+        assert_eq!(shadow.inactive_days, None);
+        assert_eq!(shadow.expires, Some(19783));
+        assert_eq!(parse_expiry("2024-03-01"), Ok(Some(19783)));
+        assert_eq!(parse_expiry("never"), Ok(None));
+        assert!(parse_expiry("01/03/2024").is_err());
+        assert!(parse_shadow_entry("alice:x").is_none());
+    }
+
+    #[test]
+    fn shadow_hashes_are_only_seen_as_digests() {
+        assert_eq!(hash_digest("*"), "*");
+        assert_eq!(hash_digest(""), "");
+        assert_eq!(
+            hash_digest("$6$old"),
+            format!("sha256-{}", Algorithm::Sha256.digest(b"$6$old"))
+        );
+        let script = shadow_script("alice");
+        assert!(script.starts_with("e=$(getent shadow 'alice') || exit 2;"));
+        assert!(script.contains("sha256sum"));
+    }
+
+    #[test]
+    fn password_keeps_lock_and_aging_goes_through_chage() {
+        let entry = format!("alice:!{}:19700:0:99999:7:::", hash_digest("$6$old"));
+        let shadow = parse_shadow_entry(&entry).unwrap();
+        let mut wanted = UserAction {
+            user: String::from("alice"),
+            uid: None,
+            system: false,
+            gid: None,
+            groups: None,
+            append: false,
+            create_home: false,
+            create_user_group: false,
+            gecos: None,
+            shell: None,
+            password: Some(String::from("$6$new")),
+            locked: None,
+            expires: Some(None),
+            min_days: None,
+            max_days: Some(90),
+            warn_days: None,
+            inactive_days: None,
+            remove: false,
+            cleanup: false,
+        };
+
+        // the account stays locked because locked was not given
+        let changes = wanted.shadow_changes(&shadow);
+        assert_eq!(changes, vec![Field::Password, Field::MaxDays]);
+        assert_eq!(
+            wanted.set_password_command(&shadow, &changes),
+            Some(String::from("usermod -p '!$6$new' 'alice'"))
+        );
+        assert_eq!(
+            wanted.chage_command(&changes),
+            Some(String::from("chage -M 90 'alice'"))
+        );
+
+        wanted.locked = Some(false);
+        wanted.password = Some(String::from("$6$old"));
+        assert_eq!(
+            wanted.shadow_changes(&shadow),
+            vec![Field::Locked, Field::MaxDays]
+        );
+        assert_eq!(wanted.chage_command(&[Field::Locked]), None);
+
+        // nothing to unlock on an account that never had a password
+        let placeholder = parse_shadow_entry("alice:!!:19700:0:99999:7:::").unwrap();
+        wanted.password = None;
+        wanted.max_days = None;
+        assert!(wanted.shadow_changes(&placeholder).is_empty());
+        wanted.locked = Some(true);
+        assert!(wanted.shadow_changes(&placeholder).is_empty());
+        wanted.locked = None;
+        wanted.password = Some(String::from("$6$first"));
+        let changes = wanted.shadow_changes(&placeholder);
+        assert_eq!(
+            wanted.set_password_command(&placeholder, &changes),
+            Some(String::from("usermod -p '$6$first' 'alice'"))
+        );
+    }
+}
@@ -33,7 +33,7 @@ pub struct CommandTask {
     pub failed_when: Option<String>,
     pub changed_when: Option<String>,
     #[serde(rename = "unsafe")]
-    pub unsafe_: Option<String>, /* FIXME: can use r#unsafe instead */
+    pub unsafe_: Option<Templated<bool>>, /* FIXME: can use r#unsafe instead */
     pub with: Option<PreLogicInput>,
     pub and: Option<PostLogicInput>,
 }
@@ -144,6 +144,7 @@ impl IsAction for ExternalAction {
                     request,
                     self.use_module.as_path(),
                     &module_str_path.clone(),
+                    &None,
                     |_f| Ok(()),
                 )?;
 
@@ -151,6 +152,7 @@ impl IsAction for ExternalAction {
                     request,
                     &self.params.clone(),
                     &param_str_path.clone(),
+                    &None,
                     |_f| {
                         // not using the after save handler for this module
                         Ok(())
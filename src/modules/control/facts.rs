@@ -27,8 +27,8 @@ const MODULE: &str = "facts";
 #[derive(Default)]
 pub struct FactsTask {
     pub name: Option<String>,
-    pub facter: Option<String>,
-    pub ohai: Option<String>,
+    pub facter: Option<Templated<bool>>,
+    pub ohai: Option<Templated<bool>>,
     pub with: Option<PreLogicInput>,
     pub and: Option<PostLogicInput>,
 }
@@ -163,6 +163,11 @@ impl FactsAction {
             &String::from("jet_os_flavor"),
             &String::from("OSX"),
         );
+        self.insert_string(
+            mapping,
+            &String::from("jet_init_system"),
+            &String::from("launchd"),
+        );
         Ok(())
     }
 
@@ -178,6 +183,7 @@ impl FactsAction {
             &String::from("Linux"),
         );
         self.do_linux_os_release(handle, request, mapping)?;
+        self.do_linux_init_system(handle, request, mapping)?;
         Ok(())
     }
 
@@ -255,6 +261,35 @@ impl FactsAction {
         Ok(())
     }
 
+    fn do_linux_init_system(
+        &self,
+        handle: &Arc<TaskHandle>,
+        request: &Arc<TaskRequest>,
+        mapping: &Arc<RwLock<serde_yaml::Mapping>>,
+    ) -> Result<(), Arc<TaskResponse>> {
+        // /run/systemd/system is what sd_booted() checks, OpenRC keeps its state in
+        // /run/openrc whatever runs as PID 1, and runit is PID 1 itself (Void)
+        let init = if handle
+            .remote
+            .get_is_directory(request, "/run/systemd/system")?
+        {
+            "systemd"
+        } else if handle.remote.get_is_directory(request, "/run/openrc")? {
+            "openrc"
+        } else {
+            let result = handle
+                .remote
+                .run(request, "cat /proc/1/comm", CheckRc::Unchecked)?;
+            let (_rc, out) = cmd_info(&result);
+            match out.trim() {
+                "runit" => "runit",
+                _ => "unknown",
+            }
+        };
+        self.insert_string(mapping, &String::from("jet_init_system"), init);
+        Ok(())
+    }
+
     fn do_arch(
         &self,
         handle: &Arc<TaskHandle>,
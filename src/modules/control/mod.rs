@@ -20,6 +20,7 @@ pub mod debug;
 pub mod echo;
 pub mod facts;
 pub mod fail;
+pub mod reboot;
 pub mod self_locate;
 pub mod set;
 pub mod wait_for_host;
@@ -0,0 +1,284 @@
+// Jetpack
+// Copyright (C) Riff Labs Limited <team@riff.cc>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// at your option) any later version.
+
+//! Reboot a host and wait for it to come back
+//!
+//! The reboot is only considered done once the host answers over SSH with a
+//! different boot ID, so a host that is slow to go down is never mistaken for one
+//! that has already come back. The cached connection is replaced along the way and
+//! later tasks in the play run over the new one.
+
+use crate::handle::handle::{CheckRc, TaskHandle};
+use crate::inventory::hosts::HostOSType;
+use crate::tasks::cmd_library::shell_quote;
+use crate::tasks::*;
+use serde::Deserialize;
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+const MODULE: &str = "reboot";
+
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+#[derive(Default)]
+pub struct RebootTask {
+    pub name: Option<String>,
+    /// Command that reboots the host (default: shutdown -r now)
+    pub command: Option<String>,
+    /// Seconds to wait for the host to go down once the reboot is issued (default: 120)
+    pub shutdown_timeout: Option<Templated<u64>>,
+    /// Seconds to wait for the host to come back, counted from the reboot (default: 600)
+    pub timeout: Option<Templated<u64>>,
+    /// Delay between checks in seconds (default: 5)
+    pub delay: Option<Templated<u64>>,
+    pub with: Option<PreLogicInput>,
+    pub and: Option<PostLogicInput>,
+}
+
+struct RebootAction {
+    pub command: String,
+    pub shutdown_timeout: u64,
+    pub timeout: u64,
+    pub delay: u64,
+}
+
+impl IsTask for RebootTask {
+    fn get_module(&self) -> String {
+        String::from(MODULE)
+    }
+    fn get_name(&self) -> Option<String> {
+        self.name.clone()
+    }
+    fn get_with(&self) -> Option<PreLogicInput> {
+        self.with.clone()
+    }
+
+    fn evaluate(
+        &self,
+        handle: &Arc<TaskHandle>,
+        request: &Arc<TaskRequest>,
+        tm: TemplateMode,
+    ) -> Result<EvaluatedTask, Arc<TaskResponse>> {
+        let command = handle
+            .template
+            .string_option_unsafe_for_shell(request, tm, "command", &self.command)?
+            .unwrap_or_else(|| String::from("shutdown -r now"));
+        let shutdown_timeout = handle.template.integer_option_to_integer(
+            request,
+            tm,
+            &String::from("shutdown_timeout"),
+            &self.shutdown_timeout,
+            120,
+        )?;
+        let timeout = handle.template.integer_option_to_integer(
+            request,
+            tm,
+            &String::from("timeout"),
+            &self.timeout,
+            600,
+        )?;
+        let delay = handle.template.integer_option_to_integer(
+            request,
+            tm,
+            &String::from("delay"),
+            &self.delay,
+            5,
+        )?;
+
+        Ok(EvaluatedTask {
+            action: Arc::new(RebootAction {
+                command,
+                shutdown_timeout,
+                timeout,
+                delay,
+            }),
+            with: Arc::new(PreLogicInput::template(handle, request, tm, &self.with)?),
+            and: Arc::new(PostLogicInput::template(handle, request, tm, &self.and)?),
+        })
+    }
+}
+
+impl IsAction for RebootAction {
+    fn dispatch(
+        &self,
+        handle: &Arc<TaskHandle>,
+        request: &Arc<TaskRequest>,
+    ) -> Result<Arc<TaskResponse>, Arc<TaskResponse>> {
+        match request.request_type {
+            TaskRequestType::Query => Ok(handle.response.needs_execution(request)),
+
+            TaskRequestType::Execute => {
+                if is_local(handle) {
+                    return Err(handle.response.is_failed(
+                        request,
+                        "refusing to reboot the machine jetpack is running on",
+                    ));
+                }
+                let before = read_boot_id(handle, request)?;
+
+                // the command is detached so it outlives this SSH session, which would
+                // otherwise be torn down underneath it
+                handle
+                    .remote
+                    .run_unsafe(request, &detached(&self.command), CheckRc::Checked)?;
+                forget_connection(handle);
+
+                let start = Instant::now();
+                let delay = Duration::from_secs(self.delay);
+                let mut went_down = false;
+                loop {
+                    thread::sleep(delay);
+                    match poll_boot_id(handle, request) {
+                        Some(after) if after != before => {
+                            return Ok(handle.response.is_executed(request));
+                        }
+                        // still the old boot, this session will die with it
+                        Some(_) => {
+                            forget_connection(handle);
+                            if !went_down
+                                && start.elapsed() >= Duration::from_secs(self.shutdown_timeout)
+                            {
+                                return Err(handle.response.is_failed(
+                                    request,
+                                    &format!(
+                                        "host did not go down within {} seconds of the reboot",
+                                        self.shutdown_timeout
+                                    ),
+                                ));
+                            }
+                        }
+                        None => went_down = true,
+                    }
+                    if start.elapsed() >= Duration::from_secs(self.timeout) {
+                        return Err(handle.response.is_failed(
+                            request,
+                            &format!(
+                                "host did not come back within {} seconds of the reboot",
+                                self.timeout
+                            ),
+                        ));
+                    }
+                }
+            }
+
+            _ => Err(handle.response.not_supported(request)),
+        }
+    }
+}
+
+// local mode, or an inventory entry that points at localhost, hands back the one
+// shared local connection instead of an SSH session
+
+fn is_local(handle: &Arc<TaskHandle>) -> bool {
+    let run_state = &handle.run_state;
+    let factory = run_state.connection_factory.read().unwrap();
+    match (
+        factory.get_connection(&run_state.context, &handle.host),
+        factory.get_local_connection(&run_state.context),
+    ) {
+        (Ok(conn), Ok(local)) => Arc::ptr_eq(&conn, &local),
+        _ => false,
+    }
+}
+
+fn forget_connection(handle: &Arc<TaskHandle>) {
+    handle
+        .run_state
+        .context
+        .read()
+        .unwrap()
+        .connection_cache
+        .write()
+        .unwrap()
+        .remove_connection(&handle.host);
+}
+
+fn read_boot_id(
+    handle: &Arc<TaskHandle>,
+    request: &Arc<TaskRequest>,
+) -> Result<String, Arc<TaskResponse>> {
+    let cmd = match handle.remote.get_os_type() {
+        HostOSType::Linux => "cat /proc/sys/kernel/random/boot_id",
+        HostOSType::MacOS => "sysctl -n kern.boottime",
+    };
+    let result = handle.remote.run(request, cmd, CheckRc::Checked)?;
+    let (_, out) = cmd_info(&result);
+    match parse_boot_id(&out) {
+        Some(id) => Ok(id),
+        None => Err(handle
+            .response
+            .is_failed(request, "could not read the boot ID of the host")),
+    }
+}
+
+// opens a fresh connection, which the factory caches for the tasks that follow.
+// None means the host could not be reached or did not answer yet.
+
+fn poll_boot_id(handle: &Arc<TaskHandle>, request: &Arc<TaskRequest>) -> Option<String> {
+    let connection = handle
+        .run_state
+        .connection_factory
+        .read()
+        .unwrap()
+        .get_connection(&handle.run_state.context, &handle.host)
+        .ok()?;
+    let fresh = Arc::new(TaskHandle::new(
+        Arc::clone(&handle.run_state),
+        connection,
+        Arc::clone(&handle.host),
+    ));
+    match read_boot_id(&fresh, request) {
+        Ok(id) => Some(id),
+        Err(_) => {
+            forget_connection(handle);
+            None
+        }
+    }
+}
+
+fn parse_boot_id(out: &str) -> Option<String> {
+    let id = out.trim();
+    match id.is_empty() {
+        true => None,
+        false => Some(id.to_string()),
+    }
+}
+
+fn detached(command: &str) -> String {
+    format!(
+        "nohup sh -c {} >/dev/null 2>&1 &",
+        shell_quote(&format!("sleep 2; {}", command))
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reboot_command_is_delayed_and_detached() {
+        assert_eq!(
+            detached("shutdown -r now"),
+            "nohup sh -c 'sleep 2; shutdown -r now' >/dev/null 2>&1 &"
+        );
+        assert_eq!(
+            detached("shutdown -r now 'patching'"),
+            "nohup sh -c 'sleep 2; shutdown -r now '\"'\"'patching'\"'\"'' >/dev/null 2>&1 &"
+        );
+    }
+
+    #[test]
+    fn boot_id_ignores_surrounding_whitespace() {
+        assert_eq!(
+            parse_boot_id("8c1d2f43-6a0e-4c1e-9d1b-2b5b0f6f7a10\n"),
+            Some(String::from("8c1d2f43-6a0e-4c1e-9d1b-2b5b0f6f7a10"))
+        );
+        assert_eq!(parse_boot_id(" \n"), None);
+    }
+}
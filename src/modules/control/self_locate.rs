@@ -16,6 +16,7 @@
 use crate::connection::command::cmd_info;
 use crate::handle::handle::TaskHandle;
 use crate::inventory::dependencies::{DependencyBuilder, VirtualizationType};
+use crate::tasks::cmd_library::shell_quote;
 use crate::tasks::*;
 use serde::Deserialize;
 use std::sync::Arc;
@@ -198,7 +199,7 @@ echo "WORKLOAD_ID=$WORKLOAD_ID"
 "#;
 
         // Run detection using shell
-        let shell_cmd = format!("/bin/sh -c '{}'", detect_script.replace("'", "'\"'\"'"));
+        let shell_cmd = format!("/bin/sh -c {}", shell_quote(detect_script));
         let task_result = handle
             .remote
             .run_unsafe(request, &shell_cmd, CheckRc::Unchecked)?;
@@ -25,9 +25,9 @@ const MODULE: &str = "wait_for_host";
 pub struct WaitForHostTask {
     pub name: Option<String>,
     /// Timeout in seconds (default: 300)
-    pub timeout: Option<String>,
+    pub timeout: Option<Templated<u64>>,
     /// Delay between retries in seconds (default: 5)
-    pub delay: Option<String>,
+    pub delay: Option<Templated<u64>>,
     pub with: Option<PreLogicInput>,
     pub and: Option<PostLogicInput>,
 }
@@ -54,22 +54,20 @@ impl IsTask for WaitForHostTask {
         request: &Arc<TaskRequest>,
         tm: TemplateMode,
     ) -> Result<EvaluatedTask, Arc<TaskResponse>> {
-        let timeout = match &self.timeout {
-            Some(t) => handle
-                .template
-                .string_no_spaces(request, tm, &String::from("timeout"), t)?
-                .parse::<u64>()
-                .unwrap_or(300),
-            None => 300,
-        };
-        let delay = match &self.delay {
-            Some(d) => handle
-                .template
-                .string_no_spaces(request, tm, &String::from("delay"), d)?
-                .parse::<u64>()
-                .unwrap_or(5),
-            None => 5,
-        };
+        let timeout = handle.template.integer_option_to_integer(
+            request,
+            tm,
+            &String::from("timeout"),
+            &self.timeout,
+            300,
+        )?;
+        let delay = handle.template.integer_option_to_integer(
+            request,
+            tm,
+            &String::from("delay"),
+            &self.delay,
+            5,
+        )?;
 
         Ok(EvaluatedTask {
             action: Arc::new(WaitForHostAction { timeout, delay }),
@@ -36,9 +36,9 @@ pub struct WaitForHttpTask {
     /// HTTP(S) URL to poll until healthy.
     pub url: String,
     /// Timeout in seconds (default 300).
-    pub timeout: Option<String>,
+    pub timeout: Option<Templated<u64>>,
     /// Delay between attempts in seconds (default 5).
-    pub delay: Option<String>,
+    pub delay: Option<Templated<u64>>,
     /// Expected status code (e.g. "200"). Default: any 2xx.
     pub expected: Option<String>,
     /// Verify the TLS certificate (default true; set false for self-signed).
@@ -54,14 +54,13 @@ fn default_verify_tls() -> bool {
 
 struct WaitForHttpAction {
     url: String,
-    // Numeric fields are kept as their templated *strings* and parsed in
-    // dispatch(), NOT in evaluate(). evaluate() runs once in TemplateMode::Off
-    // before the task's skip-condition is considered; in Off mode the templar
-    // substitutes the literal "empty" for every value (see templar.rs), so
-    // parsing here would abort the task before its `with.condition` could skip
-    // it. Parsing under Strict in dispatch() sees the real rendered values.
-    timeout: Option<String>,
-    delay: Option<String>,
+    timeout: u64,
+    delay: u64,
+    // kept as the templated string and parsed in dispatch(), NOT in evaluate().
+    // evaluate() runs once in TemplateMode::Off before the task's skip-condition
+    // is considered; in Off mode the templar substitutes the literal "empty" for
+    // every value (see templar.rs), so parsing here would abort the task before
+    // its `with.condition` could skip it.
     expected: Option<String>,
     verify_tls: bool,
 }
@@ -89,17 +88,19 @@ impl IsTask for WaitForHttpTask {
         Ok(EvaluatedTask {
             action: Arc::new(WaitForHttpAction {
                 url,
-                timeout: handle.template.string_option(
+                timeout: handle.template.integer_option_to_integer(
                     request,
                     tm,
                     &String::from("timeout"),
                     &self.timeout,
+                    300,
                 )?,
-                delay: handle.template.string_option(
+                delay: handle.template.integer_option_to_integer(
                     request,
                     tm,
                     &String::from("delay"),
                     &self.delay,
+                    5,
                 )?,
                 expected: handle.template.string_option(
                     request,
@@ -115,18 +116,6 @@ impl IsTask for WaitForHttpTask {
     }
 }
 
-/// Parse an already-templated duration field into seconds, defaulting to
-/// `default` when unset. Free function (no host/handle) so it is unit-testable.
-fn parse_secs(field: &str, value: &Option<String>, default: u64) -> Result<u64, String> {
-    match value {
-        Some(v) => v
-            .trim()
-            .parse::<u64>()
-            .map_err(|e| format!("{}: {}", field, e)),
-        None => Ok(default),
-    }
-}
-
 impl IsAction for WaitForHttpAction {
     fn dispatch(
         &self,
@@ -136,16 +125,8 @@ impl IsAction for WaitForHttpAction {
         match request.request_type {
             TaskRequestType::Query => Ok(handle.response.needs_passive(request)),
             TaskRequestType::Passive => {
-                // The action was templated under Strict in evaluate(); these are
-                // the real rendered values. Parse now, not earlier.
-                let timeout = match parse_secs("timeout", &self.timeout, 300) {
-                    Ok(v) => v,
-                    Err(e) => return Err(handle.response.is_failed(request, &e)),
-                };
-                let delay = match parse_secs("delay", &self.delay, 5) {
-                    Ok(v) => v,
-                    Err(e) => return Err(handle.response.is_failed(request, &e)),
-                };
+                // The action was templated under Strict in evaluate(); this is
+                // the real rendered value. Parse now, not earlier.
                 let expected = match &self.expected {
                     Some(code) => match code.trim().parse::<u16>() {
                         Ok(v) => Some(v),
@@ -157,7 +138,13 @@ impl IsAction for WaitForHttpAction {
                     },
                     None => None,
                 };
-                match poll_until_ready(&self.url, self.verify_tls, expected, timeout, delay) {
+                match poll_until_ready(
+                    &self.url,
+                    self.verify_tls,
+                    expected,
+                    self.timeout,
+                    self.delay,
+                ) {
                     Ok(()) => Ok(handle.response.is_passive(request)),
                     Err(e) => Err(handle.response.is_failed(request, &e)),
                 }
@@ -258,41 +245,12 @@ mod tests {
         assert!(poll_until_ready(&url, true, Some(200), 1, 0).is_err());
     }
 
-    #[test]
-    fn parse_secs_uses_default_when_unset() {
-        assert_eq!(parse_secs("timeout", &None, 300).unwrap(), 300);
-    }
-
-    #[test]
-    fn parse_secs_parses_rendered_number() {
-        // role tasks write `timeout: 240` (a YAML integer); the struct field is
-        // Option<String> and serde_yaml renders the scalar as "240".
-        assert_eq!(
-            parse_secs("timeout", &Some("240".to_string()), 300).unwrap(),
-            240
-        );
-        // tolerate surrounding whitespace from templating
-        assert_eq!(
-            parse_secs("delay", &Some(" 2 \n".to_string()), 5).unwrap(),
-            2
-        );
-    }
-
-    #[test]
-    fn parse_secs_rejects_garbage() {
-        // e.g. what TemplateMode::Off would have produced before parsing moved
-        // out of evaluate() — must surface as a clear error, never panic.
-        let err = parse_secs("timeout", &Some("empty".to_string()), 300).unwrap_err();
-        assert!(err.contains("timeout"), "{}", err);
-    }
-
     #[test]
     fn deserializes_unquoted_numeric_fields() {
-        // role tasks write bare integers for timeout/delay; the text load path
-        // (serde_yaml::from_str) must render them as parseable strings.
-        let yaml = "url: https://x\ntimeout: 240\ndelay: 2\nverify_tls: false\n";
+        // role tasks write bare integers for timeout/delay, or a template
+        let yaml = "url: https://x\ntimeout: 240\ndelay: \"{{ delay }}\"\nverify_tls: false\n";
         let task: WaitForHttpTask = serde_yaml::from_str(yaml).expect("deserialize");
-        assert_eq!(task.timeout.as_deref(), Some("240"));
-        assert_eq!(task.delay.as_deref(), Some("2"));
+        assert!(matches!(task.timeout, Some(Templated::Value(240))));
+        assert!(matches!(task.delay, Some(Templated::Template(ref t)) if t == "{{ delay }}"));
     }
 }
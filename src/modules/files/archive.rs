@@ -0,0 +1,611 @@
+// Jetporch
+// Copyright (C) 2023 - Michael DeHaan <michael@michaeldehaan.net> + contributors
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// long with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use crate::handle::handle::{CheckRc, TaskHandle};
+use crate::inventory::hosts::HostOSType;
+use crate::tasks::checksum::Algorithm;
+use crate::tasks::cmd_library::shell_quote;
+use crate::tasks::fields::Field;
+use crate::tasks::files::Recurse;
+use crate::tasks::*;
+use serde::Deserialize;
+use std::path::Path;
+use std::sync::Arc;
+use std::vec::Vec;
+
+const MODULE: &str = "archive";
+
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+#[derive(Default)]
+pub struct ArchiveTask {
+    pub name: Option<String>,
+    pub src: Vec<String>,
+    pub dest: String,
+    pub format: Option<String>,
+    pub exclude: Option<Vec<String>>,
+    pub attributes: Option<FileAttributesInput>,
+    pub with: Option<PreLogicInput>,
+    pub and: Option<PostLogicInput>,
+}
+
+struct ArchiveAction {
+    pub src: Vec<String>,
+    pub dest: String,
+    pub format: Format,
+    pub exclude: Vec<String>,
+    pub attributes: Option<FileAttributesEvaluated>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Format {
+    Tar,
+    TarGz,
+    TarBz2,
+    TarXz,
+    Zip,
+}
+
+impl Format {
+    fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "tar" => Some(Format::Tar),
+            "tar.gz" | "tgz" | "gz" => Some(Format::TarGz),
+            "tar.bz2" | "tbz2" | "bz2" => Some(Format::TarBz2),
+            "tar.xz" | "txz" | "xz" => Some(Format::TarXz),
+            "zip" => Some(Format::Zip),
+            _ => None,
+        }
+    }
+
+    fn from_path(path: &str) -> Option<Self> {
+        [
+            ".tar.gz", ".tgz", ".tar.bz2", ".tbz2", ".tar.xz", ".txz", ".tar", ".zip",
+        ]
+        .iter()
+        .find(|ext| path.ends_with(*ext))
+        .and_then(|ext| Format::from_name(&ext[1..]))
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            Format::Tar => "tar",
+            Format::TarGz => "tar.gz",
+            Format::TarBz2 => "tar.bz2",
+            Format::TarXz => "tar.xz",
+            Format::Zip => "zip",
+        }
+    }
+}
+
+impl IsTask for ArchiveTask {
+    fn get_module(&self) -> String {
+        String::from(MODULE)
+    }
+    fn get_name(&self) -> Option<String> {
+        self.name.clone()
+    }
+    fn get_with(&self) -> Option<PreLogicInput> {
+        self.with.clone()
+    }
+
+    fn evaluate(
+        &self,
+        handle: &Arc<TaskHandle>,
+        request: &Arc<TaskRequest>,
+        tm: TemplateMode,
+    ) -> Result<EvaluatedTask, Arc<TaskResponse>> {
+        let dest = handle
+            .template
+            .path(request, tm, &String::from("dest"), &self.dest)?;
+
+        let mut src: Vec<String> = Vec::new();
+        for path in self.src.iter() {
+            src.push(
+                handle
+                    .template
+                    .path(request, tm, &String::from("src"), path)?,
+            );
+        }
+
+        // the pattern list is only ever passed to tar/zip shell-quoted
+        let mut exclude: Vec<String> = Vec::new();
+        for pattern in self.exclude.clone().unwrap_or_default().iter() {
+            exclude.push(handle.template.string_unsafe_for_shell(
+                request,
+                tm,
+                &String::from("exclude"),
+                pattern,
+            )?);
+        }
+
+        let format_name = handle.template.string_option_unsafe_for_shell(
+            request,
+            tm,
+            &String::from("format"),
+            &self.format,
+        )?;
+        let format = match (&format_name, tm) {
+            (_, TemplateMode::Off) => Format::Tar,
+            (Some(name), _) => Format::from_name(name).ok_or_else(|| {
+                handle.response.is_failed(
+                    request,
+                    &format!(
+                        "field format: unsupported archive format '{}', expected tar, tar.gz, tar.bz2, tar.xz or zip",
+                        name
+                    ),
+                )
+            })?,
+            (None, _) => Format::from_path(&dest).ok_or_else(|| {
+                handle.response.is_failed(
+                    request,
+                    &format!(
+                        "cannot tell the archive format from '{}', set format or use a .tar, .tar.gz, .tar.bz2, .tar.xz or .zip extension",
+                        dest
+                    ),
+                )
+            })?,
+        };
+
+        if tm == TemplateMode::Strict {
+            if !dest.starts_with('/') {
+                return Err(handle.response.is_failed(
+                    request,
+                    &format!("dest '{}': must be an absolute path", dest),
+                ));
+            }
+            if src.is_empty() {
+                return Err(handle
+                    .response
+                    .is_failed(request, &String::from("src: at least one path is required")));
+            }
+            for path in src.iter() {
+                if split_path(path).is_none() {
+                    return Err(handle.response.is_failed(
+                        request,
+                        &format!("src '{}': must be an absolute path below /", path),
+                    ));
+                }
+            }
+        }
+
+        Ok(EvaluatedTask {
+            action: Arc::new(ArchiveAction {
+                src,
+                dest,
+                format,
+                exclude,
+                attributes: FileAttributesInput::template(handle, request, tm, &self.attributes)?,
+            }),
+            with: Arc::new(PreLogicInput::template(handle, request, tm, &self.with)?),
+            and: Arc::new(PostLogicInput::template(handle, request, tm, &self.and)?),
+        })
+    }
+}
+
+impl IsAction for ArchiveAction {
+    fn dispatch(
+        &self,
+        handle: &Arc<TaskHandle>,
+        request: &Arc<TaskRequest>,
+    ) -> Result<Arc<TaskResponse>, Arc<TaskResponse>> {
+        match request.request_type {
+            TaskRequestType::Query => {
+                for path in self.src.iter() {
+                    if handle.remote.get_mode(request, path)?.is_none() {
+                        return Err(handle
+                            .response
+                            .is_failed(request, &format!("src '{}': does not exist", path)));
+                    }
+                }
+
+                let mut changes: Vec<Field> = Vec::new();
+                let remote_mode = handle.remote.query_common_file_attributes(
+                    request,
+                    &self.dest,
+                    &self.attributes,
+                    &mut changes,
+                    Recurse::No,
+                )?;
+                if remote_mode.is_none() {
+                    return Ok(handle.response.needs_creation(request));
+                }
+
+                let recorded = handle
+                    .remote
+                    .read_file(request, &self.state_file(handle, request)?)?;
+                let current = self.content_digest(handle, request)?;
+                if recorded.as_deref().map(str::trim) != Some(current.as_str()) {
+                    changes.push(Field::Content);
+                }
+
+                match changes.is_empty() {
+                    true => Ok(handle.response.is_matched(request)),
+                    false => Ok(handle.response.needs_modification(request, &changes)),
+                }
+            }
+
+            TaskRequestType::Create => {
+                self.build_in_place(handle, request)?;
+                Ok(handle.response.is_created(request))
+            }
+
+            TaskRequestType::Modify => {
+                if request.changes.contains(&Field::Content) {
+                    self.build_in_place(handle, request)?;
+                } else {
+                    handle.remote.process_common_file_attributes(
+                        request,
+                        &self.dest,
+                        &self.attributes,
+                        &request.changes,
+                        Recurse::No,
+                    )?;
+                }
+                Ok(handle
+                    .response
+                    .is_modified(request, request.changes.clone()))
+            }
+
+            _ => Err(handle.response.not_supported(request)),
+        }
+    }
+}
+
+impl ArchiveAction {
+    // as with !download, the archive is written next to dest, given its attributes and
+    // only then renamed over the old one, so a failed run never leaves a partial archive
+
+    fn build_in_place(
+        &self,
+        handle: &Arc<TaskHandle>,
+        request: &Arc<TaskRequest>,
+    ) -> Result<(), Arc<TaskResponse>> {
+        // taken before building, so anything edited mid-build is picked up next run
+        let digest = self.content_digest(handle, request)?;
+        let temp = format!("{}.jetpack-archive", self.dest);
+        if let Err(e) = self.build(handle, request, &temp) {
+            handle.remote.delete_file(request, &temp)?;
+            return Err(e);
+        }
+        handle.remote.process_all_common_file_attributes(
+            request,
+            &temp,
+            &self.attributes,
+            Recurse::No,
+        )?;
+        handle.remote.run(
+            request,
+            &format!("mv -f {} {}", shell_quote(&temp), shell_quote(&self.dest)),
+            CheckRc::Checked,
+        )?;
+        let state = self.state_file(handle, request)?;
+        handle
+            .remote
+            .write_data(request, &digest, &state, &None, |_| Ok(()))?;
+        Ok(())
+    }
+
+    fn build(
+        &self,
+        handle: &Arc<TaskHandle>,
+        request: &Arc<TaskRequest>,
+        output: &str,
+    ) -> Result<(), Arc<TaskResponse>> {
+        // zip adds to an existing archive rather than replacing it
+        handle.remote.delete_file(request, output)?;
+        let cmd = build_command(self.format, output, &self.src, &self.exclude)
+            .map_err(|e| handle.response.is_failed(request, &e))?;
+        handle.remote.run_unsafe(request, &cmd, CheckRc::Checked)?;
+        Ok(())
+    }
+
+    // the digest of the last build is kept in the jet temp directory on the target,
+    // named after dest, so a rebuild happens exactly when the archived content or the
+    // task's src, exclude or format differ, and nothing is left beside the archive
+
+    fn state_file(
+        &self,
+        handle: &Arc<TaskHandle>,
+        request: &Arc<TaskRequest>,
+    ) -> Result<String, Arc<TaskResponse>> {
+        let (temp_dir, _) = handle.remote.get_transfer_location(request)?;
+        Ok(state_file_in(&temp_dir.unwrap_or_default(), &self.dest))
+    }
+
+    fn content_digest(
+        &self,
+        handle: &Arc<TaskHandle>,
+        request: &Arc<TaskRequest>,
+    ) -> Result<String, Arc<TaskResponse>> {
+        let cmd = manifest_command(handle.remote.get_os_type(), &self.src, &self.exclude)
+            .map_err(|e| handle.response.is_failed(request, &e))?;
+        let result = handle.remote.run_unsafe(request, &cmd, CheckRc::Checked)?;
+        let (_rc, out) = cmd_info(&result);
+        Ok(manifest_digest(self.format, &self.src, &self.exclude, &out))
+    }
+}
+
+// each source is stored under its own name, relative to its parent directory
+
+fn split_path(path: &str) -> Option<(String, String)> {
+    let trimmed = path.trim_end_matches('/');
+    let (parent, name) = trimmed.rsplit_once('/')?;
+    if name.is_empty() || name == "." || name == ".." {
+        return None;
+    }
+    let parent = match parent.is_empty() {
+        true => String::from("/"),
+        false => parent.to_string(),
+    };
+    Some((parent, name.to_string()))
+}
+
+// one line per entry that would be archived: "f <size> <sha256> <path>" for files,
+// "l <target> <path>" for symlinks and "d <path>" for directories, with paths as they
+// are stored. Bare-name excludes are matched against each component and patterns with
+// a slash against the stored path, as tar does.
+
+fn manifest_command(
+    os_type: HostOSType,
+    src: &[String],
+    exclude: &[String],
+) -> Result<String, String> {
+    let sum = match os_type {
+        HostOSType::Linux => "sha256sum",
+        HostOSType::MacOS => "shasum -a 256",
+    };
+    let script = format!(
+        "for f; do if [ -L \"$f\" ]; then printf 'l %s %s\\n' \"$(readlink \"$f\")\" \"$f\"; \
+         elif [ -d \"$f\" ]; then printf 'd %s\\n' \"$f\"; \
+         else printf 'f %s %s %s\\n' $(($(wc -c < \"$f\"))) \"$({} < \"$f\" | cut -d ' ' -f 1)\" \"$f\"; fi; done",
+        sum
+    );
+    let patterns: Vec<String> = exclude
+        .iter()
+        .map(|pattern| match pattern.contains('/') {
+            true => format!("-path {}", shell_quote(pattern)),
+            false => format!("-name {}", shell_quote(pattern)),
+        })
+        .collect();
+    let mut steps: Vec<String> = Vec::new();
+    for path in src.iter() {
+        let (parent, name) =
+            split_path(path).ok_or_else(|| format!("cannot archive '{}'", path))?;
+        let mut step = format!("cd {} && find {}", shell_quote(&parent), shell_quote(&name));
+        if !patterns.is_empty() {
+            step.push_str(&format!(" \\( {} \\) -prune -o", patterns.join(" -o ")));
+        }
+        step.push_str(&format!(" -exec sh -c {} sh {{}} +", shell_quote(&script)));
+        steps.push(step);
+    }
+    Ok(steps.join(" && "))
+}
+
+// sources keep their order, as it is the order they are stored in; the manifest is
+// sorted since find's order depends on the filesystem
+
+fn manifest_digest(format: Format, src: &[String], exclude: &[String], manifest: &str) -> String {
+    let mut exclude: Vec<&str> = exclude.iter().map(|pattern| pattern.as_str()).collect();
+    exclude.sort();
+    exclude.dedup();
+    let mut lines: Vec<&str> = manifest.lines().filter(|line| !line.is_empty()).collect();
+    lines.sort();
+
+    let mut data = format!("format {}\n", format.name());
+    for path in src.iter() {
+        data.push_str(&format!("src {}\n", path.trim_end_matches('/')));
+    }
+    for pattern in exclude.iter() {
+        data.push_str(&format!("exclude {}\n", pattern));
+    }
+    for line in lines.iter() {
+        data.push_str(line);
+        data.push('\n');
+    }
+    Algorithm::Sha256.digest(data.as_bytes())
+}
+
+// tar skips any path component matching an exclude pattern. zip matches patterns
+// against the whole stored path, so a bare name is expanded to get the same result.
+// gzip is run with -n so the compressed stream carries no timestamp and the archive
+// stays byte-identical between runs over unchanged files.
+
+fn state_file_in(temp_dir: &Path, dest: &str) -> String {
+    let key = Algorithm::Sha256.digest(dest.as_bytes());
+    temp_dir
+        .join(format!("archive-{}.sha256", key))
+        .display()
+        .to_string()
+}
+
+fn build_command(
+    format: Format,
+    output: &str,
+    src: &[String],
+    exclude: &[String],
+) -> Result<String, String> {
+    let mut sources: Vec<(String, String)> = Vec::new();
+    for path in src.iter() {
+        sources.push(split_path(path).ok_or_else(|| format!("cannot archive '{}'", path))?);
+    }
+    let output = shell_quote(output);
+
+    if format == Format::Zip {
+        let mut excludes: Vec<String> = Vec::new();
+        for pattern in exclude.iter() {
+            match pattern.contains('/') {
+                true => excludes.push(shell_quote(pattern)),
+                false => {
+                    for expanded in [
+                        pattern.clone(),
+                        format!("{}/*", pattern),
+                        format!("*/{}", pattern),
+                        format!("*/{}/*", pattern),
+                    ] {
+                        excludes.push(shell_quote(&expanded));
+                    }
+                }
+            }
+        }
+        let steps: Vec<String> = sources
+            .iter()
+            .map(|(parent, name)| {
+                let mut step = format!(
+                    "cd {} && zip -q -X -r -y {} {}",
+                    shell_quote(parent),
+                    output,
+                    shell_quote(name)
+                );
+                if !excludes.is_empty() {
+                    step.push_str(&format!(" -x {}", excludes.join(" ")));
+                }
+                step
+            })
+            .collect();
+        return Ok(steps.join(" && "));
+    }
+
+    let compression = match format {
+        Format::TarGz => " --use-compress-program='gzip -n'",
+        Format::TarBz2 => " -j",
+        Format::TarXz => " -J",
+        _ => "",
+    };
+    let mut cmd = format!("tar -c -f {}{}", output, compression);
+    for pattern in exclude.iter() {
+        cmd.push_str(&format!(" --exclude={}", shell_quote(pattern)));
+    }
+    for (parent, name) in sources.iter() {
+        cmd.push_str(&format!(
+            " -C {} {}",
+            shell_quote(parent),
+            shell_quote(name)
+        ));
+    }
+    Ok(cmd)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn format_follows_the_extension() {
+        assert_eq!(Format::from_path("/backup/etc.tar.gz"), Some(Format::TarGz));
+        assert_eq!(Format::from_path("/backup/etc.tgz"), Some(Format::TarGz));
+        assert_eq!(Format::from_path("/backup/db.tar.xz"), Some(Format::TarXz));
+        assert_eq!(Format::from_path("/backup/site.zip"), Some(Format::Zip));
+        assert_eq!(Format::from_path("/backup/etc.tar"), Some(Format::Tar));
+        assert_eq!(Format::from_path("/backup/etc.bak"), None);
+    }
+
+    #[test]
+    fn tar_command_stores_sources_by_name() {
+        let cmd = build_command(
+            Format::TarGz,
+            "/backup/etc.tar.gz",
+            &[String::from("/etc/nginx/"), String::from("/var/www/it's")],
+            &[String::from("*.log")],
+        )
+        .unwrap();
+        assert_eq!(
+            cmd,
+            "tar -c -f '/backup/etc.tar.gz' --use-compress-program='gzip -n' --exclude='*.log' -C '/etc' 'nginx' -C '/var/www' 'it'\"'\"'s'"
+        );
+        assert!(build_command(Format::Tar, "/x.tar", &[String::from("/")], &[]).is_err());
+    }
+
+    #[test]
+    fn manifest_prunes_excludes_per_source() {
+        let cmd = manifest_command(
+            HostOSType::Linux,
+            &[String::from("/srv/site"), String::from("/etc/site.conf")],
+            &[String::from(".git"), String::from("site/tmp/*")],
+        )
+        .unwrap();
+        assert!(cmd.starts_with(
+            "cd '/srv' && find 'site' \\( -name '.git' -o -path 'site/tmp/*' \\) -prune -o -exec sh -c '"
+        ));
+        assert!(cmd.contains("sha256sum < "));
+        assert!(cmd.contains(" && cd '/etc' && find 'site.conf' \\( -name"));
+        assert!(
+            manifest_command(HostOSType::MacOS, &[String::from("/etc")], &[])
+                .unwrap()
+                .contains("shasum -a 256 < ")
+        );
+    }
+
+    #[test]
+    fn digest_covers_content_and_task_settings() {
+        let src = vec![String::from("/etc/nginx")];
+        let manifest = "f 3 abc nginx/a.conf\nd nginx\n";
+        let base = manifest_digest(Format::Tar, &src, &[], manifest);
+
+        // listing order and a trailing slash on src don't matter
+        assert_eq!(
+            base,
+            manifest_digest(
+                Format::Tar,
+                &[String::from("/etc/nginx/")],
+                &[],
+                "d nginx\nf 3 abc nginx/a.conf\n"
+            )
+        );
+        assert_ne!(
+            base,
+            manifest_digest(Format::Tar, &src, &[], "f 3 abd nginx/a.conf\nd nginx\n")
+        );
+        assert_ne!(base, manifest_digest(Format::TarGz, &src, &[], manifest));
+        assert_ne!(
+            base,
+            manifest_digest(Format::Tar, &src, &[String::from("*.log")], manifest)
+        );
+        assert_ne!(
+            base,
+            manifest_digest(
+                Format::Tar,
+                &[String::from("/etc/nginx"), String::from("/etc/hosts")],
+                &[],
+                manifest
+            )
+        );
+    }
+
+    #[test]
+    fn zip_excludes_bare_names_at_any_depth() {
+        let cmd = build_command(
+            Format::Zip,
+            "/backup/site.zip",
+            &[String::from("/srv/site")],
+            &[String::from(".git"), String::from("site/tmp/*")],
+        )
+        .unwrap();
+        assert_eq!(
+            cmd,
+            "cd '/srv' && zip -q -X -r -y '/backup/site.zip' 'site' -x '.git' '.git/*' '*/.git' '*/.git/*' 'site/tmp/*'"
+        );
+    }
+
+    #[test]
+    fn the_digest_is_kept_in_the_temp_directory_per_dest() {
+        let temp = Path::new("/home/deploy/.jet/tmp");
+        let state = state_file_in(temp, "/backup/site.tar.gz");
+        assert!(state.starts_with("/home/deploy/.jet/tmp/archive-"));
+        assert!(state.ends_with(".sha256"));
+        assert_ne!(state, state_file_in(temp, "/backup/db.tar.gz"));
+    }
+}
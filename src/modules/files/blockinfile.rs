@@ -0,0 +1,328 @@
+// Jetporch
+// Copyright (C) 2023 - Michael DeHaan <michael@michaeldehaan.net> + contributors
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// long with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use crate::handle::handle::TaskHandle;
+use crate::modules::files::lineinfile::{Anchor, join_lines, line_ending, split_lines};
+use crate::tasks::fields::Field;
+use crate::tasks::files::Recurse;
+use crate::tasks::*;
+use crate::util::diff::unified;
+use serde::Deserialize;
+use std::sync::Arc;
+use std::vec::Vec;
+
+const MODULE: &str = "blockinfile";
+const DEFAULT_MARKER: &str = "# {mark} JETPACK MANAGED BLOCK";
+
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+#[derive(Default)]
+pub struct BlockInFileTask {
+    pub name: Option<String>,
+    pub path: String,
+    pub block: Option<String>,
+    pub marker: Option<String>,
+    pub insert_after: Option<String>,
+    pub insert_before: Option<String>,
+    pub remove: Option<Templated<bool>>,
+    pub create: Option<Templated<bool>>,
+    pub attributes: Option<FileAttributesInput>,
+    pub with: Option<PreLogicInput>,
+    pub and: Option<PostLogicInput>,
+}
+
+struct BlockInFileAction {
+    pub path: String,
+    pub block: Vec<String>,
+    pub begin: String,
+    pub end: String,
+    pub anchor: Anchor,
+    pub remove: bool,
+    pub create: bool,
+    pub attributes: Option<FileAttributesEvaluated>,
+}
+
+impl IsTask for BlockInFileTask {
+    fn get_module(&self) -> String {
+        String::from(MODULE)
+    }
+    fn get_name(&self) -> Option<String> {
+        self.name.clone()
+    }
+    fn get_with(&self) -> Option<PreLogicInput> {
+        self.with.clone()
+    }
+
+    fn evaluate(
+        &self,
+        handle: &Arc<TaskHandle>,
+        request: &Arc<TaskRequest>,
+        tm: TemplateMode,
+    ) -> Result<EvaluatedTask, Arc<TaskResponse>> {
+        let block = handle
+            .template
+            .string_option_unsafe_for_shell(request, tm, &String::from("block"), &self.block)?
+            .unwrap_or_default();
+        let marker = handle
+            .template
+            .string_option_unsafe_for_shell(request, tm, &String::from("marker"), &self.marker)?
+            .unwrap_or_else(|| String::from(DEFAULT_MARKER));
+        if tm == TemplateMode::Strict && !marker.contains("{mark}") {
+            return Err(handle
+                .response
+                .is_failed(request, "marker must contain {mark}"));
+        }
+
+        Ok(EvaluatedTask {
+            action: Arc::new(BlockInFileAction {
+                path: handle
+                    .template
+                    .path(request, tm, &String::from("path"), &self.path)?,
+                block: split_lines(&block),
+                begin: marker.replace("{mark}", "BEGIN"),
+                end: marker.replace("{mark}", "END"),
+                anchor: Anchor::template(
+                    handle,
+                    request,
+                    tm,
+                    &self.insert_after,
+                    &self.insert_before,
+                )?,
+                remove: handle.template.boolean_option_default_false(
+                    request,
+                    tm,
+                    &String::from("remove"),
+                    &self.remove,
+                )?,
+                create: handle.template.boolean_option_default_false(
+                    request,
+                    tm,
+                    &String::from("create"),
+                    &self.create,
+                )?,
+                attributes: FileAttributesInput::template(handle, request, tm, &self.attributes)?,
+            }),
+            with: Arc::new(PreLogicInput::template(handle, request, tm, &self.with)?),
+            and: Arc::new(PostLogicInput::template(handle, request, tm, &self.and)?),
+        })
+    }
+}
+
+impl IsAction for BlockInFileAction {
+    fn dispatch(
+        &self,
+        handle: &Arc<TaskHandle>,
+        request: &Arc<TaskRequest>,
+    ) -> Result<Arc<TaskResponse>, Arc<TaskResponse>> {
+        match request.request_type {
+            TaskRequestType::Query => {
+                let content = match handle.remote.read_file(request, &self.path)? {
+                    Some(content) => content,
+                    None if self.removing() => return Ok(handle.response.is_matched(request)),
+                    None if self.create => {
+                        let response = handle.response.needs_creation(request);
+                        let diff = match handle.response.wants_diff() {
+                            true => unified(
+                                &self.path,
+                                None,
+                                self.edit("").unwrap_or_default().as_bytes(),
+                            ),
+                            false => None,
+                        };
+                        return Ok(handle.response.with_diff(response, diff));
+                    }
+                    None => {
+                        return Err(handle.response.is_failed(
+                            request,
+                            &format!("{} does not exist (set create: true)", self.path),
+                        ));
+                    }
+                };
+                let mut changes: Vec<Field> = Vec::new();
+                handle.remote.query_common_file_attributes(
+                    request,
+                    &self.path,
+                    &self.attributes,
+                    &mut changes,
+                    Recurse::No,
+                )?;
+                let mut diff: Option<String> = None;
+                if let Some(data) = self.edit(&content) {
+                    changes.push(Field::Content);
+                    if handle.response.wants_diff() {
+                        diff = unified(&self.path, Some(content.as_bytes()), data.as_bytes());
+                    }
+                }
+                if !changes.is_empty() {
+                    let response = handle.response.needs_modification(request, &changes);
+                    return Ok(handle.response.with_diff(response, diff));
+                }
+                Ok(handle.response.is_matched(request))
+            }
+
+            TaskRequestType::Create => {
+                let data = self.edit("").unwrap_or_default();
+                handle
+                    .remote
+                    .write_data(request, &data, &self.path, &self.attributes, |f| {
+                        handle.remote.process_transferred_file_attributes(
+                            request,
+                            f,
+                            &self.attributes,
+                        )
+                    })?;
+                Ok(handle.response.is_created(request))
+            }
+
+            TaskRequestType::Modify => {
+                if request.changes.contains(&Field::Content) {
+                    let content = handle
+                        .remote
+                        .read_file(request, &self.path)?
+                        .unwrap_or_default();
+                    if let Some(data) = self.edit(&content) {
+                        handle.remote.write_data_in_place(
+                            request,
+                            &data,
+                            &self.path,
+                            &self.attributes,
+                        )?;
+                    }
+                } else {
+                    handle.remote.process_common_file_attributes(
+                        request,
+                        &self.path,
+                        &self.attributes,
+                        &request.changes,
+                        Recurse::No,
+                    )?;
+                }
+                Ok(handle
+                    .response
+                    .is_modified(request, request.changes.clone()))
+            }
+
+            _ => Err(handle.response.not_supported(request)),
+        }
+    }
+}
+
+impl BlockInFileAction {
+    // an empty block removes the markers too, the same as remove: true
+
+    fn removing(&self) -> bool {
+        self.remove || self.block.is_empty()
+    }
+
+    // the new file content, or None when the block is already as it should be
+
+    fn edit(&self, content: &str) -> Option<String> {
+        let mut lines = split_lines(content);
+        let eol = line_ending(content);
+        let begin = lines.iter().position(|l| l.trim_end() == self.begin);
+        let end = begin.and_then(|b| {
+            lines[b..]
+                .iter()
+                .position(|l| l.trim_end() == self.end)
+                .map(|e| b + e)
+        });
+
+        let mut wanted: Vec<String> = Vec::new();
+        if !self.removing() {
+            wanted.push(self.begin.clone());
+            wanted.extend(self.block.iter().cloned());
+            wanted.push(self.end.clone());
+        }
+
+        match (begin, end) {
+            (Some(b), Some(e)) => {
+                if lines[b..=e] == wanted[..] {
+                    return None;
+                }
+                lines.splice(b..=e, wanted);
+            }
+            _ if self.removing() => return None,
+            _ => {
+                let index = self.anchor.position(&lines);
+                lines.splice(index..index, wanted);
+            }
+        }
+        Some(join_lines(&lines, eol))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use regex::Regex;
+
+    const SSHD: &str = "Port 22\nMatch User backup\n  ForceCommand internal-sftp\n";
+
+    #[test]
+    fn block_is_inserted_then_updated_between_markers() {
+        let a = BlockInFileAction {
+            path: String::from("/etc/ssh/sshd_config"),
+            block: split_lines("PermitRootLogin no\nPasswordAuthentication no"),
+            begin: DEFAULT_MARKER.replace("{mark}", "BEGIN"),
+            end: DEFAULT_MARKER.replace("{mark}", "END"),
+            anchor: Anchor::Before(Regex::new("^Match").unwrap()),
+            remove: false,
+            create: false,
+            attributes: None,
+        };
+        let inserted = a.edit(SSHD).unwrap();
+        assert_eq!(
+            inserted,
+            "Port 22\n# BEGIN JETPACK MANAGED BLOCK\nPermitRootLogin no\nPasswordAuthentication no\n\
+             # END JETPACK MANAGED BLOCK\nMatch User backup\n  ForceCommand internal-sftp\n"
+        );
+        assert!(a.edit(&inserted).is_none());
+
+        let b = BlockInFileAction {
+            block: split_lines("PermitRootLogin prohibit-password"),
+            anchor: Anchor::Eof,
+            ..a
+        };
+        let updated = b.edit(&inserted).unwrap();
+        assert!(updated.contains(
+            "BLOCK\nPermitRootLogin prohibit-password\n# END JETPACK MANAGED BLOCK\nMatch"
+        ));
+    }
+
+    #[test]
+    fn remove_drops_block_and_markers() {
+        let a = BlockInFileAction {
+            path: String::from("/etc/ssh/sshd_config"),
+            block: split_lines("PermitRootLogin no"),
+            begin: DEFAULT_MARKER.replace("{mark}", "BEGIN"),
+            end: DEFAULT_MARKER.replace("{mark}", "END"),
+            anchor: Anchor::Eof,
+            remove: false,
+            create: false,
+            attributes: None,
+        };
+        let inserted = a.edit(SSHD).unwrap();
+        let r = BlockInFileAction { remove: true, ..a };
+        assert_eq!(r.edit(&inserted).unwrap(), SSHD);
+        assert!(r.edit(SSHD).is_none());
+        let empty = BlockInFileAction {
+            block: Vec::new(),
+            remove: false,
+            ..r
+        };
+        assert!(empty.edit(SSHD).is_none());
+    }
+}
@@ -0,0 +1,1411 @@
+// Jetporch
+// Copyright (C) 2023 - Michael DeHaan <michael@michaeldehaan.net> + contributors
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// long with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use crate::handle::handle::TaskHandle;
+use crate::tasks::fields::Field;
+use crate::tasks::files::Recurse;
+use crate::tasks::*;
+use crate::util::diff::unified;
+use serde::Deserialize;
+use std::sync::Arc;
+use std::vec::Vec;
+
+const MODULE: &str = "config_file";
+
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+#[derive(Default)]
+pub struct ConfigFileTask {
+    pub name: Option<String>,
+    pub path: String,
+    pub format: Option<String>,
+    pub key: String,
+    pub value: Option<serde_yaml::Value>,
+    pub remove: Option<Templated<bool>>,
+    pub create: Option<Templated<bool>>,
+    pub backup: Option<Templated<bool>>,
+    pub attributes: Option<FileAttributesInput>,
+    pub with: Option<PreLogicInput>,
+    pub and: Option<PostLogicInput>,
+}
+
+struct ConfigFileAction {
+    pub path: String,
+    pub format: Format,
+    pub key: Vec<String>,
+    pub value: Option<serde_yaml::Value>,
+    pub remove: bool,
+    pub create: bool,
+    pub backup: bool,
+    pub attributes: Option<FileAttributesEvaluated>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Format {
+    Json,
+    Yaml,
+    Toml,
+}
+
+impl Format {
+    fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "json" => Some(Format::Json),
+            "yaml" | "yml" => Some(Format::Yaml),
+            "toml" => Some(Format::Toml),
+            _ => None,
+        }
+    }
+
+    fn from_path(path: &str) -> Option<Self> {
+        path.rsplit_once('.')
+            .and_then(|(_, ext)| Format::from_name(&ext.to_lowercase()))
+    }
+}
+
+impl IsTask for ConfigFileTask {
+    fn get_module(&self) -> String {
+        String::from(MODULE)
+    }
+    fn get_name(&self) -> Option<String> {
+        self.name.clone()
+    }
+    fn get_with(&self) -> Option<PreLogicInput> {
+        self.with.clone()
+    }
+
+    fn evaluate(
+        &self,
+        handle: &Arc<TaskHandle>,
+        request: &Arc<TaskRequest>,
+        tm: TemplateMode,
+    ) -> Result<EvaluatedTask, Arc<TaskResponse>> {
+        let path = handle
+            .template
+            .path(request, tm, &String::from("path"), &self.path)?;
+        let remove = handle.template.boolean_option_default_false(
+            request,
+            tm,
+            &String::from("remove"),
+            &self.remove,
+        )?;
+
+        let format_name = handle.template.string_option_unsafe_for_shell(
+            request,
+            tm,
+            &String::from("format"),
+            &self.format,
+        )?;
+        let format = match (&format_name, tm) {
+            (_, TemplateMode::Off) => Format::Json,
+            (Some(name), _) => Format::from_name(name).ok_or_else(|| {
+                handle.response.is_failed(
+                    request,
+                    &format!(
+                        "field format: unsupported format '{}', expected json, yaml or toml",
+                        name
+                    ),
+                )
+            })?,
+            (None, _) => Format::from_path(&path).ok_or_else(|| {
+                handle.response.is_failed(
+                    request,
+                    &format!(
+                        "cannot tell the format of '{}' from its extension, set format to json, yaml or toml",
+                        path
+                    ),
+                )
+            })?,
+        };
+
+        let key_str = handle.template.string_unsafe_for_shell(
+            request,
+            tm,
+            &String::from("key"),
+            &self.key,
+        )?;
+        let key: Vec<String> = key_str.split('.').map(String::from).collect();
+        if key.iter().any(|k| k.is_empty()) {
+            return Err(handle.response.is_failed(
+                request,
+                &format!("field key: invalid key path '{}'", key_str),
+            ));
+        }
+
+        let value = match &self.value {
+            Some(v) => Some(template_value(handle, request, tm, v)?),
+            None if remove => None,
+            None => {
+                return Err(handle
+                    .response
+                    .is_failed(request, "value is required unless remove is set"));
+            }
+        };
+
+        Ok(EvaluatedTask {
+            action: Arc::new(ConfigFileAction {
+                path,
+                format,
+                key,
+                value,
+                remove,
+                create: handle.template.boolean_option_default_false(
+                    request,
+                    tm,
+                    &String::from("create"),
+                    &self.create,
+                )?,
+                backup: handle.template.boolean_option_default_false(
+                    request,
+                    tm,
+                    &String::from("backup"),
+                    &self.backup,
+                )?,
+                attributes: FileAttributesInput::template(handle, request, tm, &self.attributes)?,
+            }),
+            with: Arc::new(PreLogicInput::template(handle, request, tm, &self.with)?),
+            and: Arc::new(PostLogicInput::template(handle, request, tm, &self.and)?),
+        })
+    }
+}
+
+// strings anywhere in the value are templated. As with Templated<T> parameters, a
+// string that was a template and renders to a YAML boolean or number takes that
+// type, so `port: "{{ http_port }}"` is written as a number.
+
+fn template_value(
+    handle: &Arc<TaskHandle>,
+    request: &Arc<TaskRequest>,
+    tm: TemplateMode,
+    value: &serde_yaml::Value,
+) -> Result<serde_yaml::Value, Arc<TaskResponse>> {
+    match value {
+        serde_yaml::Value::String(s) if !s.is_empty() => {
+            let rendered =
+                handle
+                    .template
+                    .string_unsafe_for_shell(request, tm, &String::from("value"), s)?;
+            if rendered == *s {
+                return Ok(value.clone());
+            }
+            match serde_yaml::from_str::<serde_yaml::Value>(&rendered) {
+                Ok(typed @ (serde_yaml::Value::Bool(_) | serde_yaml::Value::Number(_))) => {
+                    Ok(typed)
+                }
+                _ => Ok(serde_yaml::Value::String(rendered)),
+            }
+        }
+        serde_yaml::Value::Sequence(items) => {
+            let mut out = Vec::new();
+            for item in items.iter() {
+                out.push(template_value(handle, request, tm, item)?);
+            }
+            Ok(serde_yaml::Value::Sequence(out))
+        }
+        serde_yaml::Value::Mapping(map) => {
+            let mut out = serde_yaml::Mapping::new();
+            for (k, v) in map.iter() {
+                out.insert(k.clone(), template_value(handle, request, tm, v)?);
+            }
+            Ok(serde_yaml::Value::Mapping(out))
+        }
+        _ => Ok(value.clone()),
+    }
+}
+
+impl IsAction for ConfigFileAction {
+    fn dispatch(
+        &self,
+        handle: &Arc<TaskHandle>,
+        request: &Arc<TaskRequest>,
+    ) -> Result<Arc<TaskResponse>, Arc<TaskResponse>> {
+        match request.request_type {
+            TaskRequestType::Query => {
+                let content = match handle.remote.read_file(request, &self.path)? {
+                    Some(content) => content,
+                    None if self.remove => return Ok(handle.response.is_matched(request)),
+                    None if self.create => {
+                        let data = self.edit_or_fail(handle, request, "")?;
+                        let response = handle.response.needs_creation(request);
+                        let diff = match handle.response.wants_diff() {
+                            true => unified(&self.path, None, data.unwrap_or_default().as_bytes()),
+                            false => None,
+                        };
+                        return Ok(handle.response.with_diff(response, diff));
+                    }
+                    None => {
+                        return Err(handle.response.is_failed(
+                            request,
+                            &format!("{} does not exist (set create: true)", self.path),
+                        ));
+                    }
+                };
+                let mut changes: Vec<Field> = Vec::new();
+                handle.remote.query_common_file_attributes(
+                    request,
+                    &self.path,
+                    &self.attributes,
+                    &mut changes,
+                    Recurse::No,
+                )?;
+                let mut diff: Option<String> = None;
+                if let Some(data) = self.edit_or_fail(handle, request, &content)? {
+                    changes.push(Field::Content);
+                    if handle.response.wants_diff() {
+                        diff = unified(&self.path, Some(content.as_bytes()), data.as_bytes());
+                    }
+                }
+                if !changes.is_empty() {
+                    let response = handle.response.needs_modification(request, &changes);
+                    return Ok(handle.response.with_diff(response, diff));
+                }
+                Ok(handle.response.is_matched(request))
+            }
+
+            TaskRequestType::Create => {
+                let data = self.edit_or_fail(handle, request, "")?.unwrap_or_default();
+                handle
+                    .remote
+                    .write_data(request, &data, &self.path, &self.attributes, |f| {
+                        handle.remote.process_transferred_file_attributes(
+                            request,
+                            f,
+                            &self.attributes,
+                        )
+                    })?;
+                Ok(handle.response.is_created(request))
+            }
+
+            TaskRequestType::Modify => {
+                if request.changes.contains(&Field::Content) {
+                    let content = handle
+                        .remote
+                        .read_file(request, &self.path)?
+                        .unwrap_or_default();
+                    if let Some(data) = self.edit_or_fail(handle, request, &content)? {
+                        if self.backup {
+                            handle.remote.backup_file(request, &self.path)?;
+                        }
+                        handle.remote.write_data_in_place(
+                            request,
+                            &data,
+                            &self.path,
+                            &self.attributes,
+                        )?;
+                    }
+                } else {
+                    handle.remote.process_common_file_attributes(
+                        request,
+                        &self.path,
+                        &self.attributes,
+                        &request.changes,
+                        Recurse::No,
+                    )?;
+                }
+                Ok(handle
+                    .response
+                    .is_modified(request, request.changes.clone()))
+            }
+
+            _ => Err(handle.response.not_supported(request)),
+        }
+    }
+}
+
+impl ConfigFileAction {
+    fn edit_or_fail(
+        &self,
+        handle: &Arc<TaskHandle>,
+        request: &Arc<TaskRequest>,
+        content: &str,
+    ) -> Result<Option<String>, Arc<TaskResponse>> {
+        self.edit(content).map_err(|e| {
+            handle
+                .response
+                .is_failed(request, &format!("{}: {}", self.path, e))
+        })
+    }
+
+    // the new file content, or None when the key already has the wanted value (or is
+    // already absent). Every format is edited in place, so the rest of the file keeps
+    // its comments and formatting.
+
+    fn edit(&self, content: &str) -> Result<Option<String>, String> {
+        match self.format {
+            Format::Toml => self.edit_toml(content),
+            _ => self.edit_tree(content),
+        }
+    }
+
+    // the change is worked out on the parsed document first, then spliced into the
+    // text. The splice is only used if the result parses to that same document;
+    // otherwise the file is re-serialized, which YAML comments would not survive.
+
+    fn edit_tree(&self, content: &str) -> Result<Option<String>, String> {
+        let mut doc = parse_tree(self.format, content)?;
+        let changed = match &self.value {
+            Some(value) if !self.remove => {
+                let wanted = serde_json::to_value(value).map_err(|e| e.to_string())?;
+                set_tree(&mut doc, &self.key, wanted)?
+            }
+            _ => remove_tree(&mut doc, &self.key),
+        };
+        if !changed {
+            return Ok(None);
+        }
+
+        let spliced = match self.format {
+            Format::Json => splice_json(content, &self.key, self.wanted_json()?.as_ref()),
+            _ => splice_yaml(
+                content,
+                &self.key,
+                self.value.as_ref().filter(|_| !self.remove),
+            ),
+        };
+        if let Some(text) = spliced
+            && parse_tree(self.format, &text).ok() == Some(doc.clone())
+        {
+            return Ok(Some(text));
+        }
+
+        if self.format == Format::Yaml && has_yaml_comments(content) {
+            return Err(String::from(
+                "this change cannot be made in place, and rewriting the file would drop its comments",
+            ));
+        }
+        match self.format {
+            Format::Json => serde_json::to_string_pretty(&doc)
+                .map(|s| format!("{}\n", s))
+                .map_err(|e| e.to_string()),
+            _ => serde_yaml::to_string(&doc).map_err(|e| e.to_string()),
+        }
+        .map(Some)
+    }
+
+    fn wanted_json(&self) -> Result<Option<serde_json::Value>, String> {
+        match &self.value {
+            Some(value) if !self.remove => serde_json::to_value(value)
+                .map(Some)
+                .map_err(|e| e.to_string()),
+            _ => Ok(None),
+        }
+    }
+
+    fn edit_toml(&self, content: &str) -> Result<Option<String>, String> {
+        let mut doc: toml_edit::DocumentMut = content.parse().map_err(|e| format!("{}", e))?;
+        let (last, parents) = self.key.split_last().unwrap();
+        let mut table: &mut dyn toml_edit::TableLike = doc.as_table_mut();
+        for (depth, part) in parents.iter().enumerate() {
+            if self.remove && !table.contains_key(part) {
+                return Ok(None);
+            }
+            let mut implicit = toml_edit::Table::new();
+            implicit.set_implicit(true);
+            table = table
+                .entry(part)
+                .or_insert(toml_edit::Item::Table(implicit))
+                .as_table_like_mut()
+                .ok_or_else(|| format!("'{}' is not a table", self.key[..=depth].join(".")))?;
+        }
+        match &self.value {
+            Some(value) if !self.remove => {
+                let mut wanted = toml_value(value)?;
+                match table.get_mut(last) {
+                    Some(toml_edit::Item::Value(current)) => {
+                        if toml_equal(current, &wanted) {
+                            return Ok(None);
+                        }
+                        // assigning in place keeps the key's comments, and the old
+                        // decor keeps the spacing and any trailing comment
+                        *wanted.decor_mut() = current.decor().clone();
+                        *current = wanted;
+                    }
+                    _ => {
+                        table.insert(last, toml_edit::Item::Value(wanted));
+                    }
+                }
+            }
+            _ => {
+                let index = match table.iter().position(|(k, _)| k == last) {
+                    Some(index) => index,
+                    None => return Ok(None),
+                };
+                let prefix = table
+                    .key(last)
+                    .and_then(|k| k.leaf_decor().prefix())
+                    .and_then(|p| p.as_str())
+                    .unwrap_or_default()
+                    .to_string();
+                let heads_file = parents.is_empty()
+                    && !table.iter().take(index).any(|(_, item)| item.is_value());
+                table.remove(last);
+                let kept = kept_comments(&prefix, heads_file);
+                if kept.is_empty() {
+                    return Ok(Some(doc.to_string()));
+                }
+                // the kept comments move onto whatever now follows them: the next
+                // key of the table, the next table header, or the end of the file
+                let next = table
+                    .iter()
+                    .skip(index)
+                    .find(|(_, item)| item.is_value())
+                    .map(|(k, _)| k.to_string());
+                if let Some(next) = next {
+                    let mut key = table.key_mut(&next).unwrap();
+                    let decor = key.leaf_decor_mut();
+                    let rest = decor_prefix(decor);
+                    decor.set_prefix(format!("{}{}", kept, rest));
+                    return Ok(Some(doc.to_string()));
+                }
+                let after = toml_table_position(doc.as_table(), parents);
+                let mut positions = Vec::new();
+                toml_table_positions(doc.as_table(), &mut positions);
+                match positions.into_iter().filter(|p| Some(*p) > after).min() {
+                    Some(position) => {
+                        let decor = toml_table_at(doc.as_table_mut(), position)
+                            .unwrap()
+                            .decor_mut();
+                        let rest = decor_prefix(decor);
+                        decor.set_prefix(format!("{}{}", kept, rest));
+                    }
+                    None => {
+                        let rest = doc.trailing().as_str().unwrap_or_default().to_string();
+                        doc.set_trailing(format!("{}{}", kept, rest));
+                    }
+                }
+            }
+        }
+        Ok(Some(doc.to_string()))
+    }
+}
+
+// walks (and for set, creates) the maps along the key; numeric parts index lists
+
+fn set_tree(
+    doc: &mut serde_json::Value,
+    key: &[String],
+    wanted: serde_json::Value,
+) -> Result<bool, String> {
+    let (last, parents) = key.split_last().unwrap();
+    let mut node = doc;
+    for (depth, part) in parents.iter().enumerate() {
+        if node.is_null() {
+            *node = serde_json::Value::Object(serde_json::Map::new());
+        }
+        node = match node {
+            serde_json::Value::Object(map) => map
+                .entry(part.clone())
+                .or_insert_with(|| serde_json::Value::Object(serde_json::Map::new())),
+            serde_json::Value::Array(items) => part
+                .parse::<usize>()
+                .ok()
+                .and_then(|i| items.get_mut(i))
+                .ok_or_else(|| format!("'{}' has no element {}", key[..depth].join("."), part))?,
+            _ => return Err(format!("'{}' is not a map", key[..=depth].join("."))),
+        };
+    }
+    if node.is_null() {
+        *node = serde_json::Value::Object(serde_json::Map::new());
+    }
+    let slot = match node {
+        serde_json::Value::Object(map) => {
+            if map.get(last) == Some(&wanted) {
+                return Ok(false);
+            }
+            map.insert(last.clone(), wanted);
+            return Ok(true);
+        }
+        serde_json::Value::Array(items) => last
+            .parse::<usize>()
+            .ok()
+            .and_then(|i| items.get_mut(i))
+            .ok_or_else(|| format!("'{}' has no element {}", parents.join("."), last))?,
+        _ => return Err(format!("'{}' is not a map", parents.join("."))),
+    };
+    if *slot == wanted {
+        return Ok(false);
+    }
+    *slot = wanted;
+    Ok(true)
+}
+
+fn remove_tree(doc: &mut serde_json::Value, key: &[String]) -> bool {
+    let (last, parents) = key.split_last().unwrap();
+    let mut node = doc;
+    for part in parents.iter() {
+        let next = match node {
+            serde_json::Value::Object(map) => map.get_mut(part),
+            serde_json::Value::Array(items) => {
+                part.parse::<usize>().ok().and_then(|i| items.get_mut(i))
+            }
+            _ => None,
+        };
+        node = match next {
+            Some(n) => n,
+            None => return false,
+        };
+    }
+    match node {
+        serde_json::Value::Object(map) => map.shift_remove(last).is_some(),
+        serde_json::Value::Array(items) => match last.parse::<usize>() {
+            Ok(i) if i < items.len() => {
+                items.remove(i);
+                true
+            }
+            _ => false,
+        },
+        _ => false,
+    }
+}
+
+fn parse_tree(format: Format, content: &str) -> Result<serde_json::Value, String> {
+    if content.trim().is_empty() {
+        return Ok(serde_json::Value::Object(serde_json::Map::new()));
+    }
+    match format {
+        Format::Json => serde_json::from_str(content).map_err(|e| e.to_string()),
+        _ => serde_yaml::from_str(content).map_err(|e| e.to_string()),
+    }
+}
+
+// the leading whitespace of the line holding the byte at pos
+
+fn line_indent(text: &str, pos: usize) -> &str {
+    let start = text[..pos].rfind('\n').map(|i| i + 1).unwrap_or(0);
+    let line = &text[start..];
+    &line[..line.len() - line.trim_start_matches([' ', '\t']).len()]
+}
+
+// byte spans of a JSON document's values, enough to splice one value in or out
+
+struct JsonNode {
+    start: usize,
+    end: usize,
+    kind: JsonKind,
+}
+
+enum JsonKind {
+    // (key, start of the quoted key, value)
+    Object(Vec<(String, usize, JsonNode)>),
+    Array(Vec<JsonNode>),
+    Scalar,
+}
+
+impl JsonNode {
+    fn child(&self, part: &str) -> Option<&JsonNode> {
+        match &self.kind {
+            JsonKind::Object(members) => members
+                .iter()
+                .rev()
+                .find(|(k, _, _)| k == part)
+                .map(|(_, _, v)| v),
+            JsonKind::Array(items) => part.parse::<usize>().ok().and_then(|i| items.get(i)),
+            JsonKind::Scalar => None,
+        }
+    }
+
+    // (start, end) of each member or element, a member starting at its key
+    fn entries(&self) -> Vec<(usize, usize)> {
+        match &self.kind {
+            JsonKind::Object(members) => members.iter().map(|(_, k, v)| (*k, v.end)).collect(),
+            JsonKind::Array(items) => items.iter().map(|v| (v.start, v.end)).collect(),
+            JsonKind::Scalar => Vec::new(),
+        }
+    }
+
+    fn index_of(&self, part: &str) -> Option<usize> {
+        match &self.kind {
+            JsonKind::Object(members) => members.iter().rposition(|(k, _, _)| k == part),
+            JsonKind::Array(items) => part.parse::<usize>().ok().filter(|i| *i < items.len()),
+            JsonKind::Scalar => None,
+        }
+    }
+}
+
+struct JsonScanner<'a> {
+    text: &'a str,
+    pos: usize,
+}
+
+impl JsonScanner<'_> {
+    fn peek(&mut self) -> Option<u8> {
+        while let Some(b) = self.text.as_bytes().get(self.pos) {
+            match b {
+                b' ' | b'\t' | b'\r' | b'\n' => self.pos += 1,
+                _ => return Some(*b),
+            }
+        }
+        None
+    }
+
+    fn expect(&mut self, byte: u8) -> Option<()> {
+        (self.peek()? == byte).then(|| self.pos += 1)
+    }
+
+    fn value(&mut self) -> Option<JsonNode> {
+        let start = self.pos;
+        let kind = match self.peek()? {
+            b'{' => {
+                self.pos += 1;
+                let mut members = Vec::new();
+                while self.peek()? != b'}' {
+                    let key_start = self.pos;
+                    self.string()?;
+                    let key = serde_json::from_str(&self.text[key_start..self.pos]).ok()?;
+                    self.expect(b':')?;
+                    members.push((key, key_start, self.value()?));
+                    if self.peek()? == b',' {
+                        self.pos += 1;
+                    }
+                }
+                self.pos += 1;
+                JsonKind::Object(members)
+            }
+            b'[' => {
+                self.pos += 1;
+                let mut items = Vec::new();
+                while self.peek()? != b']' {
+                    items.push(self.value()?);
+                    if self.peek()? == b',' {
+                        self.pos += 1;
+                    }
+                }
+                self.pos += 1;
+                JsonKind::Array(items)
+            }
+            b'"' => {
+                self.string()?;
+                JsonKind::Scalar
+            }
+            _ => {
+                let rest = &self.text[self.pos..];
+                let len = rest
+                    .find(|c: char| c.is_whitespace() || matches!(c, ',' | '}' | ']'))
+                    .unwrap_or(rest.len());
+                self.pos += len;
+                JsonKind::Scalar
+            }
+        };
+        // the value starts after any whitespace skipped by peek
+        let start = start + self.text[start..].len() - self.text[start..].trim_start().len();
+        Some(JsonNode {
+            start,
+            end: self.pos,
+            kind,
+        })
+    }
+
+    fn string(&mut self) -> Option<()> {
+        self.expect(b'"')?;
+        let bytes = self.text.as_bytes();
+        while let Some(b) = bytes.get(self.pos) {
+            self.pos += 1;
+            match b {
+                b'\\' => self.pos += 1,
+                b'"' => return Some(()),
+                _ => {}
+            }
+        }
+        None
+    }
+}
+
+// pretty-printed, with continuation lines indented to sit under the line at pos
+fn render_json(value: &serde_json::Value, indent: &str) -> Option<String> {
+    let text = serde_json::to_string_pretty(value).ok()?;
+    Some(text.replace('\n', &format!("\n{}", indent)))
+}
+
+// sets (wanted is Some) or removes the key. Returns None when the splice cannot be
+// worked out, which sends the caller to re-serializing.
+
+fn splice_json(
+    content: &str,
+    key: &[String],
+    wanted: Option<&serde_json::Value>,
+) -> Option<String> {
+    let mut scanner = JsonScanner {
+        text: content,
+        pos: 0,
+    };
+    let root = scanner.value()?;
+    let splice = |start: usize, end: usize, with: &str| {
+        Some(format!("{}{}{}", &content[..start], with, &content[end..]))
+    };
+
+    let (last, parents) = key.split_last()?;
+    let wanted = match wanted {
+        Some(wanted) => wanted,
+        None => {
+            let mut parent = &root;
+            for part in parents.iter() {
+                parent = parent.child(part)?;
+            }
+            let entries = parent.entries();
+            let i = parent.index_of(last)?;
+            return match (i, entries.len()) {
+                (0, 1) => splice(parent.start + 1, parent.end - 1, ""),
+                (0, _) => splice(entries[0].0, entries[1].0, ""),
+                (i, _) => splice(entries[i - 1].1, entries[i].1, ""),
+            };
+        }
+    };
+
+    let mut node = &root;
+    for (depth, part) in key.iter().enumerate() {
+        let members = match (node.child(part), &node.kind) {
+            (Some(child), _) => {
+                node = child;
+                continue;
+            }
+            (None, JsonKind::Object(members)) => members,
+            (None, _) => return None,
+        };
+        // nest the value under the keys that don't exist yet
+        let mut value = wanted.clone();
+        for missing in key[depth + 1..].iter().rev() {
+            let mut map = serde_json::Map::new();
+            map.insert(missing.clone(), value);
+            value = serde_json::Value::Object(map);
+        }
+        let name = serde_json::to_string(part).ok()?;
+        return match members.last() {
+            Some((_, key_start, previous)) => {
+                let indent = line_indent(content, *key_start);
+                let entry = match content[..*key_start].ends_with(indent)
+                    && content[..*key_start - indent.len()].ends_with('\n')
+                {
+                    true => format!(",\n{}{}: {}", indent, name, render_json(&value, indent)?),
+                    false => format!(", {}: {}", name, serde_json::to_string(&value).ok()?),
+                };
+                splice(previous.end, previous.end, &entry)
+            }
+            None => {
+                let outer = line_indent(content, node.start);
+                let inner = format!("{}  ", outer);
+                let object = format!(
+                    "{{\n{}{}: {}\n{}}}",
+                    inner,
+                    name,
+                    render_json(&value, &inner)?,
+                    outer
+                );
+                splice(node.start, node.end, &object)
+            }
+        };
+    }
+    splice(
+        node.start,
+        node.end,
+        &render_json(wanted, line_indent(content, node.start))?,
+    )
+}
+
+// YAML is spliced line by line, which covers block mappings of `key: value` lines.
+// Anything else on the way to the key (lists, flow maps, multi-document files) is
+// left to re-serializing.
+
+struct YamlLine<'a> {
+    text: &'a str,
+    indent: usize,
+    significant: bool,
+}
+
+impl<'a> YamlLine<'a> {
+    fn new(text: &'a str) -> Self {
+        let body = text.trim_start_matches(' ');
+        let trimmed = body.trim();
+        YamlLine {
+            text,
+            indent: text.len() - body.len(),
+            significant: !trimmed.is_empty() && !trimmed.starts_with('#'),
+        }
+    }
+
+    // the key and the byte offset just past its colon
+    fn entry(&self) -> Option<(String, usize)> {
+        let body = &self.text[self.indent..];
+        let (key, rest) = match body.chars().next()? {
+            quote @ ('"' | '\'') => {
+                let close = body[1..].find(quote)? + 1;
+                (body[1..close].to_string(), &body[close + 1..])
+            }
+            '-' | '?' | '[' | '{' | '&' | '*' | '!' | '|' | '>' | '%' => return None,
+            _ => {
+                let colon = body.match_indices(':').map(|(i, _)| i).find(|i| {
+                    body[i + 1..].starts_with([' ', '\t', '\r', '\n']) || body.len() == i + 1
+                })?;
+                (body[..colon].trim_end().to_string(), &body[colon..])
+            }
+        };
+        let rest = rest.trim_start_matches([' ', '\t']).strip_prefix(':')?;
+        Some((key, self.text.len() - rest.len()))
+    }
+
+    // (start, end) of the inline value after the colon, leaving any comment out
+    fn inline_value(&self, after_colon: usize) -> Option<(usize, usize)> {
+        let rest = &self.text[after_colon..];
+        let start = after_colon + rest.len() - rest.trim_start_matches([' ', '\t']).len();
+        let value = self.text[start..].trim_end_matches(['\r', '\n']);
+        let scan_from = match value.chars().next() {
+            None | Some('#') => return None,
+            Some(quote @ ('"' | '\'')) => value[1..].rfind(quote)? + 2,
+            _ => 0,
+        };
+        let end = value[scan_from..]
+            .find(" #")
+            .or_else(|| value[scan_from..].find("\t#"))
+            .map(|i| scan_from + i)
+            .unwrap_or(value.len());
+        Some((start, start + value[..end].trim_end().len()))
+    }
+}
+
+// (line, key, offset past the colon)
+type YamlEntry = (usize, String, usize);
+
+// the mapping entries among lines[lo..hi] at its shallowest indentation
+fn yaml_entries(
+    lines: &[YamlLine],
+    lo: usize,
+    hi: usize,
+) -> Option<(Option<usize>, Vec<YamlEntry>)> {
+    let indent = lines[lo..hi]
+        .iter()
+        .filter(|l| l.significant)
+        .map(|l| l.indent)
+        .min();
+    let mut entries = Vec::new();
+    for (i, line) in lines.iter().enumerate().take(hi).skip(lo) {
+        if line.significant && Some(line.indent) == indent {
+            let (key, colon) = line.entry()?;
+            entries.push((i, key, colon));
+        }
+    }
+    Some((indent, entries))
+}
+
+// one past the last significant line belonging to the entry at line i
+fn yaml_block_end(lines: &[YamlLine], i: usize, hi: usize) -> usize {
+    let mut end = i + 1;
+    for (j, line) in lines.iter().enumerate().take(hi).skip(i + 1) {
+        if line.significant {
+            if line.indent <= lines[i].indent {
+                break;
+            }
+            end = j + 1;
+        }
+    }
+    end
+}
+
+fn render_yaml_entry(keys: &[String], value: &serde_yaml::Value, indent: usize) -> Option<String> {
+    let mut value = value.clone();
+    for part in keys.iter().rev() {
+        let mut map = serde_yaml::Mapping::new();
+        map.insert(serde_yaml::Value::String(part.clone()), value);
+        value = serde_yaml::Value::Mapping(map);
+    }
+    let text = serde_yaml::to_string(&value).ok()?;
+    Some(
+        text.lines()
+            .map(|line| format!("{}{}\n", " ".repeat(indent), line))
+            .collect(),
+    )
+}
+
+fn splice_yaml(
+    content: &str,
+    key: &[String],
+    wanted: Option<&serde_yaml::Value>,
+) -> Option<String> {
+    // a missing final newline is put back afterwards
+    let owned = match content.is_empty() || content.ends_with('\n') {
+        true => content.to_string(),
+        false => format!("{}\n", content),
+    };
+    let lines: Vec<YamlLine> = owned.split_inclusive('\n').map(YamlLine::new).collect();
+    let offsets: Vec<usize> = lines
+        .iter()
+        .scan(0, |at, line| {
+            let start = *at;
+            *at += line.text.len();
+            Some(start)
+        })
+        .collect();
+    let offset = |i: usize| offsets.get(i).copied().unwrap_or(owned.len());
+    let splice = |start: usize, end: usize, with: &str| {
+        let text = format!("{}{}{}", &owned[..start], with, &owned[end..]);
+        match owned.len() == content.len() {
+            true => text,
+            false => text.strip_suffix('\n').unwrap_or(&text).to_string(),
+        }
+    };
+
+    let (mut lo, mut hi) = (0, lines.len());
+    let mut parent: Option<(usize, usize)> = None;
+    for (depth, part) in key.iter().enumerate() {
+        let (indent, entries) = yaml_entries(&lines, lo, hi)?;
+        let indent = indent.unwrap_or(parent.map(|(i, _)| lines[i].indent + 2).unwrap_or(0));
+        let found = entries.iter().rev().find(|(_, k, _)| k == part);
+        let Some((i, _, colon)) = found else {
+            let wanted = wanted?;
+            let at = lines[lo..hi]
+                .iter()
+                .rposition(|l| l.significant)
+                .map(|j| offset(lo + j + 1))
+                .unwrap_or(offset(lo));
+            return Some(splice(
+                at,
+                at,
+                &render_yaml_entry(&key[depth..], wanted, indent)?,
+            ));
+        };
+        let (i, colon) = (*i, *colon);
+        let end = yaml_block_end(&lines, i, hi);
+        let inline = lines[i].inline_value(colon);
+
+        if depth + 1 < key.len() {
+            // a value on the key's own line is a scalar or flow collection
+            if inline.is_some() {
+                return None;
+            }
+            parent = Some((i, colon));
+            lo = i + 1;
+            hi = end;
+            continue;
+        }
+
+        let Some(wanted) = wanted else {
+            let removed = splice(offset(i), offset(end), "");
+            // a map left with no keys would read back as null
+            return match (entries.len(), parent) {
+                (1, Some((p, p_colon))) if lines[p].inline_value(p_colon).is_none() => {
+                    let at = offset(p) + p_colon;
+                    Some(format!("{} {{}}{}", &removed[..at], &removed[at..]))
+                }
+                _ => Some(removed),
+            };
+        };
+        let rendered = serde_yaml::to_string(wanted).ok()?;
+        let scalar = rendered.trim_end_matches('\n');
+        return match inline {
+            Some((start, stop))
+                if end == i + 1
+                    && !scalar.contains('\n')
+                    && !wanted.is_mapping()
+                    && !wanted.is_sequence() =>
+            {
+                Some(splice(offset(i) + start, offset(i) + stop, scalar))
+            }
+            _ => Some(splice(
+                offset(i),
+                offset(end),
+                &render_yaml_entry(&key[depth..], wanted, indent)?,
+            )),
+        };
+    }
+    None
+}
+
+fn has_yaml_comments(content: &str) -> bool {
+    content.lines().any(|line| {
+        let line = line.trim_start();
+        line.starts_with('#') || line.contains(" #") || line.contains("\t#")
+    })
+}
+
+fn toml_value(value: &serde_yaml::Value) -> Result<toml_edit::Value, String> {
+    match value {
+        serde_yaml::Value::Bool(b) => Ok(toml_edit::Value::from(*b)),
+        serde_yaml::Value::Number(n) => match (n.as_i64(), n.as_f64()) {
+            (Some(i), _) => Ok(toml_edit::Value::from(i)),
+            (None, Some(f)) => Ok(toml_edit::Value::from(f)),
+            _ => Err(format!("{} does not fit a TOML number", n)),
+        },
+        serde_yaml::Value::String(s) => Ok(toml_edit::Value::from(s.as_str())),
+        serde_yaml::Value::Sequence(items) => {
+            let mut array = toml_edit::Array::new();
+            for item in items.iter() {
+                array.push(toml_value(item)?);
+            }
+            Ok(toml_edit::Value::Array(array))
+        }
+        serde_yaml::Value::Mapping(map) => {
+            let mut table = toml_edit::InlineTable::new();
+            for (k, v) in map.iter() {
+                let k = k
+                    .as_str()
+                    .ok_or_else(|| String::from("TOML table keys must be strings"))?;
+                table.insert(k, toml_value(v)?);
+            }
+            Ok(toml_edit::Value::InlineTable(table))
+        }
+        serde_yaml::Value::Null => Err(String::from("TOML has no null value, use remove")),
+        serde_yaml::Value::Tagged(_) => Err(String::from("tagged values are not supported")),
+    }
+}
+
+// of the comments above a removed TOML key, those set apart from it by a blank line
+// are not about the key and stay in the file. Comments heading the file are kept
+// whole, they describe the file rather than its first key.
+
+fn kept_comments(prefix: &str, heads_file: bool) -> String {
+    if heads_file {
+        return prefix.to_string();
+    }
+    let lines: Vec<&str> = prefix.split_inclusive('\n').collect();
+    match lines.iter().rposition(|line| line.trim().is_empty()) {
+        Some(blank) => lines[..=blank].concat(),
+        None => String::new(),
+    }
+}
+
+fn decor_prefix(decor: &toml_edit::Decor) -> String {
+    decor
+        .prefix()
+        .and_then(|p| p.as_str())
+        .unwrap_or_default()
+        .to_string()
+}
+
+// the document position of the table at key, None for the root or a table without
+// a header of its own
+
+fn toml_table_position(root: &toml_edit::Table, key: &[String]) -> Option<usize> {
+    let mut table = root;
+    for part in key.iter() {
+        table = table.get(part)?.as_table()?;
+    }
+    match table.is_implicit() || table.is_dotted() {
+        true => None,
+        false => table.position(),
+    }
+}
+
+// the positions of every table header in the document, arrays of tables included
+
+fn toml_table_positions(table: &toml_edit::Table, positions: &mut Vec<usize>) {
+    for (_, item) in table.iter() {
+        let children: Vec<&toml_edit::Table> = match item {
+            toml_edit::Item::Table(t) => vec![t],
+            toml_edit::Item::ArrayOfTables(a) => a.iter().collect(),
+            _ => continue,
+        };
+        for child in children {
+            if !child.is_implicit()
+                && !child.is_dotted()
+                && let Some(position) = child.position()
+            {
+                positions.push(position);
+            }
+            toml_table_positions(child, positions);
+        }
+    }
+}
+
+fn toml_table_at(table: &mut toml_edit::Table, position: usize) -> Option<&mut toml_edit::Table> {
+    for (_, item) in table.iter_mut() {
+        let children: Vec<&mut toml_edit::Table> = match item {
+            toml_edit::Item::Table(t) => vec![t],
+            toml_edit::Item::ArrayOfTables(a) => a.iter_mut().collect(),
+            _ => continue,
+        };
+        for child in children {
+            if !child.is_implicit() && !child.is_dotted() && child.position() == Some(position) {
+                return Some(child);
+            }
+            if let Some(found) = toml_table_at(child, position) {
+                return Some(found);
+            }
+        }
+    }
+    None
+}
+
+// compares what the values mean, not how they are written, so an array laid out
+// over several lines still matches the same values given in the task
+
+fn toml_equal(a: &toml_edit::Value, b: &toml_edit::Value) -> bool {
+    use toml_edit::Value;
+    match (a, b) {
+        (Value::String(x), Value::String(y)) => x.value() == y.value(),
+        (Value::Integer(x), Value::Integer(y)) => x.value() == y.value(),
+        (Value::Float(x), Value::Float(y)) => x.value() == y.value(),
+        (Value::Boolean(x), Value::Boolean(y)) => x.value() == y.value(),
+        (Value::Datetime(x), Value::Datetime(y)) => x.value() == y.value(),
+        (Value::Array(x), Value::Array(y)) => {
+            x.len() == y.len() && x.iter().zip(y.iter()).all(|(p, q)| toml_equal(p, q))
+        }
+        (Value::InlineTable(x), Value::InlineTable(y)) => {
+            x.len() == y.len()
+                && x.iter()
+                    .all(|(k, v)| y.get(k).is_some_and(|w| toml_equal(v, w)))
+        }
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn json_keys_are_set_keeping_other_content_in_order() {
+        let doc = "{\"name\": \"app\", \"server\": {\"http\": {\"port\": 80}}, \"tags\": [\"a\"]}";
+        let a = ConfigFileAction {
+            path: String::from("/etc/app/config.json"),
+            format: Format::Json,
+            key: vec![
+                String::from("server"),
+                String::from("http"),
+                String::from("port"),
+            ],
+            value: Some(serde_yaml::Value::from(8080)),
+            remove: false,
+            create: false,
+            backup: false,
+            attributes: None,
+        };
+        let edited = a.edit(doc).unwrap().unwrap();
+        assert_eq!(
+            edited,
+            "{\"name\": \"app\", \"server\": {\"http\": {\"port\": 8080}}, \"tags\": [\"a\"]}"
+        );
+        assert!(a.edit(&edited).unwrap().is_none());
+        let a = ConfigFileAction {
+            key: vec![String::from("tags"), String::from("0")],
+            value: Some(serde_yaml::Value::from("b")),
+            ..a
+        };
+        let edited = a.edit(doc).unwrap().unwrap();
+        assert!(edited.ends_with("\"tags\": [\"b\"]}"));
+        let a = ConfigFileAction {
+            key: vec![String::from("name"), String::from("first")],
+            value: Some(serde_yaml::Value::from("x")),
+            ..a
+        };
+        assert!(a.edit(doc).is_err());
+    }
+
+    #[test]
+    fn json_edits_keep_the_layout_of_untouched_keys() {
+        let doc =
+            "{\n    \"name\":   \"app\",\n    \"server\": {\n        \"port\": 80\n    }\n}\n";
+        let a = ConfigFileAction {
+            path: String::from("/etc/app/config.json"),
+            format: Format::Json,
+            key: vec![String::from("server"), String::from("tls")],
+            value: Some(serde_yaml::from_str("{enabled: true}").unwrap()),
+            remove: false,
+            create: false,
+            backup: false,
+            attributes: None,
+        };
+        assert_eq!(
+            a.edit(doc).unwrap().unwrap(),
+            "{\n    \"name\":   \"app\",\n    \"server\": {\n        \"port\": 80,\n        \"tls\": {\n          \"enabled\": true\n        }\n    }\n}\n"
+        );
+        let a = ConfigFileAction {
+            key: vec![String::from("name")],
+            value: None,
+            remove: true,
+            ..a
+        };
+        assert_eq!(
+            a.edit(doc).unwrap().unwrap(),
+            "{\n    \"server\": {\n        \"port\": 80\n    }\n}\n"
+        );
+        let a = ConfigFileAction {
+            key: vec![String::from("server"), String::from("port")],
+            ..a
+        };
+        assert_eq!(
+            a.edit(doc).unwrap().unwrap(),
+            "{\n    \"name\":   \"app\",\n    \"server\": {}\n}\n"
+        );
+    }
+
+    #[test]
+    fn yaml_edits_keep_comments_and_formatting() {
+        let doc = "# app settings\nserver:\n  host: '0.0.0.0'   # all interfaces\n\n  port: 80\nlog: info";
+        let a = ConfigFileAction {
+            path: String::from("/etc/app/config.yml"),
+            format: Format::Yaml,
+            key: vec![String::from("server"), String::from("port")],
+            value: Some(serde_yaml::Value::from(8080)),
+            remove: false,
+            create: false,
+            backup: false,
+            attributes: None,
+        };
+        assert_eq!(
+            a.edit(doc).unwrap().unwrap(),
+            "# app settings\nserver:\n  host: '0.0.0.0'   # all interfaces\n\n  port: 8080\nlog: info"
+        );
+        let a = ConfigFileAction {
+            key: vec![String::from("server"), String::from("host")],
+            value: Some(serde_yaml::Value::from("127.0.0.1")),
+            ..a
+        };
+        assert_eq!(
+            a.edit(doc).unwrap().unwrap(),
+            "# app settings\nserver:\n  host: 127.0.0.1   # all interfaces\n\n  port: 80\nlog: info"
+        );
+        let a = ConfigFileAction {
+            key: vec![String::from("log")],
+            value: Some(serde_yaml::from_str("{level: debug}").unwrap()),
+            ..a
+        };
+        assert_eq!(
+            a.edit(doc).unwrap().unwrap(),
+            "# app settings\nserver:\n  host: '0.0.0.0'   # all interfaces\n\n  port: 80\nlog:\n  level: debug"
+        );
+    }
+
+    #[test]
+    fn yaml_with_comments_is_never_rewritten_whole() {
+        let doc = "# hosts\nservers:\n  - name: a\n    port: 80\n";
+        let a = ConfigFileAction {
+            path: String::from("/etc/app/config.yml"),
+            format: Format::Yaml,
+            key: vec![
+                String::from("servers"),
+                String::from("0"),
+                String::from("port"),
+            ],
+            value: Some(serde_yaml::Value::from(8080)),
+            remove: false,
+            create: false,
+            backup: false,
+            attributes: None,
+        };
+        assert!(a.edit(doc).unwrap_err().contains("comments"));
+        assert_eq!(
+            a.edit("servers:\n  - name: a\n    port: 80\n")
+                .unwrap()
+                .unwrap(),
+            "servers:\n- name: a\n  port: 8080\n"
+        );
+    }
+
+    #[test]
+    fn yaml_keys_are_created_and_removed() {
+        let doc = "server:\n  host: 0.0.0.0\nlog: info\n";
+        let a = ConfigFileAction {
+            path: String::from("/etc/app/config.yml"),
+            format: Format::Yaml,
+            key: vec![
+                String::from("server"),
+                String::from("tls"),
+                String::from("enabled"),
+            ],
+            value: Some(serde_yaml::Value::from(true)),
+            remove: false,
+            create: false,
+            backup: false,
+            attributes: None,
+        };
+        assert_eq!(
+            a.edit(doc).unwrap().unwrap(),
+            "server:\n  host: 0.0.0.0\n  tls:\n    enabled: true\nlog: info\n"
+        );
+        let a = ConfigFileAction {
+            key: vec![String::from("server"), String::from("host")],
+            value: None,
+            remove: true,
+            ..a
+        };
+        assert_eq!(a.edit(doc).unwrap().unwrap(), "server: {}\nlog: info\n");
+        let a = ConfigFileAction {
+            key: vec![String::from("missing"), String::from("key")],
+            ..a
+        };
+        assert!(a.edit(doc).unwrap().is_none());
+    }
+
+    #[test]
+    fn toml_edits_keep_comments_and_formatting() {
+        let doc = "# app settings\ntitle = \"app\"\n\n[server]\n# listen port\nport = 80 # default\nhosts = [\n  \"a\",\n  \"b\",\n]\n";
+        let a = ConfigFileAction {
+            path: String::from("/etc/app/config.toml"),
+            format: Format::Toml,
+            key: vec![String::from("server"), String::from("port")],
+            value: Some(serde_yaml::Value::from(8080)),
+            remove: false,
+            create: false,
+            backup: false,
+            attributes: None,
+        };
+        let edited = a.edit(doc).unwrap().unwrap();
+        assert!(edited.starts_with(
+            "# app settings\ntitle = \"app\"\n\n[server]\n# listen port\nport = 8080 # default\n"
+        ));
+        assert!(edited.contains("hosts = [\n  \"a\",\n  \"b\",\n]\n"));
+        let a = ConfigFileAction {
+            key: vec![String::from("server"), String::from("hosts")],
+            value: Some(serde_yaml::from_str("[a, b]").unwrap()),
+            ..a
+        };
+        assert!(a.edit(doc).unwrap().is_none());
+        let a = ConfigFileAction {
+            key: vec![
+                String::from("database"),
+                String::from("pool"),
+                String::from("size"),
+            ],
+            value: Some(serde_yaml::Value::from(5)),
+            ..a
+        };
+        assert!(
+            a.edit(doc)
+                .unwrap()
+                .unwrap()
+                .ends_with("[database.pool]\nsize = 5\n")
+        );
+        let a = ConfigFileAction {
+            key: vec![String::from("title")],
+            value: None,
+            remove: true,
+            ..a
+        };
+        // the comments heading the file stay with the file
+        assert_eq!(
+            a.edit(doc).unwrap().unwrap(),
+            "# app settings\n\n[server]\n# listen port\nport = 80 # default\nhosts = [\n  \"a\",\n  \"b\",\n]\n"
+        );
+        // comments directly above a removed key belong to it and go with it, those
+        // set apart by a blank line do not
+        let a = ConfigFileAction {
+            key: vec![String::from("server"), String::from("port")],
+            ..a
+        };
+        assert_eq!(
+            a.edit(doc).unwrap().unwrap(),
+            "# app settings\ntitle = \"app\"\n\n[server]\nhosts = [\n  \"a\",\n  \"b\",\n]\n"
+        );
+        let doc =
+            "[server]\nport = 80\n\n# limits\n\n# seconds\ntimeout = 5\n\n[client]\nretries = 3\n";
+        let a = ConfigFileAction {
+            key: vec![String::from("server"), String::from("timeout")],
+            ..a
+        };
+        assert_eq!(
+            a.edit(doc).unwrap().unwrap(),
+            "[server]\nport = 80\n\n# limits\n\n\n[client]\nretries = 3\n"
+        );
+    }
+}
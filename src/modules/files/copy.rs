@@ -18,6 +18,7 @@ use crate::handle::handle::TaskHandle;
 use crate::tasks::fields::Field;
 use crate::tasks::files::Recurse;
 use crate::tasks::*;
+use crate::util::diff::unified;
 use serde::Deserialize;
 use std::path::{Path, PathBuf};
 use std::sync::Arc;
@@ -133,18 +134,29 @@ impl IsAction for CopyAction {
                     Recurse::No,
                 )?;
                 if remote_mode.is_none() {
-                    return Ok(handle.response.needs_creation(request));
+                    let response = handle.response.needs_creation(request);
+                    if !handle.response.wants_diff() {
+                        return Ok(response);
+                    }
+                    let diff = unified(&self.dest, None, &self.read_src(handle, request)?);
+                    return Ok(handle.response.with_diff(response, diff));
                 }
                 // this query leg is (at least originally) the same as the template module query except these two lines
                 // to calculate the checksum differently
                 let src_path = self.src.as_path();
                 let local_512 = handle.local.get_sha512(request, src_path, true)?;
                 let remote_512 = handle.remote.get_sha512(request, &self.dest)?;
+                let mut diff: Option<String> = None;
                 if !remote_512.eq(&local_512) {
                     changes.push(Field::Content);
+                    if handle.response.wants_diff() {
+                        let data = self.read_src(handle, request)?;
+                        diff = handle.remote.diff_file(request, &self.dest, &data)?;
+                    }
                 }
                 if !changes.is_empty() {
-                    return Ok(handle.response.needs_modification(request, &changes));
+                    let response = handle.response.needs_modification(request, &changes);
+                    return Ok(handle.response.with_diff(response, diff));
                 }
                 Ok(handle.response.is_matched(request))
             }
@@ -184,6 +196,21 @@ impl IsAction for CopyAction {
 }
 
 impl CopyAction {
+    // local source bytes, only read for --diff since the transfer itself streams the file
+
+    fn read_src(
+        &self,
+        handle: &Arc<TaskHandle>,
+        request: &Arc<TaskRequest>,
+    ) -> Result<Vec<u8>, Arc<TaskResponse>> {
+        std::fs::read(&self.src).map_err(|e| {
+            handle.response.is_failed(
+                request,
+                &format!("failed to read '{}': {}", self.src.display(), e),
+            )
+        })
+    }
+
     pub fn do_copy(
         &self,
         handle: &Arc<TaskHandle>,
@@ -195,13 +222,12 @@ impl CopyAction {
         }
         handle
             .remote
-            .copy_file(request, &self.src, &self.dest, |f| {
+            .copy_file(request, &self.src, &self.dest, &self.attributes, |f| {
                 /* after save */
-                match handle.remote.process_all_common_file_attributes(
+                match handle.remote.process_transferred_file_attributes(
                     request,
                     f,
                     &self.attributes,
-                    Recurse::No,
                 ) {
                     Ok(_x) => Ok(()),
                     Err(y) => Err(y),
@@ -252,7 +278,7 @@ impl CopyAction {
             } else if local_path.is_file() {
                 handle
                     .remote
-                    .copy_file(request, &local_path, &remote_path, |_f| Ok(()))?;
+                    .copy_file(request, &local_path, &remote_path, &None, |_f| Ok(()))?;
             }
             // Symlinks and other special files are intentionally skipped.
         }
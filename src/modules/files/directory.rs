@@ -30,8 +30,8 @@ const MODULE: &str = "directory";
 pub struct DirectoryTask {
     pub name: Option<String>,
     pub path: String,
-    pub remove: Option<String>,
-    pub recurse: Option<String>,
+    pub remove: Option<Templated<bool>>,
+    pub recurse: Option<Templated<bool>>,
     pub attributes: Option<FileAttributesInput>,
     pub with: Option<PreLogicInput>,
     pub and: Option<PostLogicInput>,
@@ -15,13 +15,23 @@
 // long with this program.  If not, see <http://www.gnu.org/licenses/>.
 
 use crate::handle::handle::{CheckRc, TaskHandle};
+use crate::tasks::checksum::Algorithm;
+use crate::tasks::cmd_library::shell_quote;
 use crate::tasks::fields::Field;
 use crate::tasks::*;
+use crate::util::redact::register_secret;
+use once_cell::sync::Lazy;
 use serde::Deserialize;
-use std::sync::Arc;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
 
 const MODULE: &str = "Download";
 
+// controller-side download cache, shared by every host in the run (and across runs
+// for checksum-pinned files)
+const CACHE_ENV: &str = "JET_DOWNLOAD_CACHE";
+
 #[derive(Deserialize, Debug)]
 #[serde(deny_unknown_fields)]
 #[derive(Default)]
@@ -32,7 +42,12 @@ pub struct DownloadTask {
     pub mode: Option<String>,
     pub owner: Option<String>,
     pub group: Option<String>,
-    pub force: Option<String>,
+    pub force: Option<Templated<bool>>,
+    pub checksum: Option<String>,
+    pub cache: Option<Templated<bool>>,
+    pub headers: Option<HashMap<String, String>>,
+    pub username: Option<String>,
+    pub password: Option<String>,
     pub with: Option<PreLogicInput>,
     pub and: Option<PostLogicInput>,
 }
@@ -44,6 +59,80 @@ struct DownloadAction {
     pub owner: Option<String>,
     pub group: Option<String>,
     pub force: bool,
+    pub checksum: Option<Checksum>,
+    pub cache: bool,
+    pub http: HttpOptions,
+}
+
+// the headers and credentials sent with the download and with any checksum file fetch
+
+#[derive(Default)]
+struct HttpOptions {
+    headers: Vec<(String, String)>,
+    auth: Option<(String, String)>,
+}
+
+impl HttpOptions {
+    // a digest of the headers and credentials, so cache entries are never shared
+    // between requests that identify themselves differently
+    fn identity(&self) -> String {
+        let mut headers: Vec<String> = self
+            .headers
+            .iter()
+            .map(|(name, value)| format!("{}: {}\n", name.to_ascii_lowercase(), value))
+            .collect();
+        headers.sort();
+        let mut data = headers.concat();
+        if let Some((username, password)) = &self.auth {
+            data.push_str(&format!("auth {}:{}\n", username, password));
+        }
+        Algorithm::Sha256.digest(data.as_bytes())[..16].to_string()
+    }
+}
+
+// checksum is either an inline digest (sha256:abc...) or a URL to a checksum file
+// (sha256:https://.../SHA256SUMS) that lists the digest next to the file name
+
+#[derive(Debug, PartialEq)]
+struct Checksum {
+    algorithm: Algorithm,
+    source: ChecksumSource,
+}
+
+#[derive(Debug, PartialEq)]
+enum ChecksumSource {
+    Digest(String),
+    File(String),
+}
+
+impl Checksum {
+    fn parse(spec: &str) -> Result<Self, String> {
+        let (name, rest) = spec.split_once(':').ok_or_else(|| {
+            format!(
+                "checksum must look like sha256:<digest> or sha256:<url>, got '{}'",
+                spec
+            )
+        })?;
+        let algorithm = Algorithm::from_name(name)?;
+        let rest = rest.trim();
+        if rest.starts_with("http://") || rest.starts_with("https://") {
+            return Ok(Checksum {
+                algorithm,
+                source: ChecksumSource::File(rest.to_string()),
+            });
+        }
+        match algorithm.is_digest(rest) {
+            true => Ok(Checksum {
+                algorithm,
+                source: ChecksumSource::Digest(rest.to_ascii_lowercase()),
+            }),
+            false => Err(format!(
+                "'{}' is not a valid {} digest",
+                rest,
+                algorithm.name()
+            )),
+        }
+    }
 }
 
 impl IsTask for DownloadTask {
@@ -63,9 +152,13 @@ impl IsTask for DownloadTask {
         request: &Arc<TaskRequest>,
         tm: TemplateMode,
     ) -> Result<EvaluatedTask, Arc<TaskResponse>> {
-        let url = handle
-            .template
-            .string(request, tm, &String::from("url"), &self.url)?;
+        // urls often carry query strings (signed mirror links), they are always quoted
+        let url = handle.template.string_unsafe_for_shell(
+            request,
+            tm,
+            &String::from("url"),
+            &self.url,
+        )?;
         let dest = handle
             .template
             .path(request, tm, &String::from("dest"), &self.dest)?;
@@ -97,14 +190,67 @@ impl IsTask for DownloadTask {
             None => None,
         };
 
-        let force = match &self.force {
-            Some(f) => {
-                let force_str = handle
-                    .template
-                    .string(request, tm, &String::from("force"), f)?;
-                force_str == "true" || force_str == "yes" || force_str == "1"
+        let force = handle.template.boolean_option_default_false(
+            request,
+            tm,
+            &String::from("force"),
+            &self.force,
+        )?;
+
+        // the checksum never reaches a shell: digests are validated as hex and
+        // checksum file URLs are fetched from the controller
+        let checksum = match handle.template.string_option_unsafe_for_shell(
+            request,
+            tm,
+            &String::from("checksum"),
+            &self.checksum,
+        )? {
+            Some(spec) if tm == TemplateMode::Strict => {
+                Some(Checksum::parse(&spec).map_err(|e| handle.response.is_failed(request, &e))?)
             }
-            None => false,
+            _ => None,
+        };
+
+        let mut headers: Vec<(String, String)> = Vec::new();
+        if let Some(input_headers) = &self.headers {
+            for (k, v) in input_headers.iter() {
+                let value = handle.template.string_unsafe_for_shell(
+                    request,
+                    tm,
+                    &format!("headers.{}", k),
+                    v,
+                )?;
+                // typically a token, and it ends up on the curl command line
+                register_secret(&value);
+                headers.push((k.clone(), value));
+            }
+        }
+        headers.sort();
+
+        let username = handle.template.string_option_unsafe_for_shell(
+            request,
+            tm,
+            &String::from("username"),
+            &self.username,
+        )?;
+        let password = handle.template.string_option_unsafe_for_shell(
+            request,
+            tm,
+            &String::from("password"),
+            &self.password,
+        )?;
+        let auth = match (username, password) {
+            (Some(u), p) => {
+                let p = p.unwrap_or_default();
+                register_secret(&p);
+                Some((u, p))
+            }
+            (None, Some(_)) => {
+                return Err(handle
+                    .response
+                    .is_failed(request, "password requires username"));
+            }
+            (None, None) => None,
         };
 
         Ok(EvaluatedTask {
@@ -115,6 +261,14 @@ impl IsTask for DownloadTask {
                 owner,
                 group,
                 force,
+                checksum,
+                cache: handle.template.boolean_option_default_false(
+                    request,
+                    tm,
+                    &String::from("cache"),
+                    &self.cache,
+                )?,
+                http: HttpOptions { headers, auth },
             }),
             with: Arc::new(PreLogicInput::template(handle, request, tm, &self.with)?),
             and: Arc::new(PostLogicInput::template(handle, request, tm, &self.and)?),
@@ -140,22 +294,25 @@ impl IsAction for DownloadAction {
                     Err(_) => false,
                 };
 
-                if exists && !self.force {
-                    // File already exists and force is not set
-                    Ok(handle.response.is_matched(request))
-                } else {
-                    // File doesn't exist or force is set
-                    let mut changes = vec![Field::Content];
-                    if self.mode.is_some() {
-                        changes.push(Field::Mode);
-                    }
-                    if self.owner.is_some() {
-                        changes.push(Field::Owner);
-                    }
-                    if self.group.is_some() {
-                        changes.push(Field::Group);
+                if !exists || self.force {
+                    return Ok(handle.response.needs_modification(request, &self.changes()));
+                }
+                match &self.checksum {
+                    // without a checksum an existing file is trusted as-is
+                    None => Ok(handle.response.is_matched(request)),
+                    Some(checksum) => {
+                        let expected = self.expected_digest(handle, request, checksum)?;
+                        let actual =
+                            handle
+                                .remote
+                                .get_checksum(request, &self.dest, checksum.algorithm)?;
+                        match actual.eq_ignore_ascii_case(&expected) {
+                            true => Ok(handle.response.is_matched(request)),
+                            false => {
+                                Ok(handle.response.needs_modification(request, &self.changes()))
+                            }
+                        }
                     }
-                    Ok(handle.response.needs_modification(request, &changes))
                 }
             }
 
@@ -169,33 +326,46 @@ impl IsAction for DownloadAction {
                     handle.remote.run(request, &mkdir_cmd, CheckRc::Checked)?;
                 }
 
-                // Check if curl is available (required for !download)
-                let has_curl = match handle.remote.run(
-                    request,
-                    &String::from("command -v curl"),
-                    CheckRc::Unchecked,
-                ) {
-                    Ok(result) => {
-                        let (rc, _) = cmd_info(&result);
-                        rc == 0
-                    }
-                    Err(_) => false,
-                };
+                // everything happens on a temporary file next to dest, which is only renamed
+                // into place once it is verified and has its final permissions
+                let temp = format!("{}.jetpack-download", self.dest);
 
-                if !has_curl {
-                    return Err(handle.response.is_failed(request,
-                        &String::from("curl is required for !download but not found. Install curl first: apt install curl")));
+                if self.cache {
+                    let local = self.fetch_to_cache(handle, request)?;
+                    handle
+                        .remote
+                        .copy_file(request, &local, &temp, &None, |_| Ok(()))?;
+                } else {
+                    self.curl(handle, request, &temp)?;
                 }
 
-                // Download using curl on the target host
-                let download_cmd = format!("curl -fsSL -o '{}' '{}'", self.dest, self.url);
-                handle
-                    .remote
-                    .run(request, &download_cmd, CheckRc::Checked)?;
+                if let Some(checksum) = &self.checksum {
+                    let expected = self.expected_digest(handle, request, checksum)?;
+                    let actual = handle
+                        .remote
+                        .get_checksum(request, &temp, checksum.algorithm)?;
+                    if !actual.eq_ignore_ascii_case(&expected) {
+                        handle.remote.run(
+                            request,
+                            &format!("rm -f '{}'", temp),
+                            CheckRc::Unchecked,
+                        )?;
+                        return Err(handle.response.is_failed(
+                            request,
+                            &format!(
+                                "checksum mismatch for {}: expected {}:{}, got {}",
+                                self.url,
+                                checksum.algorithm.name(),
+                                expected,
+                                actual
+                            ),
+                        ));
+                    }
+                }
 
                 // Apply permissions if specified
                 if let Some(ref mode) = self.mode {
-                    let chmod_cmd = format!("chmod {} '{}'", mode, self.dest);
+                    let chmod_cmd = format!("chmod {} '{}'", mode, temp);
                     handle.remote.run(request, &chmod_cmd, CheckRc::Checked)?;
                 }
 
@@ -207,24 +377,411 @@ impl IsAction for DownloadAction {
                         (None, Some(g)) => format!(":{}", g),
                         (None, None) => unreachable!(),
                     };
-                    let chown_cmd = format!("chown {} '{}'", owner_str, self.dest);
+                    let chown_cmd = format!("chown {} '{}'", owner_str, temp);
                     handle.remote.run(request, &chown_cmd, CheckRc::Checked)?;
                 }
 
-                let mut changes = vec![Field::Content];
-                if self.mode.is_some() {
-                    changes.push(Field::Mode);
-                }
-                if self.owner.is_some() {
-                    changes.push(Field::Owner);
-                }
-                if self.group.is_some() {
-                    changes.push(Field::Group);
-                }
-                Ok(handle.response.is_modified(request, changes))
+                let mv_cmd = format!("mv -f '{}' '{}'", temp, self.dest);
+                handle.remote.run(request, &mv_cmd, CheckRc::Checked)?;
+
+                Ok(handle.response.is_modified(request, self.changes()))
             }
 
             _ => Err(handle.response.not_supported(request)),
         }
     }
 }
+
+impl DownloadAction {
+    fn changes(&self) -> Vec<Field> {
+        let mut changes = vec![Field::Content];
+        if self.mode.is_some() {
+            changes.push(Field::Mode);
+        }
+        if self.owner.is_some() {
+            changes.push(Field::Owner);
+        }
+        if self.group.is_some() {
+            changes.push(Field::Group);
+        }
+        changes
+    }
+
+    // download directly on the target host
+
+    fn curl(
+        &self,
+        handle: &Arc<TaskHandle>,
+        request: &Arc<TaskRequest>,
+        temp: &str,
+    ) -> Result<(), Arc<TaskResponse>> {
+        // Check if curl is available (required for !download)
+        let has_curl = match handle.remote.run(
+            request,
+            &String::from("command -v curl"),
+            CheckRc::Unchecked,
+        ) {
+            Ok(result) => {
+                let (rc, _) = cmd_info(&result);
+                rc == 0
+            }
+            Err(_) => false,
+        };
+
+        if !has_curl {
+            return Err(handle.response.is_failed(request,
+                &String::from("curl is required for !download but not found. Install curl first: apt install curl")));
+        }
+
+        // header values and credentials are free text, so they are quoted and the
+        // command is run without the usual screening
+        let mut options = String::new();
+        for (k, v) in self.http.headers.iter() {
+            options.push_str(&format!(" -H {}", shell_quote(&format!("{}: {}", k, v))));
+        }
+        if let Some((user, password)) = &self.http.auth {
+            options.push_str(&format!(
+                " -u {}",
+                shell_quote(&format!("{}:{}", user, password))
+            ));
+        }
+        let download_cmd = format!(
+            "curl -fsSL{} -o {} {}",
+            options,
+            shell_quote(temp),
+            shell_quote(&self.url)
+        );
+        handle
+            .remote
+            .run_unsafe(request, &download_cmd, CheckRc::Checked)?;
+        Ok(())
+    }
+
+    fn expected_digest(
+        &self,
+        handle: &Arc<TaskHandle>,
+        request: &Arc<TaskRequest>,
+        checksum: &Checksum,
+    ) -> Result<String, Arc<TaskResponse>> {
+        match &checksum.source {
+            ChecksumSource::Digest(digest) => Ok(digest.clone()),
+            ChecksumSource::File(url) => {
+                checksum_from_file(url, checksum.algorithm, &self.url, &self.http)
+                    .map_err(|e| handle.response.is_failed(request, &e))
+            }
+        }
+    }
+
+    fn fetch_to_cache(
+        &self,
+        handle: &Arc<TaskHandle>,
+        request: &Arc<TaskRequest>,
+    ) -> Result<PathBuf, Arc<TaskResponse>> {
+        let expected = match &self.checksum {
+            Some(checksum) => Some((
+                checksum.algorithm,
+                self.expected_digest(handle, request, checksum)?,
+            )),
+            None => None,
+        };
+        cached_download(&cache_dir(), &self.url, &self.http, expected.as_ref())
+            .map_err(|e| handle.response.is_failed(request, &e))
+    }
+}
+
+fn cache_dir() -> PathBuf {
+    match std::env::var(CACHE_ENV) {
+        Ok(dir) => PathBuf::from(dir),
+        Err(_) => {
+            let home = std::env::var("HOME").unwrap_or_else(|_| String::from("/tmp"));
+            PathBuf::from(home).join(".cache/jetpack/downloads")
+        }
+    }
+}
+
+// one entry per cache key, true once the cached file is known good for this run.
+// Holding the entry's lock while downloading keeps parallel hosts from fetching the
+// same URL at once: the first host downloads, the rest wait and reuse the file.
+static CACHE_ENTRIES: Lazy<Mutex<HashMap<String, Arc<Mutex<bool>>>>> =
+    Lazy::new(|| Mutex::new(HashMap::new()));
+
+// checksum files are fetched once per run, keyed by URL and request identity
+static CHECKSUM_FILES: Lazy<Mutex<HashMap<String, String>>> =
+    Lazy::new(|| Mutex::new(HashMap::new()));
+
+// files pinned by a checksum are stored by digest and reused across runs, even under
+// force, since the digest proves the copy is right. Nothing proves an unpinned copy is
+// still current, so those are downloaded afresh by the first host of every run.
+
+fn cached_download(
+    dir: &Path,
+    url: &str,
+    http: &HttpOptions,
+    expected: Option<&(Algorithm, String)>,
+) -> Result<PathBuf, String> {
+    let key = match expected {
+        Some((algorithm, digest)) => {
+            format!("{}-{}-{}", algorithm.name(), digest, http.identity())
+        }
+        None => format!(
+            "url-{}-{}",
+            Algorithm::Sha256.digest(url.as_bytes()),
+            http.identity()
+        ),
+    };
+    let path = dir.join(&key);
+    let entry = Arc::clone(
+        CACHE_ENTRIES
+            .lock()
+            .unwrap()
+            .entry(path.display().to_string())
+            .or_insert_with(|| Arc::new(Mutex::new(false))),
+    );
+    let mut ready = entry.lock().unwrap();
+    if *ready {
+        return Ok(path);
+    }
+
+    let usable = path.is_file()
+        && match expected {
+            Some((algorithm, digest)) => std::fs::read(&path)
+                .map(|data| algorithm.digest(&data) == *digest)
+                .unwrap_or(false),
+            None => false,
+        };
+    if !usable {
+        let data = http_get(url, http)?;
+        if let Some((algorithm, digest)) = expected {
+            let actual = algorithm.digest(&data);
+            if actual != *digest {
+                return Err(format!(
+                    "checksum mismatch for {}: expected {}:{}, got {}",
+                    url,
+                    algorithm.name(),
+                    digest,
+                    actual
+                ));
+            }
+        }
+        std::fs::create_dir_all(dir)
+            .map_err(|e| format!("failed to create {}: {}", dir.display(), e))?;
+        let partial = path.with_extension("part");
+        std::fs::write(&partial, &data)
+            .and_then(|_| std::fs::rename(&partial, &path))
+            .map_err(|e| format!("failed to write {}: {}", path.display(), e))?;
+    }
+    *ready = true;
+    Ok(path)
+}
+
+fn checksum_from_file(
+    checksum_url: &str,
+    algorithm: Algorithm,
+    url: &str,
+    http: &HttpOptions,
+) -> Result<String, String> {
+    let key = format!("{} {}", http.identity(), checksum_url);
+    let cached = CHECKSUM_FILES.lock().unwrap().get(&key).cloned();
+    let body = match cached {
+        Some(body) => body,
+        None => {
+            let data = http_get(checksum_url, http)?;
+            let body = String::from_utf8_lossy(&data).to_string();
+            CHECKSUM_FILES.lock().unwrap().insert(key, body.clone());
+            body
+        }
+    };
+    let file_name = url_file_name(url);
+    find_digest(&body, algorithm, file_name).ok_or_else(|| {
+        format!(
+            "no {} digest for {} found in {}",
+            algorithm.name(),
+            file_name,
+            checksum_url
+        )
+    })
+}
+
+fn url_file_name(url: &str) -> &str {
+    let path = url.split(['?', '#']).next().unwrap_or(url);
+    path.rsplit('/').next().unwrap_or(path)
+}
+
+// understands GNU coreutils output ("<digest>  <name>", "<digest> *<name>"), BSD
+// output ("SHA256 (<name>) = <digest>") and files holding nothing but the digest
+
+fn find_digest(body: &str, algorithm: Algorithm, file_name: &str) -> Option<String> {
+    let mut lone: Vec<String> = Vec::new();
+    for line in body.lines().map(str::trim) {
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        if let Some((name, digest)) = line.split_once(") = ")
+            && let Some((_, name)) = name.split_once(" (")
+        {
+            if url_file_name(name) == file_name && algorithm.is_digest(digest.trim()) {
+                return Some(digest.trim().to_ascii_lowercase());
+            }
+            continue;
+        }
+        let mut tokens = line.split_whitespace();
+        let digest = tokens.next().unwrap_or_default();
+        if !algorithm.is_digest(digest) {
+            continue;
+        }
+        match tokens.next() {
+            Some(name) if url_file_name(name.trim_start_matches('*')) == file_name => {
+                return Some(digest.to_ascii_lowercase());
+            }
+            Some(_) => {}
+            None => lone.push(digest.to_ascii_lowercase()),
+        }
+    }
+    match lone.len() {
+        1 => lone.pop(),
+        _ => None,
+    }
+}
+
+fn http_get(url: &str, http: &HttpOptions) -> Result<Vec<u8>, String> {
+    crate::runtime::block_on(async {
+        let client = reqwest::Client::builder()
+            .user_agent("Jetpack/0.1")
+            .build()
+            .map_err(|e| format!("failed to create HTTP client: {}", e))?;
+        let mut builder = client.get(url);
+        for (k, v) in http.headers.iter() {
+            builder = builder.header(k.as_str(), v.as_str());
+        }
+        if let Some((user, password)) = &http.auth {
+            builder = builder.basic_auth(user, Some(password));
+        }
+        let response = builder
+            .send()
+            .await
+            .map_err(|e| format!("failed to download {}: {}", url, e))?;
+        if !response.status().is_success() {
+            return Err(format!(
+                "failed to download {}: HTTP {}",
+                url,
+                response.status()
+            ));
+        }
+        response
+            .bytes()
+            .await
+            .map(|b| b.to_vec())
+            .map_err(|e| format!("failed to download {}: {}", url, e))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::provisioners::dragonfly::tests::MockServer;
+
+    const BODY: &str = "release payload\n";
+
+    fn sha256(data: &str) -> String {
+        Algorithm::Sha256.digest(data.as_bytes())
+    }
+
+    #[test]
+    fn checksum_specs_parse_digests_and_urls() {
+        let digest = sha256(BODY);
+        assert_eq!(
+            Checksum::parse(&format!("sha256:{}", digest.to_uppercase())).unwrap(),
+            Checksum {
+                algorithm: Algorithm::Sha256,
+                source: ChecksumSource::Digest(digest),
+            }
+        );
+        assert_eq!(
+            Checksum::parse("sha512:https://example.com/SHA512SUMS").unwrap(),
+            Checksum {
+                algorithm: Algorithm::Sha512,
+                source: ChecksumSource::File(String::from("https://example.com/SHA512SUMS")),
+            }
+        );
+        assert!(Checksum::parse("md5:abc").is_err());
+        assert!(Checksum::parse("sha256:abc").is_err());
+        assert!(Checksum::parse("deadbeef").is_err());
+    }
+
+    #[test]
+    fn digests_are_found_in_gnu_bsd_and_single_value_files() {
+        let a = sha256("a");
+        let b = sha256("b");
+        let gnu = format!("{}  app-1.0.tar.gz\n{} *app-1.1.tar.gz\n", a, b);
+        assert_eq!(
+            find_digest(&gnu, Algorithm::Sha256, "app-1.1.tar.gz"),
+            Some(b.clone())
+        );
+        assert_eq!(find_digest(&gnu, Algorithm::Sha256, "other.tar.gz"), None);
+
+        let bsd = format!("SHA256 (app-1.0.tar.gz) = {}\n", a);
+        assert_eq!(
+            find_digest(&bsd, Algorithm::Sha256, "app-1.0.tar.gz"),
+            Some(a.clone())
+        );
+
+        assert_eq!(
+            find_digest(&format!("{}\n", b), Algorithm::Sha256, "x"),
+            Some(b)
+        );
+        assert_eq!(url_file_name("https://e.com/dl/app.tgz?token=1"), "app.tgz");
+    }
+
+    #[test]
+    fn cached_downloads_are_verified_and_fetched_once() {
+        let server = MockServer::start(|_| (200, String::from(BODY)));
+        let dir = tempfile::TempDir::new().unwrap();
+        let url = format!("{}/app.tgz", server.url());
+        let http = HttpOptions {
+            headers: vec![(String::from("X-Token"), String::from("abc"))],
+            auth: Some((String::from("deploy"), String::from("hunter22"))),
+        };
+        let expected = (Algorithm::Sha256, sha256(BODY));
+
+        let first = cached_download(dir.path(), &url, &http, Some(&expected)).unwrap();
+        let second = cached_download(dir.path(), &url, &http, Some(&expected)).unwrap();
+        assert_eq!(first, second);
+        assert_eq!(std::fs::read_to_string(&first).unwrap(), BODY);
+        let recorded = server.recorded();
+        assert_eq!(recorded.len(), 1);
+        // "deploy:hunter22", as recorded (lowercased) by the mock
+        assert_eq!(
+            recorded[0].auth.as_deref(),
+            Some("basic zgvwbg95omh1bnrlcjiy")
+        );
+
+        let wrong = (Algorithm::Sha256, sha256("something else"));
+        let err = cached_download(dir.path(), &url, &http, Some(&wrong)).unwrap_err();
+        assert!(err.starts_with("checksum mismatch"), "{}", err);
+    }
+
+    #[test]
+    fn cache_entries_are_kept_apart_by_credentials() {
+        let server = MockServer::start(|_| (200, String::from(BODY)));
+        let dir = tempfile::TempDir::new().unwrap();
+        let url = format!("{}/latest.tgz", server.url());
+        let deploy = HttpOptions {
+            headers: vec![],
+            auth: Some((String::from("deploy"), String::from("hunter22"))),
+        };
+        let anonymous = HttpOptions::default();
+
+        let first = cached_download(dir.path(), &url, &deploy, None).unwrap();
+        let second = cached_download(dir.path(), &url, &anonymous, None).unwrap();
+        assert_ne!(first, second);
+        assert_eq!(server.recorded().len(), 2);
+        assert_ne!(
+            deploy.identity(),
+            HttpOptions {
+                headers: vec![(String::from("X-Token"), String::from("abc"))],
+                auth: None,
+            }
+            .identity()
+        );
+    }
+}
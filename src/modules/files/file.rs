@@ -30,7 +30,7 @@ const MODULE: &str = "file";
 pub struct FileTask {
     pub name: Option<String>,
     pub path: String,
-    pub remove: Option<String>,
+    pub remove: Option<Templated<bool>>,
     pub attributes: Option<FileAttributesInput>,
     pub with: Option<PreLogicInput>,
     pub and: Option<PostLogicInput>,
@@ -0,0 +1,422 @@
+// Jetporch
+// Copyright (C) 2023 - Michael DeHaan <michael@michaeldehaan.net> + contributors
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// long with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use crate::handle::handle::TaskHandle;
+use crate::modules::files::synchronize::glob_to_regex;
+use crate::tasks::files::Recurse;
+use crate::tasks::*;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use std::vec::Vec;
+
+const MODULE: &str = "find";
+
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+#[derive(Default)]
+pub struct FindTask {
+    pub name: Option<String>,
+    pub path: String,
+    pub patterns: Option<Vec<String>>,
+    pub age: Option<String>,
+    pub size: Option<String>,
+    #[serde(rename = "type")]
+    pub file_type: Option<String>,
+    pub recurse: Option<Templated<bool>>,
+    pub save: String,
+    pub with: Option<PreLogicInput>,
+    pub and: Option<PostLogicInput>,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+enum FileType {
+    File,
+    Directory,
+    Link,
+    Any,
+}
+
+struct FindAction {
+    pub path: String,
+    pub patterns: Vec<Regex>,
+    // seconds; positive means at least this old, negative means newer than this
+    pub age: Option<i64>,
+    // bytes; positive means at least this large, negative means smaller than this
+    pub size: Option<i64>,
+    pub file_type: FileType,
+    pub recurse: Recurse,
+    pub save: String,
+}
+
+#[derive(Serialize, Debug, PartialEq)]
+struct FoundFile {
+    pub path: String,
+    pub size: u64,
+    pub mtime: i64,
+    pub mode: String,
+    pub owner: String,
+}
+
+impl IsTask for FindTask {
+    fn get_module(&self) -> String {
+        String::from(MODULE)
+    }
+    fn get_name(&self) -> Option<String> {
+        self.name.clone()
+    }
+    fn get_with(&self) -> Option<PreLogicInput> {
+        self.with.clone()
+    }
+
+    fn evaluate(
+        &self,
+        handle: &Arc<TaskHandle>,
+        request: &Arc<TaskRequest>,
+        tm: TemplateMode,
+    ) -> Result<EvaluatedTask, Arc<TaskResponse>> {
+        // patterns are matched here rather than by the remote shell, so they may hold globs
+        let mut patterns: Vec<Regex> = Vec::new();
+        for pattern in self.patterns.as_deref().unwrap_or_default() {
+            let templated = handle.template.string_unsafe_for_shell(
+                request,
+                tm,
+                &String::from("patterns"),
+                pattern,
+            )?;
+            patterns.push(glob_to_regex(&templated).map_err(|e| {
+                handle
+                    .response
+                    .is_failed(request, &format!("field patterns: {}", e))
+            })?);
+        }
+
+        let age = self.quantity(handle, request, tm, "age", &self.age, parse_age)?;
+        let size = self.quantity(handle, request, tm, "size", &self.size, parse_size)?;
+
+        let file_type = match handle
+            .template
+            .string_option_no_spaces(request, tm, &String::from("type"), &self.file_type)?
+            .as_deref()
+        {
+            None | Some("file") => FileType::File,
+            Some("directory") => FileType::Directory,
+            Some("link") => FileType::Link,
+            Some("any") => FileType::Any,
+            Some(_) if tm == TemplateMode::Off => FileType::File,
+            Some(other) => {
+                return Err(handle.response.is_failed(
+                    request,
+                    &format!(
+                        "field type: expected file, directory, link or any, got '{}'",
+                        other
+                    ),
+                ));
+            }
+        };
+
+        Ok(EvaluatedTask {
+            action: Arc::new(FindAction {
+                path: handle
+                    .template
+                    .path(request, tm, &String::from("path"), &self.path)?,
+                patterns,
+                age,
+                size,
+                file_type,
+                recurse: match handle.template.boolean_option_default_false(
+                    request,
+                    tm,
+                    &String::from("recurse"),
+                    &self.recurse,
+                )? {
+                    true => Recurse::Yes,
+                    false => Recurse::No,
+                },
+                save: handle.template.string_no_spaces(
+                    request,
+                    tm,
+                    &String::from("save"),
+                    &self.save,
+                )?,
+            }),
+            with: Arc::new(PreLogicInput::template(handle, request, tm, &self.with)?),
+            and: Arc::new(PostLogicInput::template(handle, request, tm, &self.and)?),
+        })
+    }
+}
+
+impl FindTask {
+    // templates and parses age or size, which only render to something parseable in strict mode
+
+    fn quantity(
+        &self,
+        handle: &Arc<TaskHandle>,
+        request: &Arc<TaskRequest>,
+        tm: TemplateMode,
+        field: &str,
+        input: &Option<String>,
+        parse: fn(&str) -> Result<i64, String>,
+    ) -> Result<Option<i64>, Arc<TaskResponse>> {
+        let value =
+            handle
+                .template
+                .string_option_no_spaces(request, tm, &String::from(field), input)?;
+        match value {
+            None => Ok(None),
+            Some(_) if tm == TemplateMode::Off => Ok(None),
+            Some(x) => parse(&x).map(Some).map_err(|e| {
+                handle
+                    .response
+                    .is_failed(request, &format!("field {}: {}", field, e))
+            }),
+        }
+    }
+}
+
+impl IsAction for FindAction {
+    fn dispatch(
+        &self,
+        handle: &Arc<TaskHandle>,
+        request: &Arc<TaskRequest>,
+    ) -> Result<Arc<TaskResponse>, Arc<TaskResponse>> {
+        match request.request_type {
+            TaskRequestType::Query => Ok(handle.response.needs_passive(request)),
+
+            TaskRequestType::Passive => {
+                if !handle.remote.get_is_directory(request, &self.path)? {
+                    return Err(handle
+                        .response
+                        .is_failed(request, &format!("{} is not a directory", self.path)));
+                }
+                let cmd = handle.remote.unwrap_string_result(
+                    request,
+                    &crate::tasks::cmd_library::get_find_command(
+                        handle.remote.get_os_type(),
+                        &self.path,
+                        self.recurse,
+                    ),
+                )?;
+                // find exits 1 when some directory below path could not be read, what it
+                // could read is still listed and the complaints are skipped by select
+                let result = handle
+                    .remote
+                    .run_unsafe(request, &cmd, CheckRc::Unchecked)?;
+                let (rc, out) = cmd_info(&result);
+                if rc > 1 {
+                    return Err(handle
+                        .response
+                        .command_failed(request, &result.command_result));
+                }
+                let found = self.select(&out);
+                save_results(handle, &self.save, found);
+                Ok(handle.response.is_passive(request))
+            }
+
+            _ => Err(handle.response.not_supported(request)),
+        }
+    }
+}
+
+impl FindAction {
+    // parses the listing from get_find_command and keeps the entries that pass every
+    // filter, sorted by path
+
+    fn select(&self, listing: &str) -> Vec<FoundFile> {
+        let mut lines = listing.lines();
+        let now: i64 = lines
+            .next()
+            .and_then(|x| x.trim().parse().ok())
+            .unwrap_or_default();
+        let mut found: Vec<FoundFile> = Vec::new();
+        for line in lines {
+            let fields: Vec<&str> = line.splitn(6, '\t').collect();
+            if fields.len() != 6 {
+                continue;
+            }
+            let (size, mtime) = match (fields[1].parse::<u64>(), fields[2].parse::<f64>()) {
+                (Ok(size), Ok(mtime)) => (size, mtime as i64),
+                _ => continue,
+            };
+            let path = fields[5];
+            let name = path.rsplit('/').next().unwrap_or(path);
+            if !self.matches_type(fields[0])
+                || !(self.patterns.is_empty() || self.patterns.iter().any(|re| re.is_match(name)))
+                || !threshold(self.age, now - mtime)
+                || !threshold(self.size, size as i64)
+            {
+                continue;
+            }
+            found.push(FoundFile {
+                path: path.to_string(),
+                size,
+                mtime,
+                mode: format!("0o{}", fields[3]),
+                owner: fields[4].to_string(),
+            });
+        }
+        found.sort_by(|a, b| a.path.cmp(&b.path));
+        found
+    }
+
+    // GNU find prints a single letter, BSD stat a description such as "Regular File"
+
+    fn matches_type(&self, kind: &str) -> bool {
+        let kind = match kind {
+            "Regular File" => "f",
+            "Directory" => "d",
+            "Symbolic Link" => "l",
+            other => other,
+        };
+        match self.file_type {
+            FileType::File => kind == "f",
+            FileType::Directory => kind == "d",
+            FileType::Link => kind == "l",
+            FileType::Any => true,
+        }
+    }
+}
+
+// "at least limit" for a positive limit, "less than -limit" for a negative one
+
+fn threshold(limit: Option<i64>, value: i64) -> bool {
+    match limit {
+        None => true,
+        Some(x) if x >= 0 => value >= x,
+        Some(x) => value < -x,
+    }
+}
+
+// a number with an optional unit suffix and an optional leading minus, e.g. 14d or -100m
+
+fn parse_quantity(input: &str, units: &[(char, i64)]) -> Result<i64, String> {
+    let (negative, rest) = match input.strip_prefix('-') {
+        Some(rest) => (true, rest),
+        None => (false, input),
+    };
+    let (digits, multiplier) = match rest.chars().last() {
+        Some(c) if c.is_ascii_alphabetic() => {
+            match units
+                .iter()
+                .find(|(unit, _)| *unit == c.to_ascii_lowercase())
+            {
+                Some((_, m)) => (&rest[..rest.len() - 1], *m),
+                None => return Err(format!("unknown unit '{}' in '{}'", c, input)),
+            }
+        }
+        _ => (rest, 1),
+    };
+    let value = digits
+        .parse::<i64>()
+        .ok()
+        .and_then(|number| number.checked_mul(multiplier))
+        .ok_or_else(|| format!("expected a number with an optional unit, got '{}'", input))?;
+    Ok(if negative { -value } else { value })
+}
+
+fn parse_age(input: &str) -> Result<i64, String> {
+    parse_quantity(
+        input,
+        &[
+            ('s', 1),
+            ('m', 60),
+            ('h', 3600),
+            ('d', 86400),
+            ('w', 604800),
+        ],
+    )
+}
+
+fn parse_size(input: &str) -> Result<i64, String> {
+    parse_quantity(
+        input,
+        &[
+            ('b', 1),
+            ('k', 1 << 10),
+            ('m', 1 << 20),
+            ('g', 1 << 30),
+            ('t', 1 << 40),
+        ],
+    )
+}
+
+fn save_results(handle: &Arc<TaskHandle>, key: &str, found: Vec<FoundFile>) {
+    let mut result = serde_yaml::Mapping::new();
+    // the following statement really can't fail.
+    let value = serde_yaml::to_value(found).expect("internal error: failed to serialize files");
+    result.insert(serde_yaml::Value::String(key.to_string()), value);
+    handle.host.write().unwrap().update_variables(result);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ages_and_sizes_take_units_and_a_sign() {
+        assert_eq!(parse_age("14d").unwrap(), 14 * 86400);
+        assert_eq!(parse_age("-2h").unwrap(), -7200);
+        assert_eq!(parse_age("90").unwrap(), 90);
+        assert_eq!(parse_size("100M").unwrap(), 100 << 20);
+        assert_eq!(parse_size("-1k").unwrap(), -1024);
+        assert!(parse_size("10q").is_err());
+        assert!(parse_age("d").is_err());
+        // too large to hold once the unit is applied
+        assert!(parse_size("99999999999999t").is_err());
+        assert!(parse_age("-9999999999999999w").is_err());
+    }
+
+    #[test]
+    fn listing_is_filtered_by_type_name_age_and_size() {
+        let action = FindAction {
+            path: String::from("/var/log/app"),
+            patterns: vec![glob_to_regex("*.log").unwrap()],
+            age: Some(14 * 86400),
+            size: Some(100 << 20),
+            file_type: FileType::File,
+            recurse: Recurse::Yes,
+            save: String::from("old_logs"),
+        };
+        let now = 1_700_000_000;
+        let old = now - 20 * 86400;
+        let listing = format!(
+            "{now}\n\
+             f\t209715200\t{old}.5\t644\tapp\t/var/log/app/b.log\n\
+             f\t209715200\t{new}.0\t644\tapp\t/var/log/app/fresh.log\n\
+             f\t1024\t{old}.0\t644\tapp\t/var/log/app/small.log\n\
+             f\t209715200\t{old}.0\t600\troot\t/var/log/app/core.dump\n\
+             d\t4096\t{old}.0\t755\tapp\t/var/log/app/x.log\n\
+             Regular File\t209715200\t{old}\t640\tapp\t/var/log/app/a b.log\n\
+             find: '/var/log/app/private': Permission denied\n",
+            now = now,
+            old = old,
+            new = now - 3600,
+        );
+        let found = action.select(&listing);
+        let paths: Vec<&str> = found.iter().map(|f| f.path.as_str()).collect();
+        assert_eq!(paths, vec!["/var/log/app/a b.log", "/var/log/app/b.log"]);
+        assert_eq!(
+            found[1],
+            FoundFile {
+                path: String::from("/var/log/app/b.log"),
+                size: 209715200,
+                mtime: old,
+                mode: String::from("0o644"),
+                owner: String::from("app"),
+            }
+        );
+    }
+}
@@ -15,6 +15,7 @@
 // long with this program.  If not, see <http://www.gnu.org/licenses/>.
 
 use crate::handle::handle::TaskHandle;
+use crate::tasks::cmd_library::shell_quote;
 use crate::tasks::fields::Field;
 use crate::tasks::files::Recurse;
 use crate::tasks::*;
@@ -34,8 +35,8 @@ pub struct GitTask {
     pub path: String,
     pub branch: Option<String>,
     pub ssh_options: Option<HashMap<String, String>>,
-    pub accept_keys: Option<String>,
-    pub update: Option<String>,
+    pub accept_keys: Option<Templated<bool>>,
+    pub update: Option<Templated<bool>>,
     pub attributes: Option<FileAttributesInput>,
     pub with: Option<PreLogicInput>,
     pub and: Option<PostLogicInput>,
@@ -225,10 +226,6 @@ impl IsAction for GitAction {
 impl GitAction {
     // BOOKMARK: fleshing this all out...
 
-    fn shell_quote(input: &str) -> String {
-        format!("'{}'", input.replace('\'', "'\"'\"'"))
-    }
-
     fn is_ssh_repo(&self) -> bool {
         self.repo.find("@").is_some() || self.repo.find("ssh://").is_some()
     }
@@ -255,7 +252,7 @@ impl GitAction {
         handle: &Arc<TaskHandle>,
         request: &Arc<TaskRequest>,
     ) -> Result<Option<String>, Arc<TaskResponse>> {
-        let cmd = format!("git -C {} rev-parse HEAD", Self::shell_quote(&self.path));
+        let cmd = format!("git -C {} rev-parse HEAD", shell_quote(&self.path));
         let result = handle
             .remote
             .run_unsafe(request, &cmd, CheckRc::Unchecked)?;
@@ -293,11 +290,7 @@ impl GitAction {
         request: &Arc<TaskRequest>,
     ) -> Result<(), Arc<TaskResponse>> {
         let ssh_options = self.get_ssh_options_string();
-        let cmd = format!(
-            "{} git -C {} pull",
-            ssh_options,
-            Self::shell_quote(&self.path)
-        );
+        let cmd = format!("{} git -C {} pull", ssh_options, shell_quote(&self.path));
         match self.is_ssh_repo() {
             true => handle
                 .remote
@@ -314,7 +307,7 @@ impl GitAction {
     ) -> Result<String, Arc<TaskResponse>> {
         let cmd = format!(
             "git -C {} rev-parse --abbrev-ref HEAD",
-            Self::shell_quote(&self.path)
+            shell_quote(&self.path)
         );
         let result = handle.remote.run_unsafe(request, &cmd, CheckRc::Checked)?;
         let (_rc, out) = cmd_info(&result);
@@ -331,8 +324,8 @@ impl GitAction {
         let cmd = format!(
             "{} git clone {} {}",
             ssh_options,
-            Self::shell_quote(&self.repo),
-            Self::shell_quote(&self.path)
+            shell_quote(&self.repo),
+            shell_quote(&self.path)
         );
         match self.is_ssh_repo() {
             true => handle
@@ -350,8 +343,8 @@ impl GitAction {
     ) -> Result<(), Arc<TaskResponse>> {
         let cmd = format!(
             "git -C {} switch {}",
-            Self::shell_quote(&self.path),
-            Self::shell_quote(&self.branch)
+            shell_quote(&self.path),
+            shell_quote(&self.branch)
         );
         handle.remote.run_unsafe(request, &cmd, CheckRc::Checked)?;
         Ok(())
@@ -367,14 +360,6 @@ impl GitAction {
 mod tests {
     use super::GitAction;
 
-    #[test]
-    fn shell_quote_handles_spaces_and_quotes() {
-        assert_eq!(
-            GitAction::shell_quote("/tmp/Application Support/it's-here"),
-            "'/tmp/Application Support/it'\"'\"'s-here'"
-        );
-    }
-
     #[test]
     fn https_repo_disables_git_ssh_command() {
         let action = GitAction {
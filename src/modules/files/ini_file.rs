@@ -0,0 +1,473 @@
+// Jetporch
+// Copyright (C) 2023 - Michael DeHaan <michael@michaeldehaan.net> + contributors
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// long with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use crate::handle::handle::TaskHandle;
+use crate::modules::files::lineinfile::{join_lines, line_ending, split_lines};
+use crate::tasks::fields::Field;
+use crate::tasks::files::Recurse;
+use crate::tasks::*;
+use crate::util::diff::unified;
+use serde::Deserialize;
+use std::sync::Arc;
+use std::vec::Vec;
+
+const MODULE: &str = "ini_file";
+
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+#[derive(Default)]
+pub struct IniFileTask {
+    pub name: Option<String>,
+    pub path: String,
+    pub section: Option<String>,
+    pub key: Option<String>,
+    pub value: Option<String>,
+    pub remove: Option<Templated<bool>>,
+    pub create: Option<Templated<bool>>,
+    pub backup: Option<Templated<bool>>,
+    pub attributes: Option<FileAttributesInput>,
+    pub with: Option<PreLogicInput>,
+    pub and: Option<PostLogicInput>,
+}
+
+struct IniFileAction {
+    pub path: String,
+    pub section: Option<String>,
+    pub key: Option<String>,
+    pub value: Option<String>,
+    pub remove: bool,
+    pub create: bool,
+    pub backup: bool,
+    pub attributes: Option<FileAttributesEvaluated>,
+}
+
+impl IsTask for IniFileTask {
+    fn get_module(&self) -> String {
+        String::from(MODULE)
+    }
+    fn get_name(&self) -> Option<String> {
+        self.name.clone()
+    }
+    fn get_with(&self) -> Option<PreLogicInput> {
+        self.with.clone()
+    }
+
+    fn evaluate(
+        &self,
+        handle: &Arc<TaskHandle>,
+        request: &Arc<TaskRequest>,
+        tm: TemplateMode,
+    ) -> Result<EvaluatedTask, Arc<TaskResponse>> {
+        let remove = handle.template.boolean_option_default_false(
+            request,
+            tm,
+            &String::from("remove"),
+            &self.remove,
+        )?;
+        let section = handle.template.string_option_unsafe_for_shell(
+            request,
+            tm,
+            &String::from("section"),
+            &self.section,
+        )?;
+        let key = handle.template.string_option_unsafe_for_shell(
+            request,
+            tm,
+            &String::from("key"),
+            &self.key,
+        )?;
+        let value = handle.template.string_option_unsafe_for_shell(
+            request,
+            tm,
+            &String::from("value"),
+            &self.value,
+        )?;
+        match (&section, &key, &value, remove) {
+            (None, None, _, true) => {
+                return Err(handle
+                    .response
+                    .is_failed(request, "remove needs a key, a section, or both"));
+            }
+            (_, None, _, false) => {
+                return Err(handle
+                    .response
+                    .is_failed(request, "key is required unless a section is removed"));
+            }
+            (_, Some(_), None, false) => {
+                return Err(handle
+                    .response
+                    .is_failed(request, "value is required unless remove is set"));
+            }
+            _ => {}
+        }
+
+        Ok(EvaluatedTask {
+            action: Arc::new(IniFileAction {
+                path: handle
+                    .template
+                    .path(request, tm, &String::from("path"), &self.path)?,
+                section,
+                key,
+                value,
+                remove,
+                create: handle.template.boolean_option_default_false(
+                    request,
+                    tm,
+                    &String::from("create"),
+                    &self.create,
+                )?,
+                backup: handle.template.boolean_option_default_false(
+                    request,
+                    tm,
+                    &String::from("backup"),
+                    &self.backup,
+                )?,
+                attributes: FileAttributesInput::template(handle, request, tm, &self.attributes)?,
+            }),
+            with: Arc::new(PreLogicInput::template(handle, request, tm, &self.with)?),
+            and: Arc::new(PostLogicInput::template(handle, request, tm, &self.and)?),
+        })
+    }
+}
+
+impl IsAction for IniFileAction {
+    fn dispatch(
+        &self,
+        handle: &Arc<TaskHandle>,
+        request: &Arc<TaskRequest>,
+    ) -> Result<Arc<TaskResponse>, Arc<TaskResponse>> {
+        match request.request_type {
+            TaskRequestType::Query => {
+                let content = match handle.remote.read_file(request, &self.path)? {
+                    Some(content) => content,
+                    None if self.remove => return Ok(handle.response.is_matched(request)),
+                    None if self.create => {
+                        let response = handle.response.needs_creation(request);
+                        let diff = match handle.response.wants_diff() {
+                            true => unified(
+                                &self.path,
+                                None,
+                                self.edit("").unwrap_or_default().as_bytes(),
+                            ),
+                            false => None,
+                        };
+                        return Ok(handle.response.with_diff(response, diff));
+                    }
+                    None => {
+                        return Err(handle.response.is_failed(
+                            request,
+                            &format!("{} does not exist (set create: true)", self.path),
+                        ));
+                    }
+                };
+                let mut changes: Vec<Field> = Vec::new();
+                handle.remote.query_common_file_attributes(
+                    request,
+                    &self.path,
+                    &self.attributes,
+                    &mut changes,
+                    Recurse::No,
+                )?;
+                let mut diff: Option<String> = None;
+                if let Some(data) = self.edit(&content) {
+                    changes.push(Field::Content);
+                    if handle.response.wants_diff() {
+                        diff = unified(&self.path, Some(content.as_bytes()), data.as_bytes());
+                    }
+                }
+                if !changes.is_empty() {
+                    let response = handle.response.needs_modification(request, &changes);
+                    return Ok(handle.response.with_diff(response, diff));
+                }
+                Ok(handle.response.is_matched(request))
+            }
+
+            TaskRequestType::Create => {
+                let data = self.edit("").unwrap_or_default();
+                handle
+                    .remote
+                    .write_data(request, &data, &self.path, &self.attributes, |f| {
+                        handle.remote.process_transferred_file_attributes(
+                            request,
+                            f,
+                            &self.attributes,
+                        )
+                    })?;
+                Ok(handle.response.is_created(request))
+            }
+
+            TaskRequestType::Modify => {
+                if request.changes.contains(&Field::Content) {
+                    let content = handle
+                        .remote
+                        .read_file(request, &self.path)?
+                        .unwrap_or_default();
+                    if let Some(data) = self.edit(&content) {
+                        if self.backup {
+                            handle.remote.backup_file(request, &self.path)?;
+                        }
+                        handle.remote.write_data_in_place(
+                            request,
+                            &data,
+                            &self.path,
+                            &self.attributes,
+                        )?;
+                    }
+                } else {
+                    handle.remote.process_common_file_attributes(
+                        request,
+                        &self.path,
+                        &self.attributes,
+                        &request.changes,
+                        Recurse::No,
+                    )?;
+                }
+                Ok(handle
+                    .response
+                    .is_modified(request, request.changes.clone()))
+            }
+
+            _ => Err(handle.response.not_supported(request)),
+        }
+    }
+}
+
+impl IniFileAction {
+    // the new file content, or None when the file already says what it should.
+    // Only the lines for the managed key (or section) are touched; comments,
+    // ordering and spacing elsewhere in the file are kept as they are.
+
+    fn edit(&self, content: &str) -> Option<String> {
+        let mut lines = split_lines(content);
+        let eol = line_ending(content);
+        let bounds = section_bounds(&lines, self.section.as_deref());
+
+        let key = match &self.key {
+            Some(key) => key,
+            None => {
+                // remove the whole section, header included
+                let (start, end) = bounds?;
+                lines.drain(start - 1..end);
+                return Some(join_lines(&lines, eol));
+            }
+        };
+
+        if self.remove {
+            let (start, end) = bounds?;
+            let before = lines.len();
+            let mut index = start;
+            let mut end = end;
+            while index < end {
+                match key_value(&lines[index], key) {
+                    Some(_) => {
+                        lines.remove(index);
+                        end -= 1;
+                    }
+                    None => index += 1,
+                }
+            }
+            return match lines.len() == before {
+                true => None,
+                false => Some(join_lines(&lines, eol)),
+            };
+        }
+
+        let value = self.value.as_deref().unwrap_or_default();
+        match bounds {
+            Some((start, end)) => {
+                let found = (start..end).find(|i| key_value(&lines[*i], key).is_some());
+                match found {
+                    Some(index) => {
+                        if key_value(&lines[index], key) == Some(value) {
+                            return None;
+                        }
+                        let spaced = lines[index].contains(" =");
+                        lines[index] = format_line(key, value, spaced);
+                    }
+                    None => {
+                        // after the last non-blank line of the section
+                        let mut at = end;
+                        while at > start && lines[at - 1].trim().is_empty() {
+                            at -= 1;
+                        }
+                        lines.insert(at, format_line(key, value, true));
+                    }
+                }
+            }
+            None => {
+                if lines.last().is_some_and(|l| !l.trim().is_empty()) {
+                    lines.push(String::new());
+                }
+                // bounds only goes missing for a named section
+                lines.push(format!("[{}]", self.section.as_deref().unwrap_or_default()));
+                lines.push(format_line(key, value, true));
+            }
+        }
+        Some(join_lines(&lines, eol))
+    }
+}
+
+fn section_name(line: &str) -> Option<&str> {
+    let trimmed = line.trim();
+    match trimmed.starts_with('[') && trimmed.ends_with(']') {
+        true => Some(trimmed[1..trimmed.len() - 1].trim()),
+        false => None,
+    }
+}
+
+// the body of a section as [start, end) line indexes. Without a section name this
+// is everything before the first header; a named section that is not in the file
+// returns None.
+
+fn section_bounds(lines: &[String], section: Option<&str>) -> Option<(usize, usize)> {
+    let start = match section {
+        None => 0,
+        Some(name) => lines.iter().position(|l| section_name(l) == Some(name))? + 1,
+    };
+    let end = lines[start..]
+        .iter()
+        .position(|l| section_name(l).is_some())
+        .map(|offset| start + offset)
+        .unwrap_or(lines.len());
+    Some((start, end))
+}
+
+// the value of a `key = value` line for the given key, ignoring comments
+
+fn key_value<'a>(line: &'a str, key: &str) -> Option<&'a str> {
+    let trimmed = line.trim_start();
+    if trimmed.starts_with('#') || trimmed.starts_with(';') {
+        return None;
+    }
+    let (k, v) = trimmed.split_once('=')?;
+    match k.trim() == key {
+        true => Some(v.trim()),
+        false => None,
+    }
+}
+
+fn format_line(key: &str, value: &str, spaced: bool) -> String {
+    match spaced {
+        true => format!("{} = {}", key, value),
+        false => format!("{}={}", key, value),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CONFIG: &str =
+        "; global\nlog=info\n\n[server]\n# the port\nport = 80\nhost=0.0.0.0\n\n[db]\nname = app\n";
+
+    #[test]
+    fn existing_keys_are_updated_in_their_own_style() {
+        let a = IniFileAction {
+            path: String::from("/etc/app.ini"),
+            section: Some(String::from("server")),
+            key: Some(String::from("port")),
+            value: Some(String::from("8080")),
+            remove: false,
+            create: false,
+            backup: false,
+            attributes: None,
+        };
+        assert_eq!(
+            a.edit(CONFIG).unwrap(),
+            CONFIG.replace("port = 80\n", "port = 8080\n")
+        );
+        let a = IniFileAction {
+            section: None,
+            key: Some(String::from("log")),
+            value: Some(String::from("debug")),
+            ..a
+        };
+        assert_eq!(
+            a.edit(CONFIG).unwrap(),
+            CONFIG.replace("log=info", "log=debug")
+        );
+        let a = IniFileAction {
+            section: Some(String::from("db")),
+            key: Some(String::from("name")),
+            value: Some(String::from("app")),
+            ..a
+        };
+        assert!(a.edit(CONFIG).is_none());
+    }
+
+    #[test]
+    fn missing_keys_and_sections_are_added() {
+        let a = IniFileAction {
+            path: String::from("/etc/app.ini"),
+            section: Some(String::from("server")),
+            key: Some(String::from("workers")),
+            value: Some(String::from("4")),
+            remove: false,
+            create: false,
+            backup: false,
+            attributes: None,
+        };
+        assert!(
+            a.edit(CONFIG)
+                .unwrap()
+                .contains("host=0.0.0.0\nworkers = 4\n\n[db]")
+        );
+        let a = IniFileAction {
+            section: Some(String::from("cache")),
+            key: Some(String::from("size")),
+            value: Some(String::from("64M")),
+            ..a
+        };
+        assert!(
+            a.edit(CONFIG)
+                .unwrap()
+                .ends_with("name = app\n\n[cache]\nsize = 64M\n")
+        );
+        assert_eq!(a.edit("").unwrap(), "[cache]\nsize = 64M\n");
+    }
+
+    #[test]
+    fn keys_and_sections_can_be_removed() {
+        let a = IniFileAction {
+            path: String::from("/etc/app.ini"),
+            section: Some(String::from("server")),
+            key: Some(String::from("host")),
+            value: None,
+            remove: true,
+            create: false,
+            backup: false,
+            attributes: None,
+        };
+        assert_eq!(
+            a.edit(CONFIG).unwrap(),
+            CONFIG.replace("host=0.0.0.0\n", "")
+        );
+        let a = IniFileAction {
+            key: Some(String::from("missing")),
+            ..a
+        };
+        assert!(a.edit(CONFIG).is_none());
+        let a = IniFileAction {
+            section: Some(String::from("db")),
+            key: None,
+            ..a
+        };
+        assert_eq!(
+            a.edit(CONFIG).unwrap(),
+            CONFIG.replace("[db]\nname = app\n", "")
+        );
+    }
+}
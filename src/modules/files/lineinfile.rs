@@ -0,0 +1,477 @@
+// Jetporch
+// Copyright (C) 2023 - Michael DeHaan <michael@michaeldehaan.net> + contributors
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// long with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use crate::handle::handle::TaskHandle;
+use crate::tasks::fields::Field;
+use crate::tasks::files::Recurse;
+use crate::tasks::*;
+use crate::util::diff::unified;
+use regex::Regex;
+use serde::Deserialize;
+use std::sync::Arc;
+use std::vec::Vec;
+
+const MODULE: &str = "lineinfile";
+
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+#[derive(Default)]
+pub struct LineInFileTask {
+    pub name: Option<String>,
+    pub path: String,
+    pub line: Option<String>,
+    pub regexp: Option<String>,
+    pub insert_after: Option<String>,
+    pub insert_before: Option<String>,
+    pub remove: Option<Templated<bool>>,
+    pub create: Option<Templated<bool>>,
+    pub attributes: Option<FileAttributesInput>,
+    pub with: Option<PreLogicInput>,
+    pub and: Option<PostLogicInput>,
+}
+
+struct LineInFileAction {
+    pub path: String,
+    pub line: Option<String>,
+    pub regexp: Option<Regex>,
+    pub anchor: Anchor,
+    pub remove: bool,
+    pub create: bool,
+    pub attributes: Option<FileAttributesEvaluated>,
+}
+
+// where a new line (or block) goes when nothing matched. insert_after/insert_before
+// take a regex or the special values EOF and BOF (either field accepts both); a regex that matches nothing
+// falls back to the end of the file.
+
+pub(crate) enum Anchor {
+    Bof,
+    Eof,
+    After(Regex),
+    Before(Regex),
+}
+
+impl Anchor {
+    pub(crate) fn template(
+        handle: &Arc<TaskHandle>,
+        request: &Arc<TaskRequest>,
+        tm: TemplateMode,
+        insert_after: &Option<String>,
+        insert_before: &Option<String>,
+    ) -> Result<Self, Arc<TaskResponse>> {
+        let after = handle.template.string_option_unsafe_for_shell(
+            request,
+            tm,
+            &String::from("insert_after"),
+            insert_after,
+        )?;
+        let before = handle.template.string_option_unsafe_for_shell(
+            request,
+            tm,
+            &String::from("insert_before"),
+            insert_before,
+        )?;
+        match (after.as_deref(), before.as_deref()) {
+            (Some(_), Some(_)) => Err(handle.response.is_failed(
+                request,
+                "insert_after and insert_before are mutually exclusive",
+            )),
+            (None, None) | (Some("EOF"), None) | (None, Some("EOF")) => Ok(Anchor::Eof),
+            (Some("BOF"), None) | (None, Some("BOF")) => Ok(Anchor::Bof),
+            (Some(x), None) => Ok(Anchor::After(compile(handle, request, "insert_after", x)?)),
+            (None, Some(x)) => Ok(Anchor::Before(compile(
+                handle,
+                request,
+                "insert_before",
+                x,
+            )?)),
+        }
+    }
+
+    pub(crate) fn position(&self, lines: &[String]) -> usize {
+        let found = match self {
+            Anchor::Bof => Some(0),
+            Anchor::Eof => None,
+            Anchor::After(re) => lines.iter().rposition(|l| re.is_match(l)).map(|i| i + 1),
+            Anchor::Before(re) => lines.iter().position(|l| re.is_match(l)),
+        };
+        found.unwrap_or(lines.len())
+    }
+}
+
+pub(crate) fn compile(
+    handle: &Arc<TaskHandle>,
+    request: &Arc<TaskRequest>,
+    field: &str,
+    pattern: &str,
+) -> Result<Regex, Arc<TaskResponse>> {
+    Regex::new(pattern).map_err(|e| {
+        handle
+            .response
+            .is_failed(request, &format!("field {}: invalid regex: {}", field, e))
+    })
+}
+
+// files are edited as a list of lines and always written back with a final newline,
+// using the line ending the file already had
+
+pub(crate) fn split_lines(content: &str) -> Vec<String> {
+    content.lines().map(String::from).collect()
+}
+
+pub(crate) fn line_ending(content: &str) -> &'static str {
+    match content.contains("\r\n") {
+        true => "\r\n",
+        false => "\n",
+    }
+}
+
+pub(crate) fn join_lines(lines: &[String], eol: &str) -> String {
+    match lines.is_empty() {
+        true => String::new(),
+        false => format!("{}{}", lines.join(eol), eol),
+    }
+}
+
+impl IsTask for LineInFileTask {
+    fn get_module(&self) -> String {
+        String::from(MODULE)
+    }
+    fn get_name(&self) -> Option<String> {
+        self.name.clone()
+    }
+    fn get_with(&self) -> Option<PreLogicInput> {
+        self.with.clone()
+    }
+
+    fn evaluate(
+        &self,
+        handle: &Arc<TaskHandle>,
+        request: &Arc<TaskRequest>,
+        tm: TemplateMode,
+    ) -> Result<EvaluatedTask, Arc<TaskResponse>> {
+        let remove = handle.template.boolean_option_default_false(
+            request,
+            tm,
+            &String::from("remove"),
+            &self.remove,
+        )?;
+        let line = handle.template.string_option_unsafe_for_shell(
+            request,
+            tm,
+            &String::from("line"),
+            &self.line,
+        )?;
+        let regexp = match handle.template.string_option_unsafe_for_shell(
+            request,
+            tm,
+            &String::from("regexp"),
+            &self.regexp,
+        )? {
+            Some(x) => Some(compile(handle, request, "regexp", &x)?),
+            None => None,
+        };
+        if !remove && line.is_none() {
+            return Err(handle
+                .response
+                .is_failed(request, "line is required unless remove is set"));
+        }
+        if remove && line.is_none() && regexp.is_none() {
+            return Err(handle
+                .response
+                .is_failed(request, "remove requires line or regexp"));
+        }
+
+        Ok(EvaluatedTask {
+            action: Arc::new(LineInFileAction {
+                path: handle
+                    .template
+                    .path(request, tm, &String::from("path"), &self.path)?,
+                line,
+                regexp,
+                anchor: Anchor::template(
+                    handle,
+                    request,
+                    tm,
+                    &self.insert_after,
+                    &self.insert_before,
+                )?,
+                remove,
+                create: handle.template.boolean_option_default_false(
+                    request,
+                    tm,
+                    &String::from("create"),
+                    &self.create,
+                )?,
+                attributes: FileAttributesInput::template(handle, request, tm, &self.attributes)?,
+            }),
+            with: Arc::new(PreLogicInput::template(handle, request, tm, &self.with)?),
+            and: Arc::new(PostLogicInput::template(handle, request, tm, &self.and)?),
+        })
+    }
+}
+
+impl IsAction for LineInFileAction {
+    fn dispatch(
+        &self,
+        handle: &Arc<TaskHandle>,
+        request: &Arc<TaskRequest>,
+    ) -> Result<Arc<TaskResponse>, Arc<TaskResponse>> {
+        match request.request_type {
+            TaskRequestType::Query => {
+                let content = match handle.remote.read_file(request, &self.path)? {
+                    Some(content) => content,
+                    None if self.remove => return Ok(handle.response.is_matched(request)),
+                    None if self.create => {
+                        let response = handle.response.needs_creation(request);
+                        let diff = match handle.response.wants_diff() {
+                            true => unified(
+                                &self.path,
+                                None,
+                                self.edit("").unwrap_or_default().as_bytes(),
+                            ),
+                            false => None,
+                        };
+                        return Ok(handle.response.with_diff(response, diff));
+                    }
+                    None => {
+                        return Err(handle.response.is_failed(
+                            request,
+                            &format!("{} does not exist (set create: true)", self.path),
+                        ));
+                    }
+                };
+                let mut changes: Vec<Field> = Vec::new();
+                handle.remote.query_common_file_attributes(
+                    request,
+                    &self.path,
+                    &self.attributes,
+                    &mut changes,
+                    Recurse::No,
+                )?;
+                let mut diff: Option<String> = None;
+                if let Some(data) = self.edit(&content) {
+                    changes.push(Field::Content);
+                    if handle.response.wants_diff() {
+                        diff = unified(&self.path, Some(content.as_bytes()), data.as_bytes());
+                    }
+                }
+                if !changes.is_empty() {
+                    let response = handle.response.needs_modification(request, &changes);
+                    return Ok(handle.response.with_diff(response, diff));
+                }
+                Ok(handle.response.is_matched(request))
+            }
+
+            TaskRequestType::Create => {
+                let data = self.edit("").unwrap_or_default();
+                handle
+                    .remote
+                    .write_data(request, &data, &self.path, &self.attributes, |f| {
+                        handle.remote.process_transferred_file_attributes(
+                            request,
+                            f,
+                            &self.attributes,
+                        )
+                    })?;
+                Ok(handle.response.is_created(request))
+            }
+
+            TaskRequestType::Modify => {
+                if request.changes.contains(&Field::Content) {
+                    let content = handle
+                        .remote
+                        .read_file(request, &self.path)?
+                        .unwrap_or_default();
+                    if let Some(data) = self.edit(&content) {
+                        handle.remote.write_data_in_place(
+                            request,
+                            &data,
+                            &self.path,
+                            &self.attributes,
+                        )?;
+                    }
+                } else {
+                    handle.remote.process_common_file_attributes(
+                        request,
+                        &self.path,
+                        &self.attributes,
+                        &request.changes,
+                        Recurse::No,
+                    )?;
+                }
+                Ok(handle
+                    .response
+                    .is_modified(request, request.changes.clone()))
+            }
+
+            _ => Err(handle.response.not_supported(request)),
+        }
+    }
+}
+
+impl LineInFileAction {
+    // the new file content, or None when the file already says what it should
+
+    fn edit(&self, content: &str) -> Option<String> {
+        let mut lines = split_lines(content);
+        let eol = line_ending(content);
+        let matches = |l: &String| match (&self.regexp, &self.line) {
+            (Some(re), _) => re.is_match(l),
+            (None, Some(line)) => l == line,
+            (None, None) => false,
+        };
+
+        if self.remove {
+            let before = lines.len();
+            lines.retain(|l| !matches(l));
+            return match lines.len() == before {
+                true => None,
+                false => Some(join_lines(&lines, eol)),
+            };
+        }
+
+        let line = self.line.as_ref()?;
+        if self.regexp.is_some()
+            && let Some(index) = lines.iter().rposition(matches)
+        {
+            if &lines[index] == line {
+                return None;
+            }
+            lines[index] = line.clone();
+            return Some(join_lines(&lines, eol));
+        }
+        if lines.iter().any(|l| l == line) {
+            return None;
+        }
+        let index = self.anchor.position(&lines);
+        lines.insert(index, line.clone());
+        Some(join_lines(&lines, eol))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SYSCTL: &str = "# tuning\nvm.swappiness = 60\nnet.ipv4.ip_forward = 0\n";
+
+    #[test]
+    fn regexp_replaces_the_last_matching_line() {
+        let a = LineInFileAction {
+            path: String::from("/etc/sysctl.conf"),
+            line: Some(String::from("vm.swappiness = 10")),
+            regexp: Some(Regex::new("^vm.swappiness").unwrap()),
+            anchor: Anchor::Eof,
+            remove: false,
+            create: false,
+            attributes: None,
+        };
+        assert_eq!(
+            a.edit(SYSCTL).unwrap(),
+            "# tuning\nvm.swappiness = 10\nnet.ipv4.ip_forward = 0\n"
+        );
+        let done = a.edit(SYSCTL).unwrap();
+        assert!(a.edit(&done).is_none());
+    }
+
+    #[test]
+    fn missing_lines_are_inserted_at_the_anchor() {
+        let a = LineInFileAction {
+            path: String::from("/etc/sysctl.conf"),
+            line: Some(String::from("vm.dirty_ratio = 5")),
+            regexp: Some(Regex::new("^vm.dirty_ratio").unwrap()),
+            anchor: Anchor::After(Regex::new("^# tuning").unwrap()),
+            remove: false,
+            create: false,
+            attributes: None,
+        };
+        assert_eq!(
+            a.edit(SYSCTL).unwrap(),
+            "# tuning\nvm.dirty_ratio = 5\nvm.swappiness = 60\nnet.ipv4.ip_forward = 0\n"
+        );
+
+        let a = LineInFileAction {
+            line: Some(String::from("# managed")),
+            regexp: None,
+            anchor: Anchor::Bof,
+            ..a
+        };
+        assert!(a.edit(SYSCTL).unwrap().starts_with("# managed\n# tuning\n"));
+
+        let a = LineInFileAction {
+            line: Some(String::from("kernel.panic = 10")),
+            anchor: Anchor::Before(Regex::new("^nomatch").unwrap()),
+            ..a
+        };
+        assert!(
+            a.edit(SYSCTL)
+                .unwrap()
+                .ends_with("= 0\nkernel.panic = 10\n")
+        );
+    }
+
+    #[test]
+    fn an_existing_line_is_left_alone_even_without_trailing_newline() {
+        let a = LineInFileAction {
+            path: String::from("/etc/sysctl.conf"),
+            line: Some(String::from("net.ipv4.ip_forward = 0")),
+            regexp: None,
+            anchor: Anchor::Eof,
+            remove: false,
+            create: false,
+            attributes: None,
+        };
+        assert!(a.edit("net.ipv4.ip_forward = 0").is_none());
+    }
+
+    #[test]
+    fn remove_deletes_every_match() {
+        let a = LineInFileAction {
+            path: String::from("/etc/sysctl.conf"),
+            line: None,
+            regexp: Some(Regex::new("^(vm|net)\\.").unwrap()),
+            anchor: Anchor::Eof,
+            remove: true,
+            create: false,
+            attributes: None,
+        };
+        assert_eq!(a.edit(SYSCTL).unwrap(), "# tuning\n");
+        assert!(a.edit("# tuning\n").is_none());
+    }
+
+    #[test]
+    fn crlf_files_keep_their_line_endings() {
+        let a = LineInFileAction {
+            path: String::from("C:/app/settings.conf"),
+            line: Some(String::from("vm.swappiness = 10")),
+            regexp: Some(Regex::new("^vm.swappiness").unwrap()),
+            anchor: Anchor::Eof,
+            remove: false,
+            create: false,
+            attributes: None,
+        };
+        let crlf = SYSCTL.replace('\n', "\r\n");
+        assert_eq!(
+            a.edit(&crlf).unwrap(),
+            "# tuning\r\nvm.swappiness = 10\r\nnet.ipv4.ip_forward = 0\r\n"
+        );
+        assert_eq!(
+            a.edit(SYSCTL).unwrap(),
+            "# tuning\nvm.swappiness = 10\nnet.ipv4.ip_forward = 0\n"
+        );
+    }
+}
@@ -0,0 +1,436 @@
+// Jetporch
+// Copyright (C) 2023 - Michael DeHaan <michael@michaeldehaan.net> + contributors
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// long with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use crate::handle::handle::TaskHandle;
+use crate::tasks::fields::Field;
+use crate::tasks::files::LinkType;
+use crate::tasks::*;
+use serde::Deserialize;
+use std::sync::Arc;
+
+const MODULE: &str = "link";
+
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+#[derive(Default)]
+pub struct LinkTask {
+    pub name: Option<String>,
+    pub path: String,
+    pub src: Option<String>,
+    #[serde(rename = "type")]
+    pub link_type: Option<String>,
+    pub force: Option<Templated<bool>>,
+    pub remove: Option<Templated<bool>>,
+    pub with: Option<PreLogicInput>,
+    pub and: Option<PostLogicInput>,
+}
+
+struct LinkAction {
+    pub path: String,
+    pub src: String,
+    pub link_type: LinkType,
+    pub force: bool,
+    pub remove: bool,
+}
+
+impl IsTask for LinkTask {
+    fn get_module(&self) -> String {
+        String::from(MODULE)
+    }
+    fn get_name(&self) -> Option<String> {
+        self.name.clone()
+    }
+    fn get_with(&self) -> Option<PreLogicInput> {
+        self.with.clone()
+    }
+
+    fn evaluate(
+        &self,
+        handle: &Arc<TaskHandle>,
+        request: &Arc<TaskRequest>,
+        tm: TemplateMode,
+    ) -> Result<EvaluatedTask, Arc<TaskResponse>> {
+        let remove = handle.template.boolean_option_default_false(
+            request,
+            tm,
+            &String::from("remove"),
+            &self.remove,
+        )?;
+        let link_type = match handle
+            .template
+            .string_option_no_spaces(request, tm, &String::from("type"), &self.link_type)?
+            .as_deref()
+        {
+            None | Some("soft") | Some("symbolic") => LinkType::Soft,
+            Some("hard") => LinkType::Hard,
+            Some(_) if tm == TemplateMode::Off => LinkType::Soft,
+            Some(other) => {
+                return Err(handle.response.is_failed(
+                    request,
+                    &format!("field type: expected soft or hard, got '{}'", other),
+                ));
+            }
+        };
+        // a soft link target is stored as written, so it may well be relative
+        let src = match &self.src {
+            Some(src) => handle
+                .template
+                .path(request, tm, &String::from("src"), src)?,
+            None if tm == TemplateMode::Off || (remove && link_type == LinkType::Soft) => {
+                String::new()
+            }
+            None => {
+                return Err(handle
+                    .response
+                    .is_failed(request, "src is required, except when removing a soft link"));
+            }
+        };
+
+        Ok(EvaluatedTask {
+            action: Arc::new(LinkAction {
+                path: handle
+                    .template
+                    .path(request, tm, &String::from("path"), &self.path)?,
+                src,
+                link_type,
+                force: handle.template.boolean_option_default_false(
+                    request,
+                    tm,
+                    &String::from("force"),
+                    &self.force,
+                )?,
+                remove,
+            }),
+            with: Arc::new(PreLogicInput::template(handle, request, tm, &self.with)?),
+            and: Arc::new(PostLogicInput::template(handle, request, tm, &self.and)?),
+        })
+    }
+}
+
+impl IsAction for LinkAction {
+    fn dispatch(
+        &self,
+        handle: &Arc<TaskHandle>,
+        request: &Arc<TaskRequest>,
+    ) -> Result<Arc<TaskResponse>, Arc<TaskResponse>> {
+        match request.request_type {
+            TaskRequestType::Query => {
+                if self.remove {
+                    return match self.is_linked(handle, request)? {
+                        true => Ok(handle.response.needs_removal(request)),
+                        false => Ok(handle.response.is_matched(request)),
+                    };
+                }
+                if self.link_type == LinkType::Hard
+                    && handle
+                        .remote
+                        .get_file_identity(request, &self.src)?
+                        .is_none()
+                {
+                    return Err(handle
+                        .response
+                        .is_failed(request, &format!("src '{}' does not exist", self.src)));
+                }
+                if self.is_linked(handle, request)? {
+                    return Ok(handle.response.is_matched(request));
+                }
+                // a soft link pointing somewhere else is simply re-pointed; anything
+                // else already at path is only replaced when asked to
+                if handle.remote.read_link(request, &self.path)?.is_some() {
+                    return Ok(handle
+                        .response
+                        .needs_modification(request, &[Field::Target]));
+                }
+                if handle.remote.get_mode(request, &self.path)?.is_none() {
+                    return Ok(handle.response.needs_creation(request));
+                }
+                if handle.remote.get_is_directory(request, &self.path)? {
+                    return Err(handle.response.is_failed(
+                        request,
+                        &format!("{} is a directory, not replacing it with a link", self.path),
+                    ));
+                }
+                match self.force {
+                    true => Ok(handle
+                        .response
+                        .needs_modification(request, &[Field::Target])),
+                    false => Err(handle.response.is_failed(
+                        request,
+                        &format!(
+                            "{} exists and is not a link to {} (set force: true to replace it)",
+                            self.path, self.src
+                        ),
+                    )),
+                }
+            }
+
+            TaskRequestType::Create => {
+                handle
+                    .remote
+                    .create_link(request, &self.src, &self.path, self.link_type, false)?;
+                Ok(handle.response.is_created(request))
+            }
+
+            TaskRequestType::Modify => {
+                handle
+                    .remote
+                    .create_link(request, &self.src, &self.path, self.link_type, true)?;
+                Ok(handle
+                    .response
+                    .is_modified(request, request.changes.clone()))
+            }
+
+            TaskRequestType::Remove => {
+                handle.remote.delete_file(request, &self.path)?;
+                Ok(handle.response.is_removed(request))
+            }
+
+            _ => Err(handle.response.not_supported(request)),
+        }
+    }
+}
+
+impl LinkAction {
+    // whether path is already the link this task describes. When removing a soft
+    // link without src, any symlink at path counts; regular files never do.
+
+    fn is_linked(
+        &self,
+        handle: &Arc<TaskHandle>,
+        request: &Arc<TaskRequest>,
+    ) -> Result<bool, Arc<TaskResponse>> {
+        match self.link_type {
+            LinkType::Soft => match handle.remote.read_link(request, &self.path)? {
+                Some(target) => Ok(self.src.is_empty() || target == self.src),
+                None => Ok(false),
+            },
+            LinkType::Hard => {
+                let path_id = handle.remote.get_file_identity(request, &self.path)?;
+                let src_id = handle.remote.get_file_identity(request, &self.src)?;
+                Ok(path_id.is_some() && path_id == src_id)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::LinkAction;
+    use crate::inventory::hosts::HostOSType;
+    use crate::tasks::cmd_library::get_create_link_command;
+    use crate::tasks::fields::Field;
+    use crate::tasks::files::LinkType;
+    use crate::tasks::{IsAction, TaskResponse, TaskStatus};
+    use crate::test_support::{RecordingConnection, query_request, test_handle};
+    use std::sync::{Arc, Mutex};
+
+    // runs the Query leg against a host whose commands answer as `host` says;
+    // anything `host` does not know about fails like a missing path
+    fn query(
+        action: LinkAction,
+        host: fn(&str) -> Option<&'static str>,
+    ) -> Result<Arc<TaskResponse>, Arc<TaskResponse>> {
+        let conn = RecordingConnection::with_output(move |cmd| match host(cmd) {
+            Some(out) => (0, String::from(out)),
+            None => (1, String::new()),
+        });
+        let handle = test_handle(Arc::new(Mutex::new(conn)));
+        action.dispatch(&handle, &query_request())
+    }
+
+    #[test]
+    fn a_link_to_the_wrong_target_is_repointed() {
+        let result = query(
+            LinkAction {
+                path: String::from("/srv/current"),
+                src: String::from("/srv/releases/2"),
+                link_type: LinkType::Soft,
+                force: false,
+                remove: false,
+            },
+            |cmd| match cmd {
+                "readlink '/srv/current'" => Some("/srv/releases/1\n"),
+                _ => None,
+            },
+        )
+        .unwrap();
+        assert_eq!(result.status, TaskStatus::NeedsModification);
+        assert_eq!(result.changes, vec![Field::Target]);
+
+        let result = query(
+            LinkAction {
+                path: String::from("/srv/current"),
+                src: String::from("/srv/releases/2"),
+                link_type: LinkType::Soft,
+                force: false,
+                remove: false,
+            },
+            |cmd| match cmd {
+                "readlink '/srv/current'" => Some("/srv/releases/2\n"),
+                _ => None,
+            },
+        )
+        .unwrap();
+        assert_eq!(result.status, TaskStatus::IsMatched);
+    }
+
+    #[test]
+    fn a_regular_file_is_only_replaced_with_force() {
+        let host = |cmd: &str| match cmd {
+            "stat --format '%a' '/srv/current'" => Some("644"),
+            "ls -ld '/srv/current'" => Some("-rw-r--r-- 1 root root 0 /srv/current"),
+            _ => None,
+        };
+        let err = query(
+            LinkAction {
+                path: String::from("/srv/current"),
+                src: String::from("/srv/releases/2"),
+                link_type: LinkType::Soft,
+                force: false,
+                remove: false,
+            },
+            host,
+        )
+        .unwrap_err();
+        assert_eq!(err.status, TaskStatus::Failed);
+        assert!(err.msg.as_ref().unwrap().contains("set force: true"));
+
+        let result = query(
+            LinkAction {
+                path: String::from("/srv/current"),
+                src: String::from("/srv/releases/2"),
+                link_type: LinkType::Soft,
+                force: true,
+                remove: false,
+            },
+            host,
+        )
+        .unwrap();
+        assert_eq!(result.status, TaskStatus::NeedsModification);
+        assert_eq!(result.changes, vec![Field::Target]);
+    }
+
+    #[test]
+    fn a_directory_is_never_replaced() {
+        let err = query(
+            LinkAction {
+                path: String::from("/srv/current"),
+                src: String::from("/srv/releases/2"),
+                link_type: LinkType::Soft,
+                force: true,
+                remove: false,
+            },
+            |cmd| match cmd {
+                "stat --format '%a' '/srv/current'" => Some("755"),
+                "ls -ld '/srv/current'" => Some("drwxr-xr-x 2 root root 4096 /srv/current"),
+                _ => None,
+            },
+        )
+        .unwrap_err();
+        assert!(err.msg.as_ref().unwrap().contains("is a directory"));
+    }
+
+    #[test]
+    fn a_missing_path_is_created() {
+        let result = query(
+            LinkAction {
+                path: String::from("/srv/current"),
+                src: String::from("/srv/releases/2"),
+                link_type: LinkType::Soft,
+                force: false,
+                remove: false,
+            },
+            |_| None,
+        )
+        .unwrap();
+        assert_eq!(result.status, TaskStatus::NeedsCreation);
+    }
+
+    #[test]
+    fn hard_links_compare_inodes() {
+        let result = query(
+            LinkAction {
+                path: String::from("/srv/current"),
+                src: String::from("/srv/releases/2"),
+                link_type: LinkType::Hard,
+                force: false,
+                remove: false,
+            },
+            |cmd| match cmd {
+                "stat --format '%d:%i' '/srv/releases/2'" => Some("2049:1234\n"),
+                "stat --format '%d:%i' '/srv/current'" => Some("2049:1234\n"),
+                _ => None,
+            },
+        )
+        .unwrap();
+        assert_eq!(result.status, TaskStatus::IsMatched);
+
+        // same contents is not enough: another inode is a different file
+        let result = query(
+            LinkAction {
+                path: String::from("/srv/current"),
+                src: String::from("/srv/releases/2"),
+                link_type: LinkType::Hard,
+                force: true,
+                remove: false,
+            },
+            |cmd| match cmd {
+                "stat --format '%d:%i' '/srv/releases/2'" => Some("2049:1234\n"),
+                "stat --format '%d:%i' '/srv/current'" => Some("2049:5678\n"),
+                "stat --format '%a' '/srv/current'" => Some("644"),
+                "ls -ld '/srv/current'" => Some("-rw-r--r-- 1 root root 0 /srv/current"),
+                _ => None,
+            },
+        )
+        .unwrap();
+        assert_eq!(result.status, TaskStatus::NeedsModification);
+
+        let err = query(
+            LinkAction {
+                path: String::from("/srv/current"),
+                src: String::from("/srv/releases/2"),
+                link_type: LinkType::Hard,
+                force: false,
+                remove: false,
+            },
+            |_| None,
+        )
+        .unwrap_err();
+        assert!(err.msg.as_ref().unwrap().contains("does not exist"));
+    }
+
+    #[test]
+    fn link_commands_never_follow_an_existing_link() {
+        let src = "../sites-available/app";
+        let path = "/etc/nginx/sites-enabled/app";
+        let cmd = |t, replace| get_create_link_command(HostOSType::Linux, src, path, t, replace);
+        assert_eq!(
+            cmd(LinkType::Soft, false).unwrap(),
+            "ln -s '../sites-available/app' '/etc/nginx/sites-enabled/app'"
+        );
+        assert_eq!(
+            cmd(LinkType::Soft, true).unwrap(),
+            "ln -sfn '../sites-available/app' '/etc/nginx/sites-enabled/app'"
+        );
+        assert_eq!(
+            cmd(LinkType::Hard, false).unwrap(),
+            "ln '../sites-available/app' '/etc/nginx/sites-enabled/app'"
+        );
+        assert!(
+            get_create_link_command(HostOSType::Linux, "a;b", path, LinkType::Soft, true).is_err()
+        );
+    }
+}
@@ -15,13 +15,22 @@
 // long with this program.  If not, see <http://www.gnu.org/licenses/>.
 
 /** ADD MODULES HERE, KEEP ALPHABETIZED **/
+pub mod archive;
+pub mod blockinfile;
+pub mod config_file;
 pub mod copy;
 pub mod directory;
 pub mod download;
 pub mod fetch;
 pub mod file;
+pub mod find;
 pub mod git;
+pub mod ini_file;
+pub mod lineinfile;
+pub mod link;
 pub mod r#move;
+pub mod replace;
 pub mod stat;
+pub mod synchronize;
 pub mod template;
 pub mod unpack;
@@ -32,8 +32,8 @@ pub struct MoveTask {
     pub mode: Option<String>,
     pub owner: Option<String>,
     pub group: Option<String>,
-    pub backup: Option<String>,
-    pub force: Option<String>,
+    pub backup: Option<Templated<bool>>,
+    pub force: Option<Templated<bool>>,
     pub with: Option<PreLogicInput>,
     pub and: Option<PostLogicInput>,
 }
@@ -99,25 +99,19 @@ impl IsTask for MoveTask {
             None => None,
         };
 
-        let backup = match &self.backup {
-            Some(b) => {
-                let backup_str = handle
-                    .template
-                    .string(request, tm, &String::from("backup"), b)?;
-                backup_str == "true" || backup_str == "yes" || backup_str == "1"
-            }
-            None => false,
-        };
+        let backup = handle.template.boolean_option_default_false(
+            request,
+            tm,
+            &String::from("backup"),
+            &self.backup,
+        )?;
 
-        let force = match &self.force {
-            Some(f) => {
-                let force_str = handle
-                    .template
-                    .string(request, tm, &String::from("force"), f)?;
-                force_str == "true" || force_str == "yes" || force_str == "1"
-            }
-            None => false,
-        };
+        let force = handle.template.boolean_option_default_false(
+            request,
+            tm,
+            &String::from("force"),
+            &self.force,
+        )?;
 
         Ok(EvaluatedTask {
             action: Arc::new(MoveAction {
@@ -171,15 +165,7 @@ impl MoveAction {
         handle: &Arc<TaskHandle>,
         request: &Arc<TaskRequest>,
     ) -> Result<(), Arc<TaskResponse>> {
-        let timestamp = chrono::Utc::now().format("%Y%m%d_%H%M%S");
-        handle.remote.run(
-            request,
-            &format!(
-                "cp -a \"{}\" \"{}.backup.{}\"",
-                self.dest, self.dest, timestamp
-            ),
-            CheckRc::Checked,
-        )?;
+        handle.remote.backup_file(request, &self.dest)?;
         Ok(())
     }
 
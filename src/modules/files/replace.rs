@@ -0,0 +1,428 @@
+// Jetporch
+// Copyright (C) 2023 - Michael DeHaan <michael@michaeldehaan.net> + contributors
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// long with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use crate::handle::handle::TaskHandle;
+use crate::modules::files::lineinfile::compile;
+use crate::tasks::fields::Field;
+use crate::tasks::files::Recurse;
+use crate::tasks::*;
+use crate::util::diff::unified;
+use regex::Regex;
+use serde::Deserialize;
+use std::sync::Arc;
+use std::vec::Vec;
+
+const MODULE: &str = "replace";
+
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+#[derive(Default)]
+pub struct ReplaceTask {
+    pub name: Option<String>,
+    pub path: String,
+    pub regexp: String,
+    pub replace: Option<String>,
+    pub after: Option<String>,
+    pub before: Option<String>,
+    pub backup: Option<Templated<bool>>,
+    pub save: Option<String>,
+    pub attributes: Option<FileAttributesInput>,
+    pub with: Option<PreLogicInput>,
+    pub and: Option<PostLogicInput>,
+}
+
+struct ReplaceAction {
+    pub path: String,
+    pub regexp: Regex,
+    pub replace: String,
+    pub after: Option<Regex>,
+    pub before: Option<Regex>,
+    pub backup: bool,
+    pub save: Option<String>,
+    pub attributes: Option<FileAttributesEvaluated>,
+}
+
+impl IsTask for ReplaceTask {
+    fn get_module(&self) -> String {
+        String::from(MODULE)
+    }
+    fn get_name(&self) -> Option<String> {
+        self.name.clone()
+    }
+    fn get_with(&self) -> Option<PreLogicInput> {
+        self.with.clone()
+    }
+
+    fn evaluate(
+        &self,
+        handle: &Arc<TaskHandle>,
+        request: &Arc<TaskRequest>,
+        tm: TemplateMode,
+    ) -> Result<EvaluatedTask, Arc<TaskResponse>> {
+        let regexp = handle.template.string_unsafe_for_shell(
+            request,
+            tm,
+            &String::from("regexp"),
+            &self.regexp,
+        )?;
+        Ok(EvaluatedTask {
+            action: Arc::new(ReplaceAction {
+                path: handle
+                    .template
+                    .path(request, tm, &String::from("path"), &self.path)?,
+                // ^ and $ match at line boundaries, which is what config patching wants
+                regexp: compile(handle, request, "regexp", &format!("(?m){}", regexp))?,
+                replace: handle
+                    .template
+                    .string_option_unsafe_for_shell(
+                        request,
+                        tm,
+                        &String::from("replace"),
+                        &self.replace,
+                    )?
+                    .unwrap_or_default(),
+                after: self.bound(handle, request, tm, "after", &self.after)?,
+                before: self.bound(handle, request, tm, "before", &self.before)?,
+                backup: handle.template.boolean_option_default_false(
+                    request,
+                    tm,
+                    &String::from("backup"),
+                    &self.backup,
+                )?,
+                save: handle.template.string_option_no_spaces(
+                    request,
+                    tm,
+                    &String::from("save"),
+                    &self.save,
+                )?,
+                attributes: FileAttributesInput::template(handle, request, tm, &self.attributes)?,
+            }),
+            with: Arc::new(PreLogicInput::template(handle, request, tm, &self.with)?),
+            and: Arc::new(PostLogicInput::template(handle, request, tm, &self.and)?),
+        })
+    }
+}
+
+impl ReplaceTask {
+    fn bound(
+        &self,
+        handle: &Arc<TaskHandle>,
+        request: &Arc<TaskRequest>,
+        tm: TemplateMode,
+        field: &str,
+        input: &Option<String>,
+    ) -> Result<Option<Regex>, Arc<TaskResponse>> {
+        match handle.template.string_option_unsafe_for_shell(
+            request,
+            tm,
+            &String::from(field),
+            input,
+        )? {
+            Some(x) => Ok(Some(compile(
+                handle,
+                request,
+                field,
+                &format!("(?m){}", x),
+            )?)),
+            None => Ok(None),
+        }
+    }
+}
+
+impl IsAction for ReplaceAction {
+    fn dispatch(
+        &self,
+        handle: &Arc<TaskHandle>,
+        request: &Arc<TaskRequest>,
+    ) -> Result<Arc<TaskResponse>, Arc<TaskResponse>> {
+        match request.request_type {
+            TaskRequestType::Query => {
+                let content = match handle.remote.read_file(request, &self.path)? {
+                    Some(content) => content,
+                    None => {
+                        return Err(handle
+                            .response
+                            .is_failed(request, &format!("{} does not exist", self.path)));
+                    }
+                };
+                let mut changes: Vec<Field> = Vec::new();
+                handle.remote.query_common_file_attributes(
+                    request,
+                    &self.path,
+                    &self.attributes,
+                    &mut changes,
+                    Recurse::No,
+                )?;
+                let (data, count) = self.substitute(&content);
+                let mut diff: Option<String> = None;
+                if count > 0 {
+                    changes.push(Field::Content);
+                    if handle.response.wants_diff() {
+                        diff = unified(&self.path, Some(content.as_bytes()), data.as_bytes());
+                    }
+                }
+                if !changes.is_empty() {
+                    let response = handle.response.needs_modification(request, &changes);
+                    let response = handle.response.with_msg(response, substitutions(count));
+                    return Ok(handle.response.with_diff(response, diff));
+                }
+                // nothing to change, but the (zero) count still has to be saved
+                match self.save.is_some() {
+                    true => Ok(handle.response.needs_passive(request)),
+                    false => Ok(handle.response.is_matched(request)),
+                }
+            }
+
+            TaskRequestType::Passive => {
+                if let Some(save) = &self.save {
+                    save_results(handle, save, 0);
+                }
+                Ok(handle.response.is_passive(request))
+            }
+
+            TaskRequestType::Modify => {
+                let mut count = 0;
+                if request.changes.contains(&Field::Content) {
+                    let content = handle
+                        .remote
+                        .read_file(request, &self.path)?
+                        .unwrap_or_default();
+                    let (data, substituted) = self.substitute(&content);
+                    count = substituted;
+                    if count > 0 {
+                        if self.backup {
+                            handle.remote.backup_file(request, &self.path)?;
+                        }
+                        handle.remote.write_data_in_place(
+                            request,
+                            &data,
+                            &self.path,
+                            &self.attributes,
+                        )?;
+                    }
+                } else {
+                    handle.remote.process_common_file_attributes(
+                        request,
+                        &self.path,
+                        &self.attributes,
+                        &request.changes,
+                        Recurse::No,
+                    )?;
+                }
+                if let Some(save) = &self.save {
+                    save_results(handle, save, count);
+                }
+                let response = handle
+                    .response
+                    .is_modified(request, request.changes.clone());
+                Ok(handle.response.with_msg(response, substitutions(count)))
+            }
+
+            _ => Err(handle.response.not_supported(request)),
+        }
+    }
+}
+
+impl ReplaceAction {
+    // the rewritten content and how many matches actually changed. A match whose
+    // replacement is identical to the original text is not counted, so a pattern
+    // that already matches its own output is still idempotent.
+
+    fn substitute(&self, content: &str) -> (String, usize) {
+        let (start, end) = match self.section(content) {
+            Some(section) => section,
+            None => return (content.to_string(), 0),
+        };
+        let mut out = String::with_capacity(content.len());
+        let mut last = start;
+        let mut count = 0;
+        out.push_str(&content[..start]);
+        for caps in self.regexp.captures_iter(&content[start..end]) {
+            let m = caps.get(0).unwrap();
+            let mut replacement = String::new();
+            caps.expand(&self.replace, &mut replacement);
+            out.push_str(&content[last..start + m.start()]);
+            out.push_str(&replacement);
+            last = start + m.end();
+            if replacement != m.as_str() {
+                count += 1;
+            }
+        }
+        out.push_str(&content[last..]);
+        (out, count)
+    }
+
+    // the byte range substitutions are limited to: after the first match of after
+    // and up to the first match of before that follows it. A bound that is given
+    // but not found leaves nothing to replace.
+
+    fn section(&self, content: &str) -> Option<(usize, usize)> {
+        let start = match &self.after {
+            Some(after) => after.find(content)?.end(),
+            None => 0,
+        };
+        let end = match &self.before {
+            Some(before) => start + before.find(&content[start..])?.start(),
+            None => content.len(),
+        };
+        Some((start, end))
+    }
+}
+
+// how many matches were (or would be) replaced, for the task's result message
+
+fn substitutions(count: usize) -> Option<String> {
+    match count {
+        0 => None,
+        1 => Some(String::from("1 substitution")),
+        n => Some(format!("{} substitutions", n)),
+    }
+}
+
+fn save_results(handle: &Arc<TaskHandle>, key: &str, count: usize) {
+    let mut value = serde_yaml::Mapping::new();
+    value.insert(
+        serde_yaml::Value::String(String::from("substitutions")),
+        serde_yaml::Value::Number((count as u64).into()),
+    );
+    let mut result = serde_yaml::Mapping::new();
+    result.insert(
+        serde_yaml::Value::String(key.to_string()),
+        serde_yaml::Value::Mapping(value),
+    );
+    handle.host.write().unwrap().update_variables(result);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{RecordingConnection, query_request, test_handle};
+    use base64::Engine;
+    use base64::engine::general_purpose::STANDARD as BASE64;
+    use std::sync::Mutex;
+
+    fn base() -> ReplaceAction {
+        ReplaceAction {
+            path: String::from("/etc/vendor.conf"),
+            regexp: Regex::new(r"(?m)^listen (\d+)$").unwrap(),
+            replace: String::from("listen 127.0.0.1:$1"),
+            after: None,
+            before: None,
+            backup: false,
+            save: None,
+            attributes: None,
+        }
+    }
+
+    #[test]
+    fn every_match_is_replaced_with_capture_groups_expanded() {
+        let a = ReplaceAction {
+            regexp: Regex::new(r"(?m)^listen\s+(\d+)$").unwrap(),
+            ..base()
+        };
+        let (out, count) = a.substitute("listen 80\nname x\nlisten 443\n");
+        assert_eq!(out, "listen 127.0.0.1:80\nname x\nlisten 127.0.0.1:443\n");
+        assert_eq!(count, 2);
+    }
+
+    #[test]
+    fn identical_replacements_are_not_counted() {
+        let a = ReplaceAction {
+            regexp: Regex::new(r"(?m)^(MaxClients) \d+$").unwrap(),
+            replace: String::from("$1 150"),
+            ..base()
+        };
+        let (out, count) = a.substitute("MaxClients 150\nMaxClients 20\n");
+        assert_eq!(out, "MaxClients 150\nMaxClients 150\n");
+        assert_eq!(count, 1);
+        assert_eq!(a.substitute(&out).1, 0);
+    }
+
+    #[test]
+    fn no_match_leaves_the_content_alone() {
+        let a = base();
+        let content = "name x\nlisten_backlog 10\n";
+        assert_eq!(a.substitute(content), (String::from(content), 0));
+    }
+
+    #[test]
+    fn several_groups_are_expanded_per_match() {
+        let a = ReplaceAction {
+            path: String::from("/etc/hosts"),
+            regexp: Regex::new(r"(?m)^(?P<ip>\S+)\s+(\S+)\.internal$").unwrap(),
+            replace: String::from("${ip} $2.internal $2"),
+            ..base()
+        };
+        let (out, count) = a.substitute("10.0.0.1 db.internal\n10.0.0.2 cache.internal\n");
+        assert_eq!(
+            out,
+            "10.0.0.1 db.internal db\n10.0.0.2 cache.internal cache\n"
+        );
+        assert_eq!(count, 2);
+    }
+
+    #[test]
+    fn only_the_section_between_after_and_before_is_replaced() {
+        let content = "port = 1\n[server]\nport = 2\nport = 3\n[client]\nport = 4\n";
+        let a = ReplaceAction {
+            regexp: Regex::new(r"(?m)^port = \d+$").unwrap(),
+            replace: String::from("port = 8080"),
+            after: Some(Regex::new(r"(?m)^\[server\]$").unwrap()),
+            before: Some(Regex::new(r"(?m)^\[").unwrap()),
+            ..base()
+        };
+        let (out, count) = a.substitute(content);
+        assert_eq!(
+            out,
+            "port = 1\n[server]\nport = 8080\nport = 8080\n[client]\nport = 4\n"
+        );
+        assert_eq!(count, 2);
+
+        // a bound that is not in the file leaves nothing to replace
+        let a = ReplaceAction {
+            after: Some(Regex::new(r"(?m)^\[agent\]$").unwrap()),
+            ..a
+        };
+        assert_eq!(a.substitute(content), (String::from(content), 0));
+    }
+
+    #[test]
+    fn the_query_reports_but_does_not_save_the_count() {
+        let encoded = BASE64.encode("listen 80\n");
+        let conn = RecordingConnection::with_output(move |cmd| match cmd.starts_with("base64") {
+            true => (0, encoded.clone()),
+            false => (0, String::from("644")),
+        });
+        let handle = test_handle(Arc::new(Mutex::new(conn)));
+        let a = ReplaceAction {
+            save: Some(String::from("patched")),
+            ..base()
+        };
+        let response = a.dispatch(&handle, &query_request()).unwrap();
+        assert_eq!(response.status, TaskStatus::NeedsModification);
+        assert_eq!(response.msg.as_deref(), Some("1 substitution"));
+        assert!(handle.host.read().unwrap().get_variables().is_empty());
+
+        // already applied: the zero count is saved in the passive leg instead
+        let a = ReplaceAction {
+            replace: String::from("listen $1"),
+            ..a
+        };
+        let response = a.dispatch(&handle, &query_request()).unwrap();
+        assert_eq!(response.status, TaskStatus::NeedsPassive);
+        assert!(handle.host.read().unwrap().get_variables().is_empty());
+    }
+}
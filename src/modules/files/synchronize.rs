@@ -0,0 +1,543 @@
+// Jetporch
+// Copyright (C) 2023 - Michael DeHaan <michael@michaeldehaan.net> + contributors
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// long with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use crate::handle::handle::TaskHandle;
+use crate::tasks::checksum::Algorithm;
+use crate::tasks::fields::Field;
+use crate::tasks::files::Recurse;
+use crate::tasks::*;
+use regex::Regex;
+use serde::Deserialize;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::vec::Vec;
+
+const MODULE: &str = "synchronize";
+
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+#[derive(Default)]
+pub struct SynchronizeTask {
+    pub name: Option<String>,
+    pub src: String,
+    pub dest: String,
+    pub delete: Option<Templated<bool>>,
+    pub exclude: Option<Vec<String>>,
+    pub with: Option<PreLogicInput>,
+    pub and: Option<PostLogicInput>,
+}
+
+struct SynchronizeAction {
+    pub src: PathBuf,
+    pub dest: String,
+    pub delete: bool,
+    pub exclude: Vec<Regex>,
+    // the query's plan, handed on to the create or modify leg that follows it
+    planned: Mutex<Option<Plan>>,
+}
+
+// what it takes to make dest look like src, all paths relative to the two roots
+
+#[derive(Default, Debug, PartialEq)]
+struct Plan {
+    directories: Vec<String>,
+    added: Vec<String>,
+    changed: Vec<String>,
+    removed_files: Vec<String>,
+    removed_directories: Vec<String>,
+}
+
+impl Plan {
+    fn is_empty(&self) -> bool {
+        self.directories.is_empty()
+            && self.added.is_empty()
+            && self.changed.is_empty()
+            && self.removed_files.is_empty()
+            && self.removed_directories.is_empty()
+    }
+
+    // the per-file change list, reported through the response diff so check mode
+    // (and the JSON log) show exactly which files would be touched
+
+    fn summary(&self) -> String {
+        let mut out = String::new();
+        for path in self.directories.iter() {
+            out.push_str(&format!("+ {}/\n", path));
+        }
+        for (mark, paths) in [
+            ("+", &self.added),
+            ("~", &self.changed),
+            ("-", &self.removed_files),
+        ] {
+            for path in paths.iter() {
+                out.push_str(&format!("{} {}\n", mark, path));
+            }
+        }
+        for path in self.removed_directories.iter() {
+            out.push_str(&format!("- {}/\n", path));
+        }
+        out
+    }
+}
+
+impl IsTask for SynchronizeTask {
+    fn get_module(&self) -> String {
+        String::from(MODULE)
+    }
+    fn get_name(&self) -> Option<String> {
+        self.name.clone()
+    }
+    fn get_with(&self) -> Option<PreLogicInput> {
+        self.with.clone()
+    }
+
+    fn evaluate(
+        &self,
+        handle: &Arc<TaskHandle>,
+        request: &Arc<TaskRequest>,
+        tm: TemplateMode,
+    ) -> Result<EvaluatedTask, Arc<TaskResponse>> {
+        let src_str = handle
+            .template
+            .string(request, tm, &String::from("src"), &self.src)?;
+        // see copy: during pre-evaluation src is a placeholder, so only check it for real
+        let src = match tm {
+            TemplateMode::Off => PathBuf::new(),
+            _ => {
+                let p = PathBuf::from(&src_str);
+                if !p.is_dir() {
+                    return Err(handle
+                        .response
+                        .is_failed(request, &format!("src '{}': not a directory", src_str)));
+                }
+                p
+            }
+        };
+
+        let mut exclude: Vec<Regex> = Vec::new();
+        for pattern in self.exclude.clone().unwrap_or_default().iter() {
+            let pattern = handle.template.string_unsafe_for_shell(
+                request,
+                tm,
+                &String::from("exclude"),
+                pattern,
+            )?;
+            exclude.push(glob_to_regex(&pattern).map_err(|e| {
+                handle.response.is_failed(
+                    request,
+                    &format!("field exclude: invalid pattern '{}': {}", pattern, e),
+                )
+            })?);
+        }
+
+        Ok(EvaluatedTask {
+            action: Arc::new(SynchronizeAction {
+                src,
+                dest: handle
+                    .template
+                    .path(request, tm, &String::from("dest"), &self.dest)?,
+                delete: handle.template.boolean_option_default_false(
+                    request,
+                    tm,
+                    &String::from("delete"),
+                    &self.delete,
+                )?,
+                exclude,
+                planned: Mutex::new(None),
+            }),
+            with: Arc::new(PreLogicInput::template(handle, request, tm, &self.with)?),
+            and: Arc::new(PostLogicInput::template(handle, request, tm, &self.and)?),
+        })
+    }
+}
+
+impl IsAction for SynchronizeAction {
+    fn dispatch(
+        &self,
+        handle: &Arc<TaskHandle>,
+        request: &Arc<TaskRequest>,
+    ) -> Result<Arc<TaskResponse>, Arc<TaskResponse>> {
+        match request.request_type {
+            TaskRequestType::Query => {
+                let exists = handle.remote.get_is_directory(request, &self.dest)?;
+                let plan = self.plan(handle, request, exists)?;
+                let response = match (exists, plan.is_empty()) {
+                    (false, _) => handle.response.needs_creation(request),
+                    (true, true) => return Ok(handle.response.is_matched(request)),
+                    (true, false) => handle
+                        .response
+                        .needs_modification(request, &[Field::Content]),
+                };
+                let summary = plan.summary();
+                *self.planned.lock().unwrap() = Some(plan);
+                Ok(handle.response.with_diff(response, Some(summary)))
+            }
+
+            TaskRequestType::Create => {
+                handle.remote.create_directory(request, &self.dest)?;
+                let plan = self.planned(handle, request, false)?;
+                self.apply(handle, request, &plan)?;
+                Ok(handle.response.is_created(request))
+            }
+
+            TaskRequestType::Modify => {
+                let plan = self.planned(handle, request, true)?;
+                self.apply(handle, request, &plan)?;
+                Ok(handle
+                    .response
+                    .is_modified(request, request.changes.clone()))
+            }
+
+            _ => Err(handle.response.not_supported(request)),
+        }
+    }
+}
+
+impl SynchronizeAction {
+    // the plan the query made, or a fresh one should an action leg ever run without it
+
+    fn planned(
+        &self,
+        handle: &Arc<TaskHandle>,
+        request: &Arc<TaskRequest>,
+        exists: bool,
+    ) -> Result<Plan, Arc<TaskResponse>> {
+        let planned = self.planned.lock().unwrap().take();
+        match planned {
+            Some(plan) => Ok(plan),
+            None => self.plan(handle, request, exists),
+        }
+    }
+
+    fn plan(
+        &self,
+        handle: &Arc<TaskHandle>,
+        request: &Arc<TaskRequest>,
+        exists: bool,
+    ) -> Result<Plan, Arc<TaskResponse>> {
+        let mut local_files: BTreeMap<String, PathBuf> = BTreeMap::new();
+        let mut local_dirs: BTreeSet<String> = BTreeSet::new();
+        self.walk(&self.src, "", &mut local_files, &mut local_dirs)
+            .map_err(|e| handle.response.is_failed(request, &e))?;
+
+        let (remote_sums, remote_dirs) = match exists {
+            true => (
+                handle.remote.get_tree_sha512(request, &self.dest)?,
+                handle.remote.get_tree_directories(request, &self.dest)?,
+            ),
+            false => (HashMap::new(), Vec::new()),
+        };
+
+        let mut local_sums: BTreeMap<String, String> = BTreeMap::new();
+        for (relative, path) in local_files.iter() {
+            let data = std::fs::read(path).map_err(|e| {
+                handle.response.is_failed(
+                    request,
+                    &format!("failed to read '{}': {}", path.display(), e),
+                )
+            })?;
+            local_sums.insert(relative.clone(), Algorithm::Sha512.digest(&data));
+        }
+        Ok(self.compare(&local_sums, &local_dirs, &remote_sums, &remote_dirs))
+    }
+
+    // the pure part of plan(): what differs between the two trees
+
+    fn compare(
+        &self,
+        local_sums: &BTreeMap<String, String>,
+        local_dirs: &BTreeSet<String>,
+        remote_sums: &HashMap<String, String>,
+        remote_dirs: &[String],
+    ) -> Plan {
+        let mut plan = Plan::default();
+        let remote_dir_set: BTreeSet<&String> = remote_dirs.iter().collect();
+        plan.directories = local_dirs
+            .iter()
+            .filter(|d| !remote_dir_set.contains(d))
+            .cloned()
+            .collect();
+        for (relative, sum) in local_sums.iter() {
+            match remote_sums.get(relative) {
+                None => plan.added.push(relative.clone()),
+                Some(remote) if remote != sum => plan.changed.push(relative.clone()),
+                Some(_) => {}
+            }
+        }
+        if !self.delete {
+            return plan;
+        }
+
+        // excluded paths on the remote side are left alone, as rsync does. A stale
+        // directory is removed as a whole, so nothing below it is listed separately,
+        // unless it holds something excluded; then only its other files go.
+        let holds_excluded = |dir: &String| {
+            let prefix = format!("{}/", dir);
+            remote_sums
+                .keys()
+                .chain(remote_dirs.iter())
+                .any(|p| p.starts_with(&prefix) && self.is_excluded(p))
+        };
+        let mut stale: Vec<String> = remote_dirs
+            .iter()
+            .filter(|d| !local_dirs.contains(*d) && !self.is_excluded(d) && !holds_excluded(d))
+            .cloned()
+            .collect();
+        stale.sort();
+        for dir in stale.iter() {
+            let nested = plan
+                .removed_directories
+                .iter()
+                .any(|parent| dir.starts_with(&format!("{}/", parent)));
+            if !nested {
+                plan.removed_directories.push(dir.clone());
+            }
+        }
+        let mut removed: Vec<String> = remote_sums
+            .keys()
+            .filter(|f| !local_sums.contains_key(*f) && !self.is_excluded(f))
+            .filter(|f| {
+                !plan
+                    .removed_directories
+                    .iter()
+                    .any(|d| f.starts_with(&format!("{}/", d)))
+            })
+            .cloned()
+            .collect();
+        removed.sort();
+        plan.removed_files = removed;
+        plan
+    }
+
+    fn apply(
+        &self,
+        handle: &Arc<TaskHandle>,
+        request: &Arc<TaskRequest>,
+        plan: &Plan,
+    ) -> Result<(), Arc<TaskResponse>> {
+        for dir in plan.directories.iter() {
+            handle
+                .remote
+                .create_directory(request, &self.remote_path(dir))?;
+        }
+        for relative in plan.added.iter().chain(plan.changed.iter()) {
+            let local = self.src.join(relative);
+            handle
+                .remote
+                .copy_file(request, &local, &self.remote_path(relative), &None, |_f| {
+                    Ok(())
+                })?;
+        }
+        for relative in plan.removed_files.iter() {
+            handle
+                .remote
+                .delete_file(request, &self.remote_path(relative))?;
+        }
+        for relative in plan.removed_directories.iter() {
+            handle
+                .remote
+                .delete_directory(request, &self.remote_path(relative), Recurse::Yes)?;
+        }
+        Ok(())
+    }
+
+    fn remote_path(&self, relative: &str) -> String {
+        format!("{}/{}", self.dest.trim_end_matches('/'), relative)
+    }
+
+    // symlinks and other special files are skipped, the same as a recursive !copy
+
+    fn walk(
+        &self,
+        dir: &Path,
+        prefix: &str,
+        files: &mut BTreeMap<String, PathBuf>,
+        dirs: &mut BTreeSet<String>,
+    ) -> Result<(), String> {
+        let entries = std::fs::read_dir(dir)
+            .map_err(|e| format!("failed to read local directory '{}': {}", dir.display(), e))?;
+        for entry in entries {
+            let entry = entry.map_err(|e| format!("failed to read '{}': {}", dir.display(), e))?;
+            let relative = format!("{}{}", prefix, entry.file_name().to_string_lossy());
+            if self.is_excluded(&relative) {
+                continue;
+            }
+            let file_type = entry
+                .file_type()
+                .map_err(|e| format!("failed to stat '{}': {}", relative, e))?;
+            if file_type.is_dir() {
+                dirs.insert(relative.clone());
+                self.walk(&entry.path(), &format!("{}/", relative), files, dirs)?;
+            } else if file_type.is_file() {
+                files.insert(relative, entry.path());
+            }
+        }
+        Ok(())
+    }
+
+    // patterns without a slash match any path component (*.pyc, .git), patterns with
+    // one match the path from the root (build/*.o); excluding a directory excludes
+    // everything below it
+
+    fn is_excluded(&self, relative: &str) -> bool {
+        let components: Vec<&str> = relative.split('/').collect();
+        (1..=components.len()).any(|n| {
+            let path = components[..n].join("/");
+            self.exclude
+                .iter()
+                .any(|re| re.is_match(&path) || re.is_match(components[n - 1]))
+        })
+    }
+}
+
+// * and ? stay within one path component, ** crosses them
+
+pub(crate) fn glob_to_regex(pattern: &str) -> Result<Regex, regex::Error> {
+    let pattern = pattern.trim_start_matches('/').trim_end_matches('/');
+    let mut out = String::from("^");
+    let mut chars = pattern.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '*' if chars.peek() == Some(&'*') => {
+                chars.next();
+                out.push_str(".*");
+            }
+            '*' => out.push_str("[^/]*"),
+            '?' => out.push_str("[^/]"),
+            _ => out.push_str(&regex::escape(&c.to_string())),
+        }
+    }
+    out.push('$');
+    Regex::new(&out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sums(entries: &[(&str, &str)]) -> BTreeMap<String, String> {
+        entries
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn only_differences_are_transferred_and_stale_files_removed() {
+        let local = sums(&[
+            ("index.html", "a"),
+            ("css/site.css", "b"),
+            ("img/logo.png", "c"),
+        ]);
+        let local_dirs: BTreeSet<String> = ["css", "img"].iter().map(|s| s.to_string()).collect();
+        let remote: HashMap<String, String> = sums(&[
+            ("index.html", "a"),
+            ("css/site.css", "old"),
+            ("old.html", "d"),
+            ("legacy/a.js", "e"),
+            ("legacy/deep/b.js", "f"),
+            ("cache/x.pyc", "g"),
+        ])
+        .into_iter()
+        .collect();
+        let remote_dirs: Vec<String> = ["css", "legacy", "legacy/deep", "cache"]
+            .iter()
+            .map(|s| s.to_string())
+            .collect();
+
+        let a = SynchronizeAction {
+            src: PathBuf::from("/srv/site"),
+            dest: String::from("/var/www/site/"),
+            delete: false,
+            exclude: Vec::new(),
+            planned: Mutex::new(None),
+        };
+        let plan = a.compare(&local, &local_dirs, &remote, &remote_dirs);
+        assert_eq!(plan.directories, vec!["img"]);
+        assert_eq!(plan.added, vec!["img/logo.png"]);
+        assert_eq!(plan.changed, vec!["css/site.css"]);
+        assert!(plan.removed_files.is_empty() && plan.removed_directories.is_empty());
+
+        let a = SynchronizeAction {
+            delete: true,
+            exclude: vec![glob_to_regex("*.pyc").unwrap()],
+            ..a
+        };
+        let plan = a.compare(&local, &local_dirs, &remote, &remote_dirs);
+        assert_eq!(plan.removed_files, vec!["old.html"]);
+        assert_eq!(plan.removed_directories, vec!["legacy"]);
+        assert_eq!(
+            plan.summary(),
+            "+ img/\n+ img/logo.png\n~ css/site.css\n- old.html\n- legacy/\n"
+        );
+
+        let a = SynchronizeAction {
+            exclude: Vec::new(),
+            ..a
+        };
+        let plan = a.compare(&local, &local_dirs, &remote, &remote_dirs);
+        assert_eq!(plan.removed_directories, vec!["cache", "legacy"]);
+
+        let a = SynchronizeAction {
+            exclude: vec![glob_to_regex("cache").unwrap()],
+            ..a
+        };
+        let plan = a.compare(&local, &local_dirs, &remote, &remote_dirs);
+        assert_eq!(plan.removed_directories, vec!["legacy"]);
+    }
+
+    #[test]
+    fn a_missing_empty_directory_is_a_change() {
+        let local_dirs: BTreeSet<String> = ["uploads"].iter().map(|s| s.to_string()).collect();
+        let a = SynchronizeAction {
+            src: PathBuf::from("/srv/site"),
+            dest: String::from("/var/www/site/"),
+            delete: false,
+            exclude: Vec::new(),
+            planned: Mutex::new(None),
+        };
+        let plan = a.compare(&sums(&[]), &local_dirs, &HashMap::new(), &[]);
+        assert!(!plan.is_empty());
+        assert_eq!(plan.summary(), "+ uploads/\n");
+
+        let remote_dirs = vec![String::from("uploads")];
+        let plan = a.compare(&sums(&[]), &local_dirs, &HashMap::new(), &remote_dirs);
+        assert!(plan.is_empty());
+    }
+
+    #[test]
+    fn exclude_globs_match_names_or_rooted_paths() {
+        let a = SynchronizeAction {
+            src: PathBuf::from("/srv/site"),
+            dest: String::from("/var/www/site/"),
+            delete: true,
+            exclude: ["*.pyc", ".git", "build/*.o", "logs/**"]
+                .iter()
+                .map(|p| glob_to_regex(p).unwrap())
+                .collect(),
+            planned: Mutex::new(None),
+        };
+        assert!(a.is_excluded("app/__pycache__/mod.pyc"));
+        assert!(a.is_excluded(".git/HEAD"));
+        assert!(a.is_excluded("sub/.git"));
+        assert!(a.is_excluded("build/main.o"));
+        assert!(!a.is_excluded("src/build/main.o"));
+        assert!(!a.is_excluded("build/sub/main.o"));
+        assert!(a.is_excluded("logs/2024/app.log"));
+        assert!(!a.is_excluded("app/main.py"));
+    }
+}
@@ -15,10 +15,12 @@
 // long with this program.  If not, see <http://www.gnu.org/licenses/>.
 
 use crate::handle::handle::TaskHandle;
+use crate::playbooks::templar::TemplateEngine;
 use crate::tasks::checksum::sha512;
 use crate::tasks::fields::Field;
 use crate::tasks::files::Recurse;
 use crate::tasks::*;
+use crate::util::diff::unified;
 use serde::Deserialize;
 use std::path::PathBuf;
 use std::sync::Arc;
@@ -97,16 +99,27 @@ impl IsAction for TemplateAction {
                     Recurse::No,
                 )?;
                 if remote_mode.is_none() {
-                    return Ok(handle.response.needs_creation(request));
+                    let response = handle.response.needs_creation(request);
+                    if !handle.response.wants_diff() {
+                        return Ok(response);
+                    }
+                    let data = self.do_template(handle, request, false, None)?;
+                    let diff = unified(&self.dest, None, data.as_bytes());
+                    return Ok(handle.response.with_diff(response, diff));
                 }
                 let data = self.do_template(handle, request, false, None)?;
                 let local_512 = sha512(&data);
                 let remote_512 = handle.remote.get_sha512(request, &self.dest)?;
+                let mut diff: Option<String> = None;
                 if !remote_512.eq(&local_512) {
                     changes.push(Field::Content);
+                    diff = handle
+                        .remote
+                        .diff_file(request, &self.dest, data.as_bytes())?;
                 }
                 if !changes.is_empty() {
-                    return Ok(handle.response.needs_modification(request, &changes));
+                    let response = handle.response.needs_modification(request, &changes);
+                    return Ok(handle.response.with_diff(response, diff));
                 }
                 Ok(handle.response.is_matched(request))
             }
@@ -152,20 +165,22 @@ impl TemplateAction {
             TemplateMode::Strict,
             &String::from("src"),
             &template_contents,
+            TemplateEngine::for_path(&self.src),
         )?;
         if write {
-            handle.remote.write_data(request, &data, &self.dest, |f| {
-                /* after save */
-                match handle.remote.process_all_common_file_attributes(
-                    request,
-                    f,
-                    &self.attributes,
-                    Recurse::No,
-                ) {
-                    Ok(_x) => Ok(()),
-                    Err(y) => Err(y),
-                }
-            })?;
+            handle
+                .remote
+                .write_data(request, &data, &self.dest, &self.attributes, |f| {
+                    /* after save */
+                    match handle.remote.process_transferred_file_attributes(
+                        request,
+                        f,
+                        &self.attributes,
+                    ) {
+                        Ok(_x) => Ok(()),
+                        Err(y) => Err(y),
+                    }
+                })?;
         }
         Ok(data)
     }
@@ -335,6 +335,7 @@ impl GithubReleaseAction {
                 command_result: Arc::new(None),
                 with: Arc::new(None),
                 and: Arc::new(None),
+                diff: None,
             })
         })
     }
@@ -521,7 +522,7 @@ mod tests {
         };
 
         // Scenario 1: v0.36.0-rc2 vs v0.35.0 - should pick the newer RC
-        let releases1 = vec![
+        let releases1 = [
             GithubRelease {
                 tag_name: String::from("v0.36.0-rc2"),
                 name: Some(String::from("Release Candidate")),
@@ -547,7 +548,7 @@ mod tests {
         assert_eq!(best1.tag_name, "v0.36.0-rc2"); // Newer version wins
 
         // Scenario 2: v0.36.0 vs v0.36.0-rc2 - should pick the stable
-        let releases2 = vec![
+        let releases2 = [
             GithubRelease {
                 tag_name: String::from("v0.36.0"),
                 name: Some(String::from("Stable Release")),
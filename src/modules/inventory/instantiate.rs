@@ -33,8 +33,8 @@
 //!   provision:
 //!     type: proxmox_vm          # or proxmox_lxc
 //!     cluster: SpaceTempAgency
-//!     memory: "2048"
-//!     cores: "4"
+//!     memory: 2048
+//!     cores: 4
 //!     storage: "moosefs"
 //!     rootfs_size: "20G"
 //!   groups:
@@ -127,13 +127,13 @@ pub struct ProvisionTemplate {
     #[serde(rename = "type")]
     pub provision_type: String,
     pub cluster: String,
-    pub memory: Option<String>,
-    pub cores: Option<String>,
+    pub memory: Option<Templated<u64>>,
+    pub cores: Option<Templated<u64>>,
     pub ostemplate: Option<String>,
     pub storage: Option<String>,
     pub rootfs_size: Option<String>,
-    pub unprivileged: Option<String>,
-    pub start_on_create: Option<String>,
+    pub unprivileged: Option<Templated<bool>>,
+    pub start_on_create: Option<Templated<bool>>,
     pub features: Option<String>,
     pub authorized_keys: Option<String>,
     pub ssh_user: Option<String>,
@@ -271,8 +271,47 @@ impl IsTask for InstantiateTask {
             .clone()
             .unwrap_or_else(|| String::from("present"));
 
-        // Template the provision fields that might have variables
+        // Template the provision fields that might have variables; typed fields are
+        // kept as the values they render to
         let mut provision_template = self.provision.clone();
+        provision_template.memory = handle
+            .template
+            .integer_option(
+                request,
+                tm,
+                &String::from("memory"),
+                &self.provision.memory,
+                None,
+            )?
+            .map(Templated::Value);
+        provision_template.cores = handle
+            .template
+            .integer_option(
+                request,
+                tm,
+                &String::from("cores"),
+                &self.provision.cores,
+                None,
+            )?
+            .map(Templated::Value);
+        provision_template.unprivileged = handle
+            .template
+            .boolean_option_default_none(
+                request,
+                tm,
+                &String::from("unprivileged"),
+                &self.provision.unprivileged,
+            )?
+            .map(Templated::Value);
+        provision_template.start_on_create = handle
+            .template
+            .boolean_option_default_none(
+                request,
+                tm,
+                &String::from("start_on_create"),
+                &self.provision.start_on_create,
+            )?
+            .map(Templated::Value);
         if let Some(ref keys) = provision_template.authorized_keys {
             provision_template.authorized_keys = Some(handle.template.string(
                 request,
@@ -465,16 +504,16 @@ impl InstantiateAction {
             }
 
             // Optional fields from template
-            if let Some(ref memory) = self.provision_template.memory {
+            if let Some(Templated::Value(memory)) = self.provision_template.memory {
                 provision.insert(
                     serde_yaml::Value::String("memory".to_string()),
-                    serde_yaml::Value::String(memory.clone()),
+                    serde_yaml::Value::from(memory),
                 );
             }
-            if let Some(ref cores) = self.provision_template.cores {
+            if let Some(Templated::Value(cores)) = self.provision_template.cores {
                 provision.insert(
                     serde_yaml::Value::String("cores".to_string()),
-                    serde_yaml::Value::String(cores.clone()),
+                    serde_yaml::Value::from(cores),
                 );
             }
             if let Some(ref ostemplate) = self.provision_template.ostemplate {
@@ -495,16 +534,17 @@ impl InstantiateAction {
                     serde_yaml::Value::String(rootfs_size.clone()),
                 );
             }
-            if let Some(ref unprivileged) = self.provision_template.unprivileged {
+            if let Some(Templated::Value(unprivileged)) = self.provision_template.unprivileged {
                 provision.insert(
                     serde_yaml::Value::String("unprivileged".to_string()),
-                    serde_yaml::Value::String(unprivileged.clone()),
+                    serde_yaml::Value::from(unprivileged),
                 );
             }
-            if let Some(ref start_on_create) = self.provision_template.start_on_create {
+            if let Some(Templated::Value(start_on_create)) = self.provision_template.start_on_create
+            {
                 provision.insert(
                     serde_yaml::Value::String("start_on_create".to_string()),
-                    serde_yaml::Value::String(start_on_create.clone()),
+                    serde_yaml::Value::from(start_on_create),
                 );
             }
             if let Some(ref features) = self.provision_template.features {
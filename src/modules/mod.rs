@@ -24,3 +24,4 @@ pub mod inventory;
 pub mod packages;
 pub mod proxmox;
 pub mod services;
+pub mod system;
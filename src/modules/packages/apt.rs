@@ -15,10 +15,14 @@
 // long with this program.  If not, see <http://www.gnu.org/licenses/>.
 
 use crate::handle::handle::{CheckRc, TaskHandle};
-use crate::modules::packages::common::{PackageDetails, PackageManagementModule};
+use crate::modules::packages::common::{
+    PackageDetails, PackageManagementModule, PackagePlan, UpgradeMode, quote_packages,
+    template_packages,
+};
 use crate::tasks::*;
 use serde::Deserialize;
-use std::sync::Arc;
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex};
 
 const MODULE: &str = "apt";
 
@@ -27,53 +31,83 @@ const MODULE: &str = "apt";
 #[derive(Default)]
 pub struct AptTask {
     pub name: Option<String>,
-    pub package: String,
+    pub package: Option<String>,
+    pub packages: Option<Vec<String>>,
     pub version: Option<String>,
-    pub update: Option<String>,
-    pub upgrade: Option<String>,
-    pub remove: Option<String>,
+    pub update: Option<Templated<bool>>,
+    pub upgrade: Option<Templated<UpgradeMode>>,
+    pub remove: Option<Templated<bool>>,
+    pub hold: Option<Templated<bool>>,
+    pub save: Option<String>,
     pub with: Option<PreLogicInput>,
+    // set by !package, which hands over package names it has already templated
+    #[serde(skip)]
+    pub rendered: bool,
     pub and: Option<PostLogicInput>,
 }
 
 struct AptAction {
-    pub package: String,
+    pub packages: Vec<String>,
     pub version: Option<String>,
     pub update: bool,
-    pub upgrade: bool,
+    pub upgrade: UpgradeMode,
     pub remove: bool,
+    pub hold: Option<bool>,
+    pub save: Option<String>,
+    planned: Mutex<Option<PackagePlan>>,
 }
 
 impl AptAction {
     /// Check if package is a local .deb file path
-    fn is_local_deb(&self) -> bool {
-        self.package.ends_with(".deb")
-            || self.package.starts_with("/") && self.package.contains(".deb")
-            || self.package.starts_with("./")
+    fn is_local_deb(package: &str) -> bool {
+        package.ends_with(".deb")
+            || package.starts_with("/") && package.contains(".deb")
+            || package.starts_with("./")
     }
 
-    /// Extract package name from a .deb file using dpkg-deb
-    fn get_deb_package_name(
+    /// Extract a control field (Package, Version) from a .deb file using dpkg-deb
+    fn get_deb_field(
         &self,
         handle: &Arc<TaskHandle>,
         request: &Arc<TaskRequest>,
+        package: &str,
+        field: &str,
     ) -> Result<String, Arc<TaskResponse>> {
-        let cmd = format!("dpkg-deb -f '{}' Package", self.package);
+        let cmd = format!("dpkg-deb -f '{}' {}", package, field);
         let result = handle.remote.run(request, &cmd, CheckRc::Checked)?;
         let (_, out) = cmd_info(&result);
         Ok(out.trim().to_string())
     }
 
-    /// Extract version from a .deb file using dpkg-deb
-    fn get_deb_version(
+    /// The installed package name for each package in the task, which for a local
+    /// .deb is read from the file
+    fn get_package_names(
         &self,
         handle: &Arc<TaskHandle>,
         request: &Arc<TaskRequest>,
-    ) -> Result<String, Arc<TaskResponse>> {
-        let cmd = format!("dpkg-deb -f '{}' Version", self.package);
-        let result = handle.remote.run(request, &cmd, CheckRc::Checked)?;
-        let (_, out) = cmd_info(&result);
-        Ok(out.trim().to_string())
+        packages: &[String],
+    ) -> Result<Vec<String>, Arc<TaskResponse>> {
+        let mut names: Vec<String> = Vec::new();
+        for package in packages.iter() {
+            match Self::is_local_deb(package) {
+                true => names.push(self.get_deb_field(handle, request, package, "Package")?),
+                false => names.push(package.clone()),
+            }
+        }
+        Ok(names)
+    }
+
+    /// name=version when a version is pinned, local .deb paths as they are
+    fn get_package_specs(&self, packages: &[String]) -> Vec<String> {
+        packages
+            .iter()
+            .map(|package| match &self.version {
+                Some(version) if !Self::is_local_deb(package) => {
+                    format!("{}={}", package, version)
+                }
+                _ => package.clone(),
+            })
+            .collect()
     }
 }
 
@@ -94,13 +128,24 @@ impl IsTask for AptTask {
         request: &Arc<TaskRequest>,
         tm: TemplateMode,
     ) -> Result<EvaluatedTask, Arc<TaskResponse>> {
+        let upgrade = handle.template.scalar_option_default(
+            request,
+            tm,
+            "upgrade",
+            &self.upgrade,
+            UpgradeMode::Off,
+        )?;
         Ok(EvaluatedTask {
             action: Arc::new(AptAction {
-                package: handle.template.string_no_spaces(
+                packages: template_packages(
+                    handle,
                     request,
                     tm,
-                    &String::from("package"),
                     &self.package,
+                    &self.packages,
+                    &self.version,
+                    upgrade,
+                    self.rendered,
                 )?,
                 version: handle.template.string_option_no_spaces(
                     request,
@@ -114,18 +159,26 @@ impl IsTask for AptTask {
                     &String::from("update"),
                     &self.update,
                 )?,
-                upgrade: handle.template.boolean_option_default_false(
-                    request,
-                    tm,
-                    &String::from("upgrade"),
-                    &self.upgrade,
-                )?,
+                upgrade,
                 remove: handle.template.boolean_option_default_false(
                     request,
                     tm,
                     &String::from("remove"),
                     &self.remove,
                 )?,
+                hold: handle.template.boolean_option_default_none(
+                    request,
+                    tm,
+                    &String::from("hold"),
+                    &self.hold,
+                )?,
+                save: handle.template.string_option_no_spaces(
+                    request,
+                    tm,
+                    &String::from("save"),
+                    &self.save,
+                )?,
+                planned: Mutex::new(None),
             }),
             with: Arc::new(PreLogicInput::template(handle, request, tm, &self.with)?),
             and: Arc::new(PostLogicInput::template(handle, request, tm, &self.and)?),
@@ -197,7 +250,7 @@ impl PackageManagementModule for AptAction {
     }
 
     fn is_update(&self) -> bool {
-        self.upgrade
+        self.upgrade == UpgradeMode::Packages
     }
 
     fn is_remove(&self) -> bool {
@@ -208,175 +261,345 @@ impl PackageManagementModule for AptAction {
         self.version.clone()
     }
 
-    fn get_remote_version(
+    fn get_packages(&self) -> &[String] {
+        &self.packages
+    }
+
+    fn get_planned(&self) -> &Mutex<Option<PackagePlan>> {
+        &self.planned
+    }
+
+    fn get_remote_versions(
         &self,
         handle: &Arc<TaskHandle>,
         request: &Arc<TaskRequest>,
-    ) -> Result<Option<PackageDetails>, Arc<TaskResponse>> {
-        // For local .deb files, get version from the .deb itself
-        if self.is_local_deb() {
-            let pkg_name = self.get_deb_package_name(handle, request)?;
-            let version = self.get_deb_version(handle, request)?;
-            return Ok(Some(PackageDetails {
-                name: pkg_name,
-                version,
-            }));
+    ) -> Result<HashMap<String, PackageDetails>, Arc<TaskResponse>> {
+        let mut versions: HashMap<String, PackageDetails> = HashMap::new();
+        // For local .deb files, the version is the one in the .deb itself
+        let (debs, named): (Vec<String>, Vec<String>) = self
+            .packages
+            .iter()
+            .cloned()
+            .partition(|x| Self::is_local_deb(x));
+        for deb in debs.iter() {
+            let details = PackageDetails {
+                name: self.get_deb_field(handle, request, deb, "Package")?,
+                version: self.get_deb_field(handle, request, deb, "Version")?,
+            };
+            versions.insert(deb.clone(), details);
+        }
+        if named.is_empty() {
+            return Ok(versions);
         }
 
-        /* need to implement so update returns the correct modification status */
-        let cmd = format!(
-            "apt-cache show {} | grep Version | cut -f2 --delimiter=':'",
-            self.package.clone()
-        );
-        let result = handle.remote.run_unsafe(request, &cmd, CheckRc::Unchecked);
-        match result {
-            Ok(r) => {
-                let (rc, out) = cmd_info(&r);
-                // (differentiated return code is unavailable for this module only, don't repeat this pattern)
-                if out.contains("No packages found") {
-                    return Ok(None);
-                }
-                if rc == 0 {
-                    self.parse_remote_package_details(handle, &out.clone())
-                } else {
-                    Ok(None)
-                }
+        let cmd = format!("apt-cache policy {}", quote_packages(&named));
+        let result = handle.remote.run(request, &cmd, CheckRc::Unchecked)?;
+        let (_, out) = cmd_info(&result);
+        let candidates = parse_policy_candidates(&out);
+        for package in named.iter() {
+            if let Some(version) = candidates.get(package) {
+                versions.insert(
+                    package.clone(),
+                    PackageDetails {
+                        name: package.clone(),
+                        version: version.clone(),
+                    },
+                );
             }
-            Err(e) => Err(e),
         }
+        Ok(versions)
     }
 
-    fn get_local_version(
+    fn get_local_versions(
         &self,
         handle: &Arc<TaskHandle>,
         request: &Arc<TaskRequest>,
-    ) -> Result<Option<PackageDetails>, Arc<TaskResponse>> {
+    ) -> Result<HashMap<String, PackageDetails>, Arc<TaskResponse>> {
         // For local .deb files, check if the package (not the file) is installed
-        let pkg_name = if self.is_local_deb() {
-            self.get_deb_package_name(handle, request)?
-        } else {
-            self.package.clone()
-        };
+        let names = self.get_package_names(handle, request, &self.packages)?;
 
-        let cmd = format!("dpkg -s '{}' >/dev/null", pkg_name);
-        let result = handle.remote.run_unsafe(request, &cmd, CheckRc::Unchecked);
-        match result {
-            Ok(r) => {
-                let (rc, _out) = cmd_info(&r);
-                if rc != 0 {
-                    return Ok(None);
-                }
-            }
-            Err(e) => return Err(e),
-        }
+        // packages removed with their configuration kept are still listed, hence the status.
+        // dpkg-query fails when any of them is unknown but still prints the rest.
+        let cmd = format!(
+            "dpkg-query -W -f='${{Package}}\\t${{Version}}\\t${{db:Status-Status}}\\n' {}",
+            quote_packages(&names)
+        );
+        let result = handle
+            .remote
+            .run_unsafe(request, &cmd, CheckRc::Unchecked)?;
+        let (_, out) = cmd_info(&result);
+        let installed = parse_installed(&out);
 
-        let cmd = format!("dpkg-query -W '{}'", pkg_name);
-        let result = handle.remote.run(request, &cmd, CheckRc::Unchecked);
-        match result {
-            Ok(r) => {
-                let (rc, out) = cmd_info(&r);
-                if rc == 0 {
-                    let details = self.parse_local_package_details_with_name(
-                        handle,
-                        &pkg_name,
-                        &out.clone(),
-                    )?;
-                    Ok(details)
-                } else {
-                    Ok(None)
-                }
+        let mut versions: HashMap<String, PackageDetails> = HashMap::new();
+        for (package, name) in self.packages.iter().zip(names.iter()) {
+            // name:arch queries are listed under the bare name
+            let bare = name.split(':').next().unwrap_or(name);
+            if let Some(version) = installed.get(bare) {
+                versions.insert(
+                    package.clone(),
+                    PackageDetails {
+                        name: name.clone(),
+                        version: version.clone(),
+                    },
+                );
             }
-            Err(e) => Err(e),
         }
+        Ok(versions)
     }
 
-    fn install_package(
+    fn install_packages(
         &self,
         handle: &Arc<TaskHandle>,
         request: &Arc<TaskRequest>,
+        packages: &[String],
     ) -> Result<Arc<TaskResponse>, Arc<TaskResponse>> {
-        // For local .deb files, use apt-get install with the file path
-        // apt-get install handles dependencies better than dpkg -i
-        if self.is_local_deb() {
-            let cmd = format!(
-                "DEBIAN_FRONTEND=noninteractive apt-get install -y '{}'",
-                self.package
-            );
-            return handle.remote.run(request, &cmd, CheckRc::Checked);
-        }
+        // apt-get install handles local .deb dependencies better than dpkg -i
+        let cmd = format!(
+            "DEBIAN_FRONTEND=noninteractive apt-get install {} -qq",
+            quote_packages(&self.get_package_specs(packages))
+        );
+        handle.remote.run(request, &cmd, CheckRc::Checked)
+    }
 
-        let cmd = match self.version.is_none() {
-            true => format!(
-                "DEBIAN_FRONTEND=noninteractive apt-get install '{}' -qq",
-                self.package
-            ),
-            false => format!(
-                "DEBIAN_FRONTEND=noninteractive apt-get install '{}={}' -qq",
-                self.package,
-                self.version.as_ref().unwrap()
-            ),
-        };
+    fn update_packages(
+        &self,
+        handle: &Arc<TaskHandle>,
+        request: &Arc<TaskRequest>,
+        packages: &[String],
+    ) -> Result<Arc<TaskResponse>, Arc<TaskResponse>> {
+        let cmd = format!(
+            "DEBIAN_FRONTEND=noninteractive apt-get install {} --only-upgrade -qq",
+            quote_packages(&self.get_package_specs(packages))
+        );
         handle.remote.run(request, &cmd, CheckRc::Checked)
     }
 
-    fn update_package(
+    fn remove_packages(
         &self,
         handle: &Arc<TaskHandle>,
         request: &Arc<TaskRequest>,
+        packages: &[String],
     ) -> Result<Arc<TaskResponse>, Arc<TaskResponse>> {
-        let cmd = match self.version.is_none() {
-            true => format!(
-                "DEBIAN_FRONTEND=noninteractive apt-get install '{}' --only-upgrade -qq",
-                self.package
-            ),
-            false => format!(
-                "DEBIAN_FRONTEND=noninteractive apt-get install '{}={}' --only-upgrade -qq",
-                self.package,
-                self.version.as_ref().unwrap()
-            ),
-        };
+        let names = self.get_package_names(handle, request, packages)?;
+        let cmd = format!(
+            "DEBIAN_FRONTEND=noninteractive apt-get remove {} -qq",
+            quote_packages(&names)
+        );
         handle.remote.run(request, &cmd, CheckRc::Checked)
     }
 
-    fn remove_package(
+    fn get_hold(&self) -> Option<bool> {
+        self.hold
+    }
+
+    fn get_upgrade_mode(&self) -> UpgradeMode {
+        self.upgrade
+    }
+
+    fn get_save(&self) -> Option<String> {
+        self.save.clone()
+    }
+
+    fn get_held_packages(
+        &self,
+        handle: &Arc<TaskHandle>,
+        request: &Arc<TaskRequest>,
+    ) -> Result<HashSet<String>, Arc<TaskResponse>> {
+        let cmd = String::from("apt-mark showhold");
+        let result = handle.remote.run(request, &cmd, CheckRc::Checked)?;
+        let (_, out) = cmd_info(&result);
+        Ok(out.lines().map(|x| x.trim().to_string()).collect())
+    }
+
+    fn hold_packages(
         &self,
         handle: &Arc<TaskHandle>,
         request: &Arc<TaskRequest>,
+        packages: &[String],
+        hold: bool,
     ) -> Result<Arc<TaskResponse>, Arc<TaskResponse>> {
+        let names = self.get_package_names(handle, request, packages)?;
         let cmd = format!(
-            "DEBIAN_FRONTEND=noninteractive apt-get remove '{}' -qq",
-            self.package
+            "apt-mark {} {}",
+            match hold {
+                true => "hold",
+                false => "unhold",
+            },
+            quote_packages(&names)
         );
         handle.remote.run(request, &cmd, CheckRc::Checked)
     }
-}
 
-impl AptAction {
-    pub fn parse_local_package_details_with_name(
+    fn plan_system_upgrade(
         &self,
-        _handle: &Arc<TaskHandle>,
-        pkg_name: &str,
-        out: &str,
-    ) -> Result<Option<PackageDetails>, Arc<TaskResponse>> {
-        let mut tokens = out.split("\t");
-        let version = tokens.nth(1);
-        match version {
-            Some(v) => Ok(Some(PackageDetails {
-                name: pkg_name.to_string(),
-                version: v.trim().to_string(),
-            })),
-            None => Ok(None),
-        }
+        handle: &Arc<TaskHandle>,
+        request: &Arc<TaskRequest>,
+    ) -> Result<PackagePlan, Arc<TaskResponse>> {
+        let cmd = String::from("apt-get -s dist-upgrade");
+        let result = handle.remote.run(request, &cmd, CheckRc::Checked)?;
+        let (_, out) = cmd_info(&result);
+        Ok(parse_simulation(
+            &out,
+            self.upgrade == UpgradeMode::Security,
+        ))
     }
 
-    pub fn parse_remote_package_details(
+    fn system_upgrade(
         &self,
-        _handle: &Arc<TaskHandle>,
-        out: &str,
-    ) -> Result<Option<PackageDetails>, Arc<TaskResponse>> {
-        Ok(Some(PackageDetails {
-            name: self.package.clone(),
-            version: out.trim().to_string(),
-        }))
+        handle: &Arc<TaskHandle>,
+        request: &Arc<TaskRequest>,
+        plan: &PackagePlan,
+    ) -> Result<Arc<TaskResponse>, Arc<TaskResponse>> {
+        // security fixes are installed by name so nothing else comes along, apart from
+        // whatever new dependencies they need
+        let cmd = match self.upgrade {
+            UpgradeMode::Security => format!(
+                "DEBIAN_FRONTEND=noninteractive apt-get install {} --only-upgrade -qq",
+                quote_packages(&plan.update_names())
+            ),
+            _ => String::from("DEBIAN_FRONTEND=noninteractive apt-get dist-upgrade -qq"),
+        };
+        handle.remote.run(request, &cmd, CheckRc::Checked)
+    }
+
+    fn is_reboot_required(
+        &self,
+        handle: &Arc<TaskHandle>,
+        request: &Arc<TaskRequest>,
+    ) -> Result<bool, Arc<TaskResponse>> {
+        handle
+            .remote
+            .file_exists(request, &String::from("/var/run/reboot-required"))
+    }
+}
+
+// name -> version for each "name\tversion\tstatus" line of dpkg-query that is installed
+
+fn parse_installed(out: &str) -> HashMap<String, String> {
+    let mut installed: HashMap<String, String> = HashMap::new();
+    for line in out.lines() {
+        let fields: Vec<&str> = line.split('\t').collect();
+        if let [name, version, status] = fields.as_slice()
+            && status.trim() == "installed"
+        {
+            installed.insert(name.to_string(), version.trim().to_string());
+        }
+    }
+    installed
+}
+
+// name -> candidate version from apt-cache policy, which prints one block per package:
+//
+// ```text
+// curl:
+//   Installed: 7.81.0-1ubuntu1.14
+//   Candidate: 7.81.0-1ubuntu1.15
+//   Version table:
+// ```
+
+fn parse_policy_candidates(out: &str) -> HashMap<String, String> {
+    let mut candidates: HashMap<String, String> = HashMap::new();
+    let mut current: Option<&str> = None;
+    for line in out.lines() {
+        if !line.starts_with(' ') && line.ends_with(':') {
+            current = Some(line.trim_end_matches(':'));
+        } else if let (Some(name), Some(version)) =
+            (current, line.trim().strip_prefix("Candidate:"))
+        {
+            let version = version.trim();
+            if version != "(none)" {
+                candidates.insert(name.to_string(), version.to_string());
+            }
+        }
+    }
+    candidates
+}
+
+// the plan of an "apt-get -s dist-upgrade" dry run, which prints lines such as:
+//
+// ```text
+// Inst libc6 [2.36-9+deb12u3] (2.36-9+deb12u4 Debian-Security:12/stable-security [amd64])
+// Inst linux-image-6.1.0-18-amd64 (6.1.76-1 Debian:12.5/stable [amd64])
+// Remv linux-image-6.1.0-13-amd64 [6.1.55-1]
+// ```
+//
+// For security only upgrades, just the upgrades coming from a security archive are kept.
+
+fn parse_simulation(out: &str, security_only: bool) -> PackagePlan {
+    let mut plan = PackagePlan::default();
+    for line in out.lines() {
+        let mut words = line.split_whitespace();
+        let (action, name) = match (words.next(), words.next()) {
+            (Some(action), Some(name)) => (action, name.to_string()),
+            _ => continue,
+        };
+        let rest: Vec<&str> = words.collect();
+        let installed = rest
+            .first()
+            .filter(|x| x.starts_with('['))
+            .map(|x| x.trim_matches(|c| c == '[' || c == ']').to_string());
+        let candidate = rest
+            .iter()
+            .position(|x| x.starts_with('('))
+            .map(|i| rest[i].trim_start_matches('(').to_string());
+        let origin = rest.join(" ").to_lowercase();
+        match (action, installed) {
+            ("Inst", Some(installed)) if !security_only || origin.contains("security") => {
+                plan.update.push((name, installed, candidate))
+            }
+            ("Inst", None) if !security_only => plan.install.push(name),
+            ("Remv", _) if !security_only => plan.remove.push(name),
+            _ => {}
+        }
+    }
+    plan
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dpkg_query_output_only_counts_installed_packages() {
+        let out = "curl\t7.81.0-1ubuntu1.15\tinstalled\n\
+                   apache2\t2.4.52-1ubuntu4\tconfig-files\n\
+                   dpkg-query: no packages found matching nosuch\n";
+        let installed = parse_installed(out);
+        assert_eq!(installed.len(), 1);
+        assert_eq!(installed.get("curl").unwrap(), "7.81.0-1ubuntu1.15");
+    }
+
+    #[test]
+    fn policy_candidates_are_read_per_package() {
+        let out = "curl:\n  Installed: 7.81.0-1ubuntu1.14\n  Candidate: 7.81.0-1ubuntu1.15\n  Version table:\n     7.81.0-1ubuntu1.15 500\n\
+                   nosuch:\n  Installed: (none)\n  Candidate: (none)\n  Version table:\n";
+        let candidates = parse_policy_candidates(out);
+        assert_eq!(candidates.get("curl").unwrap(), "7.81.0-1ubuntu1.15");
+        assert!(!candidates.contains_key("nosuch"));
+    }
+
+    #[test]
+    fn simulation_is_planned_and_filtered_for_security() {
+        let out = "NOTE: This is only a simulation!\n\
+                   Inst libc6 [2.36-9+deb12u3] (2.36-9+deb12u4 Debian-Security:12/stable-security [amd64])\n\
+                   Inst curl [7.88.1-10] (7.88.1-10+deb12u5 Debian:12.5/stable [amd64])\n\
+                   Inst linux-image-6.1.0-18-amd64 (6.1.76-1 Debian:12.5/stable [amd64])\n\
+                   Remv linux-image-6.1.0-13-amd64 [6.1.55-1]\n\
+                   Conf libc6 (2.36-9+deb12u4 Debian-Security:12/stable-security [amd64])\n";
+        let plan = parse_simulation(out, false);
+        assert_eq!(plan.install, vec!["linux-image-6.1.0-18-amd64"]);
+        assert_eq!(plan.remove, vec!["linux-image-6.1.0-13-amd64"]);
+        assert_eq!(plan.update_names(), vec!["libc6", "curl"]);
+
+        let plan = parse_simulation(out, true);
+        assert_eq!(
+            plan.update,
+            vec![(
+                String::from("libc6"),
+                String::from("2.36-9+deb12u3"),
+                Some(String::from("2.36-9+deb12u4"))
+            )]
+        );
+        assert!(plan.install.is_empty() && plan.remove.is_empty());
     }
 }
@@ -0,0 +1,548 @@
+// Jetporch
+// Copyright (C) 2023 - Michael DeHaan <michael@michaeldehaan.net> + contributors
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// long with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use crate::handle::handle::{CheckRc, TaskHandle};
+use crate::tasks::cmd_library::shell_quote;
+use crate::tasks::fields::Field;
+use crate::tasks::files::Recurse;
+use crate::tasks::*;
+use crate::util::diff::unified;
+use serde::Deserialize;
+use std::sync::Arc;
+
+const MODULE: &str = "apt_repository";
+const SOURCES_DIR: &str = "/etc/apt/sources.list.d";
+const KEYRINGS_DIR: &str = "/etc/apt/keyrings";
+
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+#[derive(Default)]
+pub struct AptRepositoryTask {
+    pub name: Option<String>,
+    pub id: String,
+    pub types: Option<Vec<String>>,
+    pub uris: Option<Vec<String>>,
+    pub suites: Option<Vec<String>>,
+    pub components: Option<Vec<String>>,
+    pub architectures: Option<Vec<String>>,
+    pub key_url: Option<String>,
+    pub key_fingerprint: Option<String>,
+    pub update: Option<Templated<bool>>,
+    pub remove: Option<Templated<bool>>,
+    pub with: Option<PreLogicInput>,
+    pub and: Option<PostLogicInput>,
+}
+
+struct AptRepositoryAction {
+    pub id: String,
+    pub sources_path: String,
+    pub keyring_path: Option<String>,
+    pub content: String,
+    pub key_url: Option<String>,
+    pub key_fingerprint: Option<String>,
+    pub update: bool,
+    pub remove: bool,
+}
+
+// the deb822 stanza written to <id>.sources
+
+#[derive(Default)]
+struct Sources {
+    types: Vec<String>,
+    uris: Vec<String>,
+    suites: Vec<String>,
+    components: Vec<String>,
+    architectures: Vec<String>,
+    signed_by: Option<String>,
+}
+
+impl Sources {
+    fn render(&self) -> String {
+        let mut out = String::new();
+        for (field, values) in [
+            ("Types", &self.types),
+            ("URIs", &self.uris),
+            ("Suites", &self.suites),
+            ("Components", &self.components),
+            ("Architectures", &self.architectures),
+        ] {
+            if !values.is_empty() {
+                out.push_str(&format!("{}: {}\n", field, values.join(" ")));
+            }
+        }
+        if let Some(signed_by) = &self.signed_by {
+            out.push_str(&format!("Signed-By: {}\n", signed_by));
+        }
+        out
+    }
+}
+
+impl IsTask for AptRepositoryTask {
+    fn get_module(&self) -> String {
+        String::from(MODULE)
+    }
+    fn get_name(&self) -> Option<String> {
+        self.name.clone()
+    }
+    fn get_with(&self) -> Option<PreLogicInput> {
+        self.with.clone()
+    }
+
+    fn evaluate(
+        &self,
+        handle: &Arc<TaskHandle>,
+        request: &Arc<TaskRequest>,
+        tm: TemplateMode,
+    ) -> Result<EvaluatedTask, Arc<TaskResponse>> {
+        let id = handle
+            .template
+            .string_no_spaces(request, tm, &String::from("id"), &self.id)?;
+        if id.contains('/') {
+            return Err(handle
+                .response
+                .is_failed(request, "id is used as a file name and cannot contain '/'"));
+        }
+        let key_url = handle.template.string_option_unsafe_for_shell(
+            request,
+            tm,
+            &String::from("key_url"),
+            &self.key_url,
+        )?;
+        let key_fingerprint = match handle.template.string_option_unsafe_for_shell(
+            request,
+            tm,
+            &String::from("key_fingerprint"),
+            &self.key_fingerprint,
+        )? {
+            Some(_) if key_url.is_none() => {
+                return Err(handle
+                    .response
+                    .is_failed(request, "key_fingerprint requires key_url"));
+            }
+            Some(fingerprint) if tm == TemplateMode::Strict => Some(
+                normalize_fingerprint(&fingerprint)
+                    .map_err(|e| handle.response.is_failed(request, &e))?,
+            ),
+            _ => None,
+        };
+        // apt reads ASCII armored keys only from .asc files, binary ones from .gpg
+        let keyring_path = key_url.as_ref().map(|url| match url.ends_with(".gpg") {
+            true => format!("{}/{}.gpg", KEYRINGS_DIR, id),
+            false => format!("{}/{}.asc", KEYRINGS_DIR, id),
+        });
+
+        let mut types = template_list(handle, request, tm, "types", &self.types)?;
+        if types.is_empty() {
+            types.push(String::from("deb"));
+        }
+        let sources = Sources {
+            types,
+            uris: template_list(handle, request, tm, "uris", &self.uris)?,
+            suites: template_list(handle, request, tm, "suites", &self.suites)?,
+            components: template_list(handle, request, tm, "components", &self.components)?,
+            architectures: template_list(
+                handle,
+                request,
+                tm,
+                "architectures",
+                &self.architectures,
+            )?,
+            signed_by: keyring_path.clone(),
+        };
+        let remove = handle.template.boolean_option_default_false(
+            request,
+            tm,
+            &String::from("remove"),
+            &self.remove,
+        )?;
+        if tm == TemplateMode::Strict && !remove {
+            if sources.uris.is_empty() || sources.suites.is_empty() {
+                return Err(handle
+                    .response
+                    .is_failed(request, "uris and suites need at least one entry each"));
+            }
+            // a suite ending in / is an exact path (flat repository), which takes no components
+            let flat = sources.suites.iter().all(|x| x.ends_with('/'));
+            if flat != sources.components.is_empty() {
+                return Err(handle.response.is_failed(
+                    request,
+                    "components are required, except for flat repositories whose suites end in /",
+                ));
+            }
+        }
+
+        Ok(EvaluatedTask {
+            action: Arc::new(AptRepositoryAction {
+                sources_path: format!("{}/{}.sources", SOURCES_DIR, id),
+                id,
+                keyring_path,
+                content: sources.render(),
+                key_url,
+                key_fingerprint,
+                update: handle.template.boolean_option_default_true(
+                    request,
+                    tm,
+                    &String::from("update"),
+                    &self.update,
+                )?,
+                remove,
+            }),
+            with: Arc::new(PreLogicInput::template(handle, request, tm, &self.with)?),
+            and: Arc::new(PostLogicInput::template(handle, request, tm, &self.and)?),
+        })
+    }
+}
+
+impl IsAction for AptRepositoryAction {
+    fn dispatch(
+        &self,
+        handle: &Arc<TaskHandle>,
+        request: &Arc<TaskRequest>,
+    ) -> Result<Arc<TaskResponse>, Arc<TaskResponse>> {
+        match request.request_type {
+            TaskRequestType::Query => {
+                let current = handle.remote.read_file(request, &self.sources_path)?;
+                if self.remove {
+                    let mut found = current.is_some();
+                    for keyring in self.owned_keyrings().iter() {
+                        found = found || handle.remote.file_exists(request, keyring)?;
+                    }
+                    return match found {
+                        true => Ok(handle.response.needs_removal(request)),
+                        false => Ok(handle.response.is_matched(request)),
+                    };
+                }
+                let key_current = self.is_key_current(handle, request)?;
+                let response = match &current {
+                    None => handle.response.needs_creation(request),
+                    Some(content) if *content != self.content || !key_current => handle
+                        .response
+                        .needs_modification(request, &[Field::Content]),
+                    Some(_) => return Ok(handle.response.is_matched(request)),
+                };
+                let diff = match handle.response.wants_diff() {
+                    true => unified(
+                        &self.sources_path,
+                        current.as_ref().map(|x| x.as_bytes()),
+                        self.content.as_bytes(),
+                    ),
+                    false => None,
+                };
+                Ok(handle.response.with_diff(response, diff))
+            }
+
+            TaskRequestType::Create => {
+                self.apply(handle, request)?;
+                Ok(handle.response.is_created(request))
+            }
+
+            TaskRequestType::Modify => {
+                self.apply(handle, request)?;
+                Ok(handle
+                    .response
+                    .is_modified(request, request.changes.clone()))
+            }
+
+            TaskRequestType::Remove => {
+                if handle.remote.file_exists(request, &self.sources_path)? {
+                    handle.remote.delete_file(request, &self.sources_path)?;
+                }
+                for keyring in self.owned_keyrings().iter() {
+                    if handle.remote.file_exists(request, keyring)? {
+                        handle.remote.delete_file(request, keyring)?;
+                    }
+                }
+                self.refresh(handle, request)?;
+                Ok(handle.response.is_removed(request))
+            }
+
+            _ => Err(handle.response.not_supported(request)),
+        }
+    }
+}
+
+impl AptRepositoryAction {
+    // removal does not need key_url, so it looks for a keyring under either name
+
+    fn owned_keyrings(&self) -> Vec<String> {
+        vec![
+            format!("{}/{}.asc", KEYRINGS_DIR, self.id),
+            format!("{}/{}.gpg", KEYRINGS_DIR, self.id),
+        ]
+    }
+
+    // installs the key if needed, then the sources file, and refreshes the package
+    // lists. Only reached when something changed, so the refresh never runs idle.
+
+    fn apply(
+        &self,
+        handle: &Arc<TaskHandle>,
+        request: &Arc<TaskRequest>,
+    ) -> Result<(), Arc<TaskResponse>> {
+        if !self.is_key_current(handle, request)? {
+            self.install_key(handle, request)?;
+        }
+        let current = handle.remote.read_file(request, &self.sources_path)?;
+        if current.as_deref() != Some(self.content.as_str()) {
+            handle
+                .remote
+                .write_data(request, &self.content, &self.sources_path, &None, |f| {
+                    handle
+                        .remote
+                        .set_mode(request, f, "0644", Recurse::No)
+                        .map(|_| ())
+                })?;
+        }
+        self.refresh(handle, request)
+    }
+
+    // the key is current when there is none to manage, or the keyring file exists and,
+    // when a fingerprint is pinned, holds that key and no other
+
+    fn is_key_current(
+        &self,
+        handle: &Arc<TaskHandle>,
+        request: &Arc<TaskRequest>,
+    ) -> Result<bool, Arc<TaskResponse>> {
+        let keyring = match &self.keyring_path {
+            Some(keyring) => keyring,
+            None => return Ok(true),
+        };
+        if !handle.remote.file_exists(request, keyring)? {
+            return Ok(false);
+        }
+        match &self.key_fingerprint {
+            Some(fingerprint) => Ok(is_only_key(
+                &get_fingerprints(handle, request, keyring)?,
+                fingerprint,
+            )),
+            None => Ok(true),
+        }
+    }
+
+    fn install_key(
+        &self,
+        handle: &Arc<TaskHandle>,
+        request: &Arc<TaskRequest>,
+    ) -> Result<(), Arc<TaskResponse>> {
+        let (url, keyring) = match (&self.key_url, &self.keyring_path) {
+            (Some(url), Some(keyring)) => (url, keyring),
+            _ => return Ok(()),
+        };
+        handle.remote.create_directory(request, KEYRINGS_DIR)?;
+        let temp = format!("{}.jetpack-download", keyring);
+        let cmd = format!("curl -fsSL -o {} {}", shell_quote(&temp), shell_quote(url));
+        handle.remote.run_unsafe(request, &cmd, CheckRc::Checked)?;
+        if let Some(fingerprint) = &self.key_fingerprint {
+            let found = get_fingerprints(handle, request, &temp)?;
+            // Signed-By trusts every key in the file, so one extra key would defeat the pin
+            if !is_only_key(&found, fingerprint) {
+                handle.remote.delete_file(request, &temp)?;
+                return Err(handle.response.is_failed(
+                    request,
+                    &format!(
+                        "the key file at {} holds key(s) {}, expected only {}",
+                        url,
+                        found.join(", "),
+                        fingerprint
+                    ),
+                ));
+            }
+        }
+        handle
+            .remote
+            .set_mode(request, &temp, "0644", Recurse::No)?;
+        handle.remote.rename(request, &temp, keyring, true)?;
+        Ok(())
+    }
+
+    fn refresh(
+        &self,
+        handle: &Arc<TaskHandle>,
+        request: &Arc<TaskRequest>,
+    ) -> Result<(), Arc<TaskResponse>> {
+        if self.update {
+            handle.remote.run(
+                request,
+                &String::from("apt-get update -qq"),
+                CheckRc::Checked,
+            )?;
+        }
+        Ok(())
+    }
+}
+
+// the fingerprints of the primary keys in a keyring file
+
+fn get_fingerprints(
+    handle: &Arc<TaskHandle>,
+    request: &Arc<TaskRequest>,
+    path: &str,
+) -> Result<Vec<String>, Arc<TaskResponse>> {
+    let cmd = format!("gpg --show-keys --with-colons {}", shell_quote(path));
+    let result = handle
+        .remote
+        .run_unsafe(request, &cmd, CheckRc::Unchecked)?;
+    let (rc, out) = cmd_info(&result);
+    if rc != 0 {
+        return Err(handle.response.is_failed(
+            request,
+            &format!(
+                "gpg is needed to check the key fingerprint of {}: {}",
+                path, out
+            ),
+        ));
+    }
+    Ok(parse_fingerprints(&out))
+}
+
+// a key's fpr record follows its pub record; subkeys have their own after each sub
+
+fn parse_fingerprints(out: &str) -> Vec<String> {
+    let mut primary = false;
+    let mut found = Vec::new();
+    for line in out.lines() {
+        let mut fields = line.split(':');
+        match fields.next() {
+            Some("pub") => primary = true,
+            Some("fpr") if primary => {
+                if let Some(fpr) = fields.nth(8).filter(|x| !x.is_empty()) {
+                    found.push(fpr.to_ascii_uppercase());
+                }
+                primary = false;
+            }
+            _ => {}
+        }
+    }
+    found
+}
+
+fn is_only_key(found: &[String], fingerprint: &str) -> bool {
+    !found.is_empty() && found.iter().all(|x| x == fingerprint)
+}
+
+// accepts fingerprints the way gpg prints them, in groups of four, or as one string
+
+fn normalize_fingerprint(input: &str) -> Result<String, String> {
+    let fingerprint: String = input
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .collect::<String>()
+        .to_ascii_uppercase();
+    match (fingerprint.len() == 40 || fingerprint.len() == 64)
+        && fingerprint.chars().all(|c| c.is_ascii_hexdigit())
+    {
+        true => Ok(fingerprint),
+        false => Err(format!(
+            "key_fingerprint must be a full 40 or 64 digit hex fingerprint, got '{}'",
+            input
+        )),
+    }
+}
+
+// list fields only ever land in the sources file, never a shell, but whitespace would
+// run one value into the next (or into a new field), so it is refused
+
+fn template_list(
+    handle: &Arc<TaskHandle>,
+    request: &Arc<TaskRequest>,
+    tm: TemplateMode,
+    field: &str,
+    input: &Option<Vec<String>>,
+) -> Result<Vec<String>, Arc<TaskResponse>> {
+    let mut result: Vec<String> = Vec::new();
+    for value in input.as_deref().unwrap_or_default() {
+        let value = handle
+            .template
+            .string_unsafe_for_shell(request, tm, field, value)?;
+        if value.is_empty() || value.chars().any(char::is_whitespace) {
+            return Err(handle.response.is_failed(
+                request,
+                &format!("field {}: '{}' must be a single word", field, value),
+            ));
+        }
+        result.push(value);
+    }
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sources_render_as_deb822() {
+        let sources = Sources {
+            types: vec![String::from("deb")],
+            uris: vec![String::from("https://download.docker.com/linux/debian")],
+            suites: vec![String::from("bookworm")],
+            components: vec![String::from("stable")],
+            architectures: vec![String::from("amd64"), String::from("arm64")],
+            signed_by: Some(String::from("/etc/apt/keyrings/docker.asc")),
+        };
+        assert_eq!(
+            sources.render(),
+            "Types: deb\n\
+             URIs: https://download.docker.com/linux/debian\n\
+             Suites: bookworm\n\
+             Components: stable\n\
+             Architectures: amd64 arm64\n\
+             Signed-By: /etc/apt/keyrings/docker.asc\n"
+        );
+    }
+
+    #[test]
+    fn fingerprints_are_normalized_and_parsed() {
+        assert_eq!(
+            normalize_fingerprint("9dc8 5822 9fc7 dd38 854a  e2d8 8d81 803c 0ebf cd88").unwrap(),
+            "9DC858229FC7DD38854AE2D88D81803C0EBFCD88"
+        );
+        assert!(normalize_fingerprint("0EBFCD88").is_err());
+        let out = "pub:-:4096:1:8D81803C0EBFCD88:1487788586:::-:::scESA::::::23::0:\n\
+                   fpr:::::::::9DC858229FC7DD38854AE2D88D81803C0EBFCD88:\n\
+                   uid:-::::1487792064::B5F2A6A1::Docker Release (CE deb):::::::::0:\n\
+                   sub:-:4096:1:7EA0A9C3F273FCD8:1487788586::::::s::::::23:\n\
+                   fpr:::::::::D3306A018370199E527AE7997EA0A9C3F273FCD8:\n";
+        assert_eq!(
+            parse_fingerprints(out),
+            vec!["9DC858229FC7DD38854AE2D88D81803C0EBFCD88"]
+        );
+        assert!(is_only_key(
+            &parse_fingerprints(out),
+            "9DC858229FC7DD38854AE2D88D81803C0EBFCD88"
+        ));
+    }
+
+    #[test]
+    fn a_pinned_key_file_must_hold_nothing_else() {
+        let out = "pub:-:4096:1:8D81803C0EBFCD88:1487788586:::-:::scESA::::::23::0:\n\
+                   fpr:::::::::9DC858229FC7DD38854AE2D88D81803C0EBFCD88:\n\
+                   uid:-::::1487792064::B5F2A6A1::Docker Release (CE deb):::::::::0:\n\
+                   pub:-:255:22:AAAABBBBCCCCDDDD:1700000000:::-:::scESC::::::ed25519:::0:\n\
+                   fpr:::::::::0123456789ABCDEF0123456789ABCDEF01234567:\n\
+                   uid:-::::1700000000::0000000000::Someone Else:::::::::0:\n";
+        let found = parse_fingerprints(out);
+        assert_eq!(found.len(), 2);
+        assert!(!is_only_key(
+            &found,
+            "9DC858229FC7DD38854AE2D88D81803C0EBFCD88"
+        ));
+        assert!(!is_only_key(
+            &[],
+            "9DC858229FC7DD38854AE2D88D81803C0EBFCD88"
+        ));
+    }
+}
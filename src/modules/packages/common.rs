@@ -15,9 +15,12 @@
 // long with this program.  If not, see <http://www.gnu.org/licenses/>.
 
 use crate::handle::handle::TaskHandle;
+use crate::tasks::cmd_library::screen_general_input_strict;
 use crate::tasks::fields::Field;
+use crate::tasks::templated::TemplatedScalar;
 use crate::tasks::*;
-use std::sync::Arc;
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex};
 
 #[derive(Clone, PartialEq, Debug)]
 pub struct PackageDetails {
@@ -25,10 +28,186 @@ pub struct PackageDetails {
     pub version: String,
 }
 
+// what a package task would do to a host, worked out by the query and carried into the
+// action leg so that the install, update and remove transactions only name the packages
+// that need them
+
+#[derive(Default, PartialEq, Debug)]
+pub struct PackagePlan {
+    pub install: Vec<String>,
+    // package, installed version, version it moves to when known
+    pub update: Vec<(String, String, Option<String>)>,
+    pub remove: Vec<String>,
+    pub hold: Vec<String>,
+    pub unhold: Vec<String>,
+}
+
+impl PackagePlan {
+    pub fn is_empty(&self) -> bool {
+        self.install.is_empty()
+            && self.update.is_empty()
+            && self.remove.is_empty()
+            && self.hold.is_empty()
+            && self.unhold.is_empty()
+    }
+
+    pub fn update_names(&self) -> Vec<String> {
+        self.update
+            .iter()
+            .map(|(name, _, _)| name.clone())
+            .collect()
+    }
+
+    // one line per package: "+ nginx", "~ curl 8.3.0 -> 8.4.0", "- apache2", "= hold linux-image-amd64"
+
+    pub fn summary(&self) -> String {
+        let mut lines: Vec<String> = Vec::new();
+        for package in self.install.iter() {
+            lines.push(format!("+ {}", package));
+        }
+        for (package, from, to) in self.update.iter() {
+            match to {
+                Some(to) => lines.push(format!("~ {} {} -> {}", package, from, to)),
+                None => lines.push(format!("~ {} {}", package, from)),
+            }
+        }
+        for package in self.remove.iter() {
+            lines.push(format!("- {}", package));
+        }
+        for package in self.hold.iter() {
+            lines.push(format!("= hold {}", package));
+        }
+        for package in self.unhold.iter() {
+            lines.push(format!("= unhold {}", package));
+        }
+        lines.join("\n")
+    }
+}
+
+// what upgrade means for a task: true upgrades the packages it names, while dist and
+// security upgrade the whole system (everything, or only security fixes)
+
+#[derive(Clone, Copy, PartialEq, Debug, Default)]
+pub enum UpgradeMode {
+    #[default]
+    Off,
+    Packages,
+    Dist,
+    Security,
+}
+
+impl UpgradeMode {
+    pub fn is_system_wide(&self) -> bool {
+        matches!(self, UpgradeMode::Dist | UpgradeMode::Security)
+    }
+}
+
+impl TemplatedScalar for UpgradeMode {
+    const TYPE_NAME: &'static str = "a boolean, dist or security";
+
+    fn from_yaml(value: &serde_yaml::Value) -> Option<Self> {
+        value.as_bool().map(|x| match x {
+            true => UpgradeMode::Packages,
+            false => UpgradeMode::Off,
+        })
+    }
+
+    fn parse_rendered(rendered: &str) -> Option<Self> {
+        match rendered.trim().to_lowercase().as_str() {
+            "dist" => Some(UpgradeMode::Dist),
+            "security" => Some(UpgradeMode::Security),
+            other => bool::parse_rendered(other).map(|x| match x {
+                true => UpgradeMode::Packages,
+                false => UpgradeMode::Off,
+            }),
+        }
+    }
+}
+
+// templates the package and packages fields of a package task into one list. A
+// version only makes sense when the task names a single package, and a system wide
+// upgrade takes no packages at all. Names that are already rendered (!package
+// templates them to look them up in its names table) are taken as they are, so a
+// rendered name is never templated a second time.
+
+#[allow(clippy::too_many_arguments)]
+pub fn template_packages(
+    handle: &Arc<TaskHandle>,
+    request: &Arc<TaskRequest>,
+    tm: TemplateMode,
+    package: &Option<String>,
+    packages: &Option<Vec<String>>,
+    version: &Option<String>,
+    upgrade: UpgradeMode,
+    rendered: bool,
+) -> Result<Vec<String>, Arc<TaskResponse>> {
+    let mut result: Vec<String> = Vec::new();
+    if rendered {
+        // still screened, a names table may have swapped in a name of its own
+        for name in package.iter().chain(packages.iter().flatten()) {
+            if name.contains(' ') {
+                return Err(handle
+                    .response
+                    .is_failed(request, "field (packages): spaces are not allowed"));
+            }
+            let name = screen_general_input_strict(name).map_err(|e| {
+                handle
+                    .response
+                    .is_failed(request, &format!("field packages, {}", e))
+            })?;
+            result.push(name);
+        }
+    } else {
+        if let Some(package) = package {
+            result.push(handle.template.string_no_spaces(
+                request,
+                tm,
+                &String::from("package"),
+                package,
+            )?);
+        }
+        for package in packages.as_deref().unwrap_or_default() {
+            result.push(handle.template.string_no_spaces(
+                request,
+                tm,
+                &String::from("packages"),
+                package,
+            )?);
+        }
+    }
+    if tm == TemplateMode::Strict {
+        if upgrade.is_system_wide() {
+            if !result.is_empty() {
+                return Err(handle.response.is_failed(
+                    request,
+                    "upgrade: dist and security apply to the whole system and take no packages",
+                ));
+            }
+            return Ok(result);
+        }
+        if result.is_empty() {
+            return Err(handle
+                .response
+                .is_failed(request, "package or packages is required"));
+        }
+        if version.is_some() && result.len() > 1 {
+            return Err(handle
+                .response
+                .is_failed(request, "version can only be used with a single package"));
+        }
+    }
+    Ok(result)
+}
+
 pub trait PackageManagementModule {
     fn is_update(&self) -> bool;
     fn is_remove(&self) -> bool;
     fn get_version(&self) -> Option<String>;
+    fn get_packages(&self) -> &[String];
+
+    // where the query leaves its plan for the create, modify or remove leg that follows
+
+    fn get_planned(&self) -> &Mutex<Option<PackagePlan>>;
 
     fn initial_setup(
         &self,
@@ -36,78 +215,250 @@ pub trait PackageManagementModule {
         request: &Arc<TaskRequest>,
     ) -> Result<(), Arc<TaskResponse>>;
 
-    fn get_local_version(
+    // the installed version of every package in the task, from a single query, keyed
+    // by the package as written in the task. Packages that are not installed are left out.
+
+    fn get_local_versions(
         &self,
         handle: &Arc<TaskHandle>,
         request: &Arc<TaskRequest>,
-    ) -> Result<Option<PackageDetails>, Arc<TaskResponse>>;
+    ) -> Result<HashMap<String, PackageDetails>, Arc<TaskResponse>>;
 
-    fn get_remote_version(
+    // likewise, the version each package would be upgraded to
+
+    fn get_remote_versions(
         &self,
         handle: &Arc<TaskHandle>,
         request: &Arc<TaskRequest>,
-    ) -> Result<Option<PackageDetails>, Arc<TaskResponse>>;
+    ) -> Result<HashMap<String, PackageDetails>, Arc<TaskResponse>>;
 
-    fn install_package(
+    fn install_packages(
         &self,
         handle: &Arc<TaskHandle>,
         request: &Arc<TaskRequest>,
+        packages: &[String],
     ) -> Result<Arc<TaskResponse>, Arc<TaskResponse>>;
 
-    fn update_package(
+    fn update_packages(
         &self,
         handle: &Arc<TaskHandle>,
         request: &Arc<TaskRequest>,
+        packages: &[String],
     ) -> Result<Arc<TaskResponse>, Arc<TaskResponse>>;
 
-    fn remove_package(
+    fn remove_packages(
         &self,
         handle: &Arc<TaskHandle>,
         request: &Arc<TaskRequest>,
+        packages: &[String],
     ) -> Result<Arc<TaskResponse>, Arc<TaskResponse>>;
 
-    fn common_package_query(
+    // holds, system wide upgrades and reboot detection are only offered by apt and
+    // dnf/yum. The other modules have no fields for them, so these defaults are never
+    // reached through a task.
+
+    fn get_hold(&self) -> Option<bool> {
+        None
+    }
+
+    fn get_upgrade_mode(&self) -> UpgradeMode {
+        UpgradeMode::Off
+    }
+
+    // variable to save { reboot_required: bool } under
+
+    fn get_save(&self) -> Option<String> {
+        None
+    }
+
+    fn get_held_packages(
+        &self,
+        _handle: &Arc<TaskHandle>,
+        _request: &Arc<TaskRequest>,
+    ) -> Result<HashSet<String>, Arc<TaskResponse>> {
+        Ok(HashSet::new())
+    }
+
+    fn hold_packages(
         &self,
         handle: &Arc<TaskHandle>,
         request: &Arc<TaskRequest>,
+        _packages: &[String],
+        _hold: bool,
     ) -> Result<Arc<TaskResponse>, Arc<TaskResponse>> {
-        let mut changes: Vec<Field> = Vec::new();
+        Err(handle.response.not_supported(request))
+    }
 
-        self.initial_setup(handle, request)?;
+    // what upgrade: dist or security would change, from a dry run of the package manager
+
+    fn plan_system_upgrade(
+        &self,
+        handle: &Arc<TaskHandle>,
+        request: &Arc<TaskRequest>,
+    ) -> Result<PackagePlan, Arc<TaskResponse>> {
+        Err(handle.response.not_supported(request))
+    }
 
-        let package_details = self.get_local_version(handle, request)?;
+    fn system_upgrade(
+        &self,
+        handle: &Arc<TaskHandle>,
+        request: &Arc<TaskRequest>,
+        _plan: &PackagePlan,
+    ) -> Result<Arc<TaskResponse>, Arc<TaskResponse>> {
+        Err(handle.response.not_supported(request))
+    }
 
-        if let Some(package_details) = package_details {
-            // package is installed
-            if self.is_remove() {
-                return Ok(handle.response.needs_removal(request));
-            }
-            let pkg = package_details;
-            let version = self.get_version();
+    fn is_reboot_required(
+        &self,
+        handle: &Arc<TaskHandle>,
+        request: &Arc<TaskRequest>,
+    ) -> Result<bool, Arc<TaskResponse>> {
+        Err(handle.response.not_supported(request))
+    }
+
+    fn save_reboot_required(
+        &self,
+        handle: &Arc<TaskHandle>,
+        request: &Arc<TaskRequest>,
+    ) -> Result<(), Arc<TaskResponse>> {
+        if let Some(save) = self.get_save() {
+            let required = self.is_reboot_required(handle, request)?;
+            let mut value = serde_yaml::Mapping::new();
+            value.insert(
+                serde_yaml::Value::String(String::from("reboot_required")),
+                serde_yaml::Value::Bool(required),
+            );
+            let mut result = serde_yaml::Mapping::new();
+            result.insert(
+                serde_yaml::Value::String(save),
+                serde_yaml::Value::Mapping(value),
+            );
+            handle.host.write().unwrap().update_variables(result);
+        }
+        Ok(())
+    }
+
+    fn apply_holds(
+        &self,
+        handle: &Arc<TaskHandle>,
+        request: &Arc<TaskRequest>,
+        plan: &PackagePlan,
+    ) -> Result<(), Arc<TaskResponse>> {
+        if !plan.hold.is_empty() {
+            self.hold_packages(handle, request, &plan.hold, true)?;
+        }
+        if !plan.unhold.is_empty() {
+            self.hold_packages(handle, request, &plan.unhold, false)?;
+        }
+        Ok(())
+    }
+
+    fn plan_packages(
+        &self,
+        handle: &Arc<TaskHandle>,
+        request: &Arc<TaskRequest>,
+    ) -> Result<PackagePlan, Arc<TaskResponse>> {
+        self.initial_setup(handle, request)?;
+        if self.get_upgrade_mode().is_system_wide() {
+            return self.plan_system_upgrade(handle, request);
+        }
+
+        let local = self.get_local_versions(handle, request)?;
+        let remote = match self.is_update() && !self.is_remove() {
+            true => self.get_remote_versions(handle, request)?,
+            false => HashMap::new(),
+        };
+        let version = self.get_version();
 
-            if self.is_update() {
-                let remote_details = self.get_remote_version(handle, request)?;
-                if remote_details.is_none() || !pkg.version.eq(&remote_details.unwrap().version) {
-                    changes.push(Field::Version);
+        let mut plan = PackagePlan::default();
+        for package in self.get_packages().iter() {
+            match local.get(package) {
+                // package is installed
+                Some(_) if self.is_remove() => plan.remove.push(package.clone()),
+                Some(pkg) if self.is_update() => match remote.get(package) {
+                    Some(remote_pkg) if remote_pkg.version == pkg.version => {}
+                    remote_pkg => plan.update.push((
+                        package.clone(),
+                        pkg.version.clone(),
+                        remote_pkg.map(|x| x.version.clone()),
+                    )),
+                },
+                Some(pkg) => {
+                    if let Some(specified_version) = &version
+                        && !pkg.version.eq(specified_version)
+                    {
+                        plan.update.push((
+                            package.clone(),
+                            pkg.version.clone(),
+                            Some(specified_version.clone()),
+                        ));
+                    }
                 }
-            } else if version.is_some() {
-                let specified_version = version.as_ref().unwrap();
-                if !pkg.version.eq(specified_version) {
-                    changes.push(Field::Version);
+                // package is not installed
+                None if self.is_remove() => {}
+                None => plan.install.push(package.clone()),
+            }
+        }
+        if let Some(hold) = self.get_hold()
+            && !self.is_remove()
+        {
+            let held = self.get_held_packages(handle, request)?;
+            for package in self.get_packages().iter() {
+                match (hold, held.contains(package)) {
+                    (true, false) => plan.hold.push(package.clone()),
+                    (false, true) => plan.unhold.push(package.clone()),
+                    _ => {}
                 }
             }
+        }
+        Ok(plan)
+    }
 
-            if !changes.is_empty() {
-                Ok(handle.response.needs_modification(request, &changes))
-            } else {
-                Ok(handle.response.is_matched(request))
-            }
+    fn common_package_query(
+        &self,
+        handle: &Arc<TaskHandle>,
+        request: &Arc<TaskRequest>,
+    ) -> Result<Arc<TaskResponse>, Arc<TaskResponse>> {
+        let plan = self.plan_packages(handle, request)?;
+        let summary = match handle.response.wants_diff() {
+            true => Some(plan.summary()),
+            false => None,
+        };
+        // reported even when nothing changes, a reboot may still be pending from an earlier run
+        self.save_reboot_required(handle, request)?;
+        let system_wide = self.get_upgrade_mode().is_system_wide();
+        let mut changes: Vec<Field> = Vec::new();
+        // a system upgrade may also pull in or drop packages, it is still one upgrade
+        if !plan.update.is_empty() || (system_wide && !plan.is_empty()) {
+            changes.push(Field::Version);
+        }
+        if !plan.hold.is_empty() || !plan.unhold.is_empty() {
+            changes.push(Field::Hold);
+        }
+        let response = if !plan.remove.is_empty() && !system_wide {
+            handle.response.needs_removal(request)
+        } else if !plan.install.is_empty() && !system_wide {
+            handle.response.needs_creation(request)
+        } else if !changes.is_empty() {
+            handle.response.needs_modification(request, &changes)
         } else {
-            // package is not installed
-            match self.is_remove() {
-                true => Ok(handle.response.is_matched(request)),
-                false => Ok(handle.response.needs_creation(request)),
-            }
+            return Ok(handle.response.is_matched(request));
+        };
+        *self.get_planned().lock().unwrap() = Some(plan);
+        Ok(handle.response.with_diff(response, summary))
+    }
+
+    // the plan the query made, or a fresh one should an action leg ever run without it
+
+    fn planned_packages(
+        &self,
+        handle: &Arc<TaskHandle>,
+        request: &Arc<TaskRequest>,
+    ) -> Result<PackagePlan, Arc<TaskResponse>> {
+        let planned = self.get_planned().lock().unwrap().take();
+        match planned {
+            Some(plan) => Ok(plan),
+            None => self.plan_packages(handle, request),
         }
     }
 
@@ -120,21 +471,43 @@ pub trait PackageManagementModule {
             TaskRequestType::Query => self.common_package_query(handle, request),
 
             TaskRequestType::Create => {
-                self.install_package(handle, request)?;
+                let plan = self.planned_packages(handle, request)?;
+                if !plan.install.is_empty() {
+                    self.install_packages(handle, request, &plan.install)?;
+                }
+                if !plan.update.is_empty() {
+                    self.update_packages(handle, request, &plan.update_names())?;
+                }
+                self.apply_holds(handle, request, &plan)?;
+                self.save_reboot_required(handle, request)?;
                 Ok(handle.response.is_created(request))
             }
 
             TaskRequestType::Modify => {
+                let plan = self.planned_packages(handle, request)?;
                 if request.changes.contains(&Field::Version) {
-                    self.update_package(handle, request)?;
+                    if self.get_upgrade_mode().is_system_wide() {
+                        if !plan.is_empty() {
+                            self.system_upgrade(handle, request, &plan)?;
+                        }
+                    } else if !plan.update.is_empty() {
+                        self.update_packages(handle, request, &plan.update_names())?;
+                    }
+                }
+                if request.changes.contains(&Field::Hold) {
+                    self.apply_holds(handle, request, &plan)?;
                 }
+                self.save_reboot_required(handle, request)?;
                 Ok(handle
                     .response
                     .is_modified(request, request.changes.clone()))
             }
 
             TaskRequestType::Remove => {
-                self.remove_package(handle, request)?;
+                let plan = self.planned_packages(handle, request)?;
+                if !plan.remove.is_empty() {
+                    self.remove_packages(handle, request, &plan.remove)?;
+                }
                 Ok(handle.response.is_removed(request))
             }
 
@@ -142,3 +515,69 @@ pub trait PackageManagementModule {
         }
     }
 }
+
+// single-quotes every package for a package manager command line
+
+pub fn quote_packages(packages: &[String]) -> String {
+    packages
+        .iter()
+        .map(|x| format!("'{}'", x))
+        .collect::<Vec<String>>()
+        .join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{RecordingConnection, query_request, test_handle};
+
+    #[test]
+    fn plan_summary_lists_every_package() {
+        let plan = PackagePlan {
+            install: vec![String::from("nginx"), String::from("jq")],
+            update: vec![
+                (
+                    String::from("curl"),
+                    String::from("8.3.0"),
+                    Some(String::from("8.4.0")),
+                ),
+                (String::from("git"), String::from("2.40"), None),
+            ],
+            ..Default::default()
+        };
+        assert!(!plan.is_empty());
+        assert_eq!(
+            plan.summary(),
+            "+ nginx\n+ jq\n~ curl 8.3.0 -> 8.4.0\n~ git 2.40"
+        );
+        assert_eq!(plan.update_names(), vec!["curl", "git"]);
+        assert!(PackagePlan::default().is_empty());
+    }
+
+    #[test]
+    fn rendered_names_are_not_templated_again() {
+        let handle = test_handle(Arc::new(Mutex::new(RecordingConnection::new())));
+        let request = query_request();
+        let rendered = |name: &str| {
+            template_packages(
+                &handle,
+                &request,
+                TemplateMode::Strict,
+                &None,
+                &Some(vec![String::from(name)]),
+                &None,
+                UpgradeMode::Off,
+                true,
+            )
+        };
+        assert_eq!(rendered("nginx").unwrap(), vec![String::from("nginx")]);
+        assert!(rendered("two words").is_err());
+        // screened as it is, never handed to the templating engine
+        let failed = rendered("{{web}}").unwrap_err();
+        assert!(
+            failed.msg.as_ref().unwrap().contains("illegal characters"),
+            "{:?}",
+            failed.msg
+        );
+    }
+}
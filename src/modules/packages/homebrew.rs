@@ -15,10 +15,14 @@
 // long with this program.  If not, see <http://www.gnu.org/licenses/>.
 
 use crate::handle::handle::{CheckRc, TaskHandle};
-use crate::modules::packages::common::{PackageDetails, PackageManagementModule};
+use crate::modules::packages::common::{
+    PackageDetails, PackageManagementModule, PackagePlan, UpgradeMode, quote_packages,
+    template_packages,
+};
 use crate::tasks::*;
 use serde::Deserialize;
-use std::sync::Arc;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
 
 const MODULE: &str = "homebrew";
 
@@ -27,19 +31,24 @@ const MODULE: &str = "homebrew";
 #[derive(Default)]
 pub struct HomebrewTask {
     pub name: Option<String>,
-    pub package: String,
+    pub package: Option<String>,
+    pub packages: Option<Vec<String>>,
     pub version: Option<String>,
-    pub update: Option<String>,
-    pub remove: Option<String>,
+    pub update: Option<Templated<bool>>,
+    pub remove: Option<Templated<bool>>,
     pub with: Option<PreLogicInput>,
+    // set by !package, which hands over package names it has already templated
+    #[serde(skip)]
+    pub rendered: bool,
     pub and: Option<PostLogicInput>,
 }
 
 struct HomebrewAction {
-    pub package: String,
+    pub packages: Vec<String>,
     pub version: Option<String>,
     pub update: bool,
     pub remove: bool,
+    planned: Mutex<Option<PackagePlan>>,
 }
 
 impl IsTask for HomebrewTask {
@@ -61,11 +70,15 @@ impl IsTask for HomebrewTask {
     ) -> Result<EvaluatedTask, Arc<TaskResponse>> {
         Ok(EvaluatedTask {
             action: Arc::new(HomebrewAction {
-                package: handle.template.string_no_spaces(
+                packages: template_packages(
+                    handle,
                     request,
                     tm,
-                    &String::from("package"),
                     &self.package,
+                    &self.packages,
+                    &self.version,
+                    UpgradeMode::Off,
+                    self.rendered,
                 )?,
                 version: handle.template.string_option_no_spaces(
                     request,
@@ -85,6 +98,7 @@ impl IsTask for HomebrewTask {
                     &String::from("remove"),
                     &self.remove,
                 )?,
+                planned: Mutex::new(None),
             }),
             with: Arc::new(PreLogicInput::template(handle, request, tm, &self.with)?),
             and: Arc::new(PostLogicInput::template(handle, request, tm, &self.and)?),
@@ -124,138 +138,177 @@ impl PackageManagementModule for HomebrewAction {
         self.version.clone()
     }
 
-    fn get_remote_version(
+    fn get_packages(&self) -> &[String] {
+        &self.packages
+    }
+
+    fn get_planned(&self) -> &Mutex<Option<PackagePlan>> {
+        &self.planned
+    }
+
+    fn get_remote_versions(
         &self,
         handle: &Arc<TaskHandle>,
         request: &Arc<TaskRequest>,
-    ) -> Result<Option<PackageDetails>, Arc<TaskResponse>> {
+    ) -> Result<HashMap<String, PackageDetails>, Arc<TaskResponse>> {
         let cmd = format!(
-            "{} info {} | head -n 1 | cut -f 4 -d ' '",
+            "{} info {}",
             self.brew_cmd(),
-            self.package.clone()
+            quote_packages(&self.packages)
         );
-        let result = handle.remote.run_unsafe(request, &cmd, CheckRc::Unchecked);
-        match result {
-            Ok(r) => {
-                let (rc, out) = cmd_info(&r);
-                if rc == 0 {
-                    self.parse_remote_package_details(handle, &out.clone())
-                } else {
-                    Ok(None)
-                }
-            }
-            Err(e) => Err(e),
-        }
+        let result = handle
+            .remote
+            .run_unsafe(request, &cmd, CheckRc::Unchecked)?;
+        let (_rc, out) = cmd_info(&result);
+        Ok(self.by_package(Self::parse_info_versions(&out)))
     }
 
-    fn get_local_version(
+    fn get_local_versions(
         &self,
         handle: &Arc<TaskHandle>,
         request: &Arc<TaskRequest>,
-    ) -> Result<Option<PackageDetails>, Arc<TaskResponse>> {
-        let cmd = format!("{} info {}", self.brew_cmd(), self.package);
-        let result = handle.remote.run(request, &cmd, CheckRc::Unchecked);
-        match result {
-            Ok(r) => {
-                let (rc, out) = cmd_info(&r);
-                if rc == 0 {
-                    let details = self.parse_local_package_details(handle, &out.clone())?;
-                    Ok(details)
-                } else {
-                    Ok(None)
-                }
-            }
-            Err(e) => Err(e),
-        }
+    ) -> Result<HashMap<String, PackageDetails>, Arc<TaskResponse>> {
+        // fails when any package is not installed, but still lists the others
+        let cmd = format!(
+            "{} list --versions {}",
+            self.brew_cmd(),
+            quote_packages(&self.packages)
+        );
+        let result = handle
+            .remote
+            .run_unsafe(request, &cmd, CheckRc::Unchecked)?;
+        let (_rc, out) = cmd_info(&result);
+        Ok(self.by_package(Self::parse_list_versions(&out)))
     }
 
-    fn install_package(
+    fn install_packages(
         &self,
         handle: &Arc<TaskHandle>,
         request: &Arc<TaskRequest>,
+        packages: &[String],
     ) -> Result<Arc<TaskResponse>, Arc<TaskResponse>> {
-        let cmd = match self.version.is_none() {
-            true => format!("{} install '{}'", self.brew_cmd(), self.package),
-            false => format!(
-                "{} install '{}@{}'",
-                self.brew_cmd(),
-                self.package,
-                self.version.as_ref().unwrap()
-            ),
-        };
-        handle.remote.run(request, &cmd, CheckRc::Checked)
+        let cmd = format!(
+            "{} install {}",
+            self.brew_cmd(),
+            quote_packages(&self.get_package_specs(packages))
+        );
+        handle.remote.run_unsafe(request, &cmd, CheckRc::Checked)
     }
 
-    fn update_package(
+    fn update_packages(
         &self,
         handle: &Arc<TaskHandle>,
         request: &Arc<TaskRequest>,
+        packages: &[String],
     ) -> Result<Arc<TaskResponse>, Arc<TaskResponse>> {
-        let cmd = match self.version.is_none() {
-            true => format!("{} upgrade '{}'", self.brew_cmd(), self.package),
-            false => format!(
-                "{} upgrade '{}@{}'",
-                self.brew_cmd(),
-                self.package,
-                self.version.as_ref().unwrap()
-            ),
-        };
-        handle.remote.run(request, &cmd, CheckRc::Checked)
+        let cmd = format!(
+            "{} upgrade {}",
+            self.brew_cmd(),
+            quote_packages(&self.get_package_specs(packages))
+        );
+        handle.remote.run_unsafe(request, &cmd, CheckRc::Checked)
     }
 
-    fn remove_package(
+    fn remove_packages(
         &self,
         handle: &Arc<TaskHandle>,
         request: &Arc<TaskRequest>,
+        packages: &[String],
     ) -> Result<Arc<TaskResponse>, Arc<TaskResponse>> {
-        let cmd = format!("{} uninstall '{}'", self.brew_cmd(), self.package);
-        handle.remote.run(request, &cmd, CheckRc::Checked)
+        let cmd = format!("{} uninstall {}", self.brew_cmd(), quote_packages(packages));
+        handle.remote.run_unsafe(request, &cmd, CheckRc::Checked)
     }
 }
 
 impl HomebrewAction {
+    // brew_cmd is a fixed shell snippet, which is why these commands go through run_unsafe;
+    // the package names themselves are screened when the task is evaluated
+
     fn brew_cmd(&self) -> &'static str {
         "if command -v brew >/dev/null 2>&1; then brew; elif [ -x /opt/homebrew/bin/brew ]; then /opt/homebrew/bin/brew; else brew; fi"
     }
 
-    pub fn parse_local_package_details(
-        &self,
-        _handle: &Arc<TaskHandle>,
-        out: &str,
-    ) -> Result<Option<PackageDetails>, Arc<TaskResponse>> {
-        let mut version: Option<String> = None;
+    /// name@version when a version is pinned
+    fn get_package_specs(&self, packages: &[String]) -> Vec<String> {
+        packages
+            .iter()
+            .map(|package| match &self.version {
+                Some(version) => format!("{}@{}", package, version),
+                None => package.clone(),
+            })
+            .collect()
+    }
 
-        for line in out.lines() {
-            if line.starts_with('/') {
-                let path_version = line.split(" ").nth(0).unwrap().trim();
-                version = Some(path_version.split("/").last().unwrap().trim().to_string());
-            }
-            // homebrew puts an asterisk next to the currently installed version
-            if line.trim().ends_with('*') {
-                break;
+    // brew prints bare formula names, so tapped packages (user/tap/name) are matched on
+    // their last component
+
+    fn by_package(&self, found: HashMap<String, String>) -> HashMap<String, PackageDetails> {
+        let mut versions: HashMap<String, PackageDetails> = HashMap::new();
+        for package in self.packages.iter() {
+            let name = package.rsplit('/').next().unwrap_or(package);
+            if let Some(version) = found.get(name) {
+                versions.insert(
+                    package.clone(),
+                    PackageDetails {
+                        name: name.to_string(),
+                        version: version.clone(),
+                    },
+                );
             }
-            // once homebrew has printed the versions, it starts printing other stuff
-            if line.starts_with("From:") {
-                break;
+        }
+        versions
+    }
+
+    // "name 1.2.3 1.2.4" per installed formula; the last version listed is the newest
+
+    pub fn parse_list_versions(out: &str) -> HashMap<String, String> {
+        let mut versions: HashMap<String, String> = HashMap::new();
+        for line in out.lines() {
+            let mut tokens = line.split_whitespace();
+            if let (Some(name), Some(version)) = (tokens.next(), tokens.last()) {
+                versions.insert(name.to_string(), version.to_string());
             }
         }
-        match version {
-            Some(v) => Ok(Some(PackageDetails {
-                name: self.package.clone(),
-                version: v.trim().to_string(),
-            })),
-            None => Ok(None),
+        versions
+    }
+
+    // one "==> name: stable 1.2.3 (bottled), HEAD" header per formula
+
+    pub fn parse_info_versions(out: &str) -> HashMap<String, String> {
+        let mut versions: HashMap<String, String> = HashMap::new();
+        for line in out.lines() {
+            if let Some((name, rest)) = line
+                .strip_prefix("==> ")
+                .and_then(|x| x.split_once(": stable "))
+                && let Some(version) = rest.split_whitespace().next()
+            {
+                versions.insert(name.to_string(), version.trim_end_matches(',').to_string());
+            }
         }
+        versions
     }
+}
 
-    pub fn parse_remote_package_details(
-        &self,
-        _handle: &Arc<TaskHandle>,
-        out: &str,
-    ) -> Result<Option<PackageDetails>, Arc<TaskResponse>> {
-        Ok(Some(PackageDetails {
-            name: self.package.clone(),
-            version: out.trim().to_string(),
-        }))
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn list_and_info_output_give_versions_per_formula() {
+        let listed = HomebrewAction::parse_list_versions(
+            "jq 1.6 1.7.1
+ripgrep 14.1.0
+",
+        );
+        assert_eq!(listed.get("jq").unwrap(), "1.7.1");
+        assert_eq!(listed.get("ripgrep").unwrap(), "14.1.0");
+        let info = HomebrewAction::parse_info_versions(
+            "==> ripgrep: stable 14.1.1 (bottled), HEAD
+Search tool
+==> jq: stable 1.7.1 (bottled)
+",
+        );
+        assert_eq!(info.get("ripgrep").unwrap(), "14.1.1");
+        assert_eq!(info.get("jq").unwrap(), "1.7.1");
     }
 }
@@ -18,7 +18,10 @@ pub mod common;
 
 /** ADD MODULES HERE, KEEP ALPHABETIZED **/
 pub mod apt;
+pub mod apt_repository;
 pub mod homebrew;
+pub mod package;
 pub mod pacman;
 pub mod yum_dnf;
+pub mod yum_repository;
 pub mod zypper;
@@ -0,0 +1,286 @@
+// Jetporch
+// Copyright (C) 2023 - Michael DeHaan <michael@michaeldehaan.net> + contributors
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// long with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use crate::handle::handle::TaskHandle;
+use crate::inventory::hosts::HostOSType;
+use crate::modules::packages::apt::AptTask;
+use crate::modules::packages::common::{UpgradeMode, template_packages};
+use crate::modules::packages::homebrew::HomebrewTask;
+use crate::modules::packages::pacman::PacmanTask;
+use crate::modules::packages::yum_dnf::YumDnfTask;
+use crate::modules::packages::zypper::ZypperTask;
+use crate::tasks::*;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+const MODULE: &str = "package";
+
+// the distribution independent package module. It works out which package manager
+// the host uses and hands the task to that module, so everything past evaluation
+// (queries, transactions, change reporting) is exactly what !apt, !dnf and friends do.
+
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+#[derive(Default)]
+pub struct PackageTask {
+    pub name: Option<String>,
+    pub package: Option<String>,
+    pub packages: Option<Vec<String>>,
+    pub version: Option<String>,
+    pub update: Option<Templated<bool>>,
+    pub remove: Option<Templated<bool>>,
+    // distribution (os-release ID or ID_LIKE, or "macos") -> generic name -> real name
+    pub names: Option<HashMap<String, HashMap<String, String>>>,
+    pub with: Option<PreLogicInput>,
+    pub and: Option<PostLogicInput>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum PackageBackend {
+    Apt,
+    YumDnf,
+    Pacman,
+    Zypper,
+    Homebrew,
+}
+
+impl IsTask for PackageTask {
+    fn get_module(&self) -> String {
+        String::from(MODULE)
+    }
+    fn get_name(&self) -> Option<String> {
+        self.name.clone()
+    }
+    fn get_with(&self) -> Option<PreLogicInput> {
+        self.with.clone()
+    }
+
+    fn evaluate(
+        &self,
+        handle: &Arc<TaskHandle>,
+        request: &Arc<TaskRequest>,
+        tm: TemplateMode,
+    ) -> Result<EvaluatedTask, Arc<TaskResponse>> {
+        let packages = template_packages(
+            handle,
+            request,
+            tm,
+            &self.package,
+            &self.packages,
+            &self.version,
+            UpgradeMode::Off,
+            false,
+        )?;
+        let distros = get_distros(handle);
+        let backend = match pick_backend(handle, &distros) {
+            Some(backend) => backend,
+            // only the with: section of this pass is used, and its condition may be
+            // what keeps the task away from hosts without facts
+            None if tm == TemplateMode::Off => PackageBackend::Apt,
+            None => {
+                return Err(handle.response.is_failed(
+                    request,
+                    "could not tell which package manager this host uses, run !facts before !package",
+                ));
+            }
+        };
+        let packages = Some(map_names(&self.names, &distros, packages));
+        let version = self.version.clone();
+        let update = self.update.clone();
+        let remove = self.remove.clone();
+        let with = self.with.clone();
+
+        let mut evaluated = match backend {
+            // apt keeps "update" for refreshing the cache, upgrading is "upgrade"
+            PackageBackend::Apt => AptTask {
+                packages,
+                version,
+                upgrade: update.map(|x| match x {
+                    Templated::Value(true) => Templated::Value(UpgradeMode::Packages),
+                    Templated::Value(false) => Templated::Value(UpgradeMode::Off),
+                    Templated::Template(t) => Templated::Template(t),
+                }),
+                remove,
+                with,
+                rendered: true,
+                ..Default::default()
+            }
+            .evaluate(handle, request, tm)?,
+            PackageBackend::YumDnf => YumDnfTask {
+                packages,
+                version,
+                update,
+                remove,
+                with,
+                rendered: true,
+                ..Default::default()
+            }
+            .evaluate(handle, request, tm)?,
+            PackageBackend::Pacman => PacmanTask {
+                packages,
+                version,
+                update,
+                remove,
+                with,
+                rendered: true,
+                ..Default::default()
+            }
+            .evaluate(handle, request, tm)?,
+            PackageBackend::Zypper => ZypperTask {
+                packages,
+                version,
+                update,
+                remove,
+                with,
+                rendered: true,
+                ..Default::default()
+            }
+            .evaluate(handle, request, tm)?,
+            PackageBackend::Homebrew => HomebrewTask {
+                packages,
+                version,
+                update,
+                remove,
+                with,
+                rendered: true,
+                ..Default::default()
+            }
+            .evaluate(handle, request, tm)?,
+        };
+        evaluated.and = Arc::new(PostLogicInput::template(handle, request, tm, &self.and)?);
+        Ok(evaluated)
+    }
+}
+
+// the names a host goes by, most specific first: the os-release ID followed by
+// everything in ID_LIKE, as gathered by !facts. Hosts without os-release are "macos".
+
+fn get_distros(handle: &Arc<TaskHandle>) -> Vec<String> {
+    let host = handle.host.read().unwrap();
+    if let Some(HostOSType::MacOS) = host.os_type {
+        return vec![String::from("macos")];
+    }
+    let variables = host.get_blended_variables();
+    let mut distros: Vec<String> = Vec::new();
+    for key in ["jet_os_release_id", "jet_os_release_id_like"] {
+        if let Some(serde_yaml::Value::String(value)) = variables.get(key) {
+            distros.extend(value.split_whitespace().map(|x| x.to_string()));
+        }
+    }
+    distros
+}
+
+fn pick_backend(handle: &Arc<TaskHandle>, distros: &[String]) -> Option<PackageBackend> {
+    // a yum or dnf module has already probed this host
+    if handle.host.read().unwrap().package_preference.is_some() {
+        return Some(PackageBackend::YumDnf);
+    }
+    backend_for_distros(distros)
+}
+
+fn backend_for_distros(distros: &[String]) -> Option<PackageBackend> {
+    distros.iter().find_map(|distro| match distro.as_str() {
+        "debian" | "ubuntu" => Some(PackageBackend::Apt),
+        "rhel" | "fedora" | "centos" | "rocky" | "almalinux" | "amzn" | "ol" => {
+            Some(PackageBackend::YumDnf)
+        }
+        "arch" | "archarm" | "manjaro" => Some(PackageBackend::Pacman),
+        "suse" | "opensuse" | "sles" | "opensuse-leap" | "opensuse-tumbleweed" => {
+            Some(PackageBackend::Zypper)
+        }
+        "macos" => Some(PackageBackend::Homebrew),
+        _ => None,
+    })
+}
+
+// renames packages using the first table in names that matches the host, so a table
+// for "ubuntu" wins over one for "debian" on Ubuntu. Unlisted packages keep their name.
+
+fn map_names(
+    names: &Option<HashMap<String, HashMap<String, String>>>,
+    distros: &[String],
+    packages: Vec<String>,
+) -> Vec<String> {
+    let table = match names {
+        Some(names) => distros.iter().find_map(|distro| names.get(distro)),
+        None => None,
+    };
+    match table {
+        Some(table) => packages
+            .into_iter()
+            .map(|package| table.get(&package).cloned().unwrap_or(package))
+            .collect(),
+        None => packages,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn distros(list: &[&str]) -> Vec<String> {
+        list.iter().map(|x| x.to_string()).collect()
+    }
+
+    #[test]
+    fn backend_follows_id_then_id_like() {
+        assert_eq!(
+            backend_for_distros(&distros(&["ubuntu", "debian"])),
+            Some(PackageBackend::Apt)
+        );
+        assert_eq!(
+            backend_for_distros(&distros(&["linuxmint", "ubuntu", "debian"])),
+            Some(PackageBackend::Apt)
+        );
+        assert_eq!(
+            backend_for_distros(&distros(&["rocky", "rhel", "centos", "fedora"])),
+            Some(PackageBackend::YumDnf)
+        );
+        assert_eq!(
+            backend_for_distros(&distros(&["opensuse-tumbleweed", "opensuse", "suse"])),
+            Some(PackageBackend::Zypper)
+        );
+        assert_eq!(backend_for_distros(&distros(&["nixos"])), None);
+        assert_eq!(backend_for_distros(&[]), None);
+    }
+
+    #[test]
+    fn most_specific_name_table_wins() {
+        let mut debian = HashMap::new();
+        debian.insert(String::from("httpd"), String::from("apache2"));
+        let mut ubuntu = HashMap::new();
+        ubuntu.insert(String::from("httpd"), String::from("apache2-bin"));
+        let mut names = HashMap::new();
+        names.insert(String::from("debian"), debian);
+        names.insert(String::from("ubuntu"), ubuntu);
+        let names = Some(names);
+        let packages = vec![String::from("httpd"), String::from("curl")];
+
+        assert_eq!(
+            map_names(&names, &distros(&["debian"]), packages.clone()),
+            vec!["apache2", "curl"]
+        );
+        assert_eq!(
+            map_names(&names, &distros(&["ubuntu", "debian"]), packages.clone()),
+            vec!["apache2-bin", "curl"]
+        );
+        assert_eq!(
+            map_names(&names, &distros(&["arch"]), packages.clone()),
+            packages
+        );
+    }
+}
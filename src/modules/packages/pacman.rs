@@ -15,10 +15,14 @@
 // long with this program.  If not, see <http://www.gnu.org/licenses/>.
 
 use crate::handle::handle::{CheckRc, TaskHandle};
-use crate::modules::packages::common::{PackageDetails, PackageManagementModule};
+use crate::modules::packages::common::{
+    PackageDetails, PackageManagementModule, PackagePlan, UpgradeMode, quote_packages,
+    template_packages,
+};
 use crate::tasks::*;
 use serde::Deserialize;
-use std::sync::Arc;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
 
 const MODULE: &str = "pacman";
 
@@ -27,19 +31,24 @@ const MODULE: &str = "pacman";
 #[derive(Default)]
 pub struct PacmanTask {
     pub name: Option<String>,
-    pub package: String,
+    pub package: Option<String>,
+    pub packages: Option<Vec<String>>,
     pub version: Option<String>,
-    pub update: Option<String>,
-    pub remove: Option<String>,
+    pub update: Option<Templated<bool>>,
+    pub remove: Option<Templated<bool>>,
     pub with: Option<PreLogicInput>,
+    // set by !package, which hands over package names it has already templated
+    #[serde(skip)]
+    pub rendered: bool,
     pub and: Option<PostLogicInput>,
 }
 
 struct PacmanAction {
-    pub package: String,
+    pub packages: Vec<String>,
     pub version: Option<String>,
     pub update: bool,
     pub remove: bool,
+    planned: Mutex<Option<PackagePlan>>,
 }
 
 impl IsTask for PacmanTask {
@@ -61,11 +70,15 @@ impl IsTask for PacmanTask {
     ) -> Result<EvaluatedTask, Arc<TaskResponse>> {
         Ok(EvaluatedTask {
             action: Arc::new(PacmanAction {
-                package: handle.template.string_no_spaces(
+                packages: template_packages(
+                    handle,
                     request,
                     tm,
-                    &String::from("package"),
                     &self.package,
+                    &self.packages,
+                    &self.version,
+                    UpgradeMode::Off,
+                    self.rendered,
                 )?,
                 version: handle.template.string_option_no_spaces(
                     request,
@@ -85,6 +98,7 @@ impl IsTask for PacmanTask {
                     &String::from("remove"),
                     &self.remove,
                 )?,
+                planned: Mutex::new(None),
             }),
             with: Arc::new(PreLogicInput::template(handle, request, tm, &self.with)?),
             and: Arc::new(PostLogicInput::template(handle, request, tm, &self.and)?),
@@ -115,6 +129,14 @@ impl PackageManagementModule for PacmanAction {
         self.version.clone()
     }
 
+    fn get_packages(&self) -> &[String] {
+        &self.packages
+    }
+
+    fn get_planned(&self) -> &Mutex<Option<PackagePlan>> {
+        &self.planned
+    }
+
     fn initial_setup(
         &self,
         _handle: &Arc<TaskHandle>,
@@ -123,126 +145,152 @@ impl PackageManagementModule for PacmanAction {
         Ok(())
     }
 
-    fn get_local_version(
+    fn get_local_versions(
         &self,
         handle: &Arc<TaskHandle>,
         request: &Arc<TaskRequest>,
-    ) -> Result<Option<PackageDetails>, Arc<TaskResponse>> {
-        let actual_package = self.get_actual_package();
-        let cmd = match self.version.is_none() {
-            true => format!("pacman -Q --info {}", actual_package),
-            false => format!(
-                "pacman -Q --info {}-{}",
-                actual_package,
-                self.version.as_ref().unwrap()
-            ),
-        };
+    ) -> Result<HashMap<String, PackageDetails>, Arc<TaskResponse>> {
+        let actual_packages: Vec<String> = self
+            .packages
+            .iter()
+            .map(|x| Self::get_actual_package(x))
+            .collect();
+        let cmd = format!("pacman -Q {}", quote_packages(&actual_packages));
         let result = handle.remote.run(request, &cmd, CheckRc::Unchecked)?;
         let (rc, out) = cmd_info(&result);
+        // rc 1 only means some of the packages are not installed
         if rc > 1 {
             return Err(handle
                 .response
                 .is_failed(request, &String::from("pacman query failed")));
         }
-        let details = self.parse_package_details(&out.clone());
-        Ok(details)
+        let installed = Self::parse_package_versions(&out);
+        let mut versions: HashMap<String, PackageDetails> = HashMap::new();
+        for (package, actual) in self.packages.iter().zip(actual_packages.iter()) {
+            if let Some(details) = installed.get(actual) {
+                versions.insert(package.clone(), details.clone());
+            }
+        }
+        Ok(versions)
     }
 
-    fn get_remote_version(
+    fn get_remote_versions(
         &self,
         _handle: &Arc<TaskHandle>,
         _request: &Arc<TaskRequest>,
-    ) -> Result<Option<PackageDetails>, Arc<TaskResponse>> {
+    ) -> Result<HashMap<String, PackageDetails>, Arc<TaskResponse>> {
         // FIXME: (?) without this implemented this module will always return "Modified" with update: true
-        Ok(None)
+        Ok(HashMap::new())
     }
 
-    fn install_package(
+    fn install_packages(
         &self,
         handle: &Arc<TaskHandle>,
         request: &Arc<TaskRequest>,
+        packages: &[String],
     ) -> Result<Arc<TaskResponse>, Arc<TaskResponse>> {
-        let cmd = match self.version.is_none() {
-            true => format!(
-                "pacman -S '{}' --noconfirm --noprogressbar --needed",
-                self.package
-            ),
-            false => format!(
-                "pacman -S '{}={}' --noconfirm --noprogressbar --needed",
-                self.package,
-                self.version.as_ref().unwrap()
-            ),
-        };
+        let specs: Vec<String> = packages
+            .iter()
+            .map(|x| self.get_package_spec(x, x))
+            .collect();
+        let cmd = format!(
+            "pacman -S {} --noconfirm --noprogressbar --needed",
+            quote_packages(&specs)
+        );
         handle.remote.run(request, &cmd, CheckRc::Checked)
     }
 
-    fn update_package(
+    fn update_packages(
         &self,
         handle: &Arc<TaskHandle>,
         request: &Arc<TaskRequest>,
+        packages: &[String],
     ) -> Result<Arc<TaskResponse>, Arc<TaskResponse>> {
-        let actual_package = self.get_actual_package();
-        let cmd = match self.version.is_none() {
-            true => format!("pacman -Syu '{}' --quiet --noconfirm", actual_package),
-            false => format!(
-                "pacman -Syu '{}={}' --quiet --noconfirm",
-                self.package,
-                self.version.as_ref().unwrap()
-            ),
-        };
+        let specs: Vec<String> = packages
+            .iter()
+            .map(|x| self.get_package_spec(x, &Self::get_actual_package(x)))
+            .collect();
+        let cmd = format!("pacman -Syu {} --quiet --noconfirm", quote_packages(&specs));
         handle.remote.run(request, &cmd, CheckRc::Checked)
     }
 
-    fn remove_package(
+    fn remove_packages(
         &self,
         handle: &Arc<TaskHandle>,
         request: &Arc<TaskRequest>,
+        packages: &[String],
     ) -> Result<Arc<TaskResponse>, Arc<TaskResponse>> {
-        let actual_package = self.get_actual_package();
-        let cmd = format!("pacman -R '{}' --noconfirm --noprogressbar", actual_package);
+        let actual_packages: Vec<String> = packages
+            .iter()
+            .map(|x| Self::get_actual_package(x))
+            .collect();
+        let cmd = format!(
+            "pacman -R {} --noconfirm --noprogressbar",
+            quote_packages(&actual_packages)
+        );
         handle.remote.run(request, &cmd, CheckRc::Checked)
     }
 }
 
 impl PacmanAction {
-    pub fn get_actual_package(&self) -> String {
-        if self.package.contains("/") {
-            let last = self.package.split("/").last();
+    pub fn get_actual_package(package: &str) -> String {
+        if package.contains("/") {
+            let last = package.split("/").last();
             match last {
                 Some(x) => x.to_string(),
-                None => self.package.clone(), // should be impossible, appease compiler
+                None => package.to_string(), // should be impossible, appease compiler
             }
         } else {
-            self.package.clone()
+            package.to_string()
+        }
+    }
+
+    // package=version when a version is pinned (keeping any repo/ prefix), otherwise unpinned
+    fn get_package_spec(&self, package: &str, unpinned: &str) -> String {
+        match &self.version {
+            Some(version) => format!("{}={}", package, version),
+            None => unpinned.to_string(),
         }
     }
 
-    pub fn parse_package_details(&self, out: &str) -> Option<PackageDetails> {
-        let mut name: Option<String> = None;
-        let mut version: Option<String> = None;
+    // pacman -Q prints "name version" for each installed package and an error line for
+    // each missing one
+
+    pub fn parse_package_versions(out: &str) -> HashMap<String, PackageDetails> {
+        let mut versions: HashMap<String, PackageDetails> = HashMap::new();
         for line in out.lines() {
             if line.starts_with("error:") {
-                // FIXME: is this possible with rc == 1?
-                return None;
+                continue;
             }
-            let mut tokens = line.split(":");
-            let key = tokens.nth(0);
-            let value = tokens.nth(0);
-            if let (Some(k), Some(v)) = (key, value) {
-                let key2 = k.trim();
-                let value2 = v.trim();
-                if key2.eq("Name") {
-                    name = Some(value2.to_string());
-                }
-                if key2.eq("Version") {
-                    version = Some(value2.to_string());
-                    break;
-                }
+            let mut tokens = line.split_whitespace();
+            if let (Some(name), Some(version), None) = (tokens.next(), tokens.next(), tokens.next())
+            {
+                versions.insert(
+                    name.to_string(),
+                    PackageDetails {
+                        name: name.to_string(),
+                        version: version.to_string(),
+                    },
+                );
             }
         }
-        match (name, version) {
-            (Some(name), Some(version)) => Some(PackageDetails { name, version }),
-            _ => None,
-        }
+        versions
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn installed_packages_are_parsed_and_missing_ones_skipped() {
+        let out = "curl 8.4.0-2
+error: package 'nosuch' was not found
+openssl 3.1.4-1
+";
+        let versions = PacmanAction::parse_package_versions(out);
+        assert_eq!(versions.len(), 2);
+        assert_eq!(versions.get("curl").unwrap().version, "8.4.0-2");
+        assert_eq!(PacmanAction::get_actual_package("extra/curl"), "curl");
     }
 }
@@ -16,10 +16,14 @@
 
 use crate::handle::handle::{CheckRc, TaskHandle};
 use crate::inventory::hosts::PackagePreference;
-use crate::modules::packages::common::{PackageDetails, PackageManagementModule};
+use crate::modules::packages::common::{
+    PackageDetails, PackageManagementModule, PackagePlan, UpgradeMode, quote_packages,
+    template_packages,
+};
 use crate::tasks::*;
 use serde::Deserialize;
-use std::sync::Arc;
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex};
 
 const MODULE: &str = "yum_dnf";
 
@@ -28,19 +32,30 @@ const MODULE: &str = "yum_dnf";
 #[derive(Default)]
 pub struct YumDnfTask {
     pub name: Option<String>,
-    pub package: String,
+    pub package: Option<String>,
+    pub packages: Option<Vec<String>>,
     pub version: Option<String>,
-    pub update: Option<String>,
-    pub remove: Option<String>,
+    pub update: Option<Templated<bool>>,
+    pub upgrade: Option<Templated<UpgradeMode>>,
+    pub remove: Option<Templated<bool>>,
+    pub hold: Option<Templated<bool>>,
+    pub save: Option<String>,
     pub with: Option<PreLogicInput>,
+    // set by !package, which hands over package names it has already templated
+    #[serde(skip)]
+    pub rendered: bool,
     pub and: Option<PostLogicInput>,
 }
 
 struct YumDnfAction {
-    pub package: String,
+    pub packages: Vec<String>,
     pub version: Option<String>,
     pub update: bool,
+    pub upgrade: UpgradeMode,
     pub remove: bool,
+    pub hold: Option<bool>,
+    pub save: Option<String>,
+    planned: Mutex<Option<PackagePlan>>,
 }
 
 impl IsTask for YumDnfTask {
@@ -60,13 +75,24 @@ impl IsTask for YumDnfTask {
         request: &Arc<TaskRequest>,
         tm: TemplateMode,
     ) -> Result<EvaluatedTask, Arc<TaskResponse>> {
+        let upgrade = handle.template.scalar_option_default(
+            request,
+            tm,
+            "upgrade",
+            &self.upgrade,
+            UpgradeMode::Off,
+        )?;
         Ok(EvaluatedTask {
             action: Arc::new(YumDnfAction {
-                package: handle.template.string_no_spaces(
+                packages: template_packages(
+                    handle,
                     request,
                     tm,
-                    &String::from("package"),
                     &self.package,
+                    &self.packages,
+                    &self.version,
+                    upgrade,
+                    self.rendered,
                 )?,
                 version: handle.template.string_option_no_spaces(
                     request,
@@ -80,12 +106,26 @@ impl IsTask for YumDnfTask {
                     &String::from("update"),
                     &self.update,
                 )?,
+                upgrade,
                 remove: handle.template.boolean_option_default_false(
                     request,
                     tm,
                     &String::from("remove"),
                     &self.remove,
                 )?,
+                hold: handle.template.boolean_option_default_none(
+                    request,
+                    tm,
+                    &String::from("hold"),
+                    &self.hold,
+                )?,
+                save: handle.template.string_option_no_spaces(
+                    request,
+                    tm,
+                    &String::from("save"),
+                    &self.save,
+                )?,
+                planned: Mutex::new(None),
             }),
             with: Arc::new(PreLogicInput::template(handle, request, tm, &self.with)?),
             and: Arc::new(PostLogicInput::template(handle, request, tm, &self.and)?),
@@ -104,8 +144,9 @@ impl IsAction for YumDnfAction {
 }
 
 impl PackageManagementModule for YumDnfAction {
+    // upgrade: true is accepted as another way of saying update: true
     fn is_update(&self) -> bool {
-        self.update
+        self.update || self.upgrade == UpgradeMode::Packages
     }
 
     fn is_remove(&self) -> bool {
@@ -116,6 +157,14 @@ impl PackageManagementModule for YumDnfAction {
         self.version.clone()
     }
 
+    fn get_packages(&self) -> &[String] {
+        &self.packages
+    }
+
+    fn get_planned(&self) -> &Mutex<Option<PackagePlan>> {
+        &self.planned
+    }
+
     fn initial_setup(
         &self,
         handle: &Arc<TaskHandle>,
@@ -125,81 +174,214 @@ impl PackageManagementModule for YumDnfAction {
         Ok(())
     }
 
-    fn get_local_version(
+    fn get_local_versions(
         &self,
         handle: &Arc<TaskHandle>,
         request: &Arc<TaskRequest>,
-    ) -> Result<Option<PackageDetails>, Arc<TaskResponse>> {
-        let which = self.get_package_manager(handle);
-        let cmd = match self.version.is_none() {
-            true => format!("{} info {}", which, self.package),
-            false => format!(
-                "{} info {}-{}",
-                which,
-                self.package,
-                self.version.as_ref().unwrap()
-            ),
-        };
-        let result = handle.remote.run(request, &cmd, CheckRc::Unchecked)?;
+    ) -> Result<HashMap<String, PackageDetails>, Arc<TaskResponse>> {
+        // rpm prints "package x is not installed" for the missing ones and fails, but
+        // still lists the rest
+        let cmd = format!(
+            "rpm -q --queryformat '%{{NAME}}\\t%{{VERSION}}\\n' {}",
+            quote_packages(&self.packages)
+        );
+        let result = handle
+            .remote
+            .run_unsafe(request, &cmd, CheckRc::Unchecked)?;
         let (_rc, out) = cmd_info(&result);
-        let details = self.parse_local_package_details(&out.clone())?;
-        Ok(details)
+        Ok(self.parse_package_versions(&out))
     }
 
-    fn get_remote_version(
+    fn get_remote_versions(
         &self,
         handle: &Arc<TaskHandle>,
         request: &Arc<TaskRequest>,
-    ) -> Result<Option<PackageDetails>, Arc<TaskResponse>> {
+    ) -> Result<HashMap<String, PackageDetails>, Arc<TaskResponse>> {
         let cmd = format!(
-            "repoquery {} --queryformat {}",
-            self.package, "'%{version}'"
+            "repoquery --queryformat '%{{name}}\\t%{{version}}\\n' {}",
+            quote_packages(&self.packages)
         );
         let result = handle
             .remote
             .run_unsafe(request, &cmd, CheckRc::Unchecked)?;
         let (_rc, out) = cmd_info(&result);
-        let details = self.parse_remote_package_details(&out.clone())?;
-        Ok(details)
+        Ok(self.parse_package_versions(&out))
     }
 
-    fn install_package(
+    fn install_packages(
         &self,
         handle: &Arc<TaskHandle>,
         request: &Arc<TaskRequest>,
+        packages: &[String],
     ) -> Result<Arc<TaskResponse>, Arc<TaskResponse>> {
         let which = self.get_package_manager(handle);
-        let cmd = match self.version.is_none() {
-            true => format!("{} install '{}' -y", which, self.package),
-            false => format!(
-                "{} install '{}-{}' -y",
-                which,
-                self.package,
-                self.version.as_ref().unwrap()
-            ),
-        };
+        let cmd = format!(
+            "{} install {} -y",
+            which,
+            quote_packages(&self.get_package_specs(packages))
+        );
         handle.remote.run(request, &cmd, CheckRc::Checked)
     }
 
-    fn update_package(
+    fn update_packages(
         &self,
         handle: &Arc<TaskHandle>,
         request: &Arc<TaskRequest>,
+        packages: &[String],
     ) -> Result<Arc<TaskResponse>, Arc<TaskResponse>> {
         let which = self.get_package_manager(handle);
-        let cmd = format!("{} update '{}' -y", which, self.package);
+        let cmd = format!(
+            "{} update {} -y",
+            which,
+            quote_packages(&self.get_package_specs(packages))
+        );
         handle.remote.run(request, &cmd, CheckRc::Checked)
     }
 
-    fn remove_package(
+    fn remove_packages(
         &self,
         handle: &Arc<TaskHandle>,
         request: &Arc<TaskRequest>,
+        packages: &[String],
     ) -> Result<Arc<TaskResponse>, Arc<TaskResponse>> {
         let which = self.get_package_manager(handle);
-        let cmd = format!("{} remove '{}' -y", which, self.package);
+        let cmd = format!("{} remove {} -y", which, quote_packages(packages));
+        handle.remote.run(request, &cmd, CheckRc::Checked)
+    }
+
+    fn get_hold(&self) -> Option<bool> {
+        self.hold
+    }
+
+    fn get_upgrade_mode(&self) -> UpgradeMode {
+        self.upgrade
+    }
+
+    fn get_save(&self) -> Option<String> {
+        self.save.clone()
+    }
+
+    fn get_held_packages(
+        &self,
+        handle: &Arc<TaskHandle>,
+        request: &Arc<TaskRequest>,
+    ) -> Result<HashSet<String>, Arc<TaskResponse>> {
+        let which = self.get_package_manager(handle);
+        let cmd = format!("{} versionlock list -q", which);
+        let result = handle.remote.run(request, &cmd, CheckRc::Unchecked)?;
+        let (rc, out) = cmd_info(&result);
+        if rc != 0 {
+            return Err(handle.response.is_failed(
+                request,
+                &format!(
+                    "{} versionlock is not available, install the versionlock plugin: {}",
+                    which, out
+                ),
+            ));
+        }
+        Ok(parse_versionlock(&out))
+    }
+
+    fn hold_packages(
+        &self,
+        handle: &Arc<TaskHandle>,
+        request: &Arc<TaskRequest>,
+        packages: &[String],
+        hold: bool,
+    ) -> Result<Arc<TaskResponse>, Arc<TaskResponse>> {
+        let cmd = format!(
+            "{} versionlock {} {}",
+            self.get_package_manager(handle),
+            match hold {
+                true => "add",
+                false => "delete",
+            },
+            quote_packages(packages)
+        );
+        handle.remote.run(request, &cmd, CheckRc::Checked)
+    }
+
+    fn plan_system_upgrade(
+        &self,
+        handle: &Arc<TaskHandle>,
+        request: &Arc<TaskRequest>,
+    ) -> Result<PackagePlan, Arc<TaskResponse>> {
+        // check-update exits with 100 when there are updates and 1 on errors
+        let cmd = format!(
+            "{} check-update -q{}",
+            self.get_package_manager(handle),
+            self.security_option()
+        );
+        let result = handle.remote.run(request, &cmd, CheckRc::Unchecked)?;
+        let (rc, out) = cmd_info(&result);
+        match rc {
+            0 => return Ok(PackagePlan::default()),
+            100 => {}
+            _ => {
+                return Err(handle
+                    .response
+                    .command_failed(request, &result.command_result));
+            }
+        }
+        let available = parse_check_update(&out);
+        let names: Vec<String> = available.iter().map(|(name, _)| name.clone()).collect();
+        let cmd = format!(
+            "rpm -q --queryformat '%{{NAME}}\\t%{{VERSION}}-%{{RELEASE}}\\n' {}",
+            quote_packages(&names)
+        );
+        let result = handle
+            .remote
+            .run_unsafe(request, &cmd, CheckRc::Unchecked)?;
+        let (_rc, out) = cmd_info(&result);
+        let installed = parse_package_versions(&out, &names);
+        let mut plan = PackagePlan::default();
+        for (name, version) in available.into_iter() {
+            let from = installed
+                .get(&name)
+                .map(|x| x.version.clone())
+                .unwrap_or_default();
+            plan.update.push((name, from, Some(version)));
+        }
+        Ok(plan)
+    }
+
+    fn system_upgrade(
+        &self,
+        handle: &Arc<TaskHandle>,
+        request: &Arc<TaskRequest>,
+        _plan: &PackagePlan,
+    ) -> Result<Arc<TaskResponse>, Arc<TaskResponse>> {
+        let cmd = format!(
+            "{} upgrade -y{}",
+            self.get_package_manager(handle),
+            self.security_option()
+        );
         handle.remote.run(request, &cmd, CheckRc::Checked)
     }
+
+    fn is_reboot_required(
+        &self,
+        handle: &Arc<TaskHandle>,
+        request: &Arc<TaskRequest>,
+    ) -> Result<bool, Arc<TaskResponse>> {
+        // needs-restarting comes with dnf-plugins-core (yum-utils on yum hosts) and
+        // exits with 1 when a reboot is needed
+        let cmd = match self.get_package_preference(handle) {
+            Some(PackagePreference::Dnf) => "dnf needs-restarting -r",
+            _ => "needs-restarting -r",
+        };
+        let result = handle
+            .remote
+            .run(request, &String::from(cmd), CheckRc::Unchecked)?;
+        match cmd_info(&result) {
+            (0, _) => Ok(false),
+            (1, _) => Ok(true),
+            (_, out) => Err(handle.response.is_failed(
+                request,
+                &format!("could not tell whether a reboot is required: {}", out),
+            )),
+        }
+    }
 }
 
 impl YumDnfAction {
@@ -249,50 +431,136 @@ impl YumDnfAction {
         }
     }
 
-    fn parse_local_package_details(
-        &self,
-        out: &str,
-    ) -> Result<Option<PackageDetails>, Arc<TaskResponse>> {
-        let mut name: Option<String> = None;
-        let mut version: Option<String> = None;
-        for line in out.lines() {
-            if line.starts_with("Available") {
-                return Ok(None);
-            }
-            let mut tokens = line.split(":");
-            let key = tokens.nth(0);
-            let value = tokens.nth(0);
-            if let (Some(k), Some(v)) = (key, value) {
-                let key2 = k.trim();
-                let value2 = v.trim();
-                if key2.eq("Name") {
-                    name = Some(value2.to_string());
-                }
-                if key2.eq("Version") {
-                    version = Some(value2.to_string());
-                    break;
-                }
-            }
+    /// name-version when a version is pinned
+    fn get_package_specs(&self, packages: &[String]) -> Vec<String> {
+        packages
+            .iter()
+            .map(|package| match &self.version {
+                Some(version) => format!("{}-{}", package, version),
+                None => package.clone(),
+            })
+            .collect()
+    }
+
+    // "name\tversion" lines from rpm or repoquery, keyed by the packages of the task.
+    // When a name is listed more than once (several kernels, or every version in the
+    // repositories) the last one wins, which is the newest as both tools sort by version.
+
+    fn parse_package_versions(&self, out: &str) -> HashMap<String, PackageDetails> {
+        parse_package_versions(out, &self.packages)
+    }
+
+    fn security_option(&self) -> &'static str {
+        match self.upgrade {
+            UpgradeMode::Security => " --security",
+            _ => "",
         }
-        match (name, version) {
-            (Some(name), Some(version)) => Ok(Some(PackageDetails { name, version })),
-            _ => Ok(None),
+    }
+}
+
+fn parse_package_versions(out: &str, packages: &[String]) -> HashMap<String, PackageDetails> {
+    let mut versions: HashMap<String, PackageDetails> = HashMap::new();
+    for line in out.lines() {
+        if let Some((name, version)) = line.split_once('\t')
+            && packages.iter().any(|x| x == name)
+        {
+            versions.insert(
+                name.to_string(),
+                PackageDetails {
+                    name: name.to_string(),
+                    version: version.trim().to_string(),
+                },
+            );
         }
     }
+    versions
+}
 
-    fn parse_remote_package_details(
-        &self,
-        out: &str,
-    ) -> Result<Option<PackageDetails>, Arc<TaskResponse>> {
-        // FYI: this command doesn't have useful return codes
-        for line in out.lines() {
-            if !line.contains("metadata expiration") {
-                return Ok(Some(PackageDetails {
-                    name: self.package.clone(),
-                    version: line.trim().to_string(),
-                }));
+// names from "versionlock list", which prints one "name-[epoch:]version-release.*"
+// pattern per lock, or "Package name: name" blocks on dnf5
+
+fn parse_versionlock(out: &str) -> HashSet<String> {
+    let mut names: HashSet<String> = HashSet::new();
+    for line in out.lines() {
+        let line = line.trim();
+        if let Some(name) = line.strip_prefix("Package name:") {
+            names.insert(name.trim().to_string());
+        } else if let Some(pattern) = line.strip_suffix(".*") {
+            let parts: Vec<&str> = pattern.rsplitn(3, '-').collect();
+            if let [_release, _version, name] = parts.as_slice() {
+                names.insert(name.to_string());
             }
         }
-        Ok(None)
+    }
+    names
+}
+
+// (name, version-release) for each "name.arch  version-release  repo" line of
+// check-update, stopping at the obsoletes section that may follow
+
+fn parse_check_update(out: &str) -> Vec<(String, String)> {
+    let mut available: Vec<(String, String)> = Vec::new();
+    for line in out.lines() {
+        if line.starts_with("Obsoleting") {
+            break;
+        }
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        if let [package, version, _repo] = fields.as_slice()
+            && let Some((name, _arch)) = package.rsplit_once('.')
+        {
+            available.push((name.to_string(), version.to_string()));
+        }
+    }
+    available
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn newest_listed_version_wins_and_unknown_lines_are_ignored() {
+        let action = YumDnfAction {
+            packages: vec![String::from("kernel"), String::from("httpd")],
+            version: None,
+            update: false,
+            upgrade: UpgradeMode::Off,
+            remove: false,
+            hold: None,
+            save: None,
+            planned: Mutex::new(None),
+        };
+        let out = "kernel\t5.14.0\nkernel\t5.14.1\npackage httpd is not installed\nbash\t5.1.8\n";
+        let versions = action.parse_package_versions(out);
+        assert_eq!(versions.len(), 1);
+        assert_eq!(versions.get("kernel").unwrap().version, "5.14.1");
+    }
+
+    #[test]
+    fn locks_and_available_updates_are_parsed() {
+        let locks = parse_versionlock(
+            "nginx-1:1.20.1-14.el9_2.1.*\nkernel-core-0:5.14.0-362.8.1.el9_3.*\n",
+        );
+        assert!(locks.contains("nginx") && locks.contains("kernel-core"));
+        let locks = parse_versionlock("# Added by 'versionlock add'\nPackage name: nginx\n");
+        assert_eq!(locks.len(), 1);
+
+        let out = "\nopenssl-libs.x86_64   1:3.0.7-25.el9_3   baseos\n\
+                   kernel.x86_64   5.14.0-362.13.1.el9_3   baseos\n\
+                   Obsoleting Packages\n\
+                   grub2-tools.x86_64   1:2.06-70.el9_3.1   baseos\n";
+        assert_eq!(
+            parse_check_update(out),
+            vec![
+                (
+                    String::from("openssl-libs"),
+                    String::from("1:3.0.7-25.el9_3")
+                ),
+                (
+                    String::from("kernel"),
+                    String::from("5.14.0-362.13.1.el9_3")
+                ),
+            ]
+        );
     }
 }
@@ -0,0 +1,287 @@
+// Jetporch
+// Copyright (C) 2023 - Michael DeHaan <michael@michaeldehaan.net> + contributors
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// long with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use crate::handle::handle::TaskHandle;
+use crate::tasks::fields::Field;
+use crate::tasks::files::Recurse;
+use crate::tasks::*;
+use crate::util::diff::unified;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+const MODULE: &str = "yum_repository";
+const REPOS_DIR: &str = "/etc/yum.repos.d";
+
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+#[derive(Default)]
+pub struct YumRepositoryTask {
+    pub name: Option<String>,
+    pub id: String,
+    pub description: Option<String>,
+    pub baseurl: Option<String>,
+    pub metalink: Option<String>,
+    pub mirrorlist: Option<String>,
+    pub gpgkey: Option<String>,
+    pub gpgcheck: Option<Templated<bool>>,
+    pub enabled: Option<Templated<bool>>,
+    pub options: Option<BTreeMap<String, String>>,
+    pub remove: Option<Templated<bool>>,
+    pub with: Option<PreLogicInput>,
+    pub and: Option<PostLogicInput>,
+}
+
+struct YumRepositoryAction {
+    pub path: String,
+    pub content: String,
+    pub remove: bool,
+}
+
+impl IsTask for YumRepositoryTask {
+    fn get_module(&self) -> String {
+        String::from(MODULE)
+    }
+    fn get_name(&self) -> Option<String> {
+        self.name.clone()
+    }
+    fn get_with(&self) -> Option<PreLogicInput> {
+        self.with.clone()
+    }
+
+    fn evaluate(
+        &self,
+        handle: &Arc<TaskHandle>,
+        request: &Arc<TaskRequest>,
+        tm: TemplateMode,
+    ) -> Result<EvaluatedTask, Arc<TaskResponse>> {
+        let id = handle
+            .template
+            .string_no_spaces(request, tm, &String::from("id"), &self.id)?;
+        if id.contains('/') {
+            return Err(handle
+                .response
+                .is_failed(request, "id is used as a file name and cannot contain '/'"));
+        }
+        let remove = handle.template.boolean_option_default_false(
+            request,
+            tm,
+            &String::from("remove"),
+            &self.remove,
+        )?;
+
+        // everything but id ends up as key=value lines, so values may hold anything
+        // except a line break
+        let mut lines: Vec<(String, String)> = Vec::new();
+        let description = handle
+            .template
+            .string_option_unsafe_for_shell(
+                request,
+                tm,
+                &String::from("description"),
+                &self.description,
+            )?
+            .unwrap_or_else(|| id.clone());
+        lines.push((String::from("name"), description));
+        for (key, value) in [
+            ("baseurl", &self.baseurl),
+            ("metalink", &self.metalink),
+            ("mirrorlist", &self.mirrorlist),
+        ] {
+            if let Some(value) = handle
+                .template
+                .string_option_unsafe_for_shell(request, tm, key, value)?
+            {
+                lines.push((key.to_string(), value));
+            }
+        }
+        if tm == TemplateMode::Strict && !remove && lines.len() == 1 {
+            return Err(handle.response.is_failed(
+                request,
+                "one of baseurl, metalink or mirrorlist is required",
+            ));
+        }
+        let enabled = handle.template.boolean_option_default_true(
+            request,
+            tm,
+            &String::from("enabled"),
+            &self.enabled,
+        )?;
+        lines.push((String::from("enabled"), flag(enabled)));
+        let gpgkey = handle.template.string_option_unsafe_for_shell(
+            request,
+            tm,
+            &String::from("gpgkey"),
+            &self.gpgkey,
+        )?;
+        // signatures are checked whenever there is a key to check them with
+        let gpgcheck = handle
+            .template
+            .boolean_option_default_none(request, tm, &String::from("gpgcheck"), &self.gpgcheck)?
+            .unwrap_or(gpgkey.is_some());
+        lines.push((String::from("gpgcheck"), flag(gpgcheck)));
+        if let Some(gpgkey) = gpgkey {
+            lines.push((String::from("gpgkey"), gpgkey));
+        }
+        for (key, value) in self.options.iter().flatten() {
+            let value = handle.template.string_unsafe_for_shell(
+                request,
+                tm,
+                &format!("options.{}", key),
+                value,
+            )?;
+            lines.push((key.clone(), value));
+        }
+        if let Some((key, _)) = lines
+            .iter()
+            .find(|(key, value)| key.contains(['=', '\n', '[']) || value.contains('\n'))
+        {
+            return Err(handle.response.is_failed(
+                request,
+                &format!(
+                    "field {}: keys cannot hold = or [ and values cannot span lines",
+                    key
+                ),
+            ));
+        }
+
+        Ok(EvaluatedTask {
+            action: Arc::new(YumRepositoryAction {
+                path: format!("{}/{}.repo", REPOS_DIR, id),
+                content: render(&id, &lines),
+                remove,
+            }),
+            with: Arc::new(PreLogicInput::template(handle, request, tm, &self.with)?),
+            and: Arc::new(PostLogicInput::template(handle, request, tm, &self.and)?),
+        })
+    }
+}
+
+impl IsAction for YumRepositoryAction {
+    fn dispatch(
+        &self,
+        handle: &Arc<TaskHandle>,
+        request: &Arc<TaskRequest>,
+    ) -> Result<Arc<TaskResponse>, Arc<TaskResponse>> {
+        match request.request_type {
+            TaskRequestType::Query => {
+                let current = handle.remote.read_file(request, &self.path)?;
+                let response = match (&current, self.remove) {
+                    (Some(_), true) => return Ok(handle.response.needs_removal(request)),
+                    (None, true) => return Ok(handle.response.is_matched(request)),
+                    (None, false) => handle.response.needs_creation(request),
+                    (Some(content), false) if *content != self.content => handle
+                        .response
+                        .needs_modification(request, &[Field::Content]),
+                    (Some(_), false) => return Ok(handle.response.is_matched(request)),
+                };
+                let diff = match handle.response.wants_diff() {
+                    true => unified(
+                        &self.path,
+                        current.as_ref().map(|x| x.as_bytes()),
+                        self.content.as_bytes(),
+                    ),
+                    false => None,
+                };
+                Ok(handle.response.with_diff(response, diff))
+            }
+
+            // dnf notices a new or changed .repo file by itself and fetches its metadata
+            // the next time it runs, so there is no cache to refresh here
+            TaskRequestType::Create => {
+                self.write(handle, request)?;
+                Ok(handle.response.is_created(request))
+            }
+
+            TaskRequestType::Modify => {
+                self.write(handle, request)?;
+                Ok(handle
+                    .response
+                    .is_modified(request, request.changes.clone()))
+            }
+
+            TaskRequestType::Remove => {
+                handle.remote.delete_file(request, &self.path)?;
+                Ok(handle.response.is_removed(request))
+            }
+
+            _ => Err(handle.response.not_supported(request)),
+        }
+    }
+}
+
+impl YumRepositoryAction {
+    fn write(
+        &self,
+        handle: &Arc<TaskHandle>,
+        request: &Arc<TaskRequest>,
+    ) -> Result<(), Arc<TaskResponse>> {
+        handle
+            .remote
+            .write_data(request, &self.content, &self.path, &None, |f| {
+                handle
+                    .remote
+                    .set_mode(request, f, "0644", Recurse::No)
+                    .map(|_| ())
+            })
+    }
+}
+
+fn flag(value: bool) -> String {
+    String::from(match value {
+        true => "1",
+        false => "0",
+    })
+}
+
+fn render(id: &str, lines: &[(String, String)]) -> String {
+    let mut out = format!("[{}]\n", id);
+    for (key, value) in lines.iter() {
+        out.push_str(&format!("{}={}\n", key, value));
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn repo_file_lists_keys_in_a_stable_order() {
+        let lines = vec![
+            (String::from("name"), String::from("Docker CE Stable")),
+            (
+                String::from("baseurl"),
+                String::from("https://download.docker.com/linux/rhel/$releasever/$basearch/stable"),
+            ),
+            (String::from("enabled"), flag(true)),
+            (String::from("gpgcheck"), flag(true)),
+            (
+                String::from("gpgkey"),
+                String::from("https://download.docker.com/linux/rhel/gpg"),
+            ),
+        ];
+        assert_eq!(
+            render("docker-ce-stable", &lines),
+            "[docker-ce-stable]\n\
+             name=Docker CE Stable\n\
+             baseurl=https://download.docker.com/linux/rhel/$releasever/$basearch/stable\n\
+             enabled=1\n\
+             gpgcheck=1\n\
+             gpgkey=https://download.docker.com/linux/rhel/gpg\n"
+        );
+    }
+}
@@ -15,10 +15,14 @@
 // long with this program.  If not, see <http://www.gnu.org/licenses/>.
 
 use crate::handle::handle::{CheckRc, TaskHandle};
-use crate::modules::packages::common::{PackageDetails, PackageManagementModule};
+use crate::modules::packages::common::{
+    PackageDetails, PackageManagementModule, PackagePlan, UpgradeMode, quote_packages,
+    template_packages,
+};
 use crate::tasks::*;
 use serde::Deserialize;
-use std::sync::Arc;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
 
 const MODULE: &str = "zypper";
 
@@ -27,19 +31,24 @@ const MODULE: &str = "zypper";
 #[derive(Default)]
 pub struct ZypperTask {
     pub name: Option<String>,
-    pub package: String,
+    pub package: Option<String>,
+    pub packages: Option<Vec<String>>,
     pub version: Option<String>,
-    pub update: Option<String>,
-    pub remove: Option<String>,
+    pub update: Option<Templated<bool>>,
+    pub remove: Option<Templated<bool>>,
     pub with: Option<PreLogicInput>,
+    // set by !package, which hands over package names it has already templated
+    #[serde(skip)]
+    pub rendered: bool,
     pub and: Option<PostLogicInput>,
 }
 
 struct ZypperAction {
-    pub package: String,
+    pub packages: Vec<String>,
     pub version: Option<String>,
     pub update: bool,
     pub remove: bool,
+    planned: Mutex<Option<PackagePlan>>,
 }
 
 impl IsTask for ZypperTask {
@@ -61,11 +70,15 @@ impl IsTask for ZypperTask {
     ) -> Result<EvaluatedTask, Arc<TaskResponse>> {
         Ok(EvaluatedTask {
             action: Arc::new(ZypperAction {
-                package: handle.template.string_no_spaces(
+                packages: template_packages(
+                    handle,
                     request,
                     tm,
-                    &String::from("package"),
                     &self.package,
+                    &self.packages,
+                    &self.version,
+                    UpgradeMode::Off,
+                    self.rendered,
                 )?,
                 version: handle.template.string_option_no_spaces(
                     request,
@@ -85,6 +98,7 @@ impl IsTask for ZypperTask {
                     &String::from("remove"),
                     &self.remove,
                 )?,
+                planned: Mutex::new(None),
             }),
             with: Arc::new(PreLogicInput::template(handle, request, tm, &self.with)?),
             and: Arc::new(PostLogicInput::template(handle, request, tm, &self.and)?),
@@ -124,101 +138,109 @@ impl PackageManagementModule for ZypperAction {
         self.version.clone()
     }
 
-    fn get_remote_version(
+    fn get_packages(&self) -> &[String] {
+        &self.packages
+    }
+
+    fn get_planned(&self) -> &Mutex<Option<PackagePlan>> {
+        &self.planned
+    }
+
+    fn get_remote_versions(
         &self,
         handle: &Arc<TaskHandle>,
         request: &Arc<TaskRequest>,
-    ) -> Result<Option<PackageDetails>, Arc<TaskResponse>> {
+    ) -> Result<HashMap<String, PackageDetails>, Arc<TaskResponse>> {
         let cmd = format!(
-            "zypper --non-interactive --quiet search --match-exact --details '{}'",
-            self.package
+            "zypper --non-interactive --quiet search --match-exact --details {}",
+            quote_packages(&self.packages)
         );
-        let result = handle.remote.run_unsafe(request, &cmd, CheckRc::Unchecked);
-        match result {
-            Ok(r) => {
-                let (rc, out) = cmd_info(&r);
-                if rc == 104 {
-                    Ok(None)
-                } else if rc != 0 {
-                    Err(r)
-                } else {
-                    self.parse_zypper_search_table(handle, request, &out)
-                }
-            }
-            Err(e) => Err(e),
-        }
+        self.search(handle, request, &cmd)
     }
 
-    fn get_local_version(
+    fn get_local_versions(
         &self,
         handle: &Arc<TaskHandle>,
         request: &Arc<TaskRequest>,
-    ) -> Result<Option<PackageDetails>, Arc<TaskResponse>> {
+    ) -> Result<HashMap<String, PackageDetails>, Arc<TaskResponse>> {
         let cmd = format!(
-            "zypper --non-interactive --quiet search --match-exact --details --installed-only '{}'",
-            self.package
+            "zypper --non-interactive --quiet search --match-exact --details --installed-only {}",
+            quote_packages(&self.packages)
         );
-        let result = handle.remote.run(request, &cmd, CheckRc::Unchecked);
-        match result {
-            Ok(r) => {
-                let (rc, out) = cmd_info(&r);
-                if rc == 104 {
-                    Ok(None)
-                } else if rc != 0 {
-                    Err(r)
-                } else {
-                    self.parse_zypper_search_table(handle, request, &out)
-                }
-            }
-            Err(e) => Err(e),
-        }
+        self.search(handle, request, &cmd)
     }
 
-    fn install_package(
+    fn install_packages(
         &self,
         handle: &Arc<TaskHandle>,
         request: &Arc<TaskRequest>,
+        packages: &[String],
     ) -> Result<Arc<TaskResponse>, Arc<TaskResponse>> {
-        let cmd = match &self.version {
-            Some(version) => format!(
-                "zypper --non-interactive --quiet  install '{}={}'",
-                self.package, version
-            ),
-            None => format!(
-                "zypper --non-interactive --quiet install '{}'",
-                self.package
-            ),
-        };
+        let cmd = format!(
+            "zypper --non-interactive --quiet install {}",
+            quote_packages(&self.get_package_specs(packages))
+        );
         handle.remote.run(request, &cmd, CheckRc::Checked)
     }
 
-    fn update_package(
+    fn update_packages(
         &self,
         handle: &Arc<TaskHandle>,
         request: &Arc<TaskRequest>,
+        packages: &[String],
     ) -> Result<Arc<TaskResponse>, Arc<TaskResponse>> {
-        let cmd = match &self.version {
-            Some(version) => format!(
-                "zypper --non-interactive --quiet update '{}={}'",
-                self.package, version
-            ),
-            None => format!("zypper --non-interactive --quiet update '{}'", self.package),
-        };
+        let cmd = format!(
+            "zypper --non-interactive --quiet update {}",
+            quote_packages(&self.get_package_specs(packages))
+        );
         handle.remote.run(request, &cmd, CheckRc::Checked)
     }
 
-    fn remove_package(
+    fn remove_packages(
         &self,
         handle: &Arc<TaskHandle>,
         request: &Arc<TaskRequest>,
+        packages: &[String],
     ) -> Result<Arc<TaskResponse>, Arc<TaskResponse>> {
-        let cmd = format!("zypper --non-interactive --quiet remove '{}'", self.package);
+        let cmd = format!(
+            "zypper --non-interactive --quiet remove {}",
+            quote_packages(packages)
+        );
         handle.remote.run(request, &cmd, CheckRc::Checked)
     }
 }
 
 impl ZypperAction {
-    // Takes the zypper output table and extract the version out of the table body
+    /// name=version when a version is pinned
+    fn get_package_specs(&self, packages: &[String]) -> Vec<String> {
+        packages
+            .iter()
+            .map(|package| match &self.version {
+                Some(version) => format!("{}={}", package, version),
+                None => package.clone(),
+            })
+            .collect()
+    }
+
+    // runs a zypper search, where exit code 104 means nothing matched at all
+
+    fn search(
+        &self,
+        handle: &Arc<TaskHandle>,
+        request: &Arc<TaskRequest>,
+        cmd: &str,
+    ) -> Result<HashMap<String, PackageDetails>, Arc<TaskResponse>> {
+        let result = handle.remote.run(request, cmd, CheckRc::Unchecked)?;
+        let (rc, out) = cmd_info(&result);
+        match rc {
+            104 => Ok(HashMap::new()),
+            0 => self.parse_zypper_search_table(handle, request, &out),
+            _ => Err(result),
+        }
+    }
+
+    // Takes the zypper output table and extracts the version of each package out of the
+    // table body, using the first row for a package listed more than once.
     // The tables often looks like this, including the additional empty line.
     //
     // ```text
@@ -233,26 +255,27 @@ impl ZypperAction {
         handle: &Arc<TaskHandle>,
         request: &Arc<TaskRequest>,
         out: &str,
-    ) -> Result<Option<PackageDetails>, Arc<TaskResponse>> {
-        let skip_header = out.trim().lines().nth(2);
-        let row = match skip_header {
-            Some(r) => r,
-            None => {
+    ) -> Result<HashMap<String, PackageDetails>, Arc<TaskResponse>> {
+        let mut versions: HashMap<String, PackageDetails> = HashMap::new();
+        for row in out.trim().lines().skip(2) {
+            let columns: Vec<&str> = row.split("|").map(str::trim).collect();
+            if columns.len() < 4 {
                 return Err(handle.response.is_failed(
                     request,
-                    &format!("unable to parse unexpected output from zypper (1): {}", out),
+                    &format!("unable to parse unexpected output from zypper: {}", out),
                 ));
             }
-        };
-        match row.split("|").nth(3) {
-            Some(version) => Ok(Some(PackageDetails {
-                name: self.package.clone(),
-                version: version.trim().to_string(),
-            })),
-            None => Err(handle.response.is_failed(
-                request,
-                &format!("unable to parse unexpected output from zypper (2): {}", out),
-            )),
+            let name = columns[1];
+            if self.packages.iter().any(|x| x == name) && !versions.contains_key(name) {
+                versions.insert(
+                    name.to_string(),
+                    PackageDetails {
+                        name: name.to_string(),
+                        version: columns[3].to_string(),
+                    },
+                );
+            }
         }
+        Ok(versions)
     }
 }
@@ -36,13 +36,13 @@ pub struct ProxmoxLxcTask {
     pub hostname: Option<String>,
     pub ostemplate: Option<String>,
     pub storage: Option<String>,
-    pub memory: Option<String>,
-    pub cores: Option<String>,
+    pub memory: Option<Templated<u64>>,
+    pub cores: Option<Templated<u64>>,
     pub rootfs_size: Option<String>,
     pub net0: Option<String>,
     pub password: Option<String>,
-    pub unprivileged: Option<String>,
-    pub start_on_create: Option<String>,
+    pub unprivileged: Option<Templated<bool>>,
+    pub start_on_create: Option<Templated<bool>>,
     pub state: Option<String>,
     pub with: Option<PreLogicInput>,
     pub and: Option<PostLogicInput>,
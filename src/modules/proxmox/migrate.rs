@@ -67,11 +67,11 @@ pub struct ProxmoxMigrateTask {
     /// Guest type: "vm" (qemu) or "lxc"
     pub guest_type: String,
     /// Live migration (true) or offline (false). Default: true
-    pub online: Option<String>,
+    pub online: Option<Templated<bool>>,
     /// Restart guest after migration if it was running. Default: true
-    pub restart: Option<String>,
+    pub restart: Option<Templated<bool>>,
     /// Timeout in seconds to wait for migration. Default: 300
-    pub timeout: Option<String>,
+    pub timeout: Option<Templated<u64>>,
     pub with: Option<PreLogicInput>,
     pub and: Option<PostLogicInput>,
 }
@@ -57,11 +57,11 @@ pub struct ProxmoxNodeTask {
     /// Variable name to save node info into
     pub save_to: Option<String>,
     /// If true, also fetch cluster quorum status
-    pub include_cluster: Option<String>,
+    pub include_cluster: Option<Templated<bool>>,
     /// If true, also list VMs on this node
-    pub include_vms: Option<String>,
+    pub include_vms: Option<Templated<bool>>,
     /// If true, also list LXCs on this node
-    pub include_lxc: Option<String>,
+    pub include_lxc: Option<Templated<bool>>,
     pub with: Option<PreLogicInput>,
     pub and: Option<PostLogicInput>,
 }
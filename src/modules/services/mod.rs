@@ -16,3 +16,4 @@
 
 /** ADD MODULES HERE, KEEP ALPHABETIZED **/
 pub mod sd_service;
+pub mod service;
@@ -15,13 +15,19 @@
 // long with this program.  If not, see <http://www.gnu.org/licenses/>.
 
 use crate::handle::handle::{CheckRc, TaskHandle};
+use crate::playbooks::templar::TemplateEngine;
 use crate::tasks::fields::Field;
+use crate::tasks::files::Recurse;
 use crate::tasks::*;
+use crate::util::diff::unified;
 use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::path::PathBuf;
 use std::sync::Arc;
 use std::vec::Vec;
 
 const MODULE: &str = "sd_service";
+const SYSTEM_UNIT_DIR: &str = "/etc/systemd/system";
 
 #[derive(Deserialize, Debug)]
 #[serde(deny_unknown_fields)]
@@ -29,22 +35,55 @@ const MODULE: &str = "sd_service";
 pub struct SystemdServiceTask {
     pub name: Option<String>,
     pub service: String,
-    pub enabled: Option<String>,
-    pub started: Option<String>,
-    pub reload: Option<String>,
-    pub restart: Option<String>,
+    pub content: Option<String>,
+    pub src: Option<String>,
+    // drop-in name -> content, written to <unit>.d/<name>.conf
+    pub dropins: Option<BTreeMap<String, String>>,
+    pub timer: Option<String>,
+    pub user: Option<String>,
+    pub masked: Option<Templated<bool>>,
+    pub enabled: Option<Templated<bool>>,
+    pub started: Option<Templated<bool>>,
+    pub reload: Option<Templated<bool>>,
+    pub restart: Option<Templated<bool>>,
     pub with: Option<PreLogicInput>,
     pub and: Option<PostLogicInput>,
 }
 
 struct SystemdServiceAction {
+    // the unit whose state is managed, which is the timer when there is one
     pub service: String,
+    pub unit: String,
+    pub unit_source: Option<UnitSource>,
+    // unit file name and content
+    pub timer: Option<(String, String)>,
+    // file name and content
+    pub dropins: Vec<(String, String)>,
+    pub user: Option<String>,
+    pub masked: Option<bool>,
     pub enabled: Option<bool>,
     pub started: Option<bool>,
     pub reload: bool,
     pub restart: bool,
 }
 
+enum UnitSource {
+    Inline(String),
+    Template(PathBuf),
+}
+
+struct UnitFile {
+    path: String,
+    content: String,
+}
+
+// where unit files go, and for --user units who has to own them
+struct UnitDir {
+    path: String,
+    owner: Option<(String, String)>,
+    parents: Vec<String>,
+}
+
 /// How `systemctl is-enabled <unit>` classifies a unit's boot-time state.
 #[derive(Clone, Copy, PartialEq, Eq, Debug)]
 enum Enablement {
@@ -52,11 +91,13 @@ enum Enablement {
     Enabled,
     /// disabled — won't start at boot.
     Disabled,
-    /// static / indirect / generated / transient / masked — the unit has no
+    /// static / indirect / generated / transient — the unit has no
     /// `[Install]` section, so it cannot be enabled or disabled. Typically
     /// activated by other means (socket activation, udev, dependencies).
     /// qemu-guest-agent, dbus and getty are common examples.
     Static,
+    /// masked / masked-runtime — linked to /dev/null, cannot be started at all.
+    Masked,
 }
 
 #[derive(Clone, PartialEq, Debug)]
@@ -82,38 +123,136 @@ impl IsTask for SystemdServiceTask {
         request: &Arc<TaskRequest>,
         tm: TemplateMode,
     ) -> Result<EvaluatedTask, Arc<TaskResponse>> {
-        Ok(EvaluatedTask {
-            action: Arc::new(SystemdServiceAction {
-                service: handle.template.string_no_spaces(
-                    request,
-                    tm,
-                    &String::from("service"),
-                    &self.service,
-                )?,
-                enabled: handle.template.boolean_option_default_none(
+        let service = handle.template.string_no_spaces(
+            request,
+            tm,
+            &String::from("service"),
+            &self.service,
+        )?;
+        let unit = unit_file_name(&service);
+        let unit_source = match (&self.content, &self.src) {
+            (Some(_), Some(_)) => {
+                return Err(handle
+                    .response
+                    .is_failed(request, "content and src are mutually exclusive"));
+            }
+            (Some(content), None) => Some(UnitSource::Inline(
+                handle
+                    .template
+                    .string_unsafe_for_shell(request, tm, "content", content)?,
+            )),
+            (None, Some(src)) => {
+                let src = handle.template.string(request, tm, "src", src)?;
+                Some(UnitSource::Template(
+                    handle
+                        .template
+                        .find_template_path(request, tm, "src", &src)?,
+                ))
+            }
+            (None, None) => None,
+        };
+        let timer = match &self.timer {
+            Some(timer) => {
+                let content = handle
+                    .template
+                    .string_unsafe_for_shell(request, tm, "timer", timer)?;
+                match unit.strip_suffix(".service") {
+                    Some(base) => Some((format!("{}.timer", base), content)),
+                    None if tm == TemplateMode::Off => None,
+                    None => {
+                        return Err(handle
+                            .response
+                            .is_failed(request, "timer requires a .service unit"));
+                    }
+                }
+            }
+            None => None,
+        };
+        let mut dropins: Vec<(String, String)> = Vec::new();
+        for (name, content) in self.dropins.iter().flatten() {
+            let field = format!("dropins.{}", name);
+            let name = handle
+                .template
+                .string_no_spaces(request, tm, &field, name)?;
+            if name.contains('/') {
+                return Err(handle.response.is_failed(
                     request,
-                    tm,
-                    &String::from("enabled"),
-                    &self.enabled,
-                )?,
-                started: handle.template.boolean_option_default_none(
+                    &format!("{}: drop-in names cannot contain '/'", field),
+                ));
+            }
+            let content = handle
+                .template
+                .string_unsafe_for_shell(request, tm, &field, content)?;
+            dropins.push((dropin_file_name(&name), content));
+        }
+        let masked = handle.template.boolean_option_default_none(
+            request,
+            tm,
+            &String::from("masked"),
+            &self.masked,
+        )?;
+        let enabled = handle.template.boolean_option_default_none(
+            request,
+            tm,
+            &String::from("enabled"),
+            &self.enabled,
+        )?;
+        let started = handle.template.boolean_option_default_none(
+            request,
+            tm,
+            &String::from("started"),
+            &self.started,
+        )?;
+        let reload = handle.template.boolean_option_default_false(
+            request,
+            tm,
+            &String::from("reload"),
+            &self.reload,
+        )?;
+        let restart = handle.template.boolean_option_default_false(
+            request,
+            tm,
+            &String::from("restart"),
+            &self.restart,
+        )?;
+
+        if tm == TemplateMode::Strict && masked == Some(true) {
+            if enabled == Some(true) || started == Some(true) || reload || restart {
+                return Err(handle.response.is_failed(
                     request,
-                    tm,
-                    &String::from("started"),
-                    &self.started,
-                )?,
-                reload: handle.template.boolean_option_default_false(
+                    "masked:true conflicts with enabled:true, started:true, reload and restart",
+                ));
+            }
+            // systemctl mask refuses to replace a unit file in the unit directory
+            if unit_source.is_some() || timer.is_some() {
+                return Err(handle.response.is_failed(
                     request,
-                    tm,
-                    &String::from("reload"),
-                    &self.reload,
-                )?,
-                restart: handle.template.boolean_option_default_false(
+                    "masked:true cannot be combined with content, src or timer",
+                ));
+            }
+        }
+
+        Ok(EvaluatedTask {
+            action: Arc::new(SystemdServiceAction {
+                service: match &timer {
+                    Some((name, _)) => name.clone(),
+                    None => unit.clone(),
+                },
+                unit,
+                unit_source,
+                timer,
+                dropins,
+                user: handle.template.string_option_no_spaces(
                     request,
                     tm,
-                    &String::from("restart"),
-                    &self.restart,
+                    &String::from("user"),
+                    &self.user,
                 )?,
+                masked,
+                enabled,
+                started,
+                reload,
+                restart,
             }),
             with: Arc::new(PreLogicInput::template(handle, request, tm, &self.with)?),
             and: Arc::new(PostLogicInput::template(handle, request, tm, &self.and)?),
@@ -130,7 +269,35 @@ impl IsAction for SystemdServiceAction {
         match request.request_type {
             TaskRequestType::Query => {
                 let mut changes: Vec<Field> = Vec::new();
-                let actual = self.get_service_details(handle, request)?;
+                let mut diffs: Vec<String> = Vec::new();
+                for (file, current) in self.get_stale_unit_files(handle, request)?.iter() {
+                    if !changes.contains(&Field::Content) {
+                        changes.push(Field::Content);
+                    }
+                    if handle.response.wants_diff() {
+                        diffs.extend(unified(
+                            &file.path,
+                            current.as_ref().map(|x| x.as_bytes()),
+                            file.content.as_bytes(),
+                        ));
+                    }
+                }
+                let mut actual =
+                    self.get_service_details(handle, request, changes.contains(&Field::Content))?;
+
+                match (actual.enablement, self.masked) {
+                    (Enablement::Masked, Some(false)) => {
+                        changes.push(Field::Unmask);
+                        // the real enablement only shows once the mask is gone, a unit
+                        // that was masked is as good as disabled
+                        actual.enablement = Enablement::Disabled;
+                    }
+                    (Enablement::Masked, _) => {}
+                    (_, Some(true)) => {
+                        changes.push(Field::Mask);
+                    }
+                    _ => {}
+                }
 
                 match (actual.enablement, self.enabled) {
                     (Enablement::Enabled, Some(false)) => {
@@ -139,6 +306,10 @@ impl IsAction for SystemdServiceAction {
                     (Enablement::Disabled, Some(true)) => {
                         changes.push(Field::Enable);
                     }
+                    (Enablement::Masked, _) => {
+                        // masked units have no enablement to speak of until they are
+                        // unmasked, and masked:false has been dealt with above
+                    }
                     (Enablement::Static, _) => {
                         // Static units have no [Install] section: `systemctl
                         // enable`/`disable` are no-ops (systemd itself reports
@@ -202,13 +373,28 @@ impl IsAction for SystemdServiceAction {
                 };
 
                 if !changes.is_empty() {
-                    Ok(handle.response.needs_modification(request, &changes))
+                    let response = handle.response.needs_modification(request, &changes);
+                    let diff = match diffs.is_empty() {
+                        true => None,
+                        false => Some(diffs.concat()),
+                    };
+                    Ok(handle.response.with_diff(response, diff))
                 } else {
                     Ok(handle.response.is_matched(request))
                 }
             }
 
             TaskRequestType::Modify => {
+                // systemd only picks up changed unit files on daemon-reload, which has
+                // to happen before the unit is (re)started below
+                if request.changes.contains(&Field::Content) {
+                    self.write_unit_files(handle, request)?;
+                    self.do_daemon_reload(handle, request)?;
+                }
+                if request.changes.contains(&Field::Unmask) {
+                    self.do_unmask(handle, request)?;
+                }
+
                 if request.changes.contains(&Field::Start) {
                     self.do_start(handle, request)?;
                 } else if request.changes.contains(&Field::Stop) {
@@ -225,6 +411,10 @@ impl IsAction for SystemdServiceAction {
                     self.do_disable(handle, request)?;
                 }
 
+                if request.changes.contains(&Field::Mask) {
+                    self.do_mask(handle, request)?;
+                }
+
                 Ok(handle
                     .response
                     .is_modified(request, request.changes.clone()))
@@ -240,10 +430,10 @@ impl SystemdServiceAction {
         &self,
         handle: &Arc<TaskHandle>,
         request: &Arc<TaskRequest>,
+        writing_units: bool,
     ) -> Result<ServiceDetails, Arc<TaskResponse>> {
-        let is_active: bool;
-        let is_enabled_cmd = format!("systemctl is-enabled '{}'", self.service);
-        let is_active_cmd = format!("systemctl is-active '{}'", self.service);
+        let is_enabled_cmd = self.systemctl(&format!("is-enabled '{}'", self.service));
+        let is_active_cmd = self.systemctl(&format!("is-active '{}'", self.service));
 
         let result = handle
             .remote
@@ -251,6 +441,8 @@ impl SystemdServiceAction {
         let (_rc, out) = cmd_info(&result);
         let enablement = match classify_enablement(&out) {
             Ok(e) => e,
+            // a unit this task is about to install is not known to systemd yet
+            Err(_) if writing_units => Enablement::Disabled,
             Err(reason) => {
                 return Err(handle.response.is_failed(
                     request,
@@ -266,7 +458,7 @@ impl SystemdServiceAction {
             .remote
             .run(request, &is_active_cmd, CheckRc::Unchecked)?;
         let (_rc2, out2) = cmd_info(&result2);
-        is_active = match classify_activity(&out2) {
+        let is_active = match classify_activity(&out2) {
             Ok(active) => active,
             Err(reason) => {
                 return Err(handle.response.is_failed(
@@ -285,12 +477,188 @@ impl SystemdServiceAction {
         })
     }
 
+    fn systemctl(&self, args: &str) -> String {
+        match &self.user {
+            None => format!("systemctl {}", args),
+            // reaches the user's own service manager, which has to be running
+            // (a login session, or lingering enabled with loginctl enable-linger)
+            Some(user) => format!("systemctl --user --machine='{}@' {}", user, args),
+        }
+    }
+
+    fn get_unit_dir(
+        &self,
+        handle: &Arc<TaskHandle>,
+        request: &Arc<TaskRequest>,
+    ) -> Result<UnitDir, Arc<TaskResponse>> {
+        let user = match &self.user {
+            None => {
+                return Ok(UnitDir {
+                    path: String::from(SYSTEM_UNIT_DIR),
+                    owner: None,
+                    parents: Vec::new(),
+                });
+            }
+            Some(user) => user,
+        };
+        let (gid, home) = match handle.remote.get_user_home(request, user)? {
+            Some(entry) => entry,
+            None => {
+                return Err(handle
+                    .response
+                    .is_failed(request, &format!("user {} does not exist", user)));
+            }
+        };
+        let parents = vec![
+            format!("{}/.config", home),
+            format!("{}/.config/systemd", home),
+        ];
+        Ok(UnitDir {
+            path: format!("{}/.config/systemd/user", home),
+            owner: Some((user.clone(), gid)),
+            parents,
+        })
+    }
+
+    fn get_unit_files(
+        &self,
+        handle: &Arc<TaskHandle>,
+        request: &Arc<TaskRequest>,
+        dir: &UnitDir,
+    ) -> Result<Vec<UnitFile>, Arc<TaskResponse>> {
+        let mut files: Vec<UnitFile> = Vec::new();
+        let unit_path = format!("{}/{}", dir.path, self.unit);
+        match &self.unit_source {
+            Some(UnitSource::Inline(content)) => files.push(UnitFile {
+                path: unit_path,
+                content: content.clone(),
+            }),
+            Some(UnitSource::Template(src)) => {
+                let template = handle.local.read_file(request, src)?;
+                let content = handle.template.string_for_template_module_use_only(
+                    request,
+                    TemplateMode::Strict,
+                    &String::from("src"),
+                    &template,
+                    TemplateEngine::for_path(src),
+                )?;
+                files.push(UnitFile {
+                    path: unit_path,
+                    content,
+                });
+            }
+            None => {}
+        }
+        if let Some((name, content)) = &self.timer {
+            files.push(UnitFile {
+                path: format!("{}/{}", dir.path, name),
+                content: content.clone(),
+            });
+        }
+        for (name, content) in self.dropins.iter() {
+            files.push(UnitFile {
+                path: format!("{}/{}.d/{}", dir.path, self.unit, name),
+                content: content.clone(),
+            });
+        }
+        Ok(files)
+    }
+
+    fn get_stale_unit_files(
+        &self,
+        handle: &Arc<TaskHandle>,
+        request: &Arc<TaskRequest>,
+    ) -> Result<Vec<(UnitFile, Option<String>)>, Arc<TaskResponse>> {
+        if self.unit_source.is_none() && self.timer.is_none() && self.dropins.is_empty() {
+            return Ok(Vec::new());
+        }
+        let dir = self.get_unit_dir(handle, request)?;
+        let mut stale: Vec<(UnitFile, Option<String>)> = Vec::new();
+        for file in self.get_unit_files(handle, request, &dir)? {
+            let current = handle.remote.read_file(request, &file.path)?;
+            if current.as_deref() != Some(file.content.as_str()) {
+                stale.push((file, current));
+            }
+        }
+        Ok(stale)
+    }
+
+    fn write_unit_files(
+        &self,
+        handle: &Arc<TaskHandle>,
+        request: &Arc<TaskRequest>,
+    ) -> Result<(), Arc<TaskResponse>> {
+        let dir = self.get_unit_dir(handle, request)?;
+        let mut directories = dir.parents.clone();
+        directories.push(dir.path.clone());
+        if !self.dropins.is_empty() {
+            directories.push(format!("{}/{}.d", dir.path, self.unit));
+        }
+        for directory in directories.iter() {
+            if !handle.remote.get_is_directory(request, directory)? {
+                handle.remote.create_directory(request, directory)?;
+                self.set_ownership(handle, request, &dir, directory)?;
+            }
+        }
+        for (file, _) in self.get_stale_unit_files(handle, request)?.iter() {
+            handle
+                .remote
+                .write_data(request, &file.content, &file.path, &None, |f| {
+                    handle.remote.set_mode(request, f, "0644", Recurse::No)?;
+                    self.set_ownership(handle, request, &dir, f)
+                })?;
+        }
+        Ok(())
+    }
+
+    fn set_ownership(
+        &self,
+        handle: &Arc<TaskHandle>,
+        request: &Arc<TaskRequest>,
+        dir: &UnitDir,
+        path: &str,
+    ) -> Result<(), Arc<TaskResponse>> {
+        if let Some((user, gid)) = &dir.owner {
+            handle.remote.set_owner(request, path, user, Recurse::No)?;
+            handle.remote.set_group(request, path, gid, Recurse::No)?;
+        }
+        Ok(())
+    }
+
+    pub fn do_daemon_reload(
+        &self,
+        handle: &Arc<TaskHandle>,
+        request: &Arc<TaskRequest>,
+    ) -> Result<Arc<TaskResponse>, Arc<TaskResponse>> {
+        handle
+            .remote
+            .run(request, &self.systemctl("daemon-reload"), CheckRc::Checked)
+    }
+
+    pub fn do_mask(
+        &self,
+        handle: &Arc<TaskHandle>,
+        request: &Arc<TaskRequest>,
+    ) -> Result<Arc<TaskResponse>, Arc<TaskResponse>> {
+        let cmd = self.systemctl(&format!("mask '{}'", self.service));
+        handle.remote.run(request, &cmd, CheckRc::Checked)
+    }
+
+    pub fn do_unmask(
+        &self,
+        handle: &Arc<TaskHandle>,
+        request: &Arc<TaskRequest>,
+    ) -> Result<Arc<TaskResponse>, Arc<TaskResponse>> {
+        let cmd = self.systemctl(&format!("unmask '{}'", self.service));
+        handle.remote.run(request, &cmd, CheckRc::Checked)
+    }
+
     pub fn do_start(
         &self,
         handle: &Arc<TaskHandle>,
         request: &Arc<TaskRequest>,
     ) -> Result<Arc<TaskResponse>, Arc<TaskResponse>> {
-        let cmd = format!("systemctl start '{}'", self.service);
+        let cmd = self.systemctl(&format!("start '{}'", self.service));
         handle.remote.run(request, &cmd, CheckRc::Checked)
     }
 
@@ -299,7 +667,7 @@ impl SystemdServiceAction {
         handle: &Arc<TaskHandle>,
         request: &Arc<TaskRequest>,
     ) -> Result<Arc<TaskResponse>, Arc<TaskResponse>> {
-        let cmd = format!("systemctl stop '{}'", self.service);
+        let cmd = self.systemctl(&format!("stop '{}'", self.service));
         handle.remote.run(request, &cmd, CheckRc::Checked)
     }
 
@@ -308,7 +676,7 @@ impl SystemdServiceAction {
         handle: &Arc<TaskHandle>,
         request: &Arc<TaskRequest>,
     ) -> Result<Arc<TaskResponse>, Arc<TaskResponse>> {
-        let cmd = format!("systemctl enable '{}'", self.service);
+        let cmd = self.systemctl(&format!("enable '{}'", self.service));
         handle.remote.run(request, &cmd, CheckRc::Checked)
     }
 
@@ -317,7 +685,7 @@ impl SystemdServiceAction {
         handle: &Arc<TaskHandle>,
         request: &Arc<TaskRequest>,
     ) -> Result<Arc<TaskResponse>, Arc<TaskResponse>> {
-        let cmd = format!("systemctl disable '{}'", self.service);
+        let cmd = self.systemctl(&format!("disable '{}'", self.service));
         handle.remote.run(request, &cmd, CheckRc::Checked)
     }
 
@@ -326,7 +694,7 @@ impl SystemdServiceAction {
         handle: &Arc<TaskHandle>,
         request: &Arc<TaskRequest>,
     ) -> Result<Arc<TaskResponse>, Arc<TaskResponse>> {
-        let cmd = format!("systemctl restart '{}'", self.service);
+        let cmd = self.systemctl(&format!("restart '{}'", self.service));
         handle.remote.run(request, &cmd, CheckRc::Checked)
     }
 
@@ -335,11 +703,26 @@ impl SystemdServiceAction {
         handle: &Arc<TaskHandle>,
         request: &Arc<TaskRequest>,
     ) -> Result<Arc<TaskResponse>, Arc<TaskResponse>> {
-        let cmd = format!("systemctl reload '{}'", self.service);
+        let cmd = self.systemctl(&format!("reload '{}'", self.service));
         handle.remote.run(request, &cmd, CheckRc::Checked)
     }
 }
 
+/// Units named without a type suffix are services, as `systemctl` assumes too.
+fn unit_file_name(service: &str) -> String {
+    match service.contains('.') {
+        true => service.to_string(),
+        false => format!("{}.service", service),
+    }
+}
+
+fn dropin_file_name(name: &str) -> String {
+    match name.ends_with(".conf") {
+        true => name.to_string(),
+        false => format!("{}.conf", name),
+    }
+}
+
 /// Classify the stdout of `systemctl is-enabled <unit>` into an [`Enablement`]
 /// state. Kept as a free function so the parsing logic is unit-testable without
 /// a live host. `systemctl is-enabled` emits exactly one token per unit, so we
@@ -354,9 +737,8 @@ fn classify_enablement(out: &str) -> Result<Enablement, String> {
             Ok(Enablement::Enabled)
         }
         "disabled" => Ok(Enablement::Disabled),
-        "static" | "indirect" | "generated" | "transient" | "masked" | "masked-runtime" => {
-            Ok(Enablement::Static)
-        }
+        "static" | "indirect" | "generated" | "transient" => Ok(Enablement::Static),
+        "masked" | "masked-runtime" => Ok(Enablement::Masked),
         other => Err(format!(
             "{:?} — expected one of: enabled, enabled-runtime, alias, linked, \
              linked-runtime, disabled, static, indirect, generated, transient, \
@@ -428,18 +810,28 @@ mod tests {
     #[test]
     fn classifies_static_units_as_not_enableable() {
         // qemu-guest-agent, dbus, getty, ... ship without an [Install] section.
-        for s in [
-            "static",
-            "indirect",
-            "generated",
-            "transient",
-            "masked",
-            "masked-runtime",
-        ] {
+        for s in ["static", "indirect", "generated", "transient"] {
             assert_eq!(classify_enablement(s).unwrap(), Enablement::Static, "{}", s);
         }
     }
 
+    #[test]
+    fn classifies_masked_units_apart_from_static_ones() {
+        // masked: true|false has to see these, unlike enabled: which skips both
+        for s in ["masked", "masked-runtime\n"] {
+            assert_eq!(classify_enablement(s).unwrap(), Enablement::Masked, "{}", s);
+        }
+    }
+
+    #[test]
+    fn unit_and_dropin_names_get_their_suffixes() {
+        assert_eq!(unit_file_name("nginx"), "nginx.service");
+        assert_eq!(unit_file_name("nginx.service"), "nginx.service");
+        assert_eq!(unit_file_name("backup.timer"), "backup.timer");
+        assert_eq!(dropin_file_name("limits"), "limits.conf");
+        assert_eq!(dropin_file_name("10-limits.conf"), "10-limits.conf");
+    }
+
     #[test]
     fn rejects_unrecognized_states() {
         // "not-found" / "bad" — the unit is absent or broken. Must surface as an
@@ -0,0 +1,657 @@
+// Jetporch
+// Copyright (C) 2023 - Michael DeHaan <michael@michaeldehaan.net> + contributors
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// long with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use crate::handle::handle::{CheckRc, TaskHandle};
+use crate::modules::services::sd_service::SystemdServiceTask;
+use crate::tasks::fields::Field;
+use crate::tasks::*;
+use serde::Deserialize;
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+use std::vec::Vec;
+
+const MODULE: &str = "service";
+
+// the init system independent service module. systemd hosts get everything
+// !sd_service does, OpenRC, runit and launchd are handled here with the same
+// enabled/started/restart semantics.
+
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+#[derive(Default)]
+pub struct ServiceTask {
+    pub name: Option<String>,
+    pub service: String,
+    pub enabled: Option<Templated<bool>>,
+    pub started: Option<Templated<bool>>,
+    pub restart: Option<Templated<bool>>,
+    pub with: Option<PreLogicInput>,
+    pub and: Option<PostLogicInput>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum InitSystem {
+    Systemd,
+    OpenRc,
+    Runit,
+    Launchd,
+}
+
+struct ServiceAction {
+    pub service: String,
+    pub init: InitSystem,
+    pub enabled: Option<bool>,
+    pub started: Option<bool>,
+    pub restart: bool,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct ServiceState {
+    enabled: bool,
+    started: bool,
+}
+
+impl IsTask for ServiceTask {
+    fn get_module(&self) -> String {
+        String::from(MODULE)
+    }
+    fn get_name(&self) -> Option<String> {
+        self.name.clone()
+    }
+    fn get_with(&self) -> Option<PreLogicInput> {
+        self.with.clone()
+    }
+
+    fn evaluate(
+        &self,
+        handle: &Arc<TaskHandle>,
+        request: &Arc<TaskRequest>,
+        tm: TemplateMode,
+    ) -> Result<EvaluatedTask, Arc<TaskResponse>> {
+        let init = match get_init_system(handle) {
+            Ok(init) => init,
+            // the template-off pass is only read for with:, whose condition may skip
+            // this task on hosts that never ran !facts or that it does not support
+            Err(_) if tm == TemplateMode::Off => InitSystem::Systemd,
+            Err(msg) => return Err(handle.response.is_failed(request, &msg)),
+        };
+
+        if init == InitSystem::Systemd {
+            let mut evaluated = SystemdServiceTask {
+                service: self.service.clone(),
+                enabled: self.enabled.clone(),
+                started: self.started.clone(),
+                restart: self.restart.clone(),
+                with: self.with.clone(),
+                ..Default::default()
+            }
+            .evaluate(handle, request, tm)?;
+            evaluated.and = Arc::new(PostLogicInput::template(handle, request, tm, &self.and)?);
+            return Ok(evaluated);
+        }
+
+        let service = handle.template.string_no_spaces(
+            request,
+            tm,
+            &String::from("service"),
+            &self.service,
+        )?;
+        // runit and launchd build paths out of the name
+        if service.contains('/') {
+            return Err(handle
+                .response
+                .is_failed(request, "service names cannot contain '/'"));
+        }
+        Ok(EvaluatedTask {
+            action: Arc::new(ServiceAction {
+                service,
+                init,
+                enabled: handle.template.boolean_option_default_none(
+                    request,
+                    tm,
+                    &String::from("enabled"),
+                    &self.enabled,
+                )?,
+                started: handle.template.boolean_option_default_none(
+                    request,
+                    tm,
+                    &String::from("started"),
+                    &self.started,
+                )?,
+                restart: handle.template.boolean_option_default_false(
+                    request,
+                    tm,
+                    &String::from("restart"),
+                    &self.restart,
+                )?,
+            }),
+            with: Arc::new(PreLogicInput::template(handle, request, tm, &self.with)?),
+            and: Arc::new(PostLogicInput::template(handle, request, tm, &self.and)?),
+        })
+    }
+}
+
+fn get_init_system(handle: &Arc<TaskHandle>) -> Result<InitSystem, String> {
+    let host = handle.host.read().unwrap();
+    match host.get_blended_variables().get("jet_init_system") {
+        Some(serde_yaml::Value::String(init)) => init_system_from_fact(init),
+        _ => Err(String::from(
+            "could not tell which init system this host uses, run !facts before !service",
+        )),
+    }
+}
+
+// jet_init_system as set by !facts, which says "unknown" when it recognizes none
+
+fn init_system_from_fact(init: &str) -> Result<InitSystem, String> {
+    match init {
+        "systemd" => Ok(InitSystem::Systemd),
+        "openrc" => Ok(InitSystem::OpenRc),
+        "runit" => Ok(InitSystem::Runit),
+        "launchd" => Ok(InitSystem::Launchd),
+        "unknown" => Err(String::from(
+            "the init system of this host is not supported by !service (systemd, openrc, runit and launchd are)",
+        )),
+        other => Err(format!(
+            "init system '{}' is not supported by !service (systemd, openrc, runit and launchd are)",
+            other
+        )),
+    }
+}
+
+impl IsAction for ServiceAction {
+    fn dispatch(
+        &self,
+        handle: &Arc<TaskHandle>,
+        request: &Arc<TaskRequest>,
+    ) -> Result<Arc<TaskResponse>, Arc<TaskResponse>> {
+        match request.request_type {
+            TaskRequestType::Query => {
+                let actual = self.get_state(handle, request)?;
+                match plan_changes(self.init, actual, self.enabled, self.started, self.restart) {
+                    Ok(changes) if changes.is_empty() => Ok(handle.response.is_matched(request)),
+                    Ok(changes) => Ok(handle.response.needs_modification(request, &changes)),
+                    Err(msg) => Err(handle.response.is_failed(request, &msg)),
+                }
+            }
+
+            TaskRequestType::Modify => {
+                // enabling comes first as runit and launchd will not start a service
+                // that is not enabled, disabling last so a stop still finds it
+                if request.changes.contains(&Field::Enable) {
+                    self.do_enable(handle, request)?;
+                }
+
+                if request.changes.contains(&Field::Start) {
+                    self.do_start(handle, request)?;
+                } else if request.changes.contains(&Field::Stop) {
+                    self.do_stop(handle, request)?;
+                } else if request.changes.contains(&Field::Restart) {
+                    self.do_restart(handle, request)?;
+                }
+
+                if request.changes.contains(&Field::Disable) {
+                    self.do_disable(handle, request)?;
+                }
+
+                Ok(handle
+                    .response
+                    .is_modified(request, request.changes.clone()))
+            }
+
+            _ => Err(handle.response.not_supported(request)),
+        }
+    }
+}
+
+/// Work out what has to change to get from `actual` to the requested state. The
+/// rules match `!sd_service`: restart restarts a running service and starts a
+/// stopped one, and cannot be combined with started:false.
+fn plan_changes(
+    init: InitSystem,
+    actual: ServiceState,
+    enabled: Option<bool>,
+    started: Option<bool>,
+    restart: bool,
+) -> Result<Vec<Field>, String> {
+    if started == Some(false) && restart {
+        return Err(String::from("started:false and restart:true conflict"));
+    }
+    let mut changes: Vec<Field> = Vec::new();
+    match (actual.enabled, enabled) {
+        (true, Some(false)) => changes.push(Field::Disable),
+        (false, Some(true)) => changes.push(Field::Enable),
+        _ => {}
+    }
+
+    let mut running = actual.started;
+    if init == InitSystem::Runit {
+        // runsvdir supervises exactly the services linked into the service
+        // directory, starting them as they appear and stopping them as they go
+        if !enabled.unwrap_or(actual.enabled) && (started == Some(true) || restart) {
+            return Err(String::from(
+                "runit only runs enabled services, set enabled:true to start it",
+            ));
+        }
+        if changes.contains(&Field::Enable) {
+            running = true;
+        } else if changes.contains(&Field::Disable) {
+            running = false;
+        }
+    }
+
+    match (running, started, restart) {
+        (true, _, true) => changes.push(Field::Restart),
+        (false, _, true) => changes.push(Field::Start),
+        (false, Some(true), false) => changes.push(Field::Start),
+        (true, Some(false), false) => changes.push(Field::Stop),
+        _ => {}
+    }
+    Ok(changes)
+}
+
+impl ServiceAction {
+    fn get_state(
+        &self,
+        handle: &Arc<TaskHandle>,
+        request: &Arc<TaskRequest>,
+    ) -> Result<ServiceState, Arc<TaskResponse>> {
+        match self.init {
+            InitSystem::OpenRc => self.get_openrc_state(handle, request),
+            InitSystem::Runit => self.get_runit_state(handle, request),
+            InitSystem::Launchd => self.get_launchd_state(handle, request),
+            InitSystem::Systemd => Err(handle.response.not_supported(request)),
+        }
+    }
+
+    fn run_command(
+        &self,
+        handle: &Arc<TaskHandle>,
+        request: &Arc<TaskRequest>,
+        command: &str,
+    ) -> Result<Arc<TaskResponse>, Arc<TaskResponse>> {
+        handle.remote.run(request, command, CheckRc::Checked)
+    }
+
+    fn not_found(&self, handle: &Arc<TaskHandle>, request: &Arc<TaskRequest>) -> Arc<TaskResponse> {
+        handle
+            .response
+            .is_failed(request, &format!("service {} does not exist", self.service))
+    }
+
+    // OpenRC: services are enabled by adding them to the default runlevel
+
+    fn get_openrc_state(
+        &self,
+        handle: &Arc<TaskHandle>,
+        request: &Arc<TaskRequest>,
+    ) -> Result<ServiceState, Arc<TaskResponse>> {
+        let exists = handle.remote.run(
+            request,
+            &format!("rc-service -e '{}'", self.service),
+            CheckRc::Unchecked,
+        )?;
+        if cmd_info(&exists).0 != 0 {
+            return Err(self.not_found(handle, request));
+        }
+        let runlevel = handle
+            .remote
+            .run(request, "rc-update show default", CheckRc::Checked)?;
+        let status = handle.remote.run(
+            request,
+            &format!("rc-service '{}' status", self.service),
+            CheckRc::Unchecked,
+        )?;
+        Ok(ServiceState {
+            enabled: runlevel_lists(&cmd_info(&runlevel).1, &self.service),
+            started: cmd_info(&status).0 == 0,
+        })
+    }
+
+    // runit: services live in /etc/sv and are enabled by linking them into the
+    // directory runsvdir watches, which is /var/service on Void
+
+    fn get_runit_service_dir(
+        &self,
+        handle: &Arc<TaskHandle>,
+        request: &Arc<TaskRequest>,
+    ) -> Result<String, Arc<TaskResponse>> {
+        for dir in ["/var/service", "/etc/service", "/service"] {
+            if handle.remote.get_is_directory(request, dir)? {
+                return Ok(format!("{}/{}", dir, self.service));
+            }
+        }
+        Err(handle
+            .response
+            .is_failed(request, "could not find the runit service directory"))
+    }
+
+    fn get_runit_state(
+        &self,
+        handle: &Arc<TaskHandle>,
+        request: &Arc<TaskRequest>,
+    ) -> Result<ServiceState, Arc<TaskResponse>> {
+        if !handle
+            .remote
+            .get_is_directory(request, &format!("/etc/sv/{}", self.service))?
+        {
+            return Err(self.not_found(handle, request));
+        }
+        let link = self.get_runit_service_dir(handle, request)?;
+        let enabled = handle.remote.file_exists(request, &link)?;
+        let started = match enabled {
+            true => {
+                let status = handle.remote.run(
+                    request,
+                    &format!("sv status '{}'", link),
+                    CheckRc::Unchecked,
+                )?;
+                sv_status_is_running(&cmd_info(&status).1)
+            }
+            false => false,
+        };
+        Ok(ServiceState { enabled, started })
+    }
+
+    // runsv needs a moment to notice a new link, and sv cannot talk to the
+    // service until it has
+
+    fn wait_for_runsv(
+        &self,
+        handle: &Arc<TaskHandle>,
+        request: &Arc<TaskRequest>,
+        link: &str,
+    ) -> Result<(), Arc<TaskResponse>> {
+        for _ in 0..15 {
+            let status = handle.remote.run(
+                request,
+                &format!("sv status '{}'", link),
+                CheckRc::Unchecked,
+            )?;
+            if cmd_info(&status).0 == 0 {
+                return Ok(());
+            }
+            thread::sleep(Duration::from_secs(1));
+        }
+        Err(handle.response.is_failed(
+            request,
+            &format!(
+                "runsv did not pick up {} after it was enabled",
+                self.service
+            ),
+        ))
+    }
+
+    // launchd: system daemons, with the service being the job label and the job
+    // defined in /Library/LaunchDaemons/<label>.plist
+
+    fn get_launchd_plist(&self) -> String {
+        format!("/Library/LaunchDaemons/{}.plist", self.service)
+    }
+
+    fn get_launchd_state(
+        &self,
+        handle: &Arc<TaskHandle>,
+        request: &Arc<TaskRequest>,
+    ) -> Result<ServiceState, Arc<TaskResponse>> {
+        if !handle
+            .remote
+            .file_exists(request, &self.get_launchd_plist())?
+        {
+            return Err(self.not_found(handle, request));
+        }
+        let disabled =
+            handle
+                .remote
+                .run(request, "launchctl print-disabled system", CheckRc::Checked)?;
+        let loaded = handle.remote.run(
+            request,
+            &format!("launchctl print 'system/{}'", self.service),
+            CheckRc::Unchecked,
+        )?;
+        Ok(ServiceState {
+            enabled: !launchd_lists_disabled(&cmd_info(&disabled).1, &self.service),
+            started: cmd_info(&loaded).0 == 0,
+        })
+    }
+
+    fn do_enable(
+        &self,
+        handle: &Arc<TaskHandle>,
+        request: &Arc<TaskRequest>,
+    ) -> Result<(), Arc<TaskResponse>> {
+        match self.init {
+            InitSystem::OpenRc => {
+                self.run_command(
+                    handle,
+                    request,
+                    &format!("rc-update add '{}' default", self.service),
+                )?;
+            }
+            InitSystem::Runit => {
+                let link = self.get_runit_service_dir(handle, request)?;
+                self.run_command(
+                    handle,
+                    request,
+                    &format!("ln -s '/etc/sv/{}' '{}'", self.service, link),
+                )?;
+                self.wait_for_runsv(handle, request, &link)?;
+            }
+            InitSystem::Launchd => {
+                self.run_command(
+                    handle,
+                    request,
+                    &format!("launchctl enable 'system/{}'", self.service),
+                )?;
+            }
+            InitSystem::Systemd => return Err(handle.response.not_supported(request)),
+        }
+        Ok(())
+    }
+
+    fn do_disable(
+        &self,
+        handle: &Arc<TaskHandle>,
+        request: &Arc<TaskRequest>,
+    ) -> Result<(), Arc<TaskResponse>> {
+        match self.init {
+            InitSystem::OpenRc => {
+                self.run_command(
+                    handle,
+                    request,
+                    &format!("rc-update del '{}' default", self.service),
+                )?;
+            }
+            InitSystem::Runit => {
+                let link = self.get_runit_service_dir(handle, request)?;
+                handle.remote.delete_file(request, &link)?;
+            }
+            InitSystem::Launchd => {
+                self.run_command(
+                    handle,
+                    request,
+                    &format!("launchctl disable 'system/{}'", self.service),
+                )?;
+            }
+            InitSystem::Systemd => return Err(handle.response.not_supported(request)),
+        }
+        Ok(())
+    }
+
+    fn do_start(
+        &self,
+        handle: &Arc<TaskHandle>,
+        request: &Arc<TaskRequest>,
+    ) -> Result<Arc<TaskResponse>, Arc<TaskResponse>> {
+        let cmd = match self.init {
+            InitSystem::OpenRc => format!("rc-service '{}' start", self.service),
+            InitSystem::Runit => {
+                format!("sv up '{}'", self.get_runit_service_dir(handle, request)?)
+            }
+            InitSystem::Launchd => {
+                format!("launchctl bootstrap system '{}'", self.get_launchd_plist())
+            }
+            InitSystem::Systemd => return Err(handle.response.not_supported(request)),
+        };
+        self.run_command(handle, request, &cmd)
+    }
+
+    fn do_stop(
+        &self,
+        handle: &Arc<TaskHandle>,
+        request: &Arc<TaskRequest>,
+    ) -> Result<Arc<TaskResponse>, Arc<TaskResponse>> {
+        let cmd = match self.init {
+            InitSystem::OpenRc => format!("rc-service '{}' stop", self.service),
+            InitSystem::Runit => {
+                format!("sv down '{}'", self.get_runit_service_dir(handle, request)?)
+            }
+            InitSystem::Launchd => format!("launchctl bootout 'system/{}'", self.service),
+            InitSystem::Systemd => return Err(handle.response.not_supported(request)),
+        };
+        self.run_command(handle, request, &cmd)
+    }
+
+    fn do_restart(
+        &self,
+        handle: &Arc<TaskHandle>,
+        request: &Arc<TaskRequest>,
+    ) -> Result<Arc<TaskResponse>, Arc<TaskResponse>> {
+        let cmd = match self.init {
+            InitSystem::OpenRc => format!("rc-service '{}' restart", self.service),
+            InitSystem::Runit => format!(
+                "sv restart '{}'",
+                self.get_runit_service_dir(handle, request)?
+            ),
+            InitSystem::Launchd => {
+                format!("launchctl kickstart -k 'system/{}'", self.service)
+            }
+            InitSystem::Systemd => return Err(handle.response.not_supported(request)),
+        };
+        self.run_command(handle, request, &cmd)
+    }
+}
+
+/// `rc-update show <runlevel>` prints one `  name | runlevel` line per service.
+fn runlevel_lists(out: &str, service: &str) -> bool {
+    out.lines()
+        .filter_map(|line| line.split('|').next())
+        .any(|name| name.trim() == service)
+}
+
+/// `sv status` reports `run: <dir>: (pid 123) 45s` for a running service, and
+/// `down:` or `fail:` otherwise.
+fn sv_status_is_running(out: &str) -> bool {
+    out.trim_start().starts_with("run:")
+}
+
+/// `launchctl print-disabled` lists overrides as `"label" => disabled`, or as
+/// `=> true` on releases before Big Sur. Labels without an override are enabled.
+fn launchd_lists_disabled(out: &str, label: &str) -> bool {
+    out.lines().any(|line| match line.trim().split_once("=>") {
+        Some((name, state)) => {
+            name.trim().trim_matches('"') == label && matches!(state.trim(), "disabled" | "true")
+        }
+        None => false,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn state(enabled: bool, started: bool) -> ServiceState {
+        ServiceState { enabled, started }
+    }
+
+    #[test]
+    fn unsupported_init_systems_are_named_as_such() {
+        assert_eq!(init_system_from_fact("openrc"), Ok(InitSystem::OpenRc));
+        let msg = init_system_from_fact("unknown").unwrap_err();
+        assert!(msg.contains("not supported"), "{}", msg);
+        assert!(!msg.contains("!facts"), "{}", msg);
+        assert!(init_system_from_fact("s6").unwrap_err().contains("'s6'"));
+    }
+
+    #[test]
+    fn plans_follow_sd_service_semantics() {
+        let openrc = InitSystem::OpenRc;
+        assert_eq!(
+            plan_changes(openrc, state(false, false), Some(true), Some(true), false),
+            Ok(vec![Field::Enable, Field::Start])
+        );
+        assert_eq!(
+            plan_changes(openrc, state(true, true), Some(true), Some(true), false),
+            Ok(vec![])
+        );
+        assert_eq!(
+            plan_changes(openrc, state(true, true), Some(false), Some(false), false),
+            Ok(vec![Field::Disable, Field::Stop])
+        );
+        assert_eq!(
+            plan_changes(openrc, state(true, true), None, None, true),
+            Ok(vec![Field::Restart])
+        );
+        assert_eq!(
+            plan_changes(openrc, state(true, false), None, None, true),
+            Ok(vec![Field::Start])
+        );
+        assert!(plan_changes(openrc, state(true, true), None, Some(false), true).is_err());
+    }
+
+    #[test]
+    fn runit_enabling_is_starting() {
+        let runit = InitSystem::Runit;
+        // linking the service in starts it, so there is nothing left to start
+        assert_eq!(
+            plan_changes(runit, state(false, false), Some(true), Some(true), false),
+            Ok(vec![Field::Enable])
+        );
+        assert_eq!(
+            plan_changes(runit, state(false, false), Some(true), Some(false), false),
+            Ok(vec![Field::Enable, Field::Stop])
+        );
+        assert_eq!(
+            plan_changes(runit, state(true, true), Some(false), Some(false), false),
+            Ok(vec![Field::Disable])
+        );
+        assert!(plan_changes(runit, state(false, false), None, Some(true), false).is_err());
+    }
+
+    #[test]
+    fn parses_status_output() {
+        let runlevel = "                sshd | default\n               crond | default\n";
+        assert!(runlevel_lists(runlevel, "sshd"));
+        assert!(!runlevel_lists(runlevel, "ssh"));
+
+        assert!(sv_status_is_running(
+            "run: /var/service/sshd: (pid 812) 3651s\n"
+        ));
+        assert!(!sv_status_is_running(
+            "down: /var/service/sshd: 4s, normally up\n"
+        ));
+
+        let disabled = "disabled services = {\n\t\"com.openssh.sshd\" => disabled\n\t\"org.nginx\" => enabled\n}\n";
+        assert!(launchd_lists_disabled(disabled, "com.openssh.sshd"));
+        assert!(!launchd_lists_disabled(disabled, "org.nginx"));
+        assert!(!launchd_lists_disabled(disabled, "org.other"));
+        assert!(launchd_lists_disabled(
+            "\t\"com.openssh.sshd\" => true\n",
+            "com.openssh.sshd"
+        ));
+    }
+}
@@ -0,0 +1,333 @@
+// Jetpack
+// Copyright (C) Riff Labs Limited <team@riff.cc>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// at your option) any later version.
+
+//! Load a kernel module now and on every boot
+//!
+//! Boot-time loading is a file in /etc/modules-load.d, module parameters go into
+//! /etc/modprobe.d so that they are used however the module ends up loaded.
+
+use crate::handle::handle::{CheckRc, TaskHandle};
+use crate::inventory::hosts::HostOSType;
+use crate::tasks::cmd_library::shell_quote;
+use crate::tasks::fields::Field;
+use crate::tasks::files::Recurse;
+use crate::tasks::*;
+use crate::util::diff::unified;
+use serde::Deserialize;
+use std::sync::Arc;
+
+const MODULE: &str = "kernel_module";
+const MODULES_LOAD_DIR: &str = "/etc/modules-load.d";
+const MODPROBE_DIR: &str = "/etc/modprobe.d";
+
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+#[derive(Default)]
+pub struct KernelModuleTask {
+    pub name: Option<String>,
+    pub module: String,
+    /// Module parameters, e.g. "max_loop=64 max_part=8"
+    pub params: Option<String>,
+    /// Load the module on boot as well (default: true)
+    pub persist: Option<Templated<bool>>,
+    pub remove: Option<Templated<bool>>,
+    pub with: Option<PreLogicInput>,
+    pub and: Option<PostLogicInput>,
+}
+
+struct KernelModuleAction {
+    pub module: String,
+    pub params: Option<String>,
+    pub persist: bool,
+    pub remove: bool,
+}
+
+impl IsTask for KernelModuleTask {
+    fn get_module(&self) -> String {
+        String::from(MODULE)
+    }
+    fn get_name(&self) -> Option<String> {
+        self.name.clone()
+    }
+    fn get_with(&self) -> Option<PreLogicInput> {
+        self.with.clone()
+    }
+
+    fn evaluate(
+        &self,
+        handle: &Arc<TaskHandle>,
+        request: &Arc<TaskRequest>,
+        tm: TemplateMode,
+    ) -> Result<EvaluatedTask, Arc<TaskResponse>> {
+        let module =
+            handle
+                .template
+                .string_no_spaces(request, tm, &String::from("module"), &self.module)?;
+        let params = handle.template.string_option_unsafe_for_shell(
+            request,
+            tm,
+            &String::from("params"),
+            &self.params,
+        )?;
+        if tm == TemplateMode::Strict {
+            if module.is_empty()
+                || !module
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+            {
+                return Err(handle.response.is_failed(
+                    request,
+                    &format!("'{}' is not a valid kernel module name", module),
+                ));
+            }
+            if params.as_ref().is_some_and(|x| x.contains('\n')) {
+                return Err(handle
+                    .response
+                    .is_failed(request, "params must be a single line"));
+            }
+        }
+
+        Ok(EvaluatedTask {
+            action: Arc::new(KernelModuleAction {
+                module,
+                params: params.map(|x| x.split_whitespace().collect::<Vec<&str>>().join(" ")),
+                persist: handle.template.boolean_option_default_true(
+                    request,
+                    tm,
+                    &String::from("persist"),
+                    &self.persist,
+                )?,
+                remove: handle.template.boolean_option_default_false(
+                    request,
+                    tm,
+                    &String::from("remove"),
+                    &self.remove,
+                )?,
+            }),
+            with: Arc::new(PreLogicInput::template(handle, request, tm, &self.with)?),
+            and: Arc::new(PostLogicInput::template(handle, request, tm, &self.and)?),
+        })
+    }
+}
+
+impl IsAction for KernelModuleAction {
+    fn dispatch(
+        &self,
+        handle: &Arc<TaskHandle>,
+        request: &Arc<TaskRequest>,
+    ) -> Result<Arc<TaskResponse>, Arc<TaskResponse>> {
+        match request.request_type {
+            TaskRequestType::Query => {
+                if handle.remote.get_os_type() != HostOSType::Linux {
+                    return Err(handle
+                        .response
+                        .is_failed(request, "the kernel_module module only supports Linux"));
+                }
+                let loaded = self.is_loaded(handle, request)?;
+                if self.remove {
+                    let mut found = loaded;
+                    for (path, _) in self.owned_files().iter() {
+                        found = found || handle.remote.file_exists(request, path)?;
+                    }
+                    return match found {
+                        true => Ok(handle.response.needs_removal(request)),
+                        false => Ok(handle.response.is_matched(request)),
+                    };
+                }
+
+                let mut changes: Vec<Field> = Vec::new();
+                let mut diffs: Vec<String> = Vec::new();
+                for (path, content) in self.owned_files().iter() {
+                    let current = handle.remote.read_file(request, path)?;
+                    if current.as_deref() == Some(content.as_str()) {
+                        continue;
+                    }
+                    if !changes.contains(&Field::Content) {
+                        changes.push(Field::Content);
+                    }
+                    if handle.response.wants_diff()
+                        && let Some(diff) = unified(
+                            path,
+                            current.as_ref().map(|x| x.as_bytes()),
+                            content.as_bytes(),
+                        )
+                    {
+                        diffs.push(diff);
+                    }
+                }
+                if !loaded {
+                    changes.push(Field::Load);
+                }
+                if changes.is_empty() {
+                    return Ok(handle.response.is_matched(request));
+                }
+                let response = handle.response.needs_modification(request, &changes);
+                let diff = match diffs.is_empty() {
+                    true => None,
+                    false => Some(diffs.concat()),
+                };
+                Ok(handle.response.with_diff(response, diff))
+            }
+
+            TaskRequestType::Modify => {
+                if request.changes.contains(&Field::Content) {
+                    for (path, content) in self.owned_files().iter() {
+                        write_file(handle, request, path, content)?;
+                    }
+                }
+                // with persist the parameters are already in modprobe.d, which modprobe reads
+                if request.changes.contains(&Field::Load) {
+                    let mut cmd = format!("modprobe {}", self.module);
+                    if let (false, Some(params)) = (self.persist, &self.params) {
+                        for param in params.split_whitespace() {
+                            cmd.push_str(&format!(" {}", shell_quote(param)));
+                        }
+                    }
+                    handle.remote.run_unsafe(request, &cmd, CheckRc::Checked)?;
+                }
+                Ok(handle
+                    .response
+                    .is_modified(request, request.changes.clone()))
+            }
+
+            TaskRequestType::Remove => {
+                if self.is_loaded(handle, request)? {
+                    let cmd = format!("modprobe -r {}", self.module);
+                    handle.remote.run(request, &cmd, CheckRc::Checked)?;
+                }
+                for (path, _) in self.owned_files().iter() {
+                    if handle.remote.file_exists(request, path)? {
+                        handle.remote.delete_file(request, path)?;
+                    }
+                }
+                Ok(handle.response.is_removed(request))
+            }
+
+            _ => Err(handle.response.not_supported(request)),
+        }
+    }
+}
+
+impl KernelModuleAction {
+    // the files this task manages with their wanted content. The modprobe.d file may
+    // hold someone else's blacklist or install lines, so it is only claimed when
+    // params are given.
+
+    fn owned_files(&self) -> Vec<(String, String)> {
+        let mut files: Vec<(String, String)> = Vec::new();
+        if self.persist || self.remove {
+            files.push((
+                format!("{}/{}.conf", MODULES_LOAD_DIR, self.module),
+                format!("{}\n", self.module),
+            ));
+        }
+        if let (true, Some(params)) = (self.persist || self.remove, &self.params) {
+            files.push((
+                format!("{}/{}.conf", MODPROBE_DIR, self.module),
+                format!("options {} {}\n", self.module, params),
+            ));
+        }
+        files
+    }
+
+    // /proc/modules lists loadable modules and is missing on kernels built without
+    // them, built-in ones only show up in /sys/module
+
+    fn is_loaded(
+        &self,
+        handle: &Arc<TaskHandle>,
+        request: &Arc<TaskRequest>,
+    ) -> Result<bool, Arc<TaskResponse>> {
+        let name = sysfs_name(&self.module);
+        let result = handle
+            .remote
+            .run(request, "cat /proc/modules", CheckRc::Unchecked)?;
+        let (rc, out) = cmd_info(&result);
+        if rc == 0
+            && out
+                .lines()
+                .any(|line| line.split_whitespace().next() == Some(name.as_str()))
+        {
+            return Ok(true);
+        }
+        handle
+            .remote
+            .file_exists(request, &format!("/sys/module/{}", name))
+    }
+}
+
+// modprobe treats - and _ alike, the kernel always reports _
+
+fn sysfs_name(module: &str) -> String {
+    module.replace('-', "_")
+}
+
+fn write_file(
+    handle: &Arc<TaskHandle>,
+    request: &Arc<TaskRequest>,
+    path: &str,
+    content: &str,
+) -> Result<(), Arc<TaskResponse>> {
+    if handle.remote.read_file(request, path)?.as_deref() == Some(content) {
+        return Ok(());
+    }
+    if let Some((dir, _)) = path.rsplit_once('/') {
+        handle.remote.create_directory(request, dir)?;
+    }
+    handle
+        .remote
+        .write_data(request, content, path, &None, |f| {
+            handle
+                .remote
+                .set_mode(request, f, "0644", Recurse::No)
+                .map(|_| ())
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn persisted_files_claim_modprobe_d_only_with_params() {
+        let a = KernelModuleAction {
+            module: String::from("br_netfilter"),
+            params: None,
+            persist: true,
+            remove: false,
+        };
+        assert_eq!(
+            a.owned_files(),
+            vec![(
+                String::from("/etc/modules-load.d/br_netfilter.conf"),
+                String::from("br_netfilter\n")
+            )]
+        );
+        let a = KernelModuleAction {
+            params: Some(String::from("nf_conntrack_helper=1")),
+            ..a
+        };
+        assert_eq!(
+            a.owned_files()[1],
+            (
+                String::from("/etc/modprobe.d/br_netfilter.conf"),
+                String::from("options br_netfilter nf_conntrack_helper=1\n")
+            )
+        );
+        let a = KernelModuleAction {
+            persist: false,
+            ..a
+        };
+        assert!(a.owned_files().is_empty());
+    }
+
+    #[test]
+    fn dashes_are_reported_as_underscores() {
+        assert_eq!(sysfs_name("snd-hda-intel"), "snd_hda_intel");
+    }
+}
@@ -0,0 +1,651 @@
+// Jetpack
+// Copyright (C) Riff Labs Limited <team@riff.cc>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// at your option) any later version.
+
+//! Manage a filesystem's fstab entry and whether it is mounted
+//!
+//! Mounting always goes through the fstab entry (`mount <path>`), so what is
+//! mounted now is also what comes back after a reboot.
+
+use crate::handle::handle::{CheckRc, TaskHandle};
+use crate::inventory::hosts::HostOSType;
+use crate::tasks::cmd_library::shell_quote;
+use crate::tasks::fields::Field;
+use crate::tasks::files::Recurse;
+use crate::tasks::*;
+use crate::util::diff::unified;
+use serde::Deserialize;
+use std::sync::Arc;
+
+const MODULE: &str = "mount";
+const FSTAB: &str = "/etc/fstab";
+
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+#[derive(Default)]
+pub struct MountTask {
+    pub name: Option<String>,
+    pub path: String,
+    pub src: Option<String>,
+    /// Filesystem type, e.g. ext4 or nfs (default: none for bind mounts)
+    pub fstype: Option<String>,
+    pub opts: Option<String>,
+    pub dump: Option<Templated<u64>>,
+    pub passno: Option<Templated<u64>>,
+    /// mounted, present, unmounted or absent (default: mounted)
+    pub state: Option<String>,
+    pub fstab: Option<String>,
+    pub with: Option<PreLogicInput>,
+    pub and: Option<PostLogicInput>,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum MountState {
+    // in fstab and mounted
+    Mounted,
+    // in fstab, mounted or not
+    Present,
+    // not mounted, fstab left alone
+    Unmounted,
+    // neither
+    Absent,
+}
+
+struct MountAction {
+    pub path: String,
+    pub entry: FstabEntry,
+    pub state: MountState,
+    pub fstab: String,
+}
+
+#[derive(Clone, PartialEq, Debug, Default)]
+struct FstabEntry {
+    src: String,
+    path: String,
+    fstype: String,
+    opts: String,
+    dump: u64,
+    passno: u64,
+}
+
+impl FstabEntry {
+    fn parse(line: &str) -> Option<FstabEntry> {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            return None;
+        }
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        if fields.len() < 3 {
+            return None;
+        }
+        let number = |index: usize| fields.get(index).and_then(|x| x.parse::<u64>().ok());
+        Some(FstabEntry {
+            src: unescape(fields[0]),
+            path: normalize_path(&unescape(fields[1])),
+            fstype: fields[2].to_string(),
+            opts: fields
+                .get(3)
+                .map(|x| x.to_string())
+                .unwrap_or_else(|| String::from("defaults")),
+            dump: number(4).unwrap_or(0),
+            passno: number(5).unwrap_or(0),
+        })
+    }
+
+    // dump and passno only matter to backups and boot-time fsck
+
+    fn mounts_differently(&self, other: &FstabEntry) -> bool {
+        self.src != other.src || self.fstype != other.fstype || self.opts != other.opts
+    }
+
+    fn has_opt(&self, opt: &str) -> bool {
+        self.opts.split(',').any(|x| x == opt)
+    }
+
+    fn is_bind(&self) -> bool {
+        self.has_opt("bind") || self.has_opt("rbind")
+    }
+
+    // whether a live mount (from /proc/self/mounts) has this entry's type and ro/rw.
+    // The kernel adds its own default options, so the rest cannot be compared, and a
+    // bind mount shows the type of the filesystem it was taken from.
+
+    fn matches_live(&self, live: &FstabEntry) -> bool {
+        let fstype = self.is_bind()
+            || self.fstype == "auto"
+            || self.fstype == live.fstype
+            || (self.fstype == "nfs" && live.fstype == "nfs4");
+        fstype && self.has_opt("ro") == live.has_opt("ro")
+    }
+
+    fn render(&self) -> String {
+        format!(
+            "{} {} {} {} {} {}",
+            escape(&self.src),
+            escape(&self.path),
+            self.fstype,
+            self.opts,
+            self.dump,
+            self.passno
+        )
+    }
+}
+
+impl IsTask for MountTask {
+    fn get_module(&self) -> String {
+        String::from(MODULE)
+    }
+    fn get_name(&self) -> Option<String> {
+        self.name.clone()
+    }
+    fn get_with(&self) -> Option<PreLogicInput> {
+        self.with.clone()
+    }
+
+    fn evaluate(
+        &self,
+        handle: &Arc<TaskHandle>,
+        request: &Arc<TaskRequest>,
+        tm: TemplateMode,
+    ) -> Result<EvaluatedTask, Arc<TaskResponse>> {
+        // paths and sources may hold spaces, they are escaped in fstab and quoted for mount
+        let path = normalize_path(&handle.template.string_unsafe_for_shell(
+            request,
+            tm,
+            &String::from("path"),
+            &self.path,
+        )?);
+        let src = handle.template.string_option_unsafe_for_shell(
+            request,
+            tm,
+            &String::from("src"),
+            &self.src,
+        )?;
+        // options such as size=8m or addr=10.0.0.5 only ever go into fstab
+        let opts = handle
+            .template
+            .string_option_unsafe_for_shell(request, tm, &String::from("opts"), &self.opts)?
+            .unwrap_or_else(|| String::from("defaults"));
+        let bind = opts.split(',').any(|x| x == "bind" || x == "rbind");
+        let fstype = handle
+            .template
+            .string_option_no_spaces(request, tm, &String::from("fstype"), &self.fstype)?
+            .or_else(|| bind.then(|| String::from("none")));
+        let state = handle.template.string_option_no_spaces(
+            request,
+            tm,
+            &String::from("state"),
+            &self.state,
+        )?;
+        let state = match state.as_deref() {
+            None | Some("mounted") => MountState::Mounted,
+            Some("present") => MountState::Present,
+            Some("unmounted") => MountState::Unmounted,
+            Some("absent") => MountState::Absent,
+            Some(x) if tm == TemplateMode::Strict => {
+                return Err(handle.response.is_failed(
+                    request,
+                    &format!(
+                        "state must be mounted, present, unmounted or absent, not '{}'",
+                        x
+                    ),
+                ));
+            }
+            Some(_) => MountState::Mounted,
+        };
+        if tm == TemplateMode::Strict {
+            if opts.chars().any(char::is_whitespace) {
+                return Err(handle
+                    .response
+                    .is_failed(request, "opts cannot contain whitespace"));
+            }
+            if !path.starts_with('/') {
+                return Err(handle
+                    .response
+                    .is_failed(request, "path must be an absolute path"));
+            }
+            let needs_entry = matches!(state, MountState::Mounted | MountState::Present);
+            if needs_entry && (src.is_none() || fstype.is_none()) {
+                return Err(handle.response.is_failed(
+                    request,
+                    "src and fstype are required for state mounted and present",
+                ));
+            }
+        }
+
+        Ok(EvaluatedTask {
+            action: Arc::new(MountAction {
+                entry: FstabEntry {
+                    src: src.unwrap_or_default(),
+                    path: path.clone(),
+                    fstype: fstype.unwrap_or_default(),
+                    opts,
+                    dump: handle.template.integer_option_to_integer(
+                        request,
+                        tm,
+                        &String::from("dump"),
+                        &self.dump,
+                        0,
+                    )?,
+                    passno: handle.template.integer_option_to_integer(
+                        request,
+                        tm,
+                        &String::from("passno"),
+                        &self.passno,
+                        0,
+                    )?,
+                },
+                path,
+                state,
+                fstab: handle
+                    .template
+                    .string_option_no_spaces(request, tm, &String::from("fstab"), &self.fstab)?
+                    .unwrap_or_else(|| String::from(FSTAB)),
+            }),
+            with: Arc::new(PreLogicInput::template(handle, request, tm, &self.with)?),
+            and: Arc::new(PostLogicInput::template(handle, request, tm, &self.and)?),
+        })
+    }
+}
+
+impl IsAction for MountAction {
+    fn dispatch(
+        &self,
+        handle: &Arc<TaskHandle>,
+        request: &Arc<TaskRequest>,
+    ) -> Result<Arc<TaskResponse>, Arc<TaskResponse>> {
+        match request.request_type {
+            TaskRequestType::Query => {
+                if handle.remote.get_os_type() != HostOSType::Linux {
+                    return Err(handle
+                        .response
+                        .is_failed(request, "the mount module only supports Linux"));
+                }
+                let current = handle.remote.read_file(request, &self.fstab)?;
+                let current = current.unwrap_or_default();
+                let live = self.get_live_mount(handle, request)?;
+                let mounted = live.is_some();
+                let plan = self.plan(&current);
+
+                let mut changes: Vec<Field> = Vec::new();
+                match self.state {
+                    MountState::Absent => {
+                        return match mounted || plan.is_some() {
+                            true => Ok(handle.response.needs_removal(request)),
+                            false => Ok(handle.response.is_matched(request)),
+                        };
+                    }
+                    MountState::Unmounted if mounted => changes.push(Field::Unmount),
+                    MountState::Unmounted => {}
+                    MountState::Present | MountState::Mounted => {
+                        if plan.is_some() {
+                            changes.push(Field::Content);
+                        }
+                        if self.state == MountState::Mounted {
+                            // what is mounted may not come from fstab at all, so it is
+                            // checked as well as the entry it would be remounted from
+                            let old = find_entry(&current, &self.path);
+                            match &live {
+                                None => changes.push(Field::Mount),
+                                Some(live) => {
+                                    if !self.is_live_match(handle, request, live)?
+                                        || old.is_some_and(|x| x.mounts_differently(&self.entry))
+                                    {
+                                        changes.push(Field::Remount);
+                                    }
+                                }
+                            }
+                        }
+                    }
+                }
+                if changes.is_empty() {
+                    return Ok(handle.response.is_matched(request));
+                }
+                let response = handle.response.needs_modification(request, &changes);
+                let diff = match (handle.response.wants_diff(), &plan) {
+                    (true, Some(content)) if changes.contains(&Field::Content) => {
+                        unified(&self.fstab, Some(current.as_bytes()), content.as_bytes())
+                    }
+                    _ => None,
+                };
+                Ok(handle.response.with_diff(response, diff))
+            }
+
+            TaskRequestType::Modify => {
+                let current = handle
+                    .remote
+                    .read_file(request, &self.fstab)?
+                    .unwrap_or_default();
+                let live = self.get_live_mount(handle, request)?;
+                if request.changes.contains(&Field::Content) {
+                    self.write(handle, request, &current)?;
+                }
+                if request.changes.contains(&Field::Mount) {
+                    if !handle.remote.file_exists(request, &self.path)? {
+                        handle.remote.create_directory(request, &self.path)?;
+                    }
+                    self.mount(handle, request, "")?;
+                }
+                if request.changes.contains(&Field::Remount) {
+                    match live {
+                        // only the options changed, which a remount picks up from fstab
+                        Some(live)
+                            if self.entry.matches_live(&live)
+                                && self.is_same_source(handle, request, &live)? =>
+                        {
+                            self.mount(handle, request, "-o remount ")?;
+                        }
+                        _ => {
+                            self.unmount(handle, request)?;
+                            self.mount(handle, request, "")?;
+                        }
+                    }
+                }
+                if request.changes.contains(&Field::Unmount) {
+                    self.unmount(handle, request)?;
+                }
+                Ok(handle
+                    .response
+                    .is_modified(request, request.changes.clone()))
+            }
+
+            // the mount point directory is left behind
+            TaskRequestType::Remove => {
+                if self.get_live_mount(handle, request)?.is_some() {
+                    self.unmount(handle, request)?;
+                }
+                let current = handle
+                    .remote
+                    .read_file(request, &self.fstab)?
+                    .unwrap_or_default();
+                self.write(handle, request, &current)?;
+                Ok(handle.response.is_removed(request))
+            }
+
+            _ => Err(handle.response.not_supported(request)),
+        }
+    }
+}
+
+impl MountAction {
+    fn plan(&self, current: &str) -> Option<String> {
+        match self.state {
+            MountState::Absent => plan_fstab(current, &self.path, None),
+            _ => plan_fstab(current, &self.path, Some(&self.entry)),
+        }
+    }
+
+    fn write(
+        &self,
+        handle: &Arc<TaskHandle>,
+        request: &Arc<TaskRequest>,
+        current: &str,
+    ) -> Result<(), Arc<TaskResponse>> {
+        let content = match self.plan(current) {
+            Some(content) => content,
+            None => return Ok(()),
+        };
+        handle
+            .remote
+            .write_data(request, &content, &self.fstab, &None, |f| {
+                handle
+                    .remote
+                    .set_mode(request, f, "0644", Recurse::No)
+                    .map(|_| ())
+            })
+    }
+
+    // mount reads /etc/fstab unless told otherwise with -T
+
+    fn mount(
+        &self,
+        handle: &Arc<TaskHandle>,
+        request: &Arc<TaskRequest>,
+        extra: &str,
+    ) -> Result<(), Arc<TaskResponse>> {
+        let fstab = match self.fstab == FSTAB {
+            true => String::new(),
+            false => format!("-T {} ", shell_quote(&self.fstab)),
+        };
+        let cmd = format!("mount {}{}{}", fstab, extra, shell_quote(&self.path));
+        handle.remote.run_unsafe(request, &cmd, CheckRc::Checked)?;
+        Ok(())
+    }
+
+    fn unmount(
+        &self,
+        handle: &Arc<TaskHandle>,
+        request: &Arc<TaskRequest>,
+    ) -> Result<(), Arc<TaskResponse>> {
+        let cmd = format!("umount {}", shell_quote(&self.path));
+        handle.remote.run_unsafe(request, &cmd, CheckRc::Checked)?;
+        Ok(())
+    }
+
+    // the filesystem mounted at path now, the last one when mounts are stacked
+
+    fn get_live_mount(
+        &self,
+        handle: &Arc<TaskHandle>,
+        request: &Arc<TaskRequest>,
+    ) -> Result<Option<FstabEntry>, Arc<TaskResponse>> {
+        let result = handle
+            .remote
+            .run(request, "cat /proc/self/mounts", CheckRc::Checked)?;
+        let (_, out) = cmd_info(&result);
+        // /proc/self/mounts has the same layout and escaping as fstab
+        Ok(out
+            .lines()
+            .rev()
+            .filter_map(FstabEntry::parse)
+            .find(|x| x.path == self.path))
+    }
+
+    fn is_live_match(
+        &self,
+        handle: &Arc<TaskHandle>,
+        request: &Arc<TaskRequest>,
+        live: &FstabEntry,
+    ) -> Result<bool, Arc<TaskResponse>> {
+        Ok(self.entry.matches_live(live) && self.is_same_source(handle, request, live)?)
+    }
+
+    // the kernel names the device where fstab may use UUID=, LABEL= or a symlink such
+    // as /dev/disk/by-id/..., so both sides are resolved before comparing. A bind
+    // mount shows the device of its source directory and cannot be compared.
+
+    fn is_same_source(
+        &self,
+        handle: &Arc<TaskHandle>,
+        request: &Arc<TaskRequest>,
+        live: &FstabEntry,
+    ) -> Result<bool, Arc<TaskResponse>> {
+        if self.entry.is_bind() || live.src == self.entry.src {
+            return Ok(true);
+        }
+        Ok(resolve_source(handle, request, &live.src)?
+            == resolve_source(handle, request, &self.entry.src)?)
+    }
+}
+
+fn resolve_source(
+    handle: &Arc<TaskHandle>,
+    request: &Arc<TaskRequest>,
+    src: &str,
+) -> Result<String, Arc<TaskResponse>> {
+    let cmd = match source_kind(src) {
+        SourceKind::Tag => format!("findfs {}", shell_quote(src)),
+        SourceKind::Device => format!("readlink -f {}", shell_quote(src)),
+        SourceKind::Other => return Ok(src.to_string()),
+    };
+    let result = handle
+        .remote
+        .run_unsafe(request, &cmd, CheckRc::Unchecked)?;
+    let (rc, out) = cmd_info(&result);
+    match (rc, out.trim()) {
+        (0, resolved) if !resolved.is_empty() => Ok(resolved.to_string()),
+        _ => Ok(src.to_string()),
+    }
+}
+
+#[derive(Debug, PartialEq)]
+enum SourceKind {
+    Tag,
+    Device,
+    Other,
+}
+
+fn source_kind(src: &str) -> SourceKind {
+    let tags = ["UUID=", "LABEL=", "PARTUUID=", "PARTLABEL="];
+    if tags.iter().any(|tag| src.starts_with(tag)) {
+        SourceKind::Tag
+    } else if src.starts_with("/dev/") {
+        SourceKind::Device
+    } else {
+        SourceKind::Other
+    }
+}
+
+fn find_entry(fstab: &str, path: &str) -> Option<FstabEntry> {
+    fstab
+        .lines()
+        .filter_map(FstabEntry::parse)
+        .find(|x| x.path == path)
+}
+
+// puts entry in place of the first line for path, or appends it, and drops any other
+// lines for path. None drops them all. None is returned when fstab is already right.
+
+fn plan_fstab(current: &str, path: &str, entry: Option<&FstabEntry>) -> Option<String> {
+    let mut out = String::new();
+    let mut found = false;
+    for line in current.lines() {
+        match FstabEntry::parse(line) {
+            Some(existing) if existing.path == path => {
+                if let (false, Some(entry)) = (found, entry) {
+                    match existing == *entry {
+                        true => out.push_str(line),
+                        false => out.push_str(&entry.render()),
+                    }
+                    out.push('\n');
+                }
+                found = true;
+            }
+            _ => {
+                out.push_str(line);
+                out.push('\n');
+            }
+        }
+    }
+    if let (false, Some(entry)) = (found, entry) {
+        out.push_str(&entry.render());
+        out.push('\n');
+    }
+    match out == current {
+        true => None,
+        false => Some(out),
+    }
+}
+
+fn normalize_path(path: &str) -> String {
+    match path.trim_end_matches('/') {
+        "" => String::from("/"),
+        x => x.to_string(),
+    }
+}
+
+fn escape(field: &str) -> String {
+    field
+        .replace('\\', "\\134")
+        .replace(' ', "\\040")
+        .replace('\t', "\\011")
+}
+
+fn unescape(field: &str) -> String {
+    field
+        .replace("\\040", " ")
+        .replace("\\011", "\t")
+        .replace("\\134", "\\")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn nfs() -> FstabEntry {
+        FstabEntry {
+            src: String::from("nas:/export/media files"),
+            path: String::from("/srv/media"),
+            fstype: String::from("nfs"),
+            opts: String::from("rw,_netdev"),
+            dump: 0,
+            passno: 0,
+        }
+    }
+
+    #[test]
+    fn entries_round_trip_with_escaped_spaces() {
+        let line = nfs().render();
+        assert_eq!(
+            line,
+            "nas:/export/media\\040files /srv/media nfs rw,_netdev 0 0"
+        );
+        assert_eq!(FstabEntry::parse(&line), Some(nfs()));
+        let short = FstabEntry::parse("/dev/sdb1\t/data/\text4").unwrap();
+        assert_eq!(short.path, "/data");
+        assert_eq!(short.opts, "defaults");
+        assert_eq!(FstabEntry::parse("# /dev/sdb1 /data ext4"), None);
+    }
+
+    #[test]
+    fn live_mounts_are_compared_by_type_and_access() {
+        let live = FstabEntry::parse(
+            "nas:/export/media\\040files /srv/media nfs4 rw,relatime,vers=4.2 0 0",
+        )
+        .unwrap();
+        assert!(nfs().matches_live(&live));
+        let ro = FstabEntry {
+            opts: String::from("ro,_netdev"),
+            ..nfs()
+        };
+        assert!(!ro.matches_live(&live));
+        let tmpfs = FstabEntry {
+            fstype: String::from("tmpfs"),
+            ..nfs()
+        };
+        assert!(!tmpfs.matches_live(&live));
+        let bind = FstabEntry {
+            src: String::from("/data/media"),
+            fstype: String::from("none"),
+            opts: String::from("bind"),
+            ..nfs()
+        };
+        assert!(bind.matches_live(&live));
+        assert_eq!(source_kind("UUID=0a1b"), SourceKind::Tag);
+        assert_eq!(source_kind("/dev/disk/by-id/nvme0"), SourceKind::Device);
+        assert_eq!(source_kind("nas:/export"), SourceKind::Other);
+    }
+
+    #[test]
+    fn fstab_entries_are_replaced_appended_and_removed() {
+        let current = "# static\nUUID=1 / ext4 defaults 0 1\nnas:/old /srv/media nfs ro 0 0\n";
+        let plan = plan_fstab(current, "/srv/media", Some(&nfs())).unwrap();
+        assert_eq!(
+            plan,
+            format!("# static\nUUID=1 / ext4 defaults 0 1\n{}\n", nfs().render())
+        );
+        assert_eq!(plan_fstab(&plan, "/srv/media", Some(&nfs())), None);
+        assert_eq!(
+            plan_fstab(&plan, "/srv/media", None),
+            Some(String::from("# static\nUUID=1 / ext4 defaults 0 1\n"))
+        );
+        assert_eq!(
+            plan_fstab("", "/srv/media", Some(&nfs())),
+            Some(format!("{}\n", nfs().render()))
+        );
+    }
+}
@@ -0,0 +1,381 @@
+// Jetpack
+// Copyright (C) Riff Labs Limited <team@riff.cc>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// at your option) any later version.
+
+//! Set kernel parameters, persisted under /etc/sysctl.d and applied live
+
+use crate::handle::handle::{CheckRc, TaskHandle};
+use crate::inventory::hosts::HostOSType;
+use crate::tasks::cmd_library::shell_quote;
+use crate::tasks::fields::Field;
+use crate::tasks::files::Recurse;
+use crate::tasks::*;
+use crate::util::diff::unified;
+use serde::Deserialize;
+use std::sync::Arc;
+
+const MODULE: &str = "sysctl";
+const SYSCTL_DIR: &str = "/etc/sysctl.d";
+const DEFAULT_FILE: &str = "99-jetpack.conf";
+
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+#[derive(Default)]
+pub struct SysctlTask {
+    pub name: Option<String>,
+    pub key: String,
+    pub value: Option<String>,
+    /// File the setting is kept in, a name under /etc/sysctl.d or an absolute path
+    /// (default: 99-jetpack.conf)
+    pub file: Option<String>,
+    /// Also set the running value with sysctl -w (default: true)
+    pub apply: Option<Templated<bool>>,
+    pub remove: Option<Templated<bool>>,
+    pub with: Option<PreLogicInput>,
+    pub and: Option<PostLogicInput>,
+}
+
+struct SysctlAction {
+    pub key: String,
+    pub value: String,
+    pub path: String,
+    pub apply: bool,
+    pub remove: bool,
+}
+
+impl IsTask for SysctlTask {
+    fn get_module(&self) -> String {
+        String::from(MODULE)
+    }
+    fn get_name(&self) -> Option<String> {
+        self.name.clone()
+    }
+    fn get_with(&self) -> Option<PreLogicInput> {
+        self.with.clone()
+    }
+
+    fn evaluate(
+        &self,
+        handle: &Arc<TaskHandle>,
+        request: &Arc<TaskRequest>,
+        tm: TemplateMode,
+    ) -> Result<EvaluatedTask, Arc<TaskResponse>> {
+        let key = handle
+            .template
+            .string_no_spaces(request, tm, &String::from("key"), &self.key)?;
+        // values such as net.ipv4.tcp_rmem hold several numbers separated by spaces
+        let value = handle.template.string_option_unsafe_for_shell(
+            request,
+            tm,
+            &String::from("value"),
+            &self.value,
+        )?;
+        let file = handle
+            .template
+            .string_option_no_spaces(request, tm, &String::from("file"), &self.file)?
+            .unwrap_or_else(|| String::from(DEFAULT_FILE));
+        let remove = handle.template.boolean_option_default_false(
+            request,
+            tm,
+            &String::from("remove"),
+            &self.remove,
+        )?;
+        if tm == TemplateMode::Strict {
+            if !is_valid_key(&key) {
+                return Err(handle
+                    .response
+                    .is_failed(request, &format!("'{}' is not a valid sysctl key", key)));
+            }
+            match &value {
+                None if !remove => {
+                    return Err(handle
+                        .response
+                        .is_failed(request, "value is required unless remove: true"));
+                }
+                Some(value) if value.contains('\n') => {
+                    return Err(handle
+                        .response
+                        .is_failed(request, "value must be a single line"));
+                }
+                _ => {}
+            }
+            // systemd-sysctl and sysctl --system only read *.conf from the directories
+            if !file.starts_with('/') && (file.contains('/') || !file.ends_with(".conf")) {
+                return Err(handle.response.is_failed(
+                    request,
+                    "file must be a *.conf name under /etc/sysctl.d or an absolute path",
+                ));
+            }
+        }
+
+        Ok(EvaluatedTask {
+            action: Arc::new(SysctlAction {
+                key,
+                value: normalize_value(&value.unwrap_or_default()),
+                path: match file.starts_with('/') {
+                    true => file,
+                    false => format!("{}/{}", SYSCTL_DIR, file),
+                },
+                apply: handle.template.boolean_option_default_true(
+                    request,
+                    tm,
+                    &String::from("apply"),
+                    &self.apply,
+                )?,
+                remove,
+            }),
+            with: Arc::new(PreLogicInput::template(handle, request, tm, &self.with)?),
+            and: Arc::new(PostLogicInput::template(handle, request, tm, &self.and)?),
+        })
+    }
+}
+
+impl IsAction for SysctlAction {
+    fn dispatch(
+        &self,
+        handle: &Arc<TaskHandle>,
+        request: &Arc<TaskRequest>,
+    ) -> Result<Arc<TaskResponse>, Arc<TaskResponse>> {
+        match request.request_type {
+            TaskRequestType::Query => {
+                if handle.remote.get_os_type() != HostOSType::Linux {
+                    return Err(handle
+                        .response
+                        .is_failed(request, "the sysctl module only supports Linux"));
+                }
+                let current = handle.remote.read_file(request, &self.path)?;
+                let plan = self.plan(current.as_deref().unwrap_or(""));
+                if self.remove {
+                    return match plan {
+                        Some(_) => Ok(handle.response.needs_removal(request)),
+                        None => Ok(handle.response.is_matched(request)),
+                    };
+                }
+
+                let mut changes: Vec<Field> = Vec::new();
+                if plan.is_some() {
+                    changes.push(Field::Content);
+                }
+                if self.apply && self.get_live_value(handle, request)? != self.value {
+                    changes.push(Field::Value);
+                }
+                if changes.is_empty() {
+                    return Ok(handle.response.is_matched(request));
+                }
+                let response = handle.response.needs_modification(request, &changes);
+                let diff = match (handle.response.wants_diff(), &plan) {
+                    (true, Some(content)) => unified(
+                        &self.path,
+                        current.as_ref().map(|x| x.as_bytes()),
+                        content.as_bytes(),
+                    ),
+                    _ => None,
+                };
+                Ok(handle.response.with_diff(response, diff))
+            }
+
+            TaskRequestType::Modify => {
+                if request.changes.contains(&Field::Content) {
+                    self.write(handle, request)?;
+                }
+                if request.changes.contains(&Field::Value) {
+                    let cmd = format!(
+                        "sysctl -q -w {}",
+                        shell_quote(&format!("{}={}", self.key, self.value))
+                    );
+                    handle.remote.run_unsafe(request, &cmd, CheckRc::Checked)?;
+                }
+                Ok(handle
+                    .response
+                    .is_modified(request, request.changes.clone()))
+            }
+
+            // the running value stays as it is, there is no default to go back to
+            TaskRequestType::Remove => {
+                self.write(handle, request)?;
+                Ok(handle.response.is_removed(request))
+            }
+
+            _ => Err(handle.response.not_supported(request)),
+        }
+    }
+}
+
+impl SysctlAction {
+    fn plan(&self, current: &str) -> Option<String> {
+        match self.remove {
+            true => plan_file(current, &self.key, None),
+            false => plan_file(current, &self.key, Some(&self.value)),
+        }
+    }
+
+    fn get_live_value(
+        &self,
+        handle: &Arc<TaskHandle>,
+        request: &Arc<TaskRequest>,
+    ) -> Result<String, Arc<TaskResponse>> {
+        let cmd = format!("sysctl -n {}", self.key);
+        let result = handle.remote.run(request, &cmd, CheckRc::Unchecked)?;
+        let (rc, out) = cmd_info(&result);
+        match rc {
+            0 => Ok(normalize_value(&out)),
+            _ => Err(handle.response.is_failed(
+                request,
+                &format!("the kernel has no parameter '{}': {}", self.key, out),
+            )),
+        }
+    }
+
+    // the file is read again rather than trusting the query, other tasks of the same
+    // batch may have written their keys to it since
+
+    fn write(
+        &self,
+        handle: &Arc<TaskHandle>,
+        request: &Arc<TaskRequest>,
+    ) -> Result<(), Arc<TaskResponse>> {
+        let current = handle.remote.read_file(request, &self.path)?;
+        let content = match self.plan(current.as_deref().unwrap_or("")) {
+            Some(content) => content,
+            None => return Ok(()),
+        };
+        if content.trim().is_empty() && self.path.starts_with(SYSCTL_DIR) {
+            handle.remote.delete_file(request, &self.path)?;
+            return Ok(());
+        }
+        if let Some((dir, _)) = self.path.rsplit_once('/') {
+            handle.remote.create_directory(request, dir)?;
+        }
+        handle
+            .remote
+            .write_data(request, &content, &self.path, &None, |f| {
+                handle
+                    .remote
+                    .set_mode(request, f, "0644", Recurse::No)
+                    .map(|_| ())
+            })
+    }
+}
+
+fn is_valid_key(key: &str) -> bool {
+    !key.is_empty()
+        && key
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '/' | '_' | '-'))
+}
+
+// the kernel separates multiple numbers with tabs, files and playbooks with spaces
+
+fn normalize_value(value: &str) -> String {
+    value.split_whitespace().collect::<Vec<&str>>().join(" ")
+}
+
+// the key of a "key = value" line, a leading '-' only tells sysctl to ignore errors
+
+fn line_key(line: &str) -> Option<&str> {
+    let line = line.trim();
+    if line.is_empty() || line.starts_with('#') || line.starts_with(';') {
+        return None;
+    }
+    let (key, _) = line.split_once('=')?;
+    Some(key.trim().trim_start_matches('-'))
+}
+
+// sets key to value, or drops it when value is None, keeping every other line. The
+// first line of the key is rewritten in place, unless it already holds the value,
+// and any later ones are dropped, as they would win. None means the file is already
+// right.
+
+fn plan_file(current: &str, key: &str, value: Option<&str>) -> Option<String> {
+    let mut out = String::new();
+    let mut found = false;
+    for line in current.split_inclusive('\n') {
+        if line_key(line) != Some(key) {
+            out.push_str(line);
+            continue;
+        }
+        if let (false, Some(value)) = (found, value) {
+            let existing = line.split_once('=').map(|(_, x)| normalize_value(x));
+            match existing.as_deref() == Some(normalize_value(value).as_str()) {
+                true => out.push_str(line),
+                false => out.push_str(&format!("{} = {}\n", key, value)),
+            }
+        }
+        found = true;
+    }
+    if let (false, Some(value)) = (found, value) {
+        if !out.is_empty() && !out.ends_with('\n') {
+            out.push('\n');
+        }
+        out.push_str(&format!("{} = {}\n", key, value));
+    }
+    match out == current {
+        true => None,
+        false => Some(out),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn settings_are_added_rewritten_and_deduplicated() {
+        assert_eq!(
+            plan_file("", "vm.swappiness", Some("10")),
+            Some(String::from("vm.swappiness = 10\n"))
+        );
+        let current = "# tuning\nvm.swappiness=60\nnet.ipv4.ip_forward = 1\n-vm.swappiness = 30\n";
+        assert_eq!(
+            plan_file(current, "vm.swappiness", Some("10")),
+            Some(String::from(
+                "# tuning\nvm.swappiness = 10\nnet.ipv4.ip_forward = 1\n"
+            ))
+        );
+        assert_eq!(plan_file(current, "net.ipv4.ip_forward", Some("1")), None);
+        assert_eq!(
+            plan_file(current, "vm.swappiness", None),
+            Some(String::from("# tuning\nnet.ipv4.ip_forward = 1\n"))
+        );
+        assert_eq!(plan_file(current, "kernel.pid_max", None), None);
+    }
+
+    #[test]
+    fn matching_values_are_left_as_written() {
+        assert_eq!(
+            plan_file("vm.swappiness=10\n", "vm.swappiness", Some("10")),
+            None
+        );
+        assert_eq!(
+            plan_file(
+                "net.ipv4.tcp_rmem =4096  87380\t6291456",
+                "net.ipv4.tcp_rmem",
+                Some("4096 87380 6291456")
+            ),
+            None
+        );
+        assert_eq!(
+            plan_file("vm.swappiness=10", "kernel.pid_max", Some("65536")),
+            Some(String::from("vm.swappiness=10\nkernel.pid_max = 65536\n"))
+        );
+        assert_eq!(
+            plan_file("# tuning\nvm.swappiness=60", "vm.swappiness", Some("10")),
+            Some(String::from("# tuning\nvm.swappiness = 10\n"))
+        );
+    }
+
+    #[test]
+    fn values_and_keys() {
+        assert_eq!(
+            normalize_value("4096\t87380\t6291456\n"),
+            "4096 87380 6291456"
+        );
+        assert!(is_valid_key("net.ipv4.conf.all.rp_filter"));
+        assert!(is_valid_key("net/ipv4/conf/eth0.100/rp_filter"));
+        assert!(!is_valid_key("vm.swappiness;reboot"));
+    }
+}
@@ -17,6 +17,7 @@
 use crate::inventory::hosts::Host;
 use crate::tasks::request::TaskRequest;
 use crate::tasks::response::TaskResponse;
+use crate::util::redact::redact;
 use inline_colorization::{color_green, color_red, color_reset, color_yellow};
 use std::sync::Arc;
 
@@ -155,7 +156,11 @@ impl OutputHandler for TerminalOutputHandler {
         if (self.verbosity > 0 || response.status == TaskStatus::Failed)
             && let Some(msg) = &response.msg
         {
-            println!("  {}", msg);
+            println!("  {}", redact(msg));
+        }
+
+        if let Some(diff) = &response.diff {
+            crate::util::terminal::diff_print(&redact(diff));
         }
     }
 
@@ -183,6 +188,7 @@ impl OutputHandler for TerminalOutputHandler {
     }
 
     fn log(&self, level: LogLevel, message: &str) {
+        let message = redact(message);
         match level {
             LogLevel::Debug if self.verbosity >= 3 => println!("DEBUG: {}", message),
             LogLevel::Info if self.verbosity >= 1 => println!("INFO: {}", message),
@@ -19,7 +19,7 @@ use crate::connection::cache::ConnectionCache;
 use crate::handle::template::BlendTarget;
 use crate::inventory::hosts::Host;
 use crate::playbooks::language::{Play, Role, RoleInvocation};
-use crate::playbooks::templar::{Templar, TemplateMode};
+use crate::playbooks::templar::{Templar, TemplateEngine, TemplateMode};
 use crate::registry::list::Task;
 use crate::util::io::{directory_as_string, path_as_string};
 use crate::util::yaml::blend_variables;
@@ -37,6 +37,8 @@ use std::sync::{Arc, RwLock};
 
 pub struct PlaybookContext {
     pub verbosity: u32,
+    /// `--diff`: file modules compute a unified diff while querying.
+    pub show_diff: bool,
 
     pub playbook_path: Option<String>,
     pub playbook_directory: Option<String>,
@@ -50,6 +52,8 @@ pub struct PlaybookContext {
 
     pub task_count: usize,
     pub task: Option<String>,
+    /// the current task is marked `no_log`, see util/redact.rs
+    pub no_log: bool,
 
     seen_hosts: HashMap<String, Arc<RwLock<Host>>>,
     targetted_hosts: HashMap<String, Arc<RwLock<Host>>>,
@@ -91,12 +95,14 @@ impl PlaybookContext {
     pub fn new(parser: &CliParser) -> Self {
         let mut s = Self {
             verbosity: parser.verbosity,
+            show_diff: parser.show_diff,
             playbook_path: None,
             playbook_directory: None,
             failed_tasks: 0,
             play: None,
             role: None,
             task: None,
+            no_log: false,
             play_count: 0,
             play_index: 0,
             role_count: 0,
@@ -204,11 +210,16 @@ impl PlaybookContext {
 
     pub fn set_task(&mut self, task: &Task) {
         self.task = Some(task.get_display_name());
+        self.no_log = task.is_no_log();
     }
 
     pub fn set_play(&mut self, play: &Play) {
         self.play = Some(play.name.clone());
         self.play_count += 1;
+        self.templar
+            .write()
+            .unwrap()
+            .set_engine(play.template_engine.unwrap_or_default());
     }
 
     pub fn get_play_name(&self) -> String {
@@ -349,6 +360,23 @@ impl PlaybookContext {
             .render(template, vars, template_mode);
     }
 
+    // same as render_template but with an explicit engine rather than the one the play selected
+
+    pub fn render_template_with_engine(
+        &self,
+        template: &str,
+        host: &Arc<RwLock<Host>>,
+        blend_target: BlendTarget,
+        template_mode: TemplateMode,
+        engine: TemplateEngine,
+    ) -> Result<String, String> {
+        let vars = self.get_complete_blended_variables(host, blend_target);
+        self.templar
+            .read()
+            .unwrap()
+            .render_with_engine(template, vars, template_mode, engine)
+    }
+
     // testing conditions for truthiness works much like templating strings
 
     pub fn test_condition(
@@ -14,7 +14,9 @@
 // You should have received a copy of the GNU General Public License
 // long with this program.  If not, see <http://www.gnu.org/licenses/>.
 
+use crate::playbooks::templar::TemplateEngine;
 use crate::registry::list::Task;
+use crate::tasks::Templated;
 use serde::Deserialize;
 use std::collections::HashMap;
 
@@ -36,6 +38,9 @@ pub struct Play {
     pub tasks: Option<Vec<Task>>,
     pub handlers: Option<Vec<Task>>,
     pub batch_size: Option<usize>,
+    /// Template language for inline parameters, conditions and templates without
+    /// a recognised extension: handlebars (default) or jinja
+    pub template_engine: Option<TemplateEngine>,
     /// Auto-generate hosts in this group before running
     pub instantiate: Option<InstantiateSpec>,
 }
@@ -68,8 +73,8 @@ pub struct ProvisionSpec {
     #[serde(rename = "type")]
     pub provision_type: String,
     pub cluster: String,
-    pub memory: Option<String>,
-    pub cores: Option<String>,
+    pub memory: Option<Templated<u64>>,
+    pub cores: Option<Templated<u64>>,
     pub ostemplate: Option<String>,
     pub storage: Option<String>,
     pub rootfs_size: Option<String>,
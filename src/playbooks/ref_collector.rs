@@ -33,8 +33,12 @@ use crate::playbooks::language::Play;
 use crate::playbooks::role_tree::{
     RoleSection, RoleWalkState, resolve_role_file, resolve_template_src, walk_role_tree,
 };
-use crate::playbooks::template_refs::{referenced_variables, referenced_variables_in_value};
+use crate::playbooks::templar::TemplateEngine;
+use crate::playbooks::template_refs::{
+    referenced_variables, referenced_variables_in_value, referenced_variables_jinja,
+};
 use crate::registry::list::Task;
+use crate::util::vault::read_yaml_file;
 
 /// Per-play collected variables: what a single play references, what it defines,
 /// and the (raw, possibly templated) groups it targets. Kept per-play so the
@@ -216,12 +220,16 @@ fn template_file_refs(root: &Path, src: &str) -> BTreeSet<String> {
     let Ok(content) = fs::read_to_string(&path) else {
         return BTreeSet::new();
     };
-    referenced_variables(&content).unwrap_or_default()
+    match TemplateEngine::for_path(&path) {
+        Some(TemplateEngine::Jinja) => referenced_variables_jinja(&content).unwrap_or_default(),
+        _ => referenced_variables(&content).unwrap_or_default(),
+    }
 }
 
 // Add a vars_file's top-level keys to the `defined` set. Relative paths resolve
-// against the playbook directory (as traversal does); unreadable or unparseable
-// files are skipped — not what this diagnostic reports.
+// against the playbook directory and vault encrypted files are decrypted (as
+// traversal does); unreadable or unparseable files are skipped — not what this
+// diagnostic reports.
 fn collect_defined_from_vars_file(playbook_dir: &Path, file: &str, defined: &mut BTreeSet<String>) {
     let path = Path::new(file);
     let resolved = if path.is_absolute() {
@@ -229,7 +237,7 @@ fn collect_defined_from_vars_file(playbook_dir: &Path, file: &str, defined: &mut
     } else {
         playbook_dir.join(file)
     };
-    let Ok(source) = fs::read_to_string(&resolved) else {
+    let Ok(source) = read_yaml_file(&resolved) else {
         return;
     };
     let Ok(value) = serde_yaml::from_str::<serde_yaml::Value>(&source) else {
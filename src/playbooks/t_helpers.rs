@@ -13,6 +13,7 @@
 // You should have received a copy of the GNU General Public License
 // long with this program.  If not, see <http://www.gnu.org/licenses/>.
 
+use crate::util::secrets;
 use handlebars::{
     Context, Handlebars, Helper, HelperDef, JsonValue, RenderContext, RenderError,
     RenderErrorReason, ScopedJson, handlebars_helper,
@@ -45,6 +46,37 @@ impl HelperDef for IsDefined {
     }
 }
 
+// {{ secret "pass:infra/db" }} - fetch a value from an external secret backend,
+// see util/secrets.rs
+
+pub struct Secret;
+
+impl HelperDef for Secret {
+    fn call_inner<'reg: 'rc, 'rc>(
+        &self,
+        h: &Helper<'rc>,
+        _: &'reg Handlebars,
+        _: &'rc Context,
+        _: &mut RenderContext<'reg, 'rc>,
+    ) -> Result<ScopedJson<'rc>, RenderError> {
+        let reference = match h.params().as_slice() {
+            [param] => param.value().as_str().ok_or_else(|| {
+                RenderError::from(RenderErrorReason::Other(
+                    "secret: the reference must be a string".to_owned(),
+                ))
+            })?,
+            _ => {
+                return Err(
+                    RenderErrorReason::Other("secret: requires one parameter".to_owned()).into(),
+                );
+            }
+        };
+        let value = secrets::resolve(reference)
+            .map_err(|e| RenderError::from(RenderErrorReason::Other(e)))?;
+        Ok(ScopedJson::Derived(JsonValue::from(value)))
+    }
+}
+
 pub fn register_helpers(handlebars: &mut Handlebars) {
     // Comparison helpers
     {
@@ -107,6 +139,9 @@ pub fn register_helpers(handlebars: &mut Handlebars) {
     {
         handlebars.register_helper("isdefined", Box::new(IsDefined));
     }
+    {
+        handlebars.register_helper("secret", Box::new(Secret));
+    }
 }
 
 #[cfg(test)]
@@ -340,4 +375,19 @@ mod tests {
         let result = handlebars.render_template(template, &json!({})).unwrap();
         assert_eq!(result, "CAFÉ");
     }
+
+    #[test]
+    fn test_secret_resolves_references() {
+        let mut handlebars = Handlebars::new();
+        register_helpers(&mut handlebars);
+
+        let file = tempfile::NamedTempFile::new().unwrap();
+        std::fs::write(file.path(), "helper-secret-value\n").unwrap();
+        let template = format!("{{{{secret \"file:{}\"}}}}", file.path().display());
+        let result = handlebars.render_template(&template, &json!({})).unwrap();
+        assert_eq!(result, "helper-secret-value");
+
+        let template = "{{secret \"env:JETPACK_HELPER_SECRET_NEVER_SET\"}}";
+        assert!(handlebars.render_template(template, &json!({})).is_err());
+    }
 }
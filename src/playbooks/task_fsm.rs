@@ -555,6 +555,8 @@ fn run_task_on_host_inner(
         }
     }
 
+    let query_diff = qrc.as_ref().ok().and_then(|x| x.diff.clone());
+
     // with the query completed, what action to perform next depends on the query results
 
     let prelim_result: Result<Arc<TaskResponse>, Arc<TaskResponse>> = match qrc {
@@ -691,6 +693,13 @@ fn run_task_on_host_inner(
         },
     };
 
+    // a diff computed during the query describes the change that was just made
+
+    let prelim_result = match prelim_result {
+        Ok(x) if x.diff.is_none() => Ok(handle.response.with_diff(x, query_diff)),
+        x => x,
+    };
+
     // now that we've got a result, whether we use that result depends
     // on whether ignore_errors was set.
 
@@ -15,12 +15,17 @@
 // long with this program.  If not, see <http://www.gnu.org/licenses/>.
 
 use handlebars::Handlebars;
+use minijinja::{AutoEscape, Environment, UndefinedBehavior};
 use once_cell::sync::Lazy;
+use serde::Deserialize;
 use serde_yaml;
+use std::path::Path;
 
 use crate::playbooks::t_helpers::register_helpers;
+use crate::util::secrets;
 
-// templar contains low-level wrapping around handlebars.
+// templar contains low-level wrapping around handlebars (and minijinja, for
+// playbooks and template files written in Jinja syntax).
 // this is not used directly when evaluating templates and template
 // expressions, for this, see handle/template.rs
 
@@ -33,6 +38,46 @@ static HANDLEBARS: Lazy<Handlebars> = Lazy::new(|| {
     hb
 });
 
+static JINJA: Lazy<Environment<'static>> = Lazy::new(|| {
+    let mut env = Environment::new();
+    // same rules as handlebars above: no HTML escaping, and referencing an undefined
+    // variable is an error rather than an empty string. `default` filters and
+    // `is defined` tests still work under strict mode.
+    env.set_auto_escape_callback(|_| AutoEscape::None);
+    env.set_undefined_behavior(UndefinedBehavior::Strict);
+    env.set_keep_trailing_newline(true);
+    env.add_function("secret", |reference: &str| {
+        secrets::resolve(reference)
+            .map_err(|e| minijinja::Error::new(minijinja::ErrorKind::InvalidOperation, e))
+    });
+    env
+});
+
+// the template language used to evaluate a string. Handlebars is the default, Jinja
+// can be selected per play (template_engine: jinja) or per template file by extension
+// so that roles migrated from Ansible keep working without being rewritten.
+
+#[derive(Deserialize, PartialEq, Eq, Copy, Clone, Debug, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum TemplateEngine {
+    #[default]
+    Handlebars,
+    Jinja,
+}
+
+impl TemplateEngine {
+    /// The engine implied by a template file's extension, if any: `.j2`, `.jinja`
+    /// and `.jinja2` are Jinja, `.hb` and `.hbs` are Handlebars. Other files use
+    /// whatever engine the play selected.
+    pub fn for_path(path: &Path) -> Option<Self> {
+        match path.extension().and_then(|x| x.to_str()) {
+            Some("j2") | Some("jinja") | Some("jinja2") => Some(TemplateEngine::Jinja),
+            Some("hb") | Some("hbs") => Some(TemplateEngine::Handlebars),
+            _ => None,
+        }
+    }
+}
+
 // 'off' mode is used in a bit of a weird traversal/engine
 // situation where we need to get access to some task parameters
 // before templates are evaluated. You will notice there is no way
@@ -44,7 +89,9 @@ pub enum TemplateMode {
     Off,
 }
 
-pub struct Templar {}
+pub struct Templar {
+    engine: TemplateEngine,
+}
 
 impl Default for Templar {
     fn default() -> Self {
@@ -54,7 +101,19 @@ impl Default for Templar {
 
 impl Templar {
     pub fn new() -> Self {
-        Self {}
+        Self {
+            engine: TemplateEngine::Handlebars,
+        }
+    }
+
+    // the engine used for inline templates and conditions, set as each play starts
+
+    pub fn set_engine(&mut self, engine: TemplateEngine) {
+        self.engine = engine;
+    }
+
+    pub fn get_engine(&self) -> TemplateEngine {
+        self.engine
     }
 
     // evaluate a string
@@ -64,15 +123,33 @@ impl Templar {
         template: &str,
         data: serde_yaml::Mapping,
         template_mode: TemplateMode,
+    ) -> Result<String, String> {
+        self.render_with_engine(template, data, template_mode, self.engine)
+    }
+
+    // evaluate a string with a specific engine, used by the template module when the
+    // source file's extension picks a different engine than the play
+
+    pub fn render_with_engine(
+        &self,
+        template: &str,
+        data: serde_yaml::Mapping,
+        template_mode: TemplateMode,
+        engine: TemplateEngine,
     ) -> Result<String, String> {
         match template_mode {
             TemplateMode::Off => Ok(String::from("empty")),
             TemplateMode::Strict => {
                 let mut rendered = template.to_string();
                 for _ in 0..8 {
-                    let next = HANDLEBARS
-                        .render_template(&rendered, &data)
-                        .map_err(|y| format!("Template error: {}", y))?;
+                    let next = match engine {
+                        TemplateEngine::Handlebars => HANDLEBARS
+                            .render_template(&rendered, &data)
+                            .map_err(|y| format!("Template error: {}", y))?,
+                        TemplateEngine::Jinja => JINJA
+                            .render_str(&rendered, &data)
+                            .map_err(|y| format!("Template error: {:#}", y))?,
+                    };
                     if next == rendered || !next.contains("{{") {
                         return Ok(next);
                     }
@@ -96,7 +173,12 @@ impl Templar {
             return Ok(true);
         }
         // embed the expression in an if statement as a way to evaluate it for truth
-        let template = format!("{{{{#if {expr} }}}}true{{{{ else }}}}false{{{{/if}}}}");
+        let template = match self.engine {
+            TemplateEngine::Handlebars => {
+                format!("{{{{#if {expr} }}}}true{{{{ else }}}}false{{{{/if}}}}")
+            }
+            TemplateEngine::Jinja => format!("{{% if {expr} %}}true{{% else %}}false{{% endif %}}"),
+        };
         let result = self.render(&template, data, TemplateMode::Strict);
         match result {
             Ok(x) => {
@@ -107,7 +189,7 @@ impl Templar {
                 }
             }
             Err(y) => {
-                if y.find("Couldn't read parameter").is_some() {
+                if y.find("Couldn't read parameter").is_some() || y.contains("undefined value") {
                     Err(format!(
                         "failed to parse conditional: {}: one or more parameters may be undefined",
                         expr
@@ -142,7 +224,7 @@ mod tests {
 
         let result = templar
             .render(
-                &"{{ codex_desktop_remote_root }}/codex-app".to_string(),
+                "{{ codex_desktop_remote_root }}/codex-app",
                 data,
                 TemplateMode::Strict,
             )
@@ -153,4 +235,119 @@ mod tests {
             "/home/wings/projects/riffenvironment/projects/codex-desktop-linux/codex-app"
         );
     }
+
+    fn jinja_templar() -> Templar {
+        let mut templar = Templar::new();
+        templar.set_engine(TemplateEngine::Jinja);
+        templar
+    }
+
+    fn vars(pairs: &[(&str, &str)]) -> serde_yaml::Mapping {
+        let mut data = serde_yaml::Mapping::new();
+        for (k, v) in pairs {
+            data.insert(
+                serde_yaml::Value::String(k.to_string()),
+                serde_yaml::Value::String(v.to_string()),
+            );
+        }
+        data
+    }
+
+    #[test]
+    fn jinja_renders_filters_and_defaults() {
+        let result = jinja_templar()
+            .render(
+                "{{ user | upper }}:{{ port | default('8080') }}",
+                vars(&[("user", "wings")]),
+                TemplateMode::Strict,
+            )
+            .unwrap();
+        assert_eq!(result, "WINGS:8080");
+    }
+
+    #[test]
+    fn jinja_is_strict_about_undefined_variables() {
+        let err = jinja_templar()
+            .render("{{ missing }}", vars(&[]), TemplateMode::Strict)
+            .unwrap_err();
+        assert!(err.starts_with("Template error:"), "{}", err);
+    }
+
+    #[test]
+    fn jinja_does_not_escape_or_drop_trailing_newline() {
+        let result = jinja_templar()
+            .render("{{ x }}\n", vars(&[("x", "<a & b>")]), TemplateMode::Strict)
+            .unwrap();
+        assert_eq!(result, "<a & b>\n");
+    }
+
+    #[test]
+    fn jinja_conditions_use_jinja_expressions() {
+        let templar = jinja_templar();
+        assert!(
+            templar
+                .test_condition("x == 'a'", vars(&[("x", "a")]), TemplateMode::Strict)
+                .unwrap()
+        );
+        assert!(
+            !templar
+                .test_condition(
+                    "x is not defined",
+                    vars(&[("x", "a")]),
+                    TemplateMode::Strict
+                )
+                .unwrap()
+        );
+        assert!(
+            templar
+                .test_condition("nope", vars(&[]), TemplateMode::Strict)
+                .unwrap_err()
+                .contains("may be undefined")
+        );
+    }
+
+    #[test]
+    fn jinja_secret_function_resolves_references() {
+        let file = tempfile::NamedTempFile::new().unwrap();
+        std::fs::write(file.path(), "templar-secret-value").unwrap();
+        let result = jinja_templar()
+            .render(
+                &format!("{{{{ secret('file:{}') }}}}", file.path().display()),
+                vars(&[]),
+                TemplateMode::Strict,
+            )
+            .unwrap();
+        assert_eq!(result, "templar-secret-value");
+    }
+
+    #[test]
+    fn render_with_engine_overrides_the_configured_engine() {
+        let templar = Templar::new();
+        let result = templar
+            .render_with_engine(
+                "{{ x | lower }}",
+                vars(&[("x", "ABC")]),
+                TemplateMode::Strict,
+                TemplateEngine::Jinja,
+            )
+            .unwrap();
+        assert_eq!(result, "abc");
+    }
+
+    #[test]
+    fn engine_for_path_uses_extension() {
+        assert_eq!(
+            TemplateEngine::for_path(Path::new("templates/nginx.conf.j2")),
+            Some(TemplateEngine::Jinja)
+        );
+        assert_eq!(
+            TemplateEngine::for_path(Path::new("a.jinja2")),
+            Some(TemplateEngine::Jinja)
+        );
+        assert_eq!(
+            TemplateEngine::for_path(Path::new("redis.conf.hb")),
+            Some(TemplateEngine::Handlebars)
+        );
+        assert_eq!(TemplateEngine::for_path(Path::new("motd")), None);
+    }
 }
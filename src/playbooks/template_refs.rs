@@ -51,6 +51,20 @@ pub fn referenced_variables(template: &str) -> Result<BTreeSet<String>, String>
     Ok(out)
 }
 
+/// The top-level data-variable names a Jinja template references.
+///
+/// Jinja counterpart of [`referenced_variables`] for `.j2` template files, using
+/// minijinja's own parser for the same reason the Handlebars version reuses the
+/// handlebars one. Names assigned inside the template (`{% set %}`, loop
+/// variables) are not reported; nested paths collapse to their top-level segment.
+pub fn referenced_variables_jinja(template: &str) -> Result<BTreeSet<String>, String> {
+    let env = minijinja::Environment::new();
+    let compiled = env
+        .template_from_str(template)
+        .map_err(|e| format!("Template error: {}", e))?;
+    Ok(compiled.undeclared_variables(false).into_iter().collect())
+}
+
 /// Every variable referenced anywhere inside a parsed YAML value.
 ///
 /// Walks the value recursively — through sequences, mappings, and `!tagged`
@@ -187,12 +201,11 @@ fn walk_decorator(dt: &DecoratorTemplate, locals: &BTreeSet<String>, out: &mut B
 fn collect_param(param: &Parameter, locals: &BTreeSet<String>, out: &mut BTreeSet<String>) {
     match param {
         Parameter::Name(_) | Parameter::Path(_) => {
-            if let Some(raw) = param.as_name() {
-                if let Some(top) = top_level_variable(raw) {
-                    if !locals.contains(&top) {
-                        out.insert(top);
-                    }
-                }
+            if let Some(raw) = param.as_name()
+                && let Some(top) = top_level_variable(raw)
+                && !locals.contains(&top)
+            {
+                out.insert(top);
             }
         }
         Parameter::Subexpression(sub) => walk_subexpression(sub, locals, out),
@@ -243,7 +256,7 @@ fn top_level_variable(raw: &str) -> Option<String> {
         .strip_prefix("this.")
         .unwrap_or(rest)
         .trim_start_matches('.');
-    let seg = rest.split(|c| c == '.' || c == '/').next()?.trim();
+    let seg = rest.split(['.', '/']).next()?.trim();
     if seg.is_empty() {
         None
     } else {
@@ -253,7 +266,7 @@ fn top_level_variable(raw: &str) -> Option<String> {
 
 #[cfg(test)]
 mod tests {
-    use super::referenced_variables;
+    use super::{referenced_variables, referenced_variables_jinja};
     use std::collections::BTreeSet;
 
     fn refs(template: &str) -> BTreeSet<String> {
@@ -424,4 +437,14 @@ mod tests {
             serde_yaml::from_str("ok: \"{{ good }}\"\nbad: \"{{ oops \"").unwrap();
         assert_eq!(referenced_variables_in_value(&value), set(&["good"]));
     }
+
+    #[test]
+    fn jinja_templates_report_undeclared_top_level_names() {
+        let found = referenced_variables_jinja(
+            "{% for u in users %}{{ u.name }}{% endfor %}{{ port | default(80) }}{% set x = 1 %}{{ x }}",
+        )
+        .unwrap();
+        let expected: BTreeSet<String> = ["port", "users"].iter().map(|s| s.to_string()).collect();
+        assert_eq!(found, expected);
+    }
 }
@@ -34,6 +34,8 @@ use crate::playbooks::visitor::PlaybookVisitor;
 use crate::provisioners::{ProvisionConfig, ensure_host_provisioned};
 use crate::registry::list::Task;
 use crate::util::io::{directory_as_string, jet_file_open};
+use crate::util::redact::redact_for_task;
+use crate::util::vault::{decrypt_mapping, read_yaml_file};
 use crate::util::yaml::{blend_variables, show_yaml_error_in_context};
 use std::collections::{HashMap, HashSet};
 use std::env;
@@ -832,19 +834,13 @@ fn async_handle_batch(
                 .unwrap()
                 .get_connection(&run_state.context, host);
 
-            let connection = match connection_result {
-                Ok(conn) => conn,
-                Err(e) => {
-                    // Connection failed — withdraw from all barriers and report
-                    async_ctx.withdraw_from(0);
-                    run_state.context.write().unwrap().fail_host(host);
-                    let _ = host_tx.send(HostEvent::HostFailed {
-                        host_idx,
-                        error: e.clone(),
-                    });
-                    return 1;
-                }
-            };
+            if let Err(e) = connection_result {
+                // Connection failed — withdraw from all barriers and report
+                async_ctx.withdraw_from(0);
+                run_state.context.write().unwrap().fail_host(host);
+                let _ = host_tx.send(HostEvent::HostFailed { host_idx, error: e });
+                return 1;
+            }
 
             // Run each task sequentially on this host
             for (task_idx, task) in task_refs.iter().enumerate() {
@@ -884,6 +880,22 @@ fn async_handle_batch(
                     task_name: task_name.clone(),
                 });
 
+                // !reboot swaps the cached connection for a new one, pick up whatever is current
+                let connection = match run_state
+                    .connection_factory
+                    .read()
+                    .unwrap()
+                    .get_connection(&run_state.context, host)
+                {
+                    Ok(conn) => conn,
+                    Err(e) => {
+                        async_ctx.withdraw_from(task_idx);
+                        run_state.context.write().unwrap().fail_host(host);
+                        let _ = host_tx.send(HostEvent::HostFailed { host_idx, error: e });
+                        return 1;
+                    }
+                };
+
                 // Run the task on this host
                 match async_run_single_task(run_state, &connection, host, play, task) {
                     Ok(response) => {
@@ -896,7 +908,10 @@ fn async_handle_batch(
                             TaskStatus::IsSkipped => TaskDisplayStatus::Skipped,
                             _ => TaskDisplayStatus::Ok,
                         };
-                        let output = response.msg.clone();
+                        let output = response
+                            .msg
+                            .as_ref()
+                            .map(|msg| redact_for_task(msg, task.is_no_log()));
                         let _ = host_tx.send(HostEvent::TaskCompleted {
                             host_idx,
                             task_name,
@@ -907,7 +922,8 @@ fn async_handle_batch(
                     Err(response) => {
                         let error = response
                             .msg
-                            .clone()
+                            .as_ref()
+                            .map(|msg| redact_for_task(msg, task.is_no_log()))
                             .unwrap_or_else(|| "unknown error".to_string());
 
                         let _ = host_tx.send(HostEvent::TaskFailed {
@@ -1481,13 +1497,13 @@ fn handle_instantiate(
         if let Some(ref v) = spec.provision.memory {
             provision.insert(
                 serde_yaml::Value::String("memory".to_string()),
-                serde_yaml::Value::String(v.clone()),
+                serde_yaml::to_value(v).map_err(|e| e.to_string())?,
             );
         }
         if let Some(ref v) = spec.provision.cores {
             provision.insert(
                 serde_yaml::Value::String("cores".to_string()),
-                serde_yaml::Value::String(v.clone()),
+                serde_yaml::to_value(v).map_err(|e| e.to_string())?,
             );
         }
         if let Some(ref v) = spec.provision.storage {
@@ -1769,35 +1785,38 @@ fn load_vars_into_context(run_state: &Arc<RunState>, play: &Play) -> Result<(),
 
     if let Some(vars) = play.vars.as_ref() {
         // vars are inline variables that are loaded at maximum precedence
-        blend_variables(
-            &mut ctx_vars_storage,
-            serde_yaml::Value::Mapping(vars.clone()),
-        );
+        let mut vars = vars.clone();
+        decrypt_mapping(&mut vars)?;
+        blend_variables(&mut ctx_vars_storage, serde_yaml::Value::Mapping(vars));
     }
 
     if let Some(vars_files) = play.vars_files.as_ref() {
-        // vars_files are paths to YAML files that are loaded at maximum precedence
+        // vars_files are paths to YAML files that are loaded at maximum precedence.
+        // they may be vault encrypted as a whole or hold individually encrypted values.
         for pathname in vars_files {
             let path = Path::new(&pathname);
-            let vars_file = jet_file_open(path)?;
+            let vars_file = read_yaml_file(path)?;
             let parsed: Result<serde_yaml::Mapping, serde_yaml::Error> =
-                serde_yaml::from_reader(vars_file);
-            let mapping = match parsed {
+                serde_yaml::from_str(&vars_file);
+            let mut mapping = match parsed {
                 Ok(mapping) => mapping,
                 Err(e) => {
                     show_yaml_error_in_context(&e, path);
                     return Err("edit the file and try again?".to_string());
                 }
             };
+            decrypt_mapping(&mut mapping).map_err(|e| format!("{}: {}", path.display(), e))?;
             blend_variables(&mut ctx_vars_storage, serde_yaml::Value::Mapping(mapping));
         }
     }
 
     if let Some(defaults) = play.defaults.as_ref() {
         // defaults works like 'vars' but has the lowest precedence
+        let mut defaults = defaults.clone();
+        decrypt_mapping(&mut defaults)?;
         blend_variables(
             &mut ctx_defaults_storage,
-            serde_yaml::Value::Mapping(defaults.clone()),
+            serde_yaml::Value::Mapping(defaults),
         );
     }
 
@@ -1855,6 +1874,7 @@ mod target_groups_tests {
             tasks: None,
             handlers: None,
             batch_size: None,
+            template_engine: None,
             instantiate: None,
         }
     }
@@ -2154,6 +2174,7 @@ mod env_axis_groups_composition_tests {
             tasks: None,
             handlers: None,
             batch_size: None,
+            template_engine: None,
             instantiate: None,
         }
     }
@@ -20,6 +20,7 @@ use crate::output::OutputHandlerRef;
 use crate::playbooks::context::PlaybookContext;
 use crate::playbooks::traversal::HandlerMode;
 use crate::tasks::*;
+use crate::util::redact::{redact, redact_for_task};
 use chrono::prelude::*;
 use guid_create::GUID;
 use inline_colorization::{
@@ -65,6 +66,7 @@ pub struct LogData {
     pub cmd_out: Option<String>,
     pub task_status: Option<String>,
     pub host: Option<String>,
+    pub diff: Option<String>,
     pub summary: Option<serde_json::map::Map<String, serde_json::Value>>,
 }
 
@@ -105,6 +107,13 @@ impl PlaybookVisitor {
         self.output_handler.is_some()
     }
 
+    // commands, output and messages can carry secrets interpolated from the
+    // inventory, so everything task-specific goes through here before it is
+    // printed or logged. Don't call this while holding the context write lock.
+    fn scrub(&self, context: &Arc<RwLock<PlaybookContext>>, text: &str) -> String {
+        redact_for_task(text, context.read().unwrap().no_log)
+    }
+
     pub fn log_entry(&self, event: &str, context: Arc<RwLock<PlaybookContext>>) -> LogData {
         let ctx = context.read().unwrap();
         LogData {
@@ -119,6 +128,7 @@ impl PlaybookVisitor {
             cmd_out: None,
             task_status: None,
             host: None,
+            diff: None,
             summary: None,
         }
     }
@@ -156,14 +166,16 @@ impl PlaybookVisitor {
         if log.task.is_some() {
             obj.insert(String::from("task_ct"), json!(log.task_ct.unwrap()));
         }
-        if log.cmd.is_some() {
-            obj.insert(String::from("cmd"), json!(log.cmd.clone().unwrap()));
+        // cmd and cmd_out are usually scrubbed by the caller already, but nothing
+        // unredacted should ever reach the log file
+        if let Some(cmd) = &log.cmd {
+            obj.insert(String::from("cmd"), json!(redact(cmd)));
         }
         if let Some(cmd_rc) = log.cmd_rc {
             obj.insert(String::from("cmd_rc"), json!(cmd_rc));
         }
-        if log.cmd_out.is_some() {
-            obj.insert(String::from("cmd_out"), json!(log.cmd_out.clone().unwrap()));
+        if let Some(cmd_out) = &log.cmd_out {
+            obj.insert(String::from("cmd_out"), json!(redact(cmd_out)));
         }
         if log.task_status.is_some() {
             obj.insert(
@@ -174,6 +186,9 @@ impl PlaybookVisitor {
         if log.host.is_some() {
             obj.insert(String::from("host"), json!(log.host.clone().unwrap()));
         }
+        if let Some(diff) = &log.diff {
+            obj.insert(String::from("diff"), json!(redact(diff)));
+        }
 
         if log.summary.is_some() {
             obj.insert(String::from("summary"), json!(log.summary.clone().unwrap()));
@@ -203,14 +218,14 @@ impl PlaybookVisitor {
     }
 
     // used by the echo module
-    pub fn debug_host(&self, host: &Arc<RwLock<Host>>, message: &String) {
+    pub fn debug_host(&self, host: &Arc<RwLock<Host>>, message: &str) {
         if self.is_quiet() {
             return;
         }
         println!(
             "{color_cyan}  ..... {} : {}{color_reset}",
             host.read().unwrap().name,
-            message
+            redact(message)
         );
     }
 
@@ -442,9 +457,14 @@ impl PlaybookVisitor {
                             .map(|x| format!("{:?}", x))
                             .collect();
                         let change_str = changes2.join(",");
+                        // a module may say more about the change, e.g. !replace's count
+                        let note = match &task_response.msg {
+                            Some(msg) => format!(": {}", redact_for_task(msg, context2.no_log)),
+                            None => String::new(),
+                        };
                         println!(
-                            "{color_blue}✓ {} => modified ({}){color_reset}",
-                            &host2.name, change_str
+                            "{color_blue}✓ {} => modified ({}){}{color_reset}",
+                            &host2.name, change_str, note
                         );
                     }
                     context2.increment_modified_for_host(&host2.name);
@@ -490,6 +510,7 @@ impl PlaybookVisitor {
         let mut log_entry = self.log_entry(&String::from("TASK_STATUS"), Arc::clone(context));
         log_entry.host = Some(host2.name.clone());
         log_entry.task_status = Some(format!("{:?}", &task_response.status));
+        log_entry.diff = self.on_host_task_result(context, &host2, task_response);
         self.log(&log_entry);
     }
 
@@ -527,9 +548,14 @@ impl PlaybookVisitor {
                             .map(|x| format!("{:?}", x))
                             .collect();
                         let change_str = changes2.join(",");
+                        // a module may say more about the change, e.g. !replace's count
+                        let note = match &task_response.msg {
+                            Some(msg) => format!(": {}", redact_for_task(msg, context2.no_log)),
+                            None => String::new(),
+                        };
                         println!(
-                            "{color_blue}✓ {} => would modify ({}) {color_reset}",
-                            &host2.name, change_str
+                            "{color_blue}✓ {} => would modify ({}){} {color_reset}",
+                            &host2.name, change_str, note
                         );
                     }
                     context2.increment_modified_for_host(&host2.name);
@@ -575,9 +601,54 @@ impl PlaybookVisitor {
         let mut log_entry = self.log_entry(&String::from("TASK_CHECK_STATUS"), Arc::clone(context));
         log_entry.host = Some(host2.name.clone());
         log_entry.task_status = Some(format!("{:?}", &task_response.status));
+        log_entry.diff = self.on_host_task_result(context, &host2, task_response);
         self.log(&log_entry);
     }
 
+    // shared tail of on_host_task_ok and on_host_task_check_ok: prints the --diff output
+    // (or hands the result to the output handler) and returns the scrubbed diff for the log
+
+    fn on_host_task_result(
+        &self,
+        context: &Arc<RwLock<PlaybookContext>>,
+        host: &Host,
+        task_response: &Arc<TaskResponse>,
+    ) -> Option<String> {
+        let diff = task_response.diff.as_ref().map(|d| self.scrub(context, d));
+
+        if let Some(ref h) = self.output_handler {
+            // the visitor only sees the final response, so the request passed along
+            // describes the leg that produced it rather than the original object
+            let request = TaskRequest {
+                request_type: match task_response.status {
+                    TaskStatus::IsCreated | TaskStatus::NeedsCreation => TaskRequestType::Create,
+                    TaskStatus::IsRemoved | TaskStatus::NeedsRemoval => TaskRequestType::Remove,
+                    TaskStatus::IsModified | TaskStatus::NeedsModification => {
+                        TaskRequestType::Modify
+                    }
+                    TaskStatus::IsExecuted | TaskStatus::NeedsExecution => TaskRequestType::Execute,
+                    TaskStatus::IsPassive | TaskStatus::NeedsPassive => TaskRequestType::Passive,
+                    _ => TaskRequestType::Query,
+                },
+                changes: task_response.changes.clone(),
+                sudo_details: None,
+            };
+            let response = TaskResponse {
+                status: task_response.status.clone(),
+                changes: task_response.changes.clone(),
+                msg: task_response.msg.as_ref().map(|m| self.scrub(context, m)),
+                command_result: Arc::clone(&task_response.command_result),
+                with: Arc::clone(&task_response.with),
+                and: Arc::clone(&task_response.and),
+                diff: diff.clone(),
+            };
+            h.on_task_host_result(host, &request, &response);
+        } else if let Some(d) = diff.as_ref() {
+            crate::util::terminal::diff_print(d);
+        }
+        diff
+    }
+
     pub fn on_host_task_retry(
         &self,
         _context: &Arc<RwLock<PlaybookContext>>,
@@ -609,15 +680,17 @@ impl PlaybookVisitor {
             if task_response.command_result.is_some() {
                 {
                     let cmd_result = task_response.command_result.as_ref().as_ref().unwrap();
+                    let cmd = self.scrub(context, &cmd_result.cmd);
+                    let out = self.scrub(context, &cmd_result.out);
                     let _lock = context.write().unwrap();
                     if !quiet {
                         println!("{color_red}! {} => failed", host2.name);
-                        println!("    cmd: {}", cmd_result.cmd);
-                        println!("    out: {}", cmd_result.out);
+                        println!("    cmd: {}", cmd);
+                        println!("    out: {}", out);
                         println!("    rc: {}{color_reset}", cmd_result.rc);
                     }
-                    log_entry.cmd = Some(cmd_result.cmd.clone());
-                    log_entry.cmd_out = Some(cmd_result.out.clone());
+                    log_entry.cmd = Some(cmd);
+                    log_entry.cmd_out = Some(out);
                     log_entry.cmd_rc = Some(cmd_result.rc);
                 }
             } else {
@@ -625,7 +698,7 @@ impl PlaybookVisitor {
                     println!(
                         "{color_red}! error: {}: {}{color_reset}",
                         host2.name,
-                        msg.as_ref().unwrap()
+                        self.scrub(context, msg.as_ref().unwrap())
                     );
                 }
             }
@@ -700,7 +773,11 @@ impl PlaybookVisitor {
         }
         let host2 = host.read().unwrap();
         if context.read().unwrap().verbosity > 0 {
-            println!("{color_blue}! {} => exec: {}", host2.name, cmd);
+            println!(
+                "{color_blue}! {} => exec: {}",
+                host2.name,
+                self.scrub(context, cmd)
+            );
         }
     }
 
@@ -713,10 +790,12 @@ impl PlaybookVisitor {
         let host2 = host.read().unwrap();
         let cmd_result = result.as_ref().as_ref().expect("missing command result");
         if context.read().unwrap().verbosity > 2 {
+            let cmd = self.scrub(context, &cmd_result.cmd);
+            let out = self.scrub(context, &cmd_result.out);
             let _ctx2 = context.write().unwrap(); // lock for multi-line output
             println!("{color_blue}! {} ... command ok", host2.name);
-            println!("    cmd: {}", cmd_result.cmd);
-            println!("    out: {}", cmd_result.out.clone());
+            println!("    cmd: {}", cmd);
+            println!("    out: {}", out);
             println!("    rc: {}{color_reset}", cmd_result.rc);
         }
     }
@@ -730,10 +809,12 @@ impl PlaybookVisitor {
         let host2 = host.read().expect("context read");
         let cmd_result = result.as_ref().as_ref().expect("missing command result");
         if context.read().unwrap().verbosity > 2 {
+            let cmd = self.scrub(context, &cmd_result.cmd);
+            let out = self.scrub(context, &cmd_result.out);
             let _ctx2 = context.write().unwrap(); // lock for multi-line output
             println!("{color_red}! {} ... command failed", host2.name);
-            println!("    cmd: {}", cmd_result.cmd);
-            println!("    out: {}", cmd_result.out.clone());
+            println!("    cmd: {}", cmd);
+            println!("    out: {}", out);
             println!("    rc: {}{color_reset}", cmd_result.rc);
         }
     }
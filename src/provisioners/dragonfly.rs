@@ -8,6 +8,7 @@
 // All HTTP runs on a current-thread tokio runtime via block_on, mirroring the
 // proxmox provisioners. Auth is a bearer token; the API lives under /api.
 
+use crate::util::secrets::SecretSource;
 use serde::{Deserialize, Serialize};
 use std::time::Duration;
 
@@ -153,13 +154,16 @@ fn normalize_mac(mac: &str) -> String {
 
 impl DragonflyClient {
     /// Build a client from blended inventory vars. Returns None unless both
-    /// dragonfly_api_url and dragonfly_api_token are set, so the integration is
-    /// strictly opt-in.
-    pub fn try_from_vars(vars: &serde_yaml::Mapping) -> Option<Self> {
-        Some(Self {
-            base_url: var(vars, "dragonfly_api_url")?,
-            token: var(vars, "dragonfly_api_token")?,
-        })
+    /// dragonfly_api_url and a dragonfly_api_token (inline, or via
+    /// `_file`/`_env`/`_ref`) are set, so the integration is strictly opt-in.
+    pub fn try_from_vars(vars: &serde_yaml::Mapping) -> Result<Option<Self>, String> {
+        let base_url = match var(vars, "dragonfly_api_url") {
+            Some(url) => url,
+            None => return Ok(None),
+        };
+        let token =
+            SecretSource::from_vars(vars, "dragonfly_api_token").resolve("dragonfly_api_token")?;
+        Ok(token.map(|token| Self { base_url, token }))
     }
 
     /// Pre-create (or update) a machine via the admin endpoint. Upsert by MAC:
@@ -435,7 +439,7 @@ pub(crate) mod tests {
             if reader.read_line(&mut header).unwrap() == 0 {
                 break;
             }
-            let trimmed = header.trim_end_matches(|c| c == '\r' || c == '\n');
+            let trimmed = header.trim_end_matches(['\r', '\n']);
             if trimmed.is_empty() {
                 break;
             }
@@ -473,17 +477,17 @@ pub(crate) mod tests {
     #[test]
     fn try_from_vars_is_opt_in() {
         let mut vars = serde_yaml::Mapping::new();
-        assert!(DragonflyClient::try_from_vars(&vars).is_none());
+        assert!(DragonflyClient::try_from_vars(&vars).unwrap().is_none());
         vars.insert(
             serde_yaml::Value::String("dragonfly_api_url".into()),
             serde_yaml::Value::String("http://dragonfly".into()),
         );
-        assert!(DragonflyClient::try_from_vars(&vars).is_none()); // still no token
+        assert!(DragonflyClient::try_from_vars(&vars).unwrap().is_none()); // still no token
         vars.insert(
             serde_yaml::Value::String("dragonfly_api_token".into()),
             serde_yaml::Value::String("df_x".into()),
         );
-        assert!(DragonflyClient::try_from_vars(&vars).is_some());
+        assert!(DragonflyClient::try_from_vars(&vars).unwrap().is_some());
     }
 
     #[test]
@@ -39,6 +39,7 @@ pub mod proxmox_vm;
 
 use crate::dns::DnsConfig;
 use crate::inventory::inventory::Inventory;
+use crate::tasks::Templated;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::sync::Arc;
@@ -77,11 +78,11 @@ pub struct ProvisionConfig {
     /// VM ID (optional - auto-assigned if not specified)
     pub vmid: Option<String>,
 
-    /// Memory in MB
-    pub memory: Option<String>,
+    /// Memory in MB, a number or a template rendering to one
+    pub memory: Option<Templated<u64>>,
 
-    /// Number of CPU cores
-    pub cores: Option<String>,
+    /// Number of CPU cores, a number or a template rendering to one
+    pub cores: Option<Templated<u64>>,
 
     /// OS template path (for containers)
     pub ostemplate: Option<String>,
@@ -115,17 +116,17 @@ pub struct ProvisionConfig {
     /// SSH user for the provisioned host
     pub ssh_user: Option<String>,
 
-    /// Run as unprivileged container
-    pub unprivileged: Option<String>,
+    /// Run as unprivileged container, a boolean or a template rendering to one
+    pub unprivileged: Option<Templated<bool>>,
 
-    /// Start container after creation
-    pub start_on_create: Option<String>,
+    /// Start container after creation, a boolean or a template rendering to one
+    pub start_on_create: Option<Templated<bool>>,
 
     /// Additional features (nesting, etc.)
     pub features: Option<String>,
 
     /// Enable TUN device (for VPN software like Tailscale, WireGuard)
-    pub tun: Option<String>,
+    pub tun: Option<Templated<bool>>,
 
     /// DNS nameservers (space-separated, e.g. "1.1.1.1 8.8.8.8")
     pub nameserver: Option<String>,
@@ -558,8 +559,8 @@ cores: "2"
         assert_eq!(config.provision_type, "proxmox_lxc");
         assert_eq!(config.cluster, "hypervisor1");
         assert_eq!(config.hostname, Some("testhost".to_string()));
-        assert_eq!(config.memory, Some("1024".to_string()));
-        assert_eq!(config.cores, Some("2".to_string()));
+        assert_eq!(config.memory, Some(Templated::Template("1024".to_string())));
+        assert_eq!(config.cores, Some(Templated::Template("2".to_string())));
         assert!(config.extra.is_empty());
     }
 
@@ -657,6 +658,41 @@ tags: "production;database"
         );
     }
 
+    #[test]
+    fn test_provision_config_native_scalars() {
+        let yaml = r#"
+type: proxmox_lxc
+cluster: hypervisor1
+memory: 1536
+cores: "{{ cores }}"
+unprivileged: false
+tun: "{{ vpn }}"
+"#;
+        let config: ProvisionConfig = serde_yaml::from_str(yaml).unwrap();
+        assert_eq!(config.memory, Some(Templated::Value(1536)));
+        assert_eq!(
+            config.cores,
+            Some(Templated::Template("{{ cores }}".to_string()))
+        );
+        assert_eq!(config.unprivileged, Some(Templated::Value(false)));
+        assert_eq!(
+            config.tun,
+            Some(Templated::Template("{{ vpn }}".to_string()))
+        );
+
+        let err = serde_yaml::from_str::<ProvisionConfig>(
+            "type: proxmox_lxc\ncluster: hypervisor1\nmemory: true\n",
+        )
+        .unwrap_err();
+        assert!(err.to_string().contains("expected an integer"), "{}", err);
+
+        let err = serde_yaml::from_str::<ProvisionConfig>(
+            "type: proxmox_lxc\ncluster: hypervisor1\nstart_on_create: 1\n",
+        )
+        .unwrap_err();
+        assert!(err.to_string().contains("expected a boolean"), "{}", err);
+    }
+
     #[test]
     fn test_provision_config_with_all_standard_fields() {
         let yaml = r#"
@@ -682,8 +718,8 @@ mp0: "local-lvm:50,mp=/mnt/extra"
         assert_eq!(config.cluster, "hypervisor1");
         assert_eq!(config.hostname, Some("fulltest".to_string()));
         assert_eq!(config.vmid, Some("100".to_string()));
-        assert_eq!(config.memory, Some("2048".to_string()));
-        assert_eq!(config.cores, Some("4".to_string()));
+        assert_eq!(config.memory, Some(Templated::Template("2048".to_string())));
+        assert_eq!(config.cores, Some(Templated::Template("4".to_string())));
         assert_eq!(
             config.ostemplate,
             Some("local:vztmpl/debian-13-standard.tar.zst".to_string())
@@ -695,8 +731,14 @@ mp0: "local-lvm:50,mp=/mnt/extra"
             Some("name=eth0,bridge=vmbr0,ip=10.0.0.100/24,gw=10.0.0.1".to_string())
         );
         assert_eq!(config.password, Some("secret".to_string()));
-        assert_eq!(config.unprivileged, Some("true".to_string()));
-        assert_eq!(config.start_on_create, Some("true".to_string()));
+        assert_eq!(
+            config.unprivileged,
+            Some(Templated::Template("true".to_string()))
+        );
+        assert_eq!(
+            config.start_on_create,
+            Some(Templated::Template("true".to_string()))
+        );
         assert_eq!(config.features, Some("nesting=1".to_string()));
         assert_eq!(config.nameserver, Some("1.1.1.1 8.8.8.8".to_string()));
         assert_eq!(config.extra.len(), 1);
@@ -22,6 +22,9 @@
 use crate::inventory::inventory::Inventory;
 use crate::playbooks::templar::{Templar, TemplateMode};
 use crate::provisioners::{ProvisionConfig, ProvisionResult, Provisioner};
+use crate::tasks::Templated;
+use crate::tasks::templated::TemplatedScalar;
+use crate::util::secrets::SecretSource;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::error::Error as StdError;
@@ -133,6 +136,25 @@ impl ProxmoxLxcProvisioner {
         }
     }
 
+    /// Template an optional number or boolean, failing when the result is not one
+    fn template_typed<T: TemplatedScalar>(
+        &self,
+        templar: &Templar,
+        field: &str,
+        value: &Option<Templated<T>>,
+        vars: &serde_yaml::Mapping,
+    ) -> Result<Option<Templated<T>>, String> {
+        match value {
+            Some(Templated::Template(t)) => {
+                let rendered = self.template_string(templar, t, vars)?;
+                Ok(Some(Templated::Value(Templated::<T>::parse_rendered(
+                    field, &rendered,
+                )?)))
+            }
+            other => Ok(other.clone()),
+        }
+    }
+
     /// Template all fields in a ProvisionConfig using host variables
     fn template_config(
         &self,
@@ -149,8 +171,8 @@ impl ProxmoxLxcProvisioner {
             hostname: self.template_option(&templar, &config.hostname, vars)?,
             ip: self.template_option(&templar, &config.ip, vars)?,
             vmid: self.template_option(&templar, &config.vmid, vars)?,
-            memory: self.template_option(&templar, &config.memory, vars)?,
-            cores: self.template_option(&templar, &config.cores, vars)?,
+            memory: self.template_typed(&templar, "memory", &config.memory, vars)?,
+            cores: self.template_typed(&templar, "cores", &config.cores, vars)?,
             ostemplate: self.template_option(&templar, &config.ostemplate, vars)?,
             fetch: self.template_option(&templar, &config.fetch, vars)?,
             storage: self.template_option(&templar, &config.storage, vars)?,
@@ -162,10 +184,20 @@ impl ProxmoxLxcProvisioner {
             password: self.template_option(&templar, &config.password, vars)?,
             authorized_keys: self.template_option(&templar, &config.authorized_keys, vars)?,
             ssh_user: self.template_option(&templar, &config.ssh_user, vars)?,
-            unprivileged: self.template_option(&templar, &config.unprivileged, vars)?,
-            start_on_create: self.template_option(&templar, &config.start_on_create, vars)?,
+            unprivileged: self.template_typed(
+                &templar,
+                "unprivileged",
+                &config.unprivileged,
+                vars,
+            )?,
+            start_on_create: self.template_typed(
+                &templar,
+                "start_on_create",
+                &config.start_on_create,
+                vars,
+            )?,
             features: self.template_option(&templar, &config.features, vars)?,
-            tun: self.template_option(&templar, &config.tun, vars)?,
+            tun: self.template_typed(&templar, "tun", &config.tun, vars)?,
             nameserver: self.template_option(&templar, &config.nameserver, vars)?,
             // Wait options are not templated (they're booleans/integers)
             wait_for_host: config.wait_for_host,
@@ -232,46 +264,43 @@ impl ProxmoxLxcProvisioner {
             .and_then(|v| v.as_str())
             .map(|s| s.to_string());
 
-        let api_token_secret = vars
-            .get("proxmox_api_token_secret")
-            .and_then(|v| v.as_str())
-            .map(|s| s.to_string());
-
-        let auth =
-            if let (Some(token_id), Some(token_secret)) = (api_token_id, api_token_secret) {
-                // Use token auth
-                ProxmoxAuth::Token {
-                    token_id,
-                    token_secret,
-                }
-            } else {
-                // Fall back to password auth
-                let username = vars
-                    .get("proxmox_api_user")
-                    .and_then(|v| v.as_str())
-                    .map(|s| s.to_string())
-                    .ok_or_else(|| {
-                        format!(
-                            "Cluster host '{}' missing auth credentials. Need either:\n  \
-                     - proxmox_api_token_id + proxmox_api_token_secret (recommended), or\n  \
-                     - proxmox_api_user + proxmox_api_password",
-                            config.cluster
-                        )
-                    })?;
+        let api_token_secret = SecretSource::from_vars(&vars, "proxmox_api_token_secret")
+            .resolve("proxmox_api_token_secret")?;
 
-                let password = vars.get("proxmox_api_password")
+        let auth = if let (Some(token_id), Some(token_secret)) = (api_token_id, api_token_secret) {
+            // Use token auth
+            ProxmoxAuth::Token {
+                token_id,
+                token_secret,
+            }
+        } else {
+            // Fall back to password auth
+            let username = vars
+                .get("proxmox_api_user")
                 .and_then(|v| v.as_str())
                 .map(|s| s.to_string())
-                .ok_or_else(|| format!(
-                    "Cluster host '{}' has proxmox_api_user but missing proxmox_api_password",
-                    config.cluster
-                ))?;
-
-                // Get ticket for password auth
-                let (ticket, csrf_token) =
-                    self.get_password_ticket(&api_host, &username, &password)?;
-                ProxmoxAuth::Password { ticket, csrf_token }
-            };
+                .ok_or_else(|| {
+                    format!(
+                        "Cluster host '{}' missing auth credentials. Need either:\n  \
+                     - proxmox_api_token_id + proxmox_api_token_secret (recommended), or\n  \
+                     - proxmox_api_user + proxmox_api_password",
+                        config.cluster
+                    )
+                })?;
+
+            let password = SecretSource::from_vars(&vars, "proxmox_api_password")
+                .resolve("proxmox_api_password")?
+                .ok_or_else(|| {
+                    format!(
+                        "Cluster host '{}' has proxmox_api_user but missing proxmox_api_password",
+                        config.cluster
+                    )
+                })?;
+
+            // Get ticket for password auth
+            let (ticket, csrf_token) = self.get_password_ticket(&api_host, &username, &password)?;
+            ProxmoxAuth::Password { ticket, csrf_token }
+        };
 
         Ok(ClusterConnection {
             api_host,
@@ -958,19 +987,17 @@ impl ProxmoxLxcProvisioner {
             }
 
             // Memory (default 512MB)
-            let memory = config
-                .memory
-                .as_ref()
-                .and_then(|m| m.parse::<u64>().ok())
-                .unwrap_or(512);
+            let memory = match config.memory {
+                Some(Templated::Value(memory)) => memory,
+                _ => 512,
+            };
             params.insert("memory".to_string(), memory.to_string());
 
             // Cores (default 1)
-            let cores = config
-                .cores
-                .as_ref()
-                .and_then(|c| c.parse::<u64>().ok())
-                .unwrap_or(1);
+            let cores = match config.cores {
+                Some(Templated::Value(cores)) => cores,
+                _ => 1,
+            };
             params.insert("cores".to_string(), cores.to_string());
 
             // Storage and rootfs
@@ -981,22 +1008,20 @@ impl ProxmoxLxcProvisioner {
             params.insert("rootfs".to_string(), format!("{}:{}", storage, rootfs_size));
 
             // Unprivileged (default true)
-            let unprivileged = config
-                .unprivileged
-                .as_ref()
-                .map(|u| u == "true" || u == "1" || u == "yes")
-                .unwrap_or(true);
+            let unprivileged = match config.unprivileged {
+                Some(Templated::Value(unprivileged)) => unprivileged,
+                _ => true,
+            };
             params.insert(
                 "unprivileged".to_string(),
                 if unprivileged { "1" } else { "0" }.to_string(),
             );
 
             // Start on create (default true for provisioning)
-            let start = config
-                .start_on_create
-                .as_ref()
-                .map(|s| s == "true" || s == "1" || s == "yes")
-                .unwrap_or(true);
+            let start = match config.start_on_create {
+                Some(Templated::Value(start)) => start,
+                _ => true,
+            };
             params.insert(
                 "start".to_string(),
                 if start { "1" } else { "0" }.to_string(),
@@ -1435,11 +1460,7 @@ impl Provisioner for ProxmoxLxcProvisioner {
         }
 
         // Check if TUN device is requested
-        let needs_tun = config
-            .tun
-            .as_ref()
-            .map(|t| t == "true" || t == "1" || t == "yes")
-            .unwrap_or(false);
+        let needs_tun = matches!(config.tun, Some(Templated::Value(true)));
 
         eprintln!(
             "  → Creating new container '{}' on node '{}'",
@@ -1458,7 +1479,7 @@ impl Provisioner for ProxmoxLxcProvisioner {
         // Create the container (with start=0 if we need to configure TUN first)
         let mut config_for_create = config.clone();
         if needs_tun {
-            config_for_create.start_on_create = Some("false".to_string());
+            config_for_create.start_on_create = Some(Templated::Value(false));
         }
         self.create_container(&conn, &config_for_create, hostname, vmid, &host_vars)?;
 
@@ -1950,7 +1971,7 @@ mod tests {
         );
 
         let config = ProvisionConfig {
-            memory: Some("{{memory}}".to_string()),
+            memory: Some(Templated::Template("{{memory}}".to_string())),
             ..test_config()
         };
 
@@ -1958,7 +1979,25 @@ mod tests {
         assert!(result.is_ok());
 
         let templated = result.unwrap();
-        assert_eq!(templated.memory, Some("4096".to_string()));
+        assert_eq!(templated.memory, Some(Templated::Value(4096)));
+    }
+
+    #[test]
+    fn test_memory_must_render_to_a_number() {
+        let provisioner = test_provisioner();
+        let mut vars = serde_yaml::Mapping::new();
+        vars.insert(
+            serde_yaml::Value::String("memory".to_string()),
+            serde_yaml::Value::String("4G".to_string()),
+        );
+
+        let config = ProvisionConfig {
+            memory: Some(Templated::Template("{{memory}}".to_string())),
+            ..test_config()
+        };
+
+        let err = provisioner.template_config(&config, &vars).unwrap_err();
+        assert_eq!(err, "field (memory) expected an integer, got: 4G");
     }
 
     #[test]
@@ -1971,7 +2010,7 @@ mod tests {
         );
 
         let config = ProvisionConfig {
-            cores: Some("{{cores}}".to_string()),
+            cores: Some(Templated::Template("{{cores}}".to_string())),
             ..test_config()
         };
 
@@ -1979,7 +2018,7 @@ mod tests {
         assert!(result.is_ok());
 
         let templated = result.unwrap();
-        assert_eq!(templated.cores, Some("4".to_string()));
+        assert_eq!(templated.cores, Some(Templated::Value(4)));
     }
 
     #[test]
@@ -2078,8 +2117,8 @@ mod tests {
             hostname: Some("web-01".to_string()),
             ip: Some("192.168.1.10".to_string()),
             vmid: Some("101".to_string()),
-            memory: Some("4096".to_string()),
-            cores: Some("4".to_string()),
+            memory: Some(Templated::Template("4096".to_string())),
+            cores: Some(Templated::Template("4".to_string())),
             ostemplate: Some("local:vztmpl/debian-12-standard_amd64.tar.zst".to_string()),
             fetch: Some("latest".to_string()),
             storage: Some("local-lvm".to_string()),
@@ -2091,10 +2130,10 @@ mod tests {
             password: Some("secret123".to_string()),
             authorized_keys: Some("ssh-rsa AAAA...".to_string()),
             ssh_user: Some("admin".to_string()),
-            unprivileged: Some("true".to_string()),
-            start_on_create: Some("true".to_string()),
+            unprivileged: Some(Templated::Template("true".to_string())),
+            start_on_create: Some(Templated::Template("true".to_string())),
             features: Some("nesting=1".to_string()),
-            tun: Some("true".to_string()),
+            tun: Some(Templated::Template("true".to_string())),
             nameserver: Some("1.1.1.1".to_string()),
             wait_for_host: Some(true),
             wait_timeout: Some(120),
@@ -2105,9 +2144,9 @@ mod tests {
         };
 
         assert_eq!(config.node, Some("pve-node-1".to_string()));
-        assert_eq!(config.memory, Some("4096".to_string()));
-        assert_eq!(config.cores, Some("4".to_string()));
-        assert_eq!(config.tun, Some("true".to_string()));
+        assert_eq!(config.memory, Some(Templated::Template("4096".to_string())));
+        assert_eq!(config.cores, Some(Templated::Template("4".to_string())));
+        assert_eq!(config.tun, Some(Templated::Template("true".to_string())));
         assert_eq!(config.extra.get("mp0").unwrap(), "volume:10,mp=/mnt/data");
     }
 
@@ -2127,33 +2166,33 @@ mod tests {
 
         // Test unprivileged: "true"
         let config = ProvisionConfig {
-            unprivileged: Some("true".to_string()),
+            unprivileged: Some(Templated::Template("true".to_string())),
             ..test_config()
         };
 
         let result = provisioner.template_config(&config, &vars);
         assert!(result.is_ok());
-        assert_eq!(result.unwrap().unprivileged, Some("true".to_string()));
+        assert_eq!(result.unwrap().unprivileged, Some(Templated::Value(true)));
 
         // Test unprivileged: "false"
         let config2 = ProvisionConfig {
-            unprivileged: Some("false".to_string()),
+            unprivileged: Some(Templated::Template("false".to_string())),
             ..test_config()
         };
 
         let result2 = provisioner.template_config(&config2, &vars);
         assert!(result2.is_ok());
-        assert_eq!(result2.unwrap().unprivileged, Some("false".to_string()));
+        assert_eq!(result2.unwrap().unprivileged, Some(Templated::Value(false)));
 
         // Test unprivileged: "1"
         let config3 = ProvisionConfig {
-            unprivileged: Some("1".to_string()),
+            unprivileged: Some(Templated::Template("1".to_string())),
             ..test_config()
         };
 
         let result3 = provisioner.template_config(&config3, &vars);
         assert!(result3.is_ok());
-        assert_eq!(result3.unwrap().unprivileged, Some("1".to_string()));
+        assert_eq!(result3.unwrap().unprivileged, Some(Templated::Value(true)));
     }
 
     #[test]
@@ -2163,23 +2202,29 @@ mod tests {
 
         // Test start_on_create: "true"
         let config = ProvisionConfig {
-            start_on_create: Some("true".to_string()),
+            start_on_create: Some(Templated::Template("true".to_string())),
             ..test_config()
         };
 
         let result = provisioner.template_config(&config, &vars);
         assert!(result.is_ok());
-        assert_eq!(result.unwrap().start_on_create, Some("true".to_string()));
+        assert_eq!(
+            result.unwrap().start_on_create,
+            Some(Templated::Value(true))
+        );
 
         // Test start_on_create: "false"
         let config2 = ProvisionConfig {
-            start_on_create: Some("false".to_string()),
+            start_on_create: Some(Templated::Template("false".to_string())),
             ..test_config()
         };
 
         let result2 = provisioner.template_config(&config2, &vars);
         assert!(result2.is_ok());
-        assert_eq!(result2.unwrap().start_on_create, Some("false".to_string()));
+        assert_eq!(
+            result2.unwrap().start_on_create,
+            Some(Templated::Value(false))
+        );
     }
 
     #[test]
@@ -2189,23 +2234,31 @@ mod tests {
 
         // Test tun: "true"
         let config = ProvisionConfig {
-            tun: Some("true".to_string()),
+            tun: Some(Templated::Template("true".to_string())),
             ..test_config()
         };
 
         let result = provisioner.template_config(&config, &vars);
         assert!(result.is_ok());
-        assert_eq!(result.unwrap().tun, Some("true".to_string()));
+        assert_eq!(result.unwrap().tun, Some(Templated::Value(true)));
 
         // Test tun: "yes"
         let config2 = ProvisionConfig {
-            tun: Some("yes".to_string()),
+            tun: Some(Templated::Template("yes".to_string())),
             ..test_config()
         };
 
         let result2 = provisioner.template_config(&config2, &vars);
         assert!(result2.is_ok());
-        assert_eq!(result2.unwrap().tun, Some("yes".to_string()));
+        assert_eq!(result2.unwrap().tun, Some(Templated::Value(true)));
+
+        // anything that is not a boolean fails, naming the field
+        let config3 = ProvisionConfig {
+            tun: Some(Templated::Template("maybe".to_string())),
+            ..test_config()
+        };
+        let err = provisioner.template_config(&config3, &vars).unwrap_err();
+        assert!(err.contains("tun"), "{}", err);
     }
 
     #[test]
@@ -2452,17 +2505,17 @@ mod tests {
             node: Some("pve1".to_string()),
             hostname: Some("{{hostname}}".to_string()),
             vmid: Some("100".to_string()),
-            memory: Some("{{memory}}".to_string()),
-            cores: Some("2".to_string()),
+            memory: Some(Templated::Template("{{memory}}".to_string())),
+            cores: Some(Templated::Template("2".to_string())),
             ostemplate: Some("local:vztmpl/debian-12-standard_amd64.tar.zst".to_string()),
             fetch: Some("latest".to_string()),
             storage: Some("local-lvm".to_string()),
             rootfs_size: Some("16".to_string()),
             net0: Some("name=eth0,bridge=vmbr0,ip=dhcp".to_string()),
             password: Some("securepassword".to_string()),
-            unprivileged: Some("true".to_string()),
-            start_on_create: Some("true".to_string()),
-            tun: Some("true".to_string()),
+            unprivileged: Some(Templated::Template("true".to_string())),
+            start_on_create: Some(Templated::Template("true".to_string())),
+            tun: Some(Templated::Template("true".to_string())),
             wait_for_host: Some(true),
             wait_timeout: Some(120),
             ..test_config()
@@ -2473,7 +2526,7 @@ mod tests {
 
         let templated = result.unwrap();
         assert_eq!(templated.hostname.as_deref(), Some("app-server"));
-        assert_eq!(templated.memory.as_deref(), Some("2048"));
+        assert_eq!(templated.memory, Some(Templated::Value(2048)));
         assert_eq!(templated.fetch.as_deref(), Some("latest"));
 
         // Test clone works
@@ -2551,7 +2604,7 @@ mod tests {
         let _provisioner = test_provisioner();
 
         // Simulate template list from local storage
-        let templates = vec![
+        let templates = [
             "local:vztmpl/debian-12-standard_amd64_12.2.0_pve8.tar.zst".to_string(),
             "local:vztmpl/debian-12-standard_amd64_12.3.0_pve8.tar.zst".to_string(),
             "local:vztmpl/debian-13-standard_amd64_13.0.0_pve8.tar.zst".to_string(),
@@ -2572,7 +2625,7 @@ mod tests {
         // Test that exact template name matching works
         let _provisioner = test_provisioner();
 
-        let templates = vec![
+        let templates = [
             "local:vztmpl/debian-12-standard_amd64_12.2.0_pve8.tar.zst".to_string(),
             "local:vztmpl/debian-12-standard_arm64_12.2.0_pve8.tar.zst".to_string(),
             "local:vztmpl/debian-13-standard_amd64_13.0.0_pve8.tar.zst".to_string(),
@@ -2601,7 +2654,7 @@ mod tests {
         // Test that amd64 and arm64 templates are distinguished
         let _provisioner = test_provisioner();
 
-        let templates = vec![
+        let templates = [
             "local:vztmpl/debian-12-standard_amd64.tar.zst".to_string(),
             "local:vztmpl/debian-12-standard_arm64.tar.zst".to_string(),
         ];
@@ -2620,7 +2673,7 @@ mod tests {
         // Test that sorting finds the latest version
         let _provisioner = test_provisioner();
 
-        let mut templates = vec![
+        let mut templates = [
             "local:vztmpl/debian-12-standard_amd64_12.0.0_pve8.tar.zst".to_string(),
             "local:vztmpl/debian-12-standard_amd64_12.2.0_pve8.tar.zst".to_string(),
             "local:vztmpl/debian-12-standard_amd64_12.1.0_pve8.tar.zst".to_string(),
@@ -2711,13 +2764,12 @@ mod tests {
                     .get("ip-address-type")
                     .and_then(|t| t.as_str())
                     .unwrap_or("");
-                if addr_type == "inet" {
-                    if let Some(ip) = addr.get("ip-address").and_then(|i| i.as_str()) {
-                        if ip != "127.0.0.1" {
-                            found_ip = Some(ip.to_string());
-                            break;
-                        }
-                    }
+                if addr_type == "inet"
+                    && let Some(ip) = addr.get("ip-address").and_then(|i| i.as_str())
+                    && ip != "127.0.0.1"
+                {
+                    found_ip = Some(ip.to_string());
+                    break;
                 }
             }
         }
@@ -2750,12 +2802,11 @@ mod tests {
                     .get("ip-address-type")
                     .and_then(|t| t.as_str())
                     .unwrap_or("");
-                if addr_type == "inet" {
-                    if let Some(ip) = addr.get("ip-address").and_then(|i| i.as_str()) {
-                        if ip != "127.0.0.1" {
-                            found_ip = Some(ip.to_string());
-                        }
-                    }
+                if addr_type == "inet"
+                    && let Some(ip) = addr.get("ip-address").and_then(|i| i.as_str())
+                    && ip != "127.0.0.1"
+                {
+                    found_ip = Some(ip.to_string());
                 }
             }
         }
@@ -2783,8 +2834,8 @@ mod tests {
 
         let mut ip_mode: Option<String> = None;
         for part in net0.split(',') {
-            if part.starts_with("ip=") {
-                ip_mode = Some(part[3..].to_string());
+            if let Some(mode) = part.strip_prefix("ip=") {
+                ip_mode = Some(mode.to_string());
             }
         }
 
@@ -2798,11 +2849,10 @@ mod tests {
 
         let mut ip_addr: Option<String> = None;
         for part in net0.split(',') {
-            if part.starts_with("ip=") {
-                let ip_cidr = &part[3..];
-                if ip_cidr.to_lowercase() != "dhcp" {
-                    ip_addr = Some(ip_cidr.split('/').next().unwrap_or(ip_cidr).to_string());
-                }
+            if let Some(ip_cidr) = part.strip_prefix("ip=")
+                && ip_cidr.to_lowercase() != "dhcp"
+            {
+                ip_addr = Some(ip_cidr.split('/').next().unwrap_or(ip_cidr).to_string());
             }
         }
 
@@ -2948,7 +2998,7 @@ mod tests {
         // In a multi-node cluster, container could be on any node
         // The IP lookup should search all nodes
 
-        let cluster_nodes = vec!["pve1", "pve2", "pve3"];
+        let cluster_nodes = ["pve1", "pve2", "pve3"];
         let _target_hostname = "dragonfly";
 
         // Simulate finding container on pve2
@@ -2993,7 +3043,7 @@ mod tests {
         // Updated = container existed but was modified
 
         // These are the expected outcomes from ensure_exists
-        let expected_results = vec!["Created", "AlreadyExists", "Updated"];
+        let expected_results = ["Created", "AlreadyExists", "Updated"];
 
         // Verify our expectations match the ProvisionResult enum
         assert!(expected_results.contains(&"Created"));
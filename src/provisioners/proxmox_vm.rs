@@ -7,6 +7,9 @@
 use crate::inventory::inventory::Inventory;
 use crate::playbooks::templar::{Templar, TemplateMode};
 use crate::provisioners::{ProvisionConfig, ProvisionResult, Provisioner};
+use crate::tasks::Templated;
+use crate::tasks::templated::TemplatedScalar;
+use crate::util::secrets::SecretSource;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::sync::{Arc, LazyLock, Mutex, RwLock};
@@ -88,6 +91,24 @@ impl ProxmoxVmProvisioner {
         }
     }
 
+    fn template_typed<T: TemplatedScalar>(
+        &self,
+        templar: &Templar,
+        field: &str,
+        value: &Option<Templated<T>>,
+        vars: &serde_yaml::Mapping,
+    ) -> Result<Option<Templated<T>>, String> {
+        match value {
+            Some(Templated::Template(t)) => {
+                let rendered = self.template_string(templar, t, vars)?;
+                Ok(Some(Templated::Value(Templated::<T>::parse_rendered(
+                    field, &rendered,
+                )?)))
+            }
+            other => Ok(other.clone()),
+        }
+    }
+
     fn template_config(
         &self,
         config: &ProvisionConfig,
@@ -102,8 +123,8 @@ impl ProxmoxVmProvisioner {
             hostname: self.template_option(&templar, &config.hostname, vars)?,
             ip: self.template_option(&templar, &config.ip, vars)?,
             vmid: self.template_option(&templar, &config.vmid, vars)?,
-            memory: self.template_option(&templar, &config.memory, vars)?,
-            cores: self.template_option(&templar, &config.cores, vars)?,
+            memory: self.template_typed(&templar, "memory", &config.memory, vars)?,
+            cores: self.template_typed(&templar, "cores", &config.cores, vars)?,
             ostemplate: self.template_option(&templar, &config.ostemplate, vars)?,
             fetch: self.template_option(&templar, &config.fetch, vars)?,
             storage: self.template_option(&templar, &config.storage, vars)?,
@@ -115,10 +136,20 @@ impl ProxmoxVmProvisioner {
             password: self.template_option(&templar, &config.password, vars)?,
             authorized_keys: self.template_option(&templar, &config.authorized_keys, vars)?,
             ssh_user: self.template_option(&templar, &config.ssh_user, vars)?,
-            unprivileged: self.template_option(&templar, &config.unprivileged, vars)?,
-            start_on_create: self.template_option(&templar, &config.start_on_create, vars)?,
+            unprivileged: self.template_typed(
+                &templar,
+                "unprivileged",
+                &config.unprivileged,
+                vars,
+            )?,
+            start_on_create: self.template_typed(
+                &templar,
+                "start_on_create",
+                &config.start_on_create,
+                vars,
+            )?,
             features: self.template_option(&templar, &config.features, vars)?,
-            tun: self.template_option(&templar, &config.tun, vars)?,
+            tun: self.template_typed(&templar, "tun", &config.tun, vars)?,
             nameserver: self.template_option(&templar, &config.nameserver, vars)?,
             wait_for_host: config.wait_for_host,
             wait_timeout: config.wait_timeout,
@@ -175,14 +206,14 @@ impl ProxmoxVmProvisioner {
         // Token auth
         if let (Some(tid), Some(ts)) = (
             vars.get("proxmox_api_token_id").and_then(|v| v.as_str()),
-            vars.get("proxmox_api_token_secret")
-                .and_then(|v| v.as_str()),
+            SecretSource::from_vars(&vars, "proxmox_api_token_secret")
+                .resolve("proxmox_api_token_secret")?,
         ) {
             return Ok(ClusterConnection {
                 api_host,
                 auth: ProxmoxAuth::Token {
                     token_id: tid.to_string(),
-                    token_secret: ts.to_string(),
+                    token_secret: ts,
                 },
                 node,
             });
@@ -193,12 +224,11 @@ impl ProxmoxVmProvisioner {
             .get("proxmox_api_user")
             .and_then(|v| v.as_str())
             .ok_or_else(|| "Missing proxmox_api_user".to_string())?;
-        let password = vars
-            .get("proxmox_api_password")
-            .and_then(|v| v.as_str())
+        let password = SecretSource::from_vars(&vars, "proxmox_api_password")
+            .resolve("proxmox_api_password")?
             .ok_or_else(|| "Missing proxmox_api_password".to_string())?;
 
-        let (ticket, csrf) = self.get_ticket(&api_host, username, password)?;
+        let (ticket, csrf) = self.get_ticket(&api_host, username, &password)?;
         Ok(ClusterConnection {
             api_host,
             auth: ProxmoxAuth::Password {
@@ -383,11 +413,17 @@ impl ProxmoxVmProvisioner {
             params.insert("name".to_string(), hostname.to_string());
 
             // Memory (default 2048)
-            let memory = config.memory.as_deref().unwrap_or("2048");
+            let memory = match config.memory {
+                Some(Templated::Value(memory)) => memory,
+                _ => 2048,
+            };
             params.insert("memory".to_string(), memory.to_string());
 
             // Cores (default 4)
-            let cores = config.cores.as_deref().unwrap_or("4");
+            let cores = match config.cores {
+                Some(Templated::Value(cores)) => cores,
+                _ => 4,
+            };
             params.insert("cores".to_string(), cores.to_string());
 
             // CPU type
@@ -437,11 +473,10 @@ impl ProxmoxVmProvisioner {
             params.insert("ostype".to_string(), "l26".to_string()); // Linux 2.6+ kernel
 
             // Start on create? Default true for PXE boot
-            let start = config
-                .start_on_create
-                .as_ref()
-                .map(|s| s == "true" || s == "1" || s == "yes")
-                .unwrap_or(true);
+            let start = match config.start_on_create {
+                Some(Templated::Value(start)) => start,
+                _ => true,
+            };
             if start {
                 params.insert("start".to_string(), "1".to_string());
             }
@@ -801,88 +836,6 @@ fn converge_dragonfly(
     }
 }
 
-#[cfg(test)]
-mod converge_tests {
-    use super::*;
-    use crate::provisioners::dragonfly::NetworkSpec;
-    use crate::provisioners::dragonfly::tests::{MockServer, client};
-
-    /// #26: a machine that is registered with Dragonfly but not yet `Installed`
-    /// (mid-imaging, or finished-but-not-yet-marked) must NOT be re-imaged by a
-    /// converge run — re-imaging cancels its workflow and wipes a freshly-
-    /// installed box. Only an *unregistered* machine (None) is imaged here.
-    #[test]
-    fn converge_does_not_reimage_registered_non_installed_machine() {
-        // The by-MAC lookup returns a registered, non-Installed machine. Every
-        // other endpoint is answered so that a *wrong* re-image would fully
-        // execute and be recorded (so this test fails loudly, not silently).
-        let server = MockServer::start(|req| {
-            if req.method == "GET" && req.path.starts_with("/api/machines/by-mac/") {
-                (200, r#"{"id":"m1","status":"Installing"}"#.to_string())
-            } else if req.path.ends_with("/reimage") {
-                (200, "{}".to_string())
-            } else if req.path.ends_with("/os") {
-                (200, "{}".to_string())
-            } else if req.path.ends_with("/hostname") {
-                (200, "{}".to_string())
-            } else {
-                // admin/create
-                (200, r#"{"machine_id":"m1","created":false}"#.to_string())
-            }
-        });
-        let c = client(&server.url());
-        converge_dragonfly(
-            &c,
-            "BC:24:11:22:33:44",
-            None,
-            "k8s06",
-            &NetworkSpec::default(),
-            Some("debian-13"),
-            None,
-        );
-        let reimaged = server
-            .recorded()
-            .iter()
-            .any(|r| r.method == "POST" && r.path.contains("/reimage"));
-        assert!(
-            !reimaged,
-            "converge must NOT reimage a registered non-Installed machine (#26)"
-        );
-    }
-
-    /// A machine not yet registered with Dragonfly (None) is still imaged — the
-    /// create path is unchanged by the #26 fix.
-    #[test]
-    fn converge_images_unregistered_machine() {
-        let server = MockServer::start(|req| {
-            if req.method == "GET" && req.path.starts_with("/api/machines/by-mac/") {
-                // not registered → 404 → None → image it
-                (404, r#"{"error":"Not Found"}"#.to_string())
-            } else {
-                (200, r#"{"machine_id":"m1","created":true}"#.to_string())
-            }
-        });
-        let c = client(&server.url());
-        converge_dragonfly(
-            &c,
-            "BC:24:11:22:33:44",
-            None,
-            "newhost",
-            &NetworkSpec::default(),
-            Some("debian-13"),
-            None,
-        );
-        let reimaged = server
-            .recorded()
-            .iter()
-            .any(|r| r.method == "POST" && r.path.contains("/reimage"));
-        assert!(
-            reimaged,
-            "converge must image an unregistered machine (create path unchanged)"
-        );
-    }
-}
-
 impl Provisioner for ProxmoxVmProvisioner {
     fn exists(
         &self,
@@ -945,7 +898,7 @@ impl Provisioner for ProxmoxVmProvisioner {
         let conn = self.get_cluster_connection(&config, inventory)?;
         let hostname = config.hostname.as_deref().unwrap_or(inventory_name);
         let os = crate::provisioners::dragonfly::var(&host_vars, "dragonfly_os_template");
-        let dragonfly = crate::provisioners::dragonfly::DragonflyClient::try_from_vars(&host_vars);
+        let dragonfly = crate::provisioners::dragonfly::DragonflyClient::try_from_vars(&host_vars)?;
         let network = crate::provisioners::dragonfly::NetworkSpec::from_vars(&host_vars);
 
         // Exists → converge: if the VM exists but Dragonfly hasn't finished imaging
@@ -1047,7 +1000,7 @@ impl Provisioner for ProxmoxVmProvisioner {
         // boot. Opt out when Dragonfly isn't configured so non-Dragonfly
         // deployments are unchanged. Best-effort: callers treat errors as "no IP".
         let dragonfly =
-            match crate::provisioners::dragonfly::DragonflyClient::try_from_vars(&host_vars) {
+            match crate::provisioners::dragonfly::DragonflyClient::try_from_vars(&host_vars)? {
                 Some(d) => d,
                 None => return Ok(None),
             };
@@ -1124,3 +1077,84 @@ impl Provisioner for ProxmoxVmProvisioner {
         Ok(ProvisionResult::AlreadyExists)
     }
 }
+
+#[cfg(test)]
+mod converge_tests {
+    use super::*;
+    use crate::provisioners::dragonfly::NetworkSpec;
+    use crate::provisioners::dragonfly::tests::{MockServer, client};
+
+    /// #26: a machine that is registered with Dragonfly but not yet `Installed`
+    /// (mid-imaging, or finished-but-not-yet-marked) must NOT be re-imaged by a
+    /// converge run — re-imaging cancels its workflow and wipes a freshly-
+    /// installed box. Only an *unregistered* machine (None) is imaged here.
+    #[test]
+    fn converge_does_not_reimage_registered_non_installed_machine() {
+        // The by-MAC lookup returns a registered, non-Installed machine. Every
+        // other endpoint is answered so that a *wrong* re-image would fully
+        // execute and be recorded (so this test fails loudly, not silently).
+        let server = MockServer::start(|req| {
+            if req.method == "GET" && req.path.starts_with("/api/machines/by-mac/") {
+                (200, r#"{"id":"m1","status":"Installing"}"#.to_string())
+            } else if req.path.ends_with("/reimage")
+                || req.path.ends_with("/os")
+                || req.path.ends_with("/hostname")
+            {
+                (200, "{}".to_string())
+            } else {
+                // admin/create
+                (200, r#"{"machine_id":"m1","created":false}"#.to_string())
+            }
+        });
+        let c = client(&server.url());
+        converge_dragonfly(
+            &c,
+            "BC:24:11:22:33:44",
+            None,
+            "k8s06",
+            &NetworkSpec::default(),
+            Some("debian-13"),
+            None,
+        );
+        let reimaged = server
+            .recorded()
+            .iter()
+            .any(|r| r.method == "POST" && r.path.contains("/reimage"));
+        assert!(
+            !reimaged,
+            "converge must NOT reimage a registered non-Installed machine (#26)"
+        );
+    }
+
+    /// A machine not yet registered with Dragonfly (None) is still imaged — the
+    /// create path is unchanged by the #26 fix.
+    #[test]
+    fn converge_images_unregistered_machine() {
+        let server = MockServer::start(|req| {
+            if req.method == "GET" && req.path.starts_with("/api/machines/by-mac/") {
+                // not registered → 404 → None → image it
+                (404, r#"{"error":"Not Found"}"#.to_string())
+            } else {
+                (200, r#"{"machine_id":"m1","created":true}"#.to_string())
+            }
+        });
+        let c = client(&server.url());
+        converge_dragonfly(
+            &c,
+            "BC:24:11:22:33:44",
+            None,
+            "newhost",
+            &NetworkSpec::default(),
+            Some("debian-13"),
+            None,
+        );
+        let reimaged = server
+            .recorded()
+            .iter()
+            .any(|r| r.method == "POST" && r.path.contains("/reimage"));
+        assert!(
+            reimaged,
+            "converge must image an unregistered machine (create path unchanged)"
+        );
+    }
+}
@@ -27,6 +27,7 @@ use std::sync::Arc;
 // ADD NEW MODULES HERE, KEEP ALPHABETIZED BY SECTION
 
 // accessctl
+use crate::modules::access::authorized_key::AuthorizedKeyTask;
 use crate::modules::access::group::GroupTask;
 use crate::modules::access::user::UserTask;
 
@@ -41,6 +42,7 @@ use crate::modules::control::debug::DebugTask;
 use crate::modules::control::echo::EchoTask;
 use crate::modules::control::facts::FactsTask;
 use crate::modules::control::fail::FailTask;
+use crate::modules::control::reboot::RebootTask;
 use crate::modules::control::self_locate::SelfLocateTask;
 use crate::modules::control::set::SetTask;
 use crate::modules::control::wait_for_host::WaitForHostTask;
@@ -48,14 +50,23 @@ use crate::modules::control::wait_for_http::WaitForHttpTask;
 use crate::modules::control::wait_for_others::WaitForOthersTask;
 
 // files
+use crate::modules::files::archive::ArchiveTask;
+use crate::modules::files::blockinfile::BlockInFileTask;
+use crate::modules::files::config_file::ConfigFileTask;
 use crate::modules::files::copy::CopyTask;
 use crate::modules::files::directory::DirectoryTask;
 use crate::modules::files::download::DownloadTask;
 use crate::modules::files::fetch::FetchTask;
 use crate::modules::files::file::FileTask;
+use crate::modules::files::find::FindTask;
 use crate::modules::files::git::GitTask;
+use crate::modules::files::ini_file::IniFileTask;
+use crate::modules::files::lineinfile::LineInFileTask;
+use crate::modules::files::link::LinkTask;
 use crate::modules::files::r#move::MoveTask;
+use crate::modules::files::replace::ReplaceTask;
 use crate::modules::files::stat::StatTask;
+use crate::modules::files::synchronize::SynchronizeTask;
 use crate::modules::files::template::TemplateTask;
 use crate::modules::files::unpack::UnpackTask;
 
@@ -72,13 +83,20 @@ use crate::modules::proxmox::node::ProxmoxNodeTask;
 
 // packages
 use crate::modules::packages::apt::AptTask;
+use crate::modules::packages::apt_repository::AptRepositoryTask;
 use crate::modules::packages::homebrew::HomebrewTask;
+use crate::modules::packages::package::PackageTask;
 use crate::modules::packages::pacman::PacmanTask;
 use crate::modules::packages::yum_dnf::YumDnfTask;
+use crate::modules::packages::yum_repository::YumRepositoryTask;
 use crate::modules::packages::zypper::ZypperTask;
 
 // services
 use crate::modules::services::sd_service::SystemdServiceTask;
+use crate::modules::services::service::ServiceTask;
+use crate::modules::system::kernel_module::KernelModuleTask;
+use crate::modules::system::mount::MountTask;
+use crate::modules::system::sysctl::SysctlTask;
 
 #[allow(non_camel_case_types)]
 #[derive(Deserialize, Debug, strum::EnumIter, strum::AsRefStr)]
@@ -87,8 +105,13 @@ use crate::modules::services::sd_service::SystemdServiceTask;
 pub enum Task {
     // ADD NEW MODULES HERE, KEEP ALPHABETIZED BY NAME
     Apt(AptTask),
+    Apt_Repository(AptRepositoryTask),
+    Archive(ArchiveTask),
     Assert(AssertTask),
+    Authorized_Key(AuthorizedKeyTask),
+    Blockinfile(BlockInFileTask),
     Command(CommandTask),
+    Config_File(ConfigFileTask),
     Copy(CopyTask),
     Debug(DebugTask),
     Fetch(FetchTask),
@@ -100,21 +123,33 @@ pub enum Task {
     Fail(FailTask),
     Download(DownloadTask),
     File(FileTask),
+    Find(FindTask),
     Git(GitTask),
     Github_Release(GithubReleaseTask),
     Group(GroupTask),
     Homebrew(HomebrewTask),
+    Ini_File(IniFileTask),
     Instantiate(InstantiateTask),
+    Kernel_Module(KernelModuleTask),
+    Lineinfile(LineInFileTask),
+    Link(LinkTask),
+    Mount(MountTask),
     Move(MoveTask),
+    Package(PackageTask),
     Pacman(PacmanTask),
     Proxmox_Lxc(ProxmoxLxcTask),
     Proxmox_Migrate(ProxmoxMigrateTask),
     Proxmox_Node(ProxmoxNodeTask),
+    Reboot(RebootTask),
+    Replace(ReplaceTask),
     Sd_Service(SystemdServiceTask),
     Self_Locate(SelfLocateTask),
+    Service(ServiceTask),
     Set(SetTask),
     Shell(ShellTask),
     Stat(StatTask),
+    Synchronize(SynchronizeTask),
+    Sysctl(SysctlTask),
     Template(TemplateTask),
     Unpack(UnpackTask),
     User(UserTask),
@@ -122,6 +157,7 @@ pub enum Task {
     Wait_For_Http(WaitForHttpTask),
     Wait_For_Others(WaitForOthersTask),
     Yum(YumDnfTask),
+    Yum_Repository(YumRepositoryTask),
     Zypper(ZypperTask),
 }
 
@@ -130,7 +166,12 @@ impl Task {
         // ADD NEW MODULES HERE, KEEP ALPHABETIZED BY NAME
         match self {
             Task::Apt(x) => x.get_module(),
+            Task::Apt_Repository(x) => x.get_module(),
+            Task::Archive(x) => x.get_module(),
             Task::Assert(x) => x.get_module(),
+            Task::Authorized_Key(x) => x.get_module(),
+            Task::Blockinfile(x) => x.get_module(),
+            Task::Config_File(x) => x.get_module(),
             Task::Copy(x) => x.get_module(),
             Task::Debug(x) => x.get_module(),
             Task::Fetch(x) => x.get_module(),
@@ -142,22 +183,34 @@ impl Task {
             Task::Fail(x) => x.get_module(),
             Task::Download(x) => x.get_module(),
             Task::File(x) => x.get_module(),
+            Task::Find(x) => x.get_module(),
             Task::Git(x) => x.get_module(),
             Task::Github_Release(x) => x.get_module(),
             Task::Group(x) => x.get_module(),
             Task::Homebrew(x) => x.get_module(),
+            Task::Ini_File(x) => x.get_module(),
             Task::Instantiate(x) => x.get_module(),
+            Task::Kernel_Module(x) => x.get_module(),
+            Task::Lineinfile(x) => x.get_module(),
+            Task::Link(x) => x.get_module(),
+            Task::Mount(x) => x.get_module(),
             Task::Move(x) => x.get_module(),
+            Task::Package(x) => x.get_module(),
             Task::Pacman(x) => x.get_module(),
             Task::Proxmox_Lxc(x) => x.get_module(),
             Task::Proxmox_Migrate(x) => x.get_module(),
             Task::Proxmox_Node(x) => x.get_module(),
+            Task::Reboot(x) => x.get_module(),
+            Task::Replace(x) => x.get_module(),
             Task::Sd_Service(x) => x.get_module(),
             Task::Self_Locate(x) => x.get_module(),
+            Task::Service(x) => x.get_module(),
             Task::Set(x) => x.get_module(),
             Task::Command(x) => x.get_module(),
             Task::Shell(x) => x.get_module(),
             Task::Stat(x) => x.get_module(),
+            Task::Synchronize(x) => x.get_module(),
+            Task::Sysctl(x) => x.get_module(),
             Task::Template(x) => x.get_module(),
             Task::Unpack(x) => x.get_module(),
             Task::User(x) => x.get_module(),
@@ -165,6 +218,7 @@ impl Task {
             Task::Wait_For_Http(x) => x.get_module(),
             Task::Wait_For_Others(x) => x.get_module(),
             Task::Yum(x) => x.get_module(),
+            Task::Yum_Repository(x) => x.get_module(),
             Task::Zypper(x) => x.get_module(),
         }
     }
@@ -173,7 +227,12 @@ impl Task {
         // ADD NEW MODULES HERE, KEEP ALPHABETIZED BY NAME
         match self {
             Task::Apt(x) => x.get_name(),
+            Task::Apt_Repository(x) => x.get_name(),
+            Task::Archive(x) => x.get_name(),
             Task::Assert(x) => x.get_name(),
+            Task::Authorized_Key(x) => x.get_name(),
+            Task::Blockinfile(x) => x.get_name(),
+            Task::Config_File(x) => x.get_name(),
             Task::Copy(x) => x.get_name(),
             Task::Debug(x) => x.get_name(),
             Task::Fetch(x) => x.get_name(),
@@ -185,22 +244,34 @@ impl Task {
             Task::Fail(x) => x.get_name(),
             Task::Download(x) => x.get_name(),
             Task::File(x) => x.get_name(),
+            Task::Find(x) => x.get_name(),
             Task::Git(x) => x.get_name(),
             Task::Github_Release(x) => x.get_name(),
             Task::Group(x) => x.get_name(),
             Task::Homebrew(x) => x.get_name(),
+            Task::Ini_File(x) => x.get_name(),
             Task::Instantiate(x) => x.get_name(),
+            Task::Kernel_Module(x) => x.get_name(),
+            Task::Lineinfile(x) => x.get_name(),
+            Task::Link(x) => x.get_name(),
+            Task::Mount(x) => x.get_name(),
             Task::Move(x) => x.get_name(),
+            Task::Package(x) => x.get_name(),
             Task::Pacman(x) => x.get_name(),
             Task::Proxmox_Lxc(x) => x.get_name(),
             Task::Proxmox_Migrate(x) => x.get_name(),
             Task::Proxmox_Node(x) => x.get_name(),
+            Task::Reboot(x) => x.get_name(),
+            Task::Replace(x) => x.get_name(),
             Task::Sd_Service(x) => x.get_name(),
             Task::Self_Locate(x) => x.get_name(),
+            Task::Service(x) => x.get_name(),
             Task::Set(x) => x.get_name(),
             Task::Command(x) => x.get_name(),
             Task::Shell(x) => x.get_name(),
             Task::Stat(x) => x.get_name(),
+            Task::Synchronize(x) => x.get_name(),
+            Task::Sysctl(x) => x.get_name(),
             Task::Template(x) => x.get_name(),
             Task::Unpack(x) => x.get_name(),
             Task::User(x) => x.get_name(),
@@ -208,6 +279,7 @@ impl Task {
             Task::Wait_For_Http(x) => x.get_name(),
             Task::Wait_For_Others(x) => x.get_name(),
             Task::Yum(x) => x.get_name(),
+            Task::Yum_Repository(x) => x.get_name(),
             Task::Zypper(x) => x.get_name(),
         }
     }
@@ -216,7 +288,12 @@ impl Task {
         // ADD NEW MODULES HERE, KEEP ALPHABETIZED BY NAME
         match self {
             Task::Apt(x) => x.get_with(),
+            Task::Apt_Repository(x) => x.get_with(),
+            Task::Archive(x) => x.get_with(),
             Task::Assert(x) => x.get_with(),
+            Task::Authorized_Key(x) => x.get_with(),
+            Task::Blockinfile(x) => x.get_with(),
+            Task::Config_File(x) => x.get_with(),
             Task::Copy(x) => x.get_with(),
             Task::Debug(x) => x.get_with(),
             Task::Fetch(x) => x.get_with(),
@@ -228,22 +305,34 @@ impl Task {
             Task::Fail(x) => x.get_with(),
             Task::Download(x) => x.get_with(),
             Task::File(x) => x.get_with(),
+            Task::Find(x) => x.get_with(),
             Task::Git(x) => x.get_with(),
             Task::Github_Release(x) => x.get_with(),
             Task::Group(x) => x.get_with(),
             Task::Homebrew(x) => x.get_with(),
+            Task::Ini_File(x) => x.get_with(),
             Task::Instantiate(x) => x.get_with(),
+            Task::Kernel_Module(x) => x.get_with(),
+            Task::Lineinfile(x) => x.get_with(),
+            Task::Link(x) => x.get_with(),
+            Task::Mount(x) => x.get_with(),
             Task::Move(x) => x.get_with(),
+            Task::Package(x) => x.get_with(),
             Task::Pacman(x) => x.get_with(),
             Task::Proxmox_Lxc(x) => x.get_with(),
             Task::Proxmox_Migrate(x) => x.get_with(),
             Task::Proxmox_Node(x) => x.get_with(),
+            Task::Reboot(x) => x.get_with(),
+            Task::Replace(x) => x.get_with(),
             Task::Sd_Service(x) => x.get_with(),
             Task::Self_Locate(x) => x.get_with(),
+            Task::Service(x) => x.get_with(),
             Task::Set(x) => x.get_with(),
             Task::Command(x) => x.get_with(),
             Task::Shell(x) => x.get_with(),
             Task::Stat(x) => x.get_with(),
+            Task::Synchronize(x) => x.get_with(),
+            Task::Sysctl(x) => x.get_with(),
             Task::Template(x) => x.get_with(),
             Task::Unpack(x) => x.get_with(),
             Task::User(x) => x.get_with(),
@@ -251,6 +340,7 @@ impl Task {
             Task::Wait_For_Http(x) => x.get_with(),
             Task::Wait_For_Others(x) => x.get_with(),
             Task::Yum(x) => x.get_with(),
+            Task::Yum_Repository(x) => x.get_with(),
             Task::Zypper(x) => x.get_with(),
         }
     }
@@ -264,7 +354,12 @@ impl Task {
         // ADD NEW MODULES HERE, KEEP ALPHABETIZED BY NAME
         match self {
             Task::Apt(x) => x.evaluate(handle, request, tm),
+            Task::Apt_Repository(x) => x.evaluate(handle, request, tm),
+            Task::Archive(x) => x.evaluate(handle, request, tm),
             Task::Assert(x) => x.evaluate(handle, request, tm),
+            Task::Authorized_Key(x) => x.evaluate(handle, request, tm),
+            Task::Blockinfile(x) => x.evaluate(handle, request, tm),
+            Task::Config_File(x) => x.evaluate(handle, request, tm),
             Task::Copy(x) => x.evaluate(handle, request, tm),
             Task::Debug(x) => x.evaluate(handle, request, tm),
             Task::Fetch(x) => x.evaluate(handle, request, tm),
@@ -276,22 +371,34 @@ impl Task {
             Task::Fail(x) => x.evaluate(handle, request, tm),
             Task::Download(x) => x.evaluate(handle, request, tm),
             Task::File(x) => x.evaluate(handle, request, tm),
+            Task::Find(x) => x.evaluate(handle, request, tm),
             Task::Git(x) => x.evaluate(handle, request, tm),
             Task::Github_Release(x) => x.evaluate(handle, request, tm),
             Task::Group(x) => x.evaluate(handle, request, tm),
             Task::Homebrew(x) => x.evaluate(handle, request, tm),
+            Task::Ini_File(x) => x.evaluate(handle, request, tm),
             Task::Instantiate(x) => x.evaluate(handle, request, tm),
+            Task::Kernel_Module(x) => x.evaluate(handle, request, tm),
+            Task::Lineinfile(x) => x.evaluate(handle, request, tm),
+            Task::Link(x) => x.evaluate(handle, request, tm),
+            Task::Mount(x) => x.evaluate(handle, request, tm),
             Task::Move(x) => x.evaluate(handle, request, tm),
+            Task::Package(x) => x.evaluate(handle, request, tm),
             Task::Pacman(x) => x.evaluate(handle, request, tm),
             Task::Proxmox_Lxc(x) => x.evaluate(handle, request, tm),
             Task::Proxmox_Migrate(x) => x.evaluate(handle, request, tm),
             Task::Proxmox_Node(x) => x.evaluate(handle, request, tm),
+            Task::Reboot(x) => x.evaluate(handle, request, tm),
+            Task::Replace(x) => x.evaluate(handle, request, tm),
             Task::Sd_Service(x) => x.evaluate(handle, request, tm),
             Task::Self_Locate(x) => x.evaluate(handle, request, tm),
+            Task::Service(x) => x.evaluate(handle, request, tm),
             Task::Set(x) => x.evaluate(handle, request, tm),
             Task::Command(x) => x.evaluate(handle, request, tm),
             Task::Shell(x) => x.evaluate(handle, request, tm),
             Task::Stat(x) => x.evaluate(handle, request, tm),
+            Task::Synchronize(x) => x.evaluate(handle, request, tm),
+            Task::Sysctl(x) => x.evaluate(handle, request, tm),
             Task::Template(x) => x.evaluate(handle, request, tm),
             Task::Unpack(x) => x.evaluate(handle, request, tm),
             Task::User(x) => x.evaluate(handle, request, tm),
@@ -299,6 +406,7 @@ impl Task {
             Task::Wait_For_Http(x) => x.evaluate(handle, request, tm),
             Task::Wait_For_Others(x) => x.evaluate(handle, request, tm),
             Task::Yum(x) => x.evaluate(handle, request, tm),
+            Task::Yum_Repository(x) => x.evaluate(handle, request, tm),
             Task::Zypper(x) => x.evaluate(handle, request, tm),
         }
     }
@@ -312,6 +420,12 @@ impl Task {
         }
     }
 
+    /// Returns true if the task asked for its commands and output to be hidden
+    /// (`with: { no_log: true }`).
+    pub fn is_no_log(&self) -> bool {
+        self.get_with().and_then(|w| w.no_log).unwrap_or(false)
+    }
+
     /// Returns true if this task is a `wait_for_others` barrier task.
     pub fn is_wait_for_others(&self) -> bool {
         matches!(self, Task::Wait_For_Others(_))
@@ -334,38 +448,52 @@ impl Task {
     /// a category here — so the docs reference can't silently drop a module.
     pub fn category(&self) -> &'static str {
         match self {
-            Task::Group(_) | Task::User(_) => "access",
+            Task::Authorized_Key(_) | Task::Group(_) | Task::User(_) => "access",
             Task::Command(_) | Task::External(_) | Task::Shell(_) => "commands",
             Task::Assert(_)
             | Task::Debug(_)
             | Task::Echo(_)
             | Task::Facts(_)
             | Task::Fail(_)
+            | Task::Reboot(_)
             | Task::Self_Locate(_)
             | Task::Set(_)
             | Task::Wait_For_Host(_)
             | Task::Wait_For_Http(_)
             | Task::Wait_For_Others(_) => "control",
-            Task::Copy(_)
+            Task::Archive(_)
+            | Task::Blockinfile(_)
+            | Task::Config_File(_)
+            | Task::Copy(_)
             | Task::Directory(_)
             | Task::Download(_)
             | Task::Fetch(_)
             | Task::File(_)
+            | Task::Find(_)
             | Task::Git(_)
+            | Task::Ini_File(_)
+            | Task::Lineinfile(_)
+            | Task::Link(_)
             | Task::Move(_)
+            | Task::Replace(_)
             | Task::Stat(_)
+            | Task::Synchronize(_)
             | Task::Template(_)
             | Task::Unpack(_) => "files",
             Task::Github_Release(_) => "integrations",
             Task::Instantiate(_) => "inventory",
             Task::Proxmox_Lxc(_) | Task::Proxmox_Migrate(_) | Task::Proxmox_Node(_) => "proxmox",
             Task::Apt(_)
+            | Task::Apt_Repository(_)
             | Task::Dnf(_)
             | Task::Homebrew(_)
+            | Task::Package(_)
             | Task::Pacman(_)
             | Task::Yum(_)
+            | Task::Yum_Repository(_)
             | Task::Zypper(_) => "packages",
-            Task::Sd_Service(_) => "services",
+            Task::Sd_Service(_) | Task::Service(_) => "services",
+            Task::Kernel_Module(_) | Task::Mount(_) | Task::Sysctl(_) => "system",
         }
     }
 }
@@ -375,7 +503,7 @@ mod tests {
     use super::*;
     use strum::IntoEnumIterator;
 
-    const KNOWN_CATEGORIES: [&str; 9] = [
+    const KNOWN_CATEGORIES: [&str; 10] = [
         "access",
         "commands",
         "control",
@@ -385,6 +513,7 @@ mod tests {
         "packages",
         "proxmox",
         "services",
+        "system",
     ];
 
     #[test]
@@ -14,7 +14,7 @@
 // You should have received a copy of the GNU General Public License
 // long with this program.  If not, see <http://www.gnu.org/licenses/>.
 
-use sha2::{Digest, Sha512};
+use sha2::{Digest, Sha256, Sha512};
 
 pub fn sha512(data: &String) -> String {
     let mut hasher = Sha512::new();
@@ -22,3 +22,80 @@ pub fn sha512(data: &String) -> String {
     let result = hasher.finalize();
     hex::encode(result)
 }
+
+// digests that can be pinned in playbooks (see !download), computed locally with
+// sha2 and remotely with sha256sum/sha512sum (shasum on macOS)
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Algorithm {
+    Sha256,
+    Sha512,
+}
+
+impl Algorithm {
+    pub fn from_name(name: &str) -> Result<Self, String> {
+        match name.to_ascii_lowercase().as_str() {
+            "sha256" => Ok(Algorithm::Sha256),
+            "sha512" => Ok(Algorithm::Sha512),
+            _ => Err(format!(
+                "unsupported checksum algorithm '{}', expected sha256 or sha512",
+                name
+            )),
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Algorithm::Sha256 => "sha256",
+            Algorithm::Sha512 => "sha512",
+        }
+    }
+
+    pub fn bits(&self) -> u32 {
+        match self {
+            Algorithm::Sha256 => 256,
+            Algorithm::Sha512 => 512,
+        }
+    }
+
+    pub fn is_digest(&self, value: &str) -> bool {
+        value.len() == (self.bits() / 4) as usize && value.chars().all(|c| c.is_ascii_hexdigit())
+    }
+
+    pub fn digest(&self, data: &[u8]) -> String {
+        match self {
+            Algorithm::Sha256 => hex::encode(Sha256::digest(data)),
+            Algorithm::Sha512 => hex::encode(Sha512::digest(data)),
+        }
+    }
+}
+
+// one line of sha*sum / shasum output, "<digest>  <name>" or "<digest> *<name>" in
+// binary mode. A name holding a backslash, newline or carriage return is written
+// escaped, and the line then starts with a backslash.
+
+pub fn parse_sum_line(line: &str) -> Option<(String, String)> {
+    let (escaped, line) = match line.strip_prefix('\\') {
+        Some(rest) => (true, rest),
+        None => (false, line),
+    };
+    let (sum, name) = line.split_once(' ')?;
+    let name = name.strip_prefix(' ').or_else(|| name.strip_prefix('*'))?;
+    if !escaped {
+        return Some((sum.to_string(), name.to_string()));
+    }
+    let mut out = String::with_capacity(name.len());
+    let mut chars = name.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => match chars.next()? {
+                '\\' => out.push('\\'),
+                'n' => out.push('\n'),
+                'r' => out.push('\r'),
+                _ => return None,
+            },
+            c => out.push(c),
+        }
+    }
+    Some((sum.to_string(), out))
+}
@@ -19,7 +19,8 @@
 
 use crate::inventory::hosts::HostOSType;
 use crate::tasks::FileAttributesInput;
-use crate::tasks::files::Recurse;
+use crate::tasks::checksum::Algorithm;
+use crate::tasks::files::{FileFlags, LinkType, Recurse};
 
 // **IMPORTANT**
 //
@@ -81,6 +82,13 @@ pub fn screen_general_input_loose(input: &str) -> Result<String, String> {
     Ok(input2.to_string())
 }
 
+// quotes free text (header values, passwords, patterns) as one shell word, for
+// commands that are then run with run_unsafe because screening would reject it
+
+pub fn shell_quote(input: &str) -> String {
+    format!("'{}'", input.replace('\'', "'\"'\"'"))
+}
+
 // require that octal inputs be ... octal
 
 pub fn screen_mode(mode: &str) -> Result<String, String> {
@@ -100,18 +108,56 @@ pub fn get_mode_command(os_type: HostOSType, untrusted_path: &str) -> Result<Str
 }
 
 pub fn get_sha512_command(os_type: HostOSType, untrusted_path: &str) -> Result<String, String> {
+    get_checksum_command(os_type, untrusted_path, Algorithm::Sha512)
+}
+
+pub fn get_checksum_command(
+    os_type: HostOSType,
+    untrusted_path: &str,
+    algorithm: Algorithm,
+) -> Result<String, String> {
+    let path = screen_path(untrusted_path)?;
+    match os_type {
+        HostOSType::Linux => Ok(format!("{}sum '{}'", algorithm.name(), path)),
+        HostOSType::MacOS => Ok(format!("shasum -b -a {} '{}'", algorithm.bits(), path)),
+    }
+}
+
+// file contents are base64 encoded in transit so trailing newlines and binary
+// data survive command output handling
+
+pub fn get_file_contents_command(
+    os_type: HostOSType,
+    untrusted_path: &str,
+) -> Result<String, String> {
     let path = screen_path(untrusted_path)?;
     match os_type {
-        HostOSType::Linux => Ok(format!("sha512sum '{}'", path)),
-        HostOSType::MacOS => Ok(format!("shasum -b -a 512 '{}'", path)),
+        HostOSType::Linux => Ok(format!("base64 -w 0 '{}'", path)),
+        HostOSType::MacOS => Ok(format!("base64 -i '{}'", path)),
     }
 }
 
+pub fn get_file_size_command(_os_type: HostOSType, untrusted_path: &str) -> Result<String, String> {
+    let path = screen_path(untrusted_path)?;
+    Ok(format!("wc -c '{}'", path))
+}
+
 pub fn get_ownership_command(_os_type: HostOSType, untrusted_path: &str) -> Result<String, String> {
     let path = screen_path(untrusted_path)?;
     Ok(format!("ls -ld '{}'", path))
 }
 
+pub fn get_user_home_command(os_type: HostOSType, untrusted_user: &str) -> Result<String, String> {
+    let user = screen_general_input_strict(untrusted_user)?;
+    match os_type {
+        HostOSType::Linux => Ok(format!("getent passwd '{}'", user)),
+        HostOSType::MacOS => Ok(format!(
+            "dscl . -read '/Users/{}' PrimaryGroupID NFSHomeDirectory",
+            user
+        )),
+    }
+}
+
 pub fn get_is_directory_command(
     _os_type: HostOSType,
     untrusted_path: &str,
@@ -133,6 +179,69 @@ pub fn get_create_directory_command(
     Ok(format!("mkdir -p '{}'", path))
 }
 
+// one sha512 line per regular file under a directory. Uses find -exec rather than a
+// pipe to xargs so the whole thing runs under sudo, which means the caller has to
+// run it unscreened (the path itself is screened here)
+
+pub fn get_tree_sha512_command(
+    os_type: HostOSType,
+    untrusted_path: &str,
+) -> Result<String, String> {
+    let path = screen_path(untrusted_path)?;
+    match os_type {
+        HostOSType::Linux => Ok(format!("find '{}' -type f -exec sha512sum {{}} +", path)),
+        HostOSType::MacOS => Ok(format!(
+            "find '{}' -type f -exec shasum -b -a 512 {{}} +",
+            path
+        )),
+    }
+}
+
+pub fn get_tree_directories_command(
+    _os_type: HostOSType,
+    untrusted_path: &str,
+) -> Result<String, String> {
+    let path = screen_path(untrusted_path)?;
+    Ok(format!("find '{}' -mindepth 1 -type d", path))
+}
+
+// the remote clock followed by one tab-separated line per entry below path:
+// type (f, d, l, ...), size in bytes, mtime in epoch seconds, octal mode, owner, path
+
+pub fn get_find_command(
+    os_type: HostOSType,
+    untrusted_path: &str,
+    recurse: Recurse,
+) -> Result<String, String> {
+    let path = screen_path(untrusted_path)?;
+    let depth = match recurse {
+        Recurse::No => " -maxdepth 1",
+        Recurse::Yes => "",
+    };
+    match os_type {
+        HostOSType::Linux => Ok(format!(
+            "date +%s && find '{}' -mindepth 1{} -printf '%y\\t%s\\t%T@\\t%m\\t%u\\t%p\\n'",
+            path, depth
+        )),
+        HostOSType::MacOS => Ok(format!(
+            "date +%s && find '{}' -mindepth 1{} -exec stat -f '%HT\t%z\t%m\t%Lp\t%Su\t%N' {{}} +",
+            path, depth
+        )),
+    }
+}
+
+// a copy next to the original, keeping its mode, ownership and timestamps
+
+pub fn get_backup_file_command(
+    _os_type: HostOSType,
+    untrusted_path: &str,
+    suffix: &str,
+) -> Result<String, String> {
+    let path = screen_path(untrusted_path)?;
+    let suffix = screen_path(suffix)?;
+    Ok(format!("cp -a '{}' '{}.backup.{}'", path, path, suffix))
+}
+
 pub fn get_delete_file_command(
     _os_type: HostOSType,
     untrusted_path: &str,
@@ -141,6 +250,51 @@ pub fn get_delete_file_command(
     Ok(format!("rm -f '{}'", path))
 }
 
+// prints the target of a symlink, and fails for anything that is not one
+
+pub fn get_read_link_command(_os_type: HostOSType, untrusted_path: &str) -> Result<String, String> {
+    let path = screen_path(untrusted_path)?;
+    Ok(format!("readlink '{}'", path))
+}
+
+// device and inode of the path itself (not what a symlink points to), equal for
+// two names of the same hard-linked file
+
+pub fn get_file_identity_command(
+    os_type: HostOSType,
+    untrusted_path: &str,
+) -> Result<String, String> {
+    let path = screen_path(untrusted_path)?;
+    match os_type {
+        HostOSType::Linux => Ok(format!("stat --format '%d:%i' '{}'", path)),
+        HostOSType::MacOS => Ok(format!("stat -f '%d:%i' '{}'", path)),
+    }
+}
+
+// -n keeps ln from following an existing symlink to a directory and creating the
+// new link inside it; -f replaces whatever non-directory is already there
+
+pub fn get_create_link_command(
+    _os_type: HostOSType,
+    untrusted_src: &str,
+    untrusted_path: &str,
+    link_type: LinkType,
+    replace: bool,
+) -> Result<String, String> {
+    let src = screen_path(untrusted_src)?;
+    let path = screen_path(untrusted_path)?;
+    let flags = match (link_type, replace) {
+        (LinkType::Soft, false) => "-s",
+        (LinkType::Soft, true) => "-sfn",
+        (LinkType::Hard, false) => "",
+        (LinkType::Hard, true) => "-fn",
+    };
+    match flags.is_empty() {
+        true => Ok(format!("ln '{}' '{}'", src, path)),
+        false => Ok(format!("ln {} '{}' '{}'", flags, src, path)),
+    }
+}
+
 pub fn get_delete_directory_command(
     _os_type: HostOSType,
     untrusted_path: &str,
@@ -219,6 +373,115 @@ pub fn set_mode_command(
     }
 }
 
+// ACLs, SELinux contexts and chattr flags are Linux-only file attributes
+
+pub fn get_acl_command(os_type: HostOSType, untrusted_path: &str) -> Result<String, String> {
+    let path = screen_path(untrusted_path)?;
+    match os_type {
+        HostOSType::Linux => Ok(format!("getfacl -cp '{}'", path)),
+        HostOSType::MacOS => Err(String::from("acl attributes are not supported on macOS")),
+    }
+}
+
+pub fn set_acl_command(
+    os_type: HostOSType,
+    untrusted_path: &str,
+    untrusted_entries: &[String],
+    recurse: Recurse,
+) -> Result<String, String> {
+    let path = screen_path(untrusted_path)?;
+    let mut entries: Vec<String> = Vec::new();
+    for entry in untrusted_entries.iter() {
+        entries.push(screen_general_input_strict(entry)?);
+    }
+    let flags = match recurse {
+        Recurse::No => "",
+        Recurse::Yes => "-R ",
+    };
+    match os_type {
+        HostOSType::Linux => Ok(format!(
+            "setfacl {}-m '{}' '{}'",
+            flags,
+            entries.join(","),
+            path
+        )),
+        HostOSType::MacOS => Err(String::from("acl attributes are not supported on macOS")),
+    }
+}
+
+pub fn get_selinux_context_command(
+    os_type: HostOSType,
+    untrusted_path: &str,
+) -> Result<String, String> {
+    let path = screen_path(untrusted_path)?;
+    match os_type {
+        HostOSType::Linux => Ok(format!("stat --format '%C' '{}'", path)),
+        HostOSType::MacOS => Err(String::from(
+            "SELinux attributes are not supported on macOS",
+        )),
+    }
+}
+
+pub fn set_selinux_context_command(
+    os_type: HostOSType,
+    untrusted_path: &str,
+    untrusted_seuser: Option<&str>,
+    untrusted_setype: Option<&str>,
+    recurse: Recurse,
+) -> Result<String, String> {
+    let path = screen_path(untrusted_path)?;
+    let mut cmd = String::from("chcon");
+    if recurse == Recurse::Yes {
+        cmd.push_str(" -R");
+    }
+    if let Some(seuser) = untrusted_seuser {
+        cmd.push_str(&format!(" -u '{}'", screen_general_input_strict(seuser)?));
+    }
+    if let Some(setype) = untrusted_setype {
+        cmd.push_str(&format!(" -t '{}'", screen_general_input_strict(setype)?));
+    }
+    match os_type {
+        HostOSType::Linux => Ok(format!("{} '{}'", cmd, path)),
+        HostOSType::MacOS => Err(String::from(
+            "SELinux attributes are not supported on macOS",
+        )),
+    }
+}
+
+pub fn get_file_flags_command(os_type: HostOSType, untrusted_path: &str) -> Result<String, String> {
+    let path = screen_path(untrusted_path)?;
+    match os_type {
+        HostOSType::Linux => Ok(format!("lsattr -d '{}'", path)),
+        HostOSType::MacOS => Err(String::from("chattr flags are not supported on macOS")),
+    }
+}
+
+// both flags are always spelled out, so the command leaves the file with exactly these
+
+pub fn set_file_flags_command(
+    os_type: HostOSType,
+    untrusted_path: &str,
+    flags: FileFlags,
+    recurse: Recurse,
+) -> Result<String, String> {
+    let path = screen_path(untrusted_path)?;
+    let sign = |set: bool| if set { '+' } else { '-' };
+    let options = match recurse {
+        Recurse::No => "",
+        Recurse::Yes => "-R ",
+    };
+    match os_type {
+        HostOSType::Linux => Ok(format!(
+            "chattr {}{}i {}a '{}'",
+            options,
+            sign(flags.immutable),
+            sign(flags.append_only),
+            path
+        )),
+        HostOSType::MacOS => Err(String::from("chattr flags are not supported on macOS")),
+    }
+}
+
 pub fn get_arch_command(_os_type: HostOSType) -> Result<String, String> {
     Ok(String::from("uname -m"))
 }
@@ -23,29 +23,57 @@ use std::vec::Vec;
 
 #[derive(Eq, Hash, PartialEq, Clone, Copy, Debug)]
 pub enum Field {
+    Acl,
+    AppendOnly,
     Branch,
     Content,
     Disable,
     Enable,
+    Expires,
     Gecos,
     Gid,
     Group,
     Groups,
+    Hold,
+    Immutable,
+    InactiveDays,
+    Load,
     Location,
+    Locked,
+    Mask,
+    MaxDays,
+    MinDays,
     Mode,
+    Mount,
     Owner,
+    Password,
     Reload,
+    Remount,
     Restart,
+    Selinux,
     Shell,
     Start,
     Stop,
+    Target,
     Uid,
+    Unmask,
+    Unmount,
     Users,
+    Value,
     Version,
+    WarnDays,
 }
 
 impl Field {
     pub fn all_file_attributes() -> Vec<Field> {
-        vec![Field::Owner, Field::Group, Field::Mode]
+        vec![
+            Field::Owner,
+            Field::Group,
+            Field::Mode,
+            Field::Acl,
+            Field::Selinux,
+            Field::Immutable,
+            Field::AppendOnly,
+        ]
     }
 }
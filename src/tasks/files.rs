@@ -18,26 +18,46 @@ use crate::handle::handle::TaskHandle;
 use crate::tasks::TemplateMode;
 use crate::tasks::request::TaskRequest;
 use crate::tasks::response::TaskResponse;
+use crate::tasks::templated::Templated;
 use serde::Deserialize;
 use std::sync::Arc;
 
 // this is storage behind all 'and' and 'with' statements in the program, which
 // are mostly implemented in task_fsm
 
-#[derive(Deserialize, Debug)]
+#[derive(Deserialize, Debug, Default)]
 #[serde(deny_unknown_fields)]
 pub struct FileAttributesInput {
     pub owner: Option<String>,
     pub group: Option<String>,
     pub mode: Option<String>,
+    pub acl: Option<Vec<String>>,
+    pub seuser: Option<String>,
+    pub setype: Option<String>,
+    pub immutable: Option<Templated<bool>>,
+    pub append_only: Option<Templated<bool>>,
 }
 
-#[derive(Deserialize, Debug)]
+#[derive(Deserialize, Debug, Clone, Default)]
 #[serde(deny_unknown_fields)]
 pub struct FileAttributesEvaluated {
     pub owner: Option<String>,
     pub group: Option<String>,
     pub mode: Option<String>,
+    // ACL entries in the long form getfacl prints, e.g. "default:user:deploy:rwx"
+    pub acl: Option<Vec<String>>,
+    pub seuser: Option<String>,
+    pub setype: Option<String>,
+    pub immutable: Option<bool>,
+    pub append_only: Option<bool>,
+}
+
+// the chattr flags jetpack manages. Linux only, and only on filesystems that support them.
+
+#[derive(Debug, Copy, Clone, PartialEq, Default)]
+pub struct FileFlags {
+    pub immutable: bool,
+    pub append_only: bool,
 }
 
 #[derive(Deserialize, Debug, Copy, Clone, PartialEq)]
@@ -46,6 +66,12 @@ pub enum Recurse {
     Yes,
 }
 
+#[derive(Deserialize, Debug, Copy, Clone, PartialEq)]
+pub enum LinkType {
+    Soft,
+    Hard,
+}
+
 impl FileAttributesInput {
     // given an octal string (0o755, 0755, or bare 755), return whether it is valid
     pub fn is_octal_string(mode: &str) -> bool {
@@ -168,11 +194,121 @@ impl FileAttributesInput {
                 &input2.group,
             )?,
             mode: final_mode_value,
+            acl: match &input2.acl {
+                None => None,
+                Some(entries) => {
+                    let mut acl: Vec<String> = Vec::new();
+                    for entry in entries.iter() {
+                        let templated =
+                            handle
+                                .template
+                                .string(request, tm, &String::from("acl"), entry)?;
+                        let normalized = Self::normalize_acl_entry(&templated).map_err(|e| {
+                            handle
+                                .response
+                                .is_failed(request, &format!("field (acl): {}", e))
+                        })?;
+                        acl.push(normalized);
+                    }
+                    Some(acl)
+                }
+            },
+            seuser: handle.template.string_option_no_spaces(
+                request,
+                tm,
+                &String::from("seuser"),
+                &input2.seuser,
+            )?,
+            setype: handle.template.string_option_no_spaces(
+                request,
+                tm,
+                &String::from("setype"),
+                &input2.setype,
+            )?,
+            immutable: handle.template.boolean_option_default_none(
+                request,
+                tm,
+                &String::from("immutable"),
+                &input2.immutable,
+            )?,
+            append_only: handle.template.boolean_option_default_none(
+                request,
+                tm,
+                &String::from("append_only"),
+                &input2.append_only,
+            )?,
         }))
     }
+
+    // rewrites an ACL entry as setfacl accepts it ("u:deploy:rx", "d:g:www:rw") into the
+    // long form getfacl prints ("user:deploy:r-x", "default:group:www:rw-") so that
+    // queries can compare entries as plain strings.
+
+    pub fn normalize_acl_entry(entry: &str) -> Result<String, String> {
+        let mut parts: Vec<&str> = entry.trim().split(':').collect();
+        let default = matches!(parts.first(), Some(&"d") | Some(&"default"));
+        if default {
+            parts.remove(0);
+        }
+        let (tag, qualifier, perms) = match parts.as_slice() {
+            [tag, qualifier, perms] => (*tag, *qualifier, *perms),
+            // other and mask entries may leave out the empty qualifier
+            [tag, perms] => (*tag, "", *perms),
+            _ => {
+                return Err(format!(
+                    "expected [default:]type:qualifier:perms, got '{}'",
+                    entry
+                ));
+            }
+        };
+        let tag = match tag {
+            "u" | "user" => "user",
+            "g" | "group" => "group",
+            "m" | "mask" => "mask",
+            "o" | "other" => "other",
+            _ => return Err(format!("unknown entry type '{}' in '{}'", tag, entry)),
+        };
+        if (tag == "mask" || tag == "other") && !qualifier.is_empty() {
+            return Err(format!("{} entries take no qualifier: '{}'", tag, entry));
+        }
+        if perms.is_empty() || perms.chars().any(|c| !"rwx-".contains(c)) {
+            return Err(format!(
+                "permissions must be made of r, w, x and -: '{}'",
+                entry
+            ));
+        }
+        let mut normalized = String::new();
+        for flag in ['r', 'w', 'x'] {
+            normalized.push(if perms.contains(flag) { flag } else { '-' });
+        }
+        Ok(format!(
+            "{}{}:{}:{}",
+            if default { "default:" } else { "" },
+            tag,
+            qualifier,
+            normalized
+        ))
+    }
 }
 
 impl FileAttributesEvaluated {
+    // whether the attributes ask for any chattr flag at all
+    pub fn has_flags(&self) -> bool {
+        self.immutable.is_some() || self.append_only.is_some()
+    }
+
+    // the flags a file should end up with: whatever the attributes ask for, and for
+    // anything they leave out, the flags the file already had
+
+    pub fn merge_flags(this: Option<&Self>, current: FileFlags) -> FileFlags {
+        FileFlags {
+            immutable: this.and_then(|a| a.immutable).unwrap_or(current.immutable),
+            append_only: this
+                .and_then(|a| a.append_only)
+                .unwrap_or(current.append_only),
+        }
+    }
+
     // if the action has an evaluated Attributes section, the mode will be stored as an octal string like "777", but we need
     // an integer for some internal APIs like the SSH connection put requests.
 
@@ -195,3 +331,42 @@ impl FileAttributesEvaluated {
     }
     */
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::inventory::hosts::HostOSType;
+    use crate::tasks::cmd_library::set_file_flags_command;
+
+    #[test]
+    fn acl_entries_are_normalized_to_getfacl_form() {
+        let n = |e: &str| FileAttributesInput::normalize_acl_entry(e);
+        assert_eq!(n("u:deploy:rx").unwrap(), "user:deploy:r-x");
+        assert_eq!(n("d:g:www:wr").unwrap(), "default:group:www:rw-");
+        assert_eq!(n("other::r").unwrap(), "other::r--");
+        assert_eq!(n("m:rwx").unwrap(), "mask::rwx");
+        assert!(n("z:deploy:rwx").is_err());
+        assert!(n("u:deploy:7").is_err());
+        assert!(n("o:nobody:r").is_err());
+    }
+
+    #[test]
+    fn flags_not_mentioned_keep_their_current_value() {
+        let attributes = FileAttributesEvaluated {
+            immutable: Some(true),
+            ..Default::default()
+        };
+        let current = FileFlags {
+            immutable: false,
+            append_only: true,
+        };
+        let merged = FileAttributesEvaluated::merge_flags(Some(&attributes), current);
+        assert!(merged.immutable && merged.append_only);
+        assert_eq!(FileAttributesEvaluated::merge_flags(None, current), current);
+        assert_eq!(
+            set_file_flags_command(HostOSType::Linux, "/etc/resolv.conf", merged, Recurse::No)
+                .unwrap(),
+            "chattr +i +a '/etc/resolv.conf'"
+        );
+    }
+}
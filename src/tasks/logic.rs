@@ -19,6 +19,7 @@ use crate::handle::template::BlendTarget;
 use crate::playbooks::templar::TemplateMode;
 use crate::tasks::request::TaskRequest;
 use crate::tasks::response::TaskResponse;
+use crate::tasks::templated::Templated;
 use serde::Deserialize;
 use std::sync::Arc;
 
@@ -35,6 +36,9 @@ pub struct PreLogicInput {
     pub tags: Option<Vec<String>>,
     pub delegate_to: Option<String>,
     pub skip_if_exists: Option<String>,
+    /// hide this task's commands, output and messages from the terminal and the log.
+    /// not templated: it has to be known before any variables are rendered.
+    pub no_log: Option<bool>,
 }
 
 #[derive(Deserialize, Debug, Clone)]
@@ -58,9 +62,9 @@ pub struct PreLogicEvaluated {
 #[serde(deny_unknown_fields)]
 pub struct PostLogicInput {
     pub notify: Option<String>,
-    pub ignore_errors: Option<String>,
-    pub retry: Option<String>,
-    pub delay: Option<String>,
+    pub ignore_errors: Option<Templated<bool>>,
+    pub retry: Option<Templated<u64>>,
+    pub delay: Option<Templated<u64>>,
 }
 
 #[derive(Debug)]
@@ -233,6 +237,7 @@ mod tests {
             tags: None,
             delegate_to: None,
             skip_if_exists: Some("/home/{{ user }}/.config".to_string()),
+            no_log: None,
         };
 
         // The evaluated version should have the same raw string
@@ -22,6 +22,7 @@ pub mod files;
 pub mod logic;
 pub mod request;
 pub mod response;
+pub mod templated;
 
 pub use crate::connection::command::cmd_info;
 pub use crate::handle::handle::{CheckRc, TaskHandle};
@@ -34,3 +35,4 @@ pub use crate::tasks::logic::{
 };
 pub use crate::tasks::request::{TaskRequest, TaskRequestType};
 pub use crate::tasks::response::{TaskResponse, TaskStatus};
+pub use crate::tasks::templated::Templated;
@@ -25,7 +25,7 @@ use std::vec::Vec;
 // created directly but by helper functions in handle.rs, see
 // the various modules for examples/usage
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Clone)]
 pub enum TaskStatus {
     IsCreated,
     IsRemoved,
@@ -50,6 +50,9 @@ pub struct TaskResponse {
     pub command_result: Arc<Option<CommandResult>>,
     pub with: Arc<Option<PreLogicEvaluated>>,
     pub and: Arc<Option<PostLogicEvaluated>>,
+    // unified diff of file content changes, only filled in under --diff; !synchronize
+    // uses it for its per-file change list and the package modules for their per-package one
+    pub diff: Option<String>,
 }
 
 //impl TaskResponse {
@@ -0,0 +1,198 @@
+// Jetporch
+// Copyright (C) 2023 - Michael DeHaan <michael@michaeldehaan.net> + contributors
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// long with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use serde::de::{Deserialize, Deserializer, Error};
+use serde::ser::{Serialize, Serializer};
+
+// module parameters that are not strings (booleans, ports, uids, sizes) can be written
+// in YAML either as native scalars (enabled: true, uid: 2048) or as template strings
+// (enabled: "{{ enable_nginx }}"). Templated<T> keeps native values as-is and defers
+// template strings until handle.template renders them and parses the result, so a
+// bad value surfaces as a type error naming the field when the task is validated.
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Templated<T> {
+    Value(T),
+    Template(String),
+}
+
+/// Scalar types that may appear inside a [`Templated`] module parameter.
+pub trait TemplatedScalar: Sized + Clone {
+    /// Human readable type name used in error messages ("a boolean", "an integer").
+    const TYPE_NAME: &'static str;
+
+    /// Convert a native (non-string) YAML scalar, returning None on a type mismatch.
+    fn from_yaml(value: &serde_yaml::Value) -> Option<Self>;
+
+    /// Parse the rendered output of a template string.
+    fn parse_rendered(rendered: &str) -> Option<Self>;
+}
+
+impl TemplatedScalar for bool {
+    const TYPE_NAME: &'static str = "a boolean";
+
+    fn from_yaml(value: &serde_yaml::Value) -> Option<Self> {
+        value.as_bool()
+    }
+
+    fn parse_rendered(rendered: &str) -> Option<Self> {
+        // yes/no and 1/0 are accepted for compatibility with modules that parsed
+        // these by hand before typed parameters existed (download, move)
+        match rendered.trim().to_lowercase().as_str() {
+            "true" | "yes" | "1" => Some(true),
+            "false" | "no" | "0" => Some(false),
+            _ => None,
+        }
+    }
+}
+
+impl TemplatedScalar for u64 {
+    const TYPE_NAME: &'static str = "an integer";
+
+    fn from_yaml(value: &serde_yaml::Value) -> Option<Self> {
+        value.as_u64()
+    }
+
+    fn parse_rendered(rendered: &str) -> Option<Self> {
+        rendered.trim().parse::<u64>().ok()
+    }
+}
+
+impl<T: TemplatedScalar> Templated<T> {
+    /// Resolve a rendered template string into a typed value, or describe why it can't be.
+    pub fn parse_rendered(field: &str, rendered: &str) -> Result<T, String> {
+        T::parse_rendered(rendered).ok_or_else(|| {
+            format!(
+                "field ({}) expected {}, got: {}",
+                field,
+                T::TYPE_NAME,
+                rendered
+            )
+        })
+    }
+}
+
+impl<'de, T: TemplatedScalar> Deserialize<'de> for Templated<T> {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let value = serde_yaml::Value::deserialize(deserializer)?;
+        match value {
+            serde_yaml::Value::String(s) => Ok(Templated::Template(s)),
+            other => match T::from_yaml(&other) {
+                Some(x) => Ok(Templated::Value(x)),
+                None => Err(D::Error::custom(format!(
+                    "expected {} or a template string, found {}",
+                    T::TYPE_NAME,
+                    describe_yaml(&other)
+                ))),
+            },
+        }
+    }
+}
+
+// written back out the way it was read, a native scalar or the template string
+
+impl<T: TemplatedScalar + Serialize> Serialize for Templated<T> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        match self {
+            Templated::Value(x) => x.serialize(serializer),
+            Templated::Template(s) => serializer.serialize_str(s),
+        }
+    }
+}
+
+fn describe_yaml(value: &serde_yaml::Value) -> String {
+    match value {
+        serde_yaml::Value::Null => String::from("null"),
+        serde_yaml::Value::Bool(b) => format!("boolean `{}`", b),
+        serde_yaml::Value::Number(n) => format!("number `{}`", n),
+        serde_yaml::Value::String(s) => format!("string `{}`", s),
+        serde_yaml::Value::Sequence(_) => String::from("a list"),
+        serde_yaml::Value::Mapping(_) => String::from("a mapping"),
+        serde_yaml::Value::Tagged(_) => String::from("a tagged value"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::Deserialize;
+
+    #[derive(Deserialize, Debug)]
+    struct Params {
+        enabled: Option<Templated<bool>>,
+        uid: Option<Templated<u64>>,
+    }
+
+    #[test]
+    fn native_scalars_are_kept_typed() {
+        let p: Params = serde_yaml::from_str("enabled: true\nuid: 2048").unwrap();
+        assert_eq!(p.enabled, Some(Templated::Value(true)));
+        assert_eq!(p.uid, Some(Templated::Value(2048)));
+    }
+
+    #[test]
+    fn strings_are_deferred_as_templates() {
+        let p: Params = serde_yaml::from_str("enabled: \"{{ x }}\"\nuid: \"2048\"").unwrap();
+        assert_eq!(p.enabled, Some(Templated::Template("{{ x }}".to_string())));
+        assert_eq!(p.uid, Some(Templated::Template("2048".to_string())));
+    }
+
+    #[test]
+    fn native_type_mismatch_is_a_parse_error() {
+        let err = serde_yaml::from_str::<Params>("uid: true").unwrap_err();
+        assert!(
+            err.to_string()
+                .contains("expected an integer or a template string, found boolean `true`"),
+            "{}",
+            err
+        );
+        assert!(serde_yaml::from_str::<Params>("enabled: 3").is_err());
+        assert!(serde_yaml::from_str::<Params>("uid: -1").is_err());
+    }
+
+    #[test]
+    fn values_serialize_as_they_were_written() {
+        assert_eq!(
+            serde_yaml::to_string(&Templated::<u64>::Value(2048)).unwrap(),
+            "2048\n"
+        );
+        assert_eq!(
+            serde_yaml::to_string(&Templated::<u64>::Template("{{ mem }}".to_string())).unwrap(),
+            "'{{ mem }}'\n"
+        );
+    }
+
+    #[test]
+    fn rendered_values_are_parsed() {
+        assert!(Templated::<bool>::parse_rendered("enabled", "true").unwrap());
+        assert!(!Templated::<bool>::parse_rendered("enabled", " no\n").unwrap());
+        assert_eq!(
+            Templated::<u64>::parse_rendered("uid", "2048").unwrap(),
+            2048
+        );
+        assert_eq!(
+            Templated::<u64>::parse_rendered("uid", "abc").unwrap_err(),
+            "field (uid) expected an integer, got: abc"
+        );
+        assert!(Templated::<bool>::parse_rendered("enabled", "maybe").is_err());
+    }
+}
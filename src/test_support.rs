@@ -21,7 +21,7 @@ use crate::connection::factory::ConnectionFactory;
 use crate::connection::no::NoFactory;
 use crate::handle::handle::TaskHandle;
 use crate::handle::response::Response;
-use crate::inventory::hosts::Host;
+use crate::inventory::hosts::{Host, HostOSType};
 use crate::inventory::inventory::Inventory;
 use crate::playbooks::context::PlaybookContext;
 use crate::playbooks::traversal::RunState;
@@ -29,13 +29,23 @@ use crate::playbooks::visitor::{CheckMode, PlaybookVisitor};
 use crate::tasks::request::{SudoDetails, TaskRequest};
 use crate::tasks::response::TaskResponse;
 
+// return code and output for a command
+type Reply = Box<dyn Fn(&str) -> (i32, String) + Send + Sync>;
+
 /// A fake `Connection` that records every command it is asked to run and
-/// returns a canned return code for each (default: 0). Commands are recorded in
+/// returns a canned return code and output for each (default: 0, no output).
+/// Commands are recorded in
 /// a shared log so a test can inspect them after the connection has been moved
 /// into a `TaskHandle`.
 pub struct RecordingConnection {
     log: Arc<Mutex<Vec<String>>>,
-    rc_for: Box<dyn Fn(&str) -> i32 + Send + Sync>,
+    reply_for: Reply,
+}
+
+impl Default for RecordingConnection {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl RecordingConnection {
@@ -46,9 +56,15 @@ impl RecordingConnection {
 
     /// Decide the return code per command (e.g. simulate a missing file).
     pub fn with_rc<F: Fn(&str) -> i32 + Send + Sync + 'static>(rc_for: F) -> Self {
+        Self::with_output(move |cmd| (rc_for(cmd), String::new()))
+    }
+
+    /// Decide the return code and output per command (e.g. what `readlink`
+    /// prints), for modules whose decisions depend on what they read back.
+    pub fn with_output<F: Fn(&str) -> (i32, String) + Send + Sync + 'static>(reply_for: F) -> Self {
         Self {
             log: Arc::new(Mutex::new(Vec::new())),
-            rc_for: Box::new(rc_for),
+            reply_for: Box::new(reply_for),
         }
     }
 
@@ -105,12 +121,12 @@ impl Connection for RecordingConnection {
         _forward: Forward,
     ) -> Result<Arc<TaskResponse>, Arc<TaskResponse>> {
         self.log.lock().unwrap().push(cmd.to_string());
-        let rc = (self.rc_for)(cmd);
+        let (rc, out) = (self.reply_for)(cmd);
         Ok(response.command_ok(
             request,
             &Arc::new(Some(CommandResult {
                 cmd: cmd.to_string(),
-                out: String::new(),
+                out,
                 rc,
             })),
         ))
@@ -121,7 +137,9 @@ impl Connection for RecordingConnection {
 /// driving a module's `dispatch()` in a unit test.
 pub fn test_handle(connection: Arc<Mutex<dyn Connection>>) -> Arc<TaskHandle> {
     let inventory = Arc::new(RwLock::new(Inventory::new()));
-    let host = Arc::new(RwLock::new(Host::new(&String::from("testhost"))));
+    let mut host = Host::new(&String::from("testhost"));
+    host.os_type = Some(HostOSType::Linux);
+    let host = Arc::new(RwLock::new(host));
     let parser = CliParser::new();
     let context = Arc::new(RwLock::new(PlaybookContext::new(&parser)));
     let visitor = Arc::new(RwLock::new(PlaybookVisitor::new(CheckMode::No)));
@@ -0,0 +1,113 @@
+// Jetporch
+// Copyright (C) 2023 - Michael DeHaan <michael@michaeldehaan.net> + contributors
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// long with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use similar::TextDiff;
+
+// unified diffs for --diff. File modules build one during Query from the remote
+// content and the content they are about to write, attach it to their response,
+// and the visitor prints and logs it. Large files and binary content only get a
+// one-line note, since neither is useful to read in a terminal or a JSON log.
+
+/// Files larger than this (either side) are summarised rather than diffed.
+pub const MAX_DIFF_BYTES: usize = 256 * 1024;
+
+const CONTEXT_LINES: usize = 3;
+
+// ==============================================================================================================
+// PUBLIC API
+// ==============================================================================================================
+
+/// A unified diff turning `before` into `after` for `path`, or None when the
+/// contents are identical. A missing `before` is a new file and diffs against
+/// /dev/null.
+pub fn unified(path: &str, before: Option<&[u8]>, after: &[u8]) -> Option<String> {
+    if before == Some(after) {
+        return None;
+    }
+    let old = before.unwrap_or_default();
+    if old.len() > MAX_DIFF_BYTES || after.len() > MAX_DIFF_BYTES {
+        return Some(too_large(path));
+    }
+    let (old_text, new_text) = match (as_text(old), as_text(after)) {
+        (Some(o), Some(n)) => (o, n),
+        _ => return Some(format!("diff skipped: {} is binary\n", path)),
+    };
+    let name = path.trim_start_matches('/');
+    let old_header = match before {
+        Some(_) => format!("a/{}", name),
+        None => String::from("/dev/null"),
+    };
+    let diff = TextDiff::from_lines(old_text, new_text);
+    Some(
+        diff.unified_diff()
+            .context_radius(CONTEXT_LINES)
+            .header(&old_header, &format!("b/{}", name))
+            .missing_newline_hint(true)
+            .to_string(),
+    )
+}
+
+/// The note shown instead of a diff for files over [`MAX_DIFF_BYTES`].
+pub fn too_large(path: &str) -> String {
+    format!(
+        "diff skipped: {} is larger than {} bytes\n",
+        path, MAX_DIFF_BYTES
+    )
+}
+
+// ==============================================================================================================
+// PRIVATE
+// ==============================================================================================================
+
+// NUL bytes never show up in text files; invalid UTF-8 could be a legacy
+// encoding but there is no sensible way to print it either way
+
+fn as_text(data: &[u8]) -> Option<&str> {
+    if data.contains(&0) {
+        return None;
+    }
+    std::str::from_utf8(data).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn changed_lines_produce_a_unified_diff() {
+        let diff = unified("/etc/motd", Some(b"one\ntwo\nthree\n"), b"one\n2\nthree\n").unwrap();
+        assert!(diff.starts_with("--- a/etc/motd\n+++ b/etc/motd\n"));
+        assert!(diff.contains("@@ -1,3 +1,3 @@"));
+        assert!(diff.contains("\n-two\n+2\n"));
+        assert!(unified("/etc/motd", Some(b"same\n"), b"same\n").is_none());
+    }
+
+    #[test]
+    fn new_files_diff_against_dev_null() {
+        let diff = unified("/etc/motd", None, b"hello\n").unwrap();
+        assert!(diff.starts_with("--- /dev/null\n+++ b/etc/motd\n"));
+        assert!(diff.contains("+hello\n"));
+    }
+
+    #[test]
+    fn binary_and_oversized_content_is_summarised() {
+        let diff = unified("/bin/tool", Some(b"\x7fELF\0\0"), b"\x7fELF\0\x01").unwrap();
+        assert_eq!(diff, "diff skipped: /bin/tool is binary\n");
+        let big = vec![b'a'; MAX_DIFF_BYTES + 1];
+        let diff = unified("/var/big", Some(b"a"), &big).unwrap();
+        assert!(diff.starts_with("diff skipped: /var/big is larger than"));
+    }
+}
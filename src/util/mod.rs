@@ -14,7 +14,11 @@
 // You should have received a copy of the GNU General Public License
 // long with this program.  If not, see <http://www.gnu.org/licenses/>.
 
+pub mod diff;
 pub mod io;
+pub mod redact;
 pub mod repo;
+pub mod secrets;
 pub mod terminal;
+pub mod vault;
 pub mod yaml;
@@ -0,0 +1,125 @@
+// Jetporch
+// Copyright (C) 2023 - Michael DeHaan <michael@michaeldehaan.net> + contributors
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// long with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use once_cell::sync::Lazy;
+use std::sync::RwLock;
+
+// redaction keeps secret values out of everything jetpack prints or logs. Values
+// are registered as they are loaded (the secrets_inventory overlay, anything
+// decrypted from a vault) and every output path - the visitor, the JET_LOG file,
+// output handlers and the final error message - passes text through redact()
+// before it leaves the process. Rendered commands and their output are plain
+// strings by the time they reach those places, so matching on the values
+// themselves is the only way to catch a secret interpolated into a command line.
+
+pub const MASK: &str = "********";
+
+/// Replacement text for commands, output and messages of tasks marked `no_log`.
+pub const NO_LOG_MASK: &str = "(hidden by no_log)";
+
+// shorter values (ports, "yes", single digits) would mask unrelated output all
+// over the place, so they are not tracked
+const MIN_SECRET_LEN: usize = 4;
+
+// kept sorted longest first so a secret containing another secret is masked whole
+static SECRETS: Lazy<RwLock<Vec<String>>> = Lazy::new(|| RwLock::new(Vec::new()));
+
+// ==============================================================================================================
+// PUBLIC API
+// ==============================================================================================================
+
+/// Track `value` so it is masked in all further output.
+pub fn register_secret(value: &str) {
+    let value = value.trim();
+    if value.chars().count() < MIN_SECRET_LEN {
+        return;
+    }
+    let mut secrets = SECRETS.write().unwrap();
+    if secrets.iter().any(|s| s == value) {
+        return;
+    }
+    secrets.push(value.to_string());
+    secrets.sort_by_key(|s| std::cmp::Reverse(s.len()));
+}
+
+/// Track every string and number inside a YAML value (mapping keys are names,
+/// not secrets, and are left alone).
+pub fn register_yaml_values(value: &serde_yaml::Value) {
+    match value {
+        serde_yaml::Value::String(s) => register_secret(s),
+        serde_yaml::Value::Number(n) => register_secret(&n.to_string()),
+        serde_yaml::Value::Sequence(items) => items.iter().for_each(register_yaml_values),
+        serde_yaml::Value::Mapping(mapping) => mapping.values().for_each(register_yaml_values),
+        serde_yaml::Value::Tagged(tagged) => register_yaml_values(&tagged.value),
+        serde_yaml::Value::Bool(_) | serde_yaml::Value::Null => {}
+    }
+}
+
+/// Replace every tracked secret in `text` with [`MASK`].
+pub fn redact(text: &str) -> String {
+    let secrets = SECRETS.read().unwrap();
+    let mut out = text.to_string();
+    for secret in secrets.iter() {
+        if out.contains(secret.as_str()) {
+            out = out.replace(secret.as_str(), MASK);
+        }
+    }
+    out
+}
+
+/// [`redact`], or hide the text entirely when the task is marked `no_log`.
+pub fn redact_for_task(text: &str, no_log: bool) -> String {
+    match no_log {
+        true => String::from(NO_LOG_MASK),
+        false => redact(text),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // the registry is process wide, so each test uses values no other test registers
+
+    #[test]
+    fn registered_values_are_masked_longest_first() {
+        register_secret("redact-test-pass");
+        register_secret("redact-test-pass-extended");
+        assert_eq!(
+            redact("mysql -p redact-test-pass-extended && echo redact-test-pass"),
+            format!("mysql -p {} && echo {}", MASK, MASK)
+        );
+    }
+
+    #[test]
+    fn short_values_and_yaml_keys_are_ignored() {
+        let value: serde_yaml::Value = serde_yaml::from_str(
+            "redact_key_name: [redact-list-item, 7]\nother: {port: 65431, enabled: true}",
+        )
+        .unwrap();
+        register_yaml_values(&value);
+        assert_eq!(
+            redact("redact_key_name redact-list-item 7 65431 true"),
+            format!("redact_key_name {} 7 {} true", MASK, MASK)
+        );
+    }
+
+    #[test]
+    fn no_log_hides_everything() {
+        assert_eq!(redact_for_task("anything at all", true), NO_LOG_MASK);
+        assert_eq!(redact_for_task("anything at all", false), "anything at all");
+    }
+}
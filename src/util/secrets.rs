@@ -0,0 +1,545 @@
+// Jetporch
+// Copyright (C) 2023 - Michael DeHaan <michael@michaeldehaan.net> + contributors
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// long with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use crate::util::redact;
+use once_cell::sync::Lazy;
+use std::collections::HashMap;
+use std::process::Command;
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+// secrets: values that live outside the automation content altogether and are
+// fetched when a template asks for them:
+//
+//    password: "{{ secret \"vault:kv/db#password\" }}"     (handlebars)
+//    password: "{{ secret('pass:infra/db') }}"             (jinja)
+//
+// A reference is `scheme:path`, optionally followed by `#field`. Each scheme is
+// served by a SecretResolver; env, file, pass (password-store), op (1Password
+// CLI) and vault (HashiCorp Vault KV over HTTP) are built in and more can be
+// added with register_resolver. Resolved values are cached for the rest of the
+// run, so a secret used on a hundred hosts is fetched once, and are registered
+// with util/redact.rs so they never show up in output.
+//
+// Module and provisioner credentials (the Gravity token, Proxmox and Dragonfly
+// API credentials) go through SecretSource, which accepts the same references
+// next to the older inline/file/env forms.
+
+const VAULT_DEFAULT_ADDR: &str = "http://127.0.0.1:8200";
+const VAULT_TIMEOUT_SECS: u64 = 30;
+
+/// Fetches secrets for one reference scheme.
+pub trait SecretResolver: Send + Sync {
+    /// The part of a reference before the first `:`, e.g. `pass`.
+    fn scheme(&self) -> &'static str;
+    /// Look up `path` (everything after the scheme) and, when the reference had
+    /// one, the named `field` within it.
+    fn resolve(&self, path: &str, field: Option<&str>) -> Result<String, String>;
+}
+
+static RESOLVERS: Lazy<RwLock<Vec<Arc<dyn SecretResolver>>>> = Lazy::new(|| {
+    RwLock::new(vec![
+        Arc::new(EnvResolver),
+        Arc::new(FileResolver),
+        Arc::new(PassResolver::default()),
+        Arc::new(OnePasswordResolver::default()),
+        Arc::new(VaultKvResolver::from_env()),
+    ])
+});
+
+static CACHE: Lazy<RwLock<HashMap<String, String>>> = Lazy::new(|| RwLock::new(HashMap::new()));
+
+// ==============================================================================================================
+// PUBLIC API
+// ==============================================================================================================
+
+/// Add a resolver, replacing any existing resolver for the same scheme.
+pub fn register_resolver(resolver: Arc<dyn SecretResolver>) {
+    let mut resolvers = RESOLVERS.write().unwrap();
+    resolvers.retain(|r| r.scheme() != resolver.scheme());
+    resolvers.push(resolver);
+}
+
+/// Resolve a `scheme:path[#field]` reference, fetching it at most once per run.
+pub fn resolve(reference: &str) -> Result<String, String> {
+    let reference = reference.trim();
+    if let Some(value) = CACHE.read().unwrap().get(reference) {
+        return Ok(value.clone());
+    }
+    let (scheme, path, field) = parse_reference(reference)?;
+    let resolver = RESOLVERS
+        .read()
+        .unwrap()
+        .iter()
+        .find(|r| r.scheme() == scheme)
+        .cloned()
+        .ok_or_else(|| format!("secret {}: unknown secret backend '{}'", reference, scheme))?;
+    let value = resolver
+        .resolve(path, field)
+        .map_err(|e| format!("secret {}: {}", reference, e))?;
+    redact::register_secret(&value);
+    CACHE
+        .write()
+        .unwrap()
+        .insert(reference.to_string(), value.clone());
+    Ok(value)
+}
+
+/// Where a module or provisioner credential may come from. Sources are tried in
+/// field order: an inline value, a file (trimmed), an environment variable, then
+/// a secret reference. Prefer the last three so the secret never lives in
+/// inventory.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct SecretSource<'a> {
+    pub value: Option<&'a str>,
+    pub file: Option<&'a str>,
+    pub env: Option<&'a str>,
+    pub reference: Option<&'a str>,
+}
+
+impl<'a> SecretSource<'a> {
+    /// The sources for inventory variable `name`: `name` itself, `name_file`,
+    /// `name_env` and `name_ref`.
+    pub fn from_vars(vars: &'a serde_yaml::Mapping, name: &str) -> Self {
+        let get = |key: String| vars.get(key.as_str()).and_then(|v| v.as_str());
+        Self {
+            value: get(name.to_string()),
+            file: get(format!("{}_file", name)),
+            env: get(format!("{}_env", name)),
+            reference: get(format!("{}_ref", name)),
+        }
+    }
+
+    /// The credential, or None when no source is configured. `what` names the
+    /// credential in error messages.
+    pub fn resolve(&self, what: &str) -> Result<Option<String>, String> {
+        let value = if let Some(value) = self.value {
+            value.to_string()
+        } else if let Some(path) = self.file {
+            std::fs::read_to_string(path)
+                .map(|s| s.trim().to_string())
+                .map_err(|e| format!("Failed to read {} file '{}': {}", what, path, e))?
+        } else if let Some(name) = self.env {
+            std::env::var(name).map_err(|_| format!("{} env var '{}' is not set", what, name))?
+        } else if let Some(reference) = self.reference {
+            resolve(reference)?
+        } else {
+            return Ok(None);
+        };
+        redact::register_secret(&value);
+        Ok(Some(value))
+    }
+}
+
+// ==============================================================================================================
+// REFERENCES
+// ==============================================================================================================
+
+fn parse_reference(reference: &str) -> Result<(&str, &str, Option<&str>), String> {
+    let (scheme, rest) = reference.split_once(':').ok_or_else(|| {
+        format!(
+            "secret {}: expected a reference like 'pass:path' or 'vault:mount/path#field'",
+            reference
+        )
+    })?;
+    let (path, field) = match rest.rsplit_once('#') {
+        Some((path, field)) if !field.is_empty() => (path, Some(field)),
+        _ => (rest, None),
+    };
+    if scheme.is_empty() || path.is_empty() {
+        return Err(format!("secret {}: missing backend or path", reference));
+    }
+    Ok((scheme, path, field))
+}
+
+fn no_field(scheme: &str, field: Option<&str>) -> Result<(), String> {
+    match field {
+        Some(field) => Err(format!(
+            "the {} backend does not support #{}",
+            scheme, field
+        )),
+        None => Ok(()),
+    }
+}
+
+// run a secrets CLI, returning stdout. stderr is the useful part of a failure
+// ("not signed in", "is not in the password store").
+
+fn run_cli(program: &str, args: &[&str]) -> Result<String, String> {
+    let output = Command::new(program)
+        .args(args)
+        .output()
+        .map_err(|e| format!("unable to run {}: {}", program, e))?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(format!("{} failed: {}", program, stderr.trim()));
+    }
+    String::from_utf8(output.stdout).map_err(|_| format!("{} output is not valid UTF-8", program))
+}
+
+// ==============================================================================================================
+// BUILT IN RESOLVERS
+// ==============================================================================================================
+
+/// `env:NAME`
+pub struct EnvResolver;
+
+impl SecretResolver for EnvResolver {
+    fn scheme(&self) -> &'static str {
+        "env"
+    }
+
+    fn resolve(&self, path: &str, field: Option<&str>) -> Result<String, String> {
+        no_field(self.scheme(), field)?;
+        std::env::var(path).map_err(|_| format!("env var '{}' is not set", path))
+    }
+}
+
+/// `file:/path/to/secret`, trimmed of surrounding whitespace.
+pub struct FileResolver;
+
+impl SecretResolver for FileResolver {
+    fn scheme(&self) -> &'static str {
+        "file"
+    }
+
+    fn resolve(&self, path: &str, field: Option<&str>) -> Result<String, String> {
+        no_field(self.scheme(), field)?;
+        std::fs::read_to_string(path)
+            .map(|s| s.trim().to_string())
+            .map_err(|e| format!("failed to read '{}': {}", path, e))
+    }
+}
+
+/// `pass:entry` is the first line of a password-store entry (the password, by
+/// convention); `pass:entry#login` is the value of a `login: ...` line below it.
+pub struct PassResolver {
+    pub program: String,
+}
+
+impl Default for PassResolver {
+    fn default() -> Self {
+        Self {
+            program: String::from("pass"),
+        }
+    }
+}
+
+impl SecretResolver for PassResolver {
+    fn scheme(&self) -> &'static str {
+        "pass"
+    }
+
+    fn resolve(&self, path: &str, field: Option<&str>) -> Result<String, String> {
+        let output = run_cli(&self.program, &["show", path])?;
+        let mut lines = output.lines();
+        match field {
+            None => lines
+                .next()
+                .map(|line| line.to_string())
+                .ok_or_else(|| String::from("entry is empty")),
+            Some(field) => lines
+                .skip(1)
+                .filter_map(|line| line.split_once(':'))
+                .find(|(key, _)| key.trim() == field)
+                .map(|(_, value)| value.trim().to_string())
+                .ok_or_else(|| format!("entry has no '{}' line", field)),
+        }
+    }
+}
+
+/// `op:vault/item/field` (or the 1Password native `op://vault/item/field`),
+/// read with `op read`. `#field` is appended as the last path segment.
+pub struct OnePasswordResolver {
+    pub program: String,
+}
+
+impl Default for OnePasswordResolver {
+    fn default() -> Self {
+        Self {
+            program: String::from("op"),
+        }
+    }
+}
+
+impl SecretResolver for OnePasswordResolver {
+    fn scheme(&self) -> &'static str {
+        "op"
+    }
+
+    fn resolve(&self, path: &str, field: Option<&str>) -> Result<String, String> {
+        let mut uri = format!("op://{}", path.trim_start_matches('/'));
+        if let Some(field) = field {
+            uri = format!("{}/{}", uri.trim_end_matches('/'), field);
+        }
+        let output = run_cli(&self.program, &["read", "--no-newline", &uri])?;
+        Ok(output.trim_end_matches('\n').to_string())
+    }
+}
+
+/// `vault:mount/path#field` from a HashiCorp Vault (or OpenBao) KV secrets
+/// engine. The server comes from `VAULT_ADDR` and the token from `VAULT_TOKEN`
+/// or `~/.vault-token`, as with the vault CLI. KV version 2 is tried first,
+/// falling back to version 1 when the mount has no `data/` endpoint. Without
+/// `#field` the secret must hold exactly one key.
+pub struct VaultKvResolver {
+    pub addr: String,
+    pub token: Option<String>,
+    pub namespace: Option<String>,
+}
+
+impl VaultKvResolver {
+    pub fn from_env() -> Self {
+        let token = std::env::var("VAULT_TOKEN").ok().or_else(|| {
+            let home = std::env::var("HOME").ok()?;
+            std::fs::read_to_string(format!("{}/.vault-token", home))
+                .ok()
+                .map(|t| t.trim().to_string())
+        });
+        Self {
+            addr: std::env::var("VAULT_ADDR").unwrap_or_else(|_| VAULT_DEFAULT_ADDR.to_string()),
+            token,
+            namespace: std::env::var("VAULT_NAMESPACE").ok(),
+        }
+    }
+
+    async fn get(&self, url: &str, token: &str) -> Result<(u16, String), String> {
+        let client = reqwest::Client::builder()
+            .timeout(Duration::from_secs(VAULT_TIMEOUT_SECS))
+            .build()
+            .map_err(|e| format!("failed to build HTTP client: {}", e))?;
+        let mut request = client.get(url).header("X-Vault-Token", token);
+        if let Some(namespace) = self.namespace.as_deref() {
+            request = request.header("X-Vault-Namespace", namespace);
+        }
+        let response = request
+            .send()
+            .await
+            .map_err(|e| format!("GET {}: {}", url, e))?;
+        let status = response.status().as_u16();
+        let body = response
+            .text()
+            .await
+            .map_err(|e| format!("GET {}: {}", url, e))?;
+        Ok((status, body))
+    }
+
+    async fn read(&self, path: &str) -> Result<serde_json::Map<String, serde_json::Value>, String> {
+        let token = self
+            .token
+            .as_deref()
+            .ok_or_else(|| String::from("no Vault token: set VAULT_TOKEN or log in with vault"))?;
+        let base = self.addr.trim_end_matches('/');
+        let path = path.trim_matches('/');
+        let (mount, rest) = path
+            .split_once('/')
+            .ok_or_else(|| String::from("expected mount/path"))?;
+
+        let (status, body) = self
+            .get(&format!("{}/v1/{}/data/{}", base, mount, rest), token)
+            .await?;
+        let (status, body, v2) = match status {
+            404 => {
+                let (status, body) = self.get(&format!("{}/v1/{}", base, path), token).await?;
+                (status, body, false)
+            }
+            _ => (status, body, true),
+        };
+        match status {
+            200 => {}
+            404 => return Err(String::from("not found")),
+            _ => return Err(format!("Vault returned HTTP {}: {}", status, body.trim())),
+        }
+
+        let json: serde_json::Value =
+            serde_json::from_str(&body).map_err(|e| format!("invalid Vault response: {}", e))?;
+        let data = match v2 {
+            true => &json["data"]["data"],
+            false => &json["data"],
+        };
+        data.as_object()
+            .cloned()
+            .ok_or_else(|| String::from("Vault response has no data"))
+    }
+}
+
+impl SecretResolver for VaultKvResolver {
+    fn scheme(&self) -> &'static str {
+        "vault"
+    }
+
+    fn resolve(&self, path: &str, field: Option<&str>) -> Result<String, String> {
+        let data = crate::runtime::block_on(self.read(path))?;
+        let value = match field {
+            Some(field) => data
+                .get(field)
+                .ok_or_else(|| format!("secret has no field '{}'", field))?,
+            None => match data.len() {
+                1 => data.values().next().unwrap(),
+                _ => {
+                    return Err(String::from(
+                        "secret has several fields, select one with #field",
+                    ));
+                }
+            },
+        };
+        Ok(match value {
+            serde_json::Value::String(s) => s.clone(),
+            other => other.to_string(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::provisioners::dragonfly::tests::MockServer;
+    use std::os::unix::fs::PermissionsExt;
+    use tempfile::TempDir;
+
+    // a shell script standing in for pass/op that echoes canned output
+    fn stub_cli(dir: &TempDir, name: &str, output: &str) -> String {
+        let path = dir.path().join(name);
+        std::fs::write(&path, format!("#!/bin/sh\ncat <<'EOF'\n{}\nEOF\n", output)).unwrap();
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755)).unwrap();
+        path.to_string_lossy().to_string()
+    }
+
+    #[test]
+    fn references_split_into_scheme_path_and_field() {
+        assert_eq!(
+            parse_reference("vault:kv/db#password").unwrap(),
+            ("vault", "kv/db", Some("password"))
+        );
+        assert_eq!(
+            parse_reference("op://infra/db/password").unwrap(),
+            ("op", "//infra/db/password", None)
+        );
+        assert!(parse_reference("no-scheme").is_err());
+        assert!(parse_reference("pass:").is_err());
+    }
+
+    #[test]
+    fn pass_reads_first_line_or_named_field() {
+        let dir = TempDir::new().unwrap();
+        let pass = PassResolver {
+            program: stub_cli(&dir, "pass", "s3cr3t-pass\nlogin: app\nurl: https://x"),
+        };
+        assert_eq!(pass.resolve("infra/db", None).unwrap(), "s3cr3t-pass");
+        assert_eq!(pass.resolve("infra/db", Some("login")).unwrap(), "app");
+        assert!(pass.resolve("infra/db", Some("missing")).is_err());
+    }
+
+    #[test]
+    fn op_builds_a_secret_uri() {
+        let dir = TempDir::new().unwrap();
+        // echo the arguments back so the test can see the URI that was built
+        let path = dir.path().join("op");
+        std::fs::write(&path, "#!/bin/sh\nprintf '%s' \"$3\"\n").unwrap();
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755)).unwrap();
+        let op = OnePasswordResolver {
+            program: path.to_string_lossy().to_string(),
+        };
+        assert_eq!(
+            op.resolve("//infra/db/password", None).unwrap(),
+            "op://infra/db/password"
+        );
+        assert_eq!(
+            op.resolve("infra/db", Some("password")).unwrap(),
+            "op://infra/db/password"
+        );
+    }
+
+    #[test]
+    fn vault_reads_kv2_then_falls_back_to_kv1() {
+        let server = MockServer::start(|req| match req.path.as_str() {
+            "/v1/kv/data/db" => (
+                200,
+                r#"{"data":{"data":{"password":"kv2-secret","port":5432},"metadata":{}}}"#
+                    .to_string(),
+            ),
+            "/v1/old/data/db" => (404, r#"{"errors":[]}"#.to_string()),
+            "/v1/old/db" => (200, r#"{"data":{"password":"kv1-secret"}}"#.to_string()),
+            _ => (404, r#"{"errors":[]}"#.to_string()),
+        });
+        let vault = VaultKvResolver {
+            addr: server.url(),
+            token: Some(String::from("root")),
+            namespace: None,
+        };
+        assert_eq!(
+            vault.resolve("kv/db", Some("password")).unwrap(),
+            "kv2-secret"
+        );
+        assert_eq!(vault.resolve("kv/db", Some("port")).unwrap(), "5432");
+        assert!(vault.resolve("kv/db", None).unwrap_err().contains("#field"));
+        assert_eq!(vault.resolve("old/db", None).unwrap(), "kv1-secret");
+        assert_eq!(vault.resolve("kv/nope", None).unwrap_err(), "not found");
+    }
+
+    #[test]
+    fn resolved_values_are_cached_and_redacted() {
+        let dir = TempDir::new().unwrap();
+        let file = dir.path().join("token");
+        std::fs::write(&file, "secrets-test-token\n").unwrap();
+        let reference = format!("file:{}", file.display());
+
+        assert_eq!(resolve(&reference).unwrap(), "secrets-test-token");
+        std::fs::remove_file(&file).unwrap();
+        assert_eq!(resolve(&reference).unwrap(), "secrets-test-token");
+        assert_eq!(redact::redact("secrets-test-token"), redact::MASK);
+        assert!(
+            resolve("nosuch:thing")
+                .unwrap_err()
+                .contains("unknown secret backend")
+        );
+    }
+
+    #[test]
+    fn secret_source_from_vars_prefers_inline_then_file_then_env_then_ref() {
+        let dir = TempDir::new().unwrap();
+        let file = dir.path().join("pw");
+        std::fs::write(&file, "  from-file  \n").unwrap();
+        let vars: serde_yaml::Mapping = serde_yaml::from_str(&format!(
+            "api_password_file: {}\napi_password_env: JETPACK_SECRETS_NEVER_SET_Q7\n",
+            file.display()
+        ))
+        .unwrap();
+        let source = SecretSource::from_vars(&vars, "api_password");
+        assert_eq!(
+            source.resolve("API password").unwrap().unwrap(),
+            "from-file"
+        );
+
+        let vars: serde_yaml::Mapping =
+            serde_yaml::from_str("api_password_env: JETPACK_SECRETS_NEVER_SET_Q7\n").unwrap();
+        let err = SecretSource::from_vars(&vars, "api_password")
+            .resolve("API password")
+            .unwrap_err();
+        assert_eq!(
+            err,
+            "API password env var 'JETPACK_SECRETS_NEVER_SET_Q7' is not set"
+        );
+
+        let vars = serde_yaml::Mapping::new();
+        assert!(
+            SecretSource::from_vars(&vars, "api_password")
+                .resolve("API password")
+                .unwrap()
+                .is_none()
+        );
+    }
+}
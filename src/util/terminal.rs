@@ -14,6 +14,8 @@
 // You should have received a copy of the GNU General Public License
 // long with this program.  If not, see <http://www.gnu.org/licenses/>.
 
+use inline_colorization::{color_cyan, color_green, color_red, color_reset};
+
 pub fn markdown_print(markdown: &str) {
     termimad::print_text(markdown);
 }
@@ -48,6 +50,25 @@ pub fn captioned_display(caption: &str, body: &str) {
     println!();
 }
 
+// unified diffs are printed indented under the host line, colored like git does
+
+pub fn diff_print(diff: &str) {
+    for line in diff.lines() {
+        let color = if line.starts_with("+++") || line.starts_with("---") {
+            color_reset
+        } else if line.starts_with('+') {
+            color_green
+        } else if line.starts_with('-') {
+            color_red
+        } else if line.starts_with("@@") {
+            color_cyan
+        } else {
+            color_reset
+        };
+        println!("    {color}{}{color_reset}", line);
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
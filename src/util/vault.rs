@@ -0,0 +1,469 @@
+// Jetporch
+// Copyright (C) 2023 - Michael DeHaan <michael@michaeldehaan.net> + contributors
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// long with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use crate::util::redact;
+use aes_gcm::aead::{Aead, AeadCore, KeyInit, OsRng};
+use aes_gcm::{Aes256Gcm, Key, Nonce};
+use argon2::Argon2;
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use once_cell::sync::Lazy;
+use std::collections::HashMap;
+use std::io::{IsTerminal, Read};
+use std::os::fd::AsRawFd;
+use std::path::{Path, PathBuf};
+use std::sync::RwLock;
+
+// vault: encrypted secrets that can be committed alongside the rest of the
+// automation content. A whole file (group_vars/host_vars, vars_files) can be
+// encrypted with `jetp vault encrypt`, or a single value inside an otherwise
+// plaintext file can be tagged:
+//
+//    db_password: !vault |
+//      $JETPACK_VAULT;1.0;AES256-GCM
+//      3q2+7w...
+//
+// Both use the same envelope: a header line followed by base64 of
+// salt || nonce || ciphertext. The key is derived from the vault password with
+// Argon2id and the payload is sealed with AES-256-GCM, so a wrong password or
+// a tampered file fails loudly rather than producing garbage.
+
+pub const VAULT_HEADER: &str = "$JETPACK_VAULT;1.0;AES256-GCM";
+pub const VAULT_TAG: &str = "vault";
+
+pub const ENV_VAULT_PASSWORD_FILE: &str = "JET_VAULT_PASSWORD_FILE";
+pub const ENV_VAULT_PASSWORD: &str = "JET_VAULT_PASSWORD";
+
+const SALT_LEN: usize = 16;
+const NONCE_LEN: usize = 12;
+const WRAP_WIDTH: usize = 76;
+
+// the password file given on the command line, consulted before the environment
+static PASSWORD_FILE: Lazy<RwLock<Option<PathBuf>>> = Lazy::new(|| RwLock::new(None));
+// the password, resolved the first time encrypted content is seen
+static PASSWORD: Lazy<RwLock<Option<String>>> = Lazy::new(|| RwLock::new(None));
+// key derivation is deliberately slow, so keys are cached per (password, salt)
+type KeyCache = HashMap<(String, Vec<u8>), [u8; 32]>;
+static DERIVED_KEYS: Lazy<RwLock<KeyCache>> = Lazy::new(|| RwLock::new(HashMap::new()));
+
+// ==============================================================================================================
+// PUBLIC API
+// ==============================================================================================================
+
+/// Use this file for the vault password instead of $JET_VAULT_PASSWORD_FILE
+/// or $JET_VAULT_PASSWORD (--vault-password-file).
+pub fn set_password_file(path: Option<PathBuf>) {
+    *PASSWORD_FILE.write().unwrap() = path;
+    *PASSWORD.write().unwrap() = None;
+}
+
+/// The vault password for this run. Resolved once, from --vault-password-file,
+/// then $JET_VAULT_PASSWORD_FILE, then $JET_VAULT_PASSWORD, and finally by
+/// prompting when attached to a terminal.
+pub fn password() -> Result<String, String> {
+    if let Some(password) = PASSWORD.read().unwrap().as_ref() {
+        return Ok(password.clone());
+    }
+    let configured = PASSWORD_FILE.read().unwrap().clone();
+    let password = resolve_password(configured.as_deref(), "vault password")?;
+    *PASSWORD.write().unwrap() = Some(password.clone());
+    Ok(password)
+}
+
+/// Resolve a password from an explicit file, falling back to the environment and
+/// then to a terminal prompt labelled with `prompt`.
+pub fn resolve_password(file: Option<&Path>, prompt: &str) -> Result<String, String> {
+    if let Some(path) = file {
+        return read_password_file(path);
+    }
+    if let Ok(path) = std::env::var(ENV_VAULT_PASSWORD_FILE)
+        && !path.is_empty()
+    {
+        return read_password_file(Path::new(&path));
+    }
+    if let Ok(password) = std::env::var(ENV_VAULT_PASSWORD)
+        && !password.is_empty()
+    {
+        return Ok(password);
+    }
+    if std::io::stdin().is_terminal() {
+        return prompt_password(prompt);
+    }
+    Err(format!(
+        "encrypted content found but no vault password was supplied: pass --vault-password-file, or set ${} or ${}",
+        ENV_VAULT_PASSWORD_FILE, ENV_VAULT_PASSWORD
+    ))
+}
+
+/// Ask for a password on the terminal without echoing it.
+pub fn prompt_password(prompt: &str) -> Result<String, String> {
+    let value = read_hidden_line(&format!("enter {}: ", prompt))?;
+    non_empty(value, prompt)
+}
+
+/// Read one line from the terminal with echo turned off, for secrets. Fails when
+/// standard input is not a terminal rather than reading a piped line in the clear.
+pub fn read_hidden_line(prompt: &str) -> Result<String, String> {
+    let stdin = std::io::stdin();
+    if !stdin.is_terminal() {
+        return Err(String::from(
+            "cannot prompt for a secret: standard input is not a terminal",
+        ));
+    }
+    eprint!("{}", prompt);
+    let fd = stdin.as_raw_fd();
+    // SAFETY: termios is plain data, filled in by tcgetattr before use
+    let mut saved: libc::termios = unsafe { std::mem::zeroed() };
+    if unsafe { libc::tcgetattr(fd, &mut saved) } != 0 {
+        return Err(format!(
+            "failure reading terminal settings: {}",
+            std::io::Error::last_os_error()
+        ));
+    }
+    let mut hidden = saved;
+    hidden.c_lflag &= !libc::ECHO;
+    hidden.c_lflag |= libc::ECHONL;
+    unsafe { libc::tcsetattr(fd, libc::TCSANOW, &hidden) };
+    let mut value = String::new();
+    let result = stdin.read_line(&mut value);
+    unsafe { libc::tcsetattr(fd, libc::TCSANOW, &saved) };
+    result.map_err(|e| format!("failure reading input: {}", e))?;
+    Ok(value.trim_end_matches(['\r', '\n']).to_string())
+}
+
+/// Read a password from the first line of a file.
+pub fn read_password_file(path: &Path) -> Result<String, String> {
+    let mut file = crate::util::io::jet_file_open(path)?;
+    let mut buffer = String::new();
+    file.read_to_string(&mut buffer).map_err(|e| {
+        format!(
+            "unable to read vault password file: {}, {}",
+            path.display(),
+            e
+        )
+    })?;
+    // only the first line counts, so files written by `echo` or an editor work
+    let first_line = buffer.lines().next().unwrap_or("").to_string();
+    non_empty(
+        first_line,
+        &format!("vault password file {}", path.display()),
+    )
+}
+
+pub fn is_encrypted(content: &str) -> bool {
+    content.trim_start().starts_with(VAULT_HEADER)
+}
+
+/// Seal `plaintext` into a vault envelope (header line plus wrapped base64).
+pub fn encrypt(plaintext: &[u8], password: &str) -> Result<String, String> {
+    let mut salt = [0u8; SALT_LEN];
+    aes_gcm::aead::rand_core::RngCore::fill_bytes(&mut OsRng, &mut salt);
+    let key = derive_key(password, &salt)?;
+    let cipher = Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&key));
+    let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+    let ciphertext = cipher
+        .encrypt(&nonce, plaintext)
+        .map_err(|_| String::from("vault encryption failed"))?;
+
+    let mut payload = Vec::with_capacity(SALT_LEN + NONCE_LEN + ciphertext.len());
+    payload.extend_from_slice(&salt);
+    payload.extend_from_slice(nonce.as_slice());
+    payload.extend_from_slice(&ciphertext);
+    let encoded = BASE64.encode(payload);
+
+    let mut out = String::from(VAULT_HEADER);
+    out.push('\n');
+    for chunk in encoded.as_bytes().chunks(WRAP_WIDTH) {
+        out.push_str(std::str::from_utf8(chunk).unwrap());
+        out.push('\n');
+    }
+    Ok(out)
+}
+
+/// Open a vault envelope produced by [`encrypt`].
+pub fn decrypt(envelope: &str, password: &str) -> Result<Vec<u8>, String> {
+    let mut lines = envelope.trim().lines();
+    match lines.next() {
+        Some(header) if header.trim() == VAULT_HEADER => {}
+        Some(header) if header.trim().starts_with("$JETPACK_VAULT;") => {
+            return Err(format!("unsupported vault format: {}", header.trim()));
+        }
+        _ => return Err(String::from("not a vault envelope")),
+    }
+    let encoded: String = lines.flat_map(|l| l.trim().chars()).collect();
+    let payload = BASE64
+        .decode(encoded)
+        .map_err(|e| format!("vault payload is not valid base64: {}", e))?;
+    if payload.len() < SALT_LEN + NONCE_LEN {
+        return Err(String::from("vault payload is truncated"));
+    }
+    let (salt, rest) = payload.split_at(SALT_LEN);
+    let (nonce, ciphertext) = rest.split_at(NONCE_LEN);
+    let key = derive_key(password, salt)?;
+    let cipher = Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&key));
+    cipher
+        .decrypt(Nonce::from_slice(nonce), ciphertext)
+        .map_err(|_| String::from("vault decryption failed: wrong password or corrupted data"))
+}
+
+/// Decrypt an envelope that holds text, with the run's vault password.
+pub fn decrypt_to_string(envelope: &str) -> Result<String, String> {
+    let plaintext = decrypt(envelope, &password()?)?;
+    String::from_utf8(plaintext).map_err(|_| String::from("vault content is not valid UTF-8"))
+}
+
+/// Read a YAML file that may be vault encrypted, returning its plaintext.
+pub fn read_yaml_file(path: &Path) -> Result<String, String> {
+    let content = crate::util::io::read_local_file(path)?;
+    if !is_encrypted(&content) {
+        return Ok(content);
+    }
+    let plaintext =
+        decrypt_to_string(&content).map_err(|e| format!("{}: {}", path.display(), e))?;
+    // everything in an encrypted file is secret, keep it out of output and logs
+    if let Ok(value) = serde_yaml::from_str::<serde_yaml::Value>(&plaintext) {
+        redact::register_yaml_values(&value);
+    }
+    Ok(plaintext)
+}
+
+/// Replace every `!vault` tagged string inside `value` with its decrypted text.
+/// Decrypted values are parsed as YAML scalars, so an encrypted port stays a number.
+pub fn decrypt_tagged_values(value: &mut serde_yaml::Value) -> Result<(), String> {
+    match value {
+        serde_yaml::Value::Mapping(mapping) => {
+            for (_, v) in mapping.iter_mut() {
+                decrypt_tagged_values(v)?;
+            }
+        }
+        serde_yaml::Value::Sequence(items) => {
+            for v in items.iter_mut() {
+                decrypt_tagged_values(v)?;
+            }
+        }
+        serde_yaml::Value::Tagged(tagged) if tagged.tag == VAULT_TAG => {
+            let envelope = match &tagged.value {
+                serde_yaml::Value::String(s) => s.clone(),
+                _ => return Err(String::from("!vault values must be strings")),
+            };
+            let plaintext = decrypt_to_string(&envelope)?;
+            redact::register_secret(&plaintext);
+            *value = scalar_from_plaintext(plaintext);
+        }
+        serde_yaml::Value::Tagged(tagged) => decrypt_tagged_values(&mut tagged.value)?,
+        _ => {}
+    }
+    Ok(())
+}
+
+/// The same as [`decrypt_tagged_values`], for the mappings most callers hold.
+pub fn decrypt_mapping(mapping: &mut serde_yaml::Mapping) -> Result<(), String> {
+    for (_, v) in mapping.iter_mut() {
+        decrypt_tagged_values(v)?;
+    }
+    Ok(())
+}
+
+/// Format an encrypted value for pasting into a YAML file under `name`.
+pub fn format_tagged_value(name: &str, envelope: &str) -> String {
+    let mut out = format!("{}: !{} |\n", name, VAULT_TAG);
+    for line in envelope.lines() {
+        out.push_str("  ");
+        out.push_str(line);
+        out.push('\n');
+    }
+    out
+}
+
+/// Re-encrypt every envelope in `content` (a whole encrypted file, or `!vault`
+/// blocks inside a plaintext one) from `old_password` to `new_password`, keeping
+/// each block's indentation. Returns the new text and how many envelopes changed.
+pub fn rekey_text(
+    content: &str,
+    old_password: &str,
+    new_password: &str,
+) -> Result<(String, usize), String> {
+    let lines: Vec<&str> = content.lines().collect();
+    let mut out = String::new();
+    let mut count = 0;
+    let mut i = 0;
+    while i < lines.len() {
+        let line = lines[i];
+        if line.trim() != VAULT_HEADER {
+            out.push_str(line);
+            out.push('\n');
+            i += 1;
+            continue;
+        }
+        let indent = &line[..line.len() - line.trim_start().len()];
+        let mut envelope = format!("{}\n", VAULT_HEADER);
+        i += 1;
+        while i < lines.len()
+            && lines[i].starts_with(indent)
+            && is_base64_line(&lines[i][indent.len()..])
+        {
+            envelope.push_str(lines[i].trim());
+            envelope.push('\n');
+            i += 1;
+        }
+        let plaintext = decrypt(&envelope, old_password)?;
+        for sealed in encrypt(&plaintext, new_password)?.lines() {
+            out.push_str(indent);
+            out.push_str(sealed);
+            out.push('\n');
+        }
+        count += 1;
+    }
+    if !content.ends_with('\n') {
+        out.pop();
+    }
+    Ok((out, count))
+}
+
+// ==============================================================================================================
+// PRIVATE INTERNALS
+// ==============================================================================================================
+
+fn derive_key(password: &str, salt: &[u8]) -> Result<[u8; 32], String> {
+    let cache_key = (password.to_string(), salt.to_vec());
+    if let Some(key) = DERIVED_KEYS.read().unwrap().get(&cache_key) {
+        return Ok(*key);
+    }
+    let mut key = [0u8; 32];
+    Argon2::default()
+        .hash_password_into(password.as_bytes(), salt, &mut key)
+        .map_err(|e| format!("vault key derivation failed: {}", e))?;
+    DERIVED_KEYS.write().unwrap().insert(cache_key, key);
+    Ok(key)
+}
+
+fn non_empty(value: String, what: &str) -> Result<String, String> {
+    match value.is_empty() {
+        true => Err(format!("{} is empty", what)),
+        false => Ok(value),
+    }
+}
+
+fn is_base64_line(line: &str) -> bool {
+    !line.is_empty()
+        && line
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '+' || c == '/' || c == '=')
+}
+
+fn scalar_from_plaintext(plaintext: String) -> serde_yaml::Value {
+    match serde_yaml::from_str::<serde_yaml::Value>(&plaintext) {
+        Ok(v @ serde_yaml::Value::Bool(_)) | Ok(v @ serde_yaml::Value::Number(_)) => v,
+        _ => serde_yaml::Value::String(plaintext),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encrypt_then_decrypt_round_trips() {
+        let envelope = encrypt(b"db_password: hunter2\n", "correct horse").unwrap();
+        assert!(envelope.starts_with(VAULT_HEADER));
+        assert!(is_encrypted(&envelope));
+        assert!(
+            envelope
+                .lines()
+                .all(|l| l.len() <= WRAP_WIDTH.max(VAULT_HEADER.len()))
+        );
+        let plaintext = decrypt(&envelope, "correct horse").unwrap();
+        assert_eq!(plaintext, b"db_password: hunter2\n");
+    }
+
+    #[test]
+    fn wrong_password_and_tampering_are_rejected() {
+        let envelope = encrypt(b"secret", "correct horse").unwrap();
+        assert!(
+            decrypt(&envelope, "battery staple")
+                .unwrap_err()
+                .contains("wrong password")
+        );
+
+        let mut lines: Vec<String> = envelope.lines().map(String::from).collect();
+        let last = lines.last_mut().unwrap();
+        let flipped = if last.starts_with('A') { 'B' } else { 'A' };
+        last.replace_range(0..1, &flipped.to_string());
+        assert!(decrypt(&lines.join("\n"), "correct horse").is_err());
+    }
+
+    #[test]
+    fn unknown_versions_and_plaintext_are_not_envelopes() {
+        assert!(!is_encrypted("db_password: hunter2"));
+        assert_eq!(
+            decrypt("db_password: hunter2", "x").unwrap_err(),
+            "not a vault envelope"
+        );
+        assert!(
+            decrypt("$JETPACK_VAULT;2.0;XCHACHA\nAAAA", "x")
+                .unwrap_err()
+                .contains("unsupported vault format")
+        );
+    }
+
+    #[test]
+    fn rekey_rewrites_whole_files_and_inline_values() {
+        let whole = encrypt(b"a: 1\n", "old").unwrap();
+        let (rekeyed, count) = rekey_text(&whole, "old", "new").unwrap();
+        assert_eq!(count, 1);
+        assert_eq!(decrypt(&rekeyed, "new").unwrap(), b"a: 1\n");
+
+        let inline = format!(
+            "user: admin\n{}port: 22\n",
+            format_tagged_value("password", &encrypt(b"hunter2", "old").unwrap())
+        );
+        let (rekeyed, count) = rekey_text(&inline, "old", "new").unwrap();
+        assert_eq!(count, 1);
+        assert!(rekeyed.starts_with("user: admin\npassword: !vault |\n  $JETPACK_VAULT"));
+        assert!(rekeyed.ends_with("port: 22\n"));
+        let value: serde_yaml::Value = serde_yaml::from_str(&rekeyed).unwrap();
+        let serde_yaml::Value::Tagged(tagged) = &value["password"] else {
+            panic!("expected a tagged value");
+        };
+        let envelope = tagged.value.as_str().unwrap();
+        assert_eq!(decrypt(envelope, "new").unwrap(), b"hunter2");
+        assert!(rekey_text(&inline, "wrong", "new").is_err());
+    }
+
+    #[test]
+    fn tagged_values_are_decrypted_in_place() {
+        let password = "tagged-values-test";
+        *PASSWORD.write().unwrap() = Some(password.to_string());
+        let secret = encrypt(b"hunter2", password).unwrap();
+        let port = encrypt(b"5432", password).unwrap();
+        let indent = |envelope: &str, pad: &str| -> String {
+            envelope
+                .lines()
+                .map(|l| format!("{}{}\n", pad, l))
+                .collect()
+        };
+        let yaml = format!(
+            "plain: visible\n{}nested:\n  list:\n    - !vault |\n{}",
+            format_tagged_value("db_password", &secret),
+            indent(&port, "      ")
+        );
+        let mut value: serde_yaml::Value = serde_yaml::from_str(&yaml).unwrap();
+        decrypt_tagged_values(&mut value).unwrap();
+        assert_eq!(value["plain"].as_str(), Some("visible"));
+        assert_eq!(value["db_password"].as_str(), Some("hunter2"));
+        assert_eq!(value["nested"]["list"][0].as_u64(), Some(5432));
+    }
+}
@@ -15,7 +15,7 @@
 // long with this program.  If not, see <http://www.gnu.org/licenses/>.
 
 use crate::util::terminal::banner;
-use std::fs::read_to_string;
+use crate::util::vault::is_encrypted;
 use std::path::Path;
 
 const YAML_ERROR_SHOW_LINES: usize = 10;
@@ -54,13 +54,6 @@ pub fn show_yaml_error_in_context(yaml_error: &serde_yaml::Error, path: &Path) {
     let error_line = location.line();
     let error_column = location.column();
 
-    let lines: Vec<String> = read_to_string(path)
-        .unwrap()
-        .lines()
-        .map(String::from)
-        .collect();
-    let line_count = lines.len();
-
     banner(
         &format!(
             "Error reading YAML file: {}, {}",
@@ -70,6 +63,22 @@ pub fn show_yaml_error_in_context(yaml_error: &serde_yaml::Error, path: &Path) {
         .to_string(),
     );
 
+    // context comes from the bytes on disk. A vault encrypted file would have to be
+    // decrypted to line up with the error, which would print its secrets, so it is
+    // shown without any
+    let raw = std::fs::read_to_string(path).unwrap_or_default();
+    if is_encrypted(&raw) {
+        println!();
+        println!(
+            "     (vault encrypted, see line {} column {} of the decrypted content)",
+            error_line, error_column
+        );
+        println!();
+        return;
+    }
+    let lines: Vec<String> = raw.lines().map(String::from).collect();
+    let line_count = lines.len();
+
     let mut show_stop: usize = error_line + YAML_ERROR_SHOW_LINES;
 
     let show_start: usize = error_line.saturating_sub(YAML_ERROR_SHOW_LINES);
@@ -49,6 +49,7 @@ fn test_cmd_info() {
         })),
         with: Arc::new(None),
         and: Arc::new(None),
+        diff: None,
     };
 
     let arc_response = Arc::new(response);
@@ -71,6 +72,7 @@ fn test_cmd_info_with_error() {
         })),
         with: Arc::new(None),
         and: Arc::new(None),
+        diff: None,
     };
 
     let arc_response = Arc::new(response);
@@ -90,6 +92,7 @@ fn test_cmd_info_without_command_result() {
         command_result: Arc::new(None),
         with: Arc::new(None),
         and: Arc::new(None),
+        diff: None,
     };
 
     let arc_response = Arc::new(response);
@@ -24,6 +24,7 @@ fn gravity_config(api_url: &str) -> DnsConfig {
             api_token: Some("test-token".to_string()),
             api_token_file: None,
             api_token_env: None,
+            api_token_ref: None,
             default_ttl: None,
         }),
     }
@@ -32,7 +32,7 @@ fn create_test_response() -> Response {
         limit_hosts: Vec::new(),
         limit_groups: Vec::new(),
         batch_size: None,
-        context: context,
+        context,
         visitor: Arc::new(RwLock::new(PlaybookVisitor::new(CheckMode::No))),
         connection_factory: Arc::new(RwLock::new(NoFactory::new())),
         tags: None,
@@ -139,7 +139,7 @@ fn test_response_is_failed() {
     let response = create_test_response();
     let request = TaskRequest::validate();
 
-    let result = response.is_failed(&request, &"Test error message".to_string());
+    let result = response.is_failed(&request, "Test error message");
     assert!(result.status == TaskStatus::Failed);
     assert_eq!(result.msg, Some("Test error message".to_string()));
 }
@@ -0,0 +1,38 @@
+use jetpack::modules::access::authorized_key::*;
+use jetpack::tasks::*;
+
+#[test]
+fn test_authorized_key_task_basic() {
+    let task = AuthorizedKeyTask {
+        name: Some("Let alice in".to_string()),
+        user: "deploy".to_string(),
+        url: Some("https://github.com/alice.keys".to_string()),
+        ..Default::default()
+    };
+
+    assert_eq!(task.get_module(), "authorized_key");
+    assert_eq!(task.get_name(), Some("Let alice in".to_string()));
+    assert!(task.key.is_none());
+    assert!(task.exclusive.is_none());
+}
+
+#[test]
+fn test_authorized_key_task_deserialization() {
+    let yaml = r#"
+user: backup
+keys:
+  - ssh-ed25519 AAAAC3NzaC1lZDI1NTE5AAAAIbackup backup@vault
+options: from="10.0.0.5",command="/usr/local/bin/receive-backup"
+exclusive: true
+"#;
+
+    let task: AuthorizedKeyTask = serde_yaml::from_str(yaml).unwrap();
+    assert_eq!(task.user, "backup");
+    assert_eq!(task.keys.as_ref().unwrap().len(), 1);
+    assert_eq!(
+        task.options,
+        Some("from=\"10.0.0.5\",command=\"/usr/local/bin/receive-backup\"".to_string())
+    );
+    assert_eq!(task.exclusive, Some(Templated::Value(true)));
+    assert!(task.remove.is_none());
+}
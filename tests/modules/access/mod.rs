@@ -1 +1,2 @@
+mod authorized_key;
 mod user;
@@ -16,6 +16,13 @@ fn test_user_task_basic() {
         create_user_group: None,
         gecos: None,
         shell: None,
+        password: None,
+        locked: None,
+        expires: None,
+        password_min_days: None,
+        password_max_days: None,
+        password_warn_days: None,
+        password_inactive_days: None,
         remove: None,
         cleanup: None,
         with: None,
@@ -37,15 +44,22 @@ fn test_user_task_with_details() {
     let task = UserTask {
         name: None,
         user: "webuser".to_string(),
-        uid: Some("1001".to_string()),
-        system: Some("no".to_string()),
+        uid: Some(Templated::Template("1001".to_string())),
+        system: Some(Templated::Template("no".to_string())),
         gid: Some("1001".to_string()),
         groups: Some(groups),
-        append: Some("yes".to_string()),
-        create_home: Some("yes".to_string()),
-        create_user_group: Some("yes".to_string()),
+        append: Some(Templated::Template("yes".to_string())),
+        create_home: Some(Templated::Template("yes".to_string())),
+        create_user_group: Some(Templated::Template("yes".to_string())),
         gecos: Some("Web Application User".to_string()),
         shell: Some("/bin/bash".to_string()),
+        password: None,
+        locked: None,
+        expires: None,
+        password_min_days: None,
+        password_max_days: None,
+        password_warn_days: None,
+        password_inactive_days: None,
         remove: None,
         cleanup: None,
         with: None,
@@ -53,7 +67,7 @@ fn test_user_task_with_details() {
     };
 
     assert_eq!(task.get_module(), "user");
-    assert_eq!(task.uid, Some("1001".to_string()));
+    assert_eq!(task.uid, Some(Templated::Template("1001".to_string())));
     assert!(task.groups.is_some());
     let groups = task.groups.unwrap();
     assert!(groups.contains("wheel"));
@@ -66,22 +80,32 @@ fn test_user_task_system_user() {
         name: Some("Create system user".to_string()),
         user: "prometheus".to_string(),
         uid: None,
-        system: Some("yes".to_string()),
+        system: Some(Templated::Template("yes".to_string())),
         gid: None,
         groups: None,
         append: None,
-        create_home: Some("no".to_string()),
-        create_user_group: Some("yes".to_string()),
+        create_home: Some(Templated::Template("no".to_string())),
+        create_user_group: Some(Templated::Template("yes".to_string())),
         gecos: None,
         shell: Some("/usr/sbin/nologin".to_string()),
+        password: None,
+        locked: None,
+        expires: None,
+        password_min_days: None,
+        password_max_days: None,
+        password_warn_days: None,
+        password_inactive_days: None,
         remove: None,
         cleanup: None,
         with: None,
         and: None,
     };
 
-    assert_eq!(task.system, Some("yes".to_string()));
-    assert_eq!(task.create_home, Some("no".to_string()));
+    assert_eq!(task.system, Some(Templated::Template("yes".to_string())));
+    assert_eq!(
+        task.create_home,
+        Some(Templated::Template("no".to_string()))
+    );
     assert_eq!(task.shell, Some("/usr/sbin/nologin".to_string()));
 }
 
@@ -99,14 +123,21 @@ fn test_user_task_remove() {
         create_user_group: None,
         gecos: None,
         shell: None,
-        remove: Some("yes".to_string()),
+        password: None,
+        locked: None,
+        expires: None,
+        password_min_days: None,
+        password_max_days: None,
+        password_warn_days: None,
+        password_inactive_days: None,
+        remove: Some(Templated::Template("yes".to_string())),
         cleanup: None,
         with: None,
         and: None,
     };
 
     assert!(task.remove.is_some());
-    assert_eq!(task.remove.unwrap(), "yes");
+    assert_eq!(task.remove.unwrap(), Templated::Template("yes".to_string()));
 }
 
 #[test]
@@ -127,7 +158,7 @@ gecos: "Application User"
     let task = task.unwrap();
     assert_eq!(task.name, Some("Create application user".to_string()));
     assert_eq!(task.user, "appuser");
-    assert_eq!(task.uid, Some("2001".to_string()));
+    assert_eq!(task.uid, Some(Templated::Template("2001".to_string())));
     assert!(task.groups.is_some());
     assert_eq!(task.shell, Some("/bin/bash".to_string()));
 }
@@ -166,3 +197,22 @@ and:
     assert!(task.with.is_some());
     assert!(task.and.is_some());
 }
+
+#[test]
+fn test_user_task_password_and_aging() {
+    let yaml = r#"
+user: alice
+password: $6$rounds=5000$salt$hash
+locked: false
+expires: 2027-06-30
+password_max_days: 90
+password_warn_days: 14
+"#;
+
+    let task: UserTask = serde_yaml::from_str(yaml).unwrap();
+    assert_eq!(task.password, Some("$6$rounds=5000$salt$hash".to_string()));
+    assert_eq!(task.locked, Some(Templated::Value(false)));
+    assert_eq!(task.expires, Some("2027-06-30".to_string()));
+    assert_eq!(task.password_max_days, Some(Templated::Value(90)));
+    assert!(task.password_min_days.is_none());
+}
@@ -5,8 +5,8 @@ use jetpack::tasks::*;
 fn test_facts_task_basic() {
     let task = FactsTask {
         name: Some("Test Facts".to_string()),
-        facter: Some("true".to_string()),
-        ohai: Some("false".to_string()),
+        facter: Some(Templated::Template("true".to_string())),
+        ohai: Some(Templated::Template("false".to_string())),
         with: None,
         and: None,
     };
@@ -45,8 +45,8 @@ ohai: "no"
 
     let task = task.unwrap();
     assert_eq!(task.name, Some("Gather Facts".to_string()));
-    assert_eq!(task.facter, Some("yes".to_string()));
-    assert_eq!(task.ohai, Some("no".to_string()));
+    assert_eq!(task.facter, Some(Templated::Template("yes".to_string())));
+    assert_eq!(task.ohai, Some(Templated::Template("no".to_string())));
 }
 
 #[test]
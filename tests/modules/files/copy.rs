@@ -26,6 +26,7 @@ fn test_copy_task_with_attributes() {
         owner: Some("app".to_string()),
         group: Some("app".to_string()),
         mode: Some("0600".to_string()),
+        ..Default::default()
     };
 
     let task = CopyTask {
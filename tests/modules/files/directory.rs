@@ -25,11 +25,12 @@ fn test_directory_task_with_recurse() {
         name: None,
         path: "/var/www".to_string(),
         remove: None,
-        recurse: Some("yes".to_string()),
+        recurse: Some(Templated::Template("yes".to_string())),
         attributes: Some(FileAttributesInput {
             owner: Some("www-data".to_string()),
             group: Some("www-data".to_string()),
             mode: Some("0755".to_string()),
+            ..Default::default()
         }),
         with: None,
         and: None,
@@ -45,8 +46,8 @@ fn test_directory_task_remove() {
     let task = DirectoryTask {
         name: Some("Remove directory".to_string()),
         path: "/tmp/old_dir".to_string(),
-        remove: Some("yes".to_string()),
-        recurse: Some("yes".to_string()),
+        remove: Some(Templated::Template("yes".to_string())),
+        recurse: Some(Templated::Template("yes".to_string())),
         attributes: None,
         with: None,
         and: None,
@@ -74,7 +75,7 @@ attributes:
     let task = task.unwrap();
     assert_eq!(task.name, Some("Create web directory".to_string()));
     assert_eq!(task.path, "/var/www/html");
-    assert_eq!(task.recurse, Some("yes".to_string()));
+    assert_eq!(task.recurse, Some(Templated::Template("yes".to_string())));
     assert!(task.attributes.is_some());
 }
 
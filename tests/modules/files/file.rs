@@ -23,7 +23,7 @@ fn test_file_task_with_remove() {
     let task = FileTask {
         name: None,
         path: "/tmp/remove_me.txt".to_string(),
-        remove: Some("yes".to_string()),
+        remove: Some(Templated::Template("yes".to_string())),
         attributes: None,
         with: None,
         and: None,
@@ -39,6 +39,7 @@ fn test_file_task_with_attributes() {
         owner: Some("user".to_string()),
         group: Some("group".to_string()),
         mode: Some("0644".to_string()),
+        ..Default::default()
     };
 
     let task = FileTask {
@@ -89,7 +90,7 @@ remove: "yes"
 
     let task = task.unwrap();
     assert_eq!(task.path, "/tmp/delete_me.txt");
-    assert_eq!(task.remove, Some("yes".to_string()));
+    assert_eq!(task.remove, Some(Templated::Template("yes".to_string())));
 }
 
 #[test]
@@ -32,8 +32,8 @@ fn test_git_task_with_branch() {
         path: "/var/lib/app".to_string(),
         branch: Some("develop".to_string()),
         ssh_options: None,
-        accept_keys: Some("yes".to_string()),
-        update: Some("yes".to_string()),
+        accept_keys: Some(Templated::Template("yes".to_string())),
+        update: Some(Templated::Template("yes".to_string())),
         attributes: None,
         with: None,
         and: None,
@@ -57,7 +57,7 @@ fn test_git_task_with_ssh_options() {
         path: "/opt/project".to_string(),
         branch: Some("main".to_string()),
         ssh_options: Some(ssh_opts),
-        accept_keys: Some("no".to_string()),
+        accept_keys: Some(Templated::Template("no".to_string())),
         update: None,
         attributes: None,
         with: None,
@@ -79,6 +79,7 @@ fn test_git_task_with_attributes() {
         owner: Some("deploy".to_string()),
         group: Some("deploy".to_string()),
         mode: Some("0755".to_string()),
+        ..Default::default()
     };
 
     let task = GitTask {
@@ -88,7 +89,7 @@ fn test_git_task_with_attributes() {
         branch: None,
         ssh_options: None,
         accept_keys: None,
-        update: Some("no".to_string()),
+        update: Some(Templated::Template("no".to_string())),
         attributes: Some(attrs),
         with: None,
         and: None,
@@ -121,7 +122,7 @@ attributes:
     assert_eq!(task.repo, "https://github.com/mycompany/myapp.git");
     assert_eq!(task.path, "/opt/myapp");
     assert_eq!(task.branch, Some("production".to_string()));
-    assert_eq!(task.update, Some("yes".to_string()));
+    assert_eq!(task.update, Some(Templated::Template("yes".to_string())));
     assert!(task.attributes.is_some());
 }
 
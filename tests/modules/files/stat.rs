@@ -28,6 +28,7 @@ fn test_stat_task_with_logic() {
         tags: None,
         delegate_to: None,
         skip_if_exists: None,
+        no_log: None,
     };
 
     let post_logic = PostLogicInput {
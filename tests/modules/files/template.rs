@@ -25,6 +25,7 @@ fn test_template_task_with_attributes() {
         owner: Some("nginx".to_string()),
         group: Some("nginx".to_string()),
         mode: Some("0640".to_string()),
+        ..Default::default()
     };
 
     let task = TemplateTask {
@@ -4,3 +4,4 @@ pub mod control;
 pub mod files;
 pub mod packages;
 pub mod services;
+pub mod system;
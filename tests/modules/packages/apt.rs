@@ -1,22 +1,27 @@
 use jetpack::modules::packages::apt::*;
+use jetpack::modules::packages::common::UpgradeMode;
 use jetpack::tasks::*;
 
 #[test]
 fn test_apt_task_basic() {
     let task = AptTask {
         name: Some("Install nginx".to_string()),
-        package: "nginx".to_string(),
+        package: Some("nginx".to_string()),
+        packages: None,
         version: None,
         update: None,
         upgrade: None,
         remove: None,
+        hold: None,
+        save: None,
         with: None,
+        rendered: false,
         and: None,
     };
 
     assert_eq!(task.get_module(), "apt");
     assert_eq!(task.get_name(), Some("Install nginx".to_string()));
-    assert_eq!(task.package, "nginx");
+    assert_eq!(task.package.as_deref(), Some("nginx"));
     assert!(task.version.is_none());
     assert!(task.get_with().is_none());
 }
@@ -25,17 +30,21 @@ fn test_apt_task_basic() {
 fn test_apt_task_with_version() {
     let task = AptTask {
         name: None,
-        package: "postgresql".to_string(),
+        package: Some("postgresql".to_string()),
+        packages: None,
         version: Some("14".to_string()),
         update: None,
         upgrade: None,
         remove: None,
+        hold: None,
+        save: None,
         with: None,
+        rendered: false,
         and: None,
     };
 
     assert_eq!(task.get_module(), "apt");
-    assert_eq!(task.package, "postgresql");
+    assert_eq!(task.package.as_deref(), Some("postgresql"));
     assert_eq!(task.version, Some("14".to_string()));
 }
 
@@ -43,34 +52,42 @@ fn test_apt_task_with_version() {
 fn test_apt_task_remove() {
     let task = AptTask {
         name: Some("Remove old package".to_string()),
-        package: "apache2".to_string(),
+        package: Some("apache2".to_string()),
+        packages: None,
         version: None,
         update: None,
         upgrade: None,
-        remove: Some("yes".to_string()),
+        remove: Some(Templated::Template("yes".to_string())),
+        hold: None,
+        save: None,
         with: None,
+        rendered: false,
         and: None,
     };
 
     assert!(task.remove.is_some());
-    assert_eq!(task.remove.unwrap(), "yes");
+    assert_eq!(task.remove.unwrap(), Templated::Template("yes".to_string()));
 }
 
 #[test]
 fn test_apt_task_with_update() {
     let task = AptTask {
         name: Some("Install latest vim".to_string()),
-        package: "vim".to_string(),
+        package: Some("vim".to_string()),
+        packages: None,
         version: None,
-        update: Some("yes".to_string()),
+        update: Some(Templated::Template("yes".to_string())),
         upgrade: None,
         remove: None,
+        hold: None,
+        save: None,
         with: None,
+        rendered: false,
         and: None,
     };
 
     assert!(task.update.is_some());
-    assert_eq!(task.update.unwrap(), "yes");
+    assert_eq!(task.update.unwrap(), Templated::Template("yes".to_string()));
 }
 
 #[test]
@@ -87,9 +104,9 @@ update: "yes"
 
     let task = task.unwrap();
     assert_eq!(task.name, Some("Install web server".to_string()));
-    assert_eq!(task.package, "nginx");
+    assert_eq!(task.package.as_deref(), Some("nginx"));
     assert_eq!(task.version, Some("1.22".to_string()));
-    assert_eq!(task.update, Some("yes".to_string()));
+    assert_eq!(task.update, Some(Templated::Template("yes".to_string())));
 }
 
 #[test]
@@ -102,13 +119,34 @@ package: git
     assert!(task.is_ok());
 
     let task = task.unwrap();
-    assert_eq!(task.package, "git");
+    assert_eq!(task.package.as_deref(), Some("git"));
     assert!(task.name.is_none());
     assert!(task.version.is_none());
     assert!(task.update.is_none());
     assert!(task.remove.is_none());
 }
 
+#[test]
+fn test_apt_task_deserialization_package_list() {
+    let yaml = r#"
+packages:
+  - nginx
+  - curl
+  - jq
+"#;
+
+    let task: AptTask = serde_yaml::from_str(yaml).unwrap();
+    assert!(task.package.is_none());
+    assert_eq!(
+        task.packages,
+        Some(vec![
+            "nginx".to_string(),
+            "curl".to_string(),
+            "jq".to_string()
+        ])
+    );
+}
+
 #[test]
 fn test_apt_task_with_logic() {
     let yaml = r#"
@@ -124,7 +162,34 @@ and:
     assert!(task.is_ok());
 
     let task = task.unwrap();
-    assert!(task.package.contains("{{ package_name }}"));
+    assert!(
+        task.package
+            .as_ref()
+            .unwrap()
+            .contains("{{ package_name }}")
+    );
     assert!(task.with.is_some());
     assert!(task.and.is_some());
 }
+
+#[test]
+fn test_apt_task_deserialization_system_upgrade() {
+    let yaml = r#"
+upgrade: dist
+save: patching
+"#;
+
+    let task: AptTask = serde_yaml::from_str(yaml).unwrap();
+    assert_eq!(task.upgrade, Some(Templated::Template("dist".to_string())));
+    assert_eq!(task.save.as_deref(), Some("patching"));
+    assert!(task.package.is_none() && task.packages.is_none());
+
+    let task: AptTask = serde_yaml::from_str(
+        "package: nginx
+upgrade: true
+hold: true",
+    )
+    .unwrap();
+    assert_eq!(task.upgrade, Some(Templated::Value(UpgradeMode::Packages)));
+    assert_eq!(task.hold, Some(Templated::Value(true)));
+}
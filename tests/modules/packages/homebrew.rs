@@ -5,17 +5,19 @@ use jetpack::tasks::*;
 fn test_homebrew_task_basic() {
     let task = HomebrewTask {
         name: Some("Install wget".to_string()),
-        package: "wget".to_string(),
+        package: Some("wget".to_string()),
+        packages: None,
         version: None,
         update: None,
         remove: None,
         with: None,
+        rendered: false,
         and: None,
     };
 
     assert_eq!(task.get_module(), "homebrew");
     assert_eq!(task.get_name(), Some("Install wget".to_string()));
-    assert_eq!(task.package, "wget");
+    assert_eq!(task.package.as_deref(), Some("wget"));
     assert!(task.version.is_none());
     assert!(task.get_with().is_none());
 }
@@ -24,16 +26,18 @@ fn test_homebrew_task_basic() {
 fn test_homebrew_task_with_version() {
     let task = HomebrewTask {
         name: None,
-        package: "node".to_string(),
+        package: Some("node".to_string()),
+        packages: None,
         version: Some("18".to_string()),
         update: None,
         remove: None,
         with: None,
+        rendered: false,
         and: None,
     };
 
     assert_eq!(task.get_module(), "homebrew");
-    assert_eq!(task.package, "node");
+    assert_eq!(task.package.as_deref(), Some("node"));
     assert_eq!(task.version, Some("18".to_string()));
 }
 
@@ -41,32 +45,36 @@ fn test_homebrew_task_with_version() {
 fn test_homebrew_task_remove() {
     let task = HomebrewTask {
         name: Some("Uninstall old tool".to_string()),
-        package: "python@3.9".to_string(),
+        package: Some("python@3.9".to_string()),
+        packages: None,
         version: None,
         update: None,
-        remove: Some("yes".to_string()),
+        remove: Some(Templated::Template("yes".to_string())),
         with: None,
+        rendered: false,
         and: None,
     };
 
     assert!(task.remove.is_some());
-    assert_eq!(task.remove.unwrap(), "yes");
+    assert_eq!(task.remove.unwrap(), Templated::Template("yes".to_string()));
 }
 
 #[test]
 fn test_homebrew_task_with_update() {
     let task = HomebrewTask {
         name: Some("Update git".to_string()),
-        package: "git".to_string(),
+        package: Some("git".to_string()),
+        packages: None,
         version: None,
-        update: Some("yes".to_string()),
+        update: Some(Templated::Template("yes".to_string())),
         remove: None,
         with: None,
+        rendered: false,
         and: None,
     };
 
     assert!(task.update.is_some());
-    assert_eq!(task.update.unwrap(), "yes");
+    assert_eq!(task.update.unwrap(), Templated::Template("yes".to_string()));
 }
 
 #[test]
@@ -82,8 +90,8 @@ update: "yes"
 
     let task = task.unwrap();
     assert_eq!(task.name, Some("Install development tools".to_string()));
-    assert_eq!(task.package, "rust");
-    assert_eq!(task.update, Some("yes".to_string()));
+    assert_eq!(task.package.as_deref(), Some("rust"));
+    assert_eq!(task.update, Some(Templated::Template("yes".to_string())));
 }
 
 #[test]
@@ -96,7 +104,7 @@ package: tmux
     assert!(task.is_ok());
 
     let task = task.unwrap();
-    assert_eq!(task.package, "tmux");
+    assert_eq!(task.package.as_deref(), Some("tmux"));
     assert!(task.name.is_none());
     assert!(task.version.is_none());
 }
@@ -115,7 +123,7 @@ and:
     assert!(task.is_ok());
 
     let task = task.unwrap();
-    assert!(task.package.contains("{{ tool_name }}"));
+    assert!(task.package.as_ref().unwrap().contains("{{ tool_name }}"));
     assert!(task.with.is_some());
     assert!(task.and.is_some());
 }
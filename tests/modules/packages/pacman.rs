@@ -68,7 +68,7 @@ package: vim
     let task: Result<PacmanTask, _> = serde_yaml::from_str(yaml);
     assert!(task.is_ok());
     let task = task.unwrap();
-    assert_eq!(task.package, "vim");
+    assert_eq!(task.package.as_deref(), Some("vim"));
     assert!(task.version.is_none());
     assert!(task.update.is_none());
     assert!(task.remove.is_none());
@@ -83,7 +83,7 @@ version: "9.0.1234-1"
     let task: Result<PacmanTask, _> = serde_yaml::from_str(yaml);
     assert!(task.is_ok());
     let task = task.unwrap();
-    assert_eq!(task.package, "vim");
+    assert_eq!(task.package.as_deref(), Some("vim"));
     assert_eq!(task.version, Some("9.0.1234-1".to_string()));
 }
 
@@ -97,7 +97,7 @@ remove: false
     let task: Result<PacmanTask, _> = serde_yaml::from_str(yaml);
     assert!(task.is_ok());
     let task = task.unwrap();
-    assert_eq!(task.package, "vim");
+    assert_eq!(task.package.as_deref(), Some("vim"));
     assert_eq!(task.update, Some("true".to_string()));
     assert_eq!(task.remove, Some("false".to_string()));
 }
@@ -106,7 +106,8 @@ remove: false
 fn test_pacman_task_get_module() {
     let task = PacmanTask {
         name: None,
-        package: "vim".to_string(),
+        package: Some("vim".to_string()),
+        packages: None,
         version: None,
         update: None,
         remove: None,
@@ -120,7 +121,8 @@ fn test_pacman_task_get_module() {
 fn test_pacman_task_get_name() {
     let task = PacmanTask {
         name: Some("Install vim editor".to_string()),
-        package: "vim".to_string(),
+        package: Some("vim".to_string()),
+        packages: None,
         version: None,
         update: None,
         remove: None,
@@ -133,7 +135,8 @@ fn test_pacman_task_get_name() {
 #[test]
 fn test_pacman_action_get_actual_package() {
     let action = PacmanAction {
-        package: "vim".to_string(),
+        package: Some("vim".to_string()),
+        packages: None,
         version: None,
         update: false,
         remove: false,
@@ -144,7 +147,8 @@ fn test_pacman_action_get_actual_package() {
 #[test]
 fn test_pacman_action_get_actual_package_with_repo() {
     let action = PacmanAction {
-        package: "extra/vim".to_string(),
+        package: Some("extra/vim".to_string()),
+        packages: None,
         version: None,
         update: false,
         remove: false,
@@ -155,7 +159,8 @@ fn test_pacman_action_get_actual_package_with_repo() {
 #[test]
 fn test_pacman_action_get_actual_package_multiple_slashes() {
     let action = PacmanAction {
-        package: "community/extra/vim".to_string(),
+        package: Some("community/extra/vim".to_string()),
+        packages: None,
         version: None,
         update: false,
         remove: false,
@@ -166,7 +171,8 @@ fn test_pacman_action_get_actual_package_multiple_slashes() {
 #[test]
 fn test_pacman_action_is_update() {
     let action = PacmanAction {
-        package: "vim".to_string(),
+        package: Some("vim".to_string()),
+        packages: None,
         version: None,
         update: true,
         remove: false,
@@ -177,7 +183,8 @@ fn test_pacman_action_is_update() {
 #[test]
 fn test_pacman_action_is_remove() {
     let action = PacmanAction {
-        package: "vim".to_string(),
+        package: Some("vim".to_string()),
+        packages: None,
         version: None,
         update: false,
         remove: true,
@@ -188,7 +195,8 @@ fn test_pacman_action_is_remove() {
 #[test]
 fn test_pacman_action_get_version() {
     let action = PacmanAction {
-        package: "vim".to_string(),
+        package: Some("vim".to_string()),
+        packages: None,
         version: Some("9.0.1234-1".to_string()),
         update: false,
         remove: false,
@@ -199,7 +207,8 @@ fn test_pacman_action_get_version() {
 #[test]
 fn test_pacman_action_parse_package_details() {
     let action = PacmanAction {
-        package: "vim".to_string(),
+        package: Some("vim".to_string()),
+        packages: None,
         version: None,
         update: false,
         remove: false,
@@ -217,7 +226,8 @@ fn test_pacman_action_parse_package_details() {
 #[test]
 fn test_pacman_action_parse_package_details_not_found() {
     let action = PacmanAction {
-        package: "notfound".to_string(),
+        package: Some("notfound".to_string()),
+        packages: None,
         version: None,
         update: false,
         remove: false,
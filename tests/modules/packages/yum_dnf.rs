@@ -5,17 +5,22 @@ use jetpack::tasks::*;
 fn test_yum_dnf_task_basic() {
     let task = YumDnfTask {
         name: Some("Install httpd".to_string()),
-        package: "httpd".to_string(),
+        package: Some("httpd".to_string()),
+        packages: None,
         version: None,
         update: None,
+        upgrade: None,
         remove: None,
+        hold: None,
+        save: None,
         with: None,
+        rendered: false,
         and: None,
     };
 
     assert_eq!(task.get_module(), "yum_dnf");
     assert_eq!(task.get_name(), Some("Install httpd".to_string()));
-    assert_eq!(task.package, "httpd");
+    assert_eq!(task.package.as_deref(), Some("httpd"));
     assert!(task.version.is_none());
     assert!(task.get_with().is_none());
 }
@@ -24,16 +29,21 @@ fn test_yum_dnf_task_basic() {
 fn test_yum_dnf_task_with_version() {
     let task = YumDnfTask {
         name: None,
-        package: "mariadb-server".to_string(),
+        package: Some("mariadb-server".to_string()),
+        packages: None,
         version: Some("10.5".to_string()),
         update: None,
+        upgrade: None,
         remove: None,
+        hold: None,
+        save: None,
         with: None,
+        rendered: false,
         and: None,
     };
 
     assert_eq!(task.get_module(), "yum_dnf");
-    assert_eq!(task.package, "mariadb-server");
+    assert_eq!(task.package.as_deref(), Some("mariadb-server"));
     assert_eq!(task.version, Some("10.5".to_string()));
 }
 
@@ -41,32 +51,42 @@ fn test_yum_dnf_task_with_version() {
 fn test_yum_dnf_task_remove() {
     let task = YumDnfTask {
         name: Some("Remove old package".to_string()),
-        package: "php-5.6".to_string(),
+        package: Some("php-5.6".to_string()),
+        packages: None,
         version: None,
         update: None,
-        remove: Some("yes".to_string()),
+        upgrade: None,
+        remove: Some(Templated::Template("yes".to_string())),
+        hold: None,
+        save: None,
         with: None,
+        rendered: false,
         and: None,
     };
 
     assert!(task.remove.is_some());
-    assert_eq!(task.remove.unwrap(), "yes");
+    assert_eq!(task.remove.unwrap(), Templated::Template("yes".to_string()));
 }
 
 #[test]
 fn test_yum_dnf_task_with_update() {
     let task = YumDnfTask {
         name: Some("Update kernel".to_string()),
-        package: "kernel".to_string(),
+        package: Some("kernel".to_string()),
+        packages: None,
         version: None,
-        update: Some("yes".to_string()),
+        update: Some(Templated::Template("yes".to_string())),
+        upgrade: None,
         remove: None,
+        hold: None,
+        save: None,
         with: None,
+        rendered: false,
         and: None,
     };
 
     assert!(task.update.is_some());
-    assert_eq!(task.update.unwrap(), "yes");
+    assert_eq!(task.update.unwrap(), Templated::Template("yes".to_string()));
 }
 
 #[test]
@@ -83,9 +103,9 @@ update: "yes"
 
     let task = task.unwrap();
     assert_eq!(task.name, Some("Install database server".to_string()));
-    assert_eq!(task.package, "postgresql-server");
+    assert_eq!(task.package.as_deref(), Some("postgresql-server"));
     assert_eq!(task.version, Some("13".to_string()));
-    assert_eq!(task.update, Some("yes".to_string()));
+    assert_eq!(task.update, Some(Templated::Template("yes".to_string())));
 }
 
 #[test]
@@ -98,7 +118,7 @@ package: vim-enhanced
     assert!(task.is_ok());
 
     let task = task.unwrap();
-    assert_eq!(task.package, "vim-enhanced");
+    assert_eq!(task.package.as_deref(), Some("vim-enhanced"));
     assert!(task.name.is_none());
     assert!(task.version.is_none());
 }
@@ -118,7 +138,7 @@ and:
     assert!(task.is_ok());
 
     let task = task.unwrap();
-    assert!(task.package.contains("{{ rpm_package }}"));
+    assert!(task.package.as_ref().unwrap().contains("{{ rpm_package }}"));
     assert!(task.with.is_some());
     assert!(task.and.is_some());
 }
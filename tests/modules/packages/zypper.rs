@@ -79,7 +79,7 @@ package: vim
     let task: Result<ZypperTask, _> = serde_yaml::from_str(yaml);
     assert!(task.is_ok());
     let task = task.unwrap();
-    assert_eq!(task.package, "vim");
+    assert_eq!(task.package.as_deref(), Some("vim"));
     assert!(task.version.is_none());
     assert!(task.update.is_none());
     assert!(task.remove.is_none());
@@ -94,7 +94,7 @@ version: "8.2.5226"
     let task: Result<ZypperTask, _> = serde_yaml::from_str(yaml);
     assert!(task.is_ok());
     let task = task.unwrap();
-    assert_eq!(task.package, "vim");
+    assert_eq!(task.package.as_deref(), Some("vim"));
     assert_eq!(task.version, Some("8.2.5226".to_string()));
 }
 
@@ -108,7 +108,7 @@ remove: false
     let task: Result<ZypperTask, _> = serde_yaml::from_str(yaml);
     assert!(task.is_ok());
     let task = task.unwrap();
-    assert_eq!(task.package, "vim");
+    assert_eq!(task.package.as_deref(), Some("vim"));
     assert_eq!(task.update, Some("true".to_string()));
     assert_eq!(task.remove, Some("false".to_string()));
 }
@@ -123,14 +123,15 @@ package: vim
     assert!(task.is_ok());
     let task = task.unwrap();
     assert_eq!(task.name, Some("Install vim editor".to_string()));
-    assert_eq!(task.package, "vim");
+    assert_eq!(task.package.as_deref(), Some("vim"));
 }
 
 #[test]
 fn test_zypper_task_get_module() {
     let task = ZypperTask {
         name: None,
-        package: "vim".to_string(),
+        package: Some("vim".to_string()),
+        packages: None,
         version: None,
         update: None,
         remove: None,
@@ -144,7 +145,8 @@ fn test_zypper_task_get_module() {
 fn test_zypper_task_get_name() {
     let task = ZypperTask {
         name: Some("Install vim editor".to_string()),
-        package: "vim".to_string(),
+        package: Some("vim".to_string()),
+        packages: None,
         version: None,
         update: None,
         remove: None,
@@ -158,7 +160,8 @@ fn test_zypper_task_get_name() {
 fn test_zypper_task_get_with() {
     let task = ZypperTask {
         name: None,
-        package: "vim".to_string(),
+        package: Some("vim".to_string()),
+        packages: None,
         version: None,
         update: None,
         remove: None,
@@ -172,7 +175,8 @@ fn test_zypper_task_get_with() {
 fn test_zypper_task_evaluate() {
     let task = ZypperTask {
         name: Some("Install vim".to_string()),
-        package: "vim".to_string(),
+        package: Some("vim".to_string()),
+        packages: None,
         version: Some("8.2.5226".to_string()),
         update: Some("false".to_string()),
         remove: Some("false".to_string()),
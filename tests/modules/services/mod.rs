@@ -1 +1,2 @@
 mod sd_service;
+mod service;
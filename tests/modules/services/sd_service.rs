@@ -6,6 +6,12 @@ fn test_systemd_service_task_basic() {
     let task = SystemdServiceTask {
         name: Some("Enable nginx".to_string()),
         service: "nginx".to_string(),
+        content: None,
+        src: None,
+        dropins: None,
+        timer: None,
+        user: None,
+        masked: None,
         enabled: None,
         started: None,
         reload: None,
@@ -26,8 +32,14 @@ fn test_systemd_service_task_enabled_started() {
     let task = SystemdServiceTask {
         name: None,
         service: "postgresql".to_string(),
-        enabled: Some("yes".to_string()),
-        started: Some("yes".to_string()),
+        content: None,
+        src: None,
+        dropins: None,
+        timer: None,
+        user: None,
+        masked: None,
+        enabled: Some(Templated::Template("yes".to_string())),
+        started: Some(Templated::Template("yes".to_string())),
         reload: None,
         restart: None,
         with: None,
@@ -36,8 +48,8 @@ fn test_systemd_service_task_enabled_started() {
 
     assert_eq!(task.get_module(), "sd_service");
     assert_eq!(task.service, "postgresql");
-    assert_eq!(task.enabled, Some("yes".to_string()));
-    assert_eq!(task.started, Some("yes".to_string()));
+    assert_eq!(task.enabled, Some(Templated::Template("yes".to_string())));
+    assert_eq!(task.started, Some(Templated::Template("yes".to_string())));
 }
 
 #[test]
@@ -45,16 +57,25 @@ fn test_systemd_service_task_restart() {
     let task = SystemdServiceTask {
         name: Some("Restart web server".to_string()),
         service: "httpd".to_string(),
+        content: None,
+        src: None,
+        dropins: None,
+        timer: None,
+        user: None,
+        masked: None,
         enabled: None,
         started: None,
         reload: None,
-        restart: Some("yes".to_string()),
+        restart: Some(Templated::Template("yes".to_string())),
         with: None,
         and: None,
     };
 
     assert!(task.restart.is_some());
-    assert_eq!(task.restart.unwrap(), "yes");
+    assert_eq!(
+        task.restart.unwrap(),
+        Templated::Template("yes".to_string())
+    );
 }
 
 #[test]
@@ -62,16 +83,22 @@ fn test_systemd_service_task_disabled_stopped() {
     let task = SystemdServiceTask {
         name: Some("Disable service".to_string()),
         service: "firewalld".to_string(),
-        enabled: Some("no".to_string()),
-        started: Some("no".to_string()),
+        content: None,
+        src: None,
+        dropins: None,
+        timer: None,
+        user: None,
+        masked: None,
+        enabled: Some(Templated::Template("no".to_string())),
+        started: Some(Templated::Template("no".to_string())),
         reload: None,
         restart: None,
         with: None,
         and: None,
     };
 
-    assert_eq!(task.enabled, Some("no".to_string()));
-    assert_eq!(task.started, Some("no".to_string()));
+    assert_eq!(task.enabled, Some(Templated::Template("no".to_string())));
+    assert_eq!(task.started, Some(Templated::Template("no".to_string())));
 }
 
 #[test]
@@ -89,8 +116,8 @@ started: "yes"
     let task = task.unwrap();
     assert_eq!(task.name, Some("Manage database service".to_string()));
     assert_eq!(task.service, "mariadb");
-    assert_eq!(task.enabled, Some("yes".to_string()));
-    assert_eq!(task.started, Some("yes".to_string()));
+    assert_eq!(task.enabled, Some(Templated::Template("yes".to_string())));
+    assert_eq!(task.started, Some(Templated::Template("yes".to_string())));
 }
 
 #[test]
@@ -145,7 +172,7 @@ restart: "yes"
     let task = task.unwrap();
     assert_eq!(task.name, Some("Restart application".to_string()));
     assert_eq!(task.service, "myapp");
-    assert_eq!(task.restart, Some("yes".to_string()));
+    assert_eq!(task.restart, Some(Templated::Template("yes".to_string())));
     assert!(task.enabled.is_none());
     assert!(task.started.is_none());
 }
@@ -164,8 +191,39 @@ reload: "yes"
     let task = task.unwrap();
     assert_eq!(task.name, Some("Reload application".to_string()));
     assert_eq!(task.service, "myapp");
-    assert_eq!(task.reload, Some("yes".to_string()));
+    assert_eq!(task.reload, Some(Templated::Template("yes".to_string())));
     assert!(task.restart.is_none());
     assert!(task.enabled.is_none());
     assert!(task.started.is_none());
 }
+
+#[test]
+fn test_systemd_service_task_deserialization_timer_and_dropins() {
+    let yaml = r#"
+service: backup
+user: alice
+content: |
+  [Service]
+  Type=oneshot
+  ExecStart=/usr/local/bin/backup
+timer: |
+  [Timer]
+  OnCalendar=daily
+
+  [Install]
+  WantedBy=timers.target
+dropins:
+  limits: |
+    [Service]
+    MemoryMax=1G
+enabled: true
+started: true
+"#;
+
+    let task: SystemdServiceTask = serde_yaml::from_str(yaml).unwrap();
+    assert_eq!(task.user, Some("alice".to_string()));
+    assert!(task.content.as_ref().unwrap().contains("Type=oneshot"));
+    assert!(task.timer.as_ref().unwrap().contains("OnCalendar=daily"));
+    assert!(task.dropins.as_ref().unwrap()["limits"].contains("MemoryMax=1G"));
+    assert!(task.masked.is_none());
+}
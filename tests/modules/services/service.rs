@@ -0,0 +1,48 @@
+use jetpack::modules::services::service::*;
+use jetpack::tasks::*;
+
+#[test]
+fn test_service_task_basic() {
+    let task = ServiceTask {
+        name: Some("Run sshd".to_string()),
+        service: "sshd".to_string(),
+        enabled: Some(Templated::Value(true)),
+        started: Some(Templated::Value(true)),
+        restart: None,
+        with: None,
+        and: None,
+    };
+
+    assert_eq!(task.get_module(), "service");
+    assert_eq!(task.get_name(), Some("Run sshd".to_string()));
+    assert!(task.get_with().is_none());
+}
+
+#[test]
+fn test_service_task_deserialization() {
+    let yaml = r#"
+service: chronyd
+enabled: "{{ want_ntp }}"
+restart: true
+"#;
+
+    let task: ServiceTask = serde_yaml::from_str(yaml).unwrap();
+    assert_eq!(task.service, "chronyd");
+    assert_eq!(
+        task.enabled,
+        Some(Templated::Template("{{ want_ntp }}".to_string()))
+    );
+    assert_eq!(task.restart, Some(Templated::Value(true)));
+    assert!(task.started.is_none());
+}
+
+#[test]
+fn test_service_task_rejects_sd_service_only_fields() {
+    let yaml = r#"
+service: nginx
+masked: true
+"#;
+
+    let task: Result<ServiceTask, _> = serde_yaml::from_str(yaml);
+    assert!(task.is_err());
+}
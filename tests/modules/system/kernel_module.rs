@@ -0,0 +1,17 @@
+use jetpack::modules::system::kernel_module::*;
+use jetpack::tasks::*;
+
+#[test]
+fn test_kernel_module_task_basic() {
+    let task = KernelModuleTask {
+        name: Some("Load loop".to_string()),
+        module: "loop".to_string(),
+        params: Some("max_loop=64".to_string()),
+        ..Default::default()
+    };
+
+    assert_eq!(task.get_module(), "kernel_module");
+    assert_eq!(task.get_name(), Some("Load loop".to_string()));
+    assert!(task.persist.is_none());
+    assert!(task.remove.is_none());
+}
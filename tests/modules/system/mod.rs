@@ -0,0 +1,3 @@
+mod kernel_module;
+mod mount;
+mod sysctl;
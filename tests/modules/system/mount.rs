@@ -0,0 +1,25 @@
+use jetpack::modules::system::mount::*;
+use jetpack::tasks::*;
+
+#[test]
+fn test_mount_task_nfs() {
+    let yaml = r#"
+path: /srv/media
+src: nas.lan:/export/media
+fstype: nfs
+opts: rw,_netdev,noatime
+"#;
+
+    let task: MountTask = serde_yaml::from_str(yaml).unwrap();
+    assert_eq!(task.get_module(), "mount");
+    assert_eq!(task.src, Some("nas.lan:/export/media".to_string()));
+    assert_eq!(task.fstype, Some("nfs".to_string()));
+    assert!(task.state.is_none());
+    assert!(task.passno.is_none());
+}
+
+#[test]
+fn test_mount_task_rejects_unknown_fields() {
+    let yaml = "path: /mnt/data\nsrc: /dev/sdb1\nmount_point: /mnt/data\n";
+    assert!(serde_yaml::from_str::<MountTask>(yaml).is_err());
+}
@@ -0,0 +1,24 @@
+use jetpack::modules::system::sysctl::*;
+use jetpack::tasks::*;
+
+#[test]
+fn test_sysctl_task_deserialization() {
+    let yaml = r#"
+key: net.ipv4.tcp_rmem
+value: 4096 87380 6291456
+file: 60-network.conf
+"#;
+
+    let task: SysctlTask = serde_yaml::from_str(yaml).unwrap();
+    assert_eq!(task.get_module(), "sysctl");
+    assert_eq!(task.key, "net.ipv4.tcp_rmem");
+    assert_eq!(task.value, Some("4096 87380 6291456".to_string()));
+    assert_eq!(task.file, Some("60-network.conf".to_string()));
+    assert!(task.apply.is_none());
+}
+
+#[test]
+fn test_sysctl_numeric_value() {
+    let task: SysctlTask = serde_yaml::from_str("key: vm.swappiness\nvalue: 10\n").unwrap();
+    assert_eq!(task.value, Some("10".to_string()));
+}
@@ -1,5 +1,4 @@
 use jetpack::playbooks::language::*;
-use serde_yaml;
 
 #[test]
 fn test_play_debug() {
@@ -17,6 +16,7 @@ fn test_play_debug() {
         tasks: None,
         handlers: None,
         batch_size: None,
+        template_engine: None,
         instantiate: None,
     };
 
@@ -52,6 +52,7 @@ fn test_play_with_options() {
         tasks: None,
         handlers: None,
         batch_size: Some(10),
+        template_engine: None,
         instantiate: None,
     };
 
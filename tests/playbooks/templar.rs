@@ -1,11 +1,9 @@
 use jetpack::playbooks::templar::*;
-use serde_yaml;
 
 #[test]
 fn test_templar_new() {
     let _templar = Templar::new();
     // Just ensure we can create a new instance
-    assert!(true);
 }
 
 #[test]
@@ -68,7 +66,7 @@ fn test_test_condition_true() {
     let result = templar.test_condition(&expr, data, TemplateMode::Strict);
 
     assert!(result.is_ok());
-    assert_eq!(result.unwrap(), true);
+    assert!(result.unwrap());
 }
 
 #[test]
@@ -84,7 +82,7 @@ fn test_test_condition_false() {
     let result = templar.test_condition(&expr, data, TemplateMode::Strict);
 
     assert!(result.is_ok());
-    assert_eq!(result.unwrap(), false);
+    assert!(!result.unwrap());
 }
 
 #[test]
@@ -96,7 +94,7 @@ fn test_test_condition_off_mode() {
     let result = templar.test_condition(&expr, data, TemplateMode::Off);
 
     assert!(result.is_ok());
-    assert_eq!(result.unwrap(), true); // Always returns true in Off mode
+    assert!(result.unwrap()); // Always returns true in Off mode
 }
 
 #[test]
@@ -129,7 +127,7 @@ fn test_test_condition_with_complex_expression() {
     let result = templar.test_condition(&expr, data, TemplateMode::Strict);
 
     assert!(result.is_ok());
-    assert_eq!(result.unwrap(), true);
+    assert!(result.unwrap());
 }
 
 #[test]
@@ -188,6 +186,6 @@ fn test_template_mode_copy() {
 fn test_template_mode_clone() {
     // Test Clone trait implementation
     let mode1 = TemplateMode::Strict;
-    let mode2 = mode1.clone();
+    let mode2 = mode1;
     assert_eq!(mode1, mode2);
 }
@@ -22,7 +22,7 @@ fn test_run_state_creation() {
         limit_hosts: Vec::new(),
         limit_groups: Vec::new(),
         batch_size: None,
-        context: context,
+        context,
         visitor: Arc::new(RwLock::new(PlaybookVisitor::new(CheckMode::No))),
         connection_factory: Arc::new(RwLock::new(NoFactory::new())),
         tags: None,
@@ -60,7 +60,7 @@ fn test_run_state_with_limits() {
         limit_hosts: vec!["host1".to_string(), "host2".to_string()],
         limit_groups: vec!["webservers".to_string()],
         batch_size: Some(5),
-        context: context,
+        context,
         visitor: Arc::new(RwLock::new(PlaybookVisitor::new(CheckMode::No))),
         connection_factory: Arc::new(RwLock::new(NoFactory::new())),
         tags: Some(vec!["deploy".to_string(), "configure".to_string()]),
@@ -113,7 +113,7 @@ fn test_run_state_paths() {
         limit_hosts: Vec::new(),
         limit_groups: Vec::new(),
         batch_size: None,
-        context: context,
+        context,
         visitor: Arc::new(RwLock::new(PlaybookVisitor::new(CheckMode::Yes))),
         connection_factory: Arc::new(RwLock::new(NoFactory::new())),
         tags: None,
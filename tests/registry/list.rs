@@ -18,10 +18,10 @@ gid: "1001"
 "#;
     let group_task: Result<Task, _> = serde_yaml::from_str(group_yaml_with_module);
     assert!(group_task.is_ok());
-    match group_task.unwrap() {
-        Task::Group(_) => assert!(true),
-        _ => panic!("Expected Group task"),
-    }
+    assert!(
+        matches!(group_task.unwrap(), Task::Group(_)),
+        "Expected Group task"
+    );
 }
 
 #[test]
@@ -32,10 +32,10 @@ cmd: echo "Hello World"
 "#;
     let shell_task: Result<Task, _> = serde_yaml::from_str(shell_yaml);
     assert!(shell_task.is_ok());
-    match shell_task.unwrap() {
-        Task::Shell(_) => assert!(true),
-        _ => panic!("Expected Shell task"),
-    }
+    assert!(
+        matches!(shell_task.unwrap(), Task::Shell(_)),
+        "Expected Shell task"
+    );
 }
 
 #[test]
@@ -47,10 +47,10 @@ msg: Test message
 "#;
     let echo_task: Result<Task, _> = serde_yaml::from_str(echo_yaml);
     assert!(echo_task.is_ok());
-    match echo_task.unwrap() {
-        Task::Echo(_) => assert!(true),
-        _ => panic!("Expected Echo task"),
-    }
+    assert!(
+        matches!(echo_task.unwrap(), Task::Echo(_)),
+        "Expected Echo task"
+    );
 
     // Test Debug task
     let debug_yaml = r#"
@@ -60,10 +60,10 @@ vars:
 "#;
     let debug_task: Result<Task, _> = serde_yaml::from_str(debug_yaml);
     assert!(debug_task.is_ok());
-    match debug_task.unwrap() {
-        Task::Debug(_) => assert!(true),
-        _ => panic!("Expected Debug task"),
-    }
+    assert!(
+        matches!(debug_task.unwrap(), Task::Debug(_)),
+        "Expected Debug task"
+    );
 }
 
 #[test]
@@ -75,10 +75,10 @@ path: /tmp/test.txt
 "#;
     let file_task: Result<Task, _> = serde_yaml::from_str(file_yaml);
     assert!(file_task.is_ok());
-    match file_task.unwrap() {
-        Task::File(_) => assert!(true),
-        _ => panic!("Expected File task"),
-    }
+    assert!(
+        matches!(file_task.unwrap(), Task::File(_)),
+        "Expected File task"
+    );
 
     // Test Copy task
     let copy_yaml = r#"
@@ -88,10 +88,10 @@ dest: /tmp/dest.txt
 "#;
     let copy_task: Result<Task, _> = serde_yaml::from_str(copy_yaml);
     assert!(copy_task.is_ok());
-    match copy_task.unwrap() {
-        Task::Copy(_) => assert!(true),
-        _ => panic!("Expected Copy task"),
-    }
+    assert!(
+        matches!(copy_task.unwrap(), Task::Copy(_)),
+        "Expected Copy task"
+    );
 }
 
 #[test]
@@ -103,10 +103,10 @@ package: nginx
 "#;
     let apt_task: Result<Task, _> = serde_yaml::from_str(apt_yaml);
     assert!(apt_task.is_ok());
-    match apt_task.unwrap() {
-        Task::Apt(_) => assert!(true),
-        _ => panic!("Expected Apt task"),
-    }
+    assert!(
+        matches!(apt_task.unwrap(), Task::Apt(_)),
+        "Expected Apt task"
+    );
 
     // Test Homebrew task
     let brew_yaml = r#"
@@ -115,10 +115,10 @@ package: wget
 "#;
     let brew_task: Result<Task, _> = serde_yaml::from_str(brew_yaml);
     assert!(brew_task.is_ok());
-    match brew_task.unwrap() {
-        Task::Homebrew(_) => assert!(true),
-        _ => panic!("Expected Homebrew task"),
-    }
+    assert!(
+        matches!(brew_task.unwrap(), Task::Homebrew(_)),
+        "Expected Homebrew task"
+    );
 }
 
 #[test]
@@ -130,10 +130,10 @@ enabled: "yes"
 "#;
     let service_task: Result<Task, _> = serde_yaml::from_str(service_yaml);
     assert!(service_task.is_ok());
-    match service_task.unwrap() {
-        Task::Sd_Service(_) => assert!(true),
-        _ => panic!("Expected SystemdService task"),
-    }
+    assert!(
+        matches!(service_task.unwrap(), Task::Sd_Service(_)),
+        "Expected SystemdService task"
+    );
 }
 
 #[test]
@@ -189,16 +189,7 @@ fn test_task_list_deserialization() {
     assert_eq!(tasks.len(), 3);
 
     // Verify task types
-    match &tasks[0] {
-        Task::Echo(_) => assert!(true),
-        _ => panic!("Expected Echo task"),
-    }
-    match &tasks[1] {
-        Task::File(_) => assert!(true),
-        _ => panic!("Expected File task"),
-    }
-    match &tasks[2] {
-        Task::Shell(_) => assert!(true),
-        _ => panic!("Expected Shell task"),
-    }
+    assert!(matches!(&tasks[0], Task::Echo(_)), "Expected Echo task");
+    assert!(matches!(&tasks[1], Task::File(_)), "Expected File task");
+    assert!(matches!(&tasks[2], Task::Shell(_)), "Expected Shell task");
 }
@@ -41,3 +41,28 @@ fn test_sha512_deterministic() {
     let result2 = sha512(&input);
     assert_eq!(result1, result2);
 }
+
+#[test]
+fn test_parse_sum_line_unescapes_names() {
+    assert_eq!(
+        parse_sum_line("abc  /srv/site/index.html"),
+        Some((String::from("abc"), String::from("/srv/site/index.html")))
+    );
+    assert_eq!(
+        parse_sum_line("abc */srv/site/a b"),
+        Some((String::from("abc"), String::from("/srv/site/a b")))
+    );
+    assert_eq!(
+        parse_sum_line("\\abc  /srv/site/back\\\\slash\\nline"),
+        Some((
+            String::from("abc"),
+            String::from("/srv/site/back\\slash\nline")
+        ))
+    );
+    // a name that is not escaped keeps its backslashes as they are
+    assert_eq!(
+        parse_sum_line("abc  /srv/site/a\\b"),
+        Some((String::from("abc"), String::from("/srv/site/a\\b")))
+    );
+    assert_eq!(parse_sum_line("garbage"), None);
+}
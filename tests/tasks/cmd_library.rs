@@ -79,7 +79,7 @@ fn test_screen_mode_valid_octal() {
     let valid_modes = vec!["755", "644", "777", "000", "400", "0o755", "0o644", "12345"];
 
     for mode in valid_modes {
-        let result = screen_mode(&mode.to_string());
+        let result = screen_mode(mode);
         assert!(result.is_ok());
         assert_eq!(result.unwrap(), mode);
     }
@@ -92,7 +92,7 @@ fn test_screen_mode_invalid() {
     ];
 
     for mode in invalid_modes {
-        let result = screen_mode(&mode.to_string());
+        let result = screen_mode(mode);
         assert!(result.is_err(), "Mode '{}' should be invalid", mode);
         assert!(result.unwrap_err().contains("not an octal string"));
     }
@@ -264,7 +264,7 @@ fn test_command_injection_prevention() {
     ];
 
     for evil_path in evil_paths {
-        let result = screen_path(&evil_path.to_string());
+        let result = screen_path(evil_path);
         assert!(result.is_err(), "Should reject path: {}", evil_path);
     }
 }
@@ -281,3 +281,21 @@ fn test_trimmed_inputs() {
     assert!(result.is_ok());
     assert_eq!(result.unwrap(), "simple-text");
 }
+
+#[test]
+fn test_get_find_command_macos_skips_stat_without_matches() {
+    let result = get_find_command(HostOSType::MacOS, "/test/dir", Recurse::No);
+    assert!(result.is_ok());
+    let cmd = result.unwrap();
+    assert!(cmd.starts_with("date +%s && find '/test/dir' -mindepth 1 -maxdepth 1 -exec stat -f "));
+    assert!(cmd.ends_with(" {} +"));
+    assert!(!cmd.contains("xargs"));
+}
+
+#[test]
+fn test_shell_quote_handles_spaces_and_quotes() {
+    assert_eq!(
+        shell_quote("/tmp/Application Support/it's-here"),
+        "'/tmp/Application Support/it'\"'\"'s-here'"
+    );
+}
@@ -11,7 +11,7 @@ fn test_field_equality() {
 #[test]
 fn test_field_clone() {
     let field1 = Field::Mode;
-    let field2 = field1.clone();
+    let field2 = field1;
     assert_eq!(field1, field2);
 }
 
@@ -25,15 +25,19 @@ fn test_field_debug() {
 #[test]
 fn test_all_file_attributes() {
     let attrs = Field::all_file_attributes();
-    assert_eq!(attrs.len(), 3);
+    assert_eq!(attrs.len(), 7);
     assert!(attrs.contains(&Field::Owner));
     assert!(attrs.contains(&Field::Group));
     assert!(attrs.contains(&Field::Mode));
+    assert!(attrs.contains(&Field::Acl));
+    assert!(attrs.contains(&Field::Selinux));
 
-    // Verify order
+    // Verify order: ownership and mode first, chattr flags last
     assert_eq!(attrs[0], Field::Owner);
     assert_eq!(attrs[1], Field::Group);
     assert_eq!(attrs[2], Field::Mode);
+    assert_eq!(attrs[5], Field::Immutable);
+    assert_eq!(attrs[6], Field::AppendOnly);
 }
 
 #[test]
@@ -52,24 +56,44 @@ fn test_field_hash() {
 fn test_all_field_variants() {
     // Test that we can create each variant
     let fields = vec![
+        Field::Acl,
+        Field::AppendOnly,
         Field::Branch,
         Field::Content,
         Field::Disable,
         Field::Enable,
+        Field::Expires,
         Field::Gecos,
         Field::Gid,
         Field::Group,
         Field::Groups,
+        Field::Hold,
+        Field::Immutable,
+        Field::InactiveDays,
+        Field::Load,
+        Field::Locked,
+        Field::Mask,
+        Field::MaxDays,
+        Field::MinDays,
         Field::Mode,
+        Field::Mount,
         Field::Owner,
+        Field::Password,
         Field::Reload,
+        Field::Remount,
         Field::Restart,
+        Field::Selinux,
         Field::Shell,
         Field::Start,
         Field::Stop,
+        Field::Target,
         Field::Uid,
+        Field::Unmask,
+        Field::Unmount,
         Field::Users,
+        Field::Value,
         Field::Version,
+        Field::WarnDays,
     ];
 
     // Ensure all are unique
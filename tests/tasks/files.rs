@@ -26,41 +26,41 @@ fn test_recurse_copy() {
 #[test]
 fn test_recurse_clone() {
     let r1 = Recurse::Yes;
-    let r2 = r1.clone();
+    let r2 = r1;
     assert_eq!(r1, r2);
 }
 
 #[test]
 fn test_file_attributes_input_is_octal_string() {
     // Valid — Rust-style prefix (0o)
-    assert!(FileAttributesInput::is_octal_string(&"0o755".to_string()));
-    assert!(FileAttributesInput::is_octal_string(&"0o644".to_string()));
-    assert!(FileAttributesInput::is_octal_string(&"0o000".to_string()));
-    assert!(FileAttributesInput::is_octal_string(&"0o777".to_string()));
+    assert!(FileAttributesInput::is_octal_string("0o755"));
+    assert!(FileAttributesInput::is_octal_string("0o644"));
+    assert!(FileAttributesInput::is_octal_string("0o000"));
+    assert!(FileAttributesInput::is_octal_string("0o777"));
 
     // Valid — Unix/C-style prefix (0)
-    assert!(FileAttributesInput::is_octal_string(&"0755".to_string()));
-    assert!(FileAttributesInput::is_octal_string(&"0644".to_string()));
-    assert!(FileAttributesInput::is_octal_string(&"0000".to_string()));
-    assert!(FileAttributesInput::is_octal_string(&"0777".to_string()));
+    assert!(FileAttributesInput::is_octal_string("0755"));
+    assert!(FileAttributesInput::is_octal_string("0644"));
+    assert!(FileAttributesInput::is_octal_string("0000"));
+    assert!(FileAttributesInput::is_octal_string("0777"));
 
     // Valid — bare digits (accepted by is_octal_string for internal use)
-    assert!(FileAttributesInput::is_octal_string(&"755".to_string()));
-    assert!(FileAttributesInput::is_octal_string(&"644".to_string()));
-    assert!(FileAttributesInput::is_octal_string(&"000".to_string()));
-    assert!(FileAttributesInput::is_octal_string(&"777".to_string()));
+    assert!(FileAttributesInput::is_octal_string("755"));
+    assert!(FileAttributesInput::is_octal_string("644"));
+    assert!(FileAttributesInput::is_octal_string("000"));
+    assert!(FileAttributesInput::is_octal_string("777"));
 
     // Invalid — digits out of octal range
-    assert!(!FileAttributesInput::is_octal_string(&"999".to_string()));
-    assert!(!FileAttributesInput::is_octal_string(&"888".to_string()));
-    assert!(!FileAttributesInput::is_octal_string(&"0999".to_string()));
-    assert!(!FileAttributesInput::is_octal_string(&"0888".to_string()));
-    assert!(!FileAttributesInput::is_octal_string(&"0o999".to_string()));
-    assert!(!FileAttributesInput::is_octal_string(&"0o888".to_string()));
+    assert!(!FileAttributesInput::is_octal_string("999"));
+    assert!(!FileAttributesInput::is_octal_string("888"));
+    assert!(!FileAttributesInput::is_octal_string("0999"));
+    assert!(!FileAttributesInput::is_octal_string("0888"));
+    assert!(!FileAttributesInput::is_octal_string("0o999"));
+    assert!(!FileAttributesInput::is_octal_string("0o888"));
 
     // Invalid — non-numeric
-    assert!(!FileAttributesInput::is_octal_string(&"abc".to_string()));
-    assert!(!FileAttributesInput::is_octal_string(&"12x".to_string()));
+    assert!(!FileAttributesInput::is_octal_string("abc"));
+    assert!(!FileAttributesInput::is_octal_string("12x"));
 }
 
 #[test]
@@ -69,6 +69,7 @@ fn test_file_attributes_input_debug() {
         owner: Some("user".to_string()),
         group: Some("group".to_string()),
         mode: Some("0o755".to_string()),
+        ..Default::default()
     };
 
     let debug_str = format!("{:?}", attr);
@@ -83,6 +84,7 @@ fn test_file_attributes_evaluated_debug() {
         owner: Some("user".to_string()),
         group: Some("group".to_string()),
         mode: Some("0o755".to_string()),
+        ..Default::default()
     };
 
     let debug_str = format!("{:?}", attr);
@@ -97,6 +99,7 @@ fn test_file_attributes_with_none_values() {
         owner: None,
         group: None,
         mode: None,
+        ..Default::default()
     };
 
     assert!(attr.owner.is_none());
@@ -107,16 +110,14 @@ fn test_file_attributes_with_none_values() {
 #[test]
 fn test_is_octal_string_edge_cases() {
     // Empty string
-    assert!(!FileAttributesInput::is_octal_string(&"".to_string()));
+    assert!(!FileAttributesInput::is_octal_string(""));
 
     // Just the prefix
-    assert!(!FileAttributesInput::is_octal_string(&"0o".to_string()));
+    assert!(!FileAttributesInput::is_octal_string("0o"));
 
     // Very long valid octal
-    assert!(FileAttributesInput::is_octal_string(
-        &"12345670".to_string()
-    ));
+    assert!(FileAttributesInput::is_octal_string("12345670"));
 
     // Negative numbers - from_str_radix accepts them
-    assert!(FileAttributesInput::is_octal_string(&"-755".to_string()));
+    assert!(FileAttributesInput::is_octal_string("-755"));
 }
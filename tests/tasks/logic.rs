@@ -1,3 +1,4 @@
+use jetpack::tasks::Templated;
 use jetpack::tasks::logic::*;
 
 #[test]
@@ -34,6 +35,7 @@ fn test_pre_logic_input_struct() {
         tags: Some(vec!["tag1".to_string()]),
         delegate_to: Some("host1".to_string()),
         skip_if_exists: None,
+        no_log: None,
     };
 
     assert_eq!(pre_logic.condition, Some("test_condition".to_string()));
@@ -48,15 +50,18 @@ fn test_pre_logic_input_struct() {
 fn test_post_logic_input_struct() {
     let post_logic = PostLogicInput {
         notify: Some("handler".to_string()),
-        ignore_errors: Some("true".to_string()),
-        retry: Some("3".to_string()),
-        delay: Some("5".to_string()),
+        ignore_errors: Some(Templated::Template("true".to_string())),
+        retry: Some(Templated::Template("3".to_string())),
+        delay: Some(Templated::Template("5".to_string())),
     };
 
     assert_eq!(post_logic.notify, Some("handler".to_string()));
-    assert_eq!(post_logic.ignore_errors, Some("true".to_string()));
-    assert_eq!(post_logic.retry, Some("3".to_string()));
-    assert_eq!(post_logic.delay, Some("5".to_string()));
+    assert_eq!(
+        post_logic.ignore_errors,
+        Some(Templated::Template("true".to_string()))
+    );
+    assert_eq!(post_logic.retry, Some(Templated::Template("3".to_string())));
+    assert_eq!(post_logic.delay, Some(Templated::Template("5".to_string())));
 }
 
 #[test]
@@ -90,7 +95,7 @@ fn test_post_logic_evaluated_struct() {
     };
 
     assert_eq!(evaluated.notify, Some("handler_name".to_string()));
-    assert_eq!(evaluated.ignore_errors, true);
+    assert!(evaluated.ignore_errors);
     assert_eq!(evaluated.retry, 3);
     assert_eq!(evaluated.delay, 5);
 }
@@ -105,7 +110,7 @@ fn test_post_logic_evaluated_defaults() {
     };
 
     assert_eq!(evaluated.notify, None);
-    assert_eq!(evaluated.ignore_errors, false);
+    assert!(!evaluated.ignore_errors);
     assert_eq!(evaluated.retry, 0);
     assert_eq!(evaluated.delay, 1);
 }
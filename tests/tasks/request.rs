@@ -161,7 +161,7 @@ fn test_is_sudoing_no_sudo_details() {
 #[test]
 fn test_all_task_request_types() {
     // Ensure all variants can be created
-    let types = vec![
+    let types = [
         TaskRequestType::Validate,
         TaskRequestType::Query,
         TaskRequestType::Create,